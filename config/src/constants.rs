@@ -7,6 +7,17 @@ pub const UNTIL_EPOCH: usize = 30;
 pub const IAS_URL: &str = "https://api.trustedservices.intel.com/sgx/dev/attestation/v3/report";
 pub const TEST_SUB_KEY: &str = "77e2533de0624df28dc3be3a5b9e50d9";
 pub const MRA_TLS_SERVER_ADDRESS: &str = "localhost:12345";
+// enclave threads
+/// Must be equal to TCSNum in Enclave.config.xml
+pub const ENCLAVE_TCS_NUM: usize = 16;
+/// Threads kept for concurrent ecalls and the attestation refresh worker,
+/// and the rest can be used by mra-tls servers.
+pub const ENCLAVE_RESERVED_TCS_NUM: usize = 4;
+// key-vault
+pub const KEY_VAULT_LISTEN_ADDRESS: &str = "0.0.0.0:12345";
+pub const KEY_VAULT_STORE_METHOD: &str = "store_path_secret";
+pub const KEY_VAULT_RECOVER_METHOD: &str = "recover_path_secret";
+pub const KEY_VAULT_LIST_METHOD: &str = "list_path_secrets";
 
 // commands
 pub const ENCRYPT_COMMAND_CMD: u32 = 1;
@@ -20,3 +31,8 @@ pub const GET_ENCRYPTING_KEY_CMD: u32 = 8;
 pub const CALL_REGISTER_REPORT_CMD: u32 = 9;
 pub const START_SERVER_CMD: u32 = 10;
 pub const STOP_SERVER_CMD: u32 = 11;
+pub const GET_ATTESTATION_STATUS_CMD: u32 = 12;
+pub const CALL_ROTATE_IDENTITY_KEY_CMD: u32 = 13;
+pub const GET_STATE_COMMITMENT_CMD: u32 = 14;
+pub const CALL_COMMIT_IDENTITY_KEY_CMD: u32 = 15;
+pub const CALL_BIND_IDENTITY_KEY_CMD: u32 = 16;
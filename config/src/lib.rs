@@ -30,8 +30,25 @@ use crate::localstd::vec::Vec;
 #[cfg(feature = "sgx")]
 lazy_static! {
     pub static ref IAS_ROOT_CERT: Vec<u8> = {
-        let ias_root_cert = include_bytes!("../ias_root_cert.pem");
+        let ias_root_cert = read_ias_root_cert();
         let pem = pem::parse(ias_root_cert).expect("Cannot parse PEM File");
         pem.contents
     };
 }
+
+/// Debug builds replace the root certificate with the one at `IAS_ROOT_CERT_PATH` if it's set,
+/// so that reports signed by the mock IAS in `tests/mock-ias` are accepted.
+#[cfg(all(feature = "sgx", debug_assertions))]
+fn read_ias_root_cert() -> Vec<u8> {
+    match localstd::env::var("IAS_ROOT_CERT_PATH") {
+        Ok(path) if !path.is_empty() => {
+            localstd::untrusted::fs::read(path).expect("Cannot read IAS_ROOT_CERT_PATH")
+        }
+        _ => include_bytes!("../ias_root_cert.pem").to_vec(),
+    }
+}
+
+#[cfg(all(feature = "sgx", not(debug_assertions)))]
+fn read_ias_root_cert() -> Vec<u8> {
+    include_bytes!("../ias_root_cert.pem").to_vec()
+}
@@ -14,33 +14,84 @@ contract Anonify is ReportHandle {
     uint32 private _rosterIdxCounter;
     // Mapping of a sender and roster index
     mapping(address => uint32) private _senderToRosterIdx;
+    // Whether the sender has joined the group, since the roster index of the owner is zero as well
+    mapping(address => bool) private _isMember;
 
-    event StoreCiphertext(bytes ciphertext);
-    event StoreHandshake(bytes handshake);
+    // A new mrenclave proposed by a member, which takes effect once approved by the members.
+    struct MrenclaveProposal {
+        uint nonce;
+        bytes32 mrEnclave;
+        address verifyingKey;
+        bytes encryptingKey;
+        bytes handshake;
+        // The block number of the proposal
+        uint proposedAt;
+        // The key epoch at the proposal, which must not be changed until it is executed
+        uint32 keyEpoch;
+        uint32 approvals;
+        bool executed;
+    }
+    // The proposals indexed by the new mrenclave versions
+    mapping(uint32 => MrenclaveProposal) private _proposals;
+    // Whether a member has approved the proposal of the nonce
+    mapping(uint => mapping(address => bool)) private _approved;
+    uint private _proposalNonce;
+
+    // The number of blocks from a proposal until it can be executed
+    uint public mrenclaveTimelock;
+    // More than this percentage of the members must approve a proposal
+    uint32 public approvalPercent;
+    // A proposal must be approved by another member than the proposer even in a group of one member.
+    uint32 constant public MIN_APPROVALS = 2;
+
+    // The offsets of the fields in the SCALE-encoded `Ciphertext` and `ExportHandshake`
+    uint constant internal CIPHERTEXT_EPOCH_OFFSET = 4;
+    uint constant internal CIPHERTEXT_ROSTER_IDX_OFFSET = 8;
+    uint constant internal HANDSHAKE_EPOCH_OFFSET = 0;
+    uint constant internal HANDSHAKE_ROSTER_IDX_OFFSET = 4;
+    // The offsets of the fields in the SCALE-encoded `StateCommitment`
+    uint constant internal COMMITMENT_EPOCH_OFFSET = 0;
+    uint constant internal COMMITMENT_ROSTER_IDX_OFFSET = 4;
+
+    // The roster index and the epoch are read from the payloads signed by the enclaves,
+    // so that watchers can filter the events by them.
+    event StoreCiphertext(uint32 indexed rosterIdx, uint32 indexed epoch, bytes ciphertext);
+    // The ciphertext itself is kept in an off-chain store addressed by the hash.
+    event StoreCiphertextHash(bytes32 ciphertextHash);
+    // The epoch of a handshake is the prior one.
+    event StoreHandshake(uint32 indexed rosterIdx, uint32 indexed epoch, bytes handshake);
+    event ProposeMrenclave(uint32 indexed newVersion, bytes32 mrEnclave, address proposer);
+    event ApproveMrenclave(uint32 indexed newVersion, address approver);
     event UpdateMrenclaveVer(uint32 newVersion);
+    event RevokeKeys(address indexed verifyingKey, bytes encryptingKey);
+    // The epoch of a commitment is the one of the last ciphertext processed by the member.
+    event StateCommitment(uint32 indexed rosterIdx, uint32 indexed epoch, bytes commitment);
 
     constructor(
         bytes memory _report,
         bytes memory _reportSig,
         bytes memory _handshake,
-        uint32 mrenclaveVer
+        uint32 mrenclaveVer,
+        uint _mrenclaveTimelock,
+        uint32 _approvalPercent
     ) public ReportHandle(_report, _reportSig) {
-        // The offset of roster index is 4.
-        uint32 rosterIdx = BytesUtils.toUint32(_handshake, 4);
+        uint32 rosterIdx = BytesUtils.toUint32LE(_handshake, HANDSHAKE_ROSTER_IDX_OFFSET);
         require(rosterIdx == 0, "First roster_idx must be zero");
+        require(
+            _approvalPercent >= 50 && _approvalPercent < 100,
+            "The approval percentage must be from 50 to 99"
+        );
 
         _owner = msg.sender;
         _mrenclaveVer = mrenclaveVer;
         _senderToRosterIdx[msg.sender] = rosterIdx;
+        _isMember[msg.sender] = true;
         _rosterIdxCounter = rosterIdx;
+        mrenclaveTimelock = _mrenclaveTimelock;
+        approvalPercent = _approvalPercent;
         handshake_wo_sig(_handshake);
     }
 
-    modifier onlyOwner() {
-        require(_owner == msg.sender, "caller is not the owner");
-        _;
-    }
-
     // a new TEE node joins the group.
     function joinGroup(
         bytes memory _report,
@@ -54,18 +105,17 @@ contract Anonify is ReportHandle {
             _rosterIdx == _rosterIdxCounter + 1,
             "Joining the group must be ordered accordingly by roster index"
         );
-        require(
-            _senderToRosterIdx[msg.sender] == 0,
-            "The msg.sender can join only once"
-        );
+        require(!_isMember[msg.sender], "The msg.sender can join only once");
 
         handleReport(_report, _reportSig);
         _senderToRosterIdx[msg.sender] = _rosterIdx;
+        _isMember[msg.sender] = true;
         _rosterIdxCounter = _rosterIdx;
         handshake_wo_sig(_handshake);
     }
 
     // a recovered TEE node registers the report
+    // Only a member can register, and its roster index is never changed.
     function registerReport(
         bytes memory _report,
         bytes memory _reportSig,
@@ -73,27 +123,146 @@ contract Anonify is ReportHandle {
         uint32 _rosterIdx
     ) public {
         require(_mrenclaveVer == _version, "Must be same version");
+        require(_isMember[msg.sender], "The msg.sender has not joined the group");
+        require(
+            _senderToRosterIdx[msg.sender] == _rosterIdx,
+            "The roster index must be same as the registered one"
+        );
 
         handleReport(_report, _reportSig);
-        _senderToRosterIdx[msg.sender] = _rosterIdx;
     }
 
-    function updateMrenclave(
+    // Propose the mrenclave of a member's new enclave, which also starts the new group.
+    // The proposal counts as the proposer's approval.
+    function proposeMrenclave(
         bytes memory _report,
         bytes memory _reportSig,
         bytes memory _handshake,
         uint32 _newVersion,
         uint32 _rosterIdx
-    ) public onlyOwner {
+    ) public {
+        require(_isMember[msg.sender], "The msg.sender has not joined the group");
+        require(
+            _senderToRosterIdx[msg.sender] == _rosterIdx,
+            "The roster index must be same as the registered one"
+        );
+        require(
+            BytesUtils.toUint32LE(_handshake, HANDSHAKE_ROSTER_IDX_OFFSET) == _rosterIdx,
+            "The handshake must be of the proposer"
+        );
         require(_mrenclaveVer != _newVersion, "Must be new version");
-        require(_rosterIdx == 0, "Only owner can update mrenclave");
+        MrenclaveProposal storage proposal = _proposals[_newVersion];
+        require(
+            proposal.proposedAt == 0 || proposal.keyEpoch != keyEpoch,
+            "The version has already been proposed"
+        );
 
-        updateMrenclaveInner(_report, _reportSig);
-        handshake_wo_sig(_handshake);
+        (bytes32 inpMrEnclave, address inpVerifyingKey, bytes memory inpEncryptingKey) = extractFromReport(_report, _reportSig);
+        require(mrEnclave != inpMrEnclave, "mrenclave must be different one");
+
+        _proposalNonce++;
+        _proposals[_newVersion] = MrenclaveProposal(
+            _proposalNonce,
+            inpMrEnclave,
+            inpVerifyingKey,
+            inpEncryptingKey,
+            _handshake,
+            block.number,
+            keyEpoch,
+            0,
+            false
+        );
+        emit ProposeMrenclave(_newVersion, inpMrEnclave, msg.sender);
+        approve(_newVersion);
+    }
+
+    // A member approves the proposal of the version.
+    // It is executed by the approval reaching the threshold if the timelock has passed.
+    function approveMrenclave(uint32 _newVersion) public {
+        require(_isMember[msg.sender], "The msg.sender has not joined the group");
+
+        approve(_newVersion);
+        if (isExecutable(_newVersion)) {
+            execute(_newVersion);
+        }
+    }
+
+    // Execute the proposal approved by the threshold of the members after the timelock.
+    function executeMrenclave(uint32 _newVersion) public {
+        require(_isMember[msg.sender], "The msg.sender has not joined the group");
+        require(isExecutable(_newVersion), "The proposal is not executable");
+
+        execute(_newVersion);
+    }
+
+    // More than `approvalPercent` of the members, and at least `MIN_APPROVALS`, must approve a proposal.
+    function approvalThreshold() public view returns (uint32) {
+        // The roster indices of the members are from zero to the counter.
+        uint32 threshold = (_rosterIdxCounter + 1) * approvalPercent / 100 + 1;
+        return threshold < MIN_APPROVALS ? MIN_APPROVALS : threshold;
+    }
+
+    function isExecutable(uint32 _newVersion) public view returns (bool) {
+        MrenclaveProposal storage proposal = _proposals[_newVersion];
+        return isPending(proposal)
+            && proposal.approvals >= approvalThreshold()
+            && block.number >= proposal.proposedAt + mrenclaveTimelock;
+    }
+
+    function approve(uint32 _newVersion) private {
+        MrenclaveProposal storage proposal = _proposals[_newVersion];
+        require(isPending(proposal), "The proposal is not pending");
+        require(!_approved[proposal.nonce][msg.sender], "The msg.sender has already approved");
+
+        _approved[proposal.nonce][msg.sender] = true;
+        proposal.approvals++;
+        emit ApproveMrenclave(_newVersion, msg.sender);
+    }
+
+    function execute(uint32 _newVersion) private {
+        MrenclaveProposal storage proposal = _proposals[_newVersion];
+        proposal.executed = true;
+
+        updateMrenclaveInner(proposal.mrEnclave, proposal.verifyingKey, proposal.encryptingKey);
+        handshake_wo_sig(proposal.handshake);
         _mrenclaveVer = _newVersion;
         emit UpdateMrenclaveVer(_newVersion);
     }
 
+    // A proposal is outdated once another one is executed, because the key epoch is bumped.
+    function isPending(MrenclaveProposal storage proposal) private view returns (bool) {
+        return proposal.proposedAt != 0 && !proposal.executed && proposal.keyEpoch == keyEpoch;
+    }
+
+    // Revoke an enclave's old keys after its rotated keys are registered by `registerReport`.
+    // The revocation must be signed by the old verifying key in favor of the new one.
+    function revokeKeys(
+        address _verifyingKey,
+        bytes memory _encryptingKey,
+        address _newVerifyingKey,
+        bytes memory _enclaveSig
+    ) public {
+        require(
+            isValidVerifyingKey(_newVerifyingKey),
+            "The new verifying key has not been registered."
+        );
+        address verifyingKey = Secp256k1.recover(
+            sha256(abi.encodePacked(_verifyingKey, _encryptingKey, _newVerifyingKey)),
+            _enclaveSig
+        );
+        require(
+            verifyingKey != address(0),
+            "recovered verifyingKey was address(0)"
+        );
+        require(
+            verifyingKey == _verifyingKey,
+            "Invalid enclave signature."
+        );
+
+        revokeKeysInner(_verifyingKey, _encryptingKey);
+        emit RevokeKeys(_verifyingKey, _encryptingKey);
+    }
+
     // Store ciphertexts which is generated by trusted environment.
     function storeCommand(bytes memory _newCiphertext, bytes memory _enclaveSig)
         public
@@ -107,11 +276,67 @@ contract Anonify is ReportHandle {
             "recovered verifyingKey was address(0)"
         );
         require(
-            verifyingKeyMapping[verifyingKey] == verifyingKey,
+            isValidVerifyingKey(verifyingKey),
             "Invalid enclave signature."
         );
 
-        emit StoreCiphertext(_newCiphertext);
+        emit StoreCiphertext(
+            BytesUtils.toUint32LE(_newCiphertext, CIPHERTEXT_ROSTER_IDX_OFFSET),
+            BytesUtils.toUint32LE(_newCiphertext, CIPHERTEXT_EPOCH_OFFSET),
+            _newCiphertext
+        );
+    }
+
+    // Store the sha256 hash of a ciphertext instead of the ciphertext.
+    // The enclave signs the same hash as storeCommand.
+    function storeCommandHash(bytes32 _ciphertextHash, bytes memory _enclaveSig)
+        public
+    {
+        address verifyingKey = Secp256k1.recover(
+            _ciphertextHash,
+            _enclaveSig
+        );
+        require(
+            verifyingKey != address(0),
+            "recovered verifyingKey was address(0)"
+        );
+        require(
+            isValidVerifyingKey(verifyingKey),
+            "Invalid enclave signature."
+        );
+
+        emit StoreCiphertextHash(_ciphertextHash);
+    }
+
+    // Publish the commitment to the state of a member's enclave signed by the enclave,
+    // so that the other members are able to check they have computed the same state.
+    function publishStateCommitment(bytes memory _commitment, bytes memory _enclaveSig)
+        public
+    {
+        require(_isMember[msg.sender], "The msg.sender has not joined the group");
+        uint32 rosterIdx = BytesUtils.toUint32LE(_commitment, COMMITMENT_ROSTER_IDX_OFFSET);
+        require(
+            _senderToRosterIdx[msg.sender] == rosterIdx,
+            "The roster index must be same as the registered one"
+        );
+        address verifyingKey = Secp256k1.recover(
+            sha256(_commitment),
+            _enclaveSig
+        );
+        require(
+            verifyingKey != address(0),
+            "recovered verifyingKey was address(0)"
+        );
+        require(
+            isValidVerifyingKey(verifyingKey),
+            "Invalid enclave signature."
+        );
+
+        emit StateCommitment(
+            rosterIdx,
+            BytesUtils.toUint32LE(_commitment, COMMITMENT_EPOCH_OFFSET),
+            _commitment
+        );
     }
 
     function handshake(
@@ -119,6 +344,7 @@ contract Anonify is ReportHandle {
         bytes memory _enclaveSig,
         uint32 _rosterIdx
     ) public {
+        require(_isMember[msg.sender], "The msg.sender has not joined the group");
         require(
             _senderToRosterIdx[msg.sender] == _rosterIdx,
             "The roster index must be same as the registered one"
@@ -132,14 +358,18 @@ contract Anonify is ReportHandle {
             "recovered verifyingKey was address(0)"
         );
         require(
-            verifyingKeyMapping[verifyingKey] == verifyingKey,
+            isValidVerifyingKey(verifyingKey),
             "Invalid enclave signature."
         );
 
-        emit StoreHandshake(_handshake);
+        handshake_wo_sig(_handshake);
     }
 
     function handshake_wo_sig(bytes memory _handshake) private {
-        emit StoreHandshake(_handshake);
+        emit StoreHandshake(
+            BytesUtils.toUint32LE(_handshake, HANDSHAKE_ROSTER_IDX_OFFSET),
+            BytesUtils.toUint32LE(_handshake, HANDSHAKE_EPOCH_OFFSET),
+            _handshake
+        );
     }
 }
@@ -5,24 +5,31 @@ import "./utils/SolRsaVerify.sol";
 import "./utils/Base64.sol";
 import "./utils/BytesUtils.sol";
 import "./utils/Secp256k1.sol";
+import "./IasSigningKey.sol";
 
-contract ReportHandle {
+contract ReportHandle is IasSigningKey {
     using SafeMath for uint256;
 
     // A cryptographic hash of the measurement.
     // Different builds/versions of an enclave will result in a different MRENCLAVE value.
     bytes32 public mrEnclave;
+    // The epoch of the keys, which is bumped on updating mrenclave.
+    // The keys registered in the older epochs are no longer valid, so they need not be deleted one by one.
+    uint32 public keyEpoch;
+
+    struct KeyState {
+        bool registered;
+        bool revoked;
+        uint32 epoch;
+    }
+
     // Address-formatted verifying keys, each of them is included in `reportdata`
-    mapping(address => address) public verifyingKeyMapping;
-    address[] public verifyingKeyArray; // for deleting mapping
-    // Public keys for encrypting clients messages to TEEs, which is included `reportdata`
-    mapping(bytes => bytes) public encryptingKeyMapping;
-    bytes[] public encryptingKeyArray; // for deleting mapping
-
-    // This is the modulus and the exponent of intel's certificate, you can extract it using:
-    // `openssl x509 -noout -modulus -in AttestationReportSigningCert.pem` and `openssl x509 -in AttestationReportSigningCert.pem -text`.
-    bytes constant internal RSA_EXP = hex"0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000010001";
-    bytes constant internal RSA_MOD = hex"A97A2DE0E66EA6147C9EE745AC0162686C7192099AFC4B3F040FAD6DE093511D74E802F510D716038157DCAF84F4104BD3FED7E6B8F99C8817FD1FF5B9B864296C3D81FA8F1B729E02D21D72FFEE4CED725EFE74BEA68FBC4D4244286FCDD4BF64406A439A15BCB4CF67754489C423972B4A80DF5C2E7C5BC2DBAF2D42BB7B244F7C95BF92C75D3B33FC5410678A89589D1083DA3ACC459F2704CD99598C275E7C1878E00757E5BDB4E840226C11C0A17FF79C80B15C1DDB5AF21CC2417061FBD2A2DA819ED3B72B7EFAA3BFEBE2805C9B8AC19AA346512D484CFC81941E15F55881CC127E8F7AA12300CD5AFB5742FA1D20CB467A5BEB1C666CF76A368978B5";
+    mapping(address => KeyState) private verifyingKeys;
+    // Public keys for encrypting clients messages to TEEs, which is included `reportdata`, indexed by their keccak256 hashes
+    mapping(bytes32 => KeyState) private encryptingKeys;
+    // The hash of the encrypting key registered by the same report as the verifying key
+    mapping(address => bytes32) private pairedEncryptingKey;
+
     uint constant internal WORD_SIZE = 32;
 
     // Set new mrenclave value and enclave address
@@ -42,33 +49,49 @@ contract ReportHandle {
         setKeys(inpVerifyingKey, inpEncryptingKey);
     }
 
-    function updateMrenclaveInner(bytes memory _report, bytes memory _reportSig) internal {
-        (bytes32 inpMrEnclave, address inpVerifyingKey, bytes memory inpEncryptingKey) = extractFromReport(_report, _reportSig);
+    // Replace the mrenclave with the one in a report which has been extracted by `extractFromReport`.
+    function updateMrenclaveInner(bytes32 inpMrEnclave, address inpVerifyingKey, bytes memory inpEncryptingKey) internal {
         require(mrEnclave != inpMrEnclave, "mrenclave must be different one");
 
-        // delete all keys
-        for (uint i = 0; i < verifyingKeyArray.length; i++) {
-            delete verifyingKeyMapping[verifyingKeyArray[i]];
-            delete encryptingKeyMapping[encryptingKeyArray[i]];
-        }
-        delete verifyingKeyArray;
-        delete encryptingKeyArray;
-
+        // Invalidate all the registered keys at once.
+        keyEpoch++;
         setKeys(inpVerifyingKey, inpEncryptingKey);
         mrEnclave = inpMrEnclave;
     }
 
     function setKeys(address inpVerifyingKey, bytes memory inpEncryptingKey) private {
-        verifyingKeyMapping[inpVerifyingKey] = inpVerifyingKey;
-        encryptingKeyMapping[inpEncryptingKey] = inpEncryptingKey;
-        verifyingKeyArray.push(inpVerifyingKey);
-        encryptingKeyArray.push(inpEncryptingKey);
+        bytes32 encryptingKeyHash = keccak256(inpEncryptingKey);
+        verifyingKeys[inpVerifyingKey] = KeyState(true, false, keyEpoch);
+        encryptingKeys[encryptingKeyHash] = KeyState(true, false, keyEpoch);
+        pairedEncryptingKey[inpVerifyingKey] = encryptingKeyHash;
+    }
+
+    // Revoke a pair of keys registered by the same report.
+    function revokeKeysInner(address inpVerifyingKey, bytes memory inpEncryptingKey) internal {
+        require(isValidVerifyingKey(inpVerifyingKey), "The verifying key has not been registered.");
+        bytes32 encryptingKeyHash = keccak256(inpEncryptingKey);
+        require(
+            pairedEncryptingKey[inpVerifyingKey] == encryptingKeyHash,
+            "The encrypting key is not paired with the verifying key."
+        );
+
+        verifyingKeys[inpVerifyingKey].revoked = true;
+        encryptingKeys[encryptingKeyHash].revoked = true;
+    }
+
+    // Whether the verifying key is registered in the current epoch and not revoked
+    function isValidVerifyingKey(address inpVerifyingKey) public view returns (bool) {
+        return isValid(verifyingKeys[inpVerifyingKey]);
+    }
+
+    function isValid(KeyState storage key) private view returns (bool) {
+        return key.registered && !key.revoked && key.epoch == keyEpoch;
     }
 
     // Get the registered encrypting key
     function getEncryptingKey(bytes memory inpEncryptingKey) public view returns (bytes memory) {
-        require(encryptingKeyMapping[inpEncryptingKey].length != 0, "The encrypting key has not been registered.");
-        return encryptingKeyMapping[inpEncryptingKey];
+        require(isValid(encryptingKeys[keccak256(inpEncryptingKey)]), "The encrypting key has not been registered.");
+        return inpEncryptingKey;
     }
 
     function extractFromReport(bytes memory _report, bytes memory _reportSig) internal view returns (bytes32, address, bytes memory) {
@@ -78,8 +101,9 @@ contract ReportHandle {
         bytes32 inpMrEnclave = BytesUtils.toBytes32(extractElement(quote, 112, 32), 0);
         address inpVerifyingKey = BytesUtils.toAddress(extractElement(quote, 368, 20), 0);
         bytes memory inpEncryptingKey = extractElement(quote, 388, 33);
-        require(verifyingKeyMapping[inpVerifyingKey] == address(0), "The verifying key has already been registered.");
-        require(encryptingKeyMapping[inpEncryptingKey].length == 0, "The encrypting key has already been registered.");
+        // A key can never be registered again, even after it is revoked or its epoch has passed.
+        require(!verifyingKeys[inpVerifyingKey].registered, "The verifying key has already been registered.");
+        require(!encryptingKeys[keccak256(inpEncryptingKey)].registered, "The encrypting key has already been registered.");
 
         return (inpMrEnclave, inpVerifyingKey, inpEncryptingKey);
     }
@@ -0,0 +1,10 @@
+pragma solidity ^0.5.0;
+
+// The report signing key of the mock IAS in `tests/mock-ias` for the simulation-mode end-to-end tests.
+// Never deploy the contract built with this file, the private key is published in the repository.
+contract IasSigningKey {
+    // This is the modulus and the exponent of the mock IAS's certificate, you can extract it using:
+    // `openssl x509 -noout -modulus -in tests/mock-ias/certs/signing_cert.pem` and `openssl x509 -in tests/mock-ias/certs/signing_cert.pem -text`.
+    bytes constant internal RSA_EXP = hex"0000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000010001";
+    bytes constant internal RSA_MOD = hex"C4AAA14274602277B4154BC6B0E550A0F282DA9FD3507A2B25D877756F08479D58A73651A3653BC7FA33B9D47E4A2A891EBE76CC7B2CF3135EC326F736C66B1C2A622A7D8C66EAEEEA454F9373953D26590F21DAAEE5AE4635A7FAB19FF76F9D581FA3CD7409ABF7DCB2487FD0AA5943EA412511663A79B261F78AADFED9EE2898E8F2F8C2228359BFD9E67E2A5BA9502F0F59905DD8FF8F7199E1AE75CE65C6625703C03EA5F7C8B10FFC6FB8A8D86C6F6FB9A4A8996272C8F0CA99B6C3F3BC7B88B76F3784CA6CA229EA7CB4D9A2174089039C322F0A3D7668A1C834DB08DA21C8CEF8BD6C1B78303BC83B829EEA16107C1F3BE5D755FF08050281C9D9A049";
+}
@@ -34,4 +34,11 @@ library BytesUtils {
 
         return tempUint;
     }
+
+    // Read a little-endian uint32, in which the SCALE codec encodes integers.
+    function toUint32LE(bytes memory _bytes, uint256 _start) internal pure returns (uint32) {
+        uint32 be = toUint32(_bytes, _start);
+
+        return ((be & 0xff) << 24) | ((be & 0xff00) << 8) | ((be >> 8) & 0xff00) | (be >> 24);
+    }
 }
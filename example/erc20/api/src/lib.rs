@@ -40,7 +40,7 @@ pub mod join_group {
     }
 }
 
-pub mod update_mrenclave {
+pub mod propose_mrenclave {
     pub mod post {
         use super::super::*;
 
@@ -54,6 +54,34 @@ pub mod update_mrenclave {
     }
 }
 
+pub mod approve_mrenclave {
+    pub mod post {
+        use super::super::*;
+
+        #[derive(Clone, Deserialize, Serialize, Debug)]
+        pub struct Request {
+            pub mrenclave_ver: u32,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Response(pub H256);
+    }
+}
+
+pub mod execute_mrenclave {
+    pub mod post {
+        use super::super::*;
+
+        #[derive(Clone, Deserialize, Serialize, Debug)]
+        pub struct Request {
+            pub mrenclave_ver: u32,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Response(pub H256);
+    }
+}
+
 pub mod init_state {
     pub mod post {
         use super::super::*;
@@ -116,8 +144,15 @@ pub mod encrypting_key {
     pub mod get {
         use super::super::*;
 
+        /// The encrypting key with the IAS report attesting it.
+        /// Clients should verify the report before encrypting commands with the key.
         #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
-        pub struct Response(pub DhPubKey);
+        pub struct Response {
+            pub encrypting_key: DhPubKey,
+            pub report: Vec<u8>,
+            pub report_sig: Vec<u8>,
+            pub report_cert: Vec<u8>,
+        }
     }
 }
 
@@ -579,3 +614,100 @@ pub mod register_report {
         pub struct Response(pub H256);
     }
 }
+
+pub mod rotate_identity_key {
+    pub mod post {
+        use super::super::*;
+
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Response(pub H256);
+    }
+}
+
+pub mod sync_bc {
+    pub mod get {
+        use super::super::*;
+
+        /// The state of the sync service and how far it lags behind the ledger.
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Response {
+            pub state: String,
+            pub last_block: Option<u64>,
+            pub latest_block: Option<u64>,
+            pub lag: Option<u64>,
+            pub errors: u64,
+            pub consecutive_errors: u32,
+            pub last_error: Option<String>,
+            pub commitments_published: u64,
+            pub commitment_mismatches: usize,
+        }
+    }
+}
+
+pub mod dead_letters {
+    pub mod get {
+        use super::super::*;
+
+        /// A payload which was skipped instead of being inserted into the enclave
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct DeadLetter {
+            pub id: u64,
+            pub reason: String,
+            pub block_num: Option<u64>,
+            pub roster_idx: Option<u32>,
+            pub replayable: bool,
+            /// The data of the log which could not be decoded, in hex
+            pub raw_log: Option<String>,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Response(pub Vec<DeadLetter>);
+    }
+}
+
+pub mod replay_dead_letter {
+    pub mod post {
+        use super::super::*;
+
+        #[derive(Clone, Deserialize, Serialize, Debug)]
+        pub struct Request {
+            pub id: u64,
+        }
+    }
+}
+
+pub mod state_commitments {
+    pub mod get {
+        use super::super::*;
+
+        /// A state commitment published by a member, and the result of checking it
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Commitment {
+            pub roster_idx: u32,
+            /// The roster index, epoch and generation of the last ciphertext reflected in the state
+            pub ciphertext_id: (u32, u32, u32),
+            pub state_root: String,
+            pub block_num: Option<u64>,
+            pub check: String,
+        }
+
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Response {
+            /// The local state root after the last processed ciphertext
+            pub state_root: Option<String>,
+            /// The latest commitment of each member
+            pub commitments: Vec<Commitment>,
+            pub mismatches: Vec<Commitment>,
+        }
+    }
+}
+
+pub mod publish_state_commitment {
+    pub mod post {
+        use super::super::*;
+
+        /// None if the state has not changed since the last commitment
+        #[derive(Debug, Clone, PartialEq, Default, Deserialize, Serialize)]
+        pub struct Response(pub Option<H256>);
+    }
+}
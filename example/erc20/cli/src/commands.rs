@@ -12,9 +12,16 @@ use erc20_state_transition::{approve, burn, construct, mint, transfer, transfer_
 use frame_common::crypto::AccountId;
 use frame_runtime::primitives::U64;
 use frame_treekem::{DhPubKey, EciesCiphertext};
+use ias_verifier::ReportVerifier;
 use rand::Rng;
 use reqwest::Client;
-use std::path::PathBuf;
+use std::{fs, path::PathBuf, str::FromStr};
+use web3::{
+    contract::{Contract, Options},
+    transports::Http,
+    types::{Address, H256},
+    Web3,
+};
 
 pub(crate) fn deploy(anonify_url: String) -> Result<()> {
     let res = Client::new()
@@ -50,10 +57,10 @@ pub(crate) fn register_report(anonify_url: String, contract_addr: String) -> Res
     Ok(())
 }
 
-pub(crate) fn update_mrenclave(anonify_url: String, contract_addr: String) -> Result<()> {
-    let req = erc20_api::update_mrenclave::post::Request { contract_addr };
+pub(crate) fn propose_mrenclave(anonify_url: String, contract_addr: String) -> Result<()> {
+    let req = erc20_api::propose_mrenclave::post::Request { contract_addr };
     let res = Client::new()
-        .post(&format!("{}/api/v1/update_mrenclave", &anonify_url))
+        .post(&format!("{}/api/v1/propose_mrenclave", &anonify_url))
         .json(&req)
         .send()?
         .text()?;
@@ -62,12 +69,76 @@ pub(crate) fn update_mrenclave(anonify_url: String, contract_addr: String) -> Re
     Ok(())
 }
 
-pub(crate) fn get_encrypting_key(anonify_url: String) -> Result<DhPubKey> {
-    Client::new()
+pub(crate) fn approve_mrenclave(anonify_url: String, mrenclave_ver: u32) -> Result<()> {
+    let req = erc20_api::approve_mrenclave::post::Request { mrenclave_ver };
+    let res = Client::new()
+        .post(&format!("{}/api/v1/approve_mrenclave", &anonify_url))
+        .json(&req)
+        .send()?
+        .text()?;
+
+    println!("Transaction hash: {:?}", res);
+    Ok(())
+}
+
+pub(crate) fn execute_mrenclave(anonify_url: String, mrenclave_ver: u32) -> Result<()> {
+    let req = erc20_api::execute_mrenclave::post::Request { mrenclave_ver };
+    let res = Client::new()
+        .post(&format!("{}/api/v1/execute_mrenclave", &anonify_url))
+        .json(&req)
+        .send()?
+        .text()?;
+
+    println!("Transaction hash: {:?}", res);
+    Ok(())
+}
+
+/// Get the encrypting key from the server, and verify it is attested by
+/// the enclave whose MRENCLAVE is registered to the contract.
+pub(crate) fn get_encrypting_key(
+    anonify_url: String,
+    contract_addr: &str,
+    eth_url: &str,
+    abi_path: &str,
+    verifier: &ReportVerifier,
+) -> Result<DhPubKey> {
+    let resp: erc20_api::encrypting_key::get::Response = Client::new()
         .get(&format!("{}/api/v1/encrypting_key", &anonify_url))
         .send()?
-        .json()
-        .map_err(Into::into)
+        .json()?;
+
+    let mrenclave = get_mrenclave(contract_addr, eth_url, abi_path)?;
+    verifier.verify_encrypting_key(
+        &resp.encrypting_key.encode(),
+        &resp.report,
+        &resp.report_sig,
+        &resp.report_cert,
+        &mrenclave,
+    )?;
+
+    Ok(resp.encrypting_key)
+}
+
+fn get_mrenclave(contract_addr: &str, eth_url: &str, abi_path: &str) -> Result<[u8; 32]> {
+    let address = Address::from_str(contract_addr.trim_start_matches("0x"))
+        .map_err(|e| anyhow!("Invalid contract address: {:?}", e))?;
+    let abi = fs::read(abi_path)?;
+    // The HTTP transport of web3 runs on the tokio runtime.
+    let mut runtime = tokio::runtime::Builder::new()
+        .basic_scheduler()
+        .enable_all()
+        .build()?;
+    let mrenclave: H256 = runtime.block_on(async {
+        let transport = Http::new(eth_url).map_err(|e| anyhow!("{:?}", e))?;
+        let contract = Contract::from_json(Web3::new(transport).eth(), address, &abi)
+            .map_err(|e| anyhow!("Failed to load ABI: {:?}", e))?;
+        contract
+            .query("mrEnclave", (), None, Options::default(), None)
+            .await
+            .map_err(|e| anyhow!("Failed to call mrEnclave: {:?}", e))
+    })?;
+
+    Ok(mrenclave.to_fixed_bytes())
 }
 
 pub(crate) fn init_state<R: Rng>(
@@ -313,6 +384,74 @@ pub(crate) fn start_sync_bc(anonify_url: String) -> Result<()> {
     Ok(())
 }
 
+pub(crate) fn stop_sync_bc(anonify_url: String) -> Result<()> {
+    Client::new()
+        .post(&format!("{}/api/v1/stop_sync_bc", &anonify_url))
+        .send()?
+        .text()?;
+
+    Ok(())
+}
+
+pub(crate) fn sync_bc_status(anonify_url: String) -> Result<()> {
+    let res: erc20_api::sync_bc::get::Response = Client::new()
+        .get(&format!("{}/api/v1/sync_bc", &anonify_url))
+        .send()?
+        .json()?;
+
+    println!("Sync status: {:?}", res);
+    Ok(())
+}
+
+pub(crate) fn dead_letters(anonify_url: String) -> Result<()> {
+    let res: erc20_api::dead_letters::get::Response = Client::new()
+        .get(&format!("{}/api/v1/dead_letters", &anonify_url))
+        .send()?
+        .json()?;
+
+    for letter in res.0 {
+        println!("Dead letter: {:?}", letter);
+    }
+    Ok(())
+}
+
+pub(crate) fn replay_dead_letter(anonify_url: String, id: u64) -> Result<()> {
+    let req = erc20_api::replay_dead_letter::post::Request { id };
+    Client::new()
+        .post(&format!("{}/api/v1/replay_dead_letter", &anonify_url))
+        .json(&req)
+        .send()?
+        .text()?;
+
+    Ok(())
+}
+
+pub(crate) fn state_commitments(anonify_url: String) -> Result<()> {
+    let res: erc20_api::state_commitments::get::Response = Client::new()
+        .get(&format!("{}/api/v1/state_commitments", &anonify_url))
+        .send()?
+        .json()?;
+
+    println!("Local state root: {:?}", res.state_root);
+    for commitment in res.commitments {
+        println!("State commitment: {:?}", commitment);
+    }
+    for commitment in res.mismatches {
+        println!("Mismatched state commitment: {:?}", commitment);
+    }
+    Ok(())
+}
+
+pub(crate) fn publish_state_commitment(anonify_url: String) -> Result<()> {
+    let res: erc20_api::publish_state_commitment::post::Response = Client::new()
+        .post(&format!("{}/api/v1/publish_state_commitment", &anonify_url))
+        .send()?
+        .json()?;
+
+    println!("Transaction hash: {:?}", res);
+    Ok(())
+}
+
 pub(crate) fn set_contract_addr(anonify_url: String, contract_addr: String) -> Result<()> {
     let req = erc20_api::contract_addr::post::Request::new(contract_addr);
     Client::new()
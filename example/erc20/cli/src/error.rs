@@ -12,5 +12,7 @@ pub enum ClientError {
     #[error("{0}")]
     ReqwestError(#[from] reqwest::Error),
     #[error("{0}")]
+    IasVerifierError(#[from] ias_verifier::IasVerifierError),
+    #[error("{0}")]
     AnyhowError(#[from] anyhow::Error),
 }
@@ -5,8 +5,9 @@ use crate::config::*;
 use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use frame_common::crypto::AccountId;
 use frame_treekem::DhPubKey;
+use ias_verifier::ReportVerifier;
 use rand::{rngs::OsRng, Rng};
-use std::{env, path::PathBuf};
+use std::{env, fs, path::PathBuf};
 use term::Term;
 
 mod commands;
@@ -36,15 +37,34 @@ fn main() {
 
     let contract_addr = env::var("CONTRACT_ADDR").unwrap_or_else(|_| String::default());
     let anonify_url = env::var("ANONIFY_URL").expect("ANONIFY_URL is not set");
-    let encrypting_key =
-        commands::get_encrypting_key(anonify_url.clone()).expect("Failed getting encrypting key");
+    // The encrypting key is fetched and verified only by the commands sending encrypted ones.
+    let (key_url, key_contract_addr) = (anonify_url.clone(), contract_addr.clone());
+    let encrypting_key = move || {
+        let eth_url = env::var("ETH_URL").expect("ETH_URL is not set");
+        let abi_path = env::var("ABI_PATH").expect("ABI_PATH is not set");
+        let verifier = match env::var("IAS_ROOT_CERT_PATH") {
+            Ok(path) if !path.is_empty() => {
+                let root_cert = fs::read(path).expect("Failed to read IAS root certificate");
+                ReportVerifier::from_pem(&root_cert).expect("Invalid IAS root certificate")
+            }
+            _ => ReportVerifier::default(),
+        };
+        commands::get_encrypting_key(
+            key_url.clone(),
+            &key_contract_addr,
+            &eth_url,
+            &abi_path,
+            &verifier,
+        )
+        .expect("Failed getting encrypting key")
+    };
 
     match matches.subcommand() {
         (ANONIFY_COMMAND, Some(matches)) => subcommand_anonify(
             term,
             root_dir,
             contract_addr,
-            &encrypting_key,
+            encrypting_key,
             anonify_url,
             matches,
             rng,
@@ -71,7 +91,7 @@ fn subcommand_anonify<R: Rng>(
     mut term: Term,
     root_dir: PathBuf,
     default_contract_addr: String,
-    encrypting_key: &DhPubKey,
+    encrypting_key: impl Fn() -> DhPubKey,
     anonify_url: String,
     matches: &ArgMatches,
     rng: &mut R,
@@ -97,14 +117,34 @@ fn subcommand_anonify<R: Rng>(
             commands::register_report(anonify_url, contract_addr)
                 .expect("Failed to register_report command");
         }
-        ("update_mrenclave", Some(matches)) => {
+        ("propose_mrenclave", Some(matches)) => {
             let contract_addr = match matches.value_of("contract-addr") {
                 Some(addr) => addr.to_string(),
                 None => default_contract_addr,
             };
 
-            commands::update_mrenclave(anonify_url, contract_addr)
-                .expect("Failed to update_mrenclave command");
+            commands::propose_mrenclave(anonify_url, contract_addr)
+                .expect("Failed to propose_mrenclave command");
+        }
+        ("approve_mrenclave", Some(matches)) => {
+            let mrenclave_ver: u32 = matches
+                .value_of("mrenclave-ver")
+                .expect("Not found mrenclave-ver.")
+                .parse()
+                .expect("Failed to parse mrenclave-ver");
+
+            commands::approve_mrenclave(anonify_url, mrenclave_ver)
+                .expect("Failed to approve_mrenclave command");
+        }
+        ("execute_mrenclave", Some(matches)) => {
+            let mrenclave_ver: u32 = matches
+                .value_of("mrenclave-ver")
+                .expect("Not found mrenclave-ver.")
+                .parse()
+                .expect("Failed to parse mrenclave-ver");
+
+            commands::execute_mrenclave(anonify_url, mrenclave_ver)
+                .expect("Failed to execute_mrenclave command");
         }
         ("init_state", Some(matches)) => {
             let keyfile_index: usize = matches
@@ -124,7 +164,7 @@ fn subcommand_anonify<R: Rng>(
                 anonify_url,
                 keyfile_index,
                 total_supply,
-                encrypting_key,
+                &encrypting_key(),
                 rng,
             )
             .expect("Failed to init_state command");
@@ -150,7 +190,7 @@ fn subcommand_anonify<R: Rng>(
                 keyfile_index,
                 target_addr,
                 amount,
-                encrypting_key,
+                &encrypting_key(),
                 rng,
             )
             .expect("Failed to transfer command");
@@ -176,7 +216,7 @@ fn subcommand_anonify<R: Rng>(
                 keyfile_index,
                 target_addr,
                 amount,
-                encrypting_key,
+                &encrypting_key(),
                 rng,
             )
             .expect("Failed to approve command");
@@ -205,7 +245,7 @@ fn subcommand_anonify<R: Rng>(
                 owner_addr,
                 target_addr,
                 amount,
-                encrypting_key,
+                &encrypting_key(),
                 rng,
             )
             .expect("Failed to transfer_from command");
@@ -231,7 +271,7 @@ fn subcommand_anonify<R: Rng>(
                 keyfile_index,
                 target_addr,
                 amount,
-                encrypting_key,
+                &encrypting_key(),
                 rng,
             )
             .expect("Failed to mint command");
@@ -254,7 +294,7 @@ fn subcommand_anonify<R: Rng>(
                 anonify_url,
                 keyfile_index,
                 amount,
-                encrypting_key,
+                &encrypting_key(),
                 rng,
             )
             .expect("Failed to burn command");
@@ -294,6 +334,32 @@ fn subcommand_anonify<R: Rng>(
         ("start_sync_bc", Some(_)) => {
             commands::start_sync_bc(anonify_url).expect("Failed to start_sync_bc command");
         }
+        ("stop_sync_bc", Some(_)) => {
+            commands::stop_sync_bc(anonify_url).expect("Failed to stop_sync_bc command");
+        }
+        ("sync_bc", Some(_)) => {
+            commands::sync_bc_status(anonify_url).expect("Failed to sync_bc command");
+        }
+        ("dead_letters", Some(_)) => {
+            commands::dead_letters(anonify_url).expect("Failed to dead_letters command");
+        }
+        ("replay_dead_letter", Some(matches)) => {
+            let id: u64 = matches
+                .value_of("dead-letter-id")
+                .expect("Not found dead-letter-id.")
+                .parse()
+                .expect("Failed to parse dead-letter-id");
+
+            commands::replay_dead_letter(anonify_url, id)
+                .expect("Failed to replay_dead_letter command");
+        }
+        ("state_commitments", Some(_)) => {
+            commands::state_commitments(anonify_url).expect("Failed to state_commitments command");
+        }
+        ("publish_state_commitment", Some(_)) => {
+            commands::publish_state_commitment(anonify_url)
+                .expect("Failed to publish_state_commitment command");
+        }
         ("set_contract_addr", Some(matches)) => {
             let contract_addr = match matches.value_of("contract-addr") {
                 Some(addr) => addr.to_string(),
@@ -327,10 +393,32 @@ fn anonify_commands_definition<'a, 'b>() -> App<'a, 'b> {
                 .arg(Arg::with_name("contract-addr").short("c").takes_value(true)),
         )
         .subcommand(
-            SubCommand::with_name("update_mrenclave")
-                .about("update mrenclave a contract from anonify services.")
+            SubCommand::with_name("propose_mrenclave")
+                .about(
+                    "propose the mrenclave of anonify services as the new version of a contract.",
+                )
                 .arg(Arg::with_name("contract-addr").short("c").takes_value(true)),
         )
+        .subcommand(
+            SubCommand::with_name("approve_mrenclave")
+                .about("approve the proposed mrenclave of the version.")
+                .arg(
+                    Arg::with_name("mrenclave-ver")
+                        .short("m")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("execute_mrenclave")
+                .about("update to the proposed mrenclave of the version approved by the members.")
+                .arg(
+                    Arg::with_name("mrenclave-ver")
+                        .short("m")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("init_state")
                 .about("init_state from anonify services.")
@@ -508,6 +596,37 @@ fn anonify_commands_definition<'a, 'b>() -> App<'a, 'b> {
         .subcommand(
             SubCommand::with_name("start_sync_bc").about("Get state from anonify services."),
         )
+        .subcommand(
+            SubCommand::with_name("stop_sync_bc")
+                .about("Stop fetching the events of the contract on anonify services."),
+        )
+        .subcommand(
+            SubCommand::with_name("sync_bc")
+                .about("Get the status of fetching the events on anonify services."),
+        )
+        .subcommand(
+            SubCommand::with_name("dead_letters")
+                .about("Get the events skipped instead of being inserted into the enclave."),
+        )
+        .subcommand(
+            SubCommand::with_name("replay_dead_letter")
+                .about("Insert the skipped event into the enclave again.")
+                .arg(
+                    Arg::with_name("dead-letter-id")
+                        .short("d")
+                        .takes_value(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("state_commitments").about(
+                "Get the local state root and the state commitments of the members on anonify services.",
+            ),
+        )
+        .subcommand(
+            SubCommand::with_name("publish_state_commitment")
+                .about("Publish the commitment of the current state to the contract."),
+        )
         .subcommand(
             SubCommand::with_name("set_contract_addr")
                 .about("Get state from anonify services.")
@@ -31,4 +31,9 @@ register_ecall!(
     ),
     (GET_ENCRYPTING_KEY_CMD, EncryptingKeyGetter),
     (CALL_REGISTER_REPORT_CMD, ReportRegistration),
+    (GET_ATTESTATION_STATUS_CMD, AttestationStatusGetter),
+    (CALL_ROTATE_IDENTITY_KEY_CMD, IdentityKeyRotator),
+    (CALL_COMMIT_IDENTITY_KEY_CMD, IdentityKeyCommitter),
+    (CALL_BIND_IDENTITY_KEY_CMD, IdentityKeyBinder),
+    (GET_STATE_COMMITMENT_CMD, StateCommitmentGetter),
 );
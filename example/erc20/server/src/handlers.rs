@@ -1,20 +1,23 @@
 use crate::error::{Result, ServerError};
 use crate::Server;
-use actix_web::{web, HttpResponse};
-use anonify_eth_driver::traits::*;
+use actix_web::{web, HttpRequest, HttpResponse};
+use anonify_eth_driver::{
+    traits::*, CommitmentCheck, Dispatcher, Ethereum, HostError, RemoteCommitment,
+};
 use anyhow::anyhow;
 use erc20_state_transition::CallName;
 use frame_runtime::primitives::{Approved, U64};
-use log::{debug, error, info};
-use std::{sync::Arc, time};
+use log::{debug, info};
+use std::sync::Arc;
+use web3::types::H256;
 
 const DEFAULT_GAS: u64 = 5_000_000;
 
 pub async fn handle_deploy<D, S, W>(server: web::Data<Arc<Server<D, S, W>>>) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
     debug!("Starting deploy a contract...");
 
@@ -49,14 +52,128 @@ where
     Ok(HttpResponse::Ok().json(erc20_api::deploy::post::Response(contract_addr)))
 }
 
+/// Deploy a contract served in a newly launched enclave,
+/// independently of the contract of the routes without a contract address.
+pub async fn handle_deploy_contract<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let (eid, dispatcher) = server.launch_dispatcher()?;
+    let contract_addr = match deploy_contract(&server, dispatcher).await {
+        Ok(contract_addr) => contract_addr,
+        Err(e) => {
+            server.destroy_enclave(eid)?;
+            return Err(e);
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(erc20_api::deploy::post::Response(contract_addr)))
+}
+
+async fn deploy_contract<D, S, W>(
+    server: &Server<D, S, W>,
+    dispatcher: Arc<Dispatcher<D, S, W>>,
+) -> Result<String>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let sender_address = dispatcher
+        .get_account(server.account_index, &server.password)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+    let (contract_addr, export_path_secret) = dispatcher
+        .deploy(
+            sender_address,
+            DEFAULT_GAS,
+            &server.abi_path,
+            &server.bin_path,
+            server.confirmations,
+        )
+        .await
+        .map_err(|e| ServerError::from(e))?;
+
+    debug!("Contract address: {:?}", &contract_addr);
+    server
+        .store_path_secrets
+        .save_to_local_filesystem(&export_path_secret)
+        .map_err(|e| ServerError::from(e))?;
+    dispatcher
+        .set_contract_addr(&contract_addr, &server.abi_path)
+        .map_err(|e| ServerError::from(e))?;
+    server
+        .dispatchers
+        .insert(&contract_addr, dispatcher)
+        .map_err(|e| ServerError::from(e))?;
+
+    Ok(contract_addr)
+}
+
+/// Join the group of the contract in the path with a newly launched enclave.
+pub async fn handle_join_contract<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    contract_addr: web::Path<String>,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let (eid, dispatcher) = server.launch_dispatcher()?;
+    let tx_hash = match join_contract(&server, dispatcher, &contract_addr).await {
+        Ok(tx_hash) => tx_hash,
+        Err(e) => {
+            server.destroy_enclave(eid)?;
+            return Err(e);
+        }
+    };
+
+    Ok(HttpResponse::Ok().json(erc20_api::join_group::post::Response(tx_hash)))
+}
+
+async fn join_contract<D, S, W>(
+    server: &Server<D, S, W>,
+    dispatcher: Arc<Dispatcher<D, S, W>>,
+    contract_addr: &str,
+) -> Result<H256>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let sender_address = dispatcher
+        .get_account(server.account_index, &server.password)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+    let (tx_hash, export_path_secret) = dispatcher
+        .join_group(sender_address, DEFAULT_GAS, contract_addr, &server.abi_path)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+    server
+        .store_path_secrets
+        .save_to_local_filesystem(&export_path_secret)
+        .map_err(|e| ServerError::from(e))?;
+    server
+        .dispatchers
+        .insert(contract_addr, dispatcher)
+        .map_err(|e| ServerError::from(e))?;
+
+    Ok(tx_hash)
+}
+
 pub async fn handle_join_group<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
     req: web::Json<erc20_api::join_group::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
     let sender_address = server
         .dispatcher
@@ -81,14 +198,15 @@ where
     Ok(HttpResponse::Ok().json(erc20_api::join_group::post::Response(tx_hash)))
 }
 
-pub async fn handle_update_mrenclave<D, S, W>(
+/// Propose the mrenclave of this server's enclave, which is updated once approved by the members.
+pub async fn handle_propose_mrenclave<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
-    req: web::Json<erc20_api::update_mrenclave::post::Request>,
+    req: web::Json<erc20_api::propose_mrenclave::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
     let sender_address = server
         .dispatcher
@@ -97,7 +215,7 @@ where
         .map_err(|e| ServerError::from(e))?;
     let (tx_hash, export_path_secret) = server
         .dispatcher
-        .update_mrenclave(
+        .propose_mrenclave(
             sender_address,
             DEFAULT_GAS,
             &req.contract_addr,
@@ -110,30 +228,76 @@ where
         .save_to_local_filesystem(&export_path_secret)
         .map_err(|e| ServerError::from(e))?;
 
-    Ok(HttpResponse::Ok().json(erc20_api::update_mrenclave::post::Response(tx_hash)))
+    Ok(HttpResponse::Ok().json(erc20_api::propose_mrenclave::post::Response(tx_hash)))
 }
 
-pub async fn handle_init_state<D, S, W>(
+pub async fn handle_approve_mrenclave<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
-    req: web::Json<erc20_api::init_state::post::Request>,
+    req: web::Json<erc20_api::approve_mrenclave::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
     let sender_address = server
         .dispatcher
         .get_account(server.account_index, &server.password)
         .await
         .map_err(|e| ServerError::from(e))?;
+    let tx_hash = server
+        .dispatcher
+        .approve_mrenclave(sender_address, DEFAULT_GAS, req.mrenclave_ver)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+
+    Ok(HttpResponse::Ok().json(erc20_api::approve_mrenclave::post::Response(tx_hash)))
+}
+
+pub async fn handle_execute_mrenclave<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    req: web::Json<erc20_api::execute_mrenclave::post::Request>,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let sender_address = server
+        .dispatcher
+        .get_account(server.account_index, &server.password)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+    let tx_hash = server
+        .dispatcher
+        .execute_mrenclave(sender_address, DEFAULT_GAS, req.mrenclave_ver)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+
+    Ok(HttpResponse::Ok().json(erc20_api::execute_mrenclave::post::Response(tx_hash)))
+}
+
+pub async fn handle_init_state<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
+    req: web::Json<erc20_api::init_state::post::Request>,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let sender_address = dispatcher
+        .get_account(server.account_index, &server.password)
+        .await
+        .map_err(|e| ServerError::from(e))?;
     let access_right = req
         .into_access_right()
         .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
     let encrypted_total_supply = req.encrypted_total_supply.clone();
 
-    let tx_hash = server
-        .dispatcher
+    let tx_hash = dispatcher
         .send_command::<CallName, _>(
             access_right,
             encrypted_total_supply,
@@ -149,15 +313,16 @@ where
 
 pub async fn handle_transfer<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
     req: web::Json<erc20_api::transfer::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
-    let sender_address = server
-        .dispatcher
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let sender_address = dispatcher
         .get_account(server.account_index, &server.password)
         .await
         .map_err(|e| ServerError::from(e))?;
@@ -166,8 +331,7 @@ where
         .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
     let encrypted_transfer_cmd = req.encrypted_transfer_cmd.clone();
 
-    let tx_hash = server
-        .dispatcher
+    let tx_hash = dispatcher
         .send_command::<CallName, _>(
             access_right,
             encrypted_transfer_cmd,
@@ -183,15 +347,16 @@ where
 
 pub async fn handle_approve<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
     req: web::Json<erc20_api::approve::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
-    let sender_address = server
-        .dispatcher
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let sender_address = dispatcher
         .get_account(server.account_index, &server.password)
         .await
         .map_err(|e| ServerError::from(e))?;
@@ -200,8 +365,7 @@ where
         .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
     let encrypted_approve_cmd = req.encrypted_approve_cmd.clone();
 
-    let tx_hash = server
-        .dispatcher
+    let tx_hash = dispatcher
         .send_command::<CallName, _>(
             access_right,
             encrypted_approve_cmd,
@@ -217,15 +381,16 @@ where
 
 pub async fn handle_mint<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
     req: web::Json<erc20_api::mint::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
-    let sender_address = server
-        .dispatcher
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let sender_address = dispatcher
         .get_account(server.account_index, &server.password)
         .await
         .map_err(|e| ServerError::from(e))?;
@@ -234,8 +399,7 @@ where
         .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
     let encrypted_mint_cmd = req.encrypted_mint_cmd.clone();
 
-    let tx_hash = server
-        .dispatcher
+    let tx_hash = dispatcher
         .send_command::<CallName, _>(
             access_right,
             encrypted_mint_cmd,
@@ -251,15 +415,16 @@ where
 
 pub async fn handle_burn<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
     req: web::Json<erc20_api::burn::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
-    let sender_address = server
-        .dispatcher
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let sender_address = dispatcher
         .get_account(server.account_index, &server.password)
         .await
         .map_err(|e| ServerError::from(e))?;
@@ -268,8 +433,7 @@ where
         .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
     let encrypted_burn_cmd = req.encrypted_burn_cmd.clone();
 
-    let tx_hash = server
-        .dispatcher
+    let tx_hash = dispatcher
         .send_command::<CallName, _>(
             access_right,
             encrypted_burn_cmd,
@@ -285,15 +449,16 @@ where
 
 pub async fn handle_transfer_from<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
     req: web::Json<erc20_api::transfer_from::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
-    let sender_address = server
-        .dispatcher
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let sender_address = dispatcher
         .get_account(server.account_index, &server.password)
         .await
         .map_err(|e| ServerError::from(e))?;
@@ -302,8 +467,7 @@ where
         .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
     let encrypted_transfer_from_cmd = req.encrypted_transfer_from_cmd.clone();
 
-    let tx_hash = server
-        .dispatcher
+    let tx_hash = dispatcher
         .send_command::<CallName, _>(
             access_right,
             encrypted_transfer_from_cmd,
@@ -319,19 +483,19 @@ where
 
 pub async fn handle_key_rotation<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
-    let sender_address = server
-        .dispatcher
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let sender_address = dispatcher
         .get_account(server.account_index, &server.password)
         .await
         .map_err(|e| ServerError::from(e))?;
-    let (tx_hash, export_path_secret) = server
-        .dispatcher
+    let (tx_hash, export_path_secret) = dispatcher
         .handshake(sender_address, DEFAULT_GAS)
         .await
         .map_err(|e| ServerError::from(e))?;
@@ -346,15 +510,16 @@ where
 /// Fetch events from blockchain nodes manually, and then get the balance of the address approved by the owner from enclave.
 pub async fn handle_allowance<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
     req: web::Json<erc20_api::allowance::get::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
-    server
-        .dispatcher
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    dispatcher
         .fetch_events::<U64>()
         .await
         .map_err(|e| ServerError::from(e))?;
@@ -362,8 +527,7 @@ where
     let access_right = req
         .into_access_right()
         .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
-    let owner_approved = server
-        .dispatcher
+    let owner_approved = dispatcher
         .get_state::<Approved, _, CallName>(access_right, "approved")
         .map_err(|e| ServerError::from(e))?;
     let approved_amount = owner_approved.allowance(&req.spender).unwrap();
@@ -377,15 +541,16 @@ where
 /// Fetch events from blockchain nodes manually, and then get balance of the address from enclave.
 pub async fn handle_balance_of<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
     req: web::Json<erc20_api::state::get::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
-    server
-        .dispatcher
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    dispatcher
         .fetch_events::<U64>()
         .await
         .map_err(|e| ServerError::from(e))?;
@@ -393,8 +558,7 @@ where
     let access_right = req
         .into_access_right()
         .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
-    let state = server
-        .dispatcher
+    let state = dispatcher
         .get_state::<U64, _, CallName>(access_right, "balance_of")
         .map_err(|e| ServerError::from(e))?;
 
@@ -403,52 +567,225 @@ where
 
 pub async fn handle_encrypting_key<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
-    let pub_key = server
-        .dispatcher
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let output = dispatcher
         .get_encrypting_key()
         .map_err(|e| ServerError::from(e))?;
 
-    Ok(HttpResponse::Ok().json(erc20_api::encrypting_key::get::Response(pub_key)))
+    let resp = erc20_api::encrypting_key::get::Response {
+        report: output.report().to_vec(),
+        report_sig: output.report_sig().to_vec(),
+        report_cert: output.report_cert().to_vec(),
+        encrypting_key: output.encrypting_key(),
+    };
+
+    Ok(HttpResponse::Ok().json(resp))
 }
 
 pub async fn handle_start_sync_bc<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
 ) -> Result<HttpResponse>
 where
-    D: Deployer + Send + Sync + 'static,
-    S: Sender + Send + Sync + 'static,
-    W: Watcher + Send + Sync + 'static,
+    D: Deployer<Ledger = Ethereum> + Send + Sync + 'static,
+    S: Sender<Ledger = Ethereum> + Send + Sync + 'static,
+    W: Watcher<Ledger = Ethereum> + Send + Sync + 'static,
 {
-    // it spawns a new OS thread, and hosts an event loop.
+    let sync = server.sync_for(&http_req)?;
+    let mut worker = match sync.start() {
+        Ok(worker) => worker,
+        Err(HostError::SyncAlreadyRunning) => {
+            info!("The sync service is already running");
+            return Ok(HttpResponse::Conflict().finish());
+        }
+        Err(e) => return Err(ServerError::from(e)),
+    };
+    if server.sync_config.commitment_interval().is_some() {
+        let sender_address = server
+            .dispatcher_for(&http_req)?
+            .get_account(server.account_index, &server.password)
+            .await
+            .map_err(|e| ServerError::from(e))?;
+        worker = worker.set_commitment_signer(sender_address, DEFAULT_GAS);
+    }
+    // it spawns a new OS thread, and hosts an event loop until the worker is stopped.
     actix_rt::Arbiter::new().exec_fn(move || {
         actix_rt::spawn(async move {
-            loop {
-                match server.dispatcher.fetch_events::<U64>().await {
-                    Ok(updated_states) => info!("State updated: {:?}", updated_states),
-                    Err(err) => error!("event fetched error: {:?}", err),
-                };
-                actix_rt::time::delay_for(time::Duration::from_millis(server.sync_time)).await;
-            }
+            worker.run().await;
+            actix_rt::Arbiter::current().stop();
         });
     });
 
     Ok(HttpResponse::Ok().finish())
 }
 
+pub async fn handle_stop_sync_bc<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    if !server.sync_for(&http_req)?.stop() {
+        return Ok(HttpResponse::Conflict().finish());
+    }
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+pub async fn handle_sync_bc_status<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let status = server.sync_for(&http_req)?.status();
+    let resp = erc20_api::sync_bc::get::Response {
+        state: format!("{:?}", status.state),
+        last_block: status.metrics.last_block,
+        latest_block: status.metrics.latest_block,
+        lag: status.metrics.lag(),
+        errors: status.metrics.errors,
+        consecutive_errors: status.metrics.consecutive_errors,
+        last_error: status.metrics.last_error,
+        commitments_published: status.metrics.commitments_published,
+        commitment_mismatches: status.metrics.commitment_mismatches,
+    };
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+pub async fn handle_dead_letters<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let dead_letters = server
+        .dispatcher_for(&http_req)?
+        .dead_letters()
+        .into_iter()
+        .map(|letter| erc20_api::dead_letters::get::DeadLetter {
+            id: letter.id(),
+            reason: letter.reason().to_string(),
+            block_num: letter.block_num(),
+            roster_idx: letter.roster_idx(),
+            replayable: letter.is_replayable(),
+            raw_log: letter.raw_log().map(hex::encode),
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(erc20_api::dead_letters::get::Response(dead_letters)))
+}
+
+pub async fn handle_replay_dead_letter<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
+    req: web::Json<erc20_api::replay_dead_letter::post::Request>,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let updated_state = server
+        .dispatcher_for(&http_req)?
+        .replay_dead_letter::<U64>(req.id)
+        .map_err(|e| ServerError::from(e))?;
+    info!("State updated: {:?}", updated_state);
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// The local state root, and the state commitments of the members checked against it.
+pub async fn handle_state_commitments<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let (state_root, commitments) = dispatcher.state_commitments();
+    let resp = erc20_api::state_commitments::get::Response {
+        state_root: state_root.map(hex::encode),
+        commitments: commitments.iter().map(into_api_commitment).collect(),
+        mismatches: dispatcher
+            .commitment_mismatches()
+            .iter()
+            .map(into_api_commitment)
+            .collect(),
+    };
+
+    Ok(HttpResponse::Ok().json(resp))
+}
+
+fn into_api_commitment(remote: &RemoteCommitment) -> erc20_api::state_commitments::get::Commitment {
+    let commitment = remote.commitment();
+    let check = match remote.check() {
+        CommitmentCheck::Matched => "Matched".to_string(),
+        CommitmentCheck::Mismatched { local_root } => {
+            format!("Mismatched (local: {})", hex::encode(local_root))
+        }
+        CommitmentCheck::Unknown => "Unknown".to_string(),
+    };
+
+    erc20_api::state_commitments::get::Commitment {
+        roster_idx: commitment.roster_idx(),
+        ciphertext_id: commitment.ciphertext_id(),
+        state_root: hex::encode(commitment.state_root()),
+        block_num: remote.block_num(),
+        check,
+    }
+}
+
+pub async fn handle_publish_state_commitment<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let sender_address = dispatcher
+        .get_account(server.account_index, &server.password)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+    let tx_hash = dispatcher
+        .publish_state_commitment(sender_address, DEFAULT_GAS)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+
+    Ok(HttpResponse::Ok().json(erc20_api::publish_state_commitment::post::Response(tx_hash)))
+}
+
 pub async fn handle_set_contract_addr<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
     req: web::Json<erc20_api::contract_addr::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
     debug!("Starting set a contract address...");
 
@@ -463,18 +800,19 @@ where
 
 pub async fn handle_register_notification<D, S, W>(
     server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
     req: web::Json<erc20_api::register_notification::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
+    let dispatcher = server.dispatcher_for(&http_req)?;
     let access_right = req
         .into_access_right()
         .map_err(|e| ServerError::from(anyhow!("{:?}", e)))?;
-    server
-        .dispatcher
+    dispatcher
         .register_notification(access_right)
         .map_err(|e| ServerError::from(e))?;
 
@@ -486,9 +824,9 @@ pub async fn handle_register_report<D, S, W>(
     req: web::Json<erc20_api::register_report::post::Request>,
 ) -> Result<HttpResponse>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
     let sender_address = server
         .dispatcher
@@ -508,3 +846,26 @@ where
 
     Ok(HttpResponse::Ok().json(erc20_api::register_report::post::Response(tx_hash)))
 }
+
+/// Rotate the enclave's identity key, and then revoke the old one on the contract.
+pub async fn handle_rotate_identity_key<D, S, W>(
+    server: web::Data<Arc<Server<D, S, W>>>,
+    http_req: HttpRequest,
+) -> Result<HttpResponse>
+where
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
+{
+    let dispatcher = server.dispatcher_for(&http_req)?;
+    let sender_address = dispatcher
+        .get_account(server.account_index, &server.password)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+    let tx_hash = dispatcher
+        .rotate_identity_key(sender_address, DEFAULT_GAS)
+        .await
+        .map_err(|e| ServerError::from(e))?;
+
+    Ok(HttpResponse::Ok().json(erc20_api::rotate_identity_key::post::Response(tx_hash)))
+}
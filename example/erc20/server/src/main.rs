@@ -1,15 +1,31 @@
-use actix_web::{web, App, HttpServer};
-use anonify_eth_driver::{eth::*, traits::*, Dispatcher, EventCache};
+use crate::error::{Result, ServerError};
+use actix_web::{web, App, HttpRequest, HttpServer};
+use anonify_eth_driver::{
+    eth::{signer::LocalSigner, *},
+    traits::*,
+    Dispatcher, DispatcherRegistry, EventCache, FsBlobStore, GovernanceConfig, HttpBlobStore,
+    SharedBlobStore, SyncConfig, SyncService, TxConfig, WatcherConfig,
+};
 use frame_host::{EnclaveDir, StorePathSecrets};
+use frame_runtime::primitives::U64;
 use handlers::*;
 use sgx_types::sgx_enclave_id_t;
-use std::{env, io, sync::Arc};
+use sgx_urts::SgxEnclave;
+use std::{
+    collections::HashMap,
+    env, fmt, fs, io,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 mod error;
 mod handlers;
 #[cfg(test)]
 mod tests;
 
+/// The sync service fetching the balances of the ERC20 contracts
+pub type Erc20SyncService<D, S, W> = SyncService<D, S, W, U64>;
+
 #[derive(Debug)]
 pub struct Server<D: Deployer, S: Sender, W: Watcher> {
     pub eid: sgx_enclave_id_t,
@@ -19,16 +35,45 @@ pub struct Server<D: Deployer, S: Sender, W: Watcher> {
     pub confirmations: usize,
     pub account_index: usize,
     pub password: String,
-    pub sync_time: u64,
+    pub sync_config: SyncConfig,
     pub store_path_secrets: StorePathSecrets,
-    pub dispatcher: Dispatcher<D, S, W>,
+    pub watcher_config: WatcherConfig,
+    pub tx_config: TxConfig,
+    /// The local key which signs transactions instead of the unlocked account of the node
+    pub signer: Option<LocalSigner>,
+    /// The off-chain store of the ciphertexts whose hashes are sent to the contract
+    pub blob_store: Option<SharedBlobStore>,
+    /// The directory where the event caches persist the fetched blocks and the inserted logs
+    pub event_cache_dir: Option<String>,
+    /// The dispatcher of the routes without a contract address
+    pub dispatcher: Arc<Dispatcher<D, S, W>>,
+    /// The dispatchers of the contracts served in their own enclaves
+    pub dispatchers: DispatcherRegistry<D, S, W>,
+    /// The sync services of the dispatchers, keyed by the contract address in the path
+    syncs: Mutex<HashMap<String, Arc<Erc20SyncService<D, S, W>>>>,
+    enclaves: ContractEnclaves,
+}
+
+/// The enclaves launched for the contracts, which are destroyed when they are dropped.
+#[derive(Default)]
+struct ContractEnclaves(Mutex<Vec<SgxEnclave>>);
+
+impl fmt::Debug for ContractEnclaves {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let eids: Vec<sgx_enclave_id_t> = self
+            .0
+            .lock()
+            .map(|enclaves| enclaves.iter().map(|e| e.geteid()).collect())
+            .unwrap_or_default();
+        f.debug_tuple("ContractEnclaves").field(&eids).finish()
+    }
 }
 
 impl<D, S, W> Server<D, S, W>
 where
-    D: Deployer,
-    S: Sender,
-    W: Watcher,
+    D: Deployer<Ledger = Ethereum>,
+    S: Sender<Ledger = Ethereum>,
+    W: Watcher<Ledger = Ethereum>,
 {
     pub fn new(eid: sgx_enclave_id_t) -> Self {
         let eth_url = env::var("ETH_URL").expect("ETH_URL is not set");
@@ -38,7 +83,8 @@ where
             .expect("ACCOUNT_INDEX is not set")
             .parse()
             .expect("Failed to parse ACCOUNT_INDEX to usize");
-        let password = env::var("PASSWORD").expect("PASSWORD is not set");
+        // Not needed if transactions are signed with ETH_KEYFILE
+        let password = env::var("PASSWORD").unwrap_or_default();
         let confirmations: usize = env::var("CONFIRMATIONS")
             .expect("CONFIRMATIONS is not set")
             .parse()
@@ -47,10 +93,89 @@ where
             .unwrap_or_else(|_| "1000".to_string())
             .parse()
             .expect("Failed to parse SYNC_BC_TIME to u64");
+        let sync_max_backoff: u64 = env::var("SYNC_BC_MAX_BACKOFF_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .expect("Failed to parse SYNC_BC_MAX_BACKOFF_SECS to u64");
+        let mut sync_config = SyncConfig::new()
+            .set_interval(Duration::from_millis(sync_time))
+            .set_max_backoff(Duration::from_secs(sync_max_backoff));
+        // The state commitments are published periodically only if it is set
+        if let Ok(commitment_interval) = env::var("STATE_COMMITMENT_INTERVAL_SECS") {
+            let commitment_interval: u64 = commitment_interval
+                .parse()
+                .expect("Failed to parse STATE_COMMITMENT_INTERVAL_SECS to u64");
+            sync_config = sync_config.set_commitment_interval(Duration::from_secs(commitment_interval));
+        }
+
+        let event_confirmations: u64 = env::var("EVENT_CONFIRMATIONS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .expect("Failed to parse EVENT_CONFIRMATIONS to u64");
+        let mut watcher_config = WatcherConfig::new().set_confirmations(event_confirmations);
+        if let Ok(ws_url) = env::var("ETH_WS_URL") {
+            watcher_config = watcher_config.set_ws_url(&ws_url);
+        }
+
+        let tx_replacement_timeout: u64 = env::var("TX_REPLACEMENT_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "60".to_string())
+            .parse()
+            .expect("Failed to parse TX_REPLACEMENT_TIMEOUT_SECS to u64");
+        let tx_confirmation_timeout: u64 = env::var("TX_CONFIRMATION_TIMEOUT_SECS")
+            .unwrap_or_else(|_| "600".to_string())
+            .parse()
+            .expect("Failed to parse TX_CONFIRMATION_TIMEOUT_SECS to u64");
+        let mrenclave_timelock: u64 = env::var("MRENCLAVE_TIMELOCK_BLOCKS")
+            .unwrap_or_else(|_| "5760".to_string())
+            .parse()
+            .expect("Failed to parse MRENCLAVE_TIMELOCK_BLOCKS to u64");
+        let approval_percent: u32 = env::var("MRENCLAVE_APPROVAL_PERCENT")
+            .unwrap_or_else(|_| "50".to_string())
+            .parse()
+            .expect("Failed to parse MRENCLAVE_APPROVAL_PERCENT to u32");
+        let tx_config = TxConfig::new()
+            .set_confirmations(confirmations as u64)
+            .set_replacement_timeout(Duration::from_secs(tx_replacement_timeout))
+            .set_confirmation_timeout(Duration::from_secs(tx_confirmation_timeout))
+            .set_governance(
+                GovernanceConfig::new()
+                    .set_mrenclave_timelock(mrenclave_timelock)
+                    .set_approval_percent(approval_percent),
+            );
+
+        let signer = env::var("ETH_KEYFILE").ok().map(|keyfile| {
+            let password_file = env::var("ETH_KEYFILE_PASSWORD_FILE")
+                .expect("ETH_KEYFILE_PASSWORD_FILE is not set");
+            let password =
+                fs::read_to_string(password_file).expect("Failed to read the keyfile password");
+            LocalSigner::from_keyfile(keyfile, password.trim().as_bytes())
+                .expect("Failed to load the keyfile")
+        });
+
+        let blob_store: Option<SharedBlobStore> = match env::var("BLOB_STORE_DIR") {
+            Ok(dir) => Some(Arc::new(
+                FsBlobStore::new(dir).expect("Failed to create the blob store directory"),
+            )),
+            Err(_) => env::var("BLOB_STORE_URL")
+                .ok()
+                .map(|url| Arc::new(HttpBlobStore::new(&url)) as SharedBlobStore),
+        };
+
+        let event_cache_dir = env::var("EVENT_CACHE_DIR").ok();
 
         let store_path_secrets = StorePathSecrets::new();
-        let cache = EventCache::default();
-        let dispatcher = Dispatcher::<D, S, W>::new(eid, &eth_url, cache).unwrap();
+        let cache =
+            event_cache(event_cache_dir.as_deref()).expect("Failed to load the event cache");
+        let mut dispatcher = Dispatcher::<D, S, W>::new(eid, &eth_url, cache)
+            .unwrap()
+            .set_watcher_config(watcher_config.clone())
+            .set_tx_config(tx_config.clone());
+        if let Some(signer) = &signer {
+            dispatcher = dispatcher.set_signer(signer.clone());
+        }
+        if let Some(blob_store) = &blob_store {
+            dispatcher = dispatcher.set_blob_store(blob_store.clone());
+        }
 
         Server {
             eid,
@@ -59,12 +184,97 @@ where
             bin_path,
             confirmations,
             account_index,
-            sync_time,
+            sync_config,
             password,
             store_path_secrets,
-            dispatcher,
+            watcher_config,
+            tx_config,
+            signer,
+            blob_store,
+            event_cache_dir,
+            dispatcher: Arc::new(dispatcher),
+            dispatchers: DispatcherRegistry::new(),
+            syncs: Mutex::new(HashMap::new()),
+            enclaves: ContractEnclaves::default(),
+        }
+    }
+
+    /// The dispatcher of the contract addressed in the path,
+    /// or the default one for the routes without a contract address.
+    pub fn dispatcher_for(&self, req: &HttpRequest) -> Result<Arc<Dispatcher<D, S, W>>> {
+        match req.match_info().get("contract_addr") {
+            Some(contract_addr) => self.dispatchers.get(contract_addr).map_err(Into::into),
+            None => Ok(self.dispatcher.clone()),
         }
     }
+
+    /// The sync service of the dispatcher for the request, which is created on the first use.
+    pub fn sync_for(&self, req: &HttpRequest) -> Result<Arc<Erc20SyncService<D, S, W>>> {
+        let dispatcher = self.dispatcher_for(req)?;
+        let key = req
+            .match_info()
+            .get("contract_addr")
+            .map(|addr| addr.trim_start_matches("0x").to_lowercase())
+            .unwrap_or_default();
+        let mut syncs = self
+            .syncs
+            .lock()
+            .map_err(|e| ServerError::from(anyhow::anyhow!("{}", e)))?;
+        let sync = syncs.entry(key).or_insert_with(|| {
+            Arc::new(Erc20SyncService::new(dispatcher).set_config(self.sync_config))
+        });
+
+        Ok(sync.clone())
+    }
+
+    /// Launch a new enclave and a dispatcher connected to it to serve another contract.
+    /// The enclave must be destroyed with `destroy_enclave` if the dispatcher is not registered.
+    pub fn launch_dispatcher(&self) -> Result<(sgx_enclave_id_t, Arc<Dispatcher<D, S, W>>)> {
+        let enclave = EnclaveDir::new().init_enclave(true)?;
+        let eid = enclave.geteid();
+        let cache = event_cache(self.event_cache_dir.as_deref())?;
+        let mut dispatcher = Dispatcher::<D, S, W>::new(eid, &self.eth_url, cache)?
+            .set_watcher_config(self.watcher_config.clone())
+            .set_tx_config(self.tx_config.clone());
+        if let Some(signer) = &self.signer {
+            dispatcher = dispatcher.set_signer(signer.clone());
+        }
+        if let Some(blob_store) = &self.blob_store {
+            dispatcher = dispatcher.set_blob_store(blob_store.clone());
+        }
+        self.enclaves
+            .0
+            .lock()
+            .map_err(|e| ServerError::from(anyhow::anyhow!("{}", e)))?
+            .push(enclave);
+
+        Ok((eid, Arc::new(dispatcher)))
+    }
+
+    /// Destroy the enclave launched for a contract which failed to be deployed or joined.
+    pub fn destroy_enclave(&self, eid: sgx_enclave_id_t) -> Result<()> {
+        let mut enclaves = self
+            .enclaves
+            .0
+            .lock()
+            .map_err(|e| ServerError::from(anyhow::anyhow!("{}", e)))?;
+        if let Some(pos) = enclaves.iter().position(|e| e.geteid() == eid) {
+            // The enclave is destroyed when it is dropped.
+            enclaves.remove(pos);
+        }
+
+        Ok(())
+    }
+}
+
+/// The event cache of a dispatcher, which persists the checkpoint of its contract in the directory if it is set.
+/// The enclaves are launched with empty states, so setting a contract which has a checkpoint
+/// taken before a restart fails.
+fn event_cache(dir: Option<&str>) -> Result<EventCache> {
+    match dir {
+        Some(dir) => EventCache::with_checkpoint_dir(dir, None).map_err(Into::into),
+        None => Ok(EventCache::default()),
+    }
 }
 
 #[actix_web::main]
@@ -95,8 +305,16 @@ async fn main() -> io::Result<()> {
                 web::post().to(handle_join_group::<EthDeployer, EthSender, EventWatcher>),
             )
             .route(
-                "/api/v1/update_mrenclave",
-                web::post().to(handle_update_mrenclave::<EthDeployer, EthSender, EventWatcher>),
+                "/api/v1/propose_mrenclave",
+                web::post().to(handle_propose_mrenclave::<EthDeployer, EthSender, EventWatcher>),
+            )
+            .route(
+                "/api/v1/approve_mrenclave",
+                web::post().to(handle_approve_mrenclave::<EthDeployer, EthSender, EventWatcher>),
+            )
+            .route(
+                "/api/v1/execute_mrenclave",
+                web::post().to(handle_execute_mrenclave::<EthDeployer, EthSender, EventWatcher>),
             )
             .route(
                 "/api/v1/init_state",
@@ -138,6 +356,34 @@ async fn main() -> io::Result<()> {
                 "/api/v1/start_sync_bc",
                 web::get().to(handle_start_sync_bc::<EthDeployer, EthSender, EventWatcher>),
             )
+            .route(
+                "/api/v1/stop_sync_bc",
+                web::post().to(handle_stop_sync_bc::<EthDeployer, EthSender, EventWatcher>),
+            )
+            .route(
+                "/api/v1/sync_bc",
+                web::get().to(handle_sync_bc_status::<EthDeployer, EthSender, EventWatcher>),
+            )
+            .route(
+                "/api/v1/dead_letters",
+                web::get().to(handle_dead_letters::<EthDeployer, EthSender, EventWatcher>),
+            )
+            .route(
+                "/api/v1/replay_dead_letter",
+                web::post().to(handle_replay_dead_letter::<EthDeployer, EthSender, EventWatcher>),
+            )
+            .route(
+                "/api/v1/state_commitments",
+                web::get().to(handle_state_commitments::<EthDeployer, EthSender, EventWatcher>),
+            )
+            .route(
+                "/api/v1/publish_state_commitment",
+                web::post().to(handle_publish_state_commitment::<
+                    EthDeployer,
+                    EthSender,
+                    EventWatcher,
+                >),
+            )
             .route(
                 "/api/v1/set_contract_addr",
                 web::get().to(handle_set_contract_addr::<EthDeployer, EthSender, EventWatcher>),
@@ -155,6 +401,122 @@ async fn main() -> io::Result<()> {
                 "/api/v1/register_report",
                 web::post().to(handle_register_report::<EthDeployer, EthSender, EventWatcher>),
             )
+            .route(
+                "/api/v1/rotate_identity_key",
+                web::post().to(handle_rotate_identity_key::<EthDeployer, EthSender, EventWatcher>),
+            )
+            .route(
+                "/api/v1/contracts",
+                web::post().to(handle_deploy_contract::<EthDeployer, EthSender, EventWatcher>),
+            )
+            // The routes of the contracts served in their own enclaves
+            .service(
+                web::scope("/api/v1/contracts/{contract_addr}")
+                    .route(
+                        "/join_group",
+                        web::post()
+                            .to(handle_join_contract::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/init_state",
+                        web::post().to(handle_init_state::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/transfer",
+                        web::post().to(handle_transfer::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/key_rotation",
+                        web::post().to(handle_key_rotation::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/approve",
+                        web::post().to(handle_approve::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/transfer_from",
+                        web::post()
+                            .to(handle_transfer_from::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/mint",
+                        web::post().to(handle_mint::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/burn",
+                        web::post().to(handle_burn::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/allowance",
+                        web::get().to(handle_allowance::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/balance_of",
+                        web::get().to(handle_balance_of::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/start_sync_bc",
+                        web::get().to(handle_start_sync_bc::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/stop_sync_bc",
+                        web::post().to(handle_stop_sync_bc::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/sync_bc",
+                        web::get()
+                            .to(handle_sync_bc_status::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/dead_letters",
+                        web::get().to(handle_dead_letters::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/replay_dead_letter",
+                        web::post().to(handle_replay_dead_letter::<
+                            EthDeployer,
+                            EthSender,
+                            EventWatcher,
+                        >),
+                    )
+                    .route(
+                        "/state_commitments",
+                        web::get().to(handle_state_commitments::<
+                            EthDeployer,
+                            EthSender,
+                            EventWatcher,
+                        >),
+                    )
+                    .route(
+                        "/publish_state_commitment",
+                        web::post().to(handle_publish_state_commitment::<
+                            EthDeployer,
+                            EthSender,
+                            EventWatcher,
+                        >),
+                    )
+                    .route(
+                        "/register_notification",
+                        web::post().to(handle_register_notification::<
+                            EthDeployer,
+                            EthSender,
+                            EventWatcher,
+                        >),
+                    )
+                    .route(
+                        "/encrypting_key",
+                        web::get()
+                            .to(handle_encrypting_key::<EthDeployer, EthSender, EventWatcher>),
+                    )
+                    .route(
+                        "/rotate_identity_key",
+                        web::post().to(handle_rotate_identity_key::<
+                            EthDeployer,
+                            EthSender,
+                            EventWatcher,
+                        >),
+                    ),
+            )
     })
     .bind(anonify_url)?
     .workers(num_workers)
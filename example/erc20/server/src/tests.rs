@@ -1,19 +1,15 @@
 use crate::*;
-use actix_web::{test, web, App};
+use actix_web::{http::StatusCode, test, web, App};
+use anonify_eth_driver::mock::*;
 use codec::{Decode, Encode};
 use erc20_state_transition::{construct, transfer};
-use ethabi::Contract as ContractABI;
 use frame_common::crypto::AccountId;
 use frame_runtime::primitives::U64;
 use frame_treekem::{DhPubKey, EciesCiphertext};
+use ias_verifier::ReportVerifier;
 use integration_tests::set_env_vars;
-use std::{fs::File, io::BufReader, path::Path, str::FromStr, time};
-use web3::{
-    contract::{Contract, Options},
-    transports::Http,
-    types::Address,
-    Web3,
-};
+use std::{fs, str::FromStr, time};
+use web3::types::Address;
 
 const SYNC_TIME: u64 = 1500;
 
@@ -27,11 +23,11 @@ async fn test_deploy_post() {
         .init_enclave(true)
         .expect("Failed to initialize enclave.");
     let eid = enclave.geteid();
-    let server = Arc::new(Server::<EthDeployer, EthSender, EventWatcher>::new(eid));
+    let server = Arc::new(Server::<MockDeployer, MockSender, MockWatcher>::new(eid));
 
     let mut app = test::init_service(App::new().data(server.clone()).route(
         "/api/v1/deploy",
-        web::post().to(handle_deploy::<EthDeployer, EthSender, EventWatcher>),
+        web::post().to(handle_deploy::<MockDeployer, MockSender, MockWatcher>),
     ))
     .await;
 
@@ -47,36 +43,35 @@ async fn test_multiple_messages() {
     set_env_vars();
     set_server_env_vars();
 
-    let abi_path = env::var("ABI_PATH").expect("ABI_PATH is not set");
     let eth_url = env::var("ETH_URL").expect("ETH_URL is not set");
 
     let enclave = EnclaveDir::new()
         .init_enclave(true)
         .expect("Failed to initialize enclave.");
     let eid = enclave.geteid();
-    let server = Arc::new(Server::<EthDeployer, EthSender, EventWatcher>::new(eid));
+    let server = Arc::new(Server::<MockDeployer, MockSender, MockWatcher>::new(eid));
     let mut app = test::init_service(
         App::new()
             .data(server.clone())
             .route(
                 "/api/v1/deploy",
-                web::post().to(handle_deploy::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_deploy::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/init_state",
-                web::post().to(handle_init_state::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_init_state::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/transfer",
-                web::post().to(handle_transfer::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_transfer::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/balance_of",
-                web::get().to(handle_balance_of::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_balance_of::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/encrypting_key",
-                web::get().to(handle_encrypting_key::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_encrypting_key::<MockDeployer, MockSender, MockWatcher>),
             ),
     )
     .await;
@@ -102,8 +97,7 @@ async fn test_multiple_messages() {
     let resp = test::call_service(&mut app, req).await;
     assert!(resp.status().is_success(), "response: {:?}", resp);
     let enc_key_resp: erc20_api::encrypting_key::get::Response = test::read_body_json(resp).await;
-    let enc_key =
-        verify_encrypting_key(enc_key_resp.0, &abi_path, &eth_url, &contract_addr.0).await;
+    let enc_key = verify_encrypting_key(enc_key_resp, &eth_url, &contract_addr.0).await;
 
     let init_100_req = init_100_req(&enc_key);
     let req = test::TestRequest::post()
@@ -148,40 +142,43 @@ async fn test_skip_invalid_event() {
     set_env_vars();
     set_server_env_vars();
 
-    let abi_path = env::var("ABI_PATH").expect("ABI_PATH is not set");
     let eth_url = env::var("ETH_URL").expect("ETH_URL is not set");
 
     let enclave = EnclaveDir::new()
         .init_enclave(true)
         .expect("Failed to initialize enclave.");
     let eid = enclave.geteid();
-    let server = Arc::new(Server::<EthDeployer, EthSender, EventWatcher>::new(eid));
+    let server = Arc::new(Server::<MockDeployer, MockSender, MockWatcher>::new(eid));
     let mut app = test::init_service(
         App::new()
             .data(server.clone())
             .route(
                 "/api/v1/deploy",
-                web::post().to(handle_deploy::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_deploy::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/start_sync_bc",
-                web::get().to(handle_start_sync_bc::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_start_sync_bc::<MockDeployer, MockSender, MockWatcher>),
+            )
+            .route(
+                "/api/v1/sync_bc",
+                web::get().to(handle_sync_bc_status::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/init_state",
-                web::post().to(handle_init_state::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_init_state::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/transfer",
-                web::post().to(handle_transfer::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_transfer::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/balance_of",
-                web::get().to(handle_balance_of::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_balance_of::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/encrypting_key",
-                web::get().to(handle_encrypting_key::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_encrypting_key::<MockDeployer, MockSender, MockWatcher>),
             ),
     )
     .await;
@@ -198,14 +195,26 @@ async fn test_skip_invalid_event() {
     let resp = test::call_service(&mut app, req).await;
     assert!(resp.status().is_success(), "response: {:?}", resp);
 
+    // The second request does not start another worker.
+    let req = test::TestRequest::get()
+        .uri("/api/v1/start_sync_bc")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+
+    let req = test::TestRequest::get().uri("/api/v1/sync_bc").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+    let status: erc20_api::sync_bc::get::Response = test::read_body_json(resp).await;
+    assert_eq!(status.state, "Running");
+
     let req = test::TestRequest::get()
         .uri("/api/v1/encrypting_key")
         .to_request();
     let resp = test::call_service(&mut app, req).await;
     assert!(resp.status().is_success(), "response: {:?}", resp);
     let enc_key_resp: erc20_api::encrypting_key::get::Response = test::read_body_json(resp).await;
-    let enc_key =
-        verify_encrypting_key(enc_key_resp.0, &abi_path, &eth_url, &contract_addr.0).await;
+    let enc_key = verify_encrypting_key(enc_key_resp, &eth_url, &contract_addr.0).await;
 
     let init_100_req = init_100_req(&enc_key);
     let req = test::TestRequest::post()
@@ -265,40 +274,39 @@ async fn test_node_recovery() {
     set_server_env_vars();
     env::remove_var("AUDITOR_ENDPOINT");
 
-    let abi_path = env::var("ABI_PATH").expect("ABI_PATH is not set");
     let eth_url = env::var("ETH_URL").expect("ETH_URL is not set");
 
     let enclave = EnclaveDir::new()
         .init_enclave(true)
         .expect("Failed to initialize enclave.");
     let eid = enclave.geteid();
-    let server = Arc::new(Server::<EthDeployer, EthSender, EventWatcher>::new(eid));
+    let server = Arc::new(Server::<MockDeployer, MockSender, MockWatcher>::new(eid));
     let mut app = test::init_service(
         App::new()
             .data(server.clone())
             .route(
                 "/api/v1/deploy",
-                web::post().to(handle_deploy::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_deploy::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/start_sync_bc",
-                web::get().to(handle_start_sync_bc::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_start_sync_bc::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/init_state",
-                web::post().to(handle_init_state::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_init_state::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/transfer",
-                web::post().to(handle_transfer::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_transfer::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/balance_of",
-                web::get().to(handle_balance_of::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_balance_of::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/encrypting_key",
-                web::get().to(handle_encrypting_key::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_encrypting_key::<MockDeployer, MockSender, MockWatcher>),
             ),
     )
     .await;
@@ -307,7 +315,7 @@ async fn test_node_recovery() {
         .init_enclave(true)
         .expect("Failed to initialize enclave.");
     let recovered_eid = recovered_enclave.geteid();
-    let recovered_server = Arc::new(Server::<EthDeployer, EthSender, EventWatcher>::new(
+    let recovered_server = Arc::new(Server::<MockDeployer, MockSender, MockWatcher>::new(
         recovered_eid,
     ));
 
@@ -316,27 +324,27 @@ async fn test_node_recovery() {
             .data(recovered_server.clone())
             .route(
                 "/api/v1/balance_of",
-                web::get().to(handle_balance_of::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_balance_of::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/start_sync_bc",
-                web::get().to(handle_start_sync_bc::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_start_sync_bc::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/set_contract_addr",
-                web::get().to(handle_set_contract_addr::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_set_contract_addr::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/transfer",
-                web::post().to(handle_transfer::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_transfer::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/encrypting_key",
-                web::get().to(handle_encrypting_key::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_encrypting_key::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/register_report",
-                web::post().to(handle_register_report::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_register_report::<MockDeployer, MockSender, MockWatcher>),
             ),
     )
     .await;
@@ -359,8 +367,7 @@ async fn test_node_recovery() {
     let resp = test::call_service(&mut app, req).await;
     assert!(resp.status().is_success(), "response: {:?}", resp);
     let enc_key_resp: erc20_api::encrypting_key::get::Response = test::read_body_json(resp).await;
-    let enc_key =
-        verify_encrypting_key(enc_key_resp.0, &abi_path, &eth_url, &contract_addr.0).await;
+    let enc_key = verify_encrypting_key(enc_key_resp, &eth_url, &contract_addr.0).await;
 
     let init_100_req = init_100_req(&enc_key);
     let req = test::TestRequest::post()
@@ -433,8 +440,7 @@ async fn test_node_recovery() {
     let resp = test::call_service(&mut recovered_app, req).await;
     assert!(resp.status().is_success(), "response: {:?}", resp);
     let enc_key_resp: erc20_api::encrypting_key::get::Response = test::read_body_json(resp).await;
-    let enc_key =
-        verify_encrypting_key(enc_key_resp.0, &abi_path, &eth_url, &contract_addr.0).await;
+    let enc_key = verify_encrypting_key(enc_key_resp, &eth_url, &contract_addr.0).await;
 
     let transfer_10_req = transfer_10_req(&enc_key);
     let req = test::TestRequest::post()
@@ -459,7 +465,6 @@ async fn test_join_group_then_handshake() {
     set_env_vars();
     set_server_env_vars();
 
-    let abi_path = env::var("ABI_PATH").expect("ABI_PATH is not set");
     let eth_url = env::var("ETH_URL").expect("ETH_URL is not set");
 
     // Enclave must be initialized in main function.
@@ -467,18 +472,18 @@ async fn test_join_group_then_handshake() {
         .init_enclave(true)
         .expect("Failed to initialize enclave.");
     let eid1 = enclave1.geteid();
-    let server1 = Arc::new(Server::<EthDeployer, EthSender, EventWatcher>::new(eid1));
+    let server1 = Arc::new(Server::<MockDeployer, MockSender, MockWatcher>::new(eid1));
 
     let mut app1 = test::init_service(
         App::new()
             .data(server1.clone())
             .route(
                 "/api/v1/deploy",
-                web::post().to(handle_deploy::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_deploy::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/start_sync_bc",
-                web::get().to(handle_start_sync_bc::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_start_sync_bc::<MockDeployer, MockSender, MockWatcher>),
             ),
     )
     .await;
@@ -487,42 +492,42 @@ async fn test_join_group_then_handshake() {
         .init_enclave(true)
         .expect("Failed to initialize enclave.");
     let eid2 = enclave2.geteid();
-    let server2 = Arc::new(Server::<EthDeployer, EthSender, EventWatcher>::new(eid2));
+    let server2 = Arc::new(Server::<MockDeployer, MockSender, MockWatcher>::new(eid2));
 
     let mut app2 = test::init_service(
         App::new()
             .data(server2.clone())
             .route(
                 "/api/v1/join_group",
-                web::post().to(handle_join_group::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_join_group::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/init_state",
-                web::post().to(handle_init_state::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_init_state::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/transfer",
-                web::post().to(handle_transfer::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_transfer::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/balance_of",
-                web::get().to(handle_balance_of::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_balance_of::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/start_sync_bc",
-                web::get().to(handle_start_sync_bc::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_start_sync_bc::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/set_contract_addr",
-                web::get().to(handle_set_contract_addr::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_set_contract_addr::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/key_rotation",
-                web::post().to(handle_key_rotation::<EthDeployer, EthSender, EventWatcher>),
+                web::post().to(handle_key_rotation::<MockDeployer, MockSender, MockWatcher>),
             )
             .route(
                 "/api/v1/encrypting_key",
-                web::get().to(handle_encrypting_key::<EthDeployer, EthSender, EventWatcher>),
+                web::get().to(handle_encrypting_key::<MockDeployer, MockSender, MockWatcher>),
             ),
     )
     .await;
@@ -576,8 +581,7 @@ async fn test_join_group_then_handshake() {
     let resp = test::call_service(&mut app2, req).await;
     assert!(resp.status().is_success(), "response: {:?}", resp);
     let enc_key_resp: erc20_api::encrypting_key::get::Response = test::read_body_json(resp).await;
-    let enc_key =
-        verify_encrypting_key(enc_key_resp.0, &abi_path, &eth_url, &contract_addr.0).await;
+    let enc_key = verify_encrypting_key(enc_key_resp, &eth_url, &contract_addr.0).await;
 
     let init_100_req = init_100_req(&enc_key);
     let req = test::TestRequest::post()
@@ -621,8 +625,104 @@ async fn test_join_group_then_handshake() {
     assert_eq!(balance.0.as_raw(), 90);
 }
 
+#[actix_rt::test]
+async fn test_multiple_contracts() {
+    set_env_vars();
+    set_server_env_vars();
+
+    let eth_url = env::var("ETH_URL").expect("ETH_URL is not set");
+
+    let enclave = EnclaveDir::new()
+        .init_enclave(true)
+        .expect("Failed to initialize enclave.");
+    let eid = enclave.geteid();
+    let server = Arc::new(Server::<MockDeployer, MockSender, MockWatcher>::new(eid));
+    let mut app = test::init_service(
+        App::new()
+            .data(server.clone())
+            .route(
+                "/api/v1/deploy",
+                web::post().to(handle_deploy::<MockDeployer, MockSender, MockWatcher>),
+            )
+            .route(
+                "/api/v1/balance_of",
+                web::get().to(handle_balance_of::<MockDeployer, MockSender, MockWatcher>),
+            )
+            .route(
+                "/api/v1/contracts",
+                web::post().to(handle_deploy_contract::<MockDeployer, MockSender, MockWatcher>),
+            )
+            .service(
+                web::scope("/api/v1/contracts/{contract_addr}")
+                    .route(
+                        "/init_state",
+                        web::post().to(handle_init_state::<MockDeployer, MockSender, MockWatcher>),
+                    )
+                    .route(
+                        "/balance_of",
+                        web::get().to(handle_balance_of::<MockDeployer, MockSender, MockWatcher>),
+                    )
+                    .route(
+                        "/encrypting_key",
+                        web::get()
+                            .to(handle_encrypting_key::<MockDeployer, MockSender, MockWatcher>),
+                    ),
+            ),
+    )
+    .await;
+
+    let req = test::TestRequest::post().uri("/api/v1/deploy").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+
+    // Another contract is served in its own enclave.
+    let req = test::TestRequest::post()
+        .uri("/api/v1/contracts")
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+    let contract_addr: erc20_api::deploy::post::Response = test::read_body_json(resp).await;
+    let scope = format!("/api/v1/contracts/{}", contract_addr.0);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/encrypting_key", scope))
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+    let enc_key_resp: erc20_api::encrypting_key::get::Response = test::read_body_json(resp).await;
+    let enc_key = verify_encrypting_key(enc_key_resp, &eth_url, &contract_addr.0).await;
+
+    let init_100_req = init_100_req(&enc_key);
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/init_state", scope))
+        .set_json(&init_100_req)
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+
+    let req = test::TestRequest::get()
+        .uri(&format!("{}/balance_of", scope))
+        .set_json(&BALANCE_OF_REQ)
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+    let balance: erc20_api::state::get::Response<U64> = test::read_body_json(resp).await;
+    assert_eq!(balance.0.as_raw(), 100);
+
+    // The state of the default contract is not affected.
+    let req = test::TestRequest::get()
+        .uri("/api/v1/balance_of")
+        .set_json(&BALANCE_OF_REQ)
+        .to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+    let balance: erc20_api::state::get::Response<U64> = test::read_body_json(resp).await;
+    assert_eq!(balance.0.as_raw(), 0);
+}
+
 fn set_server_env_vars() {
-    env::set_var("ETH_URL", "http://172.28.0.2:8545");
+    // The servers of the tests deploy their own contracts on the shared mock ledger.
+    env::set_var("ETH_URL", "mock://erc20-server");
     env::set_var("ABI_PATH", "../../../contract-build/Anonify.abi");
     env::set_var("BIN_PATH", "../../../contract-build/Anonify.bin");
     env::set_var("CONFIRMATIONS", "0");
@@ -644,29 +744,30 @@ fn other_turn() {
     env::set_var("ACCOUNT_INDEX", "1");
 }
 
-async fn verify_encrypting_key<P: AsRef<Path>>(
-    encrypting_key: DhPubKey,
-    abi_path: P,
+async fn verify_encrypting_key(
+    enc_key_resp: erc20_api::encrypting_key::get::Response,
     eth_url: &str,
     contract_addr: &str,
 ) -> DhPubKey {
-    let transport = Http::new(eth_url).unwrap();
-    let web3 = Web3::new(transport);
-    let web3_conn = web3.eth();
-
+    let encrypting_key = enc_key_resp.encrypting_key;
+    let ledger = MockLedger::connect(eth_url);
     let address = Address::from_str(contract_addr).unwrap();
-    let f = File::open(abi_path).unwrap();
-    let abi = ContractABI::load(BufReader::new(f)).unwrap();
-
-    let query_encrypting_key: Vec<u8> = Contract::new(web3_conn, address, abi)
-        .query(
-            "getEncryptingKey",
-            encrypting_key.encode(),
-            None,
-            Options::default(),
-            None,
+
+    let mrenclave = ledger.mrenclave(address).unwrap();
+    let root_cert = fs::read(env::var("IAS_ROOT_CERT_PATH").unwrap()).unwrap();
+    ReportVerifier::from_pem(&root_cert)
+        .unwrap()
+        .verify_encrypting_key(
+            &encrypting_key.encode(),
+            &enc_key_resp.report,
+            &enc_key_resp.report_sig,
+            &enc_key_resp.report_cert,
+            &mrenclave,
         )
-        .await
+        .unwrap();
+
+    let query_encrypting_key = ledger
+        .get_encrypting_key(address, &encrypting_key.encode())
         .unwrap();
 
     assert_eq!(
@@ -32,4 +32,5 @@ register_ecall!(
         REGISTER_NOTIFICATION_CMD,
         RegisterNotification<Ed25519ChallengeResponse>
     ),
+    (CALL_BIND_IDENTITY_KEY_CMD, IdentityKeyBinder),
 );
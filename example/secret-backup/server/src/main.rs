@@ -1,4 +1,5 @@
 use actix_web::{web, App, HttpServer};
+use anonify_config::KEY_VAULT_LISTEN_ADDRESS;
 use frame_host::EnclaveDir;
 use handlers::*;
 use key_vault_host::Dispatcher;
@@ -18,8 +19,8 @@ pub struct Server {
 }
 
 impl Server {
-    pub fn new(eid: sgx_enclave_id_t) -> Self {
-        let dispatcher = Dispatcher::new(eid).unwrap();
+    pub fn new(eid: sgx_enclave_id_t, listen_address: String) -> Self {
+        let dispatcher = Dispatcher::new(eid, listen_address).unwrap();
         Server {
             eid,
             dispatcher,
@@ -35,12 +36,14 @@ async fn main() -> io::Result<()> {
         .unwrap_or_else(|_| "16".to_string())
         .parse()
         .expect("Failed to parse NUM_WORKERS");
+    let listen_address = env::var("KEY_VAULT_LISTEN_ADDRESS")
+        .unwrap_or_else(|_| KEY_VAULT_LISTEN_ADDRESS.to_string());
 
     let enclave = EnclaveDir::new()
         .init_enclave(true)
         .expect("Failed to initialize enclave.");
     let eid = enclave.geteid();
-    let server = Arc::new(Server::new(eid));
+    let server = Arc::new(Server::new(eid, listen_address));
 
     HttpServer::new(move || {
         App::new()
@@ -31,7 +31,7 @@ pub async fn get_encrypting_key(
     contract_addr: &str,
     dispatcher: &EthDispatcher<EthDeployer, EthSender, EventWatcher>,
 ) -> DhPubKey {
-    let encrypting_key = dispatcher.get_encrypting_key().unwrap();
+    let encrypting_key = dispatcher.get_encrypting_key().unwrap().encrypting_key();
     let transport = Http::new(ETH_URL).unwrap();
     let web3 = Web3::new(transport);
     let web3_conn = web3.eth();
@@ -67,7 +67,10 @@ async fn test_backup_path_secret() {
         .init_enclave(true)
         .expect("Failed to initialize server enclave.");
     let server_eid = server_enclave.geteid();
-    let server = Arc::new(Server::new(server_eid));
+    let server = Arc::new(Server::new(
+        server_eid,
+        anonify_config::KEY_VAULT_LISTEN_ADDRESS.to_string(),
+    ));
 
     let mut app = test::init_service(
         App::new()
@@ -84,6 +87,11 @@ async fn test_backup_path_secret() {
         test::read_body_json(resp).await;
     assert_eq!(start_response.status, "success".to_string());
 
+    // A second start must be rejected instead of panicking on the address in use.
+    let req = test::TestRequest::post().uri("/api/v1/start").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_server_error(), "response: {:?}", resp);
+
     std::thread::sleep(std::time::Duration::from_secs(1));
 
     // Setup ERC20 application
@@ -142,6 +150,19 @@ async fn test_backup_path_secret() {
         .unwrap();
 
     println!("init state receipt: {:?}", receipt);
+
+    // The stopped server releases the listen address, so that it can be started again.
+    let req = test::TestRequest::post().uri("/api/v1/stop").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+
+    let req = test::TestRequest::post().uri("/api/v1/start").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
+
+    let req = test::TestRequest::post().uri("/api/v1/stop").to_request();
+    let resp = test::call_service(&mut app, req).await;
+    assert!(resp.status().is_success(), "response: {:?}", resp);
 }
 
 pub static ENV_LOGGER_INIT: Lazy<()> = Lazy::new(|| { env_logger::init(); });
@@ -152,11 +173,9 @@ fn set_env_vars() {
     env::set_var("MY_ROSTER_IDX", "0");
     env::set_var("MAX_ROSTER_IDX", "2");
     env::set_var("SPID", "2C149BFC94A61D306A96211AED155BE9");
-    env::set_var(
-        "IAS_URL",
-        "https://api.trustedservices.intel.com/sgx/dev/attestation/v3/report",
-    );
     env::set_var("SUB_KEY", "77e2533de0624df28dc3be3a5b9e50d9");
+    // Sets IAS_URL and IAS_ROOT_CERT_PATH
+    mock_ias::set_mock_ias_env();
     env::set_var("MRA_TLS_SERVER_ADDRESS", "localhost:12345");
     env::set_var("AUDITOR_ENDPOINT", "test");
     env::set_var("ENCLAVE_PKG_NAME", "secret_backup");
@@ -67,22 +67,6 @@ lazy_static! {
 #[serde(crate = "crate::serde")]
 pub struct AccountId(pub [u8; ACCOUNT_ID_SIZE]);
 
-#[cfg(feature = "std")]
-impl From<AccountId> for web3::types::Address {
-    fn from(account_id: AccountId) -> Self {
-        let bytes = account_id.as_bytes();
-        web3::types::Address::from_slice(bytes)
-    }
-}
-
-#[cfg(feature = "std")]
-impl From<&AccountId> for web3::types::Address {
-    fn from(account_id: &AccountId) -> Self {
-        let bytes = account_id.as_bytes();
-        web3::types::Address::from_slice(bytes)
-    }
-}
-
 impl From<&str> for AccountId {
     fn from(s: &str) -> Self {
         let mut res = [0u8; ACCOUNT_ID_SIZE];
@@ -500,6 +484,11 @@ impl Ciphertext {
     pub fn encrypted_state_ref(&self) -> &[u8] {
         &self.encrypted_state
     }
+
+    /// The roster index, epoch and generation identifying the ciphertext
+    pub fn id(&self) -> (u32, u32, u32) {
+        (self.roster_idx, self.epoch, self.generation)
+    }
 }
 
 impl IntoVec for Ciphertext {
@@ -508,6 +497,52 @@ impl IntoVec for Ciphertext {
     }
 }
 
+/// A commitment to the state of an enclave after processing the ciphertext
+/// identified by its roster index, epoch and generation.
+/// It is signed and published by a member so that the other members are able to check
+/// that they have computed the same state.
+#[derive(Clone, Debug, Encode, Decode, PartialEq, Eq, Hash, Default)]
+pub struct StateCommitment {
+    // The epoch of the processed ciphertext
+    epoch: u32,
+    // The roster index of the member committing to the state
+    roster_idx: u32,
+    ciphertext_roster_idx: u32,
+    generation: u32,
+    state_root: [u8; 32],
+}
+
+impl StateCommitment {
+    /// The ciphertext is identified by the tuple returned by `Ciphertext::id`.
+    pub fn new(roster_idx: u32, ciphertext_id: (u32, u32, u32), state_root: [u8; 32]) -> Self {
+        let (ciphertext_roster_idx, epoch, generation) = ciphertext_id;
+        StateCommitment {
+            epoch,
+            roster_idx,
+            ciphertext_roster_idx,
+            generation,
+            state_root,
+        }
+    }
+
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    pub fn roster_idx(&self) -> u32 {
+        self.roster_idx
+    }
+
+    /// The roster index, epoch and generation of the processed ciphertext
+    pub fn ciphertext_id(&self) -> (u32, u32, u32) {
+        (self.ciphertext_roster_idx, self.epoch, self.generation)
+    }
+
+    pub fn state_root(&self) -> [u8; 32] {
+        self.state_root
+    }
+}
+
 // Calculated by `SgxSealedData<PathSecret>::calc_raw_sealed_data_size(add_mac_txt_size: u32, encrypt_txt_size: u32) -> u32`
 pub const SEALED_DATA_SIZE: usize = 592;
 pub const EXPORT_ID_SIZE: usize = 32;
@@ -579,23 +614,106 @@ impl ExportHandshake {
 }
 
 /// PathSecret for a backup via mra-tls
+/// The checksum of the whole path secret is stored with each share of it,
+/// so that the combined path secret can be checked against it.
 #[derive(Debug, Clone, Serialize, Deserialize, Encode, Decode, Default)]
 #[serde(crate = "crate::serde")]
 pub struct BackupPathSecret {
     path_secret: Vec<u8>,
+    checksum: [u8; 32],
     epoch: u32,
+    roster_idx: u32,
+    id: [u8; EXPORT_ID_SIZE],
 }
 
 impl BackupPathSecret {
-    pub fn new(path_secret: Vec<u8>, epoch: u32) -> Self {
-        BackupPathSecret { path_secret, epoch }
+    pub fn new(
+        path_secret: Vec<u8>,
+        checksum: [u8; 32],
+        epoch: u32,
+        roster_idx: u32,
+        id: [u8; EXPORT_ID_SIZE],
+    ) -> Self {
+        BackupPathSecret {
+            path_secret,
+            checksum,
+            epoch,
+            roster_idx,
+            id,
+        }
+    }
+
+    pub fn checksum(&self) -> [u8; 32] {
+        self.checksum
     }
 
     pub fn epoch(&self) -> u32 {
         self.epoch
     }
 
+    pub fn roster_idx(&self) -> u32 {
+        self.roster_idx
+    }
+
+    pub fn id(&self) -> [u8; EXPORT_ID_SIZE] {
+        self.id
+    }
+
     pub fn path_secret(&self) -> &[u8] {
         &self.path_secret[..]
     }
+
+    pub fn recover_key(&self) -> RecoverPathSecret {
+        RecoverPathSecret::new(self.roster_idx, self.epoch, self.id)
+    }
+}
+
+/// The key of a path secret stored in the key-vault
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Encode, Decode, Default,
+)]
+#[serde(crate = "crate::serde")]
+pub struct RecoverPathSecret {
+    roster_idx: u32,
+    epoch: u32,
+    id: [u8; EXPORT_ID_SIZE],
+}
+
+impl RecoverPathSecret {
+    pub fn new(roster_idx: u32, epoch: u32, id: [u8; EXPORT_ID_SIZE]) -> Self {
+        RecoverPathSecret {
+            roster_idx,
+            epoch,
+            id,
+        }
+    }
+
+    pub fn roster_idx(&self) -> u32 {
+        self.roster_idx
+    }
+
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    pub fn id(&self) -> [u8; EXPORT_ID_SIZE] {
+        self.id
+    }
+}
+
+/// Parameters to list the path secrets stored in the key-vault
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(crate = "crate::serde")]
+pub struct ListPathSecrets {
+    roster_idx: u32,
+}
+
+impl ListPathSecrets {
+    pub fn new(roster_idx: u32) -> Self {
+        ListPathSecrets { roster_idx }
+    }
+
+    pub fn roster_idx(&self) -> u32 {
+        self.roster_idx
+    }
 }
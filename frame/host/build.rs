@@ -20,6 +20,13 @@ fn main() {
         }
     }
 
+    // The DCAP quote library is required only for DCAP attestation.
+    println!("cargo:rerun-if-env-changed=SGX_DCAP");
+    if env::var("SGX_DCAP").map(|v| v == "1").unwrap_or(false) {
+        println!("cargo:rustc-link-lib=dylib=sgx_dcap_ql");
+        println!("cargo:rustc-cfg=dcap");
+    }
+
     let edl = format!("{}/edl", rust_sgx_sdk);
     let test_u_c_path = format!("{}/Anonify_test_u.c", build_dir);
     let common_u_c_path = format!("{}/Anonify_common_u.c", build_dir);
@@ -89,3 +89,89 @@ pub extern "C" fn ocall_get_quote(
 
     UntrustedStatus::success()
 }
+
+#[no_mangle]
+pub extern "C" fn ocall_get_qe_target_info(ret_ti: *mut sgx_target_info_t) -> UntrustedStatus {
+    let ret = unsafe { dcap_ql::sgx_qe_get_target_info(ret_ti) };
+
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        println!("sgx_qe_get_target_info returned {:?}", ret);
+        return UntrustedStatus::error();
+    }
+
+    UntrustedStatus::success()
+}
+
+#[no_mangle]
+pub extern "C" fn ocall_get_ecdsa_quote(
+    p_report: *const sgx_report_t,
+    p_quote: *mut u8,
+    maxlen: u32,
+    p_quote_len: *mut u32,
+) -> UntrustedStatus {
+    let mut real_quote_len: u32 = 0;
+
+    let ret = unsafe { dcap_ql::sgx_qe_get_quote_size(&mut real_quote_len as *mut u32) };
+
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        println!("sgx_qe_get_quote_size returned {:?}", ret);
+        return UntrustedStatus::error();
+    }
+    if real_quote_len > maxlen {
+        println!(
+            "quote size {} exceeds the buffer size {}",
+            real_quote_len, maxlen
+        );
+        return UntrustedStatus::error();
+    }
+
+    println!("ecdsa quote size = {}", real_quote_len);
+    unsafe {
+        *p_quote_len = real_quote_len;
+    }
+
+    let ret = unsafe { dcap_ql::sgx_qe_get_quote(p_report, real_quote_len, p_quote) };
+
+    if ret != sgx_quote3_error_t::SGX_QL_SUCCESS {
+        println!("sgx_qe_get_quote returned {:?}", ret);
+        return UntrustedStatus::error();
+    }
+
+    UntrustedStatus::success()
+}
+
+/// The DCAP quote library is linked only if the host is built with `SGX_DCAP=1`,
+/// since EPID-only platforms don't have it.
+mod dcap_ql {
+    #[cfg(dcap)]
+    pub use sgx_types::{sgx_qe_get_quote, sgx_qe_get_quote_size, sgx_qe_get_target_info};
+
+    #[cfg(not(dcap))]
+    pub use self::unlinked::*;
+
+    #[cfg(not(dcap))]
+    mod unlinked {
+        use sgx_types::*;
+
+        pub unsafe fn sgx_qe_get_target_info(
+            _p_qe_target_info: *mut sgx_target_info_t,
+        ) -> sgx_quote3_error_t {
+            println!("DCAP quote library is not linked. Build the host with SGX_DCAP=1");
+            sgx_quote3_error_t::SGX_QL_ERROR_UNEXPECTED
+        }
+
+        pub unsafe fn sgx_qe_get_quote_size(_p_quote_size: *mut u32) -> sgx_quote3_error_t {
+            println!("DCAP quote library is not linked. Build the host with SGX_DCAP=1");
+            sgx_quote3_error_t::SGX_QL_ERROR_UNEXPECTED
+        }
+
+        pub unsafe fn sgx_qe_get_quote(
+            _p_app_report: *const sgx_report_t,
+            _quote_size: u32,
+            _p_quote: *mut u8,
+        ) -> sgx_quote3_error_t {
+            println!("DCAP quote library is not linked. Build the host with SGX_DCAP=1");
+            sgx_quote3_error_t::SGX_QL_ERROR_UNEXPECTED
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, IasVerifierError>;
+
+#[derive(Error, Debug)]
+pub enum IasVerifierError {
+    #[error("Invalid report signature or certificate: {0:?}")]
+    WebpkiError(webpki::Error),
+    #[error("PEM error: {0}")]
+    PemError(#[from] pem::PemError),
+    #[error("Base64 decode error: {0}")]
+    Base64Error(#[from] base64::DecodeError),
+    #[error("Serde json error: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error("The Remote Attestation API version is not supported: {0}")]
+    UnsupportedVersion(u64),
+    #[error("The quote status is not accepted: {0}")]
+    QuoteStatusNotAccepted(String),
+    #[error("Invalid length of the quote body: {0}")]
+    InvalidQuoteBody(usize),
+    #[error("MRENCLAVE in the quote does not match the expected one")]
+    MrenclaveMismatch,
+    #[error("The encrypting key is not included in the report data")]
+    EncryptingKeyMismatch,
+}
+
+impl From<webpki::Error> for IasVerifierError {
+    fn from(err: webpki::Error) -> Self {
+        IasVerifierError::WebpkiError(err)
+    }
+}
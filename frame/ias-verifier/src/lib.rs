@@ -0,0 +1,241 @@
+//! Verification of IAS reports outside enclaves, so that clients can check that
+//! the encrypting key handed back by a host is generated in the enclave registered to the contract.
+//! Only EPID attestation is supported, as well as `ReportHandle.sol`.
+
+mod error;
+
+pub use crate::error::{IasVerifierError, Result};
+use serde::Deserialize;
+use std::time::SystemTime;
+
+/// The PEM-encoded Intel SGX Attestation Report Signing CA certificate
+pub const IAS_ROOT_CERT_PEM: &[u8] = include_bytes!("../../../config/ias_root_cert.pem");
+
+pub const QUOTE_STATUS_OK: &str = "OK";
+pub const QUOTE_STATUS_GROUP_OUT_OF_DATE: &str = "GROUP_OUT_OF_DATE";
+
+/// The supported version of Attestation Verification Report
+const REPORT_VERSION: u64 = 3;
+// The offsets in the quote body are the same as the ones read by `ReportHandle.sol`.
+// See https://api.trustedservices.intel.com/documents/sgx-attestation-api-spec.pdf, P.23.
+const QUOTE_BODY_SIZE: usize = 432;
+const MRENCLAVE_OFFSET: usize = 112;
+const MRENCLAVE_SIZE: usize = 32;
+const VERIFYING_KEY_OFFSET: usize = 368;
+const VERIFYING_KEY_SIZE: usize = 20;
+const ENCRYPTING_KEY_OFFSET: usize = 388;
+const ENCRYPTING_KEY_SIZE: usize = 33;
+
+static SUPPORTED_SIG_ALGS: &[&webpki::SignatureAlgorithm] = &[
+    &webpki::ECDSA_P256_SHA256,
+    &webpki::ECDSA_P256_SHA384,
+    &webpki::ECDSA_P384_SHA256,
+    &webpki::ECDSA_P384_SHA384,
+    &webpki::RSA_PSS_2048_8192_SHA256_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA384_LEGACY_KEY,
+    &webpki::RSA_PSS_2048_8192_SHA512_LEGACY_KEY,
+    &webpki::RSA_PKCS1_2048_8192_SHA256,
+    &webpki::RSA_PKCS1_2048_8192_SHA384,
+    &webpki::RSA_PKCS1_2048_8192_SHA512,
+    &webpki::RSA_PKCS1_3072_8192_SHA384,
+];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerificationReport {
+    version: u64,
+    isv_enclave_quote_status: String,
+    isv_enclave_quote_body: String,
+}
+
+/// A verifier of IAS reports issued under the root certificate.
+/// Reports with `OK` and `GROUP_OUT_OF_DATE` are accepted by default,
+/// the same as the enclave's default quote status policy.
+#[derive(Debug, Clone)]
+pub struct ReportVerifier {
+    root_cert: Vec<u8>,
+    accepted_quote_statuses: Vec<String>,
+}
+
+impl ReportVerifier {
+    /// Create a verifier with the DER-encoded root certificate.
+    pub fn new(root_cert: Vec<u8>) -> Self {
+        ReportVerifier {
+            root_cert,
+            accepted_quote_statuses: vec![QUOTE_STATUS_GROUP_OUT_OF_DATE.to_string()],
+        }
+    }
+
+    pub fn from_pem(root_cert: &[u8]) -> Result<Self> {
+        Ok(Self::new(pem::parse(root_cert)?.contents))
+    }
+
+    /// Replace the quote statuses accepted other than `OK`.
+    pub fn set_accepted_quote_statuses(mut self, statuses: &[&str]) -> Self {
+        self.accepted_quote_statuses = statuses.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Verify the report is signed by the key certified under the root certificate,
+    /// and then return the quote body.
+    pub fn verify(
+        &self,
+        report: &[u8],
+        report_sig: &[u8],
+        report_cert: &[u8],
+    ) -> Result<QuoteBody> {
+        let trust_anchors = [webpki::trust_anchor_util::cert_der_as_trust_anchor(
+            &self.root_cert,
+        )?];
+        let chain = [&self.root_cert[..]];
+        let now =
+            webpki::Time::try_from(SystemTime::now()).map_err(|_| webpki::Error::BadDERTime)?;
+
+        let report_cert = webpki::EndEntityCert::from(report_cert)?;
+        report_cert.verify_is_valid_tls_server_cert(
+            SUPPORTED_SIG_ALGS,
+            &webpki::TLSServerTrustAnchors(&trust_anchors),
+            &chain,
+            now,
+        )?;
+        report_cert.verify_signature(&webpki::RSA_PKCS1_2048_8192_SHA256, report, report_sig)?;
+
+        let report: VerificationReport = serde_json::from_slice(report)?;
+        if report.version != REPORT_VERSION {
+            return Err(IasVerifierError::UnsupportedVersion(report.version));
+        }
+        let status = &report.isv_enclave_quote_status;
+        if status != QUOTE_STATUS_OK && !self.accepted_quote_statuses.contains(status) {
+            return Err(IasVerifierError::QuoteStatusNotAccepted(status.clone()));
+        }
+
+        QuoteBody::new(base64::decode(&report.isv_enclave_quote_body)?)
+    }
+
+    /// Verify the encrypting key is included in the report data of the quote
+    /// generated by the enclave whose MRENCLAVE is registered to the contract.
+    pub fn verify_encrypting_key(
+        &self,
+        encrypting_key: &[u8],
+        report: &[u8],
+        report_sig: &[u8],
+        report_cert: &[u8],
+        mrenclave: &[u8; MRENCLAVE_SIZE],
+    ) -> Result<QuoteBody> {
+        let quote_body = self.verify(report, report_sig, report_cert)?;
+        if quote_body.mrenclave() != &mrenclave[..] {
+            return Err(IasVerifierError::MrenclaveMismatch);
+        }
+        if quote_body.encrypting_key() != encrypting_key {
+            return Err(IasVerifierError::EncryptingKeyMismatch);
+        }
+
+        Ok(quote_body)
+    }
+}
+
+impl Default for ReportVerifier {
+    /// A verifier of the reports signed by Intel
+    fn default() -> Self {
+        Self::from_pem(IAS_ROOT_CERT_PEM).expect("The IAS root certificate is invalid")
+    }
+}
+
+/// The quote body in a verified report
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteBody(Vec<u8>);
+
+impl QuoteBody {
+    fn new(body: Vec<u8>) -> Result<Self> {
+        if body.len() != QUOTE_BODY_SIZE {
+            return Err(IasVerifierError::InvalidQuoteBody(body.len()));
+        }
+        Ok(QuoteBody(body))
+    }
+
+    pub fn mrenclave(&self) -> &[u8] {
+        &self.0[MRENCLAVE_OFFSET..MRENCLAVE_OFFSET + MRENCLAVE_SIZE]
+    }
+
+    /// The address-formatted verifying key of the enclave's identity key
+    pub fn verifying_key(&self) -> &[u8] {
+        &self.0[VERIFYING_KEY_OFFSET..VERIFYING_KEY_OFFSET + VERIFYING_KEY_SIZE]
+    }
+
+    /// The compressed encrypting key of the enclave's identity key
+    pub fn encrypting_key(&self) -> &[u8] {
+        &self.0[ENCRYPTING_KEY_OFFSET..ENCRYPTING_KEY_OFFSET + ENCRYPTING_KEY_SIZE]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock_ias::MockIas;
+
+    const MRENCLAVE: [u8; MRENCLAVE_SIZE] = [1u8; MRENCLAVE_SIZE];
+    const ENCRYPTING_KEY: [u8; ENCRYPTING_KEY_SIZE] = [2u8; ENCRYPTING_KEY_SIZE];
+
+    fn mock_report() -> (String, Vec<u8>, Vec<u8>) {
+        let mut quote = vec![0u8; QUOTE_BODY_SIZE];
+        quote[MRENCLAVE_OFFSET..MRENCLAVE_OFFSET + MRENCLAVE_SIZE].copy_from_slice(&MRENCLAVE);
+        quote[ENCRYPTING_KEY_OFFSET..ENCRYPTING_KEY_OFFSET + ENCRYPTING_KEY_SIZE]
+            .copy_from_slice(&ENCRYPTING_KEY);
+        let (report, report_sig) = MockIas::new().unwrap().create_report(&quote).unwrap();
+
+        (report, report_sig, mock_ias::signing_cert().unwrap())
+    }
+
+    fn mock_verifier() -> ReportVerifier {
+        let root_cert = std::fs::read(mock_ias::root_cert_path()).unwrap();
+        ReportVerifier::from_pem(&root_cert).unwrap()
+    }
+
+    #[test]
+    fn test_verify_encrypting_key() {
+        let (report, report_sig, report_cert) = mock_report();
+        let verifier = mock_verifier();
+
+        let quote_body = verifier
+            .verify_encrypting_key(
+                &ENCRYPTING_KEY,
+                report.as_bytes(),
+                &report_sig,
+                &report_cert,
+                &MRENCLAVE,
+            )
+            .unwrap();
+        assert_eq!(quote_body.mrenclave(), &MRENCLAVE[..]);
+
+        let res = verifier.verify_encrypting_key(
+            &[3u8; ENCRYPTING_KEY_SIZE],
+            report.as_bytes(),
+            &report_sig,
+            &report_cert,
+            &MRENCLAVE,
+        );
+        assert!(matches!(res, Err(IasVerifierError::EncryptingKeyMismatch)));
+
+        let res = verifier.verify_encrypting_key(
+            &ENCRYPTING_KEY,
+            report.as_bytes(),
+            &report_sig,
+            &report_cert,
+            &[0u8; MRENCLAVE_SIZE],
+        );
+        assert!(matches!(res, Err(IasVerifierError::MrenclaveMismatch)));
+    }
+
+    #[test]
+    fn test_reject_untrusted_report() {
+        let (report, report_sig, report_cert) = mock_report();
+
+        // Signed by the test key, which is not certified by Intel
+        let res = ReportVerifier::default().verify(report.as_bytes(), &report_sig, &report_cert);
+        assert!(matches!(res, Err(IasVerifierError::WebpkiError(_))));
+
+        let tampered = report.replace("\"OK\"", "\"KO\"");
+        let res = mock_verifier().verify(tampered.as_bytes(), &report_sig, &report_cert);
+        assert!(matches!(res, Err(IasVerifierError::WebpkiError(_))));
+    }
+}
@@ -3,8 +3,10 @@ use crate::connection::Connection;
 use anyhow::{anyhow, Result};
 use http::Uri;
 use serde::{de::DeserializeOwned, Serialize};
-use std::sync::Arc;
+use serde_json::{json, Value};
+use std::{string::ToString, sync::Arc};
 
+/// A client keeping a session with the server, so that many requests can be sent over one handshake.
 pub struct Client {
     connection: Connection<rustls::ClientSession>,
 }
@@ -17,7 +19,7 @@ impl Client {
 
         let session = rustls::ClientSession::new(&Arc::new(client_config.tls().clone()), hostname);
         let stream = std::net::TcpStream::connect(address)?;
-        let connection = Connection::new(session, stream);
+        let connection = Connection::new(session, stream, client_config.max_frame_len());
 
         Ok(Client { connection })
     }
@@ -29,7 +31,32 @@ impl Client {
     {
         let wrt = serde_json::to_vec(&json)?;
         self.connection.write_frame(wrt)?;
-        let rd = self.connection.read_frame()?;
+        let rd = self
+            .connection
+            .read_frame()?
+            .ok_or_else(|| anyhow!("The server closed the session"))?;
         serde_json::from_slice(&rd).map_err(Into::into)
     }
+
+    /// Call the method routed by the server's `Router`.
+    pub fn request<SE, DE>(&mut self, method: &str, params: SE) -> Result<DE>
+    where
+        SE: Serialize,
+        DE: DeserializeOwned,
+    {
+        let req = json!({
+            "method": method,
+            "params": params,
+        });
+        let mut resp: Value = self.send_json(req)?;
+        if let Some(err) = resp.get("error") {
+            let msg = err
+                .as_str()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| err.to_string());
+            return Err(anyhow!("{} failed: {}", method, msg));
+        }
+
+        serde_json::from_value(resp["result"].take()).map_err(Into::into)
+    }
 }
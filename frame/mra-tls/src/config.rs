@@ -1,11 +1,15 @@
 use crate::error::Result;
 use crate::key::NistP256KeyPair;
+use crate::policy::AttestationPolicy;
 use crate::verifier::AttestedReportVerifier;
-use remote_attestation::QuoteTarget;
-use std::{sync::Arc, vec::Vec};
+use remote_attestation::Attestation;
+use std::{sync::Arc, time::Duration, vec::Vec};
 
 const CERT_ISSUER: &str = "Anonify";
 const CERT_SUBJECT: &str = "CN=Anonify";
+const DEFAULT_MAX_FRAME_LEN: u64 = 2048;
+const DEFAULT_NUM_WORKERS: usize = 4;
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 60;
 
 #[derive(Debug, Clone)]
 pub struct AttestedTlsConfig {
@@ -14,15 +18,21 @@ pub struct AttestedTlsConfig {
 }
 
 impl AttestedTlsConfig {
-    pub fn new_by_ra(spid: &str, ias_url: &str, sub_key: &str, root_cert: Vec<u8>) -> Result<Self> {
-        let key_pair = NistP256KeyPair::new()?;
+    /// Create a self-signed certificate with the attested evidence binding its key by the attestation scheme.
+    pub fn new_by_ra(attestation: &dyn Attestation) -> Result<Self> {
+        Self::new_by_ra_with_key(&NistP256KeyPair::new()?, attestation)
+    }
+
+    /// Create a certificate for the given key so that a caching attestation
+    /// can return the same evidence for its report data across connections.
+    pub fn new_by_ra_with_key(
+        key_pair: &NistP256KeyPair,
+        attestation: &dyn Attestation,
+    ) -> Result<Self> {
         let report_data = key_pair.report_data();
-        let attested_report = QuoteTarget::new()?
-            .set_enclave_report(&report_data)?
-            .create_quote(&spid)?
-            .remote_attestation(ias_url, sub_key, root_cert)?;
+        let attested_evidence = attestation.attest(&report_data)?;
 
-        let extension = serde_json::to_vec(&attested_report)?;
+        let extension = serde_json::to_vec(&attested_evidence)?;
         let ee_cert = key_pair.create_cert_with_extension(CERT_ISSUER, CERT_SUBJECT, &extension);
         let priv_key = key_pair.priv_key_into_der();
 
@@ -33,6 +43,9 @@ impl AttestedTlsConfig {
 #[derive(Clone)]
 pub struct ClientConfig {
     tls: rustls::ClientConfig,
+    max_frame_len: u64,
+    root_cert: Option<Vec<u8>>,
+    policy: AttestationPolicy,
 }
 
 impl ClientConfig {
@@ -52,12 +65,36 @@ impl ClientConfig {
         &self.tls
     }
 
+    pub fn max_frame_len(&self) -> u64 {
+        self.max_frame_len
+    }
+
+    /// Verify the server's attested report and then its measurements by the attestation policy.
     pub fn set_attestation_report_verifier(mut self, root_cert: Vec<u8>) -> Self {
-        let verifier = Arc::new(AttestedReportVerifier::new(root_cert));
+        let verifier = Arc::new(AttestedReportVerifier::new(
+            root_cert.clone(),
+            self.policy.clone(),
+        ));
         self.tls.dangerous().set_certificate_verifier(verifier);
+        self.root_cert = Some(root_cert);
 
         self
     }
+
+    /// Set which server enclaves are accepted. Defaults to the ones signed by the same MRSIGNER as this enclave.
+    pub fn set_attestation_policy(mut self, policy: AttestationPolicy) -> Self {
+        self.policy = policy;
+        match self.root_cert.take() {
+            Some(root_cert) => self.set_attestation_report_verifier(root_cert),
+            None => self,
+        }
+    }
+
+    /// Set the max length of a response frame the client accepts.
+    pub fn set_max_frame_len(mut self, max_frame_len: u64) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
 }
 
 impl Default for ClientConfig {
@@ -75,12 +112,20 @@ impl Default for ClientConfig {
 
         Self {
             tls: client_tls_config,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            root_cert: None,
+            policy: AttestationPolicy::default(),
         }
     }
 }
 
 pub struct ServerConfig {
     tls: rustls::ServerConfig,
+    max_frame_len: u64,
+    num_workers: usize,
+    idle_timeout: Duration,
+    root_cert: Option<Vec<u8>>,
+    policy: AttestationPolicy,
 }
 
 impl ServerConfig {
@@ -98,10 +143,54 @@ impl ServerConfig {
         &self.tls
     }
 
+    pub fn max_frame_len(&self) -> u64 {
+        self.max_frame_len
+    }
+
+    pub fn num_workers(&self) -> usize {
+        self.num_workers
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        self.idle_timeout
+    }
+
+    /// Verify the client's attested report and then its measurements by the attestation policy.
     pub fn set_attestation_report_verifier(mut self, root_cert: Vec<u8>) -> Self {
-        let verifier = Arc::new(AttestedReportVerifier::new(root_cert));
+        let verifier = Arc::new(AttestedReportVerifier::new(
+            root_cert.clone(),
+            self.policy.clone(),
+        ));
         self.tls.set_client_certificate_verifier(verifier);
+        self.root_cert = Some(root_cert);
+
+        self
+    }
 
+    /// Set which client enclaves are accepted. Defaults to the ones signed by the same MRSIGNER as this enclave.
+    pub fn set_attestation_policy(mut self, policy: AttestationPolicy) -> Self {
+        self.policy = policy;
+        match self.root_cert.take() {
+            Some(root_cert) => self.set_attestation_report_verifier(root_cert),
+            None => self,
+        }
+    }
+
+    /// Set the max length of a request frame the server accepts.
+    pub fn set_max_frame_len(mut self, max_frame_len: u64) -> Self {
+        self.max_frame_len = max_frame_len;
+        self
+    }
+
+    /// Set the number of worker threads serving sessions concurrently.
+    pub fn set_num_workers(mut self, num_workers: usize) -> Self {
+        self.num_workers = num_workers.max(1);
+        self
+    }
+
+    /// Set how long a keep-alive session can be idle before the server closes it.
+    pub fn set_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
         self
     }
 }
@@ -111,7 +200,14 @@ impl Default for ServerConfig {
         let client_tls = rustls::NoClientAuth::new();
         let server_tls = rustls::ServerConfig::new(client_tls);
 
-        Self { tls: server_tls }
+        Self {
+            tls: server_tls,
+            max_frame_len: DEFAULT_MAX_FRAME_LEN,
+            num_workers: DEFAULT_NUM_WORKERS,
+            idle_timeout: Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS),
+            root_cert: None,
+            policy: AttestationPolicy::default(),
+        }
     }
 }
 
@@ -1,56 +1,86 @@
+use crate::policy::EnclaveMeasurements;
 use crate::server::RequestHandler;
-use anyhow::{ensure, Result};
-use std::io::{Read, Write};
+use crate::verifier::peer_measurements;
+use anyhow::{anyhow, ensure, Result};
+use std::io::{self, Read, Write};
 use std::net::TcpStream;
 use std::vec::Vec;
 
-const MAX_FRAME_LEN: u64 = 2048;
-
 pub struct Connection<S: rustls::Session> {
     stream: rustls::StreamOwned<S, TcpStream>,
     max_frame_len: u64,
 }
 
 impl<S: rustls::Session> Connection<S> {
-    pub fn new(sess: S, sock: TcpStream) -> Self {
+    pub fn new(sess: S, sock: TcpStream, max_frame_len: u64) -> Self {
         Connection {
             stream: rustls::StreamOwned::new(sess, sock),
-            max_frame_len: MAX_FRAME_LEN,
+            max_frame_len,
         }
     }
 
-    pub fn read_frame(&mut self) -> Result<Vec<u8>> {
+    /// Read a length-prefixed frame. Returns `None` if the peer closed the session between frames.
+    pub fn read_frame(&mut self) -> Result<Option<Vec<u8>>> {
         let mut header = [0u8; 8];
-        self.stream.read_exact(&mut header)?;
+        match self.stream.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
         let frame_len = u64::from_be_bytes(header);
 
-        ensure!(frame_len <= self.max_frame_len, "Exceed max frame length");
+        ensure!(
+            frame_len <= self.max_frame_len,
+            "Exceed max frame length: {} > {}",
+            frame_len,
+            self.max_frame_len
+        );
 
         let mut frame = vec![0u8; frame_len as usize];
         self.stream.read_exact(&mut frame)?;
 
-        Ok(frame)
+        Ok(Some(frame))
     }
 
     pub fn write_frame(&mut self, frame: Vec<u8>) -> Result<()> {
         let frame_len = frame.len() as u64;
         let header = frame_len.to_be_bytes();
 
-        self.stream.write(&header)?;
+        self.stream.write_all(&header)?;
         self.stream.write_all(&frame)?;
         self.stream.flush()?;
 
         Ok(())
     }
 
-    pub fn serve_json<H: RequestHandler>(&mut self, handler: H) -> Result<()> {
-        let req = self.read_frame().unwrap();
-        if req.len() == 0 {
-            dbg!("request's length is 0");
-            return Ok(());
+    /// Serve request frames on the keep-alive session until the peer closes it.
+    pub fn serve_json<H: RequestHandler>(&mut self, handler: &H) -> Result<()> {
+        let mut peer = None;
+        while let Some(req) = self.read_frame()? {
+            if req.is_empty() {
+                continue;
+            }
+            // The handshake has completed by reading the first frame.
+            if peer.is_none() {
+                peer = Some(self.peer_measurements()?);
+            }
+            let resp = handler.handle_json(peer.as_ref().expect("set above"), &req)?;
+            self.write_frame(resp)?;
         }
-        let resp = handler.handle_json(&req)?;
-        self.write_frame(resp)?;
+
         Ok(())
     }
+
+    fn peer_measurements(&self) -> Result<EnclaveMeasurements> {
+        let certs = self
+            .stream
+            .sess
+            .get_peer_certificates()
+            .ok_or_else(|| anyhow!("The peer presented no certificate"))?;
+        let ee_cert = certs
+            .first()
+            .ok_or_else(|| anyhow!("The peer presented no certificate"))?;
+
+        peer_measurements(&ee_cert.0).map_err(|e| anyhow!("{:?}", e))
+    }
 }
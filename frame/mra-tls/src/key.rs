@@ -7,6 +7,7 @@ use num_bigint::BigUint;
 use sgx_tcrypto::SgxEccHandle;
 use sgx_types::{sgx_ec256_private_t, sgx_ec256_public_t, sgx_report_data_t};
 use std::borrow::ToOwned;
+use std::fmt;
 use std::time::{SystemTime, UNIX_EPOCH};
 use std::vec::Vec;
 use yasna::models::{ObjectIdentifier, UTCTime};
@@ -20,6 +21,14 @@ pub struct NistP256KeyPair {
     pub_key: sgx_ec256_public_t,
 }
 
+impl fmt::Debug for NistP256KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NistP256KeyPair")
+            .field("pub_key", &self.pub_key_into_bytes())
+            .finish()
+    }
+}
+
 impl NistP256KeyPair {
     pub fn new() -> Result<Self> {
         let ecc_handle = SgxEccHandle::new();
@@ -8,6 +8,8 @@ pub mod config;
 mod connection;
 mod error;
 mod key;
+pub mod policy;
+pub mod router;
 pub mod server;
 #[cfg(debug_assertions)]
 pub mod tests;
@@ -15,4 +17,7 @@ mod verifier;
 
 pub use client::Client;
 pub use config::{AttestedTlsConfig, ClientConfig, ServerConfig};
-pub use server::{RequestHandler, Server};
+pub use key::NistP256KeyPair;
+pub use policy::{AttestationPolicy, EnclaveMeasurements, SignerPolicy};
+pub use router::Router;
+pub use server::{RequestHandler, Server, ServerStopHandle};
@@ -0,0 +1,221 @@
+use crate::error::{MraTLSError, Result};
+use anyhow::anyhow;
+use remote_attestation::{AttestedEvidence, QuoteStatusPolicy};
+pub use remote_attestation::{QUOTE_STATUS_CONFIGURATION_NEEDED, QUOTE_STATUS_GROUP_OUT_OF_DATE};
+use std::{
+    io::{Cursor, Read},
+    vec::Vec,
+};
+
+// Offsets are defined in "Attestation Service for Intel® Software Guard Extensions (Intel® SGX): API Documentation version 6.0"
+const MR_ENCLAVE_OFFSET: u64 = 112;
+const MR_SIGNER_OFFSET: u64 = 176;
+const ISV_PROD_ID_OFFSET: u64 = 304;
+const ISV_SVN_OFFSET: u64 = 306;
+const REPORT_DATA_OFFSET: u64 = 368;
+
+/// Measurements of the attested enclave read from a quote body
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnclaveMeasurements {
+    pub mr_enclave: [u8; 32],
+    pub mr_signer: [u8; 32],
+    pub isv_prod_id: u16,
+    pub isv_svn: u16,
+    pub report_data: [u8; 64],
+}
+
+impl EnclaveMeasurements {
+    pub fn from_quote_body(quote_body: Vec<u8>) -> Result<Self> {
+        let mut quote = Cursor::new(quote_body);
+        let mut mr_enclave = [0u8; 32];
+        let mut mr_signer = [0u8; 32];
+        let mut isv_prod_id = [0u8; 2];
+        let mut isv_svn = [0u8; 2];
+        let mut report_data = [0u8; 64];
+
+        quote.set_position(MR_ENCLAVE_OFFSET);
+        quote.read_exact(&mut mr_enclave)?;
+        quote.set_position(MR_SIGNER_OFFSET);
+        quote.read_exact(&mut mr_signer)?;
+        quote.set_position(ISV_PROD_ID_OFFSET);
+        quote.read_exact(&mut isv_prod_id)?;
+        quote.set_position(ISV_SVN_OFFSET);
+        quote.read_exact(&mut isv_svn)?;
+        quote.set_position(REPORT_DATA_OFFSET);
+        quote.read_exact(&mut report_data)?;
+
+        Ok(EnclaveMeasurements {
+            mr_enclave,
+            mr_signer,
+            isv_prod_id: u16::from_le_bytes(isv_prod_id),
+            isv_svn: u16::from_le_bytes(isv_svn),
+            report_data,
+        })
+    }
+}
+
+/// Enclaves signed by the MRSIGNER with the product id and at least the security version
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignerPolicy {
+    mr_signer: [u8; 32],
+    isv_prod_id: u16,
+    min_isv_svn: u16,
+}
+
+impl SignerPolicy {
+    pub fn new(mr_signer: [u8; 32], isv_prod_id: u16, min_isv_svn: u16) -> Self {
+        SignerPolicy {
+            mr_signer,
+            isv_prod_id,
+            min_isv_svn,
+        }
+    }
+
+    fn is_satisfied(&self, measurements: &EnclaveMeasurements) -> bool {
+        self.mr_signer == measurements.mr_signer
+            && self.isv_prod_id == measurements.isv_prod_id
+            && self.min_isv_svn <= measurements.isv_svn
+    }
+}
+
+/// A policy of which attested enclaves are accepted as the peer of an mra-tls session.
+/// The peer's measurements must match one of the allowed MRENCLAVEs or the signer policy,
+/// and its quote status must be accepted by the quote status policy.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttestationPolicy {
+    mr_enclaves: Vec<[u8; 32]>,
+    signer: Option<SignerPolicy>,
+    quote_status: QuoteStatusPolicy,
+}
+
+impl AttestationPolicy {
+    /// A policy accepting no enclaves. Allow measurements by the setters.
+    pub fn new() -> Self {
+        AttestationPolicy {
+            mr_enclaves: vec![],
+            signer: None,
+            quote_status: QuoteStatusPolicy::default(),
+        }
+    }
+
+    /// A policy accepting enclaves signed by the same MRSIGNER with the same product id
+    /// as this enclave and at least its security version.
+    pub fn from_self_report() -> Self {
+        let body = sgx_tse::rsgx_self_report().body;
+        let signer = SignerPolicy::new(body.mr_signer.m, body.isv_prod_id, body.isv_svn);
+        AttestationPolicy::new().set_signer(signer)
+    }
+
+    pub fn add_mr_enclave(mut self, mr_enclave: [u8; 32]) -> Self {
+        if !self.mr_enclaves.contains(&mr_enclave) {
+            self.mr_enclaves.push(mr_enclave);
+        }
+        self
+    }
+
+    pub fn set_signer(mut self, signer: SignerPolicy) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Replace the quote statuses accepted other than `OK`.
+    pub fn set_accepted_quote_statuses(mut self, statuses: &[&str]) -> Self {
+        self.quote_status = self.quote_status.set_accepted_statuses(statuses);
+        self
+    }
+
+    pub fn set_quote_status_policy(mut self, policy: QuoteStatusPolicy) -> Self {
+        self.quote_status = policy;
+        self
+    }
+
+    pub fn verify_measurements(&self, measurements: &EnclaveMeasurements) -> Result<()> {
+        let is_allowed_enclave = self.mr_enclaves.contains(&measurements.mr_enclave);
+        let is_allowed_signer = self
+            .signer
+            .as_ref()
+            .map_or(false, |s| s.is_satisfied(measurements));
+        if !is_allowed_enclave && !is_allowed_signer {
+            return Err(MraTLSError::Error(anyhow!(
+                "The enclave measurements are not allowed by the attestation policy"
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub fn verify_quote_status(&self, status: &str) -> Result<()> {
+        self.quote_status
+            .verify_quote_status(status, &[])
+            .map_err(Into::into)
+    }
+
+    /// Verify the quote status of the evidence with its advisories and age.
+    pub fn verify_evidence_status(&self, evidence: &AttestedEvidence) -> Result<()> {
+        evidence
+            .verify_quote_status(&self.quote_status)
+            .map_err(Into::into)
+    }
+}
+
+impl Default for AttestationPolicy {
+    fn default() -> Self {
+        AttestationPolicy::from_self_report()
+    }
+}
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use remote_attestation::QUOTE_STATUS_OK;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(test_verify_measurements, test_verify_quote_status,)
+    }
+
+    fn measurements(mr_enclave: [u8; 32], isv_svn: u16) -> EnclaveMeasurements {
+        EnclaveMeasurements {
+            mr_enclave,
+            mr_signer: [1u8; 32],
+            isv_prod_id: 2,
+            isv_svn,
+            report_data: [0u8; 64],
+        }
+    }
+
+    fn test_verify_measurements() {
+        let policy = AttestationPolicy::new().add_mr_enclave([9u8; 32]);
+        assert!(policy
+            .verify_measurements(&measurements([9u8; 32], 0))
+            .is_ok());
+        assert!(policy
+            .verify_measurements(&measurements([8u8; 32], 0))
+            .is_err());
+
+        let policy = policy.set_signer(SignerPolicy::new([1u8; 32], 2, 3));
+        assert!(policy
+            .verify_measurements(&measurements([8u8; 32], 3))
+            .is_ok());
+        assert!(policy
+            .verify_measurements(&measurements([8u8; 32], 2))
+            .is_err());
+    }
+
+    fn test_verify_quote_status() {
+        let policy = AttestationPolicy::new();
+        assert!(policy.verify_quote_status(QUOTE_STATUS_OK).is_ok());
+        assert!(policy
+            .verify_quote_status(QUOTE_STATUS_GROUP_OUT_OF_DATE)
+            .is_ok());
+        assert!(policy
+            .verify_quote_status(QUOTE_STATUS_CONFIGURATION_NEEDED)
+            .is_err());
+
+        let policy = policy.set_accepted_quote_statuses(&[QUOTE_STATUS_CONFIGURATION_NEEDED]);
+        assert!(policy
+            .verify_quote_status(QUOTE_STATUS_CONFIGURATION_NEEDED)
+            .is_ok());
+        assert!(policy.verify_quote_status("REVOKED").is_err());
+    }
+}
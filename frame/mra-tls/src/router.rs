@@ -0,0 +1,79 @@
+use crate::policy::EnclaveMeasurements;
+use crate::server::RequestHandler;
+use anyhow::{anyhow, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+
+type Route = Arc<dyn Fn(&EnclaveMeasurements, Value) -> Result<Value> + Send + Sync>;
+
+/// A request handler dispatching `{"method": .., "params": ..}` requests to the routes by method name.
+/// It responds `{"result": ..}`, or `{"error": ..}` so that the session is kept even if a request fails.
+#[derive(Clone, Default)]
+pub struct Router {
+    routes: HashMap<String, Route>,
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Router::default()
+    }
+
+    pub fn route<Req, Resp, F>(self, method: &str, f: F) -> Self
+    where
+        Req: DeserializeOwned,
+        Resp: Serialize,
+        F: Fn(Req) -> Result<Resp> + Send + Sync + 'static,
+    {
+        self.route_with_peer(method, move |_, req| f(req))
+    }
+
+    /// Route the method to the handler taking the measurements of the requesting peer,
+    /// so that it can restrict which enclaves are served.
+    pub fn route_with_peer<Req, Resp, F>(mut self, method: &str, f: F) -> Self
+    where
+        Req: DeserializeOwned,
+        Resp: Serialize,
+        F: Fn(&EnclaveMeasurements, Req) -> Result<Resp> + Send + Sync + 'static,
+    {
+        let route: Route = Arc::new(move |peer, params| {
+            let req = serde_json::from_value(params)?;
+            let resp = f(peer, req)?;
+            serde_json::to_value(resp).map_err(Into::into)
+        });
+        self.routes.insert(method.to_string(), route);
+
+        self
+    }
+
+    fn dispatch(&self, peer: &EnclaveMeasurements, msg: &[u8]) -> Result<Value> {
+        let mut req: Value = serde_json::from_slice(msg)?;
+        let method = req
+            .get("method")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("The request has no method"))?
+            .to_string();
+        let route = self
+            .routes
+            .get(&method)
+            .ok_or_else(|| anyhow!("Unknown method: {}", method))?;
+
+        route(peer, req["params"].take())
+    }
+}
+
+impl RequestHandler for Router {
+    fn handle_json(&self, peer: &EnclaveMeasurements, msg: &[u8]) -> Result<Vec<u8>> {
+        let resp = match self.dispatch(peer, msg) {
+            Ok(result) => json!({ "result": result }),
+            Err(e) => json!({ "error": format!("{}", e) }),
+        };
+
+        serde_json::to_vec(&resp).map_err(Into::into)
+    }
+}
@@ -1,46 +1,233 @@
 use crate::config::ServerConfig;
 use crate::connection::Connection;
 use crate::error::Result;
+use crate::policy::EnclaveMeasurements;
+use anonify_config::{ENCLAVE_RESERVED_TCS_NUM, ENCLAVE_TCS_NUM};
 use log::error;
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
 use std::string::String;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize, Ordering},
+    mpsc, Arc, SgxMutex,
+};
+use std::thread;
 use std::vec::Vec;
 
 pub trait RequestHandler {
-    fn handle_json(&self, msg: &[u8]) -> anyhow::Result<Vec<u8>>;
+    /// Handle a request from the attested peer with the measurements.
+    fn handle_json(&self, peer: &EnclaveMeasurements, msg: &[u8]) -> anyhow::Result<Vec<u8>>;
 }
 
+/// Enclave threads available to all the servers in the enclave.
+/// Each server uses its workers and the thread running the accept loop.
+const SERVER_THREAD_BUDGET: usize = ENCLAVE_TCS_NUM - ENCLAVE_RESERVED_TCS_NUM;
+static RESERVED_THREADS: AtomicUsize = AtomicUsize::new(0);
+
 pub struct Server {
     address: String,
     config: ServerConfig,
+    listener: Option<TcpListener>,
+    threads: Option<ThreadReservation>,
+    is_stopped: Arc<AtomicBool>,
 }
 
 impl Server {
     pub fn new(address: String, config: ServerConfig) -> Self {
-        Server { address, config }
+        Server {
+            address,
+            config,
+            listener: None,
+            threads: None,
+            is_stopped: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Bind the listen address in advance so that the caller can handle
+    /// errors such as the address already in use before running the server on another thread.
+    /// It fails if the enclave does not have enough threads left for the workers.
+    pub fn bind(&mut self) -> Result<()> {
+        if self.threads.is_none() {
+            self.threads = Some(ThreadReservation::reserve(self.config.num_workers() + 1)?);
+        }
+        if self.listener.is_none() {
+            self.listener = Some(TcpListener::bind(&self.address)?);
+        }
+        Ok(())
+    }
+
+    /// Returns a handle to stop the server. The server must be bound.
+    pub fn stop_handle(&self) -> Result<ServerStopHandle> {
+        let listener = self
+            .listener
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("The server is not bound yet"))?;
+        let mut local_addr = listener.local_addr()?;
+        // Connecting to the unspecified address is not portable, so wake up via loopback.
+        if local_addr.ip().is_unspecified() {
+            local_addr.set_ip(Ipv4Addr::LOCALHOST.into());
+        }
+
+        Ok(ServerStopHandle {
+            local_addr,
+            is_stopped: self.is_stopped.clone(),
+        })
+    }
+
+    /// Accept connections and serve them on the worker threads.
+    pub fn run<H: RequestHandler + Clone + Send + 'static>(&mut self, handler: H) -> Result<()> {
+        self.bind()?;
+        let listener = self.listener.take().expect("bound above");
+        let threads = self.threads.take().expect("reserved above");
+        let res = self.serve(listener, threads, handler);
+        // Marked as stopped even on errors so that the owner can tell the server is gone.
+        self.is_stopped.store(true, Ordering::SeqCst);
+
+        res
     }
 
-    pub fn run<H: RequestHandler + Clone>(&mut self, handler: H) -> Result<()> {
-        let listener = std::net::TcpListener::bind(&self.address)?;
+    fn serve<H: RequestHandler + Clone + Send + 'static>(
+        &self,
+        listener: TcpListener,
+        mut threads: ThreadReservation,
+        handler: H,
+    ) -> Result<()> {
+        let (sender, receiver) = mpsc::channel::<TcpStream>();
+        let receiver = Arc::new(SgxMutex::new(receiver));
+        let tls = Arc::new(self.config.tls().clone());
+        for _ in 0..self.config.num_workers() {
+            let worker = Worker {
+                _thread: threads.split_one(),
+                receiver: receiver.clone(),
+                tls: tls.clone(),
+                max_frame_len: self.config.max_frame_len(),
+                idle_timeout: self.config.idle_timeout(),
+            };
+            let handler = handler.clone();
+            thread::spawn(move || worker.run(handler));
+        }
 
         #[cfg(not(test))]
-        for stream in listener.incoming() {
-            let session = rustls::ServerSession::new(&Arc::new(self.config.tls().clone()));
-            match Connection::new(session, stream?).serve_json(handler.clone()) {
-                Ok(_) => {}
-                Err(e) => error!("{:?}", e),
+        let incoming = listener.incoming();
+        #[cfg(test)]
+        let incoming = listener.incoming().take(1);
+
+        for stream in incoming {
+            if self.is_stopped.load(Ordering::SeqCst) {
+                break;
+            }
+            match stream {
+                Ok(stream) => {
+                    if sender.send(stream).is_err() {
+                        return Err(anyhow::anyhow!("All workers of the server exited").into());
+                    }
+                }
+                Err(e) => error!("Failed to accept a connection: {:?}", e),
             }
         }
 
-        #[cfg(test)]
-        for stream in listener.incoming().take(1) {
-            let session = rustls::ServerSession::new(&Arc::new(self.config.tls().clone()));
-            match Connection::new(session, stream?).serve_json(handler.clone()) {
-                Ok(_) => {}
-                Err(e) => error!("{:?}", e),
+        // Dropping the sender lets the workers exit after their current sessions.
+        Ok(())
+    }
+}
+
+/// A worker thread serving keep-alive sessions one by one
+struct Worker {
+    // Released when the worker exits
+    _thread: ThreadReservation,
+    receiver: Arc<SgxMutex<mpsc::Receiver<TcpStream>>>,
+    tls: Arc<rustls::ServerConfig>,
+    max_frame_len: u64,
+    idle_timeout: std::time::Duration,
+}
+
+impl Worker {
+    fn run<H: RequestHandler>(self, handler: H) {
+        loop {
+            let stream = match self.receiver.lock() {
+                Ok(receiver) => receiver.recv(),
+                Err(_) => return,
+            };
+            let stream = match stream {
+                Ok(stream) => stream,
+                // The server has stopped.
+                Err(_) => return,
+            };
+
+            if let Err(e) = self.serve_session(stream, &handler) {
+                error!("{:?}", e);
             }
         }
+    }
+
+    fn serve_session<H: RequestHandler>(&self, stream: TcpStream, handler: &H) -> Result<()> {
+        stream.set_read_timeout(Some(self.idle_timeout))?;
+        let session = rustls::ServerSession::new(&self.tls);
+        Connection::new(session, stream, self.max_frame_len).serve_json(handler)?;
 
         Ok(())
     }
 }
+
+/// Enclave threads reserved from the budget until dropped
+#[derive(Debug)]
+struct ThreadReservation(usize);
+
+impl ThreadReservation {
+    fn reserve(num_threads: usize) -> Result<Self> {
+        let mut reserved = RESERVED_THREADS.load(Ordering::SeqCst);
+        loop {
+            if reserved + num_threads > SERVER_THREAD_BUDGET {
+                return Err(anyhow::anyhow!(
+                    "The server needs {} threads, but only {} of {} enclave threads are left for servers. \
+                    Decrease the number of workers or increase TCSNum and ENCLAVE_TCS_NUM.",
+                    num_threads,
+                    SERVER_THREAD_BUDGET - reserved,
+                    SERVER_THREAD_BUDGET,
+                )
+                .into());
+            }
+            match RESERVED_THREADS.compare_exchange(
+                reserved,
+                reserved + num_threads,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            ) {
+                Ok(_) => return Ok(ThreadReservation(num_threads)),
+                Err(current) => reserved = current,
+            }
+        }
+    }
+
+    fn split_one(&mut self) -> ThreadReservation {
+        assert!(self.0 > 1, "The thread running the server must be kept");
+        self.0 -= 1;
+        ThreadReservation(1)
+    }
+}
+
+impl Drop for ThreadReservation {
+    fn drop(&mut self) {
+        RESERVED_THREADS.fetch_sub(self.0, Ordering::SeqCst);
+    }
+}
+
+/// A handle to stop the running server from another thread.
+#[derive(Debug, Clone)]
+pub struct ServerStopHandle {
+    local_addr: SocketAddr,
+    is_stopped: Arc<AtomicBool>,
+}
+
+impl ServerStopHandle {
+    /// Stop accepting new connections. The connection being served is completed first.
+    pub fn stop(&self) {
+        if !self.is_stopped.swap(true, Ordering::SeqCst) {
+            // The accept loop is blocking, so connect to the server to make it check the flag.
+            let _ = TcpStream::connect(self.local_addr);
+        }
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.is_stopped.load(Ordering::SeqCst)
+    }
+}
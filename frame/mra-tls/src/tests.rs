@@ -1,7 +1,11 @@
-use crate::{AttestedTlsConfig, Client, ClientConfig, RequestHandler, Server, ServerConfig};
-use anonify_config::IAS_ROOT_CERT;
-use anyhow::Result;
+use crate::{
+    AttestedTlsConfig, Client, ClientConfig, EnclaveMeasurements, RequestHandler, Router, Server,
+    ServerConfig,
+};
+use anonify_config::{ENCLAVE_TCS_NUM, IAS_ROOT_CERT};
+use anyhow::{anyhow, Result};
 use once_cell::sync::Lazy;
+use remote_attestation::Epid;
 use serde_json::Value;
 use std::{
     env,
@@ -17,11 +21,30 @@ static SERVER_ADDRESS: Lazy<String> = Lazy::new(|| {
     format!("{}:12345", host)
 });
 const LISTEN_ADDRESS: &str = "0.0.0.0:12345";
+static ROUTER_SERVER_ADDRESS: Lazy<String> = Lazy::new(|| {
+    let host = env::var("HOSTNAME").expect("failed to get env 'HOSTNAME'");
+    format!("{}:12346", host)
+});
+const ROUTER_LISTEN_ADDRESS: &str = "0.0.0.0:12346";
 
 pub fn run_tests() -> bool {
     check_all_passed!(
-        run_tests!(test_request_response,),
+        run_tests!(
+            test_request_response,
+            test_keep_alive_routing,
+            test_reject_exceeding_threads,
+        ),
         crate::key::tests::run_tests(),
+        crate::policy::tests::run_tests(),
+    )
+}
+
+fn epid() -> Epid {
+    Epid::new(
+        env::var("SPID").unwrap(),
+        env::var("IAS_URL").unwrap(),
+        env::var("SUB_KEY").unwrap(),
+        IAS_ROOT_CERT.to_vec(),
     )
 }
 
@@ -29,7 +52,7 @@ pub fn run_tests() -> bool {
 struct EchoHandler;
 
 impl RequestHandler for EchoHandler {
-    fn handle_json(&self, msg: &[u8]) -> Result<Vec<u8>> {
+    fn handle_json(&self, _peer: &EnclaveMeasurements, msg: &[u8]) -> Result<Vec<u8>> {
         let msg_json: Value = serde_json::from_slice(&msg)?;
         serde_json::to_vec(&msg_json).map_err(Into::into)
     }
@@ -37,14 +60,13 @@ impl RequestHandler for EchoHandler {
 
 fn test_request_response() {
     set_env_vars();
-    let spid = env::var("SPID").unwrap();
-    let ias_url = env::var("IAS_URL").unwrap();
-    let sub_key = env::var("SUB_KEY").unwrap();
-
-    let attested_tls_config =
-        AttestedTlsConfig::new_by_ra(&spid, &ias_url, &sub_key, IAS_ROOT_CERT.to_vec()).unwrap();
+    let attested_tls_config = AttestedTlsConfig::new_by_ra(&epid()).unwrap();
 
-    start_server(attested_tls_config.clone());
+    start_server(
+        attested_tls_config.clone(),
+        LISTEN_ADDRESS,
+        EchoHandler::default(),
+    );
 
     let client_config = ClientConfig::from_attested_tls_config(attested_tls_config)
         .unwrap()
@@ -59,13 +81,53 @@ fn test_request_response() {
     assert_eq!(msg, resp);
 }
 
-fn start_server(attested_tls_config: AttestedTlsConfig) {
+fn test_keep_alive_routing() {
+    set_env_vars();
+    let attested_tls_config = AttestedTlsConfig::new_by_ra(&epid()).unwrap();
+
+    let router = Router::new()
+        .route("add", |(a, b): (u64, u64)| Ok(a + b))
+        .route("fail", |_: Value| -> Result<Value> {
+            Err(anyhow!("always fails"))
+        })
+        .route_with_peer("peer", |peer: &EnclaveMeasurements, _: Value| {
+            Ok(peer.mr_enclave)
+        });
+    start_server(attested_tls_config.clone(), ROUTER_LISTEN_ADDRESS, router);
+
+    let client_config = ClientConfig::from_attested_tls_config(attested_tls_config)
+        .unwrap()
+        .set_attestation_report_verifier(IAS_ROOT_CERT.to_vec());
+    let mut client = Client::new(&*ROUTER_SERVER_ADDRESS, client_config).unwrap();
+
+    // Many requests are served over the same session, even after a failed one.
+    let sum: u64 = client.request("add", (1u64, 2u64)).unwrap();
+    assert_eq!(sum, 3);
+    assert!(client.request::<_, Value>("fail", ()).is_err());
+    assert!(client.request::<_, Value>("unknown", ()).is_err());
+    let sum: u64 = client.request("add", (3u64, 4u64)).unwrap();
+    assert_eq!(sum, 7);
+    // The client is this enclave itself.
+    let peer: [u8; 32] = client.request("peer", ()).unwrap();
+    assert_eq!(peer, sgx_tse::rsgx_self_report().body.mr_enclave.m);
+}
+
+fn test_reject_exceeding_threads() {
+    let server_config = ServerConfig::default().set_num_workers(ENCLAVE_TCS_NUM);
+    let mut server = Server::new("0.0.0.0:12347".to_string(), server_config);
+    assert!(server.bind().is_err());
+}
+
+fn start_server<H: RequestHandler + Clone + Send + 'static>(
+    attested_tls_config: AttestedTlsConfig,
+    listen_address: &str,
+    handler: H,
+) {
     let server_config = ServerConfig::from_attested_tls_config(attested_tls_config)
         .unwrap()
         .set_attestation_report_verifier(IAS_ROOT_CERT.to_vec());
 
-    let mut server = Server::new(LISTEN_ADDRESS.to_string(), server_config);
-    let handler = EchoHandler::default();
+    let mut server = Server::new(listen_address.to_string(), server_config);
     thread::spawn(move || server.run(handler).unwrap());
     thread::sleep(Duration::from_secs(1));
 }
@@ -1,47 +1,32 @@
 use crate::cert::*;
 use crate::error::{MraTLSError, Result};
+use crate::policy::{AttestationPolicy, EnclaveMeasurements};
 use anyhow::anyhow;
-use remote_attestation::AttestedReport;
-use std::io::{Cursor, Read};
+use remote_attestation::AttestedEvidence;
 use std::vec::Vec;
 
 #[derive(Clone, Debug)]
 pub struct AttestedReportVerifier {
     root_cert: Vec<u8>,
+    policy: AttestationPolicy,
 }
 
 impl AttestedReportVerifier {
-    pub fn new(root_cert: Vec<u8>) -> Self {
-        Self { root_cert }
+    pub fn new(root_cert: Vec<u8>, policy: AttestationPolicy) -> Self {
+        Self { root_cert, policy }
     }
 
     fn verify_cert(&self, ee_cert: &[u8]) -> Result<()> {
-        // Parse DER formatted x.509 end entity certificate
-        let x509 = yasna::parse_der(&ee_cert, X509::load)?;
-        // Extract tbs (To Be Signed) Certificate
-        let tbs_cert: <TbsCert as Asn1Ty>::ValueTy = x509.0;
-        let pubkey: <PubKey as Asn1Ty>::ValueTy = ((((((tbs_cert.1).1).1).1).1).1).0;
-        let cert_ext: <SgxRaCertExt as Asn1Ty>::ValueTy = (((((((tbs_cert.1).1).1).1).1).1).1).0;
-        let cert_ext_payload: Vec<u8> = ((cert_ext.0).1).0;
+        let (pubkey, attested_evidence) = parse_cert(ee_cert)?;
 
-        // Verify the deserialized attested_report which is included in extension field of X.509 cert
-        let attested_report = serde_json::from_slice::<AttestedReport>(&cert_ext_payload)?
-            .verify_attested_report(self.root_cert.to_vec())?;
+        // Verify the deserialized attested evidence which is included in extension field of X.509 cert
+        attested_evidence.verify_signature(self.root_cert.to_vec())?;
+        self.policy.verify_evidence_status(&attested_evidence)?;
 
-        let mut quote = Cursor::new(attested_report.get_quote_body()?);
-        let mut mr_enclave = [0u8; 32];
-        let mut mr_signer = [0u8; 32];
-        let mut report_data = [0u8; 64];
-
-        // Offsets are defined in "Attestation Service for Intel® Software Guard Extensions (Intel® SGX): API Documentation version 6.0"
-        quote.set_position(112);
-        quote.read_exact(&mut mr_enclave)?;
-        quote.set_position(176);
-        quote.read_exact(&mut mr_signer)?;
-        quote.set_position(368);
-        quote.read_exact(&mut report_data)?;
-
-        Self::verify_pubkey_eq(pubkey, report_data)?;
+        let measurements =
+            EnclaveMeasurements::from_quote_body(attested_evidence.get_quote_body()?)?;
+        self.policy.verify_measurements(&measurements)?;
+        Self::verify_pubkey_eq(pubkey, measurements.report_data)?;
 
         Ok(())
     }
@@ -57,11 +42,26 @@ impl AttestedReportVerifier {
 
         Ok(())
     }
+}
 
-    fn verify_measurements(&self) -> bool {
-        // TODO
-        true
-    }
+/// Read the measurements of the peer from its end entity certificate.
+/// The certificate must have been verified in the handshake.
+pub(crate) fn peer_measurements(ee_cert: &[u8]) -> Result<EnclaveMeasurements> {
+    let (_, attested_evidence) = parse_cert(ee_cert)?;
+    EnclaveMeasurements::from_quote_body(attested_evidence.get_quote_body()?)
+}
+
+fn parse_cert(ee_cert: &[u8]) -> Result<(<PubKey as Asn1Ty>::ValueTy, AttestedEvidence)> {
+    // Parse DER formatted x.509 end entity certificate
+    let x509 = yasna::parse_der(&ee_cert, X509::load)?;
+    // Extract tbs (To Be Signed) Certificate
+    let tbs_cert: <TbsCert as Asn1Ty>::ValueTy = x509.0;
+    let pubkey: <PubKey as Asn1Ty>::ValueTy = ((((((tbs_cert.1).1).1).1).1).1).0;
+    let cert_ext: <SgxRaCertExt as Asn1Ty>::ValueTy = (((((((tbs_cert.1).1).1).1).1).1).1).0;
+    let cert_ext_payload: Vec<u8> = ((cert_ext.0).1).0;
+    let attested_evidence = serde_json::from_slice::<AttestedEvidence>(&cert_ext_payload)?;
+
+    Ok((pubkey, attested_evidence))
 }
 
 impl rustls::ClientCertVerifier for AttestedReportVerifier {
@@ -0,0 +1,140 @@
+use crate::client::AttestedReport;
+use crate::dcap::DcapQuote;
+use crate::error::Result;
+use crate::quote::QuoteTarget;
+use crate::report::QuoteStatusPolicy;
+use anyhow::Result as AnyhowResult;
+use serde::{Deserialize, Serialize};
+use sgx_types::sgx_report_data_t;
+use std::{fmt, string::String, vec::Vec};
+
+/// A remote attestation scheme proving to remote parties that
+/// the report data is generated in a genuine enclave.
+pub trait Attestation: fmt::Debug + Send + Sync {
+    /// Attest the enclave with the report data and return the evidence verified by the root certificate.
+    fn attest(&self, report_data: &sgx_report_data_t) -> Result<AttestedEvidence>;
+
+    /// The DER-encoded root certificate which the evidence is verified by.
+    fn root_cert(&self) -> &[u8];
+}
+
+/// Evidence of a remote attestation which can be verified by remote parties
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AttestedEvidence {
+    /// A report signed by IAS
+    Epid(AttestedReport),
+    /// An ECDSA quote with its collateral
+    Dcap(DcapQuote),
+}
+
+impl AttestedEvidence {
+    /// Verify the evidence is issued under the root certificate
+    /// which is IAS's one for EPID or Intel SGX Root CA for DCAP.
+    pub fn verify_signature(&self, root_cert: Vec<u8>) -> AnyhowResult<()> {
+        match self {
+            AttestedEvidence::Epid(report) => report.verify_report_signature(root_cert),
+            AttestedEvidence::Dcap(quote) => quote.verify_signature(root_cert),
+        }
+    }
+
+    /// The quote status in IAS's terms so that the same policy applies to both schemes.
+    pub fn quote_status(&self) -> AnyhowResult<String> {
+        match self {
+            AttestedEvidence::Epid(report) => report.quote_status(),
+            AttestedEvidence::Dcap(quote) => quote.quote_status(),
+        }
+    }
+
+    /// Verify the quote status by the policy.
+    /// Advisories and the report's age are checked only for IAS reports.
+    pub fn verify_quote_status(&self, policy: &QuoteStatusPolicy) -> AnyhowResult<()> {
+        match self {
+            AttestedEvidence::Epid(report) => policy.verify(&report.verification_report()?),
+            AttestedEvidence::Dcap(quote) => {
+                policy.verify_quote_status(&quote.quote_status()?, &[])
+            }
+        }
+    }
+
+    /// Both of EPID and DCAP v3 quotes begin with a 48 bytes header followed by the enclave's report body,
+    /// so the measurements are read at the same offsets.
+    pub fn get_quote_body(&self) -> AnyhowResult<Vec<u8>> {
+        match self {
+            AttestedEvidence::Epid(report) => report.get_quote_body(),
+            AttestedEvidence::Dcap(quote) => Ok(quote.quote().to_vec()),
+        }
+    }
+
+    /// The report registered to the contract.
+    /// Only IAS reports can be verified on the contract for now,
+    /// and the empty signature of DCAP evidence makes the host refuse to send it.
+    pub fn report(&self) -> &[u8] {
+        match self {
+            AttestedEvidence::Epid(report) => report.report(),
+            AttestedEvidence::Dcap(quote) => quote.quote(),
+        }
+    }
+
+    pub fn report_sig(&self) -> &[u8] {
+        match self {
+            AttestedEvidence::Epid(report) => report.report_sig(),
+            AttestedEvidence::Dcap(_) => &[],
+        }
+    }
+
+    /// The DER-encoded certificate of the IAS report signing key
+    pub fn report_cert(&self) -> &[u8] {
+        match self {
+            AttestedEvidence::Epid(report) => report.report_cert(),
+            AttestedEvidence::Dcap(_) => &[],
+        }
+    }
+}
+
+/// EPID attestation whose quote is verified by IAS
+#[derive(Debug, Clone)]
+pub struct Epid {
+    spid: String,
+    ias_url: String,
+    sub_key: String,
+    root_cert: Vec<u8>,
+    quote_status_policy: QuoteStatusPolicy,
+}
+
+impl Epid {
+    pub fn new(spid: String, ias_url: String, sub_key: String, root_cert: Vec<u8>) -> Self {
+        Epid {
+            spid,
+            ias_url,
+            sub_key,
+            root_cert,
+            quote_status_policy: QuoteStatusPolicy::default(),
+        }
+    }
+
+    /// Replace the policy which IAS's report for this enclave is verified by.
+    pub fn set_quote_status_policy(mut self, policy: QuoteStatusPolicy) -> Self {
+        self.quote_status_policy = policy;
+        self
+    }
+}
+
+impl Attestation for Epid {
+    fn attest(&self, report_data: &sgx_report_data_t) -> Result<AttestedEvidence> {
+        let attested_report = QuoteTarget::new()?
+            .set_enclave_report(report_data)?
+            .create_quote(&self.spid)?
+            .remote_attestation(
+                &self.ias_url,
+                &self.sub_key,
+                self.root_cert.clone(),
+                &self.quote_status_policy,
+            )?;
+
+        Ok(AttestedEvidence::Epid(attested_report))
+    }
+
+    fn root_cert(&self) -> &[u8] {
+        &self.root_cert
+    }
+}
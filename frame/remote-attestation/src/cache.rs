@@ -0,0 +1,415 @@
+use crate::attestation::{Attestation, AttestedEvidence};
+use crate::error::Result;
+use anyhow::anyhow;
+use log::warn;
+use once_cell::sync::Lazy;
+use sgx_types::sgx_report_data_t;
+use std::{
+    boxed::Box,
+    collections::BTreeMap,
+    fmt,
+    string::String,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, SgxMutex, SgxRwLock,
+    },
+    thread,
+    time::{Duration, SystemTime},
+    vec::Vec,
+};
+
+/// The number of report data whose evidence is cached,
+/// e.g. the identity key and the TLS key for the key-vaults.
+const MAX_CACHED_ENTRIES: usize = 4;
+
+type RefreshJob = Box<dyn FnOnce() + Send>;
+/// The clock the age of the evidence is measured by
+type Clock = Arc<dyn Fn() -> SystemTime + Send + Sync>;
+
+/// The single worker thread refreshing the evidence of all the caches in the enclave,
+/// so that refreshes use only one of the enclave threads.
+/// It is spawned on the first refresh.
+static REFRESH_WORKER: Lazy<SgxMutex<Option<mpsc::Sender<RefreshJob>>>> =
+    Lazy::new(|| SgxMutex::new(None));
+
+/// An attestation scheme caching the evidence per report data until it gets older than `max_age`,
+/// so that IAS or PCCS is not requested on every registration, join and backup.
+/// Once the evidence passes half of `max_age`, it is refreshed on the background worker
+/// while the cached one is still returned.
+#[derive(Clone)]
+pub struct CachedAttestation {
+    inner: Arc<Inner>,
+}
+
+struct Inner {
+    attestation: Arc<dyn Attestation>,
+    max_age: Duration,
+    entries: SgxRwLock<BTreeMap<Vec<u8>, CachedEvidence>>,
+    is_refreshing: AtomicBool,
+    clock: Clock,
+    // Runs the refresh jobs, which is the shared worker except in tests
+    run_job: fn(RefreshJob) -> Result<()>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedEvidence {
+    evidence: AttestedEvidence,
+    attested_at: SystemTime,
+}
+
+/// The status of the cached evidence
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttestationStatus {
+    pub age: Duration,
+    pub max_age: Duration,
+    pub quote_status: String,
+    pub is_refreshing: bool,
+}
+
+impl CachedAttestation {
+    pub fn new(attestation: Arc<dyn Attestation>, max_age: Duration) -> Self {
+        Self::with_clock(
+            attestation,
+            max_age,
+            Arc::new(SystemTime::now),
+            send_to_worker,
+        )
+    }
+
+    fn with_clock(
+        attestation: Arc<dyn Attestation>,
+        max_age: Duration,
+        clock: Clock,
+        run_job: fn(RefreshJob) -> Result<()>,
+    ) -> Self {
+        CachedAttestation {
+            inner: Arc::new(Inner {
+                attestation,
+                max_age,
+                entries: SgxRwLock::new(BTreeMap::new()),
+                is_refreshing: AtomicBool::new(false),
+                clock,
+                run_job,
+            }),
+        }
+    }
+
+    /// The status of the evidence cached for the report data, or `None` if it has not been attested yet.
+    pub fn status(&self, report_data: &sgx_report_data_t) -> Result<Option<AttestationStatus>> {
+        let entries = self.inner.entries.read().unwrap();
+        let cached = match entries.get(&report_data.d[..]) {
+            Some(cached) => cached,
+            None => return Ok(None),
+        };
+
+        Ok(Some(AttestationStatus {
+            age: cached.age((self.inner.clock)()),
+            max_age: self.inner.max_age,
+            quote_status: cached.evidence.quote_status()?,
+            is_refreshing: self.inner.is_refreshing.load(Ordering::SeqCst),
+        }))
+    }
+
+    fn refresh_in_background(&self, report_data: &sgx_report_data_t) {
+        if self.inner.is_refreshing.swap(true, Ordering::SeqCst) {
+            return;
+        }
+
+        let inner = self.inner.clone();
+        let report_data = *report_data;
+        let job: RefreshJob = Box::new(move || {
+            if let Err(e) = inner.refresh(&report_data) {
+                warn!("Failed to refresh the attested evidence: {:?}", e);
+            }
+            inner.is_refreshing.store(false, Ordering::SeqCst);
+        });
+        if let Err(e) = (self.inner.run_job)(job) {
+            warn!("Failed to refresh the attested evidence: {:?}", e);
+            self.inner.is_refreshing.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Send the job to the refresh worker, spawning it if it is not running yet.
+fn send_to_worker(job: RefreshJob) -> Result<()> {
+    let mut worker = REFRESH_WORKER
+        .lock()
+        .map_err(|e| anyhow!("Failed to lock the refresh worker: {:?}", e))?;
+    let job = match worker.as_ref() {
+        Some(sender) => match sender.send(job) {
+            Ok(()) => return Ok(()),
+            // The worker has exited, e.g. by a panic in a job.
+            Err(mpsc::SendError(job)) => job,
+        },
+        None => job,
+    };
+
+    let (sender, receiver) = mpsc::channel::<RefreshJob>();
+    thread::Builder::new()
+        .spawn(move || {
+            for job in receiver {
+                job();
+            }
+        })
+        .map_err(|e| anyhow!("Failed to spawn the refresh worker: {:?}", e))?;
+    sender
+        .send(job)
+        .map_err(|_| anyhow!("The refresh worker exited"))?;
+    *worker = Some(sender);
+
+    Ok(())
+}
+
+impl Inner {
+    fn get(&self, report_data: &sgx_report_data_t) -> Option<CachedEvidence> {
+        self.entries
+            .read()
+            .unwrap()
+            .get(&report_data.d[..])
+            .cloned()
+    }
+
+    fn refresh(&self, report_data: &sgx_report_data_t) -> Result<AttestedEvidence> {
+        let evidence = self.attestation.attest(report_data)?;
+
+        let mut entries = self.entries.write().unwrap();
+        entries.insert(
+            report_data.d.to_vec(),
+            CachedEvidence {
+                evidence: evidence.clone(),
+                attested_at: (self.clock)(),
+            },
+        );
+        while entries.len() > MAX_CACHED_ENTRIES {
+            let oldest = entries
+                .iter()
+                .min_by_key(|(_, cached)| cached.attested_at)
+                .map(|(key, _)| key.clone())
+                .ok_or_else(|| anyhow!("The attestation cache is empty"))?;
+            entries.remove(&oldest);
+        }
+
+        Ok(evidence)
+    }
+}
+
+impl CachedEvidence {
+    fn age(&self, now: SystemTime) -> Duration {
+        // Treat the evidence as fresh if the untrusted clock goes backwards.
+        now.duration_since(self.attested_at).unwrap_or_default()
+    }
+}
+
+impl Attestation for CachedAttestation {
+    fn attest(&self, report_data: &sgx_report_data_t) -> Result<AttestedEvidence> {
+        if let Some(cached) = self.inner.get(report_data) {
+            let age = cached.age((self.inner.clock)());
+            if age < self.inner.max_age {
+                if age >= self.inner.max_age / 2 {
+                    self.refresh_in_background(report_data);
+                }
+                return Ok(cached.evidence);
+            }
+        }
+
+        self.inner.refresh(report_data)
+    }
+
+    fn root_cert(&self) -> &[u8] {
+        self.inner.attestation.root_cert()
+    }
+}
+
+impl fmt::Debug for CachedAttestation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CachedAttestation")
+            .field("attestation", &self.inner.attestation)
+            .field("max_age", &self.inner.max_age)
+            .finish()
+    }
+}
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::client::AttestedReport;
+    use crate::report::{VerificationReport, QUOTE_STATUS_OK};
+    use std::{
+        format,
+        sync::atomic::{AtomicU64, AtomicUsize},
+        time::UNIX_EPOCH,
+        vec,
+    };
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_cached_until_half_of_max_age,
+            test_refresh_in_background,
+            test_reattest_after_max_age,
+            test_evict_oldest_entry,
+            test_refresh_worker,
+        )
+    }
+
+    /// An attestation scheme numbering the evidence in the report's id
+    #[derive(Debug, Default)]
+    struct CountingAttestation {
+        count: AtomicUsize,
+    }
+
+    impl CountingAttestation {
+        fn count(&self) -> usize {
+            self.count.load(Ordering::SeqCst)
+        }
+    }
+
+    impl Attestation for CountingAttestation {
+        fn attest(&self, _report_data: &sgx_report_data_t) -> Result<AttestedEvidence> {
+            let count = self.count.fetch_add(1, Ordering::SeqCst) + 1;
+            let report = serde_json::to_vec(&serde_json::json!({
+                "id": format!("{}", count),
+                "timestamp": "2020-09-02T06:30:21.024131",
+                "version": 4,
+                "isvEnclaveQuoteStatus": QUOTE_STATUS_OK,
+                "isvEnclaveQuoteBody": "",
+            }))
+            .unwrap();
+            let report: AttestedReport = serde_json::from_value(serde_json::json!({
+                "report": report,
+                "report_sig": vec![0u8; 1],
+                "report_cert": vec![0u8; 1],
+            }))
+            .unwrap();
+
+            Ok(AttestedEvidence::Epid(report))
+        }
+
+        fn root_cert(&self) -> &[u8] {
+            &[]
+        }
+    }
+
+    /// A clock advanced only by the test
+    #[derive(Debug, Clone, Default)]
+    struct TestClock {
+        elapsed_secs: Arc<AtomicU64>,
+    }
+
+    impl TestClock {
+        fn now(&self) -> SystemTime {
+            UNIX_EPOCH + Duration::from_secs(self.elapsed_secs.load(Ordering::SeqCst))
+        }
+
+        fn advance(&self, secs: u64) {
+            self.elapsed_secs.fetch_add(secs, Ordering::SeqCst);
+        }
+    }
+
+    /// Run the refresh job on the calling thread, so that it has finished when the attestation returns.
+    fn run_inline(job: RefreshJob) -> Result<()> {
+        job();
+        Ok(())
+    }
+
+    fn report_id(evidence: &AttestedEvidence) -> String {
+        VerificationReport::from_slice(evidence.report())
+            .unwrap()
+            .id
+    }
+
+    fn report_data(n: u8) -> sgx_report_data_t {
+        let mut report_data = sgx_report_data_t::default();
+        report_data.d[0] = n;
+        report_data
+    }
+
+    fn cache(max_age_secs: u64) -> (Arc<CountingAttestation>, CachedAttestation, TestClock) {
+        let attestation = Arc::new(CountingAttestation::default());
+        let clock = TestClock::default();
+        let now = clock.clone();
+        let cache = CachedAttestation::with_clock(
+            attestation.clone(),
+            Duration::from_secs(max_age_secs),
+            Arc::new(move || now.now()),
+            run_inline,
+        );
+        (attestation, cache, clock)
+    }
+
+    fn test_cached_until_half_of_max_age() {
+        let (attestation, cache, clock) = cache(60);
+        let report_data = report_data(0);
+        assert_eq!(cache.status(&report_data).unwrap(), None);
+
+        let first = cache.attest(&report_data).unwrap();
+        clock.advance(29);
+        let second = cache.attest(&report_data).unwrap();
+        assert_eq!(attestation.count(), 1);
+        assert_eq!(report_id(&first), report_id(&second));
+
+        let status = cache.status(&report_data).unwrap().unwrap();
+        assert_eq!(status.age, Duration::from_secs(29));
+        assert_eq!(status.quote_status, QUOTE_STATUS_OK);
+        assert!(!status.is_refreshing);
+    }
+
+    fn test_refresh_in_background() {
+        let (attestation, cache, clock) = cache(60);
+        let report_data = report_data(0);
+        cache.attest(&report_data).unwrap();
+
+        clock.advance(30);
+        // The cached evidence is still returned while it is refreshed.
+        let evidence = cache.attest(&report_data).unwrap();
+        assert_eq!(report_id(&evidence), "1");
+        assert_eq!(attestation.count(), 2);
+        let status = cache.status(&report_data).unwrap().unwrap();
+        assert_eq!(status.age, Duration::from_secs(0));
+        assert!(!status.is_refreshing);
+
+        let evidence = cache.attest(&report_data).unwrap();
+        assert_eq!(report_id(&evidence), "2");
+        assert_eq!(attestation.count(), 2);
+    }
+
+    fn test_reattest_after_max_age() {
+        let (attestation, cache, clock) = cache(60);
+        let report_data = report_data(0);
+        cache.attest(&report_data).unwrap();
+
+        clock.advance(60);
+        let evidence = cache.attest(&report_data).unwrap();
+        assert_eq!(report_id(&evidence), "2");
+        assert_eq!(attestation.count(), 2);
+        assert_eq!(
+            cache.status(&report_data).unwrap().unwrap().age,
+            Duration::from_secs(0)
+        );
+    }
+
+    fn test_evict_oldest_entry() {
+        let (attestation, cache, clock) = cache(60);
+        for n in 0..=MAX_CACHED_ENTRIES as u8 {
+            cache.attest(&report_data(n)).unwrap();
+            clock.advance(1);
+        }
+        assert_eq!(attestation.count(), MAX_CACHED_ENTRIES + 1);
+
+        assert_eq!(cache.status(&report_data(0)).unwrap(), None);
+        for n in 1..=MAX_CACHED_ENTRIES as u8 {
+            assert!(cache.status(&report_data(n)).unwrap().is_some());
+        }
+    }
+
+    fn test_refresh_worker() {
+        // The jobs are run in order on the worker, which is spawned once.
+        let (sender, receiver) = mpsc::channel();
+        for n in 0..2 {
+            let sender = sender.clone();
+            send_to_worker(Box::new(move || sender.send(n).unwrap())).unwrap();
+        }
+        assert_eq!(receiver.recv().unwrap(), 0);
+        assert_eq!(receiver.recv().unwrap(), 1);
+    }
+}
@@ -1,4 +1,5 @@
-use anyhow::{anyhow, bail, ensure, Result};
+use crate::report::{QuoteStatusPolicy, VerificationReport};
+use anyhow::{anyhow, ensure, Result};
 use http_req::{
     request::{Method, Request},
     response::{Headers, Response},
@@ -6,11 +7,10 @@ use http_req::{
 };
 use log::debug;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use std::{io::Write, prelude::v1::*, str, string::String, time::SystemTime};
 
 type SignatureAlgorithms = &'static [&'static webpki::SignatureAlgorithm];
-static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
+pub(crate) static SUPPORTED_SIG_ALGS: SignatureAlgorithms = &[
     &webpki::ECDSA_P256_SHA256,
     &webpki::ECDSA_P256_SHA384,
     &webpki::ECDSA_P384_SHA256,
@@ -109,9 +109,23 @@ impl AttestedReport {
     /// 1. TLS server certificate
     /// 2. report's signature
     /// 3. report's version
-    /// 4. quote status
-    #[must_use]
-    pub fn verify_attested_report(self, root_cert: Vec<u8>) -> Result<Self> {
+    /// 4. quote status, advisories and report's age by the policy
+    /// and then return the parsed report.
+    pub fn verify_attested_report(
+        &self,
+        root_cert: Vec<u8>,
+        policy: &QuoteStatusPolicy,
+    ) -> Result<VerificationReport> {
+        self.verify_report_signature(root_cert)?;
+        let report = self.verification_report()?;
+        policy.verify(&report)?;
+
+        Ok(report)
+    }
+
+    /// Verify the TLS server certificate, the report's signature and the report's version,
+    /// leaving the quote status to be checked by the caller's own policy.
+    pub fn verify_report_signature(&self, root_cert: Vec<u8>) -> Result<()> {
         let now_func = webpki::Time::try_from(SystemTime::now())?;
 
         let mut root_store = rustls::RootCertStore::empty();
@@ -141,19 +155,20 @@ impl AttestedReport {
             &self.report_sig,
         )?;
 
-        let report = serde_json::from_slice(&self.report)?;
-        Self::verify_version(&report)?;
-        Self::verify_quote_status(&report)?;
+        self.verification_report()?.verify_version()
+    }
+
+    /// Parse the report. Its signature is not verified here.
+    pub fn verification_report(&self) -> Result<VerificationReport> {
+        VerificationReport::from_slice(&self.report)
+    }
 
-        Ok(self)
+    pub fn quote_status(&self) -> Result<String> {
+        Ok(self.verification_report()?.isv_enclave_quote_status)
     }
 
     pub fn get_quote_body(&self) -> Result<Vec<u8>> {
-        let report: Value = serde_json::from_slice(&self.report)?;
-        let encoded_quote = report["isvEnclaveQuoteBody"]
-            .as_str()
-            .ok_or_else(|| anyhow!("Invalid isvEnclaveQuoteBody"))?;
-        base64::decode(encoded_quote).map_err(Into::into)
+        self.verification_report()?.quote_body()
     }
 
     pub fn report(&self) -> &[u8] {
@@ -167,34 +182,6 @@ impl AttestedReport {
     pub fn report_cert(&self) -> &[u8] {
         &self.report_cert
     }
-
-    /// Verify API version is supported
-    fn verify_version(report: &Value) -> Result<()> {
-        let version = report["version"]
-            .as_u64()
-            .ok_or_else(|| anyhow!("The Remote Attestation API version is not valid"))?;
-        ensure!(
-            version == 3,
-            "The Remote Attestation API version is not supported"
-        );
-        Ok(())
-    }
-
-    /// Verify the quote status included the attestation report is OK
-    fn verify_quote_status(report: &Value) -> Result<()> {
-        if let Value::String(quote_status) = &report["isvEnclaveQuoteStatus"] {
-            match quote_status.as_ref() {
-                "OK" => Ok(()),
-                "GROUP_OUT_OF_DATE" => {
-                    println!("Enclave Quote Status: GROUP_OUT_OF_DATE");
-                    Ok(())
-                }
-                _ => bail!("Invalid Enclave Quote Status: {}", quote_status),
-            }
-        } else {
-            bail!("Failed to fetch isvEnclaveQuoteStatus from attestation report");
-        }
-    }
 }
 
 fn percent_decode(orig: String) -> Result<Vec<u8>> {
@@ -0,0 +1,792 @@
+use crate::client::SUPPORTED_SIG_ALGS;
+use anyhow::{anyhow, ensure, Result};
+use http_req::{
+    request::{Method, Request},
+    response::Headers,
+    uri::Uri,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{
+    prelude::v1::*,
+    str,
+    string::String,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const TCB_INFO_ISSUER_CHAIN_HEADER: &str = "SGX-TCB-Info-Issuer-Chain";
+const QE_IDENTITY_ISSUER_CHAIN_HEADER: &str = "SGX-Enclave-Identity-Issuer-Chain";
+const PCK_CRL_ISSUER_CHAIN_HEADER: &str = "SGX-PCK-CRL-Issuer-Chain";
+const NUM_TCB_COMPONENTS: usize = 16;
+const PLATFORM_CA_CN: &[u8] = b"Intel SGX PCK Platform CA";
+
+// DER tags
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_UTC_TIME: u8 = 0x17;
+const TAG_GENERALIZED_TIME: u8 = 0x18;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_EXPLICIT_0: u8 = 0xa0;
+
+/// The collateral to verify a quote, which are signed by Intel and served by PCCS
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuoteCollateral {
+    /// The TCB levels of the platform's FMSPC
+    tcb_info: Vec<u8>,
+    /// DER-encoded certificates of the TCB info's signing key
+    tcb_info_issuer_chain: Vec<Vec<u8>>,
+    /// The identity of the quoting enclave
+    qe_identity: Vec<u8>,
+    /// DER-encoded certificates of the QE identity's signing key
+    qe_identity_issuer_chain: Vec<Vec<u8>>,
+    /// DER-encoded CRL of the CA issuing the platform's PCK certificate
+    pck_crl: Vec<u8>,
+}
+
+impl QuoteCollateral {
+    /// Verify the collateral are signed under the root certificate and issued for the platform.
+    pub fn verify(&self, root_cert: &[u8], fmspc: &[u8]) -> Result<()> {
+        verify_signed_json(
+            &self.tcb_info,
+            "tcbInfo",
+            &self.tcb_info_issuer_chain,
+            root_cert,
+        )?;
+        verify_signed_json(
+            &self.qe_identity,
+            "enclaveIdentity",
+            &self.qe_identity_issuer_chain,
+            root_cert,
+        )?;
+
+        let tcb_fmspc = self.tcb_info()?["fmspc"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Invalid fmspc in TCB info"))?
+            .to_string();
+        ensure!(
+            hex::decode(tcb_fmspc).map_err(|e| anyhow!("{:?}", e))? == fmspc,
+            "The TCB info is not issued for the platform's FMSPC"
+        );
+
+        Ok(())
+    }
+
+    /// Verify the PCK certificate is not revoked by the CRL of its issuer.
+    /// The CRL must be signed by the issuer, the second certificate in the verified chain.
+    pub fn verify_pck_not_revoked(&self, pck_cert_chain: &[Vec<u8>]) -> Result<()> {
+        let issuer = pck_cert_chain
+            .get(1)
+            .ok_or_else(|| anyhow!("Not found the issuer of the PCK certificate"))?;
+        let crl = Crl::parse(&self.pck_crl)?;
+        verify_der_signature_by_cert(issuer, crl.signed, crl.signature)?;
+
+        if let Some(next_update) = crl.next_update {
+            ensure!(
+                SystemTime::now() <= next_update,
+                "The PCK CRL has been expired"
+            );
+        }
+        let serial = cert_serial_number(&pck_cert_chain[0])?;
+        ensure!(!crl.is_revoked(serial), "The PCK certificate is revoked");
+
+        Ok(())
+    }
+
+    /// The status of the highest TCB level which the platform's TCB components satisfy
+    pub fn tcb_status(&self, cpu_svn_components: &[u8], pce_svn: u16) -> Result<String> {
+        let tcb_info = self.tcb_info()?;
+        let tcb_levels = tcb_info["tcbLevels"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Invalid tcbLevels in TCB info"))?;
+
+        for level in tcb_levels {
+            let tcb = &level["tcb"];
+            let is_satisfied = (0..NUM_TCB_COMPONENTS).all(|i| {
+                let key = format!("sgxtcbcomp{:02}svn", i + 1);
+                tcb[key.as_str()]
+                    .as_u64()
+                    .map_or(false, |svn| u64::from(cpu_svn_components[i]) >= svn)
+            }) && tcb["pcesvn"]
+                .as_u64()
+                .map_or(false, |svn| u64::from(pce_svn) >= svn);
+
+            if is_satisfied {
+                return status_of(level);
+            }
+        }
+
+        Err(anyhow!("No TCB level is satisfied by the platform"))
+    }
+
+    /// Verify the quoting enclave's identity and returns the status of its TCB level.
+    pub fn qe_tcb_status(&self, qe_report_body: &[u8]) -> Result<String> {
+        let identity = self.qe_identity()?;
+        let hex_field = |key: &str| -> Result<Vec<u8>> {
+            let value = identity[key]
+                .as_str()
+                .ok_or_else(|| anyhow!("Invalid {} in QE identity", key))?;
+            hex::decode(value).map_err(|e| anyhow!("{:?}", e))
+        };
+
+        let misc_select = &qe_report_body[16..20];
+        let attributes = &qe_report_body[48..64];
+        let mr_signer = &qe_report_body[128..160];
+        let isv_prod_id = u16::from_le_bytes([qe_report_body[256], qe_report_body[257]]);
+        let isv_svn = u16::from_le_bytes([qe_report_body[258], qe_report_body[259]]);
+
+        ensure!(
+            masked_eq(
+                misc_select,
+                &hex_field("miscselectMask")?,
+                &hex_field("miscselect")?
+            ),
+            "The quoting enclave's MISCSELECT doesn't match its identity"
+        );
+        ensure!(
+            masked_eq(
+                attributes,
+                &hex_field("attributesMask")?,
+                &hex_field("attributes")?
+            ),
+            "The quoting enclave's attributes don't match its identity"
+        );
+        ensure!(
+            mr_signer == &hex_field("mrsigner")?[..],
+            "The quoting enclave's MRSIGNER doesn't match its identity"
+        );
+        ensure!(
+            identity["isvprodid"].as_u64() == Some(u64::from(isv_prod_id)),
+            "The quoting enclave's ISVPRODID doesn't match its identity"
+        );
+
+        let tcb_levels = identity["tcbLevels"]
+            .as_array()
+            .ok_or_else(|| anyhow!("Invalid tcbLevels in QE identity"))?;
+        for level in tcb_levels {
+            let is_satisfied = level["tcb"]["isvsvn"]
+                .as_u64()
+                .map_or(false, |svn| u64::from(isv_svn) >= svn);
+            if is_satisfied {
+                return status_of(level);
+            }
+        }
+
+        Err(anyhow!("No TCB level is satisfied by the quoting enclave"))
+    }
+
+    fn tcb_info(&self) -> Result<Value> {
+        let body: Value = serde_json::from_slice(&self.tcb_info)?;
+        Ok(body["tcbInfo"].clone())
+    }
+
+    fn qe_identity(&self) -> Result<Value> {
+        let body: Value = serde_json::from_slice(&self.qe_identity)?;
+        Ok(body["enclaveIdentity"].clone())
+    }
+}
+
+/// A client fetching the collateral from PCCS (Provisioning Certificate Caching Service)
+pub struct PccsClient<'a> {
+    url: &'a str,
+}
+
+impl<'a> PccsClient<'a> {
+    pub fn new(url: &'a str) -> Self {
+        PccsClient { url }
+    }
+
+    /// Fetch the collateral for the FMSPC and the CRL of the CA issuing the PCK certificate,
+    /// whose certificate is the second one in the PCK certificate chain.
+    pub fn get_collateral(&self, fmspc: &[u8], pck_issuer: &[u8]) -> Result<QuoteCollateral> {
+        let (tcb_info, tcb_info_issuer_chain) = self.get(
+            &format!("/sgx/certification/v3/tcb?fmspc={}", hex::encode(fmspc)),
+            TCB_INFO_ISSUER_CHAIN_HEADER,
+        )?;
+        let (qe_identity, qe_identity_issuer_chain) = self.get(
+            "/sgx/certification/v3/qe/identity",
+            QE_IDENTITY_ISSUER_CHAIN_HEADER,
+        )?;
+
+        let (pck_crl, _) = self.get(
+            &format!(
+                "/sgx/certification/v3/pckcrl?ca={}",
+                pck_ca_type(pck_issuer)
+            ),
+            PCK_CRL_ISSUER_CHAIN_HEADER,
+        )?;
+
+        Ok(QuoteCollateral {
+            tcb_info,
+            tcb_info_issuer_chain,
+            qe_identity,
+            qe_identity_issuer_chain,
+            pck_crl: crl_to_der(pck_crl)?,
+        })
+    }
+
+    fn get(&self, path: &str, issuer_chain_header: &str) -> Result<(Vec<u8>, Vec<Vec<u8>>)> {
+        let url = format!("{}{}", self.url.trim_end_matches('/'), path);
+        let uri: Uri = url
+            .parse()
+            .map_err(|e| anyhow!("Invalid PCCS url: {:?}", e))?;
+        let host = uri
+            .host_header()
+            .ok_or_else(|| anyhow!("Not found host in the PCCS url"))?;
+        let mut headers = Headers::new();
+        headers.insert("HOST", &host);
+        headers.insert("Connection", "close");
+
+        let mut body = Vec::new();
+        let response = Request::new(&uri)
+            .method(Method::GET)
+            .headers(headers)
+            .send(&mut body)
+            .map_err(|e| anyhow!("{:?}", e))?;
+        ensure!(
+            response.status_code().is_success(),
+            "PCCS responded {} for {}",
+            response.status_code(),
+            url
+        );
+
+        let issuer_chain = response
+            .headers()
+            .get(issuer_chain_header)
+            .ok_or_else(|| anyhow!("Not found {} header", issuer_chain_header))?;
+        let issuer_chain = pem_chain_to_der(&percent_decode(issuer_chain)?)?;
+
+        Ok((body, issuer_chain))
+    }
+}
+
+/// Verify the certificate chain whose first one is the end entity certificate
+/// is issued under the root certificate.
+pub(crate) fn verify_cert_chain(chain: &[Vec<u8>], root_cert: &[u8]) -> Result<()> {
+    let (ee_cert, intermediates) = chain
+        .split_first()
+        .ok_or_else(|| anyhow!("The certificate chain is empty"))?;
+    let now_func = webpki::Time::try_from(SystemTime::now())?;
+
+    let mut root_store = rustls::RootCertStore::empty();
+    root_store.add(&rustls::Certificate(root_cert.to_vec()))?;
+    let trust_anchors: Vec<webpki::TrustAnchor> = root_store
+        .roots
+        .iter()
+        .map(|cert| cert.to_trust_anchor())
+        .collect();
+    let intermediates: Vec<&[u8]> = intermediates.iter().map(|c| c.as_slice()).collect();
+
+    webpki::EndEntityCert::from(ee_cert)?.verify_is_valid_tls_server_cert(
+        SUPPORTED_SIG_ALGS,
+        &webpki::TLSServerTrustAnchors(&trust_anchors),
+        &intermediates,
+        now_func,
+    )?;
+
+    Ok(())
+}
+
+/// Verify the raw `r || s` ECDSA P-256 signature by the certificate's key.
+pub(crate) fn verify_signature_by_cert(cert: &[u8], msg: &[u8], raw_sig: &[u8]) -> Result<()> {
+    verify_der_signature_by_cert(cert, msg, &raw_sig_to_der(raw_sig)?)
+}
+
+/// Verify the DER-encoded ECDSA P-256 signature by the certificate's key.
+fn verify_der_signature_by_cert(cert: &[u8], msg: &[u8], der_sig: &[u8]) -> Result<()> {
+    webpki::EndEntityCert::from(cert)?.verify_signature(
+        &webpki::ECDSA_P256_SHA256,
+        msg,
+        der_sig,
+    )?;
+
+    Ok(())
+}
+
+/// "platform" or "processor" as the `ca` parameter of PCCS's pckcrl API
+fn pck_ca_type(pck_issuer: &[u8]) -> &'static str {
+    let is_platform_ca = pck_issuer
+        .windows(PLATFORM_CA_CN.len())
+        .any(|w| w == PLATFORM_CA_CN);
+    if is_platform_ca {
+        "platform"
+    } else {
+        "processor"
+    }
+}
+
+/// PCCS serves the CRL in DER, or hex-encoded DER depending on its version.
+fn crl_to_der(body: Vec<u8>) -> Result<Vec<u8>> {
+    if body.first() == Some(&TAG_SEQUENCE) {
+        return Ok(body);
+    }
+    let hex_str = str::from_utf8(&body)?.trim();
+    hex::decode(hex_str).map_err(|e| anyhow!("Invalid PCK CRL: {:?}", e))
+}
+
+/// A DER element with its whole encoding
+struct Tlv<'a> {
+    tag: u8,
+    value: &'a [u8],
+    raw: &'a [u8],
+}
+
+/// Read the DER element at the head of the bytes and returns it with the rest.
+fn der_read(bytes: &[u8]) -> Result<(Tlv, &[u8])> {
+    ensure!(bytes.len() >= 2, "The DER element is truncated");
+    let (len, header_len) = if bytes[1] & 0x80 == 0 {
+        (bytes[1] as usize, 2)
+    } else {
+        let num_bytes = (bytes[1] & 0x7f) as usize;
+        ensure!(
+            num_bytes >= 1 && num_bytes <= 4 && bytes.len() >= 2 + num_bytes,
+            "Invalid DER length"
+        );
+        let len = bytes[2..2 + num_bytes]
+            .iter()
+            .fold(0usize, |acc, b| (acc << 8) | *b as usize);
+        (len, 2 + num_bytes)
+    };
+    ensure!(
+        bytes.len() >= header_len + len,
+        "The DER element is truncated"
+    );
+    let (raw, rest) = bytes.split_at(header_len + len);
+
+    Ok((
+        Tlv {
+            tag: bytes[0],
+            value: &raw[header_len..],
+            raw,
+        },
+        rest,
+    ))
+}
+
+fn der_expect(bytes: &[u8], tag: u8) -> Result<(Tlv, &[u8])> {
+    let (tlv, rest) = der_read(bytes)?;
+    ensure!(
+        tlv.tag == tag,
+        "Unexpected DER tag: {:#x} (expected: {:#x})",
+        tlv.tag,
+        tag
+    );
+    Ok((tlv, rest))
+}
+
+/// The serial number of the DER-encoded certificate
+fn cert_serial_number(cert: &[u8]) -> Result<&[u8]> {
+    let (cert, _) = der_expect(cert, TAG_SEQUENCE)?;
+    let (tbs_cert, _) = der_expect(cert.value, TAG_SEQUENCE)?;
+    let (first, rest) = der_read(tbs_cert.value)?;
+    // Skip the explicitly tagged version
+    let serial = if first.tag == TAG_EXPLICIT_0 {
+        der_read(rest)?.0
+    } else {
+        first
+    };
+    ensure!(
+        serial.tag == TAG_INTEGER,
+        "Not found the serial number in the certificate"
+    );
+
+    Ok(serial.value)
+}
+
+/// Fields of a DER-encoded X.509 CRL borrowed from its bytes
+struct Crl<'a> {
+    /// The DER-encoded TBSCertList signed by the issuer
+    signed: &'a [u8],
+    /// The DER-encoded ECDSA signature
+    signature: &'a [u8],
+    next_update: Option<SystemTime>,
+    revoked_serials: Vec<&'a [u8]>,
+}
+
+impl<'a> Crl<'a> {
+    fn parse(crl: &'a [u8]) -> Result<Self> {
+        let (cert_list, _) = der_expect(crl, TAG_SEQUENCE)?;
+        let (tbs_cert_list, rest) = der_expect(cert_list.value, TAG_SEQUENCE)?;
+        let (_signature_alg, rest) = der_expect(rest, TAG_SEQUENCE)?;
+        let (signature, _) = der_expect(rest, TAG_BIT_STRING)?;
+        ensure!(
+            signature.value.first() == Some(&0),
+            "Invalid signature of the CRL"
+        );
+
+        let (first, rest) = der_read(tbs_cert_list.value)?;
+        // The version is optional
+        let rest = if first.tag == TAG_INTEGER {
+            der_expect(rest, TAG_SEQUENCE)?.1
+        } else {
+            ensure!(
+                first.tag == TAG_SEQUENCE,
+                "Invalid signature algorithm of the CRL"
+            );
+            rest
+        };
+        let (_issuer, rest) = der_expect(rest, TAG_SEQUENCE)?;
+        let (this_update, mut fields) = der_read(rest)?;
+        parse_time(&this_update)?;
+
+        let mut next_update = None;
+        let mut revoked_serials = vec![];
+        while !fields.is_empty() {
+            let (field, rest) = der_read(fields)?;
+            fields = rest;
+            match field.tag {
+                TAG_UTC_TIME | TAG_GENERALIZED_TIME => next_update = Some(parse_time(&field)?),
+                TAG_SEQUENCE => {
+                    let mut entries = field.value;
+                    while !entries.is_empty() {
+                        let (entry, rest) = der_expect(entries, TAG_SEQUENCE)?;
+                        entries = rest;
+                        let (serial, _) = der_expect(entry.value, TAG_INTEGER)?;
+                        revoked_serials.push(serial.value);
+                    }
+                }
+                // Extensions
+                _ => {}
+            }
+        }
+
+        Ok(Crl {
+            signed: tbs_cert_list.raw,
+            signature: &signature.value[1..],
+            next_update,
+            revoked_serials,
+        })
+    }
+
+    fn is_revoked(&self, serial: &[u8]) -> bool {
+        let serial = trim_integer(serial);
+        self.revoked_serials
+            .iter()
+            .any(|revoked| trim_integer(revoked) == serial)
+    }
+}
+
+/// Strip leading zeros of a DER integer so that the same values are compared equal.
+fn trim_integer(mut int: &[u8]) -> &[u8] {
+    while int.len() > 1 && int[0] == 0 {
+        int = &int[1..];
+    }
+    int
+}
+
+fn parse_time(tlv: &Tlv) -> Result<SystemTime> {
+    let time = str::from_utf8(tlv.value)?;
+    let format = match tlv.tag {
+        TAG_UTC_TIME => "%y%m%d%H%M%SZ",
+        TAG_GENERALIZED_TIME => "%Y%m%d%H%M%SZ",
+        tag => return Err(anyhow!("Unexpected DER tag of time: {:#x}", tag)),
+    };
+    let time = chrono::NaiveDateTime::parse_from_str(time, format)
+        .map_err(|e| anyhow!("Invalid time {}: {:?}", time, e))?;
+    ensure!(time.timestamp() >= 0, "Invalid time before the epoch");
+
+    Ok(UNIX_EPOCH + Duration::from_secs(time.timestamp() as u64))
+}
+
+/// Convert the PEM-encoded certificates into DER-encoded ones.
+pub(crate) fn pem_chain_to_der(pem: &str) -> Result<Vec<Vec<u8>>> {
+    const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+    const END: &str = "-----END CERTIFICATE-----";
+
+    let chain = pem
+        .split(BEGIN)
+        .skip(1)
+        .map(|cert| {
+            let end = cert
+                .find(END)
+                .ok_or_else(|| anyhow!("Invalid PEM certificate"))?;
+            let base64_cert: String = cert[..end].chars().filter(|c| !c.is_whitespace()).collect();
+            base64::decode(&base64_cert).map_err(Into::into)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    ensure!(!chain.is_empty(), "No certificates in the PEM");
+
+    Ok(chain)
+}
+
+/// Verify a JSON body like `{"<key>":{..},"signature":"<hex>"}` is signed over the raw bytes of the `<key>` value.
+fn verify_signed_json(
+    body: &[u8],
+    key: &str,
+    issuer_chain: &[Vec<u8>],
+    root_cert: &[u8],
+) -> Result<()> {
+    let body_str = str::from_utf8(body)?;
+    let prefix = format!("{{\"{}\":", key);
+    let suffix = ",\"signature\":\"";
+    ensure!(
+        body_str.starts_with(&prefix),
+        "The signed body doesn't begin with {}",
+        key
+    );
+    let signed_end = body_str
+        .rfind(suffix)
+        .ok_or_else(|| anyhow!("Not found signature in the signed body"))?;
+    let signed = &body[prefix.len()..signed_end];
+
+    let body: Value = serde_json::from_slice(body)?;
+    let sig = body["signature"]
+        .as_str()
+        .ok_or_else(|| anyhow!("Invalid signature in the signed body"))?;
+    let sig = hex::decode(sig).map_err(|e| anyhow!("{:?}", e))?;
+
+    verify_cert_chain(issuer_chain, root_cert)?;
+    verify_signature_by_cert(&issuer_chain[0], signed, &sig)
+}
+
+fn status_of(level: &Value) -> Result<String> {
+    level["tcbStatus"]
+        .as_str()
+        .map(Into::into)
+        .ok_or_else(|| anyhow!("Invalid tcbStatus"))
+}
+
+fn masked_eq(value: &[u8], mask: &[u8], expected: &[u8]) -> bool {
+    value.len() == mask.len()
+        && mask.len() == expected.len()
+        && value
+            .iter()
+            .zip(mask)
+            .zip(expected)
+            .all(|((v, m), e)| v & m == e & m)
+}
+
+/// Encode the raw `r || s` signature as an ASN.1 `SEQUENCE { r INTEGER, s INTEGER }`.
+fn raw_sig_to_der(raw_sig: &[u8]) -> Result<Vec<u8>> {
+    ensure!(raw_sig.len() == 64, "Invalid ECDSA signature length");
+
+    fn der_integer(mut bytes: &[u8]) -> Vec<u8> {
+        while bytes.len() > 1 && bytes[0] == 0 {
+            bytes = &bytes[1..];
+        }
+        // A leading zero keeps the integer positive.
+        let needs_pad = bytes[0] & 0x80 != 0;
+        let mut res = vec![0x02, (bytes.len() + needs_pad as usize) as u8];
+        if needs_pad {
+            res.push(0);
+        }
+        res.extend_from_slice(bytes);
+        res
+    }
+
+    let r = der_integer(&raw_sig[..32]);
+    let s = der_integer(&raw_sig[32..]);
+    let mut res = vec![0x30, (r.len() + s.len()) as u8];
+    res.extend_from_slice(&r);
+    res.extend_from_slice(&s);
+
+    Ok(res)
+}
+
+fn percent_decode(orig: &str) -> Result<String> {
+    let mut res = Vec::with_capacity(orig.len());
+    let mut bytes = orig.bytes();
+    while let Some(b) = bytes.next() {
+        if b == b'%' {
+            let hi = bytes
+                .next()
+                .ok_or_else(|| anyhow!("Invalid percent encoding"))?;
+            let lo = bytes
+                .next()
+                .ok_or_else(|| anyhow!("Invalid percent encoding"))?;
+            res.push(u8::from_str_radix(str::from_utf8(&[hi, lo])?, 16)?);
+        } else {
+            res.push(b);
+        }
+    }
+
+    String::from_utf8(res).map_err(Into::into)
+}
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_tcb_status,
+            test_qe_tcb_status,
+            test_crl_revocation,
+            test_cert_serial_number,
+            test_crl_to_der,
+            test_pck_ca_type,
+            test_raw_sig_to_der,
+            test_percent_decode,
+        )
+    }
+
+    fn tcb_level(svn: u8, pce_svn: u8, status: &str) -> String {
+        let components: Vec<String> = (1..=NUM_TCB_COMPONENTS)
+            .map(|i| format!("\"sgxtcbcomp{:02}svn\":{}", i, svn))
+            .collect();
+        format!(
+            "{{\"tcb\":{{{},\"pcesvn\":{}}},\"tcbStatus\":\"{}\"}}",
+            components.join(","),
+            pce_svn,
+            status
+        )
+    }
+
+    /// Unsigned collateral whose TCB levels are
+    /// UpToDate for SVN 4, SWHardeningNeeded for 3 and OutOfDate for 2 with PCESVN 10,
+    /// and whose QE identity is UpToDate for ISVSVN 6 and OutOfDate for 5.
+    pub(crate) fn collateral(fmspc: &[u8]) -> QuoteCollateral {
+        let tcb_info = format!(
+            "{{\"tcbInfo\":{{\"fmspc\":\"{}\",\"tcbLevels\":[{},{},{}]}},\"signature\":\"00\"}}",
+            hex::encode(fmspc),
+            tcb_level(4, 10, "UpToDate"),
+            tcb_level(3, 10, "SWHardeningNeeded"),
+            tcb_level(2, 10, "OutOfDate"),
+        );
+        let qe_identity = format!(
+            "{{\"enclaveIdentity\":{{\"miscselect\":\"00000000\",\"miscselectMask\":\"FFFFFFFF\",\
+            \"attributes\":\"{}\",\"attributesMask\":\"{}\",\"mrsigner\":\"{}\",\"isvprodid\":0,\
+            \"tcbLevels\":[{{\"tcb\":{{\"isvsvn\":6}},\"tcbStatus\":\"UpToDate\"}},\
+            {{\"tcb\":{{\"isvsvn\":5}},\"tcbStatus\":\"OutOfDate\"}}]}},\"signature\":\"00\"}}",
+            hex::encode([0u8; 16]),
+            hex::encode([0xffu8; 16]),
+            hex::encode([5u8; 32]),
+        );
+
+        QuoteCollateral {
+            tcb_info: tcb_info.into_bytes(),
+            tcb_info_issuer_chain: vec![],
+            qe_identity: qe_identity.into_bytes(),
+            qe_identity_issuer_chain: vec![],
+            pck_crl: vec![],
+        }
+    }
+
+    fn der(tag: u8, value: &[u8]) -> Vec<u8> {
+        let mut res = vec![tag];
+        if value.len() < 0x80 {
+            res.push(value.len() as u8);
+        } else {
+            res.push(0x82);
+            res.extend_from_slice(&(value.len() as u16).to_be_bytes());
+        }
+        res.extend_from_slice(value);
+        res
+    }
+
+    fn crl(revoked_serials: &[&[u8]], next_update: &str) -> Vec<u8> {
+        let entries: Vec<u8> = revoked_serials
+            .iter()
+            .flat_map(|serial| {
+                let mut entry = der(TAG_INTEGER, serial);
+                entry.extend(der(TAG_UTC_TIME, b"200101000000Z"));
+                der(TAG_SEQUENCE, &entry)
+            })
+            .collect();
+        let mut tbs = der(TAG_INTEGER, &[1]);
+        tbs.extend(der(TAG_SEQUENCE, &[0x06, 0x01, 0x00]));
+        tbs.extend(der(TAG_SEQUENCE, b"issuer"));
+        tbs.extend(der(TAG_UTC_TIME, b"200101000000Z"));
+        tbs.extend(der(TAG_UTC_TIME, next_update.as_bytes()));
+        tbs.extend(der(TAG_SEQUENCE, &entries));
+        tbs.extend(der(TAG_EXPLICIT_0, &der(TAG_SEQUENCE, &[])));
+
+        let mut cert_list = der(TAG_SEQUENCE, &tbs);
+        cert_list.extend(der(TAG_SEQUENCE, &[0x06, 0x01, 0x00]));
+        cert_list.extend(der(TAG_BIT_STRING, &[0, 0x30, 0x00]));
+        der(TAG_SEQUENCE, &cert_list)
+    }
+
+    fn test_tcb_status() {
+        let collateral = collateral(&[1, 2, 3, 4, 5, 6]);
+        assert_eq!(collateral.tcb_status(&[4u8; 16], 10).unwrap(), "UpToDate");
+        assert_eq!(
+            collateral.tcb_status(&[3u8; 16], 10).unwrap(),
+            "SWHardeningNeeded"
+        );
+        let mut components = [4u8; 16];
+        components[15] = 2;
+        assert_eq!(collateral.tcb_status(&components, 10).unwrap(), "OutOfDate");
+        // PCESVN is below all the levels
+        assert!(collateral.tcb_status(&[4u8; 16], 9).is_err());
+    }
+
+    fn test_qe_tcb_status() {
+        let collateral = collateral(&[1, 2, 3, 4, 5, 6]);
+        let mut qe_report_body = vec![0u8; 384];
+        qe_report_body[128..160].copy_from_slice(&[5u8; 32]);
+        qe_report_body[258] = 6;
+        assert_eq!(
+            collateral.qe_tcb_status(&qe_report_body).unwrap(),
+            "UpToDate"
+        );
+
+        qe_report_body[16] = 1;
+        assert!(collateral.qe_tcb_status(&qe_report_body).is_err());
+        qe_report_body[16] = 0;
+        qe_report_body[128] = 0;
+        assert!(collateral.qe_tcb_status(&qe_report_body).is_err());
+    }
+
+    fn test_crl_revocation() {
+        let crl_der = crl(&[&[0x01, 0x02], &[0x00, 0x80, 0x01]], "491231235959Z");
+        let parsed = Crl::parse(&crl_der).unwrap();
+        assert!(parsed.is_revoked(&[0x01, 0x02]));
+        assert!(parsed.is_revoked(&[0x00, 0x01, 0x02]));
+        assert!(parsed.is_revoked(&[0x80, 0x01]));
+        assert!(!parsed.is_revoked(&[0x01, 0x03]));
+        assert_eq!(parsed.signature, &[0x30, 0x00][..]);
+        assert!(parsed.next_update.unwrap() > SystemTime::now());
+        assert_eq!(parsed.signed[0], TAG_SEQUENCE);
+
+        // A CRL without revoked certificates
+        let crl_der = crl(&[], "491231235959Z");
+        assert!(!Crl::parse(&crl_der).unwrap().is_revoked(&[0x01, 0x02]));
+        assert!(Crl::parse(&crl_der[..crl_der.len() - 1]).is_err());
+    }
+
+    fn test_cert_serial_number() {
+        let mut tbs = der(TAG_EXPLICIT_0, &der(TAG_INTEGER, &[2]));
+        tbs.extend(der(TAG_INTEGER, &[0x12, 0x34]));
+        tbs.extend(der(TAG_SEQUENCE, &[]));
+        let cert = der(TAG_SEQUENCE, &der(TAG_SEQUENCE, &tbs));
+        assert_eq!(cert_serial_number(&cert).unwrap(), &[0x12, 0x34][..]);
+
+        let cert = der(TAG_SEQUENCE, &der(TAG_SEQUENCE, &der(TAG_SEQUENCE, &[])));
+        assert!(cert_serial_number(&cert).is_err());
+    }
+
+    fn test_crl_to_der() {
+        let crl_der = crl(&[&[0x01]], "491231235959Z");
+        assert_eq!(crl_to_der(crl_der.clone()).unwrap(), crl_der);
+        assert_eq!(
+            crl_to_der(hex::encode(&crl_der).into_bytes()).unwrap(),
+            crl_der
+        );
+        assert!(crl_to_der(b"not a crl".to_vec()).is_err());
+    }
+
+    fn test_pck_ca_type() {
+        assert_eq!(pck_ca_type(b"..CN=Intel SGX PCK Platform CA.."), "platform");
+        assert_eq!(
+            pck_ca_type(b"..CN=Intel SGX PCK Processor CA.."),
+            "processor"
+        );
+    }
+
+    fn test_raw_sig_to_der() {
+        let mut raw_sig = [0u8; 64];
+        raw_sig[31] = 0x01;
+        raw_sig[32] = 0x80;
+        let der_sig = raw_sig_to_der(&raw_sig).unwrap();
+        // r is shortened to 1 byte and s is padded with a leading zero
+        assert_eq!(&der_sig[..5], &[0x30, 3 + 35, 0x02, 0x01, 0x01][..]);
+        assert_eq!(&der_sig[5..8], &[0x02, 33, 0x00][..]);
+        assert!(raw_sig_to_der(&raw_sig[..63]).is_err());
+    }
+
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("a%2Bb%0A").unwrap(), "a+b\n");
+        assert!(percent_decode("a%2").is_err());
+    }
+}
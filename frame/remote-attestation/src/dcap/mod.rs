@@ -0,0 +1,138 @@
+//! DCAP attestation
+//! An ECDSA quote is generated by the quoting enclave on the platform
+//! and verified with the collateral fetched from PCCS instead of sending it to IAS.
+
+mod collateral;
+mod quote;
+
+pub use self::collateral::{PccsClient, QuoteCollateral};
+pub use self::quote::DcapQuote;
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        check_all_passed!(collateral::tests::run_tests(), quote::tests::run_tests(),)
+    }
+}
+
+use crate::attestation::{Attestation, AttestedEvidence};
+use crate::error::{FrameRAError, Result};
+use frame_types::UntrustedStatus;
+use sgx_types::*;
+use std::string::String;
+use std::vec::Vec;
+
+extern "C" {
+    fn ocall_get_qe_target_info(
+        retval: *mut UntrustedStatus,
+        ret_ti: *mut sgx_target_info_t,
+    ) -> sgx_status_t;
+
+    fn ocall_get_ecdsa_quote(
+        retval: *mut UntrustedStatus,
+        report: *const sgx_report_t,
+        p_quote: *mut u8,
+        maxlen: u32,
+        p_quote_len: *mut u32,
+    ) -> sgx_status_t;
+}
+
+/// DCAP attestation whose quote is verified with the collateral from PCCS
+#[derive(Debug, Clone)]
+pub struct Dcap {
+    pccs_url: String,
+    root_cert: Vec<u8>,
+}
+
+impl Dcap {
+    pub fn new(pccs_url: String, root_cert: Vec<u8>) -> Self {
+        Dcap {
+            pccs_url,
+            root_cert,
+        }
+    }
+}
+
+impl Attestation for Dcap {
+    fn attest(&self, report_data: &sgx_report_data_t) -> Result<AttestedEvidence> {
+        let target_info = get_qe_target_info()?;
+        let report = sgx_tse::rsgx_create_report(&target_info, report_data).map_err(|err| {
+            FrameRAError::OcallError {
+                status: err,
+                function: "sgx_tse::rsgx_create_report",
+            }
+        })?;
+        let quote = get_ecdsa_quote(&report)?;
+
+        let dcap_quote = DcapQuote::new(quote, &PccsClient::new(&self.pccs_url))?;
+        dcap_quote.verify_signature(self.root_cert.clone())?;
+
+        Ok(AttestedEvidence::Dcap(dcap_quote))
+    }
+
+    fn root_cert(&self) -> &[u8] {
+        &self.root_cert
+    }
+}
+
+fn get_qe_target_info() -> Result<sgx_target_info_t> {
+    let mut rt = UntrustedStatus::default();
+    let mut target_info = sgx_target_info_t::default();
+
+    let status = unsafe {
+        ocall_get_qe_target_info(
+            &mut rt as *mut UntrustedStatus,
+            &mut target_info as *mut sgx_target_info_t,
+        )
+    };
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(FrameRAError::OcallError {
+            status,
+            function: "ocall_get_qe_target_info",
+        });
+    }
+    if rt.is_err() {
+        return Err(FrameRAError::UntrustedError {
+            status: rt,
+            function: "ocall_get_qe_target_info",
+        });
+    }
+
+    Ok(target_info)
+}
+
+fn get_ecdsa_quote(report: &sgx_report_t) -> Result<Vec<u8>> {
+    // Enough for the quote with the PCK certificate chain
+    const RET_QUOTE_BUF_LEN: u32 = 8192;
+    let mut rt = UntrustedStatus::default();
+    let mut quote = vec![0u8; RET_QUOTE_BUF_LEN as usize];
+    let mut quote_len: u32 = 0;
+
+    let status = unsafe {
+        ocall_get_ecdsa_quote(
+            &mut rt as *mut UntrustedStatus,
+            report as *const sgx_report_t,
+            quote.as_mut_ptr(),
+            RET_QUOTE_BUF_LEN,
+            &mut quote_len as *mut u32,
+        )
+    };
+    if status != sgx_status_t::SGX_SUCCESS {
+        return Err(FrameRAError::OcallError {
+            status,
+            function: "ocall_get_ecdsa_quote",
+        });
+    }
+    if rt.is_err() {
+        return Err(FrameRAError::UntrustedError {
+            status: rt,
+            function: "ocall_get_ecdsa_quote",
+        });
+    }
+
+    quote.truncate(quote_len as usize);
+    Ok(quote)
+}
@@ -0,0 +1,429 @@
+use super::collateral::{
+    pem_chain_to_der, verify_cert_chain, verify_signature_by_cert, PccsClient, QuoteCollateral,
+};
+use anyhow::{anyhow, ensure, Result};
+use serde::{Deserialize, Serialize};
+use sgx_tcrypto::{rsgx_sha256_slice, SgxEccHandle};
+use sgx_types::{sgx_ec256_public_t, sgx_ec256_signature_t};
+use std::{prelude::v1::*, str};
+
+// The layout is defined in "Intel® SGX ECDSA Quote Library API" Appendix A.4
+const QUOTE_VERSION: u16 = 3;
+const ATT_KEY_TYPE_ECDSA_P256: u16 = 2;
+const CERT_DATA_TYPE_PCK_CERT_CHAIN: u16 = 5;
+const HEADER_LEN: usize = 48;
+const REPORT_BODY_LEN: usize = 384;
+const ECDSA_SIG_LEN: usize = 64;
+const ECDSA_PUB_KEY_LEN: usize = 64;
+const REPORT_DATA_OFFSET: usize = 320;
+
+// DER-encoded prefixes of the OIDs in the SGX extension of PCK certificates (1.2.840.113741.1.13.1.*)
+const FMSPC_OID: &[u8] = &[
+    0x06, 0x0a, 0x2a, 0x86, 0x48, 0x86, 0xf8, 0x4d, 0x01, 0x0d, 0x01, 0x04,
+];
+const TCB_COMPONENT_OID: &[u8] = &[
+    0x06, 0x0b, 0x2a, 0x86, 0x48, 0x86, 0xf8, 0x4d, 0x01, 0x0d, 0x01, 0x02,
+];
+const PCE_SVN_COMPONENT: u8 = 17;
+
+/// An ECDSA quote generated by the quoting enclave with the collateral to verify it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DcapQuote {
+    quote: Vec<u8>,
+    collateral: QuoteCollateral,
+}
+
+impl DcapQuote {
+    /// Fetch the collateral for the platform the quote is generated on.
+    pub fn new(quote: Vec<u8>, pccs: &PccsClient) -> Result<Self> {
+        let pck_cert_chain = ParsedQuote::parse(&quote)?.pck_cert_chain()?;
+        ensure!(
+            pck_cert_chain.len() >= 2,
+            "The PCK certificate chain has no issuer"
+        );
+        let fmspc = PckExtension::parse(&pck_cert_chain[0])?.fmspc;
+        let collateral = pccs.get_collateral(&fmspc, &pck_cert_chain[1])?;
+
+        Ok(DcapQuote { quote, collateral })
+    }
+
+    /// Verify that
+    /// 1. the enclave's report is signed by the attestation key
+    /// 2. the attestation key is bound to the quoting enclave's report
+    /// 3. the quoting enclave's report is signed by the PCK certificate issued under the root certificate
+    /// 4. the PCK certificate is not revoked by the CRL of its issuer
+    /// 5. the collateral is issued under the root certificate for the platform
+    pub fn verify_signature(&self, root_cert: Vec<u8>) -> Result<()> {
+        let parsed = ParsedQuote::parse(&self.quote)?;
+        ensure!(
+            parsed.version == QUOTE_VERSION,
+            "The quote version is not supported: {}",
+            parsed.version
+        );
+        ensure!(
+            parsed.att_key_type == ATT_KEY_TYPE_ECDSA_P256,
+            "The attestation key type is not supported: {}",
+            parsed.att_key_type
+        );
+
+        verify_by_raw_key(parsed.att_pub_key, parsed.signed, parsed.isv_report_sig)?;
+
+        let mut key_and_auth = parsed.att_pub_key.to_vec();
+        key_and_auth.extend_from_slice(parsed.qe_auth_data);
+        let hash = rsgx_sha256_slice(&key_and_auth).map_err(|e| anyhow!("{:?}", e))?;
+        let qe_report_data = &parsed.qe_report_body[REPORT_DATA_OFFSET..];
+        ensure!(
+            qe_report_data[..32] == hash[..] && qe_report_data[32..].iter().all(|b| *b == 0),
+            "The attestation key is not bound to the quoting enclave's report"
+        );
+
+        let pck_cert_chain = parsed.pck_cert_chain()?;
+        verify_cert_chain(&pck_cert_chain, &root_cert)?;
+        verify_signature_by_cert(
+            &pck_cert_chain[0],
+            parsed.qe_report_body,
+            parsed.qe_report_sig,
+        )?;
+        self.collateral.verify_pck_not_revoked(&pck_cert_chain)?;
+
+        let fmspc = PckExtension::parse(&pck_cert_chain[0])?.fmspc;
+        self.collateral.verify(&root_cert, &fmspc)
+    }
+
+    /// Evaluate the TCB levels of the platform and the quoting enclave,
+    /// and returns the status in the same terms as IAS's `isvEnclaveQuoteStatus`.
+    pub fn quote_status(&self) -> Result<String> {
+        let parsed = ParsedQuote::parse(&self.quote)?;
+        let pck = PckExtension::parse(&parsed.pck_cert_chain()?[0])?;
+        let tcb_status = self
+            .collateral
+            .tcb_status(&pck.cpu_svn_components, pck.pce_svn)?;
+        let qe_tcb_status = self.collateral.qe_tcb_status(parsed.qe_report_body)?;
+
+        let status = match (tcb_status.as_str(), qe_tcb_status.as_str()) {
+            ("Revoked", _) | (_, "Revoked") => "KEY_REVOKED",
+            (_, "OutOfDate") => "GROUP_OUT_OF_DATE",
+            (s, _) if s.starts_with("OutOfDate") => "GROUP_OUT_OF_DATE",
+            ("UpToDate", _) => "OK",
+            ("SWHardeningNeeded", _) => "SW_HARDENING_NEEDED",
+            ("ConfigurationNeeded", _) => "CONFIGURATION_NEEDED",
+            ("ConfigurationAndSWHardeningNeeded", _) => "CONFIGURATION_AND_SW_HARDENING_NEEDED",
+            (s, _) => return Err(anyhow!("Unknown TCB status: {}", s)),
+        };
+
+        Ok(status.to_string())
+    }
+
+    pub fn quote(&self) -> &[u8] {
+        &self.quote
+    }
+
+    pub fn collateral(&self) -> &QuoteCollateral {
+        &self.collateral
+    }
+}
+
+/// Fields of a quote borrowed from its bytes
+struct ParsedQuote<'a> {
+    version: u16,
+    att_key_type: u16,
+    /// The header and the enclave's report body signed by the attestation key
+    signed: &'a [u8],
+    isv_report_sig: &'a [u8],
+    att_pub_key: &'a [u8],
+    qe_report_body: &'a [u8],
+    qe_report_sig: &'a [u8],
+    qe_auth_data: &'a [u8],
+    cert_data_type: u16,
+    cert_data: &'a [u8],
+}
+
+impl<'a> ParsedQuote<'a> {
+    fn parse(quote: &'a [u8]) -> Result<Self> {
+        let mut reader = Reader(quote);
+        let header = reader.take(HEADER_LEN)?;
+        reader.take(REPORT_BODY_LEN)?;
+        let signed = &quote[..HEADER_LEN + REPORT_BODY_LEN];
+        let sig_data_len = reader.u32()? as usize;
+        ensure!(
+            reader.0.len() >= sig_data_len,
+            "The quote signature data is truncated"
+        );
+
+        let isv_report_sig = reader.take(ECDSA_SIG_LEN)?;
+        let att_pub_key = reader.take(ECDSA_PUB_KEY_LEN)?;
+        let qe_report_body = reader.take(REPORT_BODY_LEN)?;
+        let qe_report_sig = reader.take(ECDSA_SIG_LEN)?;
+        let qe_auth_data_len = reader.u16()? as usize;
+        let qe_auth_data = reader.take(qe_auth_data_len)?;
+        let cert_data_type = reader.u16()?;
+        let cert_data_len = reader.u32()? as usize;
+        let cert_data = reader.take(cert_data_len)?;
+
+        Ok(ParsedQuote {
+            version: u16::from_le_bytes([header[0], header[1]]),
+            att_key_type: u16::from_le_bytes([header[2], header[3]]),
+            signed,
+            isv_report_sig,
+            att_pub_key,
+            qe_report_body,
+            qe_report_sig,
+            qe_auth_data,
+            cert_data_type,
+            cert_data,
+        })
+    }
+
+    /// DER-encoded PCK certificate chain whose first one is the PCK certificate
+    fn pck_cert_chain(&self) -> Result<Vec<Vec<u8>>> {
+        ensure!(
+            self.cert_data_type == CERT_DATA_TYPE_PCK_CERT_CHAIN,
+            "The certification data type is not supported: {}",
+            self.cert_data_type
+        );
+        let pem = str::from_utf8(self.cert_data)?.trim_end_matches('\0');
+        pem_chain_to_der(pem)
+    }
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        ensure!(self.0.len() >= len, "The quote is truncated");
+        let (res, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(res)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let b = self.take(2)?;
+        Ok(u16::from_le_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
+
+/// Values in the SGX extension of a PCK certificate
+struct PckExtension {
+    fmspc: Vec<u8>,
+    cpu_svn_components: Vec<u8>,
+    pce_svn: u16,
+}
+
+impl PckExtension {
+    /// Each value follows its OID in the DER-encoded certificate,
+    /// so they are found by the OIDs without decoding the whole certificate.
+    fn parse(cert: &[u8]) -> Result<Self> {
+        let fmspc = find_after(cert, FMSPC_OID)
+            .and_then(|v| der_value(v, 0x04))
+            .ok_or_else(|| anyhow!("Not found FMSPC in the PCK certificate"))?
+            .to_vec();
+
+        let component = |index: u8| -> Result<u64> {
+            let mut oid = TCB_COMPONENT_OID.to_vec();
+            oid.push(index);
+            find_after(cert, &oid)
+                .and_then(|v| der_value(v, 0x02))
+                .map(|int| int.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b)))
+                .ok_or_else(|| anyhow!("Not found TCB component {} in the PCK certificate", index))
+        };
+        let cpu_svn_components = (1..=16)
+            .map(|i| component(i).map(|svn| svn as u8))
+            .collect::<Result<Vec<u8>>>()?;
+        let pce_svn = component(PCE_SVN_COMPONENT)? as u16;
+
+        Ok(PckExtension {
+            fmspc,
+            cpu_svn_components,
+            pce_svn,
+        })
+    }
+}
+
+/// Returns the bytes following the first occurrence of the pattern.
+fn find_after<'a>(bytes: &'a [u8], pattern: &[u8]) -> Option<&'a [u8]> {
+    bytes
+        .windows(pattern.len())
+        .position(|w| w == pattern)
+        .map(|i| &bytes[i + pattern.len()..])
+}
+
+/// Returns the value of the short-form DER TLV with the tag.
+fn der_value(bytes: &[u8], tag: u8) -> Option<&[u8]> {
+    if bytes.len() < 2 || bytes[0] != tag || bytes[1] & 0x80 != 0 {
+        return None;
+    }
+    bytes.get(2..2 + bytes[1] as usize)
+}
+
+/// Verify the raw `r || s` signature by the raw big-endian `x || y` public key.
+fn verify_by_raw_key(pub_key: &[u8], msg: &[u8], sig: &[u8]) -> Result<()> {
+    // SGX crypto library takes the coordinates and the signature in little-endian.
+    let mut gx = [0u8; 32];
+    let mut gy = [0u8; 32];
+    gx.copy_from_slice(&pub_key[..32]);
+    gy.copy_from_slice(&pub_key[32..]);
+    gx.reverse();
+    gy.reverse();
+    let pub_key = sgx_ec256_public_t { gx, gy };
+
+    let to_le_words = |be: &[u8]| {
+        let mut le = be.to_vec();
+        le.reverse();
+        let mut words = [0u32; 8];
+        for (word, chunk) in words.iter_mut().zip(le.chunks(4)) {
+            *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        words
+    };
+    let signature = sgx_ec256_signature_t {
+        x: to_le_words(&sig[..32]),
+        y: to_le_words(&sig[32..]),
+    };
+
+    let ecc_handle = SgxEccHandle::new();
+    ecc_handle.open().map_err(|e| anyhow!("{:?}", e))?;
+    let is_valid = ecc_handle
+        .ecdsa_verify_slice(msg, &pub_key, &signature)
+        .map_err(|e| anyhow!("{:?}", e))?;
+    ecc_handle.close().map_err(|e| anyhow!("{:?}", e))?;
+    ensure!(
+        is_valid,
+        "The enclave's report is not signed by the attestation key"
+    );
+
+    Ok(())
+}
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::dcap::collateral::tests::collateral;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_parse_quote,
+            test_parse_truncated_quote,
+            test_parse_pck_extension,
+            test_quote_status,
+        )
+    }
+
+    const FMSPC: [u8; 6] = [0x00, 0x90, 0x6e, 0xa1, 0x00, 0x00];
+
+    /// A fake PCK certificate which has only the SGX extension values after their OIDs
+    pub(crate) fn pck_cert(cpu_svn: u8, pce_svn: u8) -> Vec<u8> {
+        let mut cert = vec![0x30, 0x00];
+        cert.extend_from_slice(FMSPC_OID);
+        cert.extend_from_slice(&[0x04, FMSPC.len() as u8]);
+        cert.extend_from_slice(&FMSPC);
+        for index in 1..=PCE_SVN_COMPONENT {
+            cert.extend_from_slice(TCB_COMPONENT_OID);
+            cert.push(index);
+            let svn = if index == PCE_SVN_COMPONENT {
+                pce_svn
+            } else {
+                cpu_svn
+            };
+            cert.extend_from_slice(&[0x02, 0x01, svn]);
+        }
+        cert
+    }
+
+    fn pem(certs: &[Vec<u8>]) -> String {
+        certs
+            .iter()
+            .map(|cert| {
+                format!(
+                    "-----BEGIN CERTIFICATE-----\n{}\n-----END CERTIFICATE-----\n",
+                    base64::encode(cert)
+                )
+            })
+            .collect()
+    }
+
+    /// A quote with the given bytes in each field, whose QE report has the ISVSVN
+    pub(crate) fn quote(cert_data: &[u8], qe_isv_svn: u16) -> Vec<u8> {
+        let mut header = vec![0u8; HEADER_LEN];
+        header[..2].copy_from_slice(&QUOTE_VERSION.to_le_bytes());
+        header[2..4].copy_from_slice(&ATT_KEY_TYPE_ECDSA_P256.to_le_bytes());
+        let mut qe_report_body = vec![0u8; REPORT_BODY_LEN];
+        qe_report_body[128..160].copy_from_slice(&[5u8; 32]);
+        qe_report_body[258..260].copy_from_slice(&qe_isv_svn.to_le_bytes());
+
+        let mut sig_data = vec![1u8; ECDSA_SIG_LEN];
+        sig_data.extend_from_slice(&[2u8; ECDSA_PUB_KEY_LEN]);
+        sig_data.extend_from_slice(&qe_report_body);
+        sig_data.extend_from_slice(&[3u8; ECDSA_SIG_LEN]);
+        sig_data.extend_from_slice(&4u16.to_le_bytes());
+        sig_data.extend_from_slice(&[4u8; 4]);
+        sig_data.extend_from_slice(&CERT_DATA_TYPE_PCK_CERT_CHAIN.to_le_bytes());
+        sig_data.extend_from_slice(&(cert_data.len() as u32).to_le_bytes());
+        sig_data.extend_from_slice(cert_data);
+
+        let mut quote = header;
+        quote.extend_from_slice(&[9u8; REPORT_BODY_LEN]);
+        quote.extend_from_slice(&(sig_data.len() as u32).to_le_bytes());
+        quote.extend_from_slice(&sig_data);
+        quote
+    }
+
+    fn test_parse_quote() {
+        let chain = vec![pck_cert(2, 10), vec![7u8; 16]];
+        let mut cert_data = pem(&chain).into_bytes();
+        cert_data.push(0);
+        let quote = quote(&cert_data, 1);
+
+        let parsed = ParsedQuote::parse(&quote).unwrap();
+        assert_eq!(parsed.version, QUOTE_VERSION);
+        assert_eq!(parsed.att_key_type, ATT_KEY_TYPE_ECDSA_P256);
+        assert_eq!(parsed.signed.len(), HEADER_LEN + REPORT_BODY_LEN);
+        assert_eq!(parsed.isv_report_sig, &[1u8; ECDSA_SIG_LEN][..]);
+        assert_eq!(parsed.att_pub_key, &[2u8; ECDSA_PUB_KEY_LEN][..]);
+        assert_eq!(parsed.qe_report_sig, &[3u8; ECDSA_SIG_LEN][..]);
+        assert_eq!(parsed.qe_auth_data, &[4u8; 4][..]);
+        assert_eq!(parsed.pck_cert_chain().unwrap(), chain);
+    }
+
+    fn test_parse_truncated_quote() {
+        let quote = quote(pem(&[pck_cert(2, 10)]).as_bytes(), 1);
+        assert!(ParsedQuote::parse(&quote[..quote.len() - 1]).is_err());
+        assert!(ParsedQuote::parse(&quote[..HEADER_LEN]).is_err());
+
+        let mut parsed = ParsedQuote::parse(&quote).unwrap();
+        parsed.cert_data_type = 1;
+        assert!(parsed.pck_cert_chain().is_err());
+    }
+
+    fn test_parse_pck_extension() {
+        let pck = PckExtension::parse(&pck_cert(3, 11)).unwrap();
+        assert_eq!(pck.fmspc, FMSPC.to_vec());
+        assert_eq!(pck.cpu_svn_components, vec![3u8; 16]);
+        assert_eq!(pck.pce_svn, 11);
+
+        assert!(PckExtension::parse(&pck_cert(3, 11)[..40]).is_err());
+    }
+
+    fn test_quote_status() {
+        let status = |cpu_svn: u8, qe_isv_svn: u16| {
+            let cert_data = pem(&[pck_cert(cpu_svn, 10), vec![7u8; 16]]);
+            DcapQuote {
+                quote: quote(cert_data.as_bytes(), qe_isv_svn),
+                collateral: collateral(&FMSPC),
+            }
+            .quote_status()
+        };
+
+        assert_eq!(status(4, 6).unwrap(), "OK");
+        assert_eq!(status(3, 6).unwrap(), "SW_HARDENING_NEEDED");
+        assert_eq!(status(2, 6).unwrap(), "GROUP_OUT_OF_DATE");
+        assert_eq!(status(4, 5).unwrap(), "GROUP_OUT_OF_DATE");
+        assert!(status(1, 6).is_err());
+        assert!(status(4, 1).is_err());
+    }
+}
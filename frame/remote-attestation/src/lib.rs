@@ -2,10 +2,37 @@
 #[macro_use]
 extern crate sgx_tstd as std;
 
+mod attestation;
+mod cache;
 mod client;
+mod dcap;
 mod error;
 mod quote;
+mod report;
 
+pub use crate::attestation::{Attestation, AttestedEvidence, Epid};
+pub use crate::cache::{AttestationStatus, CachedAttestation};
 pub use crate::client::AttestedReport;
+pub use crate::dcap::{Dcap, DcapQuote, PccsClient, QuoteCollateral};
 pub use crate::error::FrameRAError as Error;
 pub use crate::quote::{EncodedQuote, QuoteTarget};
+pub use crate::report::{
+    QuoteStatusPolicy, VerificationReport, QUOTE_STATUS_CONFIGURATION_AND_SW_HARDENING_NEEDED,
+    QUOTE_STATUS_CONFIGURATION_NEEDED, QUOTE_STATUS_GROUP_OUT_OF_DATE, QUOTE_STATUS_OK,
+    QUOTE_STATUS_SW_HARDENING_NEEDED,
+};
+
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+    use std::prelude::v1::*;
+    use test_utils::*;
+
+    pub fn run_tests() -> bool {
+        check_all_passed!(
+            cache::tests::run_tests(),
+            dcap::tests::run_tests(),
+            report::tests::run_tests(),
+        )
+    }
+}
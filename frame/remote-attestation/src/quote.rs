@@ -1,5 +1,6 @@
 use crate::client::*;
 use crate::error::{FrameRAError, Result};
+use crate::report::QuoteStatusPolicy;
 use anyhow::anyhow;
 use frame_types::UntrustedStatus;
 use http_req::uri::Uri;
@@ -45,6 +46,7 @@ impl EncodedQuote {
         uri: &str,
         ias_api_key: &str,
         root_cert: Vec<u8>,
+        policy: &QuoteStatusPolicy,
     ) -> Result<AttestedReport> {
         let uri: Uri = uri.parse().expect("Invalid uri");
         let body = format!("{{\"isvEnclaveQuote\":\"{}\"}}\r\n", &self.base64_quote);
@@ -55,9 +57,10 @@ impl EncodedQuote {
             .quote_body_mut(&body.as_bytes())
             .send(&mut writer)?;
 
-        AttestedReport::from_response(writer, response)?
-            .verify_attested_report(root_cert)
-            .map_err(Into::into)
+        let attested_report = AttestedReport::from_response(writer, response)?;
+        attested_report.verify_attested_report(root_cert, policy)?;
+
+        Ok(attested_report)
     }
 }
 
@@ -0,0 +1,229 @@
+use anyhow::{anyhow, bail, ensure, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::{
+    string::{String, ToString},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+    vec::Vec,
+};
+
+pub const QUOTE_STATUS_OK: &str = "OK";
+pub const QUOTE_STATUS_GROUP_OUT_OF_DATE: &str = "GROUP_OUT_OF_DATE";
+pub const QUOTE_STATUS_CONFIGURATION_NEEDED: &str = "CONFIGURATION_NEEDED";
+pub const QUOTE_STATUS_SW_HARDENING_NEEDED: &str = "SW_HARDENING_NEEDED";
+pub const QUOTE_STATUS_CONFIGURATION_AND_SW_HARDENING_NEEDED: &str =
+    "CONFIGURATION_AND_SW_HARDENING_NEEDED";
+
+/// The supported version of Attestation Verification Report
+const REPORT_VERSION: u64 = 3;
+// IAS's timestamps are in UTC without the time zone
+const TIMESTAMP_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.f";
+
+/// Attestation Verification Report returned from IAS.
+/// Fields are defined in "Attestation Service for Intel® Software Guard Extensions (Intel® SGX): API Documentation version 6.0"
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationReport {
+    pub id: String,
+    pub timestamp: String,
+    pub version: u64,
+    pub isv_enclave_quote_status: String,
+    pub isv_enclave_quote_body: String,
+    #[serde(default)]
+    pub revocation_reason: Option<u64>,
+    #[serde(default)]
+    pub pse_manifest_status: Option<String>,
+    #[serde(default)]
+    pub pse_manifest_hash: Option<String>,
+    #[serde(default)]
+    pub platform_info_blob: Option<String>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub epid_pseudonym: Option<String>,
+    #[serde(rename = "advisoryURL", default)]
+    pub advisory_url: Option<String>,
+    #[serde(rename = "advisoryIDs", default)]
+    pub advisory_ids: Vec<String>,
+}
+
+impl VerificationReport {
+    pub fn from_slice(report: &[u8]) -> Result<Self> {
+        serde_json::from_slice(report).map_err(Into::into)
+    }
+
+    /// Verify API version is supported
+    pub fn verify_version(&self) -> Result<()> {
+        ensure!(
+            self.version == REPORT_VERSION,
+            "The Remote Attestation API version is not supported: {}",
+            self.version
+        );
+        Ok(())
+    }
+
+    pub fn quote_body(&self) -> Result<Vec<u8>> {
+        base64::decode(&self.isv_enclave_quote_body).map_err(Into::into)
+    }
+
+    /// The time elapsed since IAS issued the report
+    pub fn age(&self) -> Result<Duration> {
+        let issued_at = chrono::NaiveDateTime::parse_from_str(&self.timestamp, TIMESTAMP_FORMAT)
+            .map_err(|e| anyhow!("Invalid timestamp {}: {:?}", self.timestamp, e))?;
+        let issued_at = UNIX_EPOCH + Duration::from_secs(issued_at.timestamp() as u64);
+
+        // Treat the report as just issued if the untrusted clock is behind IAS's one.
+        Ok(SystemTime::now()
+            .duration_since(issued_at)
+            .unwrap_or_default())
+    }
+}
+
+/// A policy of which quote statuses are accepted in addition to `OK`.
+/// A report with an accepted status must not have advisory IDs other than the allowed ones,
+/// and must not be older than the max age if it is set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteStatusPolicy {
+    accepted_statuses: Vec<String>,
+    // Any advisories are allowed if `None`
+    allowed_advisory_ids: Option<Vec<String>>,
+    max_age: Option<Duration>,
+}
+
+impl QuoteStatusPolicy {
+    /// A policy accepting only `OK`
+    pub fn new() -> Self {
+        QuoteStatusPolicy {
+            accepted_statuses: vec![],
+            allowed_advisory_ids: None,
+            max_age: None,
+        }
+    }
+
+    /// Replace the quote statuses accepted other than `OK`.
+    pub fn set_accepted_statuses(mut self, statuses: &[&str]) -> Self {
+        self.accepted_statuses = statuses.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    /// Restrict the advisories which reports with the accepted statuses may have.
+    pub fn set_allowed_advisory_ids(mut self, advisory_ids: &[&str]) -> Self {
+        self.allowed_advisory_ids = Some(advisory_ids.iter().map(|s| s.to_string()).collect());
+        self
+    }
+
+    pub fn set_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn verify(&self, report: &VerificationReport) -> Result<()> {
+        self.verify_quote_status(&report.isv_enclave_quote_status, &report.advisory_ids)?;
+
+        if let Some(max_age) = self.max_age {
+            let age = report.age()?;
+            ensure!(
+                age <= max_age,
+                "The report is too old: {:?} secs (max: {:?} secs)",
+                age.as_secs(),
+                max_age.as_secs()
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn verify_quote_status(&self, status: &str, advisory_ids: &[String]) -> Result<()> {
+        if status == QUOTE_STATUS_OK {
+            return Ok(());
+        }
+        if !self.accepted_statuses.iter().any(|s| s == status) {
+            bail!("The quote status is not accepted: {}", status);
+        }
+        if let Some(allowed) = &self.allowed_advisory_ids {
+            if let Some(id) = advisory_ids.iter().find(|id| !allowed.contains(id)) {
+                bail!(
+                    "The advisory is not allowed: {} (quote status: {})",
+                    id,
+                    status
+                );
+            }
+        }
+
+        warn!(
+            "Accepted the quote status: {}, advisories: {:?}",
+            status, advisory_ids
+        );
+        Ok(())
+    }
+}
+
+impl Default for QuoteStatusPolicy {
+    /// Accepts `GROUP_OUT_OF_DATE` with any advisories as well as `OK`
+    fn default() -> Self {
+        QuoteStatusPolicy::new().set_accepted_statuses(&[QUOTE_STATUS_GROUP_OUT_OF_DATE])
+    }
+}
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(test_verify_advisories_and_age,)
+    }
+
+    fn report(quote_status: &str, advisory_ids: &[&str], timestamp: &str) -> VerificationReport {
+        VerificationReport {
+            id: "0".to_string(),
+            timestamp: timestamp.to_string(),
+            version: 3,
+            isv_enclave_quote_status: quote_status.to_string(),
+            isv_enclave_quote_body: String::new(),
+            revocation_reason: None,
+            pse_manifest_status: None,
+            pse_manifest_hash: None,
+            platform_info_blob: None,
+            nonce: None,
+            epid_pseudonym: None,
+            advisory_url: None,
+            advisory_ids: advisory_ids.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    fn test_verify_advisories_and_age() {
+        // Reports from the future are treated as just issued
+        const NOW: &str = "2099-01-01T00:00:00.000000";
+        let policy = QuoteStatusPolicy::new()
+            .set_accepted_statuses(&[QUOTE_STATUS_SW_HARDENING_NEEDED])
+            .set_allowed_advisory_ids(&["INTEL-SA-00334"]);
+        assert!(policy
+            .verify(&report(
+                QUOTE_STATUS_SW_HARDENING_NEEDED,
+                &["INTEL-SA-00334"],
+                NOW
+            ))
+            .is_ok());
+        assert!(policy
+            .verify(&report(
+                QUOTE_STATUS_SW_HARDENING_NEEDED,
+                &["INTEL-SA-00334", "INTEL-SA-00615"],
+                NOW
+            ))
+            .is_err());
+        // Advisories of OK reports are not checked
+        assert!(policy
+            .verify(&report(QUOTE_STATUS_OK, &["INTEL-SA-00615"], NOW))
+            .is_ok());
+
+        let policy = policy.set_max_age(Duration::from_secs(60 * 60));
+        assert!(policy.verify(&report(QUOTE_STATUS_OK, &[], NOW)).is_ok());
+        assert!(policy
+            .verify(&report(QUOTE_STATUS_OK, &[], "2020-01-01T00:00:00.000000"))
+            .is_err());
+        assert!(policy
+            .verify(&report(QUOTE_STATUS_OK, &[], "invalid"))
+            .is_err());
+    }
+}
@@ -1,17 +1,21 @@
 use crate::local_anyhow::Result;
 use crate::localstd::{
     fmt::Debug,
+    string::String,
     sync::{SgxRwLockReadGuard, SgxRwLockWriteGuard},
     vec::Vec,
 };
 use codec::{Decode, Encode};
 use frame_common::{
-    crypto::{AccountId, Ciphertext},
+    crypto::{AccountId, Ciphertext, StateCommitment},
     state_types::{MemId, ReturnState, UpdatedState},
     traits::*,
 };
-use frame_treekem::{handshake::HandshakeParams, DhPubKey, EciesCiphertext, PathSecret};
-use remote_attestation::EncodedQuote;
+use frame_treekem::{
+    handshake::{HandshakeParams, PathSecretVault},
+    DhPubKey, EciesCiphertext, PathSecret,
+};
+use remote_attestation::{Attestation, AttestationStatus, AttestedEvidence, QuoteStatusPolicy};
 
 /// Execute state transition functions from runtime
 pub trait RuntimeExecutor<G: ContextOps>: Sized {
@@ -32,14 +36,23 @@ pub trait CallKindExecutor<G: ContextOps>: Sized + Encode + Decode + Debug + Clo
 }
 
 pub trait ContextOps:
-    StateOps + GroupKeyGetter + NotificationOps + IdentityKeyOps + QuoteGetter
+    StateOps + GroupKeyGetter + NotificationOps + IdentityKeyOps + AttestationOps
 {
     fn mrenclave_ver(&self) -> usize;
     fn ias_url(&self) -> &str;
     fn sub_key(&self) -> &str;
     fn spid(&self) -> &str;
-    fn server_address(&self) -> &str;
+    /// Addresses of key-vault enclaves which path secret shares are backed up to.
+    fn key_vault_endpoints(&self) -> &[String];
+    /// The number of shares required to recover a path secret.
+    fn key_vault_threshold(&self) -> usize;
     fn is_backup_enabled(&self) -> bool;
+    /// The key-vaults which own path secrets are backed up to.
+    fn key_vault(&self) -> &dyn PathSecretVault;
+    /// The remote attestation scheme of the enclave, EPID or DCAP.
+    fn attestation(&self) -> &dyn Attestation;
+    /// The quote statuses accepted from peer enclaves, e.g. of mra-tls sessions with the key-vaults.
+    fn quote_status_policy(&self) -> &QuoteStatusPolicy;
 }
 
 /// A getter of state stored in enclave memory.
@@ -67,6 +80,13 @@ pub trait StateOps {
         &self,
         state_iter: impl Iterator<Item = UpdatedState<Self::S>> + Clone,
     ) -> Option<UpdatedState<Self::S>>;
+
+    /// Commit to the whole state after processing the ciphertext, and return the root of it.
+    fn commit_state(&self, ciphertext: &Ciphertext) -> [u8; 32];
+
+    /// The commitment to the state after the last processed ciphertext,
+    /// or `None` if no ciphertext has been processed yet.
+    fn state_commitment(&self) -> Option<StateCommitment>;
 }
 
 pub trait GroupKeyGetter {
@@ -89,6 +109,24 @@ pub trait IdentityKeyOps {
     fn decrypt(&self, ciphertext: EciesCiphertext) -> Result<Vec<u8>>;
 
     fn encrypting_key(&self) -> DhPubKey;
+
+    fn verifying_key(&self) -> secp256k1::PublicKey;
+
+    /// Generate a new identity key pending until `commit_identity_key`,
+    /// returning its verifying key and the current key's signature revoking itself in favor of it.
+    fn rotate_identity_key(
+        &self,
+    ) -> Result<(
+        secp256k1::PublicKey,
+        secp256k1::Signature,
+        secp256k1::RecoveryId,
+    )>;
+
+    /// Replace the identity key with the pending one after it is registered.
+    fn commit_identity_key(&self) -> Result<()>;
+
+    /// Seal the identity key for the contract, or reload the one sealed for it before.
+    fn bind_identity_key(&self, contract_addr: &str) -> Result<()>;
 }
 
 pub trait GroupKeyOps: Sized {
@@ -112,9 +150,15 @@ pub trait GroupKeyOps: Sized {
     fn my_roster_idx(&self) -> u32;
 }
 
-pub trait QuoteGetter: Sized {
-    /// Generate Base64-encoded QUOTE data structure.
-    /// QUOTE will be sent to Attestation Service to verify SGX's status.
-    /// For more information: https://api.trustedservices.intel.com/documents/sgx-attestation-api-spec.pdf
-    fn quote(&self) -> Result<EncodedQuote>;
+pub trait AttestationOps: Sized {
+    /// Attest the enclave's identity key with the attestation scheme of the context.
+    /// The evidence is verified by IAS for EPID, or with the collateral from PCCS for DCAP.
+    fn attest(&self) -> Result<AttestedEvidence>;
+
+    /// Attest the identity key pending to replace the current one.
+    fn attest_pending_identity_key(&self) -> Result<AttestedEvidence>;
+
+    /// The age and quote status of the evidence attesting the current identity key,
+    /// or `None` if it has not been attested yet.
+    fn attestation_status(&self) -> Result<Option<AttestationStatus>>;
 }
@@ -4,6 +4,7 @@ pub mod hash;
 pub mod hkdf;
 pub mod hmac;
 pub mod secrets;
+pub mod shamir;
 
 pub const SHA256_OUTPUT_LEN: usize = 256 / 8;
 
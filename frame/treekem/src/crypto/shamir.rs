@@ -0,0 +1,180 @@
+//! Shamir's secret sharing over GF(2^8)
+//! Each byte of a secret is shared by a random polynomial of degree `threshold - 1`
+//! and a share is the evaluations of the polynomials at its non-zero index.
+
+use crate::local_anyhow::{anyhow, ensure, Result};
+use crate::localstd::vec::Vec;
+use frame_common::crypto::rand_assign;
+
+/// A share of a secret. The index is the x-coordinate and must not be zero.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecretShare {
+    index: u8,
+    value: Vec<u8>,
+}
+
+impl SecretShare {
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Encode as `index || value`
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(self.value.len() + 1);
+        res.push(self.index);
+        res.extend_from_slice(&self.value);
+        res
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        ensure!(bytes.len() > 1, "Secret share is too short");
+        ensure!(bytes[0] != 0, "Secret share index must not be zero");
+
+        Ok(SecretShare {
+            index: bytes[0],
+            value: bytes[1..].to_vec(),
+        })
+    }
+}
+
+/// Split the secret into `num_shares` shares any `threshold` of which can recover it.
+pub fn split_secret(secret: &[u8], threshold: u8, num_shares: u8) -> Result<Vec<SecretShare>> {
+    ensure!(threshold > 0, "Threshold must be at least 1");
+    ensure!(
+        threshold <= num_shares,
+        "Threshold ({:?}) must not exceed the number of shares ({:?})",
+        threshold,
+        num_shares
+    );
+
+    let mut shares: Vec<SecretShare> = (1..=num_shares)
+        .map(|index| SecretShare {
+            index,
+            value: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+    for byte in secret {
+        coefficients[0] = *byte;
+        rand_assign(&mut coefficients[1..])?;
+        for share in shares.iter_mut() {
+            share.value.push(evaluate(&coefficients, share.index));
+        }
+    }
+
+    Ok(shares)
+}
+
+/// Recover the secret from shares by Lagrange interpolation at x = 0.
+/// The number of shares must be at least the threshold used for splitting.
+pub fn combine_shares(shares: &[SecretShare]) -> Result<Vec<u8>> {
+    let first = shares.first().ok_or_else(|| anyhow!("No secret shares"))?;
+    let len = first.value.len();
+    for (i, share) in shares.iter().enumerate() {
+        ensure!(
+            share.value.len() == len,
+            "Secret shares have different lengths"
+        );
+        ensure!(
+            shares[..i].iter().all(|s| s.index != share.index),
+            "Duplicated secret share index: {:?}",
+            share.index
+        );
+    }
+
+    let mut secret = vec![0u8; len];
+    for share in shares {
+        // basis = prod_{j != i} x_j / (x_j - x_i); subtraction is XOR in GF(2^8).
+        let basis = shares
+            .iter()
+            .filter(|s| s.index != share.index)
+            .fold(1u8, |acc, s| {
+                gf_mul(acc, gf_div(s.index, s.index ^ share.index))
+            });
+        for (out, y) in secret.iter_mut().zip(share.value.iter()) {
+            *out ^= gf_mul(*y, basis);
+        }
+    }
+
+    Ok(secret)
+}
+
+/// Evaluate the polynomial at x by Horner's method.
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0u8, |acc, c| gf_mul(acc, x) ^ c)
+}
+
+/// Multiplication in GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut res = 0u8;
+    while b != 0 {
+        if b & 1 != 0 {
+            res ^= a;
+        }
+        let carry = a & 0x80;
+        a <<= 1;
+        if carry != 0 {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    res
+}
+
+/// a^254 is the multiplicative inverse of a in GF(2^8).
+fn gf_inv(a: u8) -> u8 {
+    let mut res = 1u8;
+    let mut base = a;
+    let mut exp = 254u8;
+    while exp != 0 {
+        if exp & 1 != 0 {
+            res = gf_mul(res, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    res
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    gf_mul(a, gf_inv(b))
+}
+
+#[cfg(feature = "sgx")]
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::localstd::string::String;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_split_and_combine_any_threshold_shares,
+            test_combine_insufficient_shares,
+        )
+    }
+
+    fn test_split_and_combine_any_threshold_shares() {
+        let secret = b"thirty-two bytes of path secret!".to_vec();
+        let shares = split_secret(&secret, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        let subset = vec![shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(combine_shares(&subset).unwrap(), secret);
+        assert_eq!(combine_shares(&shares).unwrap(), secret);
+
+        let decoded = SecretShare::from_bytes(&shares[1].to_bytes()).unwrap();
+        assert_eq!(decoded, shares[1]);
+    }
+
+    fn test_combine_insufficient_shares() {
+        let secret = b"thirty-two bytes of path secret!".to_vec();
+        let shares = split_secret(&secret, 3, 5).unwrap();
+        assert_ne!(combine_shares(&shares[..2]).unwrap(), secret);
+        assert!(split_secret(&secret, 4, 3).is_err());
+    }
+}
@@ -97,10 +97,15 @@ impl Handshake for GroupState {
                         );
                         PathSecret::try_from_importing(imported_path_secret)?
                     }
-                    PathSecretSource::LocalTestKV(_) => {
-                        Self::request_new_path_secret(source, self.my_roster_idx, self.epoch)?
+                    PathSecretSource::Remote(_) | PathSecretSource::LocalTestKV(_) => {
+                        Self::recover_path_secret(
+                            source,
+                            self.my_roster_idx,
+                            self.epoch,
+                            handshake.hash().as_ref(),
+                        )?
                     }
-                    _ => unimplemented!(),
+                    PathSecretSource::LocalTest(_) => unimplemented!(),
                 };
 
                 let (node_pubkey, node_privkey, _, _) = path_secret.clone().derive_node_values()?;
@@ -165,7 +170,21 @@ impl GroupState {
             )
                 })
             }
-            PathSecretSource::Remote(_url) => unimplemented!(),
+            // A new path secret is generated inside the enclave and then backed up to the key-vault.
+            PathSecretSource::Remote(_) => Ok(PathSecret::new_from_random_sgx()),
+        }
+    }
+
+    /// Recover own path secret which was created for the handshake identified by `id`.
+    pub fn recover_path_secret(
+        source: &PathSecretSource,
+        roster_idx: u32,
+        epoch: u32,
+        id: &[u8],
+    ) -> Result<PathSecret> {
+        match source {
+            PathSecretSource::Remote(vault) => vault.recover(AccessKey::new(roster_idx, epoch), id),
+            _ => Self::request_new_path_secret(source, roster_idx, epoch),
         }
     }
 
@@ -8,7 +8,7 @@ use crate::local_ring::digest::Digest;
 use crate::localstd::sync::RwLock;
 #[cfg(feature = "sgx")]
 use crate::localstd::sync::SgxRwLock as RwLock;
-use crate::localstd::{collections::HashMap, string::String, sync::Arc, vec::Vec};
+use crate::localstd::{collections::HashMap, fmt, sync::Arc, vec::Vec};
 use codec::{Decode, Encode};
 use frame_common::crypto::{ExportHandshake, ExportPathSecret};
 
@@ -108,12 +108,22 @@ impl DirectPathNodeMsg {
 #[derive(Debug, Clone)]
 pub enum PathSecretSource {
     Local,
-    Remote(String),
+    /// Own path secrets are backed up to and recovered from the remote key-vault.
+    Remote(Arc<dyn PathSecretVault>),
     /// just for test use to derive new path secret depending on current path secret.
     LocalTest(CurrentPathSecret),
     LocalTestKV(PathSecretKVS),
 }
 
+/// A key-vault which own path secrets can be recovered from.
+pub trait PathSecretVault: fmt::Debug + Send + Sync {
+    /// Recover the path secret identified by the access key and the handshake id.
+    fn recover(&self, access_key: AccessKey, id: &[u8]) -> Result<PathSecret>;
+
+    /// Back up the path secret identified by the access key and the handshake id.
+    fn store(&self, path_secret: &PathSecret, access_key: AccessKey, id: &[u8]) -> Result<()>;
+}
+
 #[derive(Debug, Clone)]
 pub struct PathSecretKVS(HashMap<AccessKey, PathSecret>);
 
@@ -127,6 +137,14 @@ impl AccessKey {
     pub fn new(roster_idx: u32, epoch: u32) -> Self {
         AccessKey { roster_idx, epoch }
     }
+
+    pub fn roster_idx(&self) -> u32 {
+        self.roster_idx
+    }
+
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
 }
 
 impl PathSecretKVS {
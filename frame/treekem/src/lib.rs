@@ -58,6 +58,7 @@ pub use crate::crypto::ecies::EciesCiphertext;
 pub use crate::crypto::secrets::SealedPathSecret;
 #[cfg(feature = "sgx")]
 pub use crate::crypto::secrets::{PathSecret, UnsealedPathSecret};
+pub use crate::crypto::shamir::{combine_shares, split_secret, SecretShare};
 #[cfg(feature = "sgx")]
 pub use crate::group_state::GroupState;
 #[cfg(feature = "sgx")]
@@ -77,6 +78,7 @@ pub mod tests {
             application::tests::run_tests(),
             crypto::ecies::tests::run_tests(),
             crypto::secrets::tests::run_tests(),
+            crypto::shamir::tests::run_tests(),
         )
     }
 }
@@ -90,18 +90,23 @@ impl EnclaveEngine for MsgReceiver {
         group_key.receiver_ratchet(roster_idx)?;
 
         // Even if an error occurs in the state transition logic here, there is no problem because the state of `app_keychain` is consistent.
-        let iter_op = Commands::<R, C>::state_transition(
+        let updated_state = Commands::<R, C>::state_transition(
             enclave_context.clone(),
             ecall_input.ciphertext(),
             group_key,
-        )?;
-        let mut output = output::ReturnUpdatedState::default();
+        )
+        .map(|iter_op| {
+            iter_op.and_then(|updated_state_iter| enclave_context.update_state(updated_state_iter))
+        });
+        // The state is committed even if the state transition fails,
+        // because the other members fail in the same way after processing the same ciphertext.
+        let state_root = enclave_context.commit_state(ecall_input.ciphertext());
 
-        if let Some(updated_state_iter) = iter_op {
-            if let Some(updated_state) = enclave_context.update_state(updated_state_iter) {
-                output.update(updated_state);
-            }
+        let mut output = output::ReturnUpdatedState::default();
+        if let Some(updated_state) = updated_state? {
+            output.update(updated_state);
         }
+        output.set_state_root(state_root);
 
         Ok(output)
     }
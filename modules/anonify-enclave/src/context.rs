@@ -1,30 +1,39 @@
 use crate::{
-    error::Result, group_key::GroupKey, identity_key::EnclaveIdentityKey, kvs::EnclaveDB,
-    notify::Notifier,
+    error::Result, group_key::GroupKey, identity_key::IdentityKeys, key_vault::KeyVaultClient,
+    kvs::EnclaveDB, notify::Notifier,
 };
 use anonify_config::IAS_ROOT_CERT;
 use anonify_io_types::*;
 use anyhow::anyhow;
+use codec::Encode;
 use frame_common::{
-    crypto::AccountId,
+    crypto::{AccountId, Ciphertext, Sha256, StateCommitment},
     state_types::{MemId, ReturnState, StateType, UpdatedState},
+    traits::Hash256,
     AccessPolicy,
 };
 use frame_enclave::EnclaveEngine;
 use frame_runtime::traits::*;
 use frame_treekem::{
-    handshake::{PathSecretKVS, PathSecretSource},
+    handshake::{PathSecretKVS, PathSecretSource, PathSecretVault},
     init_path_secret_kvs, DhPubKey, EciesCiphertext,
 };
-use remote_attestation::{EncodedQuote, QuoteTarget};
+use remote_attestation::{
+    Attestation, AttestationStatus, AttestedEvidence, CachedAttestation, Dcap, Epid,
+    QuoteStatusPolicy,
+};
 use std::{
     env,
     marker::PhantomData,
     prelude::v1::*,
     sync::{Arc, SgxRwLock, SgxRwLockReadGuard, SgxRwLockWriteGuard},
+    time::Duration,
+    untrusted::fs,
 };
 
 pub const MRENCLAVE_VERSION: usize = 0;
+/// Attested evidence is reused until it gets older than this unless ATTESTATION_MAX_AGE_SECS is set.
+const DEFAULT_ATTESTATION_MAX_AGE_SECS: u64 = 6 * 60 * 60;
 
 /// spid: Service provider ID for the ISV.
 #[derive(Clone)]
@@ -32,13 +41,19 @@ pub struct EnclaveContext {
     version: usize,
     ias_url: String,
     sub_key: String,
-    server_address: String,
+    key_vault_endpoints: Vec<String>,
+    key_vault_threshold: usize,
     spid: String,
-    identity_key: EnclaveIdentityKey,
+    identity_key: Arc<SgxRwLock<IdentityKeys>>,
     db: EnclaveDB,
+    // The identifier of the last processed ciphertext and the state root after it
+    state_commitment: Arc<SgxRwLock<Option<((u32, u32, u32), [u8; 32])>>>,
     notifier: Notifier,
     group_key: Arc<SgxRwLock<GroupKey>>,
     is_backup_enabled: bool,
+    attestation: CachedAttestation,
+    quote_status_policy: QuoteStatusPolicy,
+    key_vault: Arc<KeyVaultClient>,
 }
 
 impl ContextOps for EnclaveContext {
@@ -54,8 +69,12 @@ impl ContextOps for EnclaveContext {
         &self.sub_key
     }
 
-    fn server_address(&self) -> &str {
-        &self.server_address
+    fn key_vault_endpoints(&self) -> &[String] {
+        &self.key_vault_endpoints
+    }
+
+    fn key_vault_threshold(&self) -> usize {
+        self.key_vault_threshold
     }
 
     fn spid(&self) -> &str { &self.spid }
@@ -63,6 +82,18 @@ impl ContextOps for EnclaveContext {
     fn is_backup_enabled(&self) -> bool {
         self.is_backup_enabled
     }
+
+    fn attestation(&self) -> &dyn Attestation {
+        &self.attestation
+    }
+
+    fn quote_status_policy(&self) -> &QuoteStatusPolicy {
+        &self.quote_status_policy
+    }
+
+    fn key_vault(&self) -> &dyn PathSecretVault {
+        &*self.key_vault
+    }
 }
 
 impl StateOps for EnclaveContext {
@@ -112,6 +143,18 @@ impl StateOps for EnclaveContext {
             .for_each(|s| self.db.insert_by_updated_state(s));
         state_iter.find(|s| self.is_notified(&s.account_id))
     }
+
+    fn commit_state(&self, ciphertext: &Ciphertext) -> [u8; 32] {
+        let state_root = self.db.state_root();
+        *self.state_commitment.write().unwrap() = Some((ciphertext.id(), state_root));
+        state_root
+    }
+
+    fn state_commitment(&self) -> Option<StateCommitment> {
+        let (ciphertext_id, state_root) = (*self.state_commitment.read().unwrap())?;
+        let my_roster_idx = self.read_group_key().my_roster_idx();
+        Some(StateCommitment::new(my_roster_idx, ciphertext_id, state_root))
+    }
 }
 
 impl GroupKeyGetter for EnclaveContext {
@@ -141,24 +184,87 @@ impl IdentityKeyOps for EnclaveContext {
     /// This signature is used to verify enclave's program dependencies and
     /// should be verified in the public available place such as smart contract on blockchain.
     fn sign(&self, msg: &[u8]) -> anyhow::Result<(secp256k1::Signature, secp256k1::RecoveryId)> {
-        self.identity_key.sign(msg).map_err(Into::into)
+        self.identity_key
+            .read()
+            .unwrap()
+            .current()
+            .sign(msg)
+            .map_err(Into::into)
     }
 
     fn decrypt(&self, ciphertext: EciesCiphertext) -> anyhow::Result<Vec<u8>> {
-        self.identity_key.decrypt(ciphertext).map_err(Into::into)
+        self.identity_key
+            .read()
+            .unwrap()
+            .current()
+            .decrypt(ciphertext)
+            .map_err(Into::into)
     }
 
     fn encrypting_key(&self) -> DhPubKey {
-        self.identity_key.encrypting_key()
+        self.identity_key.read().unwrap().current().encrypting_key()
+    }
+
+    fn verifying_key(&self) -> secp256k1::PublicKey {
+        self.identity_key.read().unwrap().current().verifying_key()
+    }
+
+    fn rotate_identity_key(
+        &self,
+    ) -> anyhow::Result<(
+        secp256k1::PublicKey,
+        secp256k1::Signature,
+        secp256k1::RecoveryId,
+    )> {
+        self.identity_key
+            .write()
+            .unwrap()
+            .rotate()
+            .map_err(Into::into)
+    }
+
+    fn commit_identity_key(&self) -> anyhow::Result<()> {
+        self.identity_key
+            .write()
+            .unwrap()
+            .commit()
+            .map_err(Into::into)
+    }
+
+    fn bind_identity_key(&self, contract_addr: &str) -> anyhow::Result<()> {
+        self.identity_key
+            .write()
+            .unwrap()
+            .bind(contract_addr)
+            .map_err(Into::into)
     }
 }
 
-impl QuoteGetter for EnclaveContext {
-    fn quote(&self) -> anyhow::Result<EncodedQuote> {
-        let report_data = &self.identity_key.report_data()?;
-        QuoteTarget::new()?
-            .set_enclave_report(&report_data)?
-            .create_quote(&self.spid)
+impl AttestationOps for EnclaveContext {
+    fn attest(&self) -> anyhow::Result<AttestedEvidence> {
+        let report_data = &self.identity_key.read().unwrap().current().report_data()?;
+        self.attestation
+            .attest(&report_data)
+            .map_err(|e| anyhow!("{:?}", e))
+    }
+
+    fn attest_pending_identity_key(&self) -> anyhow::Result<AttestedEvidence> {
+        let report_data = &self
+            .identity_key
+            .read()
+            .unwrap()
+            .pending()
+            .ok_or_else(|| anyhow!("No identity key is pending to be attested"))?
+            .report_data()?;
+        self.attestation
+            .attest(&report_data)
+            .map_err(|e| anyhow!("{:?}", e))
+    }
+
+    fn attestation_status(&self) -> anyhow::Result<Option<AttestationStatus>> {
+        let report_data = &self.identity_key.read().unwrap().current().report_data()?;
+        self.attestation
+            .status(&report_data)
             .map_err(|e| anyhow!("{:?}", e))
     }
 }
@@ -166,9 +272,68 @@ impl QuoteGetter for EnclaveContext {
 // TODO: Consider SGX_ERROR_BUSY.
 impl EnclaveContext {
     pub fn new(spid: String, is_backup_enabled: bool) -> Result<Self> {
-        let identity_key = EnclaveIdentityKey::new()?;
+        let identity_key = Arc::new(SgxRwLock::new(IdentityKeys::new()?));
         let db = EnclaveDB::new();
+        let ias_url = env::var("IAS_URL")?;
+        let sub_key = env::var("SUB_KEY")?;
+        let key_vault_endpoints: Vec<String> = env::var("KEY_VAULT_ENDPOINTS")
+            .or_else(|_| env::var("MRA_TLS_SERVER_ADDRESS"))?
+            .split(',')
+            .map(|e| e.trim().to_string())
+            .filter(|e| !e.is_empty())
+            .collect();
+        // Defaults to the majority of the key-vaults
+        let key_vault_threshold: usize = match env::var("KEY_VAULT_THRESHOLD") {
+            Ok(threshold) => threshold
+                .parse()
+                .map_err(|_| anyhow!("Failed to parse KEY_VAULT_THRESHOLD to usize"))?,
+            Err(_) => key_vault_endpoints.len() / 2 + 1,
+        };
+        if key_vault_threshold == 0
+            || key_vault_threshold > key_vault_endpoints.len()
+            || key_vault_endpoints.len() > u8::max_value() as usize
+        {
+            return Err(anyhow!(
+                "Invalid key-vault threshold ({:?}) for {:?} endpoints",
+                key_vault_threshold,
+                key_vault_endpoints.len()
+            )
+            .into());
+        }
 
+        let quote_status_policy = quote_status_policy_from_env()?;
+        // EPID with IAS by default, or DCAP with the collateral from PCCS if ATTESTATION_TYPE is "dcap".
+        // The DER-encoded Intel SGX Root CA certificate for DCAP is read from DCAP_ROOT_CERT_PATH.
+        let scheme: Arc<dyn Attestation> = match env::var("ATTESTATION_TYPE") {
+            Ok(ref ty) if ty == "dcap" => Arc::new(Dcap::new(
+                env::var("PCCS_URL")?,
+                fs::read(env::var("DCAP_ROOT_CERT_PATH")?)?,
+            )),
+            _ => Arc::new(
+                Epid::new(
+                    spid.clone(),
+                    ias_url.clone(),
+                    sub_key.clone(),
+                    IAS_ROOT_CERT.to_vec(),
+                )
+                .set_quote_status_policy(quote_status_policy.clone()),
+            ),
+        };
+        let max_age_secs: u64 = match env::var("ATTESTATION_MAX_AGE_SECS") {
+            Ok(secs) => secs
+                .parse()
+                .map_err(|_| anyhow!("Failed to parse ATTESTATION_MAX_AGE_SECS to u64"))?,
+            Err(_) => DEFAULT_ATTESTATION_MAX_AGE_SECS,
+        };
+        let attestation = CachedAttestation::new(scheme, Duration::from_secs(max_age_secs));
+        let key_vault = Arc::new(KeyVaultClient::new(
+            key_vault_endpoints.clone(),
+            key_vault_threshold,
+            Arc::new(attestation.clone()),
+            quote_status_policy.clone(),
+        )?);
+
+        // Own path secrets are recovered from the key-vaults if AUDITOR_ENDPOINT is set.
         let source = match env::var("AUDITOR_ENDPOINT") {
             Err(_) => PathSecretSource::Local,
             Ok(test) if test == "test" => {
@@ -178,7 +343,7 @@ impl EnclaveContext {
                 init_path_secret_kvs(&mut kvs, UNTIL_ROSTER_IDX, UNTIL_EPOCH);
                 PathSecretSource::LocalTestKV(kvs)
             }
-            Ok(url) => PathSecretSource::Remote(url),
+            Ok(_) => PathSecretSource::Remote(key_vault.clone()),
         };
 
         let my_roster_idx: usize = env::var("MY_ROSTER_IDX")
@@ -197,25 +362,54 @@ impl EnclaveContext {
         )?));
         let notifier = Notifier::new();
 
-        let ias_url = env::var("IAS_URL")?;
-        let sub_key = env::var("SUB_KEY")?;
-        let server_address = env::var("MRA_TLS_SERVER_ADDRESS")?;
-
         Ok(EnclaveContext {
             spid,
             identity_key,
             db,
+            state_commitment: Arc::new(SgxRwLock::new(None)),
             notifier,
             group_key,
             version: MRENCLAVE_VERSION,
             ias_url,
             sub_key,
-            server_address,
+            key_vault_endpoints,
+            key_vault_threshold,
             is_backup_enabled,
+            attestation,
+            quote_status_policy,
+            key_vault,
         })
     }
 }
 
+/// Quote statuses other than `OK` are accepted only after risk review of the platforms,
+/// so they are configured by comma-separated ACCEPTED_QUOTE_STATUSES and ALLOWED_ADVISORY_IDS.
+/// IAS reports older than MAX_REPORT_AGE_SECS are rejected if it is set.
+fn quote_status_policy_from_env() -> Result<QuoteStatusPolicy> {
+    let mut policy = QuoteStatusPolicy::default();
+    if let Ok(statuses) = env::var("ACCEPTED_QUOTE_STATUSES") {
+        policy = policy.set_accepted_statuses(&split_env_list(&statuses));
+    }
+    if let Ok(advisory_ids) = env::var("ALLOWED_ADVISORY_IDS") {
+        policy = policy.set_allowed_advisory_ids(&split_env_list(&advisory_ids));
+    }
+    if let Ok(secs) = env::var("MAX_REPORT_AGE_SECS") {
+        let secs: u64 = secs
+            .parse()
+            .map_err(|_| anyhow!("Failed to parse MAX_REPORT_AGE_SECS to u64"))?;
+        policy = policy.set_max_age(Duration::from_secs(secs));
+    }
+
+    Ok(policy)
+}
+
+fn split_env_list(list: &str) -> Vec<&str> {
+    list.split(',')
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
 #[derive(Debug, Clone)]
 pub struct GetState<AP: AccessPolicy> {
     phantom: PhantomData<AP>,
@@ -249,6 +443,70 @@ impl<AP: AccessPolicy> EnclaveEngine for GetState<AP> {
     }
 }
 
+/// Sign the commitment to the state after the last processed ciphertext with the identity key,
+/// which is published so that the members are able to check they have computed the same state.
+#[derive(Debug, Clone)]
+pub struct StateCommitmentGetter;
+
+impl EnclaveEngine for StateCommitmentGetter {
+    type EI = input::CallStateCommitment;
+    type EO = output::ReturnStateCommitment;
+
+    fn handle<R, C>(
+        _ecall_input: Self::EI,
+        enclave_context: &C,
+        _max_mem_size: usize,
+    ) -> anyhow::Result<Self::EO>
+    where
+        R: RuntimeExecutor<C, S = StateType>,
+        C: ContextOps<S = StateType> + Clone,
+    {
+        let commitment = enclave_context
+            .state_commitment()
+            .ok_or_else(|| anyhow!("No ciphertext has been processed yet"))?;
+        // Verified by `publishStateCommitment` in the contract as `sha256(commitment)`.
+        let msg = Sha256::hash(&commitment.encode());
+        let (enclave_sig, recovery_id) = enclave_context.sign(msg.as_bytes())?;
+
+        Ok(output::ReturnStateCommitment::new(
+            commitment,
+            enclave_sig,
+            recovery_id,
+        ))
+    }
+}
+
+/// Get the status of the evidence attesting the identity key
+#[derive(Debug, Clone)]
+pub struct AttestationStatusGetter;
+
+impl EnclaveEngine for AttestationStatusGetter {
+    type EI = input::GetAttestationStatus;
+    type EO = output::ReturnAttestationStatus;
+
+    fn handle<R, C>(
+        _ecall_input: Self::EI,
+        enclave_context: &C,
+        _max_mem_size: usize,
+    ) -> anyhow::Result<Self::EO>
+    where
+        R: RuntimeExecutor<C, S = StateType>,
+        C: ContextOps<S = StateType> + Clone,
+    {
+        let output = match enclave_context.attestation_status()? {
+            Some(status) => output::ReturnAttestationStatus::new(
+                status.age.as_secs(),
+                status.max_age.as_secs(),
+                status.quote_status,
+                status.is_refreshing,
+            ),
+            None => output::ReturnAttestationStatus::default(),
+        };
+
+        Ok(output)
+    }
+}
+
 /// A report registration engine
 #[derive(Debug, Clone)]
 pub struct ReportRegistration;
@@ -266,20 +524,14 @@ impl EnclaveEngine for ReportRegistration {
         R: RuntimeExecutor<C, S = StateType>,
         C: ContextOps<S = StateType> + Clone,
     {
-        let ias_url = enclave_context.ias_url();
-        let sub_key = enclave_context.sub_key();
-        let attested_report = enclave_context.quote()?.remote_attestation(
-            ias_url,
-            sub_key,
-            IAS_ROOT_CERT.to_vec(),
-        )?;
+        let attested_evidence = enclave_context.attest()?;
 
         let mrenclave_ver = enclave_context.mrenclave_ver();
         let my_roster_idx = enclave_context.read_group_key().my_roster_idx();
 
         Ok(output::ReturnRegisterReport::new(
-            attested_report.report().to_vec(),
-            attested_report.report_sig().to_vec(),
+            attested_evidence.report().to_vec(),
+            attested_evidence.report_sig().to_vec(),
             mrenclave_ver,
             my_roster_idx,
         ))
@@ -1,15 +1,16 @@
-use anonify_config::IAS_ROOT_CERT;
 use anonify_io_types::*;
 use anyhow::{anyhow, Result};
 use codec::{Decode, Encode};
 use frame_common::{
-    crypto::{BackupPathSecret, Sha256},
+    crypto::{ExportPathSecret, Sha256},
     state_types::StateType,
 };
 use frame_enclave::EnclaveEngine;
-use frame_mra_tls::{AttestedTlsConfig, Client, ClientConfig};
 use frame_runtime::traits::*;
-use frame_treekem::handshake::HandshakeParams;
+use frame_treekem::{
+    handshake::{AccessKey, HandshakeParams},
+    PathSecret,
+};
 
 /// A add handshake Sender
 #[derive(Debug, Clone)]
@@ -28,14 +29,7 @@ impl EnclaveEngine for JoinGroupSender {
         R: RuntimeExecutor<C, S = StateType>,
         C: ContextOps<S = StateType> + Clone,
     {
-        let ias_url = enclave_context.ias_url();
-        let sub_key = enclave_context.sub_key();
-        let spid = enclave_context.spid();
-        let attested_report = enclave_context.quote()?.remote_attestation(
-            ias_url,
-            sub_key,
-            IAS_ROOT_CERT.to_vec(),
-        )?;
+        let attested_evidence = enclave_context.attest()?;
 
         let mrenclave_ver = enclave_context.mrenclave_ver();
         let group_key = &*enclave_context.read_group_key();
@@ -47,21 +41,18 @@ impl EnclaveEngine for JoinGroupSender {
         let export_handshake = handshake.into_export();
 
         if enclave_context.is_backup_enabled() {
-            let backup_path_secret = BackupPathSecret::new(path_secret.as_bytes().to_vec(), epoch);
-
-            let attested_tls_config =
-                AttestedTlsConfig::new_by_ra(&spid, &ias_url, &sub_key, IAS_ROOT_CERT.to_vec())?;
-
-            let client_config = ClientConfig::from_attested_tls_config(attested_tls_config)?
-                .set_attestation_report_verifier(IAS_ROOT_CERT.to_vec());
-            let mra_tls_server_address = enclave_context.server_address();
-            let mut mra_tls_client = Client::new(mra_tls_server_address, client_config).unwrap();
-            let _resp: serde_json::Value = mra_tls_client.send_json(backup_path_secret)?;
+            backup_path_secret(
+                enclave_context,
+                &path_secret,
+                &export_path_secret,
+                epoch,
+                export_handshake.roster_idx(),
+            )?;
         }
 
         Ok(output::ReturnJoinGroup::new(
-            attested_report.report().to_vec(),
-            attested_report.report_sig().to_vec(),
+            attested_evidence.report().to_vec(),
+            attested_evidence.report_sig().to_vec(),
             export_handshake.encode(),
             mrenclave_ver,
             export_handshake.roster_idx(),
@@ -90,10 +81,22 @@ impl EnclaveEngine for HandshakeSender {
         let group_key = &*enclave_context.read_group_key();
         let (handshake, path_secret) = group_key.create_handshake()?;
         let epoch = handshake.prior_epoch();
-        let export_path_secret =
-            path_secret.try_into_exporting(epoch, handshake.hash().as_ref())?;
+        let export_path_secret = path_secret
+            .clone()
+            .try_into_exporting(epoch, handshake.hash().as_ref())?;
         let export_handshake = handshake.into_export();
         let roster_idx = export_handshake.roster_idx();
+
+        if enclave_context.is_backup_enabled() {
+            backup_path_secret(
+                enclave_context,
+                &path_secret,
+                &export_path_secret,
+                epoch,
+                roster_idx,
+            )?;
+        }
+
         let msg = Sha256::hash_with_u32(&export_handshake.encode(), roster_idx);
         let sig = enclave_context.sign(msg.as_bytes())?;
         let enclave_sig = sig.0;
@@ -109,6 +112,21 @@ impl EnclaveEngine for HandshakeSender {
     }
 }
 
+/// Store shares of own new path secret to the key-vaults so that it can be recovered when processing the handshake.
+fn backup_path_secret<C: ContextOps>(
+    enclave_context: &C,
+    path_secret: &PathSecret,
+    export_path_secret: &ExportPathSecret,
+    epoch: u32,
+    roster_idx: u32,
+) -> Result<()> {
+    enclave_context.key_vault().store(
+        path_secret,
+        AccessKey::new(roster_idx, epoch),
+        &export_path_secret.id(),
+    )
+}
+
 /// A handshake receiver
 #[derive(Debug, Clone)]
 pub struct HandshakeReceiver;
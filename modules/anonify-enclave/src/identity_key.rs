@@ -2,16 +2,31 @@
 
 use crate::error::Result;
 use anonify_io_types::*;
+use anyhow::anyhow;
 use codec::Encode;
-use frame_common::{crypto::rand_assign, state_types::StateType, traits::Keccak256};
+use frame_common::{
+    crypto::{rand_assign, Sha256},
+    state_types::StateType,
+    traits::Keccak256,
+};
 use frame_enclave::EnclaveEngine;
 use frame_runtime::traits::*;
 use frame_treekem::{DhPrivateKey, DhPubKey, EciesCiphertext};
+use log::warn;
 use secp256k1::{
     self, util::SECRET_KEY_SIZE, Message, PublicKey, RecoveryId, SecretKey, Signature,
 };
-use sgx_types::sgx_report_data_t;
-use std::prelude::v1::Vec;
+use sgx_tseal::SgxSealedData;
+use sgx_types::{
+    sgx_attributes_t, sgx_report_data_t, sgx_sealed_data_t, SGX_KEYPOLICY_MRENCLAVE,
+    TSEAL_DEFAULT_FLAGSMASK, TSEAL_DEFAULT_MISCMASK,
+};
+use std::{
+    env,
+    path::{Path, PathBuf},
+    prelude::v1::Vec,
+    untrusted::{fs, path::PathEx},
+};
 
 const HASHED_PUBKEY_SIZE: usize = 20;
 const ENCRYPTING_KEY_SIZE: usize = 33;
@@ -35,12 +50,194 @@ impl EnclaveEngine for EncryptingKeyGetter {
         C: ContextOps<S = StateType> + Clone,
     {
         let encrypting_key = enclave_context.encrypting_key();
+        // The evidence is cached, so IAS is not requested on every call.
+        let attested_evidence = enclave_context.attest()?;
+
+        Ok(output::ReturnEncryptingKey::new(
+            encrypting_key,
+            attested_evidence.report().to_vec(),
+            attested_evidence.report_sig().to_vec(),
+            attested_evidence.report_cert().to_vec(),
+        ))
+    }
+}
+
+/// Generate a new identity key which replaces the current one once it is registered.
+/// The current key signs its revocation in favor of the new key,
+/// so that the contract revokes it only after the new key is registered with the returned report.
+/// The current key is kept in use until `IdentityKeyCommitter` is called after the registration.
+#[derive(Debug, Clone)]
+pub struct IdentityKeyRotator;
+
+impl EnclaveEngine for IdentityKeyRotator {
+    type EI = input::CallRotateIdentityKey;
+    type EO = output::ReturnRotateIdentityKey;
+
+    fn handle<R, C>(
+        _ecall_input: Self::EI,
+        enclave_context: &C,
+        _max_mem_size: usize,
+    ) -> anyhow::Result<Self::EO>
+    where
+        R: RuntimeExecutor<C, S = StateType>,
+        C: ContextOps<S = StateType> + Clone,
+    {
+        let old_verifying_key = verifying_key_address(&enclave_context.verifying_key());
+        let old_encrypting_key = enclave_context.encrypting_key().encode();
+        let (new_verifying_key, revocation_sig, recovery_id) =
+            enclave_context.rotate_identity_key()?;
+        let new_verifying_key = verifying_key_address(&new_verifying_key);
+        let attested_evidence = enclave_context.attest_pending_identity_key()?;
+
+        let mrenclave_ver = enclave_context.mrenclave_ver();
+        let my_roster_idx = enclave_context.read_group_key().my_roster_idx();
+
+        Ok(output::ReturnRotateIdentityKey::new(
+            output::ReturnRegisterReport::new(
+                attested_evidence.report().to_vec(),
+                attested_evidence.report_sig().to_vec(),
+                mrenclave_ver,
+                my_roster_idx,
+            ),
+            old_verifying_key,
+            old_encrypting_key,
+            new_verifying_key,
+            revocation_sig,
+            recovery_id,
+        ))
+    }
+}
+
+/// Replace the identity key with the one generated by `IdentityKeyRotator`
+/// after the host has registered it.
+#[derive(Debug, Clone)]
+pub struct IdentityKeyCommitter;
+
+impl EnclaveEngine for IdentityKeyCommitter {
+    type EI = input::CallCommitIdentityKey;
+    type EO = output::Empty;
+
+    fn handle<R, C>(
+        _ecall_input: Self::EI,
+        enclave_context: &C,
+        _max_mem_size: usize,
+    ) -> anyhow::Result<Self::EO>
+    where
+        R: RuntimeExecutor<C, S = StateType>,
+        C: ContextOps<S = StateType> + Clone,
+    {
+        enclave_context.commit_identity_key()?;
+
+        Ok(output::Empty::default())
+    }
+}
+
+/// Seal the identity key to the path for the contract, or load the one sealed to it before.
+/// Each contract served by the node has its own sealed key,
+/// so the enclaves of different contracts never share an identity key.
+#[derive(Debug, Clone)]
+pub struct IdentityKeyBinder;
+
+impl EnclaveEngine for IdentityKeyBinder {
+    type EI = input::CallBindIdentityKey;
+    type EO = output::Empty;
+
+    fn handle<R, C>(
+        ecall_input: Self::EI,
+        enclave_context: &C,
+        _max_mem_size: usize,
+    ) -> anyhow::Result<Self::EO>
+    where
+        R: RuntimeExecutor<C, S = StateType>,
+        C: ContextOps<S = StateType> + Clone,
+    {
+        enclave_context.bind_identity_key(ecall_input.contract_addr())?;
+
+        Ok(output::Empty::default())
+    }
+}
+
+/// The identity key in use and the one generated to replace it.
+/// The new key is kept pending until it is registered in the contract,
+/// because the enclave cannot be verified with an unregistered key.
+#[derive(Debug)]
+pub struct IdentityKeys {
+    current: EnclaveIdentityKey,
+    pending: Option<EnclaveIdentityKey>,
+    path: Option<PathBuf>,
+}
+
+impl IdentityKeys {
+    /// Generate a new identity key, which is not persisted until it is bound to a contract.
+    pub fn new() -> Result<Self> {
+        Ok(IdentityKeys {
+            current: EnclaveIdentityKey::new()?,
+            pending: None,
+            path: None,
+        })
+    }
+
+    /// Bind the identity key to the contract, sealing it to IDENTITY_KEY_DIR/<contract_addr>.sealed.
+    /// The key already sealed for the contract replaces the current one,
+    /// so that the key registered before restarts is reused.
+    pub fn bind(&mut self, contract_addr: &str) -> Result<()> {
+        let path = identity_key_path(contract_addr)?;
+        self.bind_at(path)
+    }
+
+    fn bind_at(&mut self, path: Option<PathBuf>) -> Result<()> {
+        if let Some(identity_key) = EnclaveIdentityKey::load_at(path.as_deref()) {
+            self.current = identity_key;
+            self.pending = None;
+        } else {
+            self.current.store_at(path.as_deref())?;
+        }
+        self.path = path;
+
+        Ok(())
+    }
+
+    pub fn current(&self) -> &EnclaveIdentityKey {
+        &self.current
+    }
 
-        Ok(output::ReturnEncryptingKey::new(encrypting_key))
+    pub fn pending(&self) -> Option<&EnclaveIdentityKey> {
+        self.pending.as_ref()
+    }
+
+    /// Generate a new pending key, replacing the previous pending one if the rotation has not completed,
+    /// and sign the revocation of the current key in favor of it.
+    pub fn rotate(&mut self) -> Result<(PublicKey, Signature, RecoveryId)> {
+        let new_key = EnclaveIdentityKey::new()?;
+        let (revocation_sig, recovery_id) = self.current.sign_revocation(&new_key)?;
+        let new_verifying_key = new_key.verifying_key();
+        self.pending = Some(new_key);
+
+        Ok((new_verifying_key, revocation_sig, recovery_id))
+    }
+
+    /// Replace the current key with the pending one.
+    /// The new key is sealed before replacing the old one in memory, so that it is reloaded after restarts.
+    pub fn commit(&mut self) -> Result<()> {
+        let new_key = self
+            .pending
+            .take()
+            .ok_or_else(|| anyhow!("No identity key is pending to be committed"))?;
+        if let Err(e) = new_key.store_at(self.path.as_deref()) {
+            self.pending = Some(new_key);
+            return Err(e);
+        }
+        self.current = new_key;
+
+        Ok(())
     }
 }
 
 /// Enclave Identity Key
+/// If IDENTITY_KEY_DIR is set, it is sealed to the file of the contract with the MRENCLAVE-based sealing key,
+/// so that the same key is reloaded after restarts of the same enclave
+/// while other enclave versions cannot use it.
+/// Otherwise a new key is generated on every start.
 #[derive(Debug, Clone, Default, PartialEq)]
 pub struct EnclaveIdentityKey {
     signing_privkey: SecretKey,
@@ -66,6 +263,57 @@ impl EnclaveIdentityKey {
         })
     }
 
+    /// Load the sealed identity key if it exists.
+    /// A key which cannot be unsealed, e.g. sealed by the previous MRENCLAVE after an upgrade,
+    /// is ignored and overwritten by the current one, which is registered by the new enclave anyway.
+    fn load_at(path: Option<&Path>) -> Option<Self> {
+        let path = path?;
+        if !path.exists() {
+            return None;
+        }
+        match Self::load(path) {
+            Ok(identity_key) => Some(identity_key),
+            Err(e) => {
+                warn!(
+                    "Failed to unseal the identity key at {:?}, so replace it with the current one: {:?}",
+                    path, e
+                );
+                None
+            }
+        }
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let mut sealed = fs::read(path)?;
+        Self::decode(&unseal(&mut sealed)?)
+    }
+
+    /// Seal the identity key to the path, overwriting the old one.
+    /// Nothing is persisted if it is not set.
+    fn store_at(&self, path: Option<&Path>) -> Result<()> {
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, seal(&self.encode())?)?;
+
+        Ok(())
+    }
+
+    /// Sign the revocation of this key in favor of the new one,
+    /// which is verified by `revokeKeys` in the contract as
+    /// `sha256(abi.encodePacked(verifyingKey, encryptingKey, newVerifyingKey))`.
+    pub fn sign_revocation(&self, new_key: &EnclaveIdentityKey) -> Result<(Signature, RecoveryId)> {
+        let mut msg = self.verifying_key_into_array().to_vec();
+        msg.extend_from_slice(&self.encrypting_key_into_vec());
+        msg.extend_from_slice(&new_key.verifying_key_into_array());
+
+        self.sign(Sha256::hash(&msg).as_bytes())
+    }
+
     pub fn sign(&self, msg: &[u8]) -> Result<(Signature, RecoveryId)> {
         let msg = Message::parse_slice(msg)?;
         let sig = secp256k1::sign(&msg, &self.signing_privkey)?;
@@ -104,12 +352,7 @@ impl EnclaveIdentityKey {
     }
 
     fn verifying_key_into_array(&self) -> [u8; HASHED_PUBKEY_SIZE] {
-        let pubkey = &self.verifying_key().serialize()[1..];
-        let account_id = &pubkey.keccak256()[12..];
-        assert_eq!(account_id.len(), HASHED_PUBKEY_SIZE);
-        let mut res = [0u8; HASHED_PUBKEY_SIZE];
-        res.copy_from_slice(account_id);
-        res
+        verifying_key_address(&self.verifying_key())
     }
 
     fn encrypting_key_into_vec(&self) -> Vec<u8> {
@@ -117,4 +360,191 @@ impl EnclaveIdentityKey {
         assert_eq!(res.len(), ENCRYPTING_KEY_SIZE);
         res
     }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut res = self.signing_privkey.serialize().to_vec();
+        res.extend_from_slice(&self.decrypting_privkey.encode());
+        res
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() != SECRET_KEY_SIZE * 2 {
+            return Err(
+                anyhow!("Invalid length of the sealed identity key: {}", bytes.len()).into(),
+            );
+        }
+        let signing_privkey = SecretKey::parse_slice(&bytes[..SECRET_KEY_SIZE])?;
+        let decrypting_privkey = DhPrivateKey::from_bytes(&bytes[SECRET_KEY_SIZE..])?;
+
+        Ok(EnclaveIdentityKey {
+            signing_privkey,
+            decrypting_privkey,
+        })
+    }
+}
+
+/// The address-formatted verifying key, which is registered in the contract.
+fn verifying_key_address(verifying_key: &PublicKey) -> [u8; HASHED_PUBKEY_SIZE] {
+    let pubkey = &verifying_key.serialize()[1..];
+    let account_id = &pubkey.keccak256()[12..];
+    assert_eq!(account_id.len(), HASHED_PUBKEY_SIZE);
+    let mut res = [0u8; HASHED_PUBKEY_SIZE];
+    res.copy_from_slice(account_id);
+    res
+}
+
+/// The path the identity key for the contract is sealed to, or `None` if IDENTITY_KEY_DIR is not set.
+fn identity_key_path(contract_addr: &str) -> Result<Option<PathBuf>> {
+    let dir = match env::var("IDENTITY_KEY_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => return Ok(None),
+    };
+    if contract_addr.is_empty() || !contract_addr.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(anyhow!(
+            "Invalid contract address for the identity key: {}",
+            contract_addr
+        )
+        .into());
+    }
+
+    Ok(Some(dir.join(format!("{}.sealed", contract_addr))))
+}
+
+/// Seal with MRENCLAVE policy because the identity key attests this enclave's code.
+#[allow(clippy::cast_ptr_alignment)]
+fn seal(plaintext: &[u8]) -> Result<Vec<u8>> {
+    let additional = [0u8; 0];
+    let attribute_mask = sgx_attributes_t {
+        flags: TSEAL_DEFAULT_FLAGSMASK,
+        xfrm: 0,
+    };
+    let sealed_data = SgxSealedData::<[u8]>::seal_data_ex(
+        SGX_KEYPOLICY_MRENCLAVE,
+        attribute_mask,
+        TSEAL_DEFAULT_MISCMASK,
+        &additional,
+        plaintext,
+    )?;
+    let sealed_len = SgxSealedData::<[u8]>::calc_raw_sealed_data_size(0, plaintext.len() as u32);
+
+    let mut res = vec![0u8; sealed_len as usize];
+    unsafe {
+        sealed_data
+            .to_raw_sealed_data_t(res.as_mut_ptr() as *mut sgx_sealed_data_t, sealed_len)
+            .ok_or_else(|| anyhow!("Failed to seal the identity key"))?;
+    }
+
+    Ok(res)
+}
+
+#[allow(clippy::cast_ptr_alignment)]
+fn unseal(sealed: &mut [u8]) -> Result<Vec<u8>> {
+    let sealed_data = unsafe {
+        SgxSealedData::<[u8]>::from_raw_sealed_data_t(
+            sealed.as_mut_ptr() as *mut sgx_sealed_data_t,
+            sealed.len() as u32,
+        )
+    }
+    .ok_or_else(|| anyhow!("Invalid sealed identity key"))?;
+    let unsealed_data = sealed_data.unseal_data()?;
+
+    Ok(unsealed_data.get_decrypt_txt().to_vec())
+}
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_seal_unseal_identity_key,
+            test_bind_after_unseal_error,
+            test_bind_identity_key_per_contract,
+            test_rotate_and_commit_identity_key,
+        )
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let mut nonce = [0u8; 8];
+        rand_assign(&mut nonce).unwrap();
+        PathBuf::from(format!(
+            "/tmp/anonify-{}-{}",
+            name,
+            u64::from_le_bytes(nonce)
+        ))
+    }
+
+    fn test_seal_unseal_identity_key() {
+        let identity_key = EnclaveIdentityKey::new().unwrap();
+        let mut sealed = seal(&identity_key.encode()).unwrap();
+        let unsealed = EnclaveIdentityKey::decode(&unseal(&mut sealed).unwrap()).unwrap();
+
+        assert_eq!(unsealed, identity_key);
+        assert_eq!(
+            unsealed.report_data().unwrap().d[..],
+            identity_key.report_data().unwrap().d[..]
+        );
+    }
+
+    fn test_bind_after_unseal_error() {
+        let path = temp_path("identity_key");
+        // e.g. sealed by another MRENCLAVE
+        fs::write(&path, &[1u8; 64]).unwrap();
+
+        let mut keys = IdentityKeys::new().unwrap();
+        keys.bind_at(Some(path.clone())).unwrap();
+        // The current key is sealed in place of the broken one.
+        assert_eq!(&EnclaveIdentityKey::load(&path).unwrap(), keys.current());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    fn test_bind_identity_key_per_contract() {
+        let path = temp_path("identity_key");
+        let other_path = temp_path("identity_key");
+        let mut keys = IdentityKeys::new().unwrap();
+        keys.bind_at(Some(path.clone())).unwrap();
+        let bound_key = keys.current().clone();
+
+        // The enclave of another contract seals its own key.
+        let mut other_keys = IdentityKeys::new().unwrap();
+        other_keys.bind_at(Some(other_path.clone())).unwrap();
+        assert_ne!(other_keys.current(), &bound_key);
+        assert_eq!(
+            &EnclaveIdentityKey::load(&other_path).unwrap(),
+            other_keys.current()
+        );
+
+        // A restarted enclave reuses the key sealed for the contract.
+        let mut restarted = IdentityKeys::new().unwrap();
+        restarted.bind_at(Some(path.clone())).unwrap();
+        assert_eq!(restarted.current(), &bound_key);
+
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&other_path).unwrap();
+    }
+
+    fn test_rotate_and_commit_identity_key() {
+        let path = temp_path("identity_key");
+        let mut keys = IdentityKeys::new().unwrap();
+        keys.bind_at(Some(path.clone())).unwrap();
+        let old_key = keys.current().clone();
+        assert!(keys.commit().is_err());
+
+        // The current key is kept until the new one is committed after its registration.
+        let (new_verifying_key, _, _) = keys.rotate().unwrap();
+        assert_eq!(keys.current(), &old_key);
+        assert_eq!(keys.pending().unwrap().verifying_key(), new_verifying_key);
+        assert_eq!(EnclaveIdentityKey::load(&path).unwrap(), old_key);
+
+        // A failed rotation is retried with another new key.
+        let (new_verifying_key, _, _) = keys.rotate().unwrap();
+        keys.commit().unwrap();
+        assert_eq!(keys.current().verifying_key(), new_verifying_key);
+        assert!(keys.pending().is_none());
+        assert_eq!(&EnclaveIdentityKey::load(&path).unwrap(), keys.current());
+
+        fs::remove_file(&path).unwrap();
+    }
 }
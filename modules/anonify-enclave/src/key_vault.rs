@@ -0,0 +1,267 @@
+use anonify_config::{KEY_VAULT_RECOVER_METHOD, KEY_VAULT_STORE_METHOD};
+use anyhow::{anyhow, Result};
+use frame_common::{
+    crypto::{BackupPathSecret, RecoverPathSecret, Sha256, EXPORT_ID_SIZE},
+    traits::Hash256,
+};
+use frame_mra_tls::{AttestationPolicy, AttestedTlsConfig, Client, ClientConfig, NistP256KeyPair};
+use frame_treekem::{
+    combine_shares,
+    handshake::{AccessKey, PathSecretVault},
+    split_secret, PathSecret, SecretShare,
+};
+use log::warn;
+use remote_attestation::{Attestation, QuoteStatusPolicy};
+use std::{prelude::v1::*, sync::Arc};
+
+/// A client of the key-vault enclaves which path secrets are backed up to.
+/// A path secret is split into a share per key-vault and
+/// any `threshold` of the shares can recover it.
+#[derive(Debug, Clone)]
+pub struct KeyVaultClient {
+    endpoints: Vec<String>,
+    threshold: usize,
+    attestation: Arc<dyn Attestation>,
+    // The quote statuses accepted from the key-vaults
+    quote_status_policy: QuoteStatusPolicy,
+    // Reused for every connection so that its attested evidence can be cached
+    tls_key: Arc<NistP256KeyPair>,
+}
+
+impl KeyVaultClient {
+    pub fn new(
+        endpoints: Vec<String>,
+        threshold: usize,
+        attestation: Arc<dyn Attestation>,
+        quote_status_policy: QuoteStatusPolicy,
+    ) -> Result<Self> {
+        Ok(KeyVaultClient {
+            endpoints,
+            threshold,
+            attestation,
+            quote_status_policy,
+            tls_key: Arc::new(NistP256KeyPair::new()?),
+        })
+    }
+
+    /// Store shares of the path secret. It fails unless at least `threshold` key-vaults store them.
+    pub fn store(
+        &self,
+        path_secret: &PathSecret,
+        epoch: u32,
+        roster_idx: u32,
+        id: [u8; EXPORT_ID_SIZE],
+    ) -> Result<RecoverPathSecret> {
+        let shares = split_secret(
+            path_secret.as_bytes(),
+            self.threshold as u8,
+            self.endpoints.len() as u8,
+        )?;
+        let checksum = checksum(path_secret.as_bytes());
+
+        let mut stored = 0;
+        for (endpoint, share) in self.endpoints.iter().zip(shares) {
+            let backup_path_secret =
+                BackupPathSecret::new(share.to_bytes(), checksum, epoch, roster_idx, id);
+            match self.request::<_, RecoverPathSecret>(
+                endpoint,
+                KEY_VAULT_STORE_METHOD,
+                backup_path_secret,
+            ) {
+                Ok(_) => stored += 1,
+                Err(e) => warn!(
+                    "Failed to store a path secret share to {}: {:?}",
+                    endpoint, e
+                ),
+            }
+        }
+
+        if stored < self.threshold {
+            return Err(anyhow!(
+                "Only {:?} key-vaults stored the path secret shares, but the threshold is {:?}",
+                stored,
+                self.threshold
+            ));
+        }
+
+        Ok(RecoverPathSecret::new(roster_idx, epoch, id))
+    }
+
+    /// Gather shares from the key-vaults until `threshold` of them combine into
+    /// the path secret matching the stored checksum.
+    /// A faulty key-vault's share is left out by trying other subsets of the shares.
+    pub fn recover(&self, recover_path_secret: RecoverPathSecret) -> Result<PathSecret> {
+        let mut shares = Vec::with_capacity(self.endpoints.len());
+        for endpoint in &self.endpoints {
+            let share = self
+                .request::<_, BackupPathSecret>(
+                    endpoint,
+                    KEY_VAULT_RECOVER_METHOD,
+                    recover_path_secret,
+                )
+                .and_then(|backup| {
+                    Ok(ChecksummedShare {
+                        share: SecretShare::from_bytes(backup.path_secret())?,
+                        checksum: backup.checksum(),
+                    })
+                });
+            match share {
+                Ok(share) => shares.push(share),
+                Err(e) => {
+                    warn!(
+                        "Failed to recover a path secret share from {}: {:?}",
+                        endpoint, e
+                    );
+                    continue;
+                }
+            }
+
+            if let Some(path_secret) = combine_with_last(&shares, self.threshold) {
+                return Ok(PathSecret::from(path_secret));
+            }
+        }
+
+        Err(anyhow!(
+            "No {:?} of the {:?} recovered path secret shares match the checksum",
+            self.threshold,
+            shares.len()
+        ))
+    }
+
+    fn request<SE, DE>(&self, endpoint: &str, method: &str, params: SE) -> Result<DE>
+    where
+        SE: serde::Serialize,
+        DE: serde::de::DeserializeOwned,
+    {
+        let attested_tls_config =
+            AttestedTlsConfig::new_by_ra_with_key(&self.tls_key, &*self.attestation)?;
+        let policy =
+            AttestationPolicy::default().set_quote_status_policy(self.quote_status_policy.clone());
+        let client_config = ClientConfig::from_attested_tls_config(attested_tls_config)?
+            .set_attestation_policy(policy)
+            .set_attestation_report_verifier(self.attestation.root_cert().to_vec());
+
+        Client::new(endpoint, client_config)?.request(method, params)
+    }
+}
+
+impl PathSecretVault for KeyVaultClient {
+    fn recover(&self, access_key: AccessKey, id: &[u8]) -> Result<PathSecret> {
+        KeyVaultClient::recover(
+            self,
+            RecoverPathSecret::new(access_key.roster_idx(), access_key.epoch(), export_id(id)?),
+        )
+    }
+
+    fn store(&self, path_secret: &PathSecret, access_key: AccessKey, id: &[u8]) -> Result<()> {
+        KeyVaultClient::store(
+            self,
+            path_secret,
+            access_key.epoch(),
+            access_key.roster_idx(),
+            export_id(id)?,
+        )?;
+
+        Ok(())
+    }
+}
+
+struct ChecksummedShare {
+    share: SecretShare,
+    checksum: [u8; 32],
+}
+
+fn checksum(path_secret: &[u8]) -> [u8; 32] {
+    Sha256::hash(path_secret).as_array()
+}
+
+/// Combine `threshold` shares including the last one, so that the subsets are not tried twice
+/// as shares are added. The combined secret must match the checksums stored with all of them.
+fn combine_with_last(shares: &[ChecksummedShare], threshold: usize) -> Option<Vec<u8>> {
+    let (last, others) = shares.split_last()?;
+    if threshold == 0 || others.len() + 1 < threshold {
+        return None;
+    }
+
+    let mut indices: Vec<usize> = (0..threshold - 1).collect();
+    loop {
+        let subset: Vec<&ChecksummedShare> = indices
+            .iter()
+            .map(|i| &others[*i])
+            .chain(Some(last))
+            .collect();
+        let combined = combine_shares(&subset.iter().map(|s| s.share.clone()).collect::<Vec<_>>());
+        if let Ok(secret) = combined {
+            let sum = checksum(&secret);
+            if subset.iter().all(|s| s.checksum == sum) {
+                return Some(secret);
+            }
+        }
+
+        // Advance to the next combination of `threshold - 1` indices of the others
+        let k = indices.len();
+        let mut i = k;
+        loop {
+            if i == 0 {
+                return None;
+            }
+            i -= 1;
+            if indices[i] < others.len() - k + i {
+                break;
+            }
+        }
+        indices[i] += 1;
+        for j in i + 1..k {
+            indices[j] = indices[j - 1] + 1;
+        }
+    }
+}
+
+fn export_id(id: &[u8]) -> Result<[u8; EXPORT_ID_SIZE]> {
+    if id.len() != EXPORT_ID_SIZE {
+        return Err(anyhow!("Invalid path secret id length: {}", id.len()));
+    }
+    let mut id_arr = [0u8; EXPORT_ID_SIZE];
+    id_arr.copy_from_slice(id);
+
+    Ok(id_arr)
+}
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(test_combine_skips_faulty_share,)
+    }
+
+    fn checksummed(secret: &[u8], threshold: u8, num_shares: u8) -> Vec<ChecksummedShare> {
+        split_secret(secret, threshold, num_shares)
+            .unwrap()
+            .into_iter()
+            .map(|share| ChecksummedShare {
+                share,
+                checksum: checksum(secret),
+            })
+            .collect()
+    }
+
+    fn test_combine_skips_faulty_share() {
+        let secret = b"path secret".to_vec();
+        let mut shares = checksummed(&secret, 2, 4);
+        // The first key-vault returns a share of another secret with its checksum.
+        shares[0] = checksummed(b"fake secret", 2, 4).remove(0);
+
+        assert_eq!(combine_with_last(&shares[..1], 2), None);
+        // Only the faulty share and one valid share
+        assert_eq!(combine_with_last(&shares[..2], 2), None);
+        assert_eq!(combine_with_last(&shares[..3], 2), Some(secret.clone()));
+
+        // A faulty checksum with a valid share is left out as well.
+        let mut shares = checksummed(&secret, 3, 4);
+        shares[1].checksum = [0u8; 32];
+        assert_eq!(combine_with_last(&shares[..3], 3), None);
+        assert_eq!(combine_with_last(&shares, 3), Some(secret));
+    }
+}
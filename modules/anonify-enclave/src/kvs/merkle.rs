@@ -0,0 +1,148 @@
+use frame_common::{crypto::Sha256, traits::Hash256};
+use std::prelude::v1::*;
+
+// The prefixes distinguish the leaves from the inner nodes.
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+/// The hash of the key of a leaf, which decides where the leaf is in the tree
+type Path = [u8; 32];
+
+/// A binary merkle tree of the states whose leaves are split by the first differing bit of their paths.
+/// Its shape depends only on the set of the keys, so the root does not depend on the order of insertions,
+/// and each write rehashes the nodes on the path of the leaf alone.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct StateTree {
+    root: Option<Node>,
+}
+
+impl StateTree {
+    pub(crate) fn insert(&mut self, key: &[u8], value: &[u8]) {
+        let path = Sha256::hash(key).as_array();
+        let mut leaf = vec![LEAF_PREFIX];
+        leaf.extend_from_slice(key);
+        leaf.extend_from_slice(value);
+        let hash = Sha256::hash(&leaf).as_array();
+
+        self.root = Some(match self.root.take() {
+            Some(root) => root.insert(path, hash),
+            None => Node::Leaf { path, hash },
+        });
+    }
+
+    pub(crate) fn remove(&mut self, key: &[u8]) {
+        let path = Sha256::hash(key).as_array();
+        self.root = self.root.take().and_then(|root| root.remove(&path));
+    }
+
+    /// The root of the empty tree is all zeros.
+    pub(crate) fn root(&self) -> [u8; 32] {
+        self.root.as_ref().map_or([0u8; 32], Node::hash)
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Leaf {
+        path: Path,
+        hash: [u8; 32],
+    },
+    // The leaves below share the bits of their paths before `bit`,
+    // and the ones whose `bit` is unset are on the left.
+    Branch {
+        bit: usize,
+        // The path of the leftmost leaf below
+        path: Path,
+        left: Box<Node>,
+        right: Box<Node>,
+        hash: [u8; 32],
+    },
+}
+
+impl Node {
+    fn hash(&self) -> [u8; 32] {
+        match self {
+            Node::Leaf { hash, .. } | Node::Branch { hash, .. } => *hash,
+        }
+    }
+
+    fn path(&self) -> &Path {
+        match self {
+            Node::Leaf { path, .. } | Node::Branch { path, .. } => path,
+        }
+    }
+
+    fn branch(bit: usize, a: Node, b: Node) -> Node {
+        let (left, right) = if bit_at(a.path(), bit) {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        let mut node = vec![NODE_PREFIX];
+        node.extend_from_slice(&left.hash());
+        node.extend_from_slice(&right.hash());
+
+        Node::Branch {
+            bit,
+            path: *left.path(),
+            hash: Sha256::hash(&node).as_array(),
+            left: Box::new(left),
+            right: Box::new(right),
+        }
+    }
+
+    fn insert(self, path: Path, hash: [u8; 32]) -> Node {
+        let diff = first_diff(self.path(), &path);
+        match self {
+            Node::Leaf { .. } if diff.is_none() => Node::Leaf { path, hash },
+            Node::Branch {
+                bit, left, right, ..
+            } if diff.map_or(true, |diff| diff >= bit) => {
+                if bit_at(&path, bit) {
+                    Node::branch(bit, *left, right.insert(path, hash))
+                } else {
+                    Node::branch(bit, left.insert(path, hash), *right)
+                }
+            }
+            node => {
+                let diff = diff.expect("a leaf of another path differs from it");
+                Node::branch(diff, node, Node::Leaf { path, hash })
+            }
+        }
+    }
+
+    fn remove(self, path: &Path) -> Option<Node> {
+        match self {
+            Node::Leaf {
+                path: leaf_path, ..
+            } if leaf_path == *path => None,
+            Node::Branch {
+                bit, left, right, ..
+            } => {
+                let (left, right) = if bit_at(path, bit) {
+                    (Some(*left), right.remove(path))
+                } else {
+                    (left.remove(path), Some(*right))
+                };
+                match (left, right) {
+                    (Some(left), Some(right)) => Some(Node::branch(bit, left, right)),
+                    (node, None) | (None, node) => node,
+                }
+            }
+            leaf => Some(leaf),
+        }
+    }
+}
+
+fn bit_at(path: &Path, bit: usize) -> bool {
+    path[bit / 8] & (0x80 >> (bit % 8)) != 0
+}
+
+/// The index of the first bit which differs between the paths
+fn first_diff(a: &Path, b: &Path) -> Option<usize> {
+    a.iter()
+        .zip(b.iter())
+        .enumerate()
+        .find(|(_, (x, y))| x != y)
+        .map(|(i, (x, y))| i * 8 + (x ^ y).leading_zeros() as usize)
+}
@@ -1,3 +1,7 @@
+mod merkle;
+
+use self::merkle::StateTree;
+use codec::Encode;
 use frame_common::{
     crypto::AccountId,
     state_types::{MemId, StateType, UpdatedState},
@@ -8,7 +12,7 @@ use std::{
     sync::{Arc, SgxRwLock},
 };
 
-#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Encode)]
 pub struct DBKey((AccountId, MemId));
 
 // TODO: AccountId+MemId is not sufficient size for hash digest in terms of collision resistance.
@@ -19,16 +23,35 @@ impl DBKey {
 }
 
 #[derive(Debug, Clone)]
-pub struct EnclaveDB(Arc<SgxRwLock<HashMap<DBKey, StateType>>>);
+pub struct EnclaveDB(Arc<SgxRwLock<States>>);
+
+/// The states and the merkle tree of them, which are updated together
+#[derive(Debug, Default)]
+struct States {
+    states: HashMap<DBKey, StateType>,
+    tree: StateTree,
+}
+
+impl States {
+    fn insert(&mut self, key: DBKey, state: StateType) {
+        self.tree.insert(&key.encode(), &state.encode());
+        self.states.insert(key, state);
+    }
+
+    fn remove(&mut self, key: &DBKey) {
+        self.tree.remove(&key.encode());
+        self.states.remove(key);
+    }
+}
 
 impl EnclaveDB {
     pub fn new() -> Self {
-        EnclaveDB(Arc::new(SgxRwLock::new(HashMap::new())))
+        EnclaveDB(Arc::new(SgxRwLock::new(States::default())))
     }
 
     pub fn get(&self, account_id: AccountId, mem_id: MemId) -> StateType {
         let key = DBKey::new(account_id, mem_id);
-        match self.0.read().unwrap().get(&key) {
+        match self.0.read().unwrap().states.get(&key) {
             Some(v) => v.clone(),
             None => StateType::default(),
         }
@@ -36,7 +59,7 @@ impl EnclaveDB {
 
     pub fn values(&self) -> Vec<StateType> {
         let mut acc = vec![];
-        for v in self.0.read().unwrap().values() {
+        for v in self.0.read().unwrap().states.values() {
             acc.push(v.clone());
         }
         acc
@@ -59,4 +82,57 @@ impl EnclaveDB {
         let key = DBKey::new(account_id, mem_id);
         tmp.remove(&key);
     }
+
+    /// The merkle root of the whole state, which is kept up to date on each write.
+    pub fn state_root(&self) -> [u8; 32] {
+        self.0.read().unwrap().tree.root()
+    }
+}
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(test_state_root,)
+    }
+
+    fn test_state_root() {
+        let alice = AccountId::from_array([1u8; 20]);
+        let bob = AccountId::from_array([2u8; 20]);
+        let carol = AccountId::from_array([3u8; 20]);
+
+        let db = EnclaveDB::new();
+        assert_eq!(db.state_root(), [0u8; 32]);
+        db.insert(alice, MemId::from_raw(0), StateType::new(vec![1]));
+        db.insert(bob, MemId::from_raw(0), StateType::new(vec![2]));
+        db.insert(carol, MemId::from_raw(1), StateType::new(vec![3]));
+
+        let other_db = EnclaveDB::new();
+        other_db.insert(carol, MemId::from_raw(1), StateType::new(vec![3]));
+        other_db.insert(bob, MemId::from_raw(0), StateType::new(vec![2]));
+        other_db.insert(alice, MemId::from_raw(0), StateType::new(vec![1]));
+        assert_eq!(db.state_root(), other_db.state_root());
+
+        other_db.insert(bob, MemId::from_raw(0), StateType::new(vec![4]));
+        assert_ne!(db.state_root(), other_db.state_root());
+        other_db.insert(bob, MemId::from_raw(0), StateType::new(vec![2]));
+        assert_eq!(db.state_root(), other_db.state_root());
+
+        // The root is the same as if the deleted states had never been inserted.
+        let root = db.state_root();
+        let dave = AccountId::from_array([4u8; 20]);
+        db.insert(dave, MemId::from_raw(0), StateType::new(vec![5]));
+        assert_ne!(db.state_root(), root);
+        db.delete(dave, MemId::from_raw(0));
+        assert_eq!(db.state_root(), root);
+        db.delete(alice, MemId::from_raw(0));
+        db.delete(bob, MemId::from_raw(0));
+        db.delete(carol, MemId::from_raw(1));
+        assert_eq!(db.state_root(), [0u8; 32]);
+        // Deleting a missing state changes nothing.
+        db.delete(alice, MemId::from_raw(0));
+        assert_eq!(db.state_root(), [0u8; 32]);
+    }
 }
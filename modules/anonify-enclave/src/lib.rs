@@ -11,14 +11,19 @@ mod error;
 mod group_key;
 mod handshake;
 mod identity_key;
+mod key_vault;
 mod kvs;
 mod notify;
 
 pub mod workflow {
     pub use crate::commands::{MsgReceiver, MsgSender};
-    pub use crate::context::{GetState, ReportRegistration};
+    pub use crate::context::{
+        AttestationStatusGetter, GetState, ReportRegistration, StateCommitmentGetter,
+    };
     pub use crate::handshake::{HandshakeReceiver, HandshakeSender, JoinGroupSender};
-    pub use crate::identity_key::EncryptingKeyGetter;
+    pub use crate::identity_key::{
+        EncryptingKeyGetter, IdentityKeyBinder, IdentityKeyCommitter, IdentityKeyRotator,
+    };
     pub use crate::notify::RegisterNotification;
 }
 
@@ -29,6 +34,11 @@ pub mod tests {
     use test_utils::*;
 
     pub fn run_tests() -> bool {
-        check_all_passed!(notify::tests::run_tests(),)
+        check_all_passed!(
+            notify::tests::run_tests(),
+            identity_key::tests::run_tests(),
+            key_vault::tests::run_tests(),
+            kvs::tests::run_tests(),
+        )
     }
 }
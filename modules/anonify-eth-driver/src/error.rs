@@ -1,17 +1,28 @@
+use anonify_ledger_driver::HostError;
 use thiserror::Error;
 
-pub type Result<T> = std::result::Result<T, HostError>;
+pub type Result<T> = std::result::Result<T, EthError>;
 
 #[derive(Error, Debug)]
-pub enum HostError {
+pub enum EthError {
     #[error("Error: {0}")]
     Error(#[from] anyhow::Error),
-    #[error("Contract address have not been set.")]
-    AddressNotSet,
-    #[error("Event watcher have not been set.")]
-    EventWatcherNotSet,
-    #[error("Ecall output is not set. An error would have occurred in the enclave")]
-    EcallOutputNotSet,
+    #[error("Host error: {0}")]
+    HostError(#[from] HostError),
+    #[error("The event subscription has been closed")]
+    SubscriptionClosed,
+    #[error("The transaction has been reverted: {0:?}")]
+    TxFailed(web3::types::H256),
+    #[error("The transactions have not been confirmed: {0:?}")]
+    TxNotConfirmed(Vec<web3::types::H256>),
+    #[error("The estimated gas {estimated} exceeds the gas limit {gas_limit}")]
+    GasLimitExceeded {
+        estimated: web3::types::U256,
+        gas_limit: u64,
+    },
+    #[cfg(any(test, feature = "mock"))]
+    #[error("Reverted by the mock ledger: {0}")]
+    MockReverted(&'static str),
     #[error("Failed unlock the account")]
     UnlockError,
     #[error("IO error: {0}")]
@@ -24,6 +35,15 @@ pub enum HostError {
     EthabiError(#[from] ethabi::Error),
     #[error("Codec error: {0}")]
     CodecError(#[from] codec::Error),
-    #[error("Frame host error: {0}")]
-    FrameHostError(#[from] frame_host::Error),
+    #[error("Wallet error: {0}")]
+    WalletError(#[from] anonify_wallet::Error),
+}
+
+impl From<EthError> for HostError {
+    fn from(err: EthError) -> Self {
+        match err {
+            EthError::HostError(err) => err,
+            err => HostError::LedgerError(Box::new(err)),
+        }
+    }
 }
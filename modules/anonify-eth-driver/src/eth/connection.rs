@@ -1,17 +1,25 @@
-use super::event_watcher::{EthEvent, Web3Logs};
+use super::{
+    event_watcher::{EthEvent, Web3Logs},
+    signer::LocalSigner,
+    tx_manager::TxManager,
+    Ethereum,
+};
 use crate::{
-    cache::EventCache,
-    error::{HostError, Result},
-    utils::ContractInfo,
-    workflow::*,
+    error::{EthError, Result},
+    utils::{contract_abi, TxConfig},
+    EventCache,
 };
+use anonify_ledger_driver::{put_blob, workflow::*, ContractInfo, HostError, SharedBlobStore};
 use anyhow::anyhow;
 use ethabi::{Topic, TopicFilter};
-use std::{fs, path::Path};
+use futures::{Future, FutureExt, StreamExt};
+use log::warn;
+use std::{collections::BTreeMap, fs, path::Path};
 use web3::{
-    contract::{Contract, Options},
-    transports::Http,
-    types::{Address, BlockNumber, Filter, FilterBuilder, Log, H256},
+    api::SubscriptionStream,
+    contract::{tokens::Tokenize, Contract},
+    transports::{Http, WebSocket},
+    types::{Address, BlockId, BlockNumber, Filter, FilterBuilder, Log, H256, U256},
     Web3,
 };
 
@@ -21,7 +29,7 @@ const EVENT_LIMIT: usize = 100;
 // libsecp256k1 library generates RecoveryId as 0/1.
 // However Secp256k1 used in solidity use 27/28 as a value to make a public key unique to recover.
 // RECOVERY_ID_OFFSET is used to adjust the difference between libsecp256k1 and Secp256k1.
-const RECOVERY_ID_OFFSET: u8 = 27;
+pub(crate) const RECOVERY_ID_OFFSET: u8 = 27;
 
 /// Web3 connection components of a contract.
 #[derive(Debug)]
@@ -29,6 +37,8 @@ pub struct Web3Contract {
     contract: Contract<Http>,
     address: Address, // contract address
     web3_conn: Web3Http,
+    tx_manager: TxManager,
+    blob_store: Option<SharedBlobStore>,
 }
 
 impl Web3Contract {
@@ -36,20 +46,38 @@ impl Web3Contract {
         web3_conn: Web3Http,
         contract_info: ContractInfo<'_, P>,
     ) -> Result<Self> {
-        let abi = contract_info.contract_abi()?;
-        let address = contract_info.address()?;
+        let abi = contract_abi(contract_info.abi_path())?;
+        let address = contract_info.address::<Ethereum>()?;
         let contract = Contract::new(web3_conn.web3.eth(), address, abi);
+        let tx_manager = web3_conn.tx_manager(TxConfig::default());
 
         Ok(Web3Contract {
             contract,
             address,
             web3_conn,
+            tx_manager,
+            blob_store: None,
         })
     }
 
+    pub fn set_tx_config(mut self, config: TxConfig) -> Self {
+        self.tx_manager = self.tx_manager.set_config(config);
+        self
+    }
+
+    pub fn set_signer(mut self, signer: Option<LocalSigner>) -> Self {
+        self.tx_manager = self.tx_manager.set_signer(signer);
+        self
+    }
+
+    pub fn set_blob_store(mut self, blob_store: SharedBlobStore) -> Self {
+        self.blob_store = Some(blob_store);
+        self
+    }
+
     pub async fn send_report_handshake(
         &self,
-        output: host_output::JoinGroup,
+        output: host_output::JoinGroup<Address>,
         method: &str,
     ) -> Result<H256> {
         let ecall_output = output
@@ -60,24 +88,45 @@ impl Web3Contract {
         let handshake = ecall_output.handshake().to_vec();
         let gas = output.gas;
 
-        self.contract
-            .call(
-                method,
-                (
-                    report,
-                    report_sig,
-                    handshake,
-                    ecall_output.mrenclave_ver(),
-                    ecall_output.roster_idx(),
-                ),
-                output.signer,
-                Options::with(|opt| opt.gas = Some(gas.into())),
-            )
+        self.send_tx(
+            method,
+            (
+                report,
+                report_sig,
+                handshake,
+                ecall_output.mrenclave_ver(),
+                ecall_output.roster_idx(),
+            ),
+            output.signer,
+            gas,
+        )
+        .await
+    }
+
+    pub async fn approve_mrenclave(
+        &self,
+        signer: Address,
+        gas: u64,
+        new_version: u32,
+    ) -> Result<H256> {
+        self.send_tx("approveMrenclave", (new_version,), signer, gas)
+            .await
+    }
+
+    pub async fn execute_mrenclave(
+        &self,
+        signer: Address,
+        gas: u64,
+        new_version: u32,
+    ) -> Result<H256> {
+        self.send_tx("executeMrenclave", (new_version,), signer, gas)
             .await
-            .map_err(Into::into)
     }
 
-    pub async fn register_report(&self, output: host_output::RegisterReport) -> Result<H256> {
+    pub async fn register_report(
+        &self,
+        output: host_output::RegisterReport<Address>,
+    ) -> Result<H256> {
         let ecall_output = output
             .ecall_output
             .ok_or_else(|| HostError::EcallOutputNotSet)?;
@@ -85,23 +134,50 @@ impl Web3Contract {
         let report_sig = ecall_output.report_sig().to_vec();
         let gas = output.gas;
 
-        self.contract
-            .call(
-                "registerReport",
-                (
-                    report,
-                    report_sig,
-                    ecall_output.mrenclave_ver(),
-                    ecall_output.roster_idx(),
-                ),
-                output.signer,
-                Options::with(|opt| opt.gas = Some(gas.into())),
-            )
-            .await
-            .map_err(Into::into)
+        self.send_tx(
+            "registerReport",
+            (
+                report,
+                report_sig,
+                ecall_output.mrenclave_ver(),
+                ecall_output.roster_idx(),
+            ),
+            output.signer,
+            gas,
+        )
+        .await
+    }
+
+    /// Revoke the old identity key in favor of the rotated one.
+    /// The old key is revoked only if the new one has been registered,
+    /// which is checked in the contract.
+    pub async fn revoke_keys(
+        &self,
+        output: host_output::RotateIdentityKey<Address>,
+    ) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let mut enclave_sig = ecall_output.encode_revocation_sig().to_vec();
+        let recovery_id = ecall_output.encode_recovery_id() + RECOVERY_ID_OFFSET;
+        enclave_sig.push(recovery_id);
+        let gas = output.gas;
+
+        self.send_tx(
+            "revokeKeys",
+            (
+                Address::from(*ecall_output.old_verifying_key()),
+                ecall_output.old_encrypting_key().to_vec(),
+                Address::from(*ecall_output.new_verifying_key()),
+                enclave_sig,
+            ),
+            output.signer,
+            gas,
+        )
+        .await
     }
 
-    pub async fn send_command(&self, output: host_output::Command) -> Result<H256> {
+    pub async fn send_command(&self, output: host_output::Command<Address>) -> Result<H256> {
         let ecall_output = output
             .ecall_output
             .ok_or_else(|| HostError::EcallOutputNotSet)?;
@@ -111,18 +187,30 @@ impl Web3Contract {
         enclave_sig.push(recovery_id);
         let gas = output.gas;
 
-        self.contract
-            .call(
-                "storeCommand",
-                (ciphertext, enclave_sig),
-                output.signer,
-                Options::with(|opt| opt.gas = Some(gas.into())),
-            )
-            .await
-            .map_err(Into::into)
+        match &self.blob_store {
+            Some(blob_store) => {
+                let ciphertext_hash = H256::from(put_blob(blob_store, ciphertext).await?);
+                self.send_tx(
+                    "storeCommandHash",
+                    (ciphertext_hash, enclave_sig),
+                    output.signer,
+                    gas,
+                )
+                .await
+            }
+            None => {
+                self.send_tx(
+                    "storeCommand",
+                    (ciphertext, enclave_sig),
+                    output.signer,
+                    gas,
+                )
+                .await
+            }
+        }
     }
 
-    pub async fn handshake(&self, output: host_output::Handshake) -> Result<H256> {
+    pub async fn handshake(&self, output: host_output::Handshake<Address>) -> Result<H256> {
         let ecall_output = output
             .ecall_output
             .ok_or_else(|| HostError::EcallOutputNotSet)?;
@@ -132,55 +220,312 @@ impl Web3Contract {
         enclave_sig.push(recovery_id);
         let gas = output.gas;
 
-        self.contract
-            .call(
-                "handshake",
-                (handshake, enclave_sig, ecall_output.roster_idx()),
-                output.signer,
-                Options::with(|opt| opt.gas = Some(gas.into())),
+        self.send_tx(
+            "handshake",
+            (handshake, enclave_sig, ecall_output.roster_idx()),
+            output.signer,
+            gas,
+        )
+        .await
+    }
+
+    pub async fn publish_state_commitment(
+        &self,
+        output: host_output::StateCommitment<Address>,
+    ) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let commitment = ecall_output.encode_commitment();
+        let mut enclave_sig = ecall_output.encode_enclave_sig().to_vec();
+        let recovery_id = ecall_output.encode_recovery_id() + RECOVERY_ID_OFFSET;
+        enclave_sig.push(recovery_id);
+        let gas = output.gas;
+
+        self.send_tx(
+            "publishStateCommitment",
+            (commitment, enclave_sig),
+            output.signer,
+            gas,
+        )
+        .await
+    }
+
+    /// Send a transaction calling the method via the transaction manager.
+    async fn send_tx<P: Tokenize>(
+        &self,
+        method: &str,
+        params: P,
+        signer: Address,
+        gas: u64,
+    ) -> Result<H256> {
+        let data = self
+            .contract
+            .abi()
+            .function(method)?
+            .encode_input(&params.into_tokens())?;
+        self.tx_manager.send(signer, self.address, data, gas).await
+    }
+
+    /// Fetch the events in the blocks with enough confirmations since the last fetched one.
+    /// If the subscription is given, the events already received through it are used
+    /// instead of requesting `eth_getLogs` for the blocks after it started.
+    /// If the last fetched block has been reorganized, the blocks before it are fetched again.
+    pub async fn get_event(
+        &self,
+        cache: EventCache,
+        key: Address,
+        confirmations: u64,
+        mut subscription: Option<&mut LogSubscription>,
+    ) -> Result<Web3Logs> {
+        let events = EthEvent::create_event();
+        // Read next block number and the last fetched block from in-memory event cache.
+        let (mut next_block_num, scanned_block) = {
+            let cache = cache.inner().read();
+            (
+                cache.get_latest_block_num(key).unwrap_or_default(),
+                cache.get_scanned_block(key),
             )
-            .await
-            .map_err(Into::into)
+        };
+
+        let mut is_rewound = false;
+        if let Some((block_num, block_hash)) = scanned_block {
+            if self.web3_conn.get_block_hash(block_num).await? != Some(block_hash) {
+                next_block_num = cache.inner().write().rewind_on_reorg(key, block_num);
+                is_rewound = true;
+            }
+        }
+
+        let latest_block_num = self.web3_conn.block_number().await?;
+        let confirmed_block_num = match latest_block_num.checked_sub(confirmations) {
+            Some(num) if num >= next_block_num => num,
+            _ => return Ok(Web3Logs::new(vec![], cache, events, None)),
+        };
+        let confirmed_block_hash = self
+            .web3_conn
+            .get_block_hash(confirmed_block_num)
+            .await?
+            .ok_or_else(|| anyhow!("Not found block: {}", confirmed_block_num))?;
+
+        let logs = match subscription.as_mut() {
+            Some(subscription) => {
+                // The received logs of the rewound blocks have been discarded.
+                if is_rewound {
+                    subscription.refetch_until(confirmed_block_num);
+                }
+                let mut logs = vec![];
+                if next_block_num < subscription.from_block() {
+                    let to_block = confirmed_block_num.min(subscription.from_block() - 1);
+                    logs = self.get_logs(&events, next_block_num, to_block).await?;
+                }
+                for log in subscription.take_confirmed(next_block_num, confirmed_block_num) {
+                    // Drop the logs whose removal has not been notified before the disconnection.
+                    let block_num = log.block_number.map(|n| n.as_u64()).unwrap_or_default();
+                    if self.web3_conn.get_block_hash(block_num).await? == log.block_hash {
+                        logs.push(log);
+                    } else {
+                        warn!("Dropped a log in a non-canonical block: {:?}", log);
+                    }
+                }
+                logs
+            }
+            None => {
+                self.get_logs(&events, next_block_num, confirmed_block_num)
+                    .await?
+            }
+        };
+
+        // Fetch the logs again on the next call if they have been reorganized while fetching them.
+        if self.web3_conn.get_block_hash(confirmed_block_num).await? != Some(confirmed_block_hash) {
+            warn!(
+                "The block {} has been reorganized while fetching its logs",
+                confirmed_block_num
+            );
+            if let Some(subscription) = subscription {
+                subscription.refetch_until(confirmed_block_num);
+            }
+            return Ok(Web3Logs::new(vec![], cache, events, None));
+        }
+
+        Ok(Web3Logs::new(
+            logs,
+            cache,
+            events,
+            Some((confirmed_block_num, confirmed_block_hash)),
+        ))
     }
 
-    pub async fn get_event(&self, cache: EventCache, key: Address) -> Result<Web3Logs> {
+    /// Subscribe the events of the contract via the WebSocket endpoint.
+    pub async fn subscribe_logs(&self, ws_url: &str) -> Result<LogSubscription> {
         let events = EthEvent::create_event();
-        let ciphertext_sig = events.ciphertext_signature();
-        let handshake_sig = events.handshake_signature();
-        // Read latest block number from in-memory event cache.
-        let latest_fetched_num = cache
-            .inner()
-            .read()
-            .get_latest_block_num(key)
-            .unwrap_or_default();
-
-        let filter = FilterBuilder::default()
+        let web3 = Web3::new(WebSocket::new(ws_url).await?);
+        let filter = self.event_filter(&events).build();
+        let stream = web3.eth_subscribe().subscribe_logs(filter).await?;
+        // The blocks up to the current head might be built before subscribing,
+        // so their events are fetched by `eth_getLogs`.
+        let from_block = self.web3_conn.block_number().await? + 1;
+
+        Ok(LogSubscription {
+            stream,
+            from_block,
+            pending: PendingLogs::default(),
+        })
+    }
+
+    /// Fetch the events from `from_block` to `to_block` page by page.
+    async fn get_logs(
+        &self,
+        events: &EthEvent,
+        from_block: u64,
+        to_block: u64,
+    ) -> Result<Vec<Log>> {
+        paginate_logs(from_block, to_block, |from, to, limit| {
+            let mut filter = self
+                .event_filter(events)
+                .from_block(BlockNumber::Number(from.into()))
+                .to_block(BlockNumber::Number(to.into()));
+            if let Some(limit) = limit {
+                filter = filter.limit(limit);
+            }
+            self.web3_conn.get_logs(filter.build())
+        })
+        .await
+    }
+
+    fn event_filter(&self, events: &EthEvent) -> FilterBuilder {
+        FilterBuilder::default()
             .address(vec![self.address])
             .topic_filter(TopicFilter {
-                topic0: Topic::OneOf(vec![ciphertext_sig, handshake_sig]),
+                topic0: Topic::OneOf(vec![
+                    events.ciphertext_signature(),
+                    events.handshake_signature(),
+                    events.ciphertext_hash_signature(),
+                    events.state_commitment_signature(),
+                ]),
                 topic1: Topic::Any,
                 topic2: Topic::Any,
                 topic3: Topic::Any,
             })
-            .from_block(BlockNumber::Number(latest_fetched_num.into()))
-            .to_block(BlockNumber::Latest)
-            .limit(EVENT_LIMIT)
-            .build();
-
-        let logs = self.web3_conn.get_logs(filter).await?;
-
-        Ok(Web3Logs::new(logs, cache, events))
     }
 
     pub async fn get_account(&self, index: usize, password: &str) -> Result<Address> {
         self.web3_conn.get_account(index, password).await
     }
 
+    pub async fn block_number(&self) -> Result<u64> {
+        self.web3_conn.block_number().await
+    }
+
     pub fn address(&self) -> Address {
         self.address
     }
 }
 
+/// Fetch the logs from `from_block` to `to_block` by `fetch(from, to, limit)`.
+/// A page with `EVENT_LIMIT` logs might be truncated,
+/// so it is fetched again with the block range halved.
+/// The logs of a single block are fetched without the limit, because they must be inserted at once.
+async fn paginate_logs<F, Fut>(from_block: u64, to_block: u64, mut fetch: F) -> Result<Vec<Log>>
+where
+    F: FnMut(u64, u64, Option<usize>) -> Fut,
+    Fut: Future<Output = Result<Vec<Log>>>,
+{
+    let mut logs = vec![];
+    let mut from = from_block;
+    let mut to = to_block;
+
+    while from <= to_block {
+        if from == to {
+            logs.extend(fetch(from, to, None).await?);
+        } else {
+            let page = fetch(from, to, Some(EVENT_LIMIT)).await?;
+            if page.len() >= EVENT_LIMIT {
+                to = from + (to - from) / 2;
+                continue;
+            }
+            logs.extend(page);
+        }
+
+        from = to + 1;
+        to = to_block;
+    }
+
+    Ok(logs)
+}
+
+/// A subscription of the contract's events via WebSocket.
+#[derive(Debug)]
+pub struct LogSubscription {
+    stream: SubscriptionStream<WebSocket, Log>,
+    // The first block whose events are received by this subscription
+    from_block: u64,
+    pending: PendingLogs,
+}
+
+impl LogSubscription {
+    /// Receive the logs which have arrived without waiting for new ones.
+    pub fn poll_logs(&mut self) -> Result<()> {
+        loop {
+            match self.stream.next().now_or_never() {
+                Some(Some(log)) => self.pending.insert(log?),
+                Some(None) => return Err(EthError::SubscriptionClosed),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    pub fn from_block(&self) -> u64 {
+        self.from_block
+    }
+
+    fn take_confirmed(&mut self, from_block: u64, to_block: u64) -> Vec<Log> {
+        self.pending.take_confirmed(from_block, to_block)
+    }
+
+    /// Discard the received logs up to the block, so that they are fetched by `eth_getLogs` instead,
+    /// e.g. because they have been reorganized.
+    fn refetch_until(&mut self, block_num: u64) {
+        self.pending.take_confirmed(0, block_num);
+        self.from_block = self.from_block.max(block_num + 1);
+    }
+}
+
+/// Received logs are kept until their blocks get enough confirmations,
+/// and dropped when the node notifies that they are removed by a reorg.
+#[derive(Debug, Default)]
+struct PendingLogs(BTreeMap<u64, Vec<Log>>);
+
+impl PendingLogs {
+    fn insert(&mut self, log: Log) {
+        let block_num = match log.block_number {
+            Some(num) => num.as_u64(),
+            None => return, // pending logs are not delivered by subscriptions
+        };
+
+        if log.removed == Some(true) {
+            warn!("A log is removed by a reorg: {:?}", log);
+            if let Some(logs) = self.0.get_mut(&block_num) {
+                logs.retain(|l| l.block_hash != log.block_hash || l.log_index != log.log_index);
+            }
+        } else {
+            self.0.entry(block_num).or_default().push(log);
+        }
+    }
+
+    /// Take the logs in the blocks from `from_block` to `to_block`,
+    /// and discard the older ones which have been already fetched.
+    fn take_confirmed(&mut self, from_block: u64, to_block: u64) -> Vec<Log> {
+        let unconfirmed = self.0.split_off(&(to_block + 1));
+        let confirmed = std::mem::replace(&mut self.0, unconfirmed);
+
+        confirmed
+            .into_iter()
+            .filter(|(block_num, _)| *block_num >= from_block)
+            .flat_map(|(_, logs)| logs)
+            .collect()
+    }
+}
+
 /// Basic web3 connection components via HTTP.
 #[derive(Debug)]
 pub struct Web3Http {
@@ -207,7 +552,7 @@ impl Web3Http {
             .unlock_account(account, password, Some(UNLOCK_DURATION))
             .await?
         {
-            return Err(HostError::UnlockError);
+            return Err(EthError::UnlockError);
         }
 
         Ok(account)
@@ -217,15 +562,32 @@ impl Web3Http {
         self.web3.eth().logs(filter).await.map_err(Into::into)
     }
 
+    pub async fn block_number(&self) -> Result<u64> {
+        let block_num = self.web3.eth().block_number().await?;
+        Ok(block_num.as_u64())
+    }
+
+    /// The hash of the canonical block at the number, or `None` if it has not been built yet.
+    pub async fn get_block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+        let block = self
+            .web3
+            .eth()
+            .block(BlockId::Number(BlockNumber::Number(block_num.into())))
+            .await?;
+        Ok(block.and_then(|b| b.hash))
+    }
+
     pub async fn deploy<P: AsRef<Path>>(
         &self,
-        output: host_output::JoinGroup,
+        output: host_output::JoinGroup<Address>,
         abi_path: P,
         bin_path: P,
-        confirmations: usize,
+        config: TxConfig,
+        signer: Option<LocalSigner>,
     ) -> Result<Address> {
-        let abi = fs::read(abi_path)?;
-        let bin = fs::read_to_string(bin_path)?;
+        let abi = ethabi::Contract::load(fs::File::open(abi_path)?)?;
+        let bin = hex::decode(fs::read_to_string(bin_path)?.trim())
+            .map_err(|e| anyhow!("Invalid contract bytecode: {:?}", e))?;
 
         let ecall_output = output
             .ecall_output
@@ -233,25 +595,132 @@ impl Web3Http {
         let report = ecall_output.report().to_vec();
         let report_sig = ecall_output.report_sig().to_vec();
         let handshake = ecall_output.handshake().to_vec();
-        let gas = output.gas;
+        let governance = config.governance();
+        let params = (
+            report,
+            report_sig,
+            handshake,
+            ecall_output.mrenclave_ver(),
+            U256::from(governance.mrenclave_timelock()),
+            governance.approval_percent(),
+        );
 
-        let contract = Contract::deploy(self.web3.eth(), abi.as_slice())
-            .map_err(|e| anyhow!("{:?}", e))?
-            .options(Options::with(|opt| opt.gas = Some(gas.into())))
-            .confirmations(confirmations)
-            .execute(
-                bin.as_str(),
-                (report, report_sig, handshake, ecall_output.mrenclave_ver()),
-                output.signer,
-            )
-            .map_err(|e| anyhow!("{:?}", e))?
+        let data = match abi.constructor() {
+            Some(constructor) => constructor.encode_input(bin, &params.into_tokens())?,
+            None => bin,
+        };
+        self.tx_manager(config)
+            .set_signer(signer)
+            .deploy(output.signer, data, output.gas)
             .await
-            .map_err(|e| anyhow!("{:?}", e))?;
+    }
 
-        Ok(contract.address())
+    pub fn tx_manager(&self, config: TxConfig) -> TxManager {
+        TxManager::new(self.web3.clone(), &self.eth_url, config)
     }
 
     pub fn get_eth_url(&self) -> &str {
         &self.eth_url
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(block_num: u64, block_hash: H256, log_index: u64, removed: bool) -> Log {
+        Log {
+            address: Address::zero(),
+            topics: vec![],
+            data: Default::default(),
+            block_hash: Some(block_hash),
+            block_number: Some(block_num.into()),
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: Some(log_index.into()),
+            transaction_log_index: None,
+            log_type: None,
+            removed: Some(removed),
+        }
+    }
+
+    #[test]
+    fn test_pending_logs_reorg() {
+        let (hash_a, hash_b) = (H256::repeat_byte(1), H256::repeat_byte(2));
+        let mut pending = PendingLogs::default();
+        pending.insert(log(1, hash_a, 0, false));
+        pending.insert(log(2, hash_a, 0, false));
+        pending.insert(log(2, hash_a, 1, false));
+        pending.insert(log(3, hash_a, 0, false));
+
+        // The block 2 is reorganized
+        pending.insert(log(2, hash_a, 0, true));
+        pending.insert(log(2, hash_a, 1, true));
+        pending.insert(log(2, hash_b, 0, false));
+
+        let confirmed = pending.take_confirmed(2, 2);
+        assert_eq!(confirmed, vec![log(2, hash_b, 0, false)]);
+        // Block 1 is discarded as already fetched, and block 3 is still unconfirmed.
+        assert_eq!(pending.take_confirmed(0, 3), vec![log(3, hash_a, 0, false)]);
+    }
+
+    #[test]
+    fn test_paginate_logs() {
+        // Block 2 has more logs than a page.
+        let mut chain = vec![];
+        for block_num in 1..10 {
+            let num_logs = match block_num {
+                1 => 10,
+                2 => 150,
+                _ => 30,
+            };
+            for index in 0..num_logs {
+                chain.push(log(block_num, H256::zero(), index, false));
+            }
+        }
+
+        let mut requests = vec![];
+        let logs = futures::executor::block_on(paginate_logs(1, 9, |from, to, limit| {
+            requests.push((from, to, limit));
+            let page: Vec<Log> = chain
+                .iter()
+                .filter(|l| (from..=to).contains(&l.block_number.unwrap().as_u64()))
+                .take(limit.unwrap_or(usize::MAX))
+                .cloned()
+                .collect();
+            futures::future::ready(Ok(page))
+        }))
+        .unwrap();
+
+        assert_eq!(logs, chain);
+        // The range is halved until a page is not truncated, and the block 2 is fetched without the limit.
+        assert_eq!(
+            requests,
+            vec![
+                (1, 9, Some(EVENT_LIMIT)),
+                (1, 5, Some(EVENT_LIMIT)),
+                (1, 3, Some(EVENT_LIMIT)),
+                (1, 2, Some(EVENT_LIMIT)),
+                (1, 1, None),
+                (2, 9, Some(EVENT_LIMIT)),
+                (2, 5, Some(EVENT_LIMIT)),
+                (2, 3, Some(EVENT_LIMIT)),
+                (2, 2, None),
+                (3, 9, Some(EVENT_LIMIT)),
+                (3, 6, Some(EVENT_LIMIT)),
+                (3, 4, Some(EVENT_LIMIT)),
+                (5, 9, Some(EVENT_LIMIT)),
+                (5, 7, Some(EVENT_LIMIT)),
+                (8, 9, Some(EVENT_LIMIT)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_paginate_logs_error() {
+        let res = futures::executor::block_on(paginate_logs(1, 9, |_, _, _| {
+            futures::future::ready(Err(EthError::SubscriptionClosed))
+        }));
+        assert!(res.is_err());
+    }
+}
@@ -1,5 +1,10 @@
-use super::connection::{Web3Contract, Web3Http};
-use crate::{error::Result, traits::*, utils::*, workflow::*};
+use super::{
+    connection::{Web3Contract, Web3Http},
+    signer::LocalSigner,
+    Ethereum,
+};
+use crate::utils::TxConfig;
+use anonify_ledger_driver::{traits::*, workflow::*, ContractInfo, LedgerDriver, Result};
 use async_trait::async_trait;
 use sgx_types::sgx_enclave_id_t;
 use std::{marker::Send, path::Path};
@@ -15,6 +20,9 @@ pub struct EthDeployer {
 
 #[async_trait]
 impl Deployer for EthDeployer {
+    type Ledger = Ethereum;
+    type Contract = Web3Contract;
+
     fn new(enclave_id: sgx_enclave_id_t, node_url: &str) -> Result<Self> {
         let web3_conn = Web3Http::new(node_url)?;
 
@@ -26,35 +34,36 @@ impl Deployer for EthDeployer {
     }
 
     async fn get_account(&self, index: usize, password: &str) -> Result<Address> {
-        self.web3_conn.get_account(index, password).await
+        self.web3_conn
+            .get_account(index, password)
+            .await
+            .map_err(Into::into)
     }
 
     async fn deploy<P: AsRef<Path> + Send>(
         &mut self,
-        host_output: host_output::JoinGroup,
+        host_output: host_output::JoinGroup<Address>,
         abi_path: P,
         bin_path: P,
-        confirmations: usize,
+        confirmations: u64,
+        config: TxConfig,
+        signer: Option<LocalSigner>,
     ) -> Result<String> {
+        let config = config.set_confirmations(confirmations);
         let contract_addr = self
             .web3_conn
-            .deploy(host_output, abi_path, bin_path, confirmations)
+            .deploy(host_output, abi_path, bin_path, config, signer)
             .await?;
         self.address = Some(contract_addr);
 
         Ok(hex::encode(contract_addr.as_bytes()))
     }
 
-    fn get_contract<P: AsRef<Path>>(self, abi_path: P) -> Result<ContractKind> {
-        let addr = self
-            .address
-            .expect("The contract hasn't be deployed yet.")
-            .to_string();
+    fn get_contract<P: AsRef<Path>>(self, abi_path: P) -> Result<Web3Contract> {
+        let addr =
+            Ethereum::format_address(&self.address.expect("The contract hasn't be deployed yet."));
         let contract_info = ContractInfo::new(abi_path, &addr);
-        Ok(ContractKind::Web3Contract(Web3Contract::new(
-            self.web3_conn,
-            contract_info,
-        )?))
+        Web3Contract::new(self.web3_conn, contract_info).map_err(Into::into)
     }
 
     fn get_enclave_id(&self) -> sgx_enclave_id_t {
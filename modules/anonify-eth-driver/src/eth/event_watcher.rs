@@ -1,43 +1,58 @@
-use super::connection::{Web3Contract, Web3Http};
-use crate::{
-    cache::{EventCache, MAX_TRIALS_NUM},
-    error::{HostError, Result},
-    traits::*,
-    utils::*,
-    workflow::*,
+use super::{
+    connection::{LogSubscription, Web3Contract, Web3Http},
+    Ethereum,
+};
+use crate::{error::Result, EventCache};
+use anonify_ledger_driver::{
+    fetch_blob, traits::*, workflow::*, BlobHash, ContractInfo, DeadLetter, HostError, LogIdOf,
+    Payload, PayloadType, SharedBlobStore, SkipReason, WatcherConfig, MAX_BLOB_TRIALS,
+    MAX_TRIALS_NUM,
 };
 use anyhow::anyhow;
 use async_trait::async_trait;
 use codec::Decode;
 use ethabi::{decode, Event, EventParam, Hash, ParamType};
 use frame_common::{
-    crypto::{Ciphertext, ExportHandshake},
+    crypto::{Ciphertext, ExportHandshake, StateCommitment},
     state_types::UpdatedState,
     traits::*,
 };
 use frame_host::engine::HostEngine;
+use futures::lock::Mutex;
 use log::{debug, error, info, warn};
 use sgx_types::sgx_enclave_id_t;
-use std::{cmp::Ordering, path::Path};
-use web3::types::{Address, Log};
+use std::{collections::HashMap, fmt, path::Path};
+use web3::types::{Address, Log, H256};
 
 /// Components needed to watch events
 pub struct EventWatcher {
     contract: Web3Contract,
     cache: EventCache,
+    config: WatcherConfig,
+    // Subscribed lazily on fetching, and again after it is closed
+    subscription: Mutex<Option<LogSubscription>>,
 }
 
 #[async_trait]
 impl Watcher for EventWatcher {
+    type Ledger = Ethereum;
+    type Contract = Web3Contract;
+
     fn new<P: AsRef<Path>>(
         node_url: &str,
         contract_info: ContractInfo<'_, P>,
         cache: EventCache,
-    ) -> Result<Self> {
+        config: WatcherConfig,
+    ) -> anonify_ledger_driver::Result<Self> {
         let web3_http = Web3Http::new(node_url)?;
         let contract = Web3Contract::new(web3_http, contract_info)?;
 
-        Ok(EventWatcher { contract, cache })
+        Ok(EventWatcher {
+            contract,
+            cache,
+            config,
+            subscription: Mutex::new(None),
+        })
     }
 
     /// Fetch events of the specified topics on the blockchain.
@@ -45,23 +60,57 @@ impl Watcher for EventWatcher {
     /// If an error occurs in the process of updating the status due to the fetched events,
     /// that events will be skipped. (No retry process)
     /// If an error occurs on all TEE nodes due to an invalid event etc., skip processing is okay.
+    /// Only the events in the blocks with the configured confirmations are fetched,
+    /// and the blocks before the last fetched one are fetched again if it has been reorganized.
     async fn fetch_events<S: State>(
         &self,
         eid: sgx_enclave_id_t,
-    ) -> Result<Option<Vec<UpdatedState<S>>>> {
-        let enclave_updated_state = self
+    ) -> anonify_ledger_driver::Result<Option<Vec<UpdatedState<S>>>> {
+        let mut subscription = self.subscription.lock().await;
+        if let Some(ws_url) = self.config.ws_url() {
+            if subscription.is_none() {
+                *subscription = Some(self.contract.subscribe_logs(ws_url).await?);
+            }
+        }
+        if let Some(sub) = subscription.as_mut() {
+            // The events missed while it is closed are fetched by `eth_getLogs` after resubscribing,
+            // because the fetched block number is not advanced.
+            if let Err(e) = sub.poll_logs() {
+                *subscription = None;
+                return Err(e.into());
+            }
+        }
+
+        let updated_states = self
             .contract
-            .get_event(self.cache.clone(), self.contract.address())
+            .get_event(
+                self.cache.clone(),
+                self.contract.address(),
+                self.config.confirmations(),
+                subscription.as_mut(),
+            )
             .await?
-            .into_enclave_log()
-            .insert_enclave(eid)
-            .save_cache(self.contract.address());
+            .fetch_blobs(self.config.blob_store())
+            .await?
+            .insert_enclave(eid, self.contract.address())?;
 
-        Ok(enclave_updated_state.updated_states())
+        Ok(updated_states)
     }
 
-    fn get_contract(self) -> ContractKind {
-        ContractKind::Web3Contract(self.contract)
+    async fn latest_block_num(&self) -> anonify_ledger_driver::Result<u64> {
+        self.contract.block_number().await.map_err(Into::into)
+    }
+
+    fn scanned_block_num(&self) -> Option<u64> {
+        self.cache
+            .inner()
+            .read()
+            .get_scanned_block(self.contract.address())
+            .map(|(block_num, _)| block_num)
+    }
+
+    fn get_contract(self) -> Web3Contract {
+        self.contract
     }
 }
 
@@ -71,25 +120,131 @@ pub struct Web3Logs {
     logs: Vec<Log>,
     cache: EventCache,
     events: EthEvent,
+    // The last block whose logs are fetched
+    scanned_block: Option<(u64, H256)>,
+    // The ciphertexts whose hashes are stored by the logs
+    blobs: HashMap<BlobHash, Vec<u8>>,
 }
 
 impl Web3Logs {
-    pub fn new(logs: Vec<Log>, cache: EventCache, events: EthEvent) -> Self {
+    pub fn new(
+        logs: Vec<Log>,
+        cache: EventCache,
+        events: EthEvent,
+        scanned_block: Option<(u64, H256)>,
+    ) -> Self {
         Web3Logs {
             logs,
             cache,
             events,
+            scanned_block,
+            blobs: HashMap::new(),
+        }
+    }
+
+    /// Fetch the ciphertexts whose hashes are stored by the logs from the blob store.
+    /// It fails if a ciphertext has not been stored yet, so that the logs are fetched again later,
+    /// but once it has been fetched `MAX_BLOB_TRIALS` times its log is skipped as a dead letter.
+    /// A blob which does not match the hash is never inserted into the enclave.
+    pub(crate) async fn fetch_blobs(
+        mut self,
+        blob_store: Option<&SharedBlobStore>,
+    ) -> Result<Self> {
+        let mut missing = None;
+        for log in &self.logs {
+            if log.topics[0] != self.events.ciphertext_hash_signature() {
+                continue;
+            }
+            let blob_store = blob_store.ok_or(HostError::BlobStoreNotSet)?;
+            let hash = decode_hash(log)?;
+            match fetch_blob(blob_store, &hash).await {
+                Ok(blob) => {
+                    self.cache.inner().write().remove_missing_blob(&hash);
+                    self.blobs.insert(hash, blob);
+                }
+                Err(HostError::BlobMismatch(e)) => {
+                    error!("The blob does not match the hash in the log: {}", e)
+                }
+                Err(HostError::BlobNotFound(e)) => {
+                    // Every missing blob is counted, not only the first one.
+                    let retry = self
+                        .cache
+                        .inner()
+                        .write()
+                        .retry_missing_blob(hash, MAX_BLOB_TRIALS);
+                    if retry {
+                        missing = missing.or(Some(e));
+                    } else {
+                        error!(
+                            "The blob has not been stored after {} trials, so its log is skipped: {}",
+                            MAX_BLOB_TRIALS, e
+                        );
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        match missing {
+            Some(e) => Err(HostError::BlobNotFound(e).into()),
+            None => Ok(self),
+        }
+    }
+
+    /// Insert the logs into the enclave, and advance the fetched block of the contract in the cache.
+    pub(crate) fn insert_enclave<S: State>(
+        self,
+        eid: sgx_enclave_id_t,
+        contract_addr: Address,
+    ) -> Result<Option<Vec<UpdatedState<S>>>> {
+        let updated_states = self
+            .into_enclave_log()
+            .insert_enclave(eid)
+            .save_cache(contract_addr)?
+            .updated_states();
+
+        Ok(updated_states)
+    }
+
+    /// Decode the ciphertext in the log, or the one fetched from the blob store by its hash.
+    fn decode_ciphertext(&self, log: &Log) -> Result<Ciphertext> {
+        if log.topics[0] == self.events.ciphertext_hash_signature() {
+            let hash = decode_hash(log)?;
+            let data = self
+                .blobs
+                .get(&hash)
+                .ok_or_else(|| anyhow!("The blob has not been stored: {}", hex::encode(hash)))?;
+            return Ciphertext::decode(&mut &data[..]).map_err(Into::into);
+        }
+
+        let data = decode_data(log)?;
+        let ciphertext = Ciphertext::decode(&mut &data[..])?;
+        check_indexed(log, ciphertext.roster_idx(), ciphertext.epoch())?;
+
+        Ok(ciphertext)
+    }
+
+    /// Keep the log which could not be decoded into a payload as a dead letter.
+    fn skip_log(&self, log: &Log, block_num: u64, err: impl fmt::Display) {
+        let mut letter = DeadLetter::new(SkipReason::Decode(err.to_string()))
+            .set_block_num(block_num)
+            .set_raw_log(log.data.0.clone());
+        if let Ok((roster_idx, _)) = decode_indexed(log) {
+            letter = letter.set_roster_idx(roster_idx);
         }
+        self.cache.inner().write().insert_dead_letter(letter);
     }
 
     fn into_enclave_log(self) -> EnclaveLog {
         let mut payloads: Vec<PayloadType> = vec![];
+        let mut commitments: Vec<(StateCommitment, u64)> = vec![];
 
         // If log data is not fetched, return empty EnclaveLog.
         // This is occurred when it fetched data of dupulicated block number.
         if self.logs.is_empty() {
             return EnclaveLog {
                 inner: None,
+                scanned_block: self.scanned_block,
                 cache: self.cache,
             };
         }
@@ -103,21 +258,37 @@ impl Web3Logs {
                 error!("Each log should have same contract address.: index: {}", i);
                 continue;
             }
-
-            let data = match decode_data(&log) {
-                Ok(d) => d,
-                Err(e) => {
-                    error!("{}", e);
+            if log.removed == Some(true) {
+                warn!("Skipped a log removed by a reorg: index: {}", i);
+                continue;
+            }
+            // The same log is fetched again both by the subscription and `eth_getLogs`,
+            // or after the fetch failed, but it must not ratchet the keychain twice.
+            let (block_num, log_id) = match log_position(log) {
+                Some(position) => position,
+                None => {
+                    error!("The log has not been mined: index: {}", i);
                     continue;
                 }
             };
+            if !self
+                .cache
+                .inner()
+                .write()
+                .insert_processed_log(contract_addr, block_num, log_id)
+            {
+                debug!("Skipped a log already inserted: {:?}", log_id);
+                continue;
+            }
 
             // Processing conditions by ciphertext or handshake event
-            if log.topics[0] == self.events.ciphertext_signature() {
-                let res = match Ciphertext::decode(&mut &data[..]) {
+            if log.topics[0] == self.events.ciphertext_signature()
+                || log.topics[0] == self.events.ciphertext_hash_signature()
+            {
+                let res = match self.decode_ciphertext(log) {
                     Ok(c) => c,
                     Err(e) => {
-                        error!("{}", e);
+                        self.skip_log(log, block_num, e);
                         continue;
                     }
                 };
@@ -126,13 +297,18 @@ impl Web3Logs {
                     res.epoch(),
                     res.generation(),
                     Payload::Ciphertext(res),
-                );
+                )
+                .set_position(block_num, log_id.2);
                 payloads.push(payload);
             } else if log.topics[0] == self.events.handshake_signature() {
-                let res = match ExportHandshake::decode(&mut &data[..]) {
+                let res = match decode_data(&log)
+                    .and_then(|data| ExportHandshake::decode(&mut &data[..]).map_err(Into::into))
+                    .and_then(|res| {
+                        check_indexed(log, res.roster_idx(), res.prior_epoch()).map(|_| res)
+                    }) {
                     Ok(c) => c,
                     Err(e) => {
-                        error!("{}", e);
+                        self.skip_log(log, block_num, e);
                         continue;
                     }
                 };
@@ -141,26 +317,39 @@ impl Web3Logs {
                     res.prior_epoch(),
                     u32::MAX, // handshake is the last of the generation
                     Payload::Handshake(res),
-                );
+                )
+                .set_position(block_num, log_id.2);
                 payloads.push(payload);
+            } else if log.topics[0] == self.events.state_commitment_signature() {
+                let res = match decode_data(&log)
+                    .and_then(|data| StateCommitment::decode(&mut &data[..]).map_err(Into::into))
+                    .and_then(|res| check_indexed(log, res.roster_idx(), res.epoch()).map(|_| res))
+                {
+                    Ok(c) => c,
+                    Err(e) => {
+                        self.skip_log(log, block_num, e);
+                        continue;
+                    }
+                };
+                commitments.push((res, block_num));
             } else {
-                error!("Invalid topics: {:?}", log.topics[0]);
+                self.skip_log(
+                    log,
+                    block_num,
+                    format!("Invalid topics: {:?}", log.topics[0]),
+                );
                 continue;
             }
 
             // Update latest block number
-            if let Some(blc_num) = log.block_number {
-                let blc_num = blc_num.as_u64();
-                if latest_blc_num < blc_num {
-                    latest_blc_num = blc_num
-                }
+            if latest_blc_num < block_num {
+                latest_blc_num = block_num
             }
         }
 
-        // Reordered by the priority in all fetched payloads
-        payloads.sort();
-        // Removes consecutive repeated message
-        payloads.dedup();
+        // Inserted in the chain order, so that the states after the same log are identical on every node
+        // however the logs are split into fetches. The same log is never inserted twice as it is recorded above.
+        payloads.sort_by_key(|payload| payload.position());
         // Order guarantee
         let immutable_payloads = payloads.clone();
         let payloads = {
@@ -173,8 +362,9 @@ impl Web3Logs {
                 contract_addr: contract_addr.to_fixed_bytes(),
                 latest_blc_num,
                 payloads,
-                logs: self.logs,
+                commitments,
             }),
+            scanned_block: self.scanned_block,
             cache: self.cache,
         }
     }
@@ -184,6 +374,7 @@ impl Web3Logs {
 #[derive(Debug)]
 struct EnclaveLog {
     inner: Option<InnerEnclaveLog>,
+    scanned_block: Option<(u64, H256)>,
     cache: EventCache,
 }
 
@@ -191,19 +382,23 @@ impl EnclaveLog {
     /// Store logs into enclave in-memory.
     /// This returns a latest block number specified by fetched logs.
     fn insert_enclave<S: State>(self, eid: sgx_enclave_id_t) -> EnclaveUpdatedState<S> {
+        // The next fetch starts from the block after the scanned one even if it has no logs.
+        let scanned_next = self.scanned_block.map(|(num, _)| num + 1);
         match self.inner {
             Some(log) => {
-                let next_blc_num = log.latest_blc_num + 1;
-                let updated_states = log.invoke_ecall(eid);
+                let next_blc_num = scanned_next.unwrap_or(log.latest_blc_num + 1);
+                let updated_states = log.invoke_ecall(eid, &self.cache);
 
                 EnclaveUpdatedState {
                     block_num: Some(next_blc_num),
+                    scanned_block: self.scanned_block,
                     updated_states,
                     cache: self.cache,
                 }
             }
             None => EnclaveUpdatedState {
-                block_num: None,
+                block_num: scanned_next,
+                scanned_block: self.scanned_block,
                 updated_states: None,
                 cache: self.cache,
             },
@@ -217,19 +412,41 @@ struct InnerEnclaveLog {
     contract_addr: [u8; 20],
     latest_blc_num: u64,
     payloads: Vec<PayloadType>,
-    logs: Vec<Log>,
+    // The state commitments of the members and the blocks containing them
+    commitments: Vec<(StateCommitment, u64)>,
 }
 
 impl InnerEnclaveLog {
-    fn invoke_ecall<S: State>(self, eid: sgx_enclave_id_t) -> Option<Vec<UpdatedState<S>>> {
-        if self.payloads.is_empty() {
+    /// The state commitments are checked after inserting the payloads,
+    /// since they are published after the ciphertexts they commit to.
+    fn invoke_ecall<S: State>(
+        self,
+        eid: sgx_enclave_id_t,
+        cache: &EventCache,
+    ) -> Option<Vec<UpdatedState<S>>> {
+        let updated_states = Self::insert_payloads(self.payloads, eid, cache);
+        let mut w = cache.inner().write();
+        for (commitment, block_num) in self.commitments {
+            w.check_state_commitment(commitment, Some(block_num));
+        }
+
+        updated_states
+    }
+
+    /// The payloads which fail in the enclave are skipped, and kept in the cache as dead letters.
+    fn insert_payloads<S: State>(
+        payloads: Vec<PayloadType>,
+        eid: sgx_enclave_id_t,
+        cache: &EventCache,
+    ) -> Option<Vec<UpdatedState<S>>> {
+        if payloads.is_empty() {
             debug!("No logs to insert into the enclave.");
             None
         } else {
             let mut acc = vec![];
 
-            for e in self.payloads {
-                match e.payload {
+            for payload in payloads {
+                match payload.clone().into_payload() {
                     Payload::Ciphertext(ciphertext) => {
                         info!(
                             "Fetch a ciphertext: roster_idx: {}, epoch: {}, generation: {}",
@@ -238,18 +455,37 @@ impl InnerEnclaveLog {
                             ciphertext.generation()
                         );
 
-                        let inp = host_input::InsertCiphertext::new(ciphertext.clone());
+                        let ciphertext_id = ciphertext.id();
+                        let inp = host_input::InsertCiphertext::new(ciphertext);
                         match InsertCiphertextWorkflow::exec(inp, eid)
                             .map_err(Into::into)
                             .and_then(|e| {
                                 e.ecall_output.ok_or_else(|| HostError::EcallOutputNotSet)
                             }) {
                             Ok(update) => {
+                                if let (Some(state_root), Some(position)) =
+                                    (update.state_root, payload.position())
+                                {
+                                    cache.inner().write().insert_state_root(
+                                        position,
+                                        ciphertext_id,
+                                        state_root,
+                                    );
+                                }
                                 if let Some(upd_type) = update.updated_state {
                                     match UpdatedState::<S>::from_state_type(upd_type) {
                                         Ok(upd_trait) => acc.push(upd_trait),
+                                        // The state has been updated in the enclave,
+                                        // so the payload is not replayable.
                                         Err(err) => {
-                                            error!("{:?}", err);
+                                            let mut letter = DeadLetter::new(
+                                                SkipReason::StateTransition(err.to_string()),
+                                            )
+                                            .set_roster_idx(payload.roster_idx());
+                                            if let Some(block_num) = payload.block_num() {
+                                                letter = letter.set_block_num(block_num);
+                                            }
+                                            cache.inner().write().insert_dead_letter(letter);
                                             continue;
                                         }
                                     }
@@ -262,37 +498,10 @@ impl InnerEnclaveLog {
                                     "Error in enclave (InsertCiphertextWorkflow::exec): {:?}",
                                     err
                                 );
-
-                                // Logging a skipped event
-                                match (&self.logs)
-                                    .into_iter()
-                                    .find(|log| match decode_data(&log) {
-                                        Ok(data) => match Ciphertext::decode(&mut &data[..]) {
-                                            Ok(res) => res == ciphertext,
-                                            Err(error) => {
-                                                error!("Ciphertext::decode error: {:?}", error);
-                                                false
-                                            }
-                                        },
-                                        Err(error) => {
-                                            error!("decode_data error: {:?}", error);
-                                            false
-                                        }
-                                    }) {
-                                    Some(skipped_log) => {
-                                        warn!(
-                                            "A event is skipped because of occurring error in enclave: {:?}",
-                                            skipped_log
-                                        )
-                                    }
-                                    None => {
-                                        error!(
-                                            "Not found the skipped event. The corresponding ciphertext is {:?}",
-                                            ciphertext
-                                        );
-                                    }
-                                }
-
+                                cache.inner().write().insert_dead_letter(
+                                    DeadLetter::new(SkipReason::Enclave(err.to_string()))
+                                        .set_payload(payload),
+                                );
                                 continue;
                             }
                         };
@@ -306,6 +515,10 @@ impl InnerEnclaveLog {
 
                         if let Err(e) = Self::insert_handshake(eid, handshake) {
                             error!("Error in enclave (InsertHandshakeWorkflow::exec): {:?}", e);
+                            cache.inner().write().insert_dead_letter(
+                                DeadLetter::new(SkipReason::Enclave(e.to_string()))
+                                    .set_payload(payload),
+                            );
                             continue;
                         }
                     }
@@ -331,23 +544,28 @@ impl InnerEnclaveLog {
 #[derive(Debug)]
 pub struct EnclaveUpdatedState<S: State> {
     block_num: Option<u64>,
+    scanned_block: Option<(u64, H256)>,
     updated_states: Option<Vec<UpdatedState<S>>>,
     cache: EventCache,
 }
 
 impl<S: State> EnclaveUpdatedState<S> {
     /// Only if EnclaveUpdatedState has new block number to log,
-    /// it's set next block number to event cache.
-    pub fn save_cache(self, contract_addr: Address) -> Self {
-        match &self.block_num {
-            Some(block_num) => {
-                let mut w = self.cache.inner().write();
-                w.insert_next_block_num(contract_addr, *block_num);
+    /// it's set next block number and the scanned block to event cache,
+    /// which are persisted together with the processed logs.
+    pub fn save_cache(self, contract_addr: Address) -> Result<Self> {
+        {
+            let mut w = self.cache.inner().write();
+            if let Some(block_num) = self.block_num {
+                w.insert_next_block_num(contract_addr, block_num);
+            }
+            if let Some(scanned_block) = self.scanned_block {
+                w.insert_scanned_block(contract_addr, scanned_block);
             }
-            None => {}
+            w.save_checkpoint(contract_addr)?;
         }
 
-        self
+        Ok(self)
     }
 
     pub fn updated_states(self) -> Option<Vec<UpdatedState<S>>> {
@@ -355,98 +573,6 @@ impl<S: State> EnclaveUpdatedState<S> {
     }
 }
 
-#[derive(Debug, Clone, Hash)]
-pub struct PayloadType {
-    roster_idx: u32,
-    epoch: u32,
-    generation: u32,
-    payload: Payload,
-}
-
-impl PayloadType {
-    pub(crate) fn new(roster_idx: u32, epoch: u32, generation: u32, payload: Payload) -> Self {
-        PayloadType {
-            roster_idx,
-            epoch,
-            generation,
-            payload,
-        }
-    }
-
-    /// other is the next of self
-    pub fn is_next(&self, other: &Self) -> bool {
-        self.roster_idx == other.roster_idx
-            && ((self.epoch == other.epoch && self.generation + 1 == other.generation) ||
-            (self.epoch == other.epoch && other.generation == u32::MAX) || // TODO: order gurantee with handshake
-            (self.epoch + 1 == other.epoch && self.generation == u32::MAX && other.generation == 0))
-    }
-
-    pub fn roster_idx(&self) -> u32 {
-        self.roster_idx
-    }
-
-    pub fn epoch(&self) -> u32 {
-        self.epoch
-    }
-
-    pub fn generation(&self) -> u32 {
-        self.generation
-    }
-}
-
-impl PartialEq for PayloadType {
-    fn eq(&self, other: &PayloadType) -> bool {
-        self.roster_idx == other.roster_idx
-            && self.epoch == other.epoch
-            && self.generation == other.generation
-    }
-}
-
-impl Eq for PayloadType {}
-
-/// Ordering PayloadType> like:
-/// epoch      | 0              1            2 ..
-/// generation | 0 1 2 3 .. MAX 0 1 2 .. MAX 0 ..
-impl PartialOrd for PayloadType {
-    fn partial_cmp(&self, other: &PayloadType) -> Option<Ordering> {
-        let roster_idx_ord = self.roster_idx.partial_cmp(&other.roster_idx)?;
-        if roster_idx_ord != Ordering::Equal {
-            return Some(roster_idx_ord);
-        }
-
-        let epoch_ord = self.epoch.partial_cmp(&other.epoch)?;
-        if epoch_ord != Ordering::Equal {
-            return Some(epoch_ord);
-        }
-
-        let gen_ord = self.generation.partial_cmp(&other.generation)?;
-        if gen_ord != Ordering::Equal {
-            return Some(gen_ord);
-        }
-
-        Some(Ordering::Equal)
-    }
-}
-
-impl Ord for PayloadType {
-    fn cmp(&self, other: &PayloadType) -> Ordering {
-        self.partial_cmp(&other)
-            .expect("PayloadType must be ordered")
-    }
-}
-
-#[derive(Debug, Clone, Hash)]
-pub(crate) enum Payload {
-    Ciphertext(Ciphertext),
-    Handshake(ExportHandshake),
-}
-
-impl Default for Payload {
-    fn default() -> Self {
-        Payload::Ciphertext(Default::default())
-    }
-}
-
 /// A type of events from ethererum network.
 #[derive(Debug)]
 pub struct EthEvent(Vec<Event>);
@@ -456,22 +582,52 @@ impl EthEvent {
         let events = vec![
             Event {
                 name: "StoreCiphertext".to_owned(),
-                inputs: vec![EventParam {
-                    name: "ciphertext".to_owned(),
-                    kind: ParamType::Bytes,
-                    indexed: false,
-                }],
+                inputs: vec![
+                    roster_idx_param(),
+                    epoch_param(),
+                    EventParam {
+                        name: "ciphertext".to_owned(),
+                        kind: ParamType::Bytes,
+                        indexed: false,
+                    },
+                ],
                 anonymous: false,
             },
             Event {
                 name: "StoreHandshake".to_owned(),
+                inputs: vec![
+                    roster_idx_param(),
+                    epoch_param(),
+                    EventParam {
+                        name: "handshake".to_owned(),
+                        kind: ParamType::Bytes,
+                        indexed: false,
+                    },
+                ],
+                anonymous: false,
+            },
+            Event {
+                name: "StoreCiphertextHash".to_owned(),
                 inputs: vec![EventParam {
-                    name: "handshake".to_owned(),
-                    kind: ParamType::Bytes,
+                    name: "ciphertextHash".to_owned(),
+                    kind: ParamType::FixedBytes(32),
                     indexed: false,
                 }],
                 anonymous: false,
             },
+            Event {
+                name: "StateCommitment".to_owned(),
+                inputs: vec![
+                    roster_idx_param(),
+                    epoch_param(),
+                    EventParam {
+                        name: "commitment".to_owned(),
+                        kind: ParamType::Bytes,
+                        indexed: false,
+                    },
+                ],
+                anonymous: false,
+            },
         ];
 
         EthEvent(events)
@@ -484,6 +640,42 @@ impl EthEvent {
     pub fn handshake_signature(&self) -> Hash {
         self.0[1].signature()
     }
+
+    pub fn ciphertext_hash_signature(&self) -> Hash {
+        self.0[2].signature()
+    }
+
+    pub fn state_commitment_signature(&self) -> Hash {
+        self.0[3].signature()
+    }
+}
+
+fn roster_idx_param() -> EventParam {
+    EventParam {
+        name: "rosterIdx".to_owned(),
+        kind: ParamType::Uint(32),
+        indexed: true,
+    }
+}
+
+fn epoch_param() -> EventParam {
+    EventParam {
+        name: "epoch".to_owned(),
+        kind: ParamType::Uint(32),
+        indexed: true,
+    }
+}
+
+/// The number of the block containing the log, and the identifier of the log
+fn log_position(log: &Log) -> Option<(u64, LogIdOf<Ethereum>)> {
+    let block_num = log.block_number?.as_u64();
+    let log_id = (
+        log.block_hash?,
+        log.transaction_hash?,
+        log.log_index?.as_u64(),
+    );
+
+    Some((block_num, log_id))
 }
 
 fn decode_data(log: &Log) -> Result<Vec<u8>> {
@@ -500,3 +692,264 @@ fn decode_data(log: &Log) -> Result<Vec<u8>> {
 
     Ok(res)
 }
+
+fn decode_hash(log: &Log) -> Result<BlobHash> {
+    let token = decode(&[ParamType::FixedBytes(32)], &log.data.0)?
+        .pop()
+        .and_then(|token| token.to_fixed_bytes())
+        .ok_or_else(|| anyhow!("Failed token.to_fixed_bytes() when decoding data"))?;
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&token);
+
+    Ok(hash)
+}
+
+/// Decode the roster index and the epoch in the topics following the event signature.
+pub(crate) fn decode_indexed(log: &Log) -> Result<(u32, u32)> {
+    if log.topics.len() != 3 {
+        return Err(anyhow!("Invalid number of topics: {}", log.topics.len()).into());
+    }
+    let decode_u32 = |topic: &H256| -> Result<u32> {
+        decode(&[ParamType::Uint(32)], topic.as_bytes())?
+            .pop()
+            .and_then(|token| token.to_uint())
+            .map(|value| value.low_u32())
+            .ok_or_else(|| anyhow!("Failed token.to_uint() when decoding topics").into())
+    };
+
+    Ok((decode_u32(&log.topics[1])?, decode_u32(&log.topics[2])?))
+}
+
+/// The indexed roster index and epoch must be the ones in the payload signed by the enclave,
+/// from which the contract reads them.
+fn check_indexed(log: &Log, roster_idx: u32, epoch: u32) -> Result<()> {
+    let indexed = decode_indexed(log)?;
+    if indexed != (roster_idx, epoch) {
+        return Err(anyhow!(
+            "The indexed (roster_idx, epoch) {:?} does not match the payload's {:?}",
+            indexed,
+            (roster_idx, epoch)
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::EthError;
+    use anonify_ledger_driver::{blob_hash, put_blob, CommitmentCheck, FsBlobStore};
+    use codec::Encode;
+    use ethabi::Token;
+    use futures::executor::block_on;
+    use std::sync::Arc;
+    use web3::types::Bytes;
+
+    fn hash_log(hash: BlobHash, log_index: u64) -> Log {
+        Log {
+            address: Address::from_low_u64_be(1),
+            topics: vec![EthEvent::create_event().ciphertext_hash_signature()],
+            data: Bytes(ethabi::encode(&[Token::FixedBytes(hash.to_vec())])),
+            block_hash: Some(H256::repeat_byte(1)),
+            block_number: Some(1.into()),
+            transaction_hash: Some(H256::repeat_byte(2)),
+            transaction_index: Some(0.into()),
+            log_index: Some(log_index.into()),
+            transaction_log_index: Some(log_index.into()),
+            log_type: None,
+            removed: Some(false),
+        }
+    }
+
+    fn ciphertext_log(ciphertext: &Ciphertext, block_num: u64, log_index: u64) -> Log {
+        Log {
+            address: Address::from_low_u64_be(1),
+            topics: vec![
+                EthEvent::create_event().ciphertext_signature(),
+                H256::from_low_u64_be(ciphertext.roster_idx().into()),
+                H256::from_low_u64_be(ciphertext.epoch().into()),
+            ],
+            data: Bytes(ethabi::encode(&[Token::Bytes(ciphertext.encode())])),
+            block_hash: Some(H256::from_low_u64_be(block_num)),
+            block_number: Some(block_num.into()),
+            transaction_hash: Some(H256::from_low_u64_be(block_num + log_index)),
+            transaction_index: Some(log_index.into()),
+            log_index: Some(log_index.into()),
+            transaction_log_index: Some(0.into()),
+            log_type: None,
+            removed: Some(false),
+        }
+    }
+
+    #[test]
+    fn test_fetch_blobs() {
+        let dir = std::env::temp_dir().join(format!("anonify-eth-blobs-{}", std::process::id()));
+        let store: SharedBlobStore = Arc::new(FsBlobStore::new(&dir).unwrap());
+        let ciphertext = Ciphertext::new(1, 0, 0, vec![1, 2, 3]);
+        let stored = block_on(put_blob(&store, ciphertext.encode())).unwrap();
+        let missing = blob_hash(b"missing");
+        let logs = vec![hash_log(stored, 0), hash_log(missing, 1)];
+        let cache = EventCache::default();
+        let web3_logs =
+            || Web3Logs::new(logs.clone(), cache.clone(), EthEvent::create_event(), None);
+
+        let res = block_on(web3_logs().fetch_blobs(None));
+        assert!(matches!(
+            res,
+            Err(EthError::HostError(HostError::BlobStoreNotSet))
+        ));
+
+        // The logs are fetched again while the blob may be stored later,
+        for _ in 1..MAX_BLOB_TRIALS {
+            let res = block_on(web3_logs().fetch_blobs(Some(&store)));
+            assert!(matches!(
+                res,
+                Err(EthError::HostError(HostError::BlobNotFound(_)))
+            ));
+        }
+        // but the log is skipped as a dead letter once the trials run out, not stalling the others.
+        let enclave_log = block_on(web3_logs().fetch_blobs(Some(&store)))
+            .unwrap()
+            .into_enclave_log();
+        let payloads = enclave_log.inner.unwrap().payloads;
+        assert_eq!(payloads.len(), 1);
+        assert_eq!(payloads[0].generation(), 1);
+
+        let dead_letters = cache.inner().read().dead_letters();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].block_num(), Some(1));
+        assert_eq!(dead_letters[0].raw_log(), Some(&logs[1].data.0[..]));
+        assert!(!dead_letters[0].is_replayable());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_skip_processed_logs() {
+        let dir = std::env::temp_dir().join(format!("anonify-eth-logs-{}", std::process::id()));
+        let store: SharedBlobStore = Arc::new(FsBlobStore::new(dir.join("blobs")).unwrap());
+        let hashes: Vec<BlobHash> = (0..2)
+            .map(|roster_idx| {
+                let ciphertext = Ciphertext::new(1, 0, roster_idx, vec![roster_idx as u8]);
+                block_on(put_blob(&store, ciphertext.encode())).unwrap()
+            })
+            .collect();
+        // The roster indices of the payloads to be inserted into the enclave
+        let roster_indices = |cache: &EventCache, logs: Vec<Log>| -> Vec<u32> {
+            let web3_logs = Web3Logs::new(logs, cache.clone(), EthEvent::create_event(), None);
+            block_on(web3_logs.fetch_blobs(Some(&store)))
+                .unwrap()
+                .into_enclave_log()
+                .inner
+                .map(|log| log.payloads.iter().map(|p| p.roster_idx()).collect())
+                .unwrap_or_default()
+        };
+
+        // The same log fetched twice in a fetch, e.g. by the subscription and `eth_getLogs`
+        let cache = EventCache::with_checkpoint_dir(dir.join("checkpoints"), None).unwrap();
+        let log = hash_log(hashes[0], 0);
+        assert_eq!(
+            roster_indices(&cache, vec![log.clone(), log.clone()]),
+            vec![0]
+        );
+        // and fetched again after the cursor is persisted and the node is restarted
+        let contract_addr = log.address;
+        {
+            let mut w = cache.inner().write();
+            w.restore_checkpoint(contract_addr).unwrap();
+            w.insert_next_block_num(contract_addr, 1);
+            w.insert_state_root((0, 0), (0, 0, 1), [1; 32]);
+            w.save_checkpoint(contract_addr).unwrap();
+        }
+        let reloaded =
+            EventCache::with_checkpoint_dir(dir.join("checkpoints"), Some([1; 32])).unwrap();
+        reloaded
+            .inner()
+            .write()
+            .restore_checkpoint(contract_addr)
+            .unwrap();
+        assert_eq!(
+            reloaded.inner().read().get_latest_block_num(contract_addr),
+            Some(1)
+        );
+        assert_eq!(
+            roster_indices(&reloaded, vec![log, hash_log(hashes[1], 1)]),
+            vec![1]
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_commitments_in_batch_splits() {
+        // The ciphertexts of two members interleaved in two blocks
+        let blocks: Vec<Vec<Log>> = vec![
+            vec![
+                ciphertext_log(&Ciphertext::new(1, 0, 1, vec![1]), 1, 0),
+                ciphertext_log(&Ciphertext::new(1, 0, 0, vec![2]), 1, 1),
+            ],
+            vec![
+                ciphertext_log(&Ciphertext::new(2, 0, 0, vec![3]), 2, 0),
+                ciphertext_log(&Ciphertext::new(2, 0, 1, vec![4]), 2, 1),
+            ],
+        ];
+        // Insert the fetched logs, emulating the enclave state as the chain of the applied ciphertexts
+        let ingest = |cache: &EventCache, logs: Vec<Log>, state_root: &mut [u8; 32]| {
+            let web3_logs = Web3Logs::new(logs, cache.clone(), EthEvent::create_event(), None);
+            let payloads = block_on(web3_logs.fetch_blobs(None))
+                .unwrap()
+                .into_enclave_log()
+                .inner
+                .unwrap()
+                .payloads;
+            let mut applied = vec![];
+            for payload in payloads {
+                let ciphertext_id = match payload.payload() {
+                    Payload::Ciphertext(ciphertext) => ciphertext.id(),
+                    Payload::Handshake(_) => unreachable!(),
+                };
+                *state_root = blob_hash(&[&state_root[..], &ciphertext_id.encode()].concat());
+                cache.inner().write().insert_state_root(
+                    payload.position().unwrap(),
+                    ciphertext_id,
+                    *state_root,
+                );
+                applied.push((ciphertext_id, *state_root));
+            }
+            applied
+        };
+
+        // One node fetches all the logs at once,
+        let whole = EventCache::default();
+        let whole_applied = ingest(&whole, blocks.concat(), &mut [0u8; 32]);
+        // and the other fetches them block by block.
+        let split = EventCache::default();
+        let mut split_root = [0u8; 32];
+        let split_applied: Vec<_> = blocks
+            .into_iter()
+            .flat_map(|logs| ingest(&split, logs, &mut split_root))
+            .collect();
+
+        // Both apply the ciphertexts in the chain order,
+        let ciphertext_ids: Vec<_> = whole_applied.iter().map(|(id, _)| *id).collect();
+        assert_eq!(
+            ciphertext_ids,
+            vec![(1, 0, 1), (0, 0, 1), (0, 0, 2), (1, 0, 2)]
+        );
+        assert_eq!(whole_applied, split_applied);
+        // so the commitments of one match the local state of the other.
+        for (ciphertext_id, state_root) in whole_applied {
+            let commitment = StateCommitment::new(0, ciphertext_id, state_root);
+            assert_eq!(
+                split
+                    .inner()
+                    .write()
+                    .check_state_commitment(commitment, None),
+                CommitmentCheck::Matched
+            );
+        }
+        assert!(split.inner().read().commitment_mismatches().is_empty());
+    }
+}
@@ -0,0 +1,60 @@
+use super::signer::LocalSigner;
+use crate::utils::TxConfig;
+use anonify_ledger_driver::{LedgerDriver, Result};
+use anyhow::anyhow;
+use codec::{Decode, Encode};
+use std::str::FromStr;
+use web3::types::{Address, H256};
+
+/// Ethereum and the compatible ledgers such as Quorum.
+/// A block is positioned by its number and hash, so that a reorganized block is detected.
+#[derive(Debug, Clone, Copy)]
+pub struct Ethereum;
+
+impl LedgerDriver for Ethereum {
+    type Address = Address;
+    type TxId = H256;
+    type BlockPosition = (u64, H256);
+    /// The hashes of the block and the transaction, and the index of the log in the block
+    type LogId = (H256, H256, u64);
+    type TxConfig = TxConfig;
+    type Signer = LocalSigner;
+
+    fn parse_address(addr: &str) -> Result<Address> {
+        Address::from_str(addr.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid address {}: {:?}", addr, e))
+            .map_err(Into::into)
+    }
+
+    fn format_address(addr: &Address) -> String {
+        format!("0x{}", hex::encode(addr))
+    }
+
+    fn signer_address(signer: &LocalSigner) -> Address {
+        signer.address()
+    }
+
+    fn encode_block_position(position: &(u64, H256)) -> Vec<u8> {
+        (position.0, position.1.to_fixed_bytes()).encode()
+    }
+
+    fn decode_block_position(bytes: &[u8]) -> Result<(u64, H256)> {
+        let (block_num, block_hash) = <(u64, [u8; 32])>::decode(&mut &bytes[..])?;
+        Ok((block_num, H256(block_hash)))
+    }
+
+    fn encode_log_id(log_id: &(H256, H256, u64)) -> Vec<u8> {
+        (
+            log_id.0.to_fixed_bytes(),
+            log_id.1.to_fixed_bytes(),
+            log_id.2,
+        )
+            .encode()
+    }
+
+    fn decode_log_id(bytes: &[u8]) -> Result<(H256, H256, u64)> {
+        let (block_hash, tx_hash, log_index) =
+            <([u8; 32], [u8; 32], u64)>::decode(&mut &bytes[..])?;
+        Ok((H256(block_hash), H256(tx_hash), log_index))
+    }
+}
@@ -1,8 +1,12 @@
 pub mod connection;
 pub mod deployer;
 pub mod event_watcher;
+mod ledger;
 pub mod sender;
+pub mod signer;
+pub mod tx_manager;
 
 pub use self::deployer::EthDeployer;
 pub use self::event_watcher::EventWatcher;
+pub use self::ledger::Ethereum;
 pub use self::sender::EthSender;
@@ -1,5 +1,10 @@
-use super::connection::{Web3Contract, Web3Http};
-use crate::{error::Result, traits::*, utils::*, workflow::*};
+use super::{
+    connection::{Web3Contract, Web3Http},
+    signer::LocalSigner,
+    Ethereum,
+};
+use crate::utils::TxConfig;
+use anonify_ledger_driver::{traits::*, workflow::*, ContractInfo, Result, SharedBlobStore};
 use async_trait::async_trait;
 use log::info;
 use sgx_types::sgx_enclave_id_t;
@@ -15,13 +20,20 @@ pub struct EthSender {
 
 #[async_trait]
 impl Sender for EthSender {
+    type Ledger = Ethereum;
+    type Contract = Web3Contract;
+
     fn new<P: AsRef<Path>>(
         enclave_id: sgx_enclave_id_t,
         node_url: &str,
         contract_info: ContractInfo<'_, P>,
+        config: TxConfig,
+        signer: Option<LocalSigner>,
     ) -> Result<Self> {
         let web3_http = Web3Http::new(node_url)?;
-        let contract = Web3Contract::new(web3_http, contract_info)?;
+        let contract = Web3Contract::new(web3_http, contract_info)?
+            .set_tx_config(config)
+            .set_signer(signer);
 
         Ok(EthSender {
             enclave_id,
@@ -29,46 +41,115 @@ impl Sender for EthSender {
         })
     }
 
-    fn from_contract(enclave_id: sgx_enclave_id_t, contract: ContractKind) -> Self {
-        match contract {
-            ContractKind::Web3Contract(contract) => EthSender {
-                enclave_id,
-                contract,
-            },
+    fn from_contract(enclave_id: sgx_enclave_id_t, contract: Web3Contract) -> Self {
+        EthSender {
+            enclave_id,
+            contract,
         }
     }
 
+    fn set_blob_store(mut self, blob_store: SharedBlobStore) -> Self {
+        self.contract = self.contract.set_blob_store(blob_store);
+        self
+    }
+
     async fn get_account(&self, index: usize, password: &str) -> Result<Address> {
-        self.contract.get_account(index, password).await
+        self.contract
+            .get_account(index, password)
+            .await
+            .map_err(Into::into)
     }
 
     async fn send_report_handshake(
         &self,
-        host_output: host_output::JoinGroup,
+        host_output: host_output::JoinGroup<Address>,
         method: &str,
     ) -> Result<H256> {
         info!("Sending a handshake to blockchain: {:?}", host_output);
         self.contract
             .send_report_handshake(host_output, method)
             .await
+            .map_err(Into::into)
+    }
+
+    async fn approve_mrenclave(&self, signer: Address, gas: u64, new_version: u32) -> Result<H256> {
+        info!(
+            "Approving the mrenclave version {} on blockchain",
+            new_version
+        );
+        self.contract
+            .approve_mrenclave(signer, gas, new_version)
+            .await
+            .map_err(Into::into)
     }
 
-    async fn register_report(&self, host_output: host_output::RegisterReport) -> Result<H256> {
+    async fn execute_mrenclave(&self, signer: Address, gas: u64, new_version: u32) -> Result<H256> {
+        info!(
+            "Executing the mrenclave version {} on blockchain",
+            new_version
+        );
+        self.contract
+            .execute_mrenclave(signer, gas, new_version)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn register_report(
+        &self,
+        host_output: host_output::RegisterReport<Address>,
+    ) -> Result<H256> {
         info!("Registering report to blockchain: {:?}", host_output);
-        self.contract.register_report(host_output).await
+        self.contract
+            .register_report(host_output)
+            .await
+            .map_err(Into::into)
     }
 
-    async fn send_command(&self, host_output: host_output::Command) -> Result<H256> {
+    async fn revoke_keys(
+        &self,
+        host_output: host_output::RotateIdentityKey<Address>,
+    ) -> Result<H256> {
+        info!(
+            "Revoking the old identity key on blockchain: {:?}",
+            host_output
+        );
+        self.contract
+            .revoke_keys(host_output)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn send_command(&self, host_output: host_output::Command<Address>) -> Result<H256> {
         info!("Sending a command to blockchain: {:?}", host_output);
-        self.contract.send_command(host_output).await
+        self.contract
+            .send_command(host_output)
+            .await
+            .map_err(Into::into)
     }
 
-    async fn handshake(&self, host_output: host_output::Handshake) -> Result<H256> {
+    async fn handshake(&self, host_output: host_output::Handshake<Address>) -> Result<H256> {
         info!("Sending a handshake to blockchain: {:?}", host_output);
-        self.contract.handshake(host_output).await
+        self.contract
+            .handshake(host_output)
+            .await
+            .map_err(Into::into)
     }
 
-    fn get_contract(self) -> ContractKind {
-        ContractKind::Web3Contract(self.contract)
+    async fn publish_state_commitment(
+        &self,
+        host_output: host_output::StateCommitment<Address>,
+    ) -> Result<H256> {
+        info!(
+            "Publishing a state commitment to blockchain: {:?}",
+            host_output
+        );
+        self.contract
+            .publish_state_commitment(host_output)
+            .await
+            .map_err(Into::into)
+    }
+
+    fn get_contract(self) -> Web3Contract {
+        self.contract
     }
 }
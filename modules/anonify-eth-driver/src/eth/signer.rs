@@ -0,0 +1,237 @@
+use crate::error::Result;
+use anonify_wallet::EthKeyFile;
+use anyhow::anyhow;
+use rand::Rng;
+use rlp::RlpStream;
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use std::{fmt, path::Path, str::FromStr};
+use tiny_keccak::{Hasher, Keccak};
+use web3::types::{Address, Bytes, U256};
+
+/// A transaction to be signed locally.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawTransaction {
+    pub nonce: U256,
+    pub gas_price: U256,
+    pub gas: U256,
+    /// `None` to deploy a contract
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Bytes,
+}
+
+/// Signs transactions with a secp256k1 key held by the host,
+/// so that they are sent via `eth_sendRawTransaction` without unlocking node accounts.
+#[derive(Clone)]
+pub struct LocalSigner {
+    secret: SecretKey,
+    address: Address,
+}
+
+impl LocalSigner {
+    pub fn from_secret(secret: &[u8]) -> Result<Self> {
+        let secret = SecretKey::from_slice(secret).map_err(|e| anyhow!("{:?}", e))?;
+        let public = PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret);
+        let address = public_to_address(&public);
+
+        Ok(LocalSigner { secret, address })
+    }
+
+    /// Load the key from a Web3 Secret Storage keystore, e.g. created by geth, encrypted with the password.
+    pub fn from_keyfile<P: AsRef<Path>>(path: P, password: &[u8]) -> Result<Self> {
+        let keyfile = EthKeyFile::load(path)?;
+        let signer = Self::from_secret(&keyfile.get_secret(password)?)?;
+        if let Some(address) = &keyfile.address {
+            // The address may be checksummed as in EIP-55.
+            let address = Address::from_str(address.trim_start_matches("0x"))
+                .map_err(|e| anyhow!("Invalid address of the keyfile: {:?}", e))?;
+            if signer.address != address {
+                return Err(anyhow!("The key does not match the address of the keyfile").into());
+            }
+        }
+
+        Ok(signer)
+    }
+
+    pub fn to_keyfile<R: Rng>(
+        &self,
+        password: &[u8],
+        iters: u32,
+        rng: &mut R,
+    ) -> Result<EthKeyFile> {
+        EthKeyFile::new(
+            &hex::encode(self.address),
+            password,
+            iters,
+            &self.secret[..],
+            rng,
+        )
+        .map_err(Into::into)
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Sign the transaction for the chain as specified in EIP-155, and return its RLP encoding.
+    pub fn sign(&self, tx: &RawTransaction, chain_id: u64) -> Result<Bytes> {
+        let mut stream = RlpStream::new_list(9);
+        tx.rlp_append_unsigned(&mut stream);
+        stream.append(&chain_id);
+        stream.append(&0u8);
+        stream.append(&0u8);
+        let hash = keccak256(&stream.out());
+
+        let message = Message::from_slice(&hash).map_err(|e| anyhow!("{:?}", e))?;
+        let (recovery_id, sig) = Secp256k1::signing_only()
+            .sign_recoverable(&message, &self.secret)
+            .serialize_compact();
+        let v = recovery_id.to_i32() as u64 + 35 + chain_id * 2;
+
+        let mut stream = RlpStream::new_list(9);
+        tx.rlp_append_unsigned(&mut stream);
+        stream.append(&v);
+        stream.append(&U256::from_big_endian(&sig[..32]));
+        stream.append(&U256::from_big_endian(&sig[32..]));
+
+        Ok(Bytes(stream.out()))
+    }
+}
+
+impl fmt::Debug for LocalSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalSigner")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+impl RawTransaction {
+    fn rlp_append_unsigned(&self, stream: &mut RlpStream) {
+        stream.append(&self.nonce);
+        stream.append(&self.gas_price);
+        stream.append(&self.gas);
+        match self.to {
+            Some(to) => stream.append(&to),
+            None => stream.append_empty_data(),
+        };
+        stream.append(&self.value);
+        stream.append(&self.data.0);
+    }
+}
+
+/// The address is the last 20 bytes of the hash of the uncompressed public key without its prefix.
+pub(crate) fn public_to_address(public: &PublicKey) -> Address {
+    let hash = keccak256(&public.serialize_uncompressed()[1..]);
+    Address::from_slice(&hash[12..])
+}
+
+pub(crate) fn keccak256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak::v256();
+    let mut hash = [0u8; 32];
+    hasher.update(data);
+    hasher.finalize(&mut hash);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // The example in EIP-155
+    #[test]
+    fn test_sign_eip155() {
+        let signer = LocalSigner::from_secret(&[0x46; 32]).unwrap();
+        let tx = RawTransaction {
+            nonce: 9.into(),
+            gas_price: 20_000_000_000u64.into(),
+            gas: 21000.into(),
+            to: Some(Address::from_str("3535353535353535353535353535353535353535").unwrap()),
+            value: 1_000_000_000_000_000_000u64.into(),
+            data: Bytes(vec![]),
+        };
+
+        let signed = signer.sign(&tx, 1).unwrap();
+        assert_eq!(
+            hex::encode(&signed.0),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340cd13d5e2f8a6d2d8a0d7e4ff2cc0ba8e2e1a4d5bc2b8fe21c5d02b2a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+        assert_eq!(
+            signer.address(),
+            Address::from_str("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_keyfile_roundtrip() {
+        let signer = LocalSigner::from_secret(&[0x46; 32]).unwrap();
+        let keyfile = signer
+            .to_keyfile(b"password", 1024, &mut rand::thread_rng())
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("eth-keyfile-{}", rand::random::<u64>()));
+        keyfile.store(&path).unwrap();
+
+        let loaded = LocalSigner::from_keyfile(&path, b"password").unwrap();
+        assert_eq!(loaded.address(), signer.address());
+        assert!(LocalSigner::from_keyfile(&path, b"wrong").is_err());
+        fs::remove_file(path).unwrap();
+    }
+
+    // Keystores of the key in the test vectors of the Web3 Secret Storage Definition
+    // with the light KDF parameters
+    #[test]
+    fn test_load_keystore() {
+        let pbkdf2 = r#"{
+            "address": "0x008AeEda4D805471dF9b2A5B0f38A0C3bCBA786b",
+            "crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": { "iv": "000102030405060708090a0b0c0d0e0f" },
+                "ciphertext": "fd69525bed80ecda3f150af8739e3adf4d309a772d3c621467a6800ec00d30eb",
+                "kdf": "pbkdf2",
+                "kdfparams": {
+                    "c": 2,
+                    "dklen": 32,
+                    "prf": "hmac-sha256",
+                    "salt": "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"
+                },
+                "mac": "cc392fbd717d6e911557c0ed87c525521a426b03bfa52e95591c56d921d44bca"
+            },
+            "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+            "version": 3
+        }"#;
+        let scrypt = r#"{
+            "Crypto": {
+                "cipher": "aes-128-ctr",
+                "cipherparams": { "iv": "000102030405060708090a0b0c0d0e0f" },
+                "ciphertext": "3cedcc974dbf1a72ec170a3b60a53c6b0333af5775ee2ff091664a90c33f34b0",
+                "kdf": "scrypt",
+                "kdfparams": {
+                    "dklen": 32,
+                    "n": 16,
+                    "p": 1,
+                    "r": 8,
+                    "salt": "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f"
+                },
+                "mac": "98d187e8f525023b38ad40ee1637baca3b29167f6d9b50440fff038515ae281c"
+            },
+            "id": "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+            "version": 3
+        }"#;
+        let address = Address::from_str("008aeeda4d805471df9b2a5b0f38a0c3bcba786b").unwrap();
+
+        for (name, keystore) in &[("pbkdf2", pbkdf2), ("scrypt", scrypt)] {
+            let path = std::env::temp_dir().join(format!(
+                "eth-keystore-{}-{}",
+                name,
+                rand::random::<u64>()
+            ));
+            fs::write(&path, keystore).unwrap();
+
+            let signer = LocalSigner::from_keyfile(&path, b"testpassword").unwrap();
+            assert_eq!(signer.address(), address);
+            assert!(LocalSigner::from_keyfile(&path, b"wrong").is_err());
+            fs::remove_file(path).unwrap();
+        }
+    }
+}
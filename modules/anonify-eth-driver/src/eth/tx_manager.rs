@@ -0,0 +1,661 @@
+use super::signer::{LocalSigner, RawTransaction};
+use crate::{
+    error::{EthError, Result},
+    utils::TxConfig,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures_timer::Delay;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use parking_lot::Mutex;
+use std::{collections::HashMap, fmt::Debug, time::Instant};
+use web3::{
+    transports::Http,
+    types::{
+        Address, BlockNumber, Bytes, CallRequest, TransactionReceipt, TransactionRequest, H256,
+        U256, U64,
+    },
+    Web3,
+};
+
+lazy_static! {
+    /// The next nonces of the accounts per node, shared by all the senders in a process
+    /// so that concurrent transactions from an account do not use the same nonce.
+    static ref NONCES: Mutex<HashMap<(String, Address), U256>> = Mutex::new(HashMap::new());
+}
+
+/// The RPCs of the node used to send transactions.
+#[async_trait]
+pub trait TxClient: Debug + Clone + Send + Sync {
+    async fn gas_price(&self) -> Result<U256>;
+
+    async fn estimate_gas(&self, req: CallRequest) -> Result<U256>;
+
+    async fn pending_transaction_count(&self, from: Address) -> Result<U256>;
+
+    async fn chain_id(&self) -> Result<U256>;
+
+    async fn send_raw_transaction(&self, raw_tx: Bytes) -> Result<H256>;
+
+    async fn send_transaction(&self, tx: TransactionRequest) -> Result<H256>;
+
+    async fn transaction_receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>>;
+
+    async fn block_number(&self) -> Result<U64>;
+}
+
+#[async_trait]
+impl TxClient for Web3<Http> {
+    async fn gas_price(&self) -> Result<U256> {
+        self.eth().gas_price().await.map_err(Into::into)
+    }
+
+    async fn estimate_gas(&self, req: CallRequest) -> Result<U256> {
+        self.eth().estimate_gas(req, None).await.map_err(Into::into)
+    }
+
+    async fn pending_transaction_count(&self, from: Address) -> Result<U256> {
+        self.eth()
+            .transaction_count(from, Some(BlockNumber::Pending))
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn chain_id(&self) -> Result<U256> {
+        self.eth().chain_id().await.map_err(Into::into)
+    }
+
+    async fn send_raw_transaction(&self, raw_tx: Bytes) -> Result<H256> {
+        self.eth()
+            .send_raw_transaction(raw_tx)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn send_transaction(&self, tx: TransactionRequest) -> Result<H256> {
+        self.eth().send_transaction(tx).await.map_err(Into::into)
+    }
+
+    async fn transaction_receipt(&self, tx_hash: H256) -> Result<Option<TransactionReceipt>> {
+        self.eth()
+            .transaction_receipt(tx_hash)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn block_number(&self) -> Result<U64> {
+        self.eth().block_number().await.map_err(Into::into)
+    }
+}
+
+/// Sends transactions with the estimated gas and locally managed nonces,
+/// and waits for their receipts, replacing them with higher gas prices if they are stuck.
+#[derive(Debug, Clone)]
+pub struct TxManager<C: TxClient = Web3<Http>> {
+    client: C,
+    eth_url: String,
+    config: TxConfig,
+    signer: Option<LocalSigner>,
+}
+
+impl<C: TxClient> TxManager<C> {
+    /// `eth_url` identifies the node whose nonces are shared by the managers in the process.
+    pub fn new(client: C, eth_url: &str, config: TxConfig) -> Self {
+        TxManager {
+            client,
+            eth_url: eth_url.to_string(),
+            config,
+            signer: None,
+        }
+    }
+
+    pub fn set_config(mut self, config: TxConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Sign transactions from the signer's address locally instead of by the node.
+    pub fn set_signer(mut self, signer: Option<LocalSigner>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    /// Send a transaction calling the contract, and return its hash after it is confirmed.
+    /// `gas_limit` is the upper bound of the estimated gas with the headroom.
+    pub async fn send(
+        &self,
+        from: Address,
+        to: Address,
+        data: Vec<u8>,
+        gas_limit: u64,
+    ) -> Result<H256> {
+        let receipt = self
+            .send_and_confirm(from, Some(to), data, gas_limit)
+            .await?;
+        Ok(receipt.transaction_hash)
+    }
+
+    /// Send a transaction creating a contract, and return its address after it is confirmed.
+    /// `data` is the contract bytecode followed by the encoded constructor parameters.
+    pub async fn deploy(&self, from: Address, data: Vec<u8>, gas_limit: u64) -> Result<Address> {
+        let receipt = self.send_and_confirm(from, None, data, gas_limit).await?;
+        receipt
+            .contract_address
+            .ok_or_else(|| anyhow!("No contract created by {:?}", receipt.transaction_hash))
+            .map_err(Into::into)
+    }
+
+    async fn send_and_confirm(
+        &self,
+        from: Address,
+        to: Option<Address>,
+        data: Vec<u8>,
+        gas_limit: u64,
+    ) -> Result<TransactionReceipt> {
+        let data = Bytes(data);
+        let gas = self.estimate_gas(from, to, data.clone(), gas_limit).await?;
+        let mut gas_price = self.client.gas_price().await?;
+        let nonce = self.next_nonce(from).await?;
+
+        let mut tx_hashes = vec![];
+        for replacements in 0..=self.config.max_replacements() {
+            if replacements > 0 {
+                gas_price =
+                    gas_price * U256::from(100 + self.config.gas_price_bump_percent()) / 100;
+                warn!(
+                    "Replacing the stuck transactions {:?} with gas price {}",
+                    tx_hashes, gas_price
+                );
+            }
+
+            let tx = RawTransaction {
+                nonce,
+                gas_price,
+                gas,
+                to,
+                value: U256::zero(),
+                data: data.clone(),
+            };
+            match self.submit(from, tx).await {
+                Ok(tx_hash) => tx_hashes.push(tx_hash),
+                // The previous one might be mined while replacing it.
+                Err(e) if replacements > 0 => warn!("Failed to replace the transaction: {}", e),
+                Err(e) => {
+                    self.reset_nonce(from);
+                    return Err(e);
+                }
+            }
+
+            if let Some(receipt) = self.wait_for_receipt(&tx_hashes).await? {
+                return self.wait_for_confirmations(receipt).await;
+            }
+        }
+
+        // The nonce might be left unused if none of them are mined.
+        self.reset_nonce(from);
+        Err(EthError::TxNotConfirmed(tx_hashes))
+    }
+
+    /// Sign the transaction locally if it is sent from the signer, otherwise let the node sign it.
+    async fn submit(&self, from: Address, tx: RawTransaction) -> Result<H256> {
+        match &self.signer {
+            Some(signer) if signer.address() == from => {
+                let chain_id = self.client.chain_id().await?.as_u64();
+                let raw_tx = signer.sign(&tx, chain_id)?;
+                self.client.send_raw_transaction(raw_tx).await
+            }
+            _ => {
+                let tx = TransactionRequest {
+                    from,
+                    to: tx.to,
+                    gas: Some(tx.gas),
+                    gas_price: Some(tx.gas_price),
+                    value: None,
+                    data: Some(tx.data),
+                    nonce: Some(tx.nonce),
+                    condition: None,
+                };
+                self.client.send_transaction(tx).await
+            }
+        }
+    }
+
+    /// The estimated gas with the headroom, capped at `gas_limit`.
+    /// It fails if the estimate itself exceeds `gas_limit`, because the transaction would run out of gas.
+    async fn estimate_gas(
+        &self,
+        from: Address,
+        to: Option<Address>,
+        data: Bytes,
+        gas_limit: u64,
+    ) -> Result<U256> {
+        let req = CallRequest {
+            from: Some(from),
+            to,
+            gas: None,
+            gas_price: None,
+            value: None,
+            data: Some(data),
+        };
+        let estimated = self.client.estimate_gas(req).await?;
+        if estimated > gas_limit.into() {
+            return Err(EthError::GasLimitExceeded {
+                estimated,
+                gas_limit,
+            });
+        }
+        let gas = estimated * U256::from(100 + self.config.gas_headroom_percent()) / 100;
+
+        Ok(gas.min(gas_limit.into()))
+    }
+
+    async fn next_nonce(&self, from: Address) -> Result<U256> {
+        let pending = self.client.pending_transaction_count(from).await?;
+
+        let mut nonces = NONCES.lock();
+        let next = nonces
+            .entry((self.eth_url.clone(), from))
+            .or_insert(pending);
+        // Transactions might be sent from the account by others.
+        if *next < pending {
+            *next = pending;
+        }
+        let nonce = *next;
+        *next = nonce + 1;
+
+        Ok(nonce)
+    }
+
+    /// Forget the local nonce so that the next one is read from the node.
+    fn reset_nonce(&self, from: Address) {
+        NONCES.lock().remove(&(self.eth_url.clone(), from));
+    }
+
+    /// Poll the receipt of any of the transactions until the replacement timeout.
+    async fn wait_for_receipt(&self, tx_hashes: &[H256]) -> Result<Option<TransactionReceipt>> {
+        let started_at = Instant::now();
+        while started_at.elapsed() < self.config.replacement_timeout() {
+            for tx_hash in tx_hashes {
+                if let Some(receipt) = self.client.transaction_receipt(*tx_hash).await? {
+                    if receipt.block_number.is_some() {
+                        return Ok(Some(receipt));
+                    }
+                }
+            }
+            Delay::new(self.config.poll_interval()).await;
+        }
+
+        Ok(None)
+    }
+
+    /// Wait until the block containing the transaction has enough confirmations,
+    /// failing if it takes longer than the confirmation timeout.
+    async fn wait_for_confirmations(
+        &self,
+        receipt: TransactionReceipt,
+    ) -> Result<TransactionReceipt> {
+        if receipt.status == Some(U64::zero()) {
+            return Err(EthError::TxFailed(receipt.transaction_hash));
+        }
+
+        let block_num = receipt.block_number.map(|n| n.as_u64()).unwrap_or_default();
+        let started_at = Instant::now();
+        loop {
+            let latest = self.client.block_number().await?.as_u64();
+            if latest + 1 >= block_num + self.config.confirmations() {
+                break;
+            }
+            if started_at.elapsed() >= self.config.confirmation_timeout() {
+                warn!(
+                    "Timed out waiting for the confirmations of {:?}",
+                    receipt.transaction_hash
+                );
+                return Err(EthError::TxNotConfirmed(vec![receipt.transaction_hash]));
+            }
+            Delay::new(self.config.poll_interval()).await;
+        }
+
+        // Ensure the transaction has not been dropped by a reorg while waiting.
+        match self
+            .client
+            .transaction_receipt(receipt.transaction_hash)
+            .await?
+        {
+            Some(r) if r.block_hash == receipt.block_hash => {
+                info!("Transaction confirmed: {:?}", receipt.transaction_hash);
+                Ok(receipt)
+            }
+            _ => Err(EthError::TxNotConfirmed(vec![receipt.transaction_hash])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, time::Duration};
+
+    #[derive(Debug, Default)]
+    struct MockChain {
+        pending_count: U256,
+        estimated_gas: U256,
+        /// The nonce and gas price of the submitted transactions in order
+        sent: Vec<(U256, U256)>,
+        /// The transactions signed locally in order
+        raw_sent: Vec<Bytes>,
+        /// The index of the submitted transaction which is mined
+        mined: Option<usize>,
+        block_num: u64,
+        /// Whether blocks are built on every poll of the block number
+        building: bool,
+        /// Whether the mined transaction is dropped by a reorg while waiting for confirmations
+        reorged: bool,
+        reverted: bool,
+    }
+
+    #[derive(Debug, Clone, Default)]
+    struct MockClient(Arc<Mutex<MockChain>>);
+
+    fn tx_hash(index: usize) -> H256 {
+        H256::from_low_u64_be(index as u64 + 1)
+    }
+
+    fn receipt(tx_hash: H256, block_num: u64, block_hash: H256, status: u64) -> TransactionReceipt {
+        serde_json::from_value(serde_json::json!({
+            "transactionHash": tx_hash,
+            "transactionIndex": "0x0",
+            "blockHash": block_hash,
+            "blockNumber": U64::from(block_num),
+            "cumulativeGasUsed": "0x0",
+            "logs": [],
+            "status": U64::from(status),
+            "logsBloom": web3::types::H2048::zero(),
+        }))
+        .unwrap()
+    }
+
+    #[async_trait]
+    impl TxClient for MockClient {
+        async fn gas_price(&self) -> Result<U256> {
+            Ok(100.into())
+        }
+
+        async fn estimate_gas(&self, _req: CallRequest) -> Result<U256> {
+            Ok(self.0.lock().estimated_gas)
+        }
+
+        async fn pending_transaction_count(&self, _from: Address) -> Result<U256> {
+            Ok(self.0.lock().pending_count)
+        }
+
+        async fn chain_id(&self) -> Result<U256> {
+            Ok(1.into())
+        }
+
+        async fn send_raw_transaction(&self, raw_tx: Bytes) -> Result<H256> {
+            let rlp = rlp::Rlp::new(&raw_tx.0);
+            let nonce = rlp.val_at(0).map_err(|e| anyhow!("{:?}", e))?;
+            let gas_price = rlp.val_at(1).map_err(|e| anyhow!("{:?}", e))?;
+
+            let mut chain = self.0.lock();
+            chain.sent.push((nonce, gas_price));
+            chain.raw_sent.push(raw_tx);
+            Ok(tx_hash(chain.sent.len() - 1))
+        }
+
+        async fn send_transaction(&self, tx: TransactionRequest) -> Result<H256> {
+            let mut chain = self.0.lock();
+            chain.sent.push((tx.nonce.unwrap(), tx.gas_price.unwrap()));
+            Ok(tx_hash(chain.sent.len() - 1))
+        }
+
+        async fn transaction_receipt(&self, hash: H256) -> Result<Option<TransactionReceipt>> {
+            let chain = self.0.lock();
+            match chain.mined {
+                Some(index) if tx_hash(index) == hash => {
+                    let block_hash = if chain.reorged && chain.block_num > 1 {
+                        H256::repeat_byte(2)
+                    } else {
+                        H256::repeat_byte(1)
+                    };
+                    let status = if chain.reverted { 0 } else { 1 };
+                    Ok(Some(receipt(hash, 1, block_hash, status)))
+                }
+                _ => Ok(None),
+            }
+        }
+
+        async fn block_number(&self) -> Result<U64> {
+            let mut chain = self.0.lock();
+            if chain.building {
+                chain.block_num += 1;
+            }
+            Ok(chain.block_num.into())
+        }
+    }
+
+    fn tx_manager(chain: MockChain, eth_url: &str) -> (MockClient, TxManager<MockClient>) {
+        let client = MockClient(Arc::new(Mutex::new(chain)));
+        let config = TxConfig::new()
+            .set_poll_interval(Duration::from_millis(1))
+            .set_replacement_timeout(Duration::from_millis(20))
+            .set_confirmation_timeout(Duration::from_millis(50))
+            .set_confirmations(3)
+            .set_max_replacements(2);
+        let tx_manager = TxManager::new(client.clone(), eth_url, config);
+        (client, tx_manager)
+    }
+
+    fn send(tx_manager: &TxManager<MockClient>) -> Result<H256> {
+        futures::executor::block_on(tx_manager.send(
+            Address::zero(),
+            Address::zero(),
+            vec![],
+            1_000_000,
+        ))
+    }
+
+    #[test]
+    fn test_next_nonce() {
+        let (client, tx_manager) = tx_manager(
+            MockChain {
+                pending_count: 5.into(),
+                ..Default::default()
+            },
+            "http://nonce",
+        );
+        let from = Address::zero();
+        let next_nonce = || futures::executor::block_on(tx_manager.next_nonce(from)).unwrap();
+
+        // Pending transactions sent by this process are counted locally.
+        assert_eq!(next_nonce(), 5.into());
+        assert_eq!(next_nonce(), 6.into());
+        // Transactions sent by others are caught up with.
+        client.0.lock().pending_count = 10.into();
+        assert_eq!(next_nonce(), 10.into());
+        // The nonce is read from the node after a failure.
+        client.0.lock().pending_count = 8.into();
+        tx_manager.reset_nonce(from);
+        assert_eq!(next_nonce(), 8.into());
+    }
+
+    #[test]
+    fn test_estimate_gas() {
+        let (client, tx_manager) = tx_manager(
+            MockChain {
+                estimated_gas: 1000.into(),
+                ..Default::default()
+            },
+            "http://estimate",
+        );
+        let estimate_gas = |gas_limit| {
+            futures::executor::block_on(tx_manager.estimate_gas(
+                Address::zero(),
+                None,
+                Bytes(vec![]),
+                gas_limit,
+            ))
+        };
+
+        assert_eq!(estimate_gas(2000).unwrap(), 1200.into());
+        // The headroom is capped at the limit.
+        assert_eq!(estimate_gas(1100).unwrap(), 1100.into());
+        match estimate_gas(900) {
+            Err(EthError::GasLimitExceeded { estimated, .. }) => assert_eq!(estimated, 1000.into()),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert!(client.0.lock().sent.is_empty());
+    }
+
+    #[test]
+    fn test_replace_stuck_transaction() {
+        let (client, tx_manager) = tx_manager(
+            MockChain {
+                pending_count: 3.into(),
+                mined: Some(1),
+                building: true,
+                ..Default::default()
+            },
+            "http://replace",
+        );
+
+        assert_eq!(send(&tx_manager).unwrap(), tx_hash(1));
+        // The replacement has the same nonce and the bumped gas price.
+        assert_eq!(
+            client.0.lock().sent,
+            vec![(3.into(), 100.into()), (3.into(), 112.into())]
+        );
+    }
+
+    #[test]
+    fn test_replace_locally_signed_transaction() {
+        let (client, tx_manager) = tx_manager(
+            MockChain {
+                pending_count: 3.into(),
+                mined: Some(1),
+                building: true,
+                ..Default::default()
+            },
+            "http://local-signer",
+        );
+        let signer = LocalSigner::from_secret(&[1u8; 32]).unwrap();
+        let from = signer.address();
+        let tx_manager = tx_manager.set_signer(Some(signer));
+
+        let tx_hash_sent =
+            futures::executor::block_on(tx_manager.send(from, Address::zero(), vec![1], 1_000_000))
+                .unwrap();
+        assert_eq!(tx_hash_sent, tx_hash(1));
+        // Both the transaction and its replacement are signed locally with the same nonce.
+        let chain = client.0.lock();
+        assert_eq!(
+            chain.sent,
+            vec![(3.into(), 100.into()), (3.into(), 112.into())]
+        );
+        assert_eq!(chain.raw_sent.len(), 2);
+        for raw_tx in &chain.raw_sent {
+            let rlp = rlp::Rlp::new(&raw_tx.0);
+            assert_eq!(rlp.item_count().unwrap(), 9);
+            assert_eq!(rlp.val_at::<Vec<u8>>(5).unwrap(), vec![1]);
+            // EIP-155 with the chain id 1
+            assert!([37u64, 38].contains(&rlp.val_at::<u64>(6).unwrap()));
+        }
+        drop(chain);
+
+        // Transactions from other accounts are signed by the node.
+        client.0.lock().mined = Some(2);
+        assert_eq!(send(&tx_manager).unwrap(), tx_hash(2));
+        assert_eq!(client.0.lock().raw_sent.len(), 2);
+        assert_eq!(client.0.lock().sent[2], (3.into(), 100.into()));
+    }
+
+    #[test]
+    fn test_not_mined_after_max_replacements() {
+        let (client, tx_manager) = tx_manager(
+            MockChain {
+                pending_count: 3.into(),
+                ..Default::default()
+            },
+            "http://not-mined",
+        );
+
+        match send(&tx_manager) {
+            Err(EthError::TxNotConfirmed(tx_hashes)) => {
+                assert_eq!(tx_hashes, vec![tx_hash(0), tx_hash(1), tx_hash(2)])
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        // The unused nonce is allocated again.
+        client.0.lock().mined = Some(3);
+        client.0.lock().building = true;
+        assert_eq!(send(&tx_manager).unwrap(), tx_hash(3));
+        assert_eq!(client.0.lock().sent[3].0, 3.into());
+    }
+
+    #[test]
+    fn test_wait_for_confirmations() {
+        let (client, tx_manager) = tx_manager(
+            MockChain {
+                mined: Some(0),
+                block_num: 1,
+                building: true,
+                ..Default::default()
+            },
+            "http://confirmations",
+        );
+        assert_eq!(send(&tx_manager).unwrap(), tx_hash(0));
+        // Waited until the block 3 is built.
+        assert!(client.0.lock().block_num >= 3);
+    }
+
+    #[test]
+    fn test_wait_for_confirmations_timeout() {
+        let (client, tx_manager) = tx_manager(
+            MockChain {
+                mined: Some(0),
+                block_num: 1,
+                ..Default::default()
+            },
+            "http://confirmation-timeout",
+        );
+        match send(&tx_manager) {
+            Err(EthError::TxNotConfirmed(tx_hashes)) => assert_eq!(tx_hashes, vec![tx_hash(0)]),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+        assert_eq!(client.0.lock().sent.len(), 1);
+    }
+
+    #[test]
+    fn test_dropped_while_waiting_for_confirmations() {
+        let (_, tx_manager) = tx_manager(
+            MockChain {
+                mined: Some(0),
+                building: true,
+                reorged: true,
+                ..Default::default()
+            },
+            "http://reorged",
+        );
+        match send(&tx_manager) {
+            Err(EthError::TxNotConfirmed(tx_hashes)) => assert_eq!(tx_hashes, vec![tx_hash(0)]),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_reverted_transaction() {
+        let (_, tx_manager) = tx_manager(
+            MockChain {
+                mined: Some(0),
+                reverted: true,
+                ..Default::default()
+            },
+            "http://reverted",
+        );
+        match send(&tx_manager) {
+            Err(EthError::TxFailed(hash)) => assert_eq!(hash, tx_hash(0)),
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+}
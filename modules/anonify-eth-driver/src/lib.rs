@@ -1,13 +1,25 @@
 #![crate_type = "lib"]
 
-mod cache;
-pub mod dispatcher;
+//! The Ethereum backend of the ledger driver.
+//! It also provides the sync service which keeps fetching the events of a dispatcher,
+//! and the in-process mock ledger emulating the Anonify contract with the `mock` feature.
+
 mod error;
 pub mod eth;
-pub mod traits;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod sync;
 mod utils;
-mod workflow;
 
-pub use cache::EventCache;
-pub use dispatcher::Dispatcher;
-pub use error::HostError;
+pub use anonify_ledger_driver::{
+    dispatcher, traits, BlobStore, CommitmentCheck, DeadLetter, Dispatcher, DispatcherRegistry,
+    FsBlobStore, HostError, HttpBlobStore, Payload, PayloadType, RemoteCommitment, SharedBlobStore,
+    SkipReason, WatcherConfig,
+};
+pub use error::EthError;
+pub use eth::Ethereum;
+pub use sync::{SyncConfig, SyncMetrics, SyncService, SyncState, SyncStatus, SyncWorker};
+pub use utils::{GovernanceConfig, TxConfig};
+
+/// The event cache of contracts on Ethereum
+pub type EventCache = anonify_ledger_driver::EventCache<Ethereum>;
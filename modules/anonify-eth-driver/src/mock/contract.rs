@@ -0,0 +1,193 @@
+use super::ledger::MockLedger;
+use crate::{
+    error::{EthError, Result},
+    eth::{
+        connection::RECOVERY_ID_OFFSET,
+        event_watcher::{EthEvent, Web3Logs},
+    },
+    EventCache,
+};
+use anonify_ledger_driver::{put_blob, workflow::*, HostError, SharedBlobStore};
+use web3::types::{Address, H256};
+
+/// An Anonify contract deployed on the mock ledger.
+#[derive(Debug, Clone)]
+pub struct MockContract {
+    ledger: MockLedger,
+    address: Address,
+    blob_store: Option<SharedBlobStore>,
+}
+
+impl MockContract {
+    pub fn new(ledger: MockLedger, address: Address) -> Self {
+        MockContract {
+            ledger,
+            address,
+            blob_store: None,
+        }
+    }
+
+    pub fn set_blob_store(mut self, blob_store: SharedBlobStore) -> Self {
+        self.blob_store = Some(blob_store);
+        self
+    }
+
+    pub fn send_report_handshake(
+        &self,
+        output: host_output::JoinGroup<Address>,
+        method: &str,
+    ) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let (report, report_sig, handshake) = (
+            ecall_output.report(),
+            ecall_output.report_sig(),
+            ecall_output.handshake(),
+        );
+
+        match method {
+            "joinGroup" => self.ledger.join_group(
+                self.address,
+                output.signer,
+                report,
+                report_sig,
+                handshake,
+                ecall_output.mrenclave_ver(),
+                ecall_output.roster_idx(),
+            ),
+            "proposeMrenclave" => self.ledger.propose_mrenclave(
+                self.address,
+                output.signer,
+                report,
+                report_sig,
+                handshake,
+                ecall_output.mrenclave_ver(),
+                ecall_output.roster_idx(),
+            ),
+            _ => Err(EthError::MockReverted("Unknown method")),
+        }
+    }
+
+    pub fn approve_mrenclave(&self, signer: Address, new_version: u32) -> Result<H256> {
+        self.ledger
+            .approve_mrenclave(self.address, signer, new_version)
+    }
+
+    pub fn execute_mrenclave(&self, signer: Address, new_version: u32) -> Result<H256> {
+        self.ledger
+            .execute_mrenclave(self.address, signer, new_version)
+    }
+
+    pub fn register_report(&self, output: host_output::RegisterReport<Address>) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+
+        self.ledger.register_report(
+            self.address,
+            output.signer,
+            ecall_output.report(),
+            ecall_output.report_sig(),
+            ecall_output.mrenclave_ver(),
+            ecall_output.roster_idx(),
+        )
+    }
+
+    /// Revoke the old identity key in favor of the rotated one, which must have been registered.
+    pub fn revoke_keys(&self, output: host_output::RotateIdentityKey<Address>) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let mut enclave_sig = ecall_output.encode_revocation_sig().to_vec();
+        enclave_sig.push(ecall_output.encode_recovery_id() + RECOVERY_ID_OFFSET);
+
+        self.ledger.revoke_keys(
+            self.address,
+            Address::from(*ecall_output.old_verifying_key()),
+            ecall_output.old_encrypting_key(),
+            Address::from(*ecall_output.new_verifying_key()),
+            &enclave_sig,
+        )
+    }
+
+    pub async fn send_command(&self, output: host_output::Command<Address>) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let ciphertext = ecall_output.encode_ciphertext();
+        let mut enclave_sig = ecall_output.encode_enclave_sig().to_vec();
+        enclave_sig.push(ecall_output.encode_recovery_id() + RECOVERY_ID_OFFSET);
+
+        match &self.blob_store {
+            Some(blob_store) => {
+                let ciphertext_hash = put_blob(blob_store, ciphertext).await?;
+                self.ledger
+                    .store_command_hash(self.address, ciphertext_hash, &enclave_sig)
+            }
+            None => self
+                .ledger
+                .store_command(self.address, &ciphertext, &enclave_sig),
+        }
+    }
+
+    pub fn handshake(&self, output: host_output::Handshake<Address>) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let handshake = ecall_output.encode_handshake();
+        let mut enclave_sig = ecall_output.encode_enclave_sig().to_vec();
+        enclave_sig.push(ecall_output.encode_recovery_id() + RECOVERY_ID_OFFSET);
+
+        self.ledger.handshake(
+            self.address,
+            output.signer,
+            &handshake,
+            &enclave_sig,
+            ecall_output.roster_idx(),
+        )
+    }
+
+    pub fn publish_state_commitment(
+        &self,
+        output: host_output::StateCommitment<Address>,
+    ) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let commitment = ecall_output.encode_commitment();
+        let mut enclave_sig = ecall_output.encode_enclave_sig().to_vec();
+        enclave_sig.push(ecall_output.encode_recovery_id() + RECOVERY_ID_OFFSET);
+
+        self.ledger
+            .publish_state_commitment(self.address, output.signer, &commitment, &enclave_sig)
+    }
+
+    /// Fetch the events in the blocks with enough confirmations since the last fetched one.
+    pub fn get_event(&self, cache: EventCache, confirmations: u64) -> Web3Logs {
+        let from_block = cache
+            .inner()
+            .read()
+            .get_latest_block_num(self.address)
+            .unwrap_or_default();
+        let to_block = match self.ledger.block_number().checked_sub(confirmations) {
+            Some(to_block) if from_block <= to_block => to_block,
+            _ => return Web3Logs::new(vec![], cache, EthEvent::create_event(), None),
+        };
+
+        let logs = self.ledger.get_logs(self.address, from_block, to_block);
+        let scanned_block = self
+            .ledger
+            .get_block_hash(to_block)
+            .map(|hash| (to_block, hash));
+        Web3Logs::new(logs, cache, EthEvent::create_event(), scanned_block)
+    }
+
+    pub fn block_number(&self) -> u64 {
+        self.ledger.block_number()
+    }
+
+    pub fn address(&self) -> Address {
+        self.address
+    }
+}
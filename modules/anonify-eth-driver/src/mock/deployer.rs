@@ -0,0 +1,76 @@
+use super::{contract::MockContract, ledger::MockLedger};
+use crate::{
+    eth::{signer::LocalSigner, Ethereum},
+    utils::TxConfig,
+};
+use anonify_ledger_driver::{traits::*, workflow::*, HostError, Result};
+use async_trait::async_trait;
+use sgx_types::sgx_enclave_id_t;
+use std::{marker::Send, path::Path};
+use web3::types::Address;
+
+/// Components needed to deploy a contract on the mock ledger
+#[derive(Debug)]
+pub struct MockDeployer {
+    enclave_id: sgx_enclave_id_t,
+    ledger: MockLedger,
+    node_url: String,
+    address: Option<Address>, // contract address
+}
+
+#[async_trait]
+impl Deployer for MockDeployer {
+    type Ledger = Ethereum;
+    type Contract = MockContract;
+
+    fn new(enclave_id: sgx_enclave_id_t, node_url: &str) -> Result<Self> {
+        Ok(MockDeployer {
+            enclave_id,
+            ledger: MockLedger::connect(node_url),
+            node_url: node_url.to_string(),
+            address: None,
+        })
+    }
+
+    async fn get_account(&self, index: usize, _password: &str) -> Result<Address> {
+        Ok(MockLedger::account(index))
+    }
+
+    async fn deploy<P: AsRef<Path> + Send>(
+        &mut self,
+        host_output: host_output::JoinGroup<Address>,
+        _abi_path: P,
+        _bin_path: P,
+        _confirmations: u64,
+        config: TxConfig,
+        _signer: Option<LocalSigner>,
+    ) -> Result<String> {
+        let ecall_output = host_output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let contract_addr = self.ledger.deploy(
+            host_output.signer,
+            ecall_output.report(),
+            ecall_output.report_sig(),
+            ecall_output.handshake(),
+            ecall_output.mrenclave_ver(),
+            config.governance(),
+        )?;
+        self.address = Some(contract_addr);
+
+        Ok(hex::encode(contract_addr.as_bytes()))
+    }
+
+    fn get_contract<P: AsRef<Path>>(self, _abi_path: P) -> Result<MockContract> {
+        let addr = self.address.expect("The contract hasn't be deployed yet.");
+        Ok(MockContract::new(self.ledger, addr))
+    }
+
+    fn get_enclave_id(&self) -> sgx_enclave_id_t {
+        self.enclave_id
+    }
+
+    fn get_node_url(&self) -> &str {
+        &self.node_url
+    }
+}
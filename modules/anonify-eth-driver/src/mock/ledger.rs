@@ -0,0 +1,1246 @@
+use crate::{
+    error::{EthError, Result},
+    eth::{
+        event_watcher::EthEvent,
+        signer::{keccak256, public_to_address},
+    },
+    utils::GovernanceConfig,
+};
+use ethabi::Token;
+use lazy_static::lazy_static;
+use parking_lot::{Mutex, RwLock};
+use secp256k1::{
+    recovery::{RecoverableSignature, RecoveryId},
+    Message, Secp256k1,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+};
+use web3::types::{Address, Bytes, Log, H256, U256};
+
+lazy_static! {
+    /// Ledgers shared by the deployers, senders and watchers connected to the same URL.
+    static ref LEDGERS: Mutex<HashMap<String, MockLedger>> = Mutex::new(HashMap::new());
+}
+
+// The offsets of the fields in the quote. See ReportHandle.sol.
+const MRENCLAVE_OFFSET: usize = 112;
+const VERIFYING_KEY_OFFSET: usize = 368;
+const ENCRYPTING_KEY_OFFSET: usize = 388;
+const ENCRYPTING_KEY_LEN: usize = 33;
+// The offsets of the fields in the SCALE-encoded ciphertext, handshake and state commitment. See Anonify.sol.
+const CIPHERTEXT_EPOCH_OFFSET: usize = 4;
+const CIPHERTEXT_ROSTER_IDX_OFFSET: usize = 8;
+const HANDSHAKE_EPOCH_OFFSET: usize = 0;
+const HANDSHAKE_ROSTER_IDX_OFFSET: usize = 4;
+const COMMITMENT_EPOCH_OFFSET: usize = 0;
+const COMMITMENT_ROSTER_IDX_OFFSET: usize = 4;
+// A proposal must be approved by another member than the proposer even in a group of one member.
+const MIN_APPROVALS: u32 = 2;
+// secp256k1n / 2, the upper bound of `s` which the contract accepts
+const HALF_CURVE_ORDER: &str = "7FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF5D576E7357A4501DDFE92F46681B20A0";
+
+/// An in-memory ledger which keeps an ordered log of the events of the Anonify contracts,
+/// enforcing the same rules as `Anonify.sol` on each transaction.
+/// Each transaction is included in a new block.
+/// The signature of the IAS report is not verified, but the report must contain a quote.
+#[derive(Debug, Clone, Default)]
+pub struct MockLedger {
+    inner: Arc<RwLock<InnerLedger>>,
+}
+
+#[derive(Debug, Default)]
+struct InnerLedger {
+    contracts: HashMap<Address, ContractState>,
+    // The hashes of the blocks indexed by their numbers
+    blocks: Vec<H256>,
+    logs: Vec<Log>,
+}
+
+/// The storage of `Anonify.sol`
+#[derive(Debug, Clone, Default)]
+struct ContractState {
+    mrenclave: [u8; 32],
+    mrenclave_ver: u32,
+    roster_idx_counter: u32,
+    sender_to_roster_idx: HashMap<Address, u32>,
+    key_epoch: u32,
+    // All the keys ever registered, which are never removed
+    keys: Vec<RegisteredKeys>,
+    // The proposals of mrenclave indexed by the new versions
+    proposals: HashMap<u32, MrenclaveProposal>,
+    // The pairs of the nonces of the proposals and the members who have approved them
+    approved: HashSet<(u64, Address)>,
+    proposal_nonce: u64,
+    // The number of the block being built, as `block.number`
+    block_number: u64,
+    mrenclave_timelock: u64,
+    approval_percent: u32,
+}
+
+#[derive(Debug, Clone)]
+struct MrenclaveProposal {
+    nonce: u64,
+    report_data: ReportData,
+    handshake: Vec<u8>,
+    proposed_at: u64,
+    key_epoch: u32,
+    approvals: u32,
+    executed: bool,
+}
+
+/// A pair of the verifying key and the encrypting key registered by the same report
+#[derive(Debug, Clone)]
+struct RegisteredKeys {
+    verifying_key: Address,
+    encrypting_key: Vec<u8>,
+    epoch: u32,
+    revoked: bool,
+}
+
+/// The keys and the measurement in the quote of a report
+#[derive(Debug, Clone)]
+struct ReportData {
+    mrenclave: [u8; 32],
+    verifying_key: Address,
+    encrypting_key: Vec<u8>,
+}
+
+impl MockLedger {
+    /// Connect to the ledger identified by the URL, which is created on the first connection.
+    pub fn connect(node_url: &str) -> Self {
+        LEDGERS
+            .lock()
+            .entry(node_url.to_string())
+            .or_insert_with(MockLedger::default)
+            .clone()
+    }
+
+    /// The account of the index, which is always available without unlocking.
+    pub fn account(index: usize) -> Address {
+        Address::from_low_u64_be(index as u64 + 1)
+    }
+
+    pub fn deploy(
+        &self,
+        sender: Address,
+        report: &[u8],
+        report_sig: &[u8],
+        handshake: &[u8],
+        mrenclave_ver: u32,
+        governance: &GovernanceConfig,
+    ) -> Result<Address> {
+        let mut inner = self.inner.write();
+        let contract_addr =
+            Address::from_slice(&keccak256(&[sender.as_bytes(), &inner.nonce()].concat())[12..]);
+
+        let mut contract = ContractState::default();
+        let report_data = contract.extract_from_report(report, report_sig)?;
+        let roster_idx = read_u32_le(handshake, HANDSHAKE_ROSTER_IDX_OFFSET)?;
+        require(roster_idx == 0, "First roster_idx must be zero")?;
+        require(
+            governance.approval_percent() >= 50 && governance.approval_percent() < 100,
+            "The approval percentage must be from 50 to 99",
+        )?;
+
+        contract.set_keys(report_data.verifying_key, report_data.encrypting_key);
+        contract.mrenclave = report_data.mrenclave;
+        contract.mrenclave_ver = mrenclave_ver;
+        contract.sender_to_roster_idx.insert(sender, roster_idx);
+        contract.roster_idx_counter = roster_idx;
+        contract.mrenclave_timelock = governance.mrenclave_timelock();
+        contract.approval_percent = governance.approval_percent();
+        inner.contracts.insert(contract_addr, contract);
+        inner.mine(contract_addr, vec![store_handshake(handshake)?]);
+
+        Ok(contract_addr)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn join_group(
+        &self,
+        contract_addr: Address,
+        sender: Address,
+        report: &[u8],
+        report_sig: &[u8],
+        handshake: &[u8],
+        version: u32,
+        roster_idx: u32,
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            require(contract.mrenclave_ver == version, "Must be same version")?;
+            require(
+                roster_idx == contract.roster_idx_counter + 1,
+                "Joining the group must be ordered accordingly by roster index",
+            )?;
+            require(
+                !contract.is_member(&sender),
+                "The msg.sender can join only once",
+            )?;
+
+            contract.handle_report(report, report_sig)?;
+            contract.sender_to_roster_idx.insert(sender, roster_idx);
+            contract.roster_idx_counter = roster_idx;
+            Ok(vec![store_handshake(handshake)?])
+        })
+    }
+
+    pub fn register_report(
+        &self,
+        contract_addr: Address,
+        sender: Address,
+        report: &[u8],
+        report_sig: &[u8],
+        version: u32,
+        roster_idx: u32,
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            require(contract.mrenclave_ver == version, "Must be same version")?;
+            require(
+                contract.is_member(&sender),
+                "The msg.sender has not joined the group",
+            )?;
+            require(
+                contract.roster_idx_of(&sender) == roster_idx,
+                "The roster index must be same as the registered one",
+            )?;
+
+            contract.handle_report(report, report_sig)?;
+            Ok(vec![])
+        })
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn propose_mrenclave(
+        &self,
+        contract_addr: Address,
+        sender: Address,
+        report: &[u8],
+        report_sig: &[u8],
+        handshake: &[u8],
+        new_version: u32,
+        roster_idx: u32,
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            require(
+                contract.is_member(&sender),
+                "The msg.sender has not joined the group",
+            )?;
+            require(
+                contract.roster_idx_of(&sender) == roster_idx,
+                "The roster index must be same as the registered one",
+            )?;
+            require(
+                read_u32_le(handshake, HANDSHAKE_ROSTER_IDX_OFFSET)? == roster_idx,
+                "The handshake must be of the proposer",
+            )?;
+            require(contract.mrenclave_ver != new_version, "Must be new version")?;
+            require(
+                contract
+                    .proposals
+                    .get(&new_version)
+                    .map_or(true, |proposal| proposal.key_epoch != contract.key_epoch),
+                "The version has already been proposed",
+            )?;
+
+            let report_data = contract.extract_from_report(report, report_sig)?;
+            require(
+                contract.mrenclave != report_data.mrenclave,
+                "mrenclave must be different one",
+            )?;
+            contract.proposal_nonce += 1;
+            let proposal = MrenclaveProposal {
+                nonce: contract.proposal_nonce,
+                report_data,
+                handshake: handshake.to_vec(),
+                proposed_at: contract.block_number,
+                key_epoch: contract.key_epoch,
+                approvals: 0,
+                executed: false,
+            };
+            contract.proposals.insert(new_version, proposal);
+            contract.approve(sender, new_version)?;
+            Ok(vec![])
+        })
+    }
+
+    pub fn approve_mrenclave(
+        &self,
+        contract_addr: Address,
+        sender: Address,
+        new_version: u32,
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            require(
+                contract.is_member(&sender),
+                "The msg.sender has not joined the group",
+            )?;
+
+            contract.approve(sender, new_version)?;
+            if contract.is_executable(new_version) {
+                contract.execute(new_version)
+            } else {
+                Ok(vec![])
+            }
+        })
+    }
+
+    pub fn execute_mrenclave(
+        &self,
+        contract_addr: Address,
+        sender: Address,
+        new_version: u32,
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            require(
+                contract.is_member(&sender),
+                "The msg.sender has not joined the group",
+            )?;
+            require(
+                contract.is_executable(new_version),
+                "The proposal is not executable",
+            )?;
+
+            contract.execute(new_version)
+        })
+    }
+
+    pub fn revoke_keys(
+        &self,
+        contract_addr: Address,
+        verifying_key: Address,
+        encrypting_key: &[u8],
+        new_verifying_key: Address,
+        enclave_sig: &[u8],
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            require(
+                contract.is_valid(&new_verifying_key),
+                "The new verifying key has not been registered.",
+            )?;
+            let msg = [
+                verifying_key.as_bytes(),
+                encrypting_key,
+                new_verifying_key.as_bytes(),
+            ]
+            .concat();
+            let recovered = recover(&msg, enclave_sig);
+            require(
+                !recovered.is_zero(),
+                "recovered verifyingKey was address(0)",
+            )?;
+            require(recovered == verifying_key, "Invalid enclave signature.")?;
+
+            let key_epoch = contract.key_epoch;
+            let keys = contract
+                .keys
+                .iter_mut()
+                .find(|keys| {
+                    keys.verifying_key == verifying_key && !keys.revoked && keys.epoch == key_epoch
+                })
+                .ok_or_else(|| {
+                    EthError::MockReverted("The verifying key has not been registered.")
+                })?;
+            require(
+                keys.encrypting_key == encrypting_key,
+                "The encrypting key is not paired with the verifying key.",
+            )?;
+            keys.revoked = true;
+            Ok(vec![])
+        })
+    }
+
+    pub fn store_command(
+        &self,
+        contract_addr: Address,
+        ciphertext: &[u8],
+        enclave_sig: &[u8],
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            contract.verify_enclave_sig(ciphertext, enclave_sig)?;
+            Ok(vec![store_ciphertext(ciphertext)?])
+        })
+    }
+
+    pub fn store_command_hash(
+        &self,
+        contract_addr: Address,
+        ciphertext_hash: [u8; 32],
+        enclave_sig: &[u8],
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            contract.verify_enclave_sig_of_hash(&ciphertext_hash, enclave_sig)?;
+            Ok(vec![store_ciphertext_hash(ciphertext_hash)])
+        })
+    }
+
+    pub fn handshake(
+        &self,
+        contract_addr: Address,
+        sender: Address,
+        handshake: &[u8],
+        enclave_sig: &[u8],
+        roster_idx: u32,
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            require(
+                contract.is_member(&sender),
+                "The msg.sender has not joined the group",
+            )?;
+            require(
+                contract.roster_idx_of(&sender) == roster_idx,
+                "The roster index must be same as the registered one",
+            )?;
+            let msg = [handshake, &roster_idx.to_be_bytes()].concat();
+            contract.verify_enclave_sig(&msg, enclave_sig)?;
+            Ok(vec![store_handshake(handshake)?])
+        })
+    }
+
+    pub fn publish_state_commitment(
+        &self,
+        contract_addr: Address,
+        sender: Address,
+        commitment: &[u8],
+        enclave_sig: &[u8],
+    ) -> Result<H256> {
+        self.transact(contract_addr, |contract| {
+            require(
+                contract.is_member(&sender),
+                "The msg.sender has not joined the group",
+            )?;
+            require(
+                contract.roster_idx_of(&sender)
+                    == read_u32_le(commitment, COMMITMENT_ROSTER_IDX_OFFSET)?,
+                "The roster index must be same as the registered one",
+            )?;
+            contract.verify_enclave_sig(commitment, enclave_sig)?;
+            Ok(vec![state_commitment(commitment)?])
+        })
+    }
+
+    pub fn block_number(&self) -> u64 {
+        self.inner.read().blocks.len().saturating_sub(1) as u64
+    }
+
+    /// `mrEnclave` of the contract
+    pub fn mrenclave(&self, contract_addr: Address) -> Result<[u8; 32]> {
+        self.inner
+            .read()
+            .contracts
+            .get(&contract_addr)
+            .map(|contract| contract.mrenclave)
+            .ok_or_else(|| EthError::MockReverted("The contract has not been deployed"))
+    }
+
+    /// `getEncryptingKey`, which returns the key only if it is registered in the current epoch and not revoked.
+    pub fn get_encrypting_key(
+        &self,
+        contract_addr: Address,
+        encrypting_key: &[u8],
+    ) -> Result<Vec<u8>> {
+        let inner = self.inner.read();
+        let contract = inner
+            .contracts
+            .get(&contract_addr)
+            .ok_or_else(|| EthError::MockReverted("The contract has not been deployed"))?;
+        let is_valid = contract.keys.iter().any(|keys| {
+            keys.encrypting_key == encrypting_key
+                && !keys.revoked
+                && keys.epoch == contract.key_epoch
+        });
+        require(is_valid, "The encrypting key has not been registered.")?;
+
+        Ok(encrypting_key.to_vec())
+    }
+
+    /// Build the empty blocks, e.g. to pass the timelock of a proposal.
+    pub fn mine_blocks(&self, num: u64) {
+        let mut inner = self.inner.write();
+        for _ in 0..num {
+            inner.mine(Address::zero(), vec![]);
+        }
+    }
+
+    pub fn get_block_hash(&self, block_num: u64) -> Option<H256> {
+        self.inner.read().blocks.get(block_num as usize).copied()
+    }
+
+    /// The logs of the contract in the blocks from `from_block` to `to_block` inclusive.
+    pub fn get_logs(&self, contract_addr: Address, from_block: u64, to_block: u64) -> Vec<Log> {
+        self.inner
+            .read()
+            .logs
+            .iter()
+            .filter(|log| {
+                let block_num = log.block_number.unwrap_or_default().as_u64();
+                log.address == contract_addr && from_block <= block_num && block_num <= to_block
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Apply the transaction to the contract, and include it in a new block if it succeeds.
+    /// The state is left unchanged if it is reverted.
+    fn transact<F>(&self, contract_addr: Address, f: F) -> Result<H256>
+    where
+        F: FnOnce(&mut ContractState) -> Result<Vec<(Vec<H256>, Token)>>,
+    {
+        let mut inner = self.inner.write();
+        let contract = inner
+            .contracts
+            .get(&contract_addr)
+            .ok_or_else(|| EthError::MockReverted("The contract has not been deployed"))?;
+
+        let mut updated = contract.clone();
+        updated.block_number = inner.blocks.len().max(1) as u64;
+        let events = f(&mut updated)?;
+        inner.contracts.insert(contract_addr, updated);
+
+        Ok(inner.mine(contract_addr, events))
+    }
+}
+
+impl InnerLedger {
+    fn nonce(&self) -> [u8; 8] {
+        (self.blocks.len() as u64).to_be_bytes()
+    }
+
+    /// Build a new block with a transaction emitting the events, and return the transaction hash.
+    fn mine(&mut self, contract_addr: Address, events: Vec<(Vec<H256>, Token)>) -> H256 {
+        if self.blocks.is_empty() {
+            self.blocks.push(H256::from(keccak256(b"genesis")));
+        }
+        let block_num = self.blocks.len() as u64;
+        let tx_hash = H256::from(keccak256(
+            &[contract_addr.as_bytes(), &block_num.to_be_bytes()].concat(),
+        ));
+        let block_hash = H256::from(keccak256(
+            &[
+                self.blocks[block_num as usize - 1].as_bytes(),
+                tx_hash.as_bytes(),
+            ]
+            .concat(),
+        ));
+        self.blocks.push(block_hash);
+
+        for (log_index, (topics, data)) in events.into_iter().enumerate() {
+            self.logs.push(Log {
+                address: contract_addr,
+                topics,
+                data: Bytes(ethabi::encode(&[data])),
+                block_hash: Some(block_hash),
+                block_number: Some(block_num.into()),
+                transaction_hash: Some(tx_hash),
+                transaction_index: Some(0.into()),
+                log_index: Some(log_index.into()),
+                transaction_log_index: Some(log_index.into()),
+                log_type: None,
+                removed: Some(false),
+            });
+        }
+
+        tx_hash
+    }
+}
+
+impl ContractState {
+    fn roster_idx_of(&self, sender: &Address) -> u32 {
+        self.sender_to_roster_idx.get(sender).copied().unwrap_or(0)
+    }
+
+    fn is_member(&self, sender: &Address) -> bool {
+        self.sender_to_roster_idx.contains_key(sender)
+    }
+
+    /// Whether the verifying key is registered in the current epoch and not revoked
+    fn is_valid(&self, verifying_key: &Address) -> bool {
+        self.keys.iter().any(|keys| {
+            keys.verifying_key == *verifying_key && !keys.revoked && keys.epoch == self.key_epoch
+        })
+    }
+
+    fn set_keys(&mut self, verifying_key: Address, encrypting_key: Vec<u8>) {
+        self.keys.push(RegisteredKeys {
+            verifying_key,
+            encrypting_key,
+            epoch: self.key_epoch,
+            revoked: false,
+        });
+    }
+
+    /// More than `approval_percent` of the members, and at least `MIN_APPROVALS`, must approve a proposal.
+    fn approval_threshold(&self) -> u32 {
+        let threshold = (self.roster_idx_counter + 1) * self.approval_percent / 100 + 1;
+        threshold.max(MIN_APPROVALS)
+    }
+
+    fn is_pending(&self, new_version: u32) -> bool {
+        self.proposals.get(&new_version).map_or(false, |proposal| {
+            !proposal.executed && proposal.key_epoch == self.key_epoch
+        })
+    }
+
+    fn is_executable(&self, new_version: u32) -> bool {
+        self.is_pending(new_version)
+            && self.proposals.get(&new_version).map_or(false, |proposal| {
+                proposal.approvals >= self.approval_threshold()
+                    && self.block_number >= proposal.proposed_at + self.mrenclave_timelock
+            })
+    }
+
+    fn approve(&mut self, sender: Address, new_version: u32) -> Result<()> {
+        require(self.is_pending(new_version), "The proposal is not pending")?;
+        let proposal = self
+            .proposals
+            .get_mut(&new_version)
+            .expect("pending proposal");
+        require(
+            self.approved.insert((proposal.nonce, sender)),
+            "The msg.sender has already approved",
+        )?;
+        proposal.approvals += 1;
+        Ok(())
+    }
+
+    fn execute(&mut self, new_version: u32) -> Result<Vec<(Vec<H256>, Token)>> {
+        let proposal = self
+            .proposals
+            .get_mut(&new_version)
+            .expect("executable proposal");
+        proposal.executed = true;
+        let proposal = proposal.clone();
+
+        require(
+            self.mrenclave != proposal.report_data.mrenclave,
+            "mrenclave must be different one",
+        )?;
+        self.key_epoch += 1;
+        self.set_keys(
+            proposal.report_data.verifying_key,
+            proposal.report_data.encrypting_key,
+        );
+        self.mrenclave = proposal.report_data.mrenclave;
+        self.mrenclave_ver = new_version;
+        Ok(vec![store_handshake(&proposal.handshake)?])
+    }
+
+    fn handle_report(&mut self, report: &[u8], report_sig: &[u8]) -> Result<()> {
+        let report_data = self.extract_from_report(report, report_sig)?;
+        require(
+            self.mrenclave == report_data.mrenclave,
+            "mrenclave included in the report is not correct.",
+        )?;
+
+        self.set_keys(report_data.verifying_key, report_data.encrypting_key);
+        Ok(())
+    }
+
+    fn extract_from_report(&self, report: &[u8], _report_sig: &[u8]) -> Result<ReportData> {
+        let quote = extract_quote(report)
+            .ok_or_else(|| EthError::MockReverted("isvEnclaveQuoteBody not found in report"))?;
+        let mut mrenclave = [0u8; 32];
+        mrenclave.copy_from_slice(&quote[MRENCLAVE_OFFSET..MRENCLAVE_OFFSET + 32]);
+        let verifying_key =
+            Address::from_slice(&quote[VERIFYING_KEY_OFFSET..VERIFYING_KEY_OFFSET + 20]);
+        let encrypting_key =
+            quote[ENCRYPTING_KEY_OFFSET..ENCRYPTING_KEY_OFFSET + ENCRYPTING_KEY_LEN].to_vec();
+
+        require(
+            !self
+                .keys
+                .iter()
+                .any(|keys| keys.verifying_key == verifying_key),
+            "The verifying key has already been registered.",
+        )?;
+        require(
+            !self
+                .keys
+                .iter()
+                .any(|keys| keys.encrypting_key == encrypting_key),
+            "The encrypting key has already been registered.",
+        )?;
+
+        Ok(ReportData {
+            mrenclave,
+            verifying_key,
+            encrypting_key,
+        })
+    }
+
+    fn verify_enclave_sig(&self, msg: &[u8], enclave_sig: &[u8]) -> Result<()> {
+        self.verify_enclave_sig_of_hash(&Sha256::digest(msg), enclave_sig)
+    }
+
+    fn verify_enclave_sig_of_hash(&self, hash: &[u8], enclave_sig: &[u8]) -> Result<()> {
+        let verifying_key = recover_hash(hash, enclave_sig);
+        require(
+            !verifying_key.is_zero(),
+            "recovered verifyingKey was address(0)",
+        )?;
+        require(self.is_valid(&verifying_key), "Invalid enclave signature.")
+    }
+}
+
+/// Recover the address which signed the SHA-256 hash of the message, as `Secp256k1.recover` does.
+/// It returns the zero address for an invalid signature.
+fn recover(msg: &[u8], sig: &[u8]) -> Address {
+    recover_hash(&Sha256::digest(msg), sig)
+}
+
+fn recover_hash(hash: &[u8], sig: &[u8]) -> Address {
+    if sig.len() != 65
+        || U256::from_big_endian(&sig[32..64])
+            > U256::from_str(HALF_CURVE_ORDER).expect("valid hex")
+    {
+        return Address::zero();
+    }
+    let recovery_id = match sig[64] {
+        27 | 28 => sig[64] - 27,
+        _ => return Address::zero(),
+    };
+
+    RecoveryId::from_i32(recovery_id as i32)
+        .and_then(|id| RecoverableSignature::from_compact(&sig[..64], id))
+        .and_then(|sig| {
+            let message = Message::from_slice(hash)?;
+            Secp256k1::verification_only().recover(&message, &sig)
+        })
+        .map(|public| public_to_address(&public))
+        .unwrap_or_else(|_| Address::zero())
+}
+
+fn extract_quote(report: &[u8]) -> Option<Vec<u8>> {
+    let report: serde_json::Value = serde_json::from_slice(report).ok()?;
+    let quote = base64::decode(report.get("isvEnclaveQuoteBody")?.as_str()?).ok()?;
+    if quote.len() < ENCRYPTING_KEY_OFFSET + ENCRYPTING_KEY_LEN {
+        return None;
+    }
+
+    Some(quote)
+}
+
+fn require(cond: bool, reason: &'static str) -> Result<()> {
+    if cond {
+        Ok(())
+    } else {
+        Err(EthError::MockReverted(reason))
+    }
+}
+
+/// Read a little-endian u32 in a SCALE-encoded payload, as `BytesUtils.toUint32LE` does.
+fn read_u32_le(data: &[u8], offset: usize) -> Result<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| EthError::MockReverted("Read out of bounds"))
+}
+
+fn indexed_topic(value: u32) -> H256 {
+    H256::from_low_u64_be(value as u64)
+}
+
+fn store_ciphertext(ciphertext: &[u8]) -> Result<(Vec<H256>, Token)> {
+    let topics = vec![
+        EthEvent::create_event().ciphertext_signature(),
+        indexed_topic(read_u32_le(ciphertext, CIPHERTEXT_ROSTER_IDX_OFFSET)?),
+        indexed_topic(read_u32_le(ciphertext, CIPHERTEXT_EPOCH_OFFSET)?),
+    ];
+    Ok((topics, Token::Bytes(ciphertext.to_vec())))
+}
+
+fn store_ciphertext_hash(ciphertext_hash: [u8; 32]) -> (Vec<H256>, Token) {
+    (
+        vec![EthEvent::create_event().ciphertext_hash_signature()],
+        Token::FixedBytes(ciphertext_hash.to_vec()),
+    )
+}
+
+fn store_handshake(handshake: &[u8]) -> Result<(Vec<H256>, Token)> {
+    let topics = vec![
+        EthEvent::create_event().handshake_signature(),
+        indexed_topic(read_u32_le(handshake, HANDSHAKE_ROSTER_IDX_OFFSET)?),
+        indexed_topic(read_u32_le(handshake, HANDSHAKE_EPOCH_OFFSET)?),
+    ];
+    Ok((topics, Token::Bytes(handshake.to_vec())))
+}
+
+fn state_commitment(commitment: &[u8]) -> Result<(Vec<H256>, Token)> {
+    let topics = vec![
+        EthEvent::create_event().state_commitment_signature(),
+        indexed_topic(read_u32_le(commitment, COMMITMENT_ROSTER_IDX_OFFSET)?),
+        indexed_topic(read_u32_le(commitment, COMMITMENT_EPOCH_OFFSET)?),
+    ];
+    Ok((topics, Token::Bytes(commitment.to_vec())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::eth::event_watcher::decode_indexed;
+    use codec::Encode;
+    use frame_common::crypto::StateCommitment;
+    use secp256k1::{PublicKey, SecretKey};
+
+    struct Enclave {
+        secret: SecretKey,
+        encrypting_key: Vec<u8>,
+    }
+
+    impl Enclave {
+        fn new(seed: u8) -> Self {
+            Enclave {
+                secret: SecretKey::from_slice(&[seed; 32]).unwrap(),
+                encrypting_key: vec![seed; ENCRYPTING_KEY_LEN],
+            }
+        }
+
+        fn verifying_key(&self) -> Address {
+            public_to_address(&PublicKey::from_secret_key(
+                &Secp256k1::signing_only(),
+                &self.secret,
+            ))
+        }
+
+        fn report(&self, mrenclave: u8) -> Vec<u8> {
+            let mut quote = vec![0u8; 432];
+            quote[MRENCLAVE_OFFSET..MRENCLAVE_OFFSET + 32].copy_from_slice(&[mrenclave; 32]);
+            quote[VERIFYING_KEY_OFFSET..VERIFYING_KEY_OFFSET + 20]
+                .copy_from_slice(self.verifying_key().as_bytes());
+            quote[ENCRYPTING_KEY_OFFSET..ENCRYPTING_KEY_OFFSET + ENCRYPTING_KEY_LEN]
+                .copy_from_slice(&self.encrypting_key);
+            serde_json::to_vec(&serde_json::json!({ "isvEnclaveQuoteBody": base64::encode(quote) }))
+                .unwrap()
+        }
+
+        fn sign(&self, msg: &[u8]) -> Vec<u8> {
+            let message = Message::from_slice(&Sha256::digest(msg)).unwrap();
+            let (recovery_id, sig) = Secp256k1::signing_only()
+                .sign_recoverable(&message, &self.secret)
+                .serialize_compact();
+            let mut sig = sig.to_vec();
+            sig.push(recovery_id.to_i32() as u8 + 27);
+            sig
+        }
+    }
+
+    fn handshake(roster_idx: u32) -> Vec<u8> {
+        let mut handshake = vec![0u8; 8];
+        handshake[HANDSHAKE_ROSTER_IDX_OFFSET..].copy_from_slice(&roster_idx.to_le_bytes());
+        handshake
+    }
+
+    fn ciphertext(roster_idx: u32, epoch: u32) -> Vec<u8> {
+        let mut ciphertext = vec![0u8; 16];
+        ciphertext[CIPHERTEXT_EPOCH_OFFSET..CIPHERTEXT_EPOCH_OFFSET + 4]
+            .copy_from_slice(&epoch.to_le_bytes());
+        ciphertext[CIPHERTEXT_ROSTER_IDX_OFFSET..CIPHERTEXT_ROSTER_IDX_OFFSET + 4]
+            .copy_from_slice(&roster_idx.to_le_bytes());
+        ciphertext
+    }
+
+    #[test]
+    fn test_roster_ordering() {
+        let ledger = MockLedger::default();
+        let (owner, member1, member2) = (
+            MockLedger::account(0),
+            MockLedger::account(1),
+            MockLedger::account(2),
+        );
+        let enclaves: Vec<Enclave> = (1..=3).map(Enclave::new).collect();
+
+        let contract_addr = ledger
+            .deploy(
+                owner,
+                &enclaves[0].report(1),
+                &[],
+                &handshake(0),
+                0,
+                &GovernanceConfig::default(),
+            )
+            .unwrap();
+        assert!(ledger
+            .join_group(
+                contract_addr,
+                member1,
+                &enclaves[1].report(1),
+                &[],
+                &handshake(2),
+                0,
+                2
+            )
+            .is_err());
+        assert!(ledger
+            .join_group(
+                contract_addr,
+                member1,
+                &enclaves[1].report(2),
+                &[],
+                &handshake(1),
+                0,
+                1
+            )
+            .is_err());
+        ledger
+            .join_group(
+                contract_addr,
+                member1,
+                &enclaves[1].report(1),
+                &[],
+                &handshake(1),
+                0,
+                1,
+            )
+            .unwrap();
+        // The keys are registered only once
+        assert!(ledger
+            .join_group(
+                contract_addr,
+                member2,
+                &enclaves[1].report(1),
+                &[],
+                &handshake(2),
+                0,
+                2
+            )
+            .is_err());
+        ledger
+            .join_group(
+                contract_addr,
+                member2,
+                &enclaves[2].report(1),
+                &[],
+                &handshake(2),
+                0,
+                2,
+            )
+            .unwrap();
+
+        // The deployment and the two joins emit a handshake in each block
+        assert_eq!(ledger.block_number(), 3);
+        assert_eq!(ledger.get_logs(contract_addr, 0, 3).len(), 3);
+    }
+
+    #[test]
+    fn test_enclave_signature() {
+        let ledger = MockLedger::default();
+        let owner = MockLedger::account(0);
+        let (enclave, other) = (Enclave::new(1), Enclave::new(2));
+        let contract_addr = ledger
+            .deploy(
+                owner,
+                &enclave.report(1),
+                &[],
+                &handshake(0),
+                0,
+                &GovernanceConfig::default(),
+            )
+            .unwrap();
+
+        let ciphertext = &ciphertext(0, 1)[..];
+        assert!(ledger
+            .store_command(contract_addr, ciphertext, &other.sign(ciphertext))
+            .is_err());
+        let mut malformed = enclave.sign(ciphertext);
+        malformed[64] -= 27;
+        assert!(ledger
+            .store_command(contract_addr, ciphertext, &malformed)
+            .is_err());
+        ledger
+            .store_command(contract_addr, ciphertext, &enclave.sign(ciphertext))
+            .unwrap();
+
+        let msg = [&handshake(0)[..], &1u32.to_be_bytes()].concat();
+        assert!(ledger
+            .handshake(contract_addr, owner, &handshake(0), &enclave.sign(&msg), 1)
+            .is_err());
+        let msg = [&handshake(0)[..], &0u32.to_be_bytes()].concat();
+        ledger
+            .handshake(contract_addr, owner, &handshake(0), &enclave.sign(&msg), 0)
+            .unwrap();
+
+        let logs = ledger.get_logs(contract_addr, 2, ledger.block_number());
+        assert_eq!(logs.len(), 2);
+        assert_eq!(
+            logs[0].topics[0],
+            EthEvent::create_event().ciphertext_signature()
+        );
+        // The roster index and the epoch in the ciphertext are indexed
+        assert_eq!(decode_indexed(&logs[0]).unwrap(), (0, 1));
+        assert_eq!(
+            logs[1].topics[0],
+            EthEvent::create_event().handshake_signature()
+        );
+    }
+
+    #[test]
+    fn test_state_commitment() {
+        let ledger = MockLedger::default();
+        let (owner, member) = (MockLedger::account(0), MockLedger::account(1));
+        let enclaves: Vec<Enclave> = (1..=2).map(Enclave::new).collect();
+        let contract_addr = ledger
+            .deploy(
+                owner,
+                &enclaves[0].report(1),
+                &[],
+                &handshake(0),
+                0,
+                &GovernanceConfig::default(),
+            )
+            .unwrap();
+        ledger
+            .join_group(
+                contract_addr,
+                member,
+                &enclaves[1].report(1),
+                &[],
+                &handshake(1),
+                0,
+                1,
+            )
+            .unwrap();
+
+        let commitment = StateCommitment::new(1, (0, 2, 3), [7u8; 32]).encode();
+        assert!(ledger
+            .publish_state_commitment(
+                contract_addr,
+                MockLedger::account(2),
+                &commitment,
+                &enclaves[1].sign(&commitment)
+            )
+            .is_err());
+        // Only the member of the roster index publishes its commitment
+        assert!(ledger
+            .publish_state_commitment(
+                contract_addr,
+                owner,
+                &commitment,
+                &enclaves[0].sign(&commitment)
+            )
+            .is_err());
+        ledger
+            .publish_state_commitment(
+                contract_addr,
+                member,
+                &commitment,
+                &enclaves[1].sign(&commitment),
+            )
+            .unwrap();
+
+        let logs = ledger.get_logs(contract_addr, 3, ledger.block_number());
+        assert_eq!(logs.len(), 1);
+        assert_eq!(
+            logs[0].topics[0],
+            EthEvent::create_event().state_commitment_signature()
+        );
+        assert_eq!(decode_indexed(&logs[0]).unwrap(), (1, 2));
+    }
+
+    #[test]
+    fn test_key_epoch() {
+        let ledger = MockLedger::default();
+        let (owner, member, other) = (
+            MockLedger::account(0),
+            MockLedger::account(1),
+            MockLedger::account(2),
+        );
+        let enclaves: Vec<Enclave> = (1..=4).map(Enclave::new).collect();
+        let contract_addr = ledger
+            .deploy(
+                owner,
+                &enclaves[0].report(1),
+                &[],
+                &handshake(0),
+                0,
+                &GovernanceConfig::default(),
+            )
+            .unwrap();
+        ledger
+            .join_group(
+                contract_addr,
+                member,
+                &enclaves[1].report(1),
+                &[],
+                &handshake(1),
+                0,
+                1,
+            )
+            .unwrap();
+
+        // Only the members can register reports, with their own roster indices
+        assert!(ledger
+            .register_report(contract_addr, other, &enclaves[2].report(1), &[], 0, 0)
+            .is_err());
+        assert!(ledger
+            .register_report(contract_addr, member, &enclaves[2].report(1), &[], 0, 0)
+            .is_err());
+
+        // The keys of the older epoch are invalidated by updating mrenclave
+        ledger
+            .propose_mrenclave(
+                contract_addr,
+                owner,
+                &enclaves[3].report(2),
+                &[],
+                &handshake(0),
+                1,
+                0,
+            )
+            .unwrap();
+        ledger.mine_blocks(GovernanceConfig::default().mrenclave_timelock());
+        ledger.approve_mrenclave(contract_addr, member, 1).unwrap();
+        let ciphertext = &ciphertext(1, 0)[..];
+        assert!(ledger
+            .store_command(contract_addr, ciphertext, &enclaves[1].sign(ciphertext))
+            .is_err());
+        ledger
+            .store_command(contract_addr, ciphertext, &enclaves[3].sign(ciphertext))
+            .unwrap();
+        // and never registered again.
+        assert!(ledger
+            .register_report(contract_addr, member, &enclaves[1].report(2), &[], 1, 1)
+            .is_err());
+        ledger
+            .register_report(contract_addr, member, &enclaves[2].report(2), &[], 1, 1)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_ciphertext_hash() {
+        let ledger = MockLedger::default();
+        let owner = MockLedger::account(0);
+        let (enclave, other) = (Enclave::new(1), Enclave::new(2));
+        let contract_addr = ledger
+            .deploy(
+                owner,
+                &enclave.report(1),
+                &[],
+                &handshake(0),
+                0,
+                &GovernanceConfig::default(),
+            )
+            .unwrap();
+
+        // The enclave signs the hash of the ciphertext in the same way as `store_command`.
+        let ciphertext = &ciphertext(0, 0)[..];
+        let mut ciphertext_hash = [0u8; 32];
+        ciphertext_hash.copy_from_slice(&Sha256::digest(ciphertext));
+        assert!(ledger
+            .store_command_hash(contract_addr, ciphertext_hash, &other.sign(ciphertext))
+            .is_err());
+        ledger
+            .store_command_hash(contract_addr, ciphertext_hash, &enclave.sign(ciphertext))
+            .unwrap();
+
+        let logs = ledger.get_logs(contract_addr, 2, ledger.block_number());
+        assert_eq!(logs.len(), 1);
+        assert_eq!(
+            logs[0].topics[0],
+            EthEvent::create_event().ciphertext_hash_signature()
+        );
+        assert_eq!(logs[0].data.0, ciphertext_hash.to_vec());
+    }
+
+    #[test]
+    fn test_mrenclave_governance() {
+        let ledger = MockLedger::default();
+        let governance = GovernanceConfig::new().set_mrenclave_timelock(10);
+        let members: Vec<Address> = (0..3).map(MockLedger::account).collect();
+        let enclaves: Vec<Enclave> = (1..=4).map(Enclave::new).collect();
+        assert!(ledger
+            .deploy(
+                members[0],
+                &enclaves[0].report(1),
+                &[],
+                &handshake(0),
+                0,
+                &GovernanceConfig::new().set_approval_percent(100)
+            )
+            .is_err());
+        let contract_addr = ledger
+            .deploy(
+                members[0],
+                &enclaves[0].report(1),
+                &[],
+                &handshake(0),
+                0,
+                &governance,
+            )
+            .unwrap();
+
+        let propose = |sender, new_version, roster_idx| {
+            ledger.propose_mrenclave(
+                contract_addr,
+                sender,
+                &enclaves[3].report(2),
+                &[],
+                &handshake(roster_idx),
+                new_version,
+                roster_idx,
+            )
+        };
+        // The owner alone never updates mrenclave, even after the timelock.
+        propose(members[0], 1, 0).unwrap();
+        ledger.mine_blocks(governance.mrenclave_timelock());
+        assert!(ledger
+            .execute_mrenclave(contract_addr, members[0], 1)
+            .is_err());
+
+        for roster_idx in 1..3 {
+            ledger
+                .join_group(
+                    contract_addr,
+                    members[roster_idx as usize],
+                    &enclaves[roster_idx as usize].report(1),
+                    &[],
+                    &handshake(roster_idx),
+                    0,
+                    roster_idx,
+                )
+                .unwrap();
+        }
+
+        // Any member proposes with its own roster index.
+        assert!(propose(MockLedger::account(3), 2, 0).is_err());
+        assert!(propose(members[1], 2, 0).is_err());
+        propose(members[1], 2, 1).unwrap();
+        assert!(propose(members[2], 2, 2).is_err());
+
+        // Two of the three members must approve it after the timelock.
+        assert!(ledger
+            .approve_mrenclave(contract_addr, MockLedger::account(3), 2)
+            .is_err());
+        assert!(ledger
+            .approve_mrenclave(contract_addr, members[1], 2)
+            .is_err());
+        ledger
+            .approve_mrenclave(contract_addr, members[2], 2)
+            .unwrap();
+        assert!(ledger
+            .execute_mrenclave(contract_addr, members[0], 2)
+            .is_err());
+        ledger.mine_blocks(governance.mrenclave_timelock());
+        let block_num = ledger.block_number();
+        ledger
+            .execute_mrenclave(contract_addr, members[0], 2)
+            .unwrap();
+
+        // The handshake of the new enclave is emitted on the execution
+        let logs = ledger.get_logs(contract_addr, block_num + 1, ledger.block_number());
+        assert_eq!(logs.len(), 1);
+        assert_eq!(
+            logs[0].topics[0],
+            EthEvent::create_event().handshake_signature()
+        );
+        assert_eq!(decode_indexed(&logs[0]).unwrap(), (1, 0));
+        assert!(ledger
+            .execute_mrenclave(contract_addr, members[0], 2)
+            .is_err());
+        // and the other proposals are outdated.
+        assert!(ledger
+            .approve_mrenclave(contract_addr, members[1], 1)
+            .is_err());
+    }
+}
@@ -0,0 +1,14 @@
+//! An in-process ledger emulating the Anonify contract,
+//! so that the dispatcher runs without blockchain nodes.
+//! The deployers, senders and watchers connected to the same URL share a ledger.
+
+pub mod contract;
+pub mod deployer;
+pub mod ledger;
+pub mod sender;
+pub mod watcher;
+
+pub use self::deployer::MockDeployer;
+pub use self::ledger::MockLedger;
+pub use self::sender::MockSender;
+pub use self::watcher::MockWatcher;
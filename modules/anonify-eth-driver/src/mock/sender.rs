@@ -0,0 +1,150 @@
+use super::{contract::MockContract, ledger::MockLedger};
+use crate::{
+    eth::{signer::LocalSigner, Ethereum},
+    utils::TxConfig,
+};
+use anonify_ledger_driver::{traits::*, workflow::*, ContractInfo, Result, SharedBlobStore};
+use async_trait::async_trait;
+use log::info;
+use sgx_types::sgx_enclave_id_t;
+use std::path::Path;
+use web3::types::{Address, H256};
+
+/// Components needed to send a transaction to the mock ledger
+#[derive(Debug)]
+pub struct MockSender {
+    enclave_id: sgx_enclave_id_t,
+    contract: MockContract,
+}
+
+#[async_trait]
+impl Sender for MockSender {
+    type Ledger = Ethereum;
+    type Contract = MockContract;
+
+    fn new<P: AsRef<Path>>(
+        enclave_id: sgx_enclave_id_t,
+        node_url: &str,
+        contract_info: ContractInfo<'_, P>,
+        _config: TxConfig,
+        _signer: Option<LocalSigner>,
+    ) -> Result<Self> {
+        let contract = MockContract::new(
+            MockLedger::connect(node_url),
+            contract_info.address::<Ethereum>()?,
+        );
+
+        Ok(MockSender {
+            enclave_id,
+            contract,
+        })
+    }
+
+    fn from_contract(enclave_id: sgx_enclave_id_t, contract: MockContract) -> Self {
+        MockSender {
+            enclave_id,
+            contract,
+        }
+    }
+
+    fn set_blob_store(mut self, blob_store: SharedBlobStore) -> Self {
+        self.contract = self.contract.set_blob_store(blob_store);
+        self
+    }
+
+    async fn get_account(&self, index: usize, _password: &str) -> Result<Address> {
+        Ok(MockLedger::account(index))
+    }
+
+    async fn send_report_handshake(
+        &self,
+        host_output: host_output::JoinGroup<Address>,
+        method: &str,
+    ) -> Result<H256> {
+        info!("Sending a handshake to the mock ledger: {:?}", host_output);
+        self.contract
+            .send_report_handshake(host_output, method)
+            .map_err(Into::into)
+    }
+
+    async fn approve_mrenclave(
+        &self,
+        signer: Address,
+        _gas: u64,
+        new_version: u32,
+    ) -> Result<H256> {
+        info!(
+            "Approving the mrenclave version {} on the mock ledger",
+            new_version
+        );
+        self.contract
+            .approve_mrenclave(signer, new_version)
+            .map_err(Into::into)
+    }
+
+    async fn execute_mrenclave(
+        &self,
+        signer: Address,
+        _gas: u64,
+        new_version: u32,
+    ) -> Result<H256> {
+        info!(
+            "Executing the mrenclave version {} on the mock ledger",
+            new_version
+        );
+        self.contract
+            .execute_mrenclave(signer, new_version)
+            .map_err(Into::into)
+    }
+
+    async fn register_report(
+        &self,
+        host_output: host_output::RegisterReport<Address>,
+    ) -> Result<H256> {
+        info!("Registering report to the mock ledger: {:?}", host_output);
+        self.contract
+            .register_report(host_output)
+            .map_err(Into::into)
+    }
+
+    async fn revoke_keys(
+        &self,
+        host_output: host_output::RotateIdentityKey<Address>,
+    ) -> Result<H256> {
+        info!(
+            "Revoking the old identity key on the mock ledger: {:?}",
+            host_output
+        );
+        self.contract.revoke_keys(host_output).map_err(Into::into)
+    }
+
+    async fn send_command(&self, host_output: host_output::Command<Address>) -> Result<H256> {
+        info!("Sending a command to the mock ledger: {:?}", host_output);
+        self.contract
+            .send_command(host_output)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn handshake(&self, host_output: host_output::Handshake<Address>) -> Result<H256> {
+        info!("Sending a handshake to the mock ledger: {:?}", host_output);
+        self.contract.handshake(host_output).map_err(Into::into)
+    }
+
+    async fn publish_state_commitment(
+        &self,
+        host_output: host_output::StateCommitment<Address>,
+    ) -> Result<H256> {
+        info!(
+            "Publishing a state commitment to the mock ledger: {:?}",
+            host_output
+        );
+        self.contract
+            .publish_state_commitment(host_output)
+            .map_err(Into::into)
+    }
+
+    fn get_contract(self) -> MockContract {
+        self.contract
+    }
+}
@@ -0,0 +1,70 @@
+use super::{contract::MockContract, ledger::MockLedger};
+use crate::{eth::Ethereum, EventCache};
+use anonify_ledger_driver::{traits::*, ContractInfo, Result, WatcherConfig};
+use async_trait::async_trait;
+use frame_common::{state_types::UpdatedState, traits::*};
+use sgx_types::sgx_enclave_id_t;
+use std::path::Path;
+
+/// Components needed to watch events on the mock ledger
+#[derive(Debug)]
+pub struct MockWatcher {
+    contract: MockContract,
+    cache: EventCache,
+    config: WatcherConfig,
+}
+
+#[async_trait]
+impl Watcher for MockWatcher {
+    type Ledger = Ethereum;
+    type Contract = MockContract;
+
+    fn new<P: AsRef<Path>>(
+        node_url: &str,
+        contract_info: ContractInfo<'_, P>,
+        cache: EventCache,
+        config: WatcherConfig,
+    ) -> Result<Self> {
+        let contract = MockContract::new(
+            MockLedger::connect(node_url),
+            contract_info.address::<Ethereum>()?,
+        );
+
+        Ok(MockWatcher {
+            contract,
+            cache,
+            config,
+        })
+    }
+
+    /// Fetch the events in the same way as `EventWatcher`, except that the mock ledger is never reorganized.
+    async fn fetch_events<S: State>(
+        &self,
+        eid: sgx_enclave_id_t,
+    ) -> Result<Option<Vec<UpdatedState<S>>>> {
+        let updated_states = self
+            .contract
+            .get_event(self.cache.clone(), self.config.confirmations())
+            .fetch_blobs(self.config.blob_store())
+            .await?
+            .insert_enclave(eid, self.contract.address())?;
+
+        Ok(updated_states)
+    }
+
+    async fn latest_block_num(&self) -> Result<u64> {
+        Ok(self.contract.block_number())
+    }
+
+    fn scanned_block_num(&self) -> Option<u64> {
+        self.cache
+            .inner()
+            .read()
+            .get_scanned_block(self.contract.address())
+            .map(|(block_num, _)| block_num)
+    }
+
+    fn get_contract(self) -> MockContract {
+        self.contract
+    }
+}
@@ -0,0 +1,366 @@
+use anonify_ledger_driver::{traits::*, AddressOf, Dispatcher, HostError, Result};
+use frame_common::traits::State;
+use futures::{
+    channel::oneshot,
+    future::{self, Either},
+};
+use futures_timer::Delay;
+use log::{error, info, warn};
+use parking_lot::Mutex;
+use std::{
+    marker::PhantomData,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// Configuration of how often the events are fetched and the state commitments are published.
+#[derive(Debug, Clone, Copy)]
+pub struct SyncConfig {
+    interval: Duration,
+    max_backoff: Duration,
+    commitment_interval: Option<Duration>,
+}
+
+impl SyncConfig {
+    pub fn new() -> Self {
+        SyncConfig::default()
+    }
+
+    /// Set the interval between the fetches.
+    pub fn set_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set the upper bound of the interval, which is doubled on each consecutive error.
+    pub fn set_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Set the interval between the state commitments published after the fetches.
+    /// No commitment is published if it is not set.
+    pub fn set_commitment_interval(mut self, commitment_interval: Duration) -> Self {
+        self.commitment_interval = Some(commitment_interval);
+        self
+    }
+
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    pub fn commitment_interval(&self) -> Option<Duration> {
+        self.commitment_interval
+    }
+
+    fn backoff(&self, consecutive_errors: u32) -> Duration {
+        self.interval
+            .checked_mul(1 << consecutive_errors.min(16))
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff))
+    }
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        SyncConfig {
+            interval: Duration::from_millis(1000),
+            max_backoff: Duration::from_secs(60),
+            commitment_interval: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncState {
+    Stopped,
+    Running,
+    /// The worker has been signalled to stop, but its current fetch has not finished yet.
+    Stopping,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SyncMetrics {
+    /// The last block whose events have been fetched
+    pub last_block: Option<u64>,
+    /// The latest block on the ledger when the events were fetched last
+    pub latest_block: Option<u64>,
+    /// The total number of failed fetches
+    pub errors: u64,
+    /// The number of failed fetches since the last successful one
+    pub consecutive_errors: u32,
+    pub last_error: Option<String>,
+    /// The number of the state commitments published by this node
+    pub commitments_published: u64,
+    /// The number of the commitments of the members which did not match the local state
+    pub commitment_mismatches: usize,
+}
+
+impl SyncMetrics {
+    /// The number of blocks whose events have not been fetched yet,
+    /// including the ones waiting for the confirmations.
+    pub fn lag(&self) -> Option<u64> {
+        match (self.latest_block, self.last_block) {
+            (Some(latest_block), Some(last_block)) => Some(latest_block.saturating_sub(last_block)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SyncStatus {
+    pub state: SyncState,
+    pub metrics: SyncMetrics,
+}
+
+/// A service keeping the enclave in sync with the events of a dispatcher's contract.
+/// At most one worker runs for a service at a time, and it backs off while fetching fails.
+#[derive(Debug)]
+pub struct SyncService<D: Deployer, S: Sender, W: Watcher, St> {
+    dispatcher: Arc<Dispatcher<D, S, W>>,
+    config: SyncConfig,
+    inner: Arc<Mutex<InnerSync>>,
+    _state: PhantomData<fn() -> St>,
+}
+
+#[derive(Debug)]
+struct InnerSync {
+    state: SyncState,
+    stop_tx: Option<oneshot::Sender<()>>,
+    metrics: SyncMetrics,
+}
+
+impl<D, S, W, St> SyncService<D, S, W, St>
+where
+    D: Deployer,
+    S: Sender<Ledger = D::Ledger>,
+    W: Watcher<Ledger = D::Ledger>,
+    St: State,
+{
+    pub fn new(dispatcher: Arc<Dispatcher<D, S, W>>) -> Self {
+        SyncService {
+            dispatcher,
+            config: SyncConfig::default(),
+            inner: Arc::new(Mutex::new(InnerSync {
+                state: SyncState::Stopped,
+                stop_tx: None,
+                metrics: SyncMetrics::default(),
+            })),
+            _state: PhantomData,
+        }
+    }
+
+    pub fn set_config(mut self, config: SyncConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Start the worker, which is returned to be run on the caller's runtime.
+    /// It fails if the worker of this service has not stopped yet.
+    pub fn start(&self) -> Result<SyncWorker<D, S, W, St>> {
+        let mut inner = self.inner.lock();
+        if inner.state != SyncState::Stopped {
+            return Err(HostError::SyncAlreadyRunning);
+        }
+        let (stop_tx, stop_rx) = oneshot::channel();
+        inner.state = SyncState::Running;
+        inner.stop_tx = Some(stop_tx);
+
+        Ok(SyncWorker {
+            dispatcher: self.dispatcher.clone(),
+            config: self.config,
+            inner: self.inner.clone(),
+            stop_rx,
+            commitment_signer: None,
+            last_commitment: None,
+            _state: PhantomData,
+        })
+    }
+
+    /// Signal the worker to stop after its current fetch.
+    /// It returns false if the worker is not running.
+    pub fn stop(&self) -> bool {
+        let mut inner = self.inner.lock();
+        match inner.stop_tx.take() {
+            Some(stop_tx) => {
+                inner.state = SyncState::Stopping;
+                let _ = stop_tx.send(());
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn status(&self) -> SyncStatus {
+        let inner = self.inner.lock();
+        SyncStatus {
+            state: inner.state,
+            metrics: inner.metrics.clone(),
+        }
+    }
+}
+
+/// The worker of a sync service, which fetches the events until the service stops it.
+/// It resets the state of the service when it is dropped,
+/// even if its runtime is shut down before it stops.
+#[derive(Debug)]
+pub struct SyncWorker<D: Deployer, S: Sender, W: Watcher, St> {
+    dispatcher: Arc<Dispatcher<D, S, W>>,
+    config: SyncConfig,
+    inner: Arc<Mutex<InnerSync>>,
+    stop_rx: oneshot::Receiver<()>,
+    // The account and the gas to publish the state commitments with
+    commitment_signer: Option<(AddressOf<D::Ledger>, u64)>,
+    last_commitment: Option<Instant>,
+    _state: PhantomData<fn() -> St>,
+}
+
+impl<D, S, W, St> SyncWorker<D, S, W, St>
+where
+    D: Deployer,
+    S: Sender<Ledger = D::Ledger>,
+    W: Watcher<Ledger = D::Ledger>,
+    St: State,
+{
+    /// Set the account publishing the state commitments at the configured interval.
+    pub fn set_commitment_signer(mut self, signer: AddressOf<D::Ledger>, gas: u64) -> Self {
+        self.commitment_signer = Some((signer, gas));
+        self
+    }
+
+    pub async fn run(mut self) {
+        info!("Started syncing with the ledger");
+        loop {
+            let delay = self.sync_once().await;
+            if let Either::Right(_) = future::select(Delay::new(delay), &mut self.stop_rx).await {
+                break;
+            }
+        }
+        info!("Stopped syncing with the ledger");
+    }
+
+    /// Fetch the events once, and return how long to wait for the next fetch.
+    async fn sync_once(&mut self) -> Duration {
+        let result = match self.dispatcher.fetch_events::<St>().await {
+            Ok(updated_states) => {
+                info!("State updated: {:?}", updated_states);
+                self.dispatcher.sync_position().await
+            }
+            Err(err) => Err(err),
+        };
+        let published = result.is_ok() && self.publish_commitment().await;
+
+        let mut inner = self.inner.lock();
+        let metrics = &mut inner.metrics;
+        match result {
+            Ok((latest_block, last_block)) => {
+                metrics.latest_block = Some(latest_block);
+                metrics.last_block = last_block;
+                metrics.consecutive_errors = 0;
+                metrics.commitments_published += published as u64;
+                metrics.commitment_mismatches = self.dispatcher.commitment_mismatches().len();
+                self.config.interval()
+            }
+            Err(err) => {
+                error!("event fetched error: {:?}", err);
+                metrics.errors += 1;
+                metrics.consecutive_errors += 1;
+                metrics.last_error = Some(err.to_string());
+                self.config.backoff(metrics.consecutive_errors)
+            }
+        }
+    }
+
+    /// Publish the state commitment if the interval has elapsed since the last one.
+    /// A failure is only logged, so that it does not back off the fetches.
+    async fn publish_commitment(&mut self) -> bool {
+        let (interval, (signer, gas)) =
+            match (self.config.commitment_interval(), self.commitment_signer) {
+                (Some(interval), Some(commitment_signer)) => (interval, commitment_signer),
+                _ => return false,
+            };
+        if let Some(last_commitment) = self.last_commitment {
+            if last_commitment.elapsed() < interval {
+                return false;
+            }
+        }
+
+        self.last_commitment = Some(Instant::now());
+        match self.dispatcher.publish_state_commitment(signer, gas).await {
+            Ok(Some(tx_id)) => {
+                info!("Published a state commitment: {:?}", tx_id);
+                true
+            }
+            Ok(None) => false,
+            Err(err) => {
+                warn!("Failed to publish a state commitment: {:?}", err);
+                false
+            }
+        }
+    }
+}
+
+impl<D: Deployer, S: Sender, W: Watcher, St> Drop for SyncWorker<D, S, W, St> {
+    fn drop(&mut self) {
+        let mut inner = self.inner.lock();
+        inner.state = SyncState::Stopped;
+        inner.stop_tx = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mock::{MockDeployer, MockSender, MockWatcher},
+        EventCache,
+    };
+
+    type MockSyncService = SyncService<MockDeployer, MockSender, MockWatcher, u64>;
+
+    #[test]
+    fn test_backoff() {
+        let config = SyncConfig::new()
+            .set_interval(Duration::from_secs(1))
+            .set_max_backoff(Duration::from_secs(10));
+
+        assert_eq!(config.backoff(1), Duration::from_secs(2));
+        assert_eq!(config.backoff(3), Duration::from_secs(8));
+        assert_eq!(config.backoff(4), Duration::from_secs(10));
+        assert_eq!(config.backoff(u32::MAX), Duration::from_secs(10));
+        assert!(config.commitment_interval().is_none());
+    }
+
+    #[test]
+    fn test_single_worker() {
+        let dispatcher = Dispatcher::new(0, "mock://sync", EventCache::default()).unwrap();
+        let service = MockSyncService::new(Arc::new(dispatcher))
+            .set_config(SyncConfig::new().set_interval(Duration::from_millis(10)));
+        assert!(!service.stop());
+
+        let worker = service.start().unwrap();
+        assert_eq!(service.status().state, SyncState::Running);
+        assert!(matches!(
+            service.start(),
+            Err(HostError::SyncAlreadyRunning)
+        ));
+
+        assert!(service.stop());
+        assert_eq!(service.status().state, SyncState::Stopping);
+        assert!(service.start().is_err());
+
+        // The event watcher has not been set, so the fetch fails before the worker stops.
+        futures::executor::block_on(worker.run());
+        let status = service.status();
+        assert_eq!(status.state, SyncState::Stopped);
+        assert_eq!(status.metrics.errors, 1);
+        assert!(status.metrics.last_error.is_some());
+
+        let worker = service.start().unwrap();
+        drop(worker);
+        assert_eq!(service.status().state, SyncState::Stopped);
+    }
+}
@@ -1,67 +1,171 @@
-use crate::{error::Result, eth::connection::Web3Contract};
-use anonify_io_types::*;
+use crate::error::Result;
 use anyhow::anyhow;
 use ethabi::Contract as ContractABI;
-use frame_common::traits::*;
-use frame_treekem::EciesCiphertext;
-use std::{fs::File, io::BufReader, marker::PhantomData, path::Path, str::FromStr};
-use web3::types::Address;
-
-/// Needed information to handle smart contracts.
-#[derive(Debug, Clone, Copy)]
-pub struct ContractInfo<'a, P: AsRef<Path>> {
-    abi_path: P,
-    addr: &'a str,
+use std::{fs::File, io::BufReader, path::Path, time::Duration};
+
+pub(crate) fn contract_abi<P: AsRef<Path>>(abi_path: P) -> Result<ContractABI> {
+    let f = File::open(abi_path)?;
+    let reader = BufReader::new(f);
+
+    ContractABI::load(reader)
+        .map_err(|e| anyhow!("Failed to load contract abi.: {:?}", e))
+        .map_err(Into::into)
+}
+
+/// Configuration of how transactions are sent and confirmed.
+#[derive(Debug, Clone)]
+pub struct TxConfig {
+    gas_headroom_percent: u64,
+    confirmations: u64,
+    poll_interval: Duration,
+    replacement_timeout: Duration,
+    confirmation_timeout: Duration,
+    gas_price_bump_percent: u64,
+    max_replacements: u32,
+    governance: GovernanceConfig,
 }
 
-impl<'a, P: AsRef<Path>> ContractInfo<'a, P> {
-    pub fn new(abi_path: P, addr: &'a str) -> Self {
-        ContractInfo { abi_path, addr }
+impl TxConfig {
+    pub fn new() -> Self {
+        TxConfig::default()
+    }
+
+    /// Set the percentage of the estimated gas added to the gas of transactions.
+    pub fn set_gas_headroom_percent(mut self, percent: u64) -> Self {
+        self.gas_headroom_percent = percent;
+        self
+    }
+
+    /// Set the number of blocks, including the one containing a transaction,
+    /// which must be built before the transaction is regarded as confirmed.
+    pub fn set_confirmations(mut self, confirmations: u64) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    pub fn set_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
     }
 
-    pub fn contract_abi(&self) -> Result<ContractABI> {
-        let f = File::open(&self.abi_path)?;
-        let reader = BufReader::new(f);
+    /// Set how long to wait for a transaction to be mined before replacing it.
+    pub fn set_replacement_timeout(mut self, timeout: Duration) -> Self {
+        self.replacement_timeout = timeout;
+        self
+    }
+
+    /// Set how long to wait for the confirmations of a mined transaction before giving up.
+    pub fn set_confirmation_timeout(mut self, timeout: Duration) -> Self {
+        self.confirmation_timeout = timeout;
+        self
+    }
+
+    /// Set the percentage which the gas price of a replacement is raised by.
+    /// Nodes usually reject replacements raised by less than 10%.
+    pub fn set_gas_price_bump_percent(mut self, percent: u64) -> Self {
+        self.gas_price_bump_percent = percent;
+        self
+    }
+
+    pub fn set_max_replacements(mut self, max_replacements: u32) -> Self {
+        self.max_replacements = max_replacements;
+        self
+    }
+
+    /// Set the governance of the contracts deployed with this config.
+    pub fn set_governance(mut self, governance: GovernanceConfig) -> Self {
+        self.governance = governance;
+        self
+    }
+
+    pub fn gas_headroom_percent(&self) -> u64 {
+        self.gas_headroom_percent
+    }
 
-        ContractABI::load(reader)
-            .map_err(|e| anyhow!("Failed to load contract abi.: {:?}", e))
-            .map_err(Into::into)
+    pub fn confirmations(&self) -> u64 {
+        self.confirmations
     }
 
-    pub fn address(&self) -> Result<Address> {
-        Address::from_str(self.addr)
-            .map_err(|e| anyhow!("{:?}", e))
-            .map_err(Into::into)
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+
+    pub fn replacement_timeout(&self) -> Duration {
+        self.replacement_timeout
+    }
+
+    pub fn confirmation_timeout(&self) -> Duration {
+        self.confirmation_timeout
+    }
+
+    pub fn gas_price_bump_percent(&self) -> u64 {
+        self.gas_price_bump_percent
+    }
+
+    pub fn max_replacements(&self) -> u32 {
+        self.max_replacements
+    }
+
+    pub fn governance(&self) -> &GovernanceConfig {
+        &self.governance
     }
 }
 
+impl Default for TxConfig {
+    fn default() -> Self {
+        TxConfig {
+            gas_headroom_percent: 20,
+            confirmations: 1,
+            poll_interval: Duration::from_millis(500),
+            replacement_timeout: Duration::from_secs(60),
+            confirmation_timeout: Duration::from_secs(600),
+            gas_price_bump_percent: 12,
+            max_replacements: 3,
+            governance: GovernanceConfig::default(),
+        }
+    }
+}
+
+/// The parameters of the mrenclave governance of a contract, which are fixed on the deployment.
 #[derive(Debug, Clone)]
-pub struct CommandInfo<'a, C: CallNameConverter> {
-    encrypted_command: EciesCiphertext,
-    call_name: &'a str,
-    phantom: PhantomData<C>,
+pub struct GovernanceConfig {
+    mrenclave_timelock: u64,
+    approval_percent: u32,
 }
 
-impl<'a, C: CallNameConverter> CommandInfo<'a, C> {
-    pub fn new(encrypted_command: EciesCiphertext, call_name: &'a str) -> Self {
-        CommandInfo {
-            encrypted_command,
-            call_name,
-            phantom: PhantomData::<C>,
-        }
+impl GovernanceConfig {
+    pub fn new() -> Self {
+        GovernanceConfig::default()
+    }
+
+    /// Set the number of blocks from a proposal of mrenclave until it can be executed.
+    pub fn set_mrenclave_timelock(mut self, timelock: u64) -> Self {
+        self.mrenclave_timelock = timelock;
+        self
+    }
+
+    /// Set the percentage of the members which a proposal must be approved by more than.
+    /// The contract accepts it from 50 to 99.
+    pub fn set_approval_percent(mut self, percent: u32) -> Self {
+        self.approval_percent = percent;
+        self
     }
 
-    pub fn call_name_to_id(&self) -> u32 {
-        C::as_id(self.call_name)
+    pub fn mrenclave_timelock(&self) -> u64 {
+        self.mrenclave_timelock
     }
 
-    pub fn crate_input<AP: AccessPolicy>(self, access_policy: AP) -> input::Command<AP> {
-        let call_id = self.call_name_to_id();
-        input::Command::new(access_policy, self.encrypted_command, call_id)
+    pub fn approval_percent(&self) -> u32 {
+        self.approval_percent
     }
 }
 
-/// A type of contract
-pub enum ContractKind {
-    Web3Contract(Web3Contract),
+impl Default for GovernanceConfig {
+    /// About a day of blocks, and more than half of the members
+    fn default() -> Self {
+        GovernanceConfig {
+            mrenclave_timelock: 5760,
+            approval_percent: 50,
+        }
+    }
 }
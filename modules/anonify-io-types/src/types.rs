@@ -1,7 +1,7 @@
-use crate::localstd::vec::Vec;
+use crate::localstd::{string::String, vec::Vec};
 use codec::{self, Decode, Encode, Input};
 use frame_common::{
-    crypto::{Ciphertext, ExportHandshake, ExportPathSecret},
+    crypto::{Ciphertext, ExportHandshake, ExportPathSecret, StateCommitment},
     state_types::{StateType, UpdatedState},
     traits::AccessPolicy,
     EcallInput, EcallOutput,
@@ -54,6 +54,43 @@ pub mod input {
 
     impl EcallInput for CallRegisterReport {}
 
+    #[derive(Encode, Decode, Debug, Clone, Default)]
+    pub struct GetAttestationStatus;
+
+    impl EcallInput for GetAttestationStatus {}
+
+    #[derive(Encode, Decode, Debug, Clone, Default)]
+    pub struct CallRotateIdentityKey;
+
+    impl EcallInput for CallRotateIdentityKey {}
+
+    #[derive(Encode, Decode, Debug, Clone, Default)]
+    pub struct CallCommitIdentityKey;
+
+    impl EcallInput for CallCommitIdentityKey {}
+
+    #[derive(Encode, Decode, Debug, Clone)]
+    pub struct CallBindIdentityKey {
+        contract_addr: String,
+    }
+
+    impl EcallInput for CallBindIdentityKey {}
+
+    impl CallBindIdentityKey {
+        pub fn new(contract_addr: String) -> Self {
+            CallBindIdentityKey { contract_addr }
+        }
+
+        pub fn contract_addr(&self) -> &str {
+            &self.contract_addr
+        }
+    }
+
+    #[derive(Encode, Decode, Debug, Clone, Default)]
+    pub struct CallStateCommitment;
+
+    impl EcallInput for CallStateCommitment {}
+
     #[derive(Encode, Decode, Debug, Clone)]
     pub struct InsertCiphertext {
         ciphertext: Ciphertext,
@@ -131,10 +168,22 @@ pub mod input {
     }
 
     #[derive(Encode, Decode, Debug, Clone, Default)]
-    pub struct CallServerStarter;
+    pub struct CallServerStarter {
+        listen_address: Vec<u8>,
+    }
 
     impl EcallInput for CallServerStarter {}
 
+    impl CallServerStarter {
+        pub fn new(listen_address: Vec<u8>) -> Self {
+            CallServerStarter { listen_address }
+        }
+
+        pub fn listen_address(&self) -> &[u8] {
+            &self.listen_address[..]
+        }
+    }
+
     #[derive(Encode, Decode, Debug, Clone, Default)]
     pub struct CallServerStopper;
 
@@ -223,6 +272,8 @@ pub mod output {
     #[derive(Encode, Decode, Debug, Clone)]
     pub struct ReturnUpdatedState {
         pub updated_state: Option<UpdatedState<StateType>>,
+        /// The root of the whole state after the ciphertext is processed
+        pub state_root: Option<[u8; 32]>,
     }
 
     impl EcallOutput for ReturnUpdatedState {}
@@ -231,35 +282,145 @@ pub mod output {
         fn default() -> Self {
             ReturnUpdatedState {
                 updated_state: None,
+                state_root: None,
             }
         }
     }
 
     impl ReturnUpdatedState {
         pub fn new(updated_state: Option<UpdatedState<StateType>>) -> Self {
-            ReturnUpdatedState { updated_state }
+            ReturnUpdatedState {
+                updated_state,
+                state_root: None,
+            }
         }
 
         pub fn update(&mut self, updated_state: UpdatedState<StateType>) {
             self.updated_state = Some(updated_state)
         }
+
+        pub fn set_state_root(&mut self, state_root: [u8; 32]) {
+            self.state_root = Some(state_root)
+        }
+    }
+
+    /// The commitment to the state signed by the enclave's identity key
+    #[derive(Debug, Clone)]
+    pub struct ReturnStateCommitment {
+        enclave_sig: secp256k1::Signature,
+        commitment: StateCommitment,
+        recovery_id: secp256k1::RecoveryId,
+    }
+
+    impl EcallOutput for ReturnStateCommitment {}
+
+    impl Encode for ReturnStateCommitment {
+        fn encode(&self) -> Vec<u8> {
+            let mut acc = vec![];
+            acc.extend_from_slice(&self.encode_enclave_sig());
+            acc.push(self.encode_recovery_id());
+            acc.extend_from_slice(&self.encode_commitment());
+
+            acc
+        }
+    }
+
+    impl Decode for ReturnStateCommitment {
+        fn decode<I: Input>(value: &mut I) -> Result<Self, codec::Error> {
+            let mut enclave_sig_buf = [0u8; 64];
+            value.read(&mut enclave_sig_buf)?;
+
+            let recovery_id_buf = value.read_byte()?;
+            let commitment = StateCommitment::decode(value)?;
+
+            let enclave_sig = secp256k1::Signature::parse(&enclave_sig_buf);
+            let recovery_id = secp256k1::RecoveryId::parse(recovery_id_buf)
+                .map_err(|_| codec::Error::from("Failed to parse recovery_id"))?;
+
+            Ok(ReturnStateCommitment {
+                enclave_sig,
+                commitment,
+                recovery_id,
+            })
+        }
     }
 
+    impl ReturnStateCommitment {
+        pub fn new(
+            commitment: StateCommitment,
+            enclave_sig: secp256k1::Signature,
+            recovery_id: secp256k1::RecoveryId,
+        ) -> Self {
+            ReturnStateCommitment {
+                enclave_sig,
+                commitment,
+                recovery_id,
+            }
+        }
+
+        pub fn commitment(&self) -> &StateCommitment {
+            &self.commitment
+        }
+
+        pub fn encode_commitment(&self) -> Vec<u8> {
+            self.commitment.encode()
+        }
+
+        pub fn encode_recovery_id(&self) -> u8 {
+            self.recovery_id.serialize()
+        }
+
+        pub fn encode_enclave_sig(&self) -> [u8; 64] {
+            self.enclave_sig.serialize()
+        }
+    }
+
+    /// The encrypting key with the IAS report attesting it,
+    /// so that clients can verify the key is generated in the enclave.
     #[derive(Encode, Decode, Debug, Clone)]
     pub struct ReturnEncryptingKey {
         encrypting_key: DhPubKey,
+        report: Vec<u8>,
+        report_sig: Vec<u8>,
+        report_cert: Vec<u8>,
     }
 
     impl EcallOutput for ReturnEncryptingKey {}
 
     impl ReturnEncryptingKey {
-        pub fn new(encrypting_key: DhPubKey) -> Self {
-            ReturnEncryptingKey { encrypting_key }
+        pub fn new(
+            encrypting_key: DhPubKey,
+            report: Vec<u8>,
+            report_sig: Vec<u8>,
+            report_cert: Vec<u8>,
+        ) -> Self {
+            ReturnEncryptingKey {
+                encrypting_key,
+                report,
+                report_sig,
+                report_cert,
+            }
         }
 
         pub fn encrypting_key(self) -> DhPubKey {
             self.encrypting_key
         }
+
+        pub fn encrypting_key_as_ref(&self) -> &DhPubKey {
+            &self.encrypting_key
+        }
+
+        pub fn report(&self) -> &[u8] {
+            &self.report[..]
+        }
+
+        pub fn report_sig(&self) -> &[u8] {
+            &self.report_sig[..]
+        }
+
+        pub fn report_cert(&self) -> &[u8] {
+            &self.report_cert[..]
+        }
     }
 
     #[derive(Encode, Decode, Debug, Clone, Default)]
@@ -390,6 +551,154 @@ pub mod output {
         }
     }
 
+    /// The status of the cached evidence attesting the identity key.
+    /// `is_attested` is false if it has not been attested yet.
+    #[derive(Encode, Decode, Debug, Clone, Default)]
+    pub struct ReturnAttestationStatus {
+        is_attested: bool,
+        age_secs: u64,
+        max_age_secs: u64,
+        quote_status: String,
+        is_refreshing: bool,
+    }
+
+    impl EcallOutput for ReturnAttestationStatus {}
+
+    impl ReturnAttestationStatus {
+        pub fn new(
+            age_secs: u64,
+            max_age_secs: u64,
+            quote_status: String,
+            is_refreshing: bool,
+        ) -> Self {
+            ReturnAttestationStatus {
+                is_attested: true,
+                age_secs,
+                max_age_secs,
+                quote_status,
+                is_refreshing,
+            }
+        }
+
+        pub fn is_attested(&self) -> bool {
+            self.is_attested
+        }
+
+        pub fn age_secs(&self) -> u64 {
+            self.age_secs
+        }
+
+        pub fn max_age_secs(&self) -> u64 {
+            self.max_age_secs
+        }
+
+        pub fn quote_status(&self) -> &str {
+            &self.quote_status
+        }
+
+        pub fn is_refreshing(&self) -> bool {
+            self.is_refreshing
+        }
+    }
+
+    /// A report attesting the new identity key,
+    /// and the revocation of the old key signed by itself in favor of the new one.
+    #[derive(Debug, Clone)]
+    pub struct ReturnRotateIdentityKey {
+        register_report: ReturnRegisterReport,
+        old_verifying_key: [u8; 20],
+        old_encrypting_key: Vec<u8>,
+        new_verifying_key: [u8; 20],
+        revocation_sig: secp256k1::Signature,
+        recovery_id: secp256k1::RecoveryId,
+    }
+
+    impl EcallOutput for ReturnRotateIdentityKey {}
+
+    impl Encode for ReturnRotateIdentityKey {
+        fn encode(&self) -> Vec<u8> {
+            let mut acc = vec![];
+            acc.extend_from_slice(&self.register_report.encode());
+            acc.extend_from_slice(&self.old_verifying_key.encode());
+            acc.extend_from_slice(&self.old_encrypting_key.encode());
+            acc.extend_from_slice(&self.new_verifying_key.encode());
+            acc.extend_from_slice(&self.encode_revocation_sig());
+            acc.push(self.encode_recovery_id());
+
+            acc
+        }
+    }
+
+    impl Decode for ReturnRotateIdentityKey {
+        fn decode<I: Input>(value: &mut I) -> Result<Self, codec::Error> {
+            let register_report = ReturnRegisterReport::decode(value)?;
+            let old_verifying_key = <[u8; 20]>::decode(value)?;
+            let old_encrypting_key = Vec::<u8>::decode(value)?;
+            let new_verifying_key = <[u8; 20]>::decode(value)?;
+
+            let mut revocation_sig_buf = [0u8; 64];
+            value.read(&mut revocation_sig_buf)?;
+            let revocation_sig = secp256k1::Signature::parse(&revocation_sig_buf);
+
+            let recovery_id_buf = value.read_byte()?;
+            let recovery_id = secp256k1::RecoveryId::parse(recovery_id_buf)
+                .map_err(|_| codec::Error::from("Failed to parse recovery_id"))?;
+
+            Ok(ReturnRotateIdentityKey {
+                register_report,
+                old_verifying_key,
+                old_encrypting_key,
+                new_verifying_key,
+                revocation_sig,
+                recovery_id,
+            })
+        }
+    }
+
+    impl ReturnRotateIdentityKey {
+        pub fn new(
+            register_report: ReturnRegisterReport,
+            old_verifying_key: [u8; 20],
+            old_encrypting_key: Vec<u8>,
+            new_verifying_key: [u8; 20],
+            revocation_sig: secp256k1::Signature,
+            recovery_id: secp256k1::RecoveryId,
+        ) -> Self {
+            ReturnRotateIdentityKey {
+                register_report,
+                old_verifying_key,
+                old_encrypting_key,
+                new_verifying_key,
+                revocation_sig,
+                recovery_id,
+            }
+        }
+
+        pub fn register_report(&self) -> &ReturnRegisterReport {
+            &self.register_report
+        }
+
+        pub fn old_verifying_key(&self) -> &[u8; 20] {
+            &self.old_verifying_key
+        }
+
+        pub fn old_encrypting_key(&self) -> &[u8] {
+            &self.old_encrypting_key[..]
+        }
+
+        pub fn new_verifying_key(&self) -> &[u8; 20] {
+            &self.new_verifying_key
+        }
+
+        pub fn encode_recovery_id(&self) -> u8 {
+            self.recovery_id.serialize()
+        }
+
+        pub fn encode_revocation_sig(&self) -> [u8; 64] {
+            self.revocation_sig.serialize()
+        }
+    }
+
     #[derive(Debug, Clone)]
     pub struct ReturnHandshake {
         export_path_secret: ExportPathSecret,
@@ -0,0 +1,290 @@
+use crate::error::{HostError, Result};
+use anyhow::anyhow;
+use futures::channel::oneshot;
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::Debug,
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+};
+
+/// The number of fetches of a blob which has not been stored before its log is skipped as a dead letter,
+/// so that a blob which is never stored does not stall the sync.
+pub const MAX_BLOB_TRIALS: u32 = 10;
+
+/// The SHA-256 hash of a blob, which addresses it in a blob store
+pub type BlobHash = [u8; 32];
+
+pub type SharedBlobStore = Arc<dyn BlobStore>;
+
+/// A content-addressed store of the ciphertexts kept off the ledger.
+/// Only their hashes are stored on the ledger, and the watchers fetch the ciphertexts from the store.
+/// The methods may block, so they are called via `put_blob` and `fetch_blob` in async code.
+pub trait BlobStore: Debug + Send + Sync {
+    /// Store the blob and return its hash.
+    fn put(&self, blob: &[u8]) -> Result<BlobHash>;
+
+    /// The blob with the hash, or `None` if it has not been stored.
+    fn get(&self, hash: &BlobHash) -> Result<Option<Vec<u8>>>;
+}
+
+pub fn blob_hash(blob: &[u8]) -> BlobHash {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&Sha256::digest(blob));
+    hash
+}
+
+/// Store the blob on a blocking thread, and return its hash.
+pub async fn put_blob(store: &SharedBlobStore, blob: Vec<u8>) -> Result<BlobHash> {
+    let store = store.clone();
+    spawn_blocking(move || store.put(&blob)).await
+}
+
+/// Fetch the blob from the store on a blocking thread, and verify that it matches the hash.
+pub async fn fetch_blob(store: &SharedBlobStore, hash: &BlobHash) -> Result<Vec<u8>> {
+    let (store, hash) = (store.clone(), *hash);
+    let blob = spawn_blocking(move || store.get(&hash))
+        .await?
+        .ok_or_else(|| HostError::BlobNotFound(hex::encode(hash)))?;
+    if blob_hash(&blob) != hash {
+        return Err(HostError::BlobMismatch(hex::encode(hash)));
+    }
+
+    Ok(blob)
+}
+
+/// Run the blocking call on its own thread, so that it does not block the executor.
+/// The drivers are not tied to a runtime, so no blocking pool of a runtime is used.
+async fn spawn_blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let (tx, rx) = oneshot::channel();
+    thread::spawn(move || {
+        // The receiver has gone only if the fetch has been canceled.
+        let _ = tx.send(f());
+    });
+
+    rx.await
+        .map_err(|_| anyhow!("The blob store panicked while handling the blob"))?
+}
+
+/// A blob store in a local directory, where each blob is a file named by its hash in hex.
+#[derive(Debug, Clone)]
+pub struct FsBlobStore {
+    dir: PathBuf,
+}
+
+impl FsBlobStore {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(FsBlobStore {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    fn path(&self, hash: &BlobHash) -> PathBuf {
+        self.dir.join(hex::encode(hash))
+    }
+}
+
+impl BlobStore for FsBlobStore {
+    fn put(&self, blob: &[u8]) -> Result<BlobHash> {
+        let hash = blob_hash(blob);
+        let path = self.path(&hash);
+        if !path.exists() {
+            // Renamed after written so that a partially written blob is never read.
+            let tmp_path = path.with_extension("tmp");
+            fs::write(&tmp_path, blob)?;
+            fs::rename(&tmp_path, &path)?;
+        }
+
+        Ok(hash)
+    }
+
+    fn get(&self, hash: &BlobHash) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.path(hash)) {
+            Ok(blob) => Ok(Some(blob)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// A blob store served over HTTP, where a blob is put to and got from `{base_url}/{hash in hex}`.
+#[derive(Debug, Clone)]
+pub struct HttpBlobStore {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl HttpBlobStore {
+    pub fn new(base_url: &str) -> Self {
+        HttpBlobStore {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    fn url(&self, hash: &BlobHash) -> String {
+        format!("{}/{}", self.base_url, hex::encode(hash))
+    }
+}
+
+impl BlobStore for HttpBlobStore {
+    fn put(&self, blob: &[u8]) -> Result<BlobHash> {
+        let hash = blob_hash(blob);
+        self.client
+            .put(&self.url(&hash))
+            .body(blob.to_vec())
+            .send()
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| anyhow!("Failed to put the blob: {}", e))?;
+
+        Ok(hash)
+    }
+
+    fn get(&self, hash: &BlobHash) -> Result<Option<Vec<u8>>> {
+        let res = self
+            .client
+            .get(&self.url(hash))
+            .send()
+            .map_err(|e| anyhow!("Failed to get the blob: {}", e))?;
+        if res.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        let mut blob = vec![];
+        res.error_for_status()
+            .and_then(|mut res| res.copy_to(&mut blob))
+            .map_err(|e| anyhow!("Failed to get the blob: {}", e))?;
+        Ok(Some(blob))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn test_fs_blob_store() {
+        let dir = std::env::temp_dir().join(format!("anonify-blobs-{}", std::process::id()));
+        let fs_store = FsBlobStore::new(&dir).unwrap();
+        let store: SharedBlobStore = Arc::new(fs_store.clone());
+
+        let hash = block_on(put_blob(&store, b"ciphertext".to_vec())).unwrap();
+        assert_eq!(hash, blob_hash(b"ciphertext"));
+        assert_eq!(block_on(fetch_blob(&store, &hash)).unwrap(), b"ciphertext");
+        assert!(matches!(
+            block_on(fetch_blob(&store, &blob_hash(b"missing"))),
+            Err(HostError::BlobNotFound(_))
+        ));
+
+        // A blob altered in the store is rejected.
+        fs::write(fs_store.path(&hash), b"altered").unwrap();
+        assert!(matches!(
+            block_on(fetch_blob(&store, &hash)),
+            Err(HostError::BlobMismatch(_))
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_http_blob_store() {
+        let base_url = serve_blobs();
+        let http_store = HttpBlobStore::new(&base_url);
+        let store: SharedBlobStore = Arc::new(http_store.clone());
+
+        let hash = block_on(put_blob(&store, b"ciphertext".to_vec())).unwrap();
+        assert_eq!(hash, blob_hash(b"ciphertext"));
+        assert_eq!(block_on(fetch_blob(&store, &hash)).unwrap(), b"ciphertext");
+        assert!(matches!(
+            block_on(fetch_blob(&store, &blob_hash(b"missing"))),
+            Err(HostError::BlobNotFound(_))
+        ));
+
+        // A blob altered on the server is rejected.
+        let altered = blob_hash(b"other");
+        http_store
+            .client
+            .put(&http_store.url(&altered))
+            .body(b"altered".to_vec())
+            .send()
+            .unwrap();
+        assert!(matches!(
+            block_on(fetch_blob(&store, &altered)),
+            Err(HostError::BlobMismatch(_))
+        ));
+
+        // The errors other than a missing blob are not regarded as it.
+        let store: SharedBlobStore = Arc::new(HttpBlobStore::new("http://127.0.0.1:1"));
+        assert!(matches!(
+            block_on(fetch_blob(&store, &hash)),
+            Err(HostError::Error(_))
+        ));
+    }
+
+    /// Serve the blobs in memory over HTTP, one request per connection, and return the base URL.
+    fn serve_blobs() -> String {
+        use std::{
+            collections::HashMap,
+            io::{BufRead, BufReader, Read, Write},
+            net::TcpListener,
+        };
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}/blobs/", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let mut blobs: HashMap<String, Vec<u8>> = HashMap::new();
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    let header = header.to_ascii_lowercase();
+                    if let Some(len) = header.strip_prefix("content-length:") {
+                        content_length = len.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut request = request_line.split_whitespace();
+                let (method, path) = (request.next().unwrap(), request.next().unwrap());
+                let (status, body) = match method {
+                    "PUT" => {
+                        blobs.insert(path.to_string(), body);
+                        ("200 OK", vec![])
+                    }
+                    _ => match blobs.get(path) {
+                        Some(blob) => ("200 OK", blob.clone()),
+                        None => ("404 Not Found", vec![]),
+                    },
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+
+        base_url
+    }
+}
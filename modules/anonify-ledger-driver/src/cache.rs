@@ -0,0 +1,1007 @@
+use crate::{
+    blob::BlobHash,
+    checkpoint::Checkpoint,
+    commitment::{CommitmentCheck, RemoteCommitment, StateCommitments},
+    dead_letter::{DeadLetter, DeadLetters, SkipReason},
+    error::{HostError, Result},
+    ledger::{AddressOf, BlockPositionOf, LedgerDriver, LogIdOf},
+    payload::PayloadType,
+};
+use frame_common::crypto::StateCommitment;
+use log::{info, warn};
+use parking_lot::RwLock;
+use std::collections::{
+    hash_map::{Entry, HashMap},
+    BTreeMap, HashSet,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+type BlockNum = u64;
+type RosterIdx = u32;
+type Epoch = u32;
+type Generation = u32;
+
+// TODO: Prevent malicious TEE fraudulently setting the number of trials to break consistency.
+pub const MAX_TRIALS_NUM: u32 = 50;
+/// The number of blocks fetched again before a reorganized block.
+/// The processed logs of as many blocks are kept so that they are not inserted twice.
+pub const REORG_REWIND_DEPTH: u64 = 64;
+
+/// Cache data from events for arrival guarantee and order guarantee.
+/// Unordered events are cached.
+#[derive(Debug)]
+pub struct EventCache<L: LedgerDriver> {
+    inner: Arc<RwLock<InnerEventCache<L>>>,
+}
+
+impl<L: LedgerDriver> EventCache<L> {
+    /// Persist the cursor and the processed logs of the contract which the cache serves in the directory
+    /// by `save_checkpoint`, and restore the ones persisted before by `restore_checkpoint`,
+    /// so that each log is inserted only once across restarts.
+    /// The restored logs are never inserted again, so the checkpoint is restored only into the enclave
+    /// restored to the state it was taken after, whose root is given (`None` for a new enclave).
+    pub fn with_checkpoint_dir<P: AsRef<Path>>(
+        dir: P,
+        state_root: Option<[u8; 32]>,
+    ) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        let mut inner = InnerEventCache::default();
+        inner.checkpoint_dir = Some((dir.as_ref().to_path_buf(), state_root));
+
+        Ok(EventCache {
+            inner: Arc::new(RwLock::new(inner)),
+        })
+    }
+
+    pub fn inner(&self) -> &Arc<RwLock<InnerEventCache<L>>> {
+        &self.inner
+    }
+}
+
+impl<L: LedgerDriver> Clone for EventCache<L> {
+    fn clone(&self) -> Self {
+        EventCache {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<L: LedgerDriver> Default for EventCache<L> {
+    fn default() -> Self {
+        EventCache {
+            inner: Arc::new(RwLock::new(InnerEventCache::default())),
+        }
+    }
+}
+
+/// Do not implement `Clone` trait due to cache duplication.
+#[derive(Debug)]
+pub struct InnerEventCache<L: LedgerDriver> {
+    block_num_counter: HashMap<AddressOf<L>, BlockNum>,
+    // The last block whose events have been fetched, to detect reorgs
+    scanned_blocks: HashMap<AddressOf<L>, BlockPositionOf<L>>,
+    // The logs inserted into the enclave by block number, so that each log is inserted only once
+    processed_logs: HashMap<AddressOf<L>, BTreeMap<BlockNum, HashSet<LogIdOf<L>>>>,
+    treekem_counter: HashMap<RosterIdx, (Epoch, Generation)>,
+    trials_counter: HashMap<RosterIdx, u32>,
+    payloads_pool: HashMap<RosterIdx, Vec<PayloadType>>,
+    // The number of fetches of each blob which has not been stored yet
+    blob_trials: HashMap<BlobHash, u32>,
+    dead_letters: DeadLetters,
+    state_commitments: StateCommitments,
+    // The directory where the checkpoint is persisted, and the root of the enclave state to restore it into
+    checkpoint_dir: Option<(PathBuf, Option<[u8; 32]>)>,
+    // The contract whose checkpoint is persisted, which is the only one the cache serves
+    checkpoint_contract: Option<AddressOf<L>>,
+}
+
+impl<L: LedgerDriver> Default for InnerEventCache<L> {
+    fn default() -> Self {
+        InnerEventCache {
+            block_num_counter: HashMap::new(),
+            scanned_blocks: HashMap::new(),
+            processed_logs: HashMap::new(),
+            treekem_counter: HashMap::new(),
+            trials_counter: HashMap::new(),
+            payloads_pool: HashMap::new(),
+            blob_trials: HashMap::new(),
+            dead_letters: DeadLetters::default(),
+            state_commitments: StateCommitments::default(),
+            checkpoint_dir: None,
+            checkpoint_contract: None,
+        }
+    }
+}
+
+impl<L: LedgerDriver> InnerEventCache<L> {
+    /// The processed logs of the blocks older than `REORG_REWIND_DEPTH` are dropped,
+    /// because they are never fetched again.
+    pub fn insert_next_block_num(
+        &mut self,
+        contract_addr: AddressOf<L>,
+        block_num: BlockNum,
+    ) -> Option<BlockNum> {
+        info!("Insert: Cached block number: {}", block_num);
+        if let Some(logs) = self.processed_logs.get_mut(&contract_addr) {
+            *logs = logs.split_off(&block_num.saturating_sub(REORG_REWIND_DEPTH));
+        }
+        self.block_num_counter.insert(contract_addr, block_num)
+    }
+
+    /// Rewind the next block to fetch by `REORG_REWIND_DEPTH` before the reorganized block,
+    /// and forget the scanned block, so that the blocks of the new canonical chain are fetched again.
+    /// The logs still in the canonical chain are skipped as processed,
+    /// while the ones inserted from the orphaned blocks cannot be reverted in the enclave.
+    /// Returns the next block to fetch.
+    pub fn rewind_on_reorg(
+        &mut self,
+        contract_addr: AddressOf<L>,
+        reorg_block_num: BlockNum,
+    ) -> BlockNum {
+        let next_block_num = self
+            .get_latest_block_num(contract_addr)
+            .unwrap_or_default()
+            .min(reorg_block_num.saturating_sub(REORG_REWIND_DEPTH));
+        warn!(
+            "The fetched block {} has been reorganized, so the blocks are fetched again from {}",
+            reorg_block_num, next_block_num
+        );
+        self.block_num_counter.insert(contract_addr, next_block_num);
+        self.scanned_blocks.remove(&contract_addr);
+
+        next_block_num
+    }
+
+    pub fn get_latest_block_num(&self, contract_addr: AddressOf<L>) -> Option<BlockNum> {
+        let block_num = self.block_num_counter.get(&contract_addr).map(|e| *e);
+        info!("Get: Cached block number: {:?}", block_num);
+        block_num
+    }
+
+    pub fn insert_scanned_block(
+        &mut self,
+        contract_addr: AddressOf<L>,
+        position: BlockPositionOf<L>,
+    ) -> Option<BlockPositionOf<L>> {
+        self.scanned_blocks.insert(contract_addr, position)
+    }
+
+    pub fn get_scanned_block(&self, contract_addr: AddressOf<L>) -> Option<BlockPositionOf<L>> {
+        self.scanned_blocks.get(&contract_addr).copied()
+    }
+
+    /// Record the log as inserted into the enclave.
+    /// It returns false if the log has already been inserted, which must be skipped.
+    pub fn insert_processed_log(
+        &mut self,
+        contract_addr: AddressOf<L>,
+        block_num: BlockNum,
+        log_id: LogIdOf<L>,
+    ) -> bool {
+        self.processed_logs
+            .entry(contract_addr)
+            .or_default()
+            .entry(block_num)
+            .or_default()
+            .insert(log_id)
+    }
+
+    pub fn is_processed_log(
+        &self,
+        contract_addr: AddressOf<L>,
+        block_num: BlockNum,
+        log_id: &LogIdOf<L>,
+    ) -> bool {
+        self.processed_logs
+            .get(&contract_addr)
+            .and_then(|logs| logs.get(&block_num))
+            .map_or(false, |ids| ids.contains(log_id))
+    }
+
+    /// Persist the next block to fetch, the scanned block and the processed logs of the contract at once,
+    /// so that the restored cursor never points before a log which is not restored as processed.
+    /// The dead letters and the payloads waiting for the order guarantee are persisted with them,
+    /// since the cache serves the contract alone, and so is the latest state root to restore them only into the same state.
+    /// It does nothing unless the cache has been created by `with_checkpoint_dir` and bound to the contract
+    /// by `restore_checkpoint`.
+    pub fn save_checkpoint(&self, contract_addr: AddressOf<L>) -> Result<()> {
+        let dir = match (&self.checkpoint_dir, self.checkpoint_contract) {
+            (Some((dir, _)), Some(addr)) if addr == contract_addr => dir,
+            _ => return Ok(()),
+        };
+        let processed_logs = self
+            .processed_logs
+            .get(&contract_addr)
+            .map(|logs| {
+                logs.iter()
+                    .map(|(block_num, ids)| {
+                        (*block_num, ids.iter().map(L::encode_log_id).collect())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let checkpoint = Checkpoint {
+            next_block_num: self.block_num_counter.get(&contract_addr).copied(),
+            scanned_block: self
+                .scanned_blocks
+                .get(&contract_addr)
+                .map(L::encode_block_position),
+            processed_logs,
+            dead_letters: self.dead_letters.list(),
+            treekem_counter: self.treekem_counter.iter().map(|(k, v)| (*k, *v)).collect(),
+            trials_counter: self.trials_counter.iter().map(|(k, v)| (*k, *v)).collect(),
+            payloads_pool: self
+                .payloads_pool
+                .iter()
+                .map(|(k, v)| (*k, v.clone()))
+                .collect(),
+            state_root: self.state_commitments.latest_local_position(),
+        };
+
+        checkpoint.save(dir, &L::format_address(&contract_addr))
+    }
+
+    /// Persist the checkpoint of the contract which the cache serves,
+    /// e.g. after the dead letters have been replayed.
+    pub fn save_checkpoints(&self) -> Result<()> {
+        match self.checkpoint_contract {
+            Some(contract_addr) => self.save_checkpoint(contract_addr),
+            None => Ok(()),
+        }
+    }
+
+    /// Bind the cache to the contract, restoring its checkpoint if it has been persisted before.
+    /// The checkpoints of the other contracts in the directory are neither restored nor overwritten,
+    /// and it fails if the cache has been bound to another contract.
+    /// It does nothing unless the cache has been created by `with_checkpoint_dir`.
+    pub fn restore_checkpoint(&mut self, contract_addr: AddressOf<L>) -> Result<()> {
+        let (dir, state_root) = match &self.checkpoint_dir {
+            Some((dir, state_root)) => (dir.clone(), *state_root),
+            None => return Ok(()),
+        };
+        match self.checkpoint_contract {
+            Some(addr) if addr == contract_addr => return Ok(()),
+            Some(addr) => return Err(HostError::ContractNotServed(L::format_address(&addr))),
+            None => {}
+        }
+        let name = L::format_address(&contract_addr);
+        if let Some(checkpoint) = Checkpoint::load(&dir, &name)? {
+            let checkpoint_root = checkpoint.state_root.map(|(_, _, state_root)| state_root);
+            if state_root.is_none() || checkpoint_root != state_root {
+                return Err(HostError::CheckpointMismatch(name));
+            }
+            self.load_checkpoint(contract_addr, checkpoint)?;
+        }
+        self.checkpoint_contract = Some(contract_addr);
+
+        Ok(())
+    }
+
+    fn load_checkpoint(
+        &mut self,
+        contract_addr: AddressOf<L>,
+        checkpoint: Checkpoint,
+    ) -> Result<()> {
+        if let Some(block_num) = checkpoint.next_block_num {
+            self.block_num_counter.insert(contract_addr, block_num);
+        }
+        if let Some(position) = checkpoint.scanned_block {
+            self.scanned_blocks
+                .insert(contract_addr, L::decode_block_position(&position)?);
+        }
+        let mut logs = BTreeMap::new();
+        for (block_num, ids) in checkpoint.processed_logs {
+            let ids = ids
+                .iter()
+                .map(|id| L::decode_log_id(id))
+                .collect::<Result<HashSet<_>>>()?;
+            logs.insert(block_num, ids);
+        }
+        self.processed_logs.insert(contract_addr, logs);
+        for letter in checkpoint.dead_letters {
+            self.dead_letters.restore(letter);
+        }
+        self.treekem_counter.extend(checkpoint.treekem_counter);
+        self.trials_counter.extend(checkpoint.trials_counter);
+        self.payloads_pool.extend(checkpoint.payloads_pool);
+        if let Some((position, ciphertext_id, state_root)) = checkpoint.state_root {
+            self.state_commitments
+                .insert_local_root(position, ciphertext_id, state_root);
+        }
+
+        Ok(())
+    }
+
+    /// Count a fetch of the blob which has not been stored yet.
+    /// It returns false once the blob has been fetched `max_trials` times,
+    /// when its log must be skipped instead of being fetched again.
+    pub fn retry_missing_blob(&mut self, hash: BlobHash, max_trials: u32) -> bool {
+        let trials = self.blob_trials.entry(hash).or_default();
+        *trials += 1;
+        if *trials < max_trials {
+            return true;
+        }
+
+        self.blob_trials.remove(&hash);
+        false
+    }
+
+    /// Forget the trials of the blob which has been fetched.
+    pub fn remove_missing_blob(&mut self, hash: &BlobHash) {
+        self.blob_trials.remove(hash);
+    }
+
+    /// Keep the skipped payload as a dead letter, and return its id.
+    pub fn insert_dead_letter(&mut self, letter: DeadLetter) -> u64 {
+        warn!("Skipped a payload: {}: {:?}", letter.reason(), letter);
+        self.dead_letters.insert(letter)
+    }
+
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.dead_letters.list()
+    }
+
+    /// Take the dead letter out to replay it.
+    pub fn take_dead_letter(&mut self, id: u64) -> Option<DeadLetter> {
+        self.dead_letters.take(id)
+    }
+
+    /// Put back the dead letter which failed to be replayed.
+    pub fn restore_dead_letter(&mut self, letter: DeadLetter) {
+        self.dead_letters.restore(letter)
+    }
+
+    /// Record the root of the enclave state after processing the ciphertext at the chain position,
+    /// which the commitments of the other members are checked against.
+    pub fn insert_state_root(
+        &mut self,
+        position: (u64, u64),
+        ciphertext_id: (u32, u32, u32),
+        state_root: [u8; 32],
+    ) {
+        self.state_commitments
+            .insert_local_root(position, ciphertext_id, state_root)
+    }
+
+    /// The last processed ciphertext and the state root after it
+    pub fn latest_state_root(&self) -> Option<((u32, u32, u32), [u8; 32])> {
+        self.state_commitments.latest_local_root()
+    }
+
+    /// Check the commitment published by a member against the local state, logging an error on mismatch.
+    pub fn check_state_commitment(
+        &mut self,
+        commitment: StateCommitment,
+        block_num: Option<BlockNum>,
+    ) -> CommitmentCheck {
+        self.state_commitments.check(commitment, block_num)
+    }
+
+    /// The latest commitment published by each member
+    pub fn state_commitments(&self) -> Vec<RemoteCommitment> {
+        self.state_commitments.latest()
+    }
+
+    /// The commitments which did not match the local state
+    pub fn commitment_mismatches(&self) -> Vec<RemoteCommitment> {
+        self.state_commitments.mismatches()
+    }
+
+    /// The ciphertext of the last commitment published by this node
+    pub fn published_commitment(&self) -> Option<(u32, u32, u32)> {
+        self.state_commitments.published()
+    }
+
+    pub fn set_published_commitment(&mut self, ciphertext_id: (u32, u32, u32)) {
+        self.state_commitments.set_published(ciphertext_id)
+    }
+
+    /// In regard to order gurantee:
+    /// There are two cases where the generation of received messages is not continuous.
+    /// 1. In regard to the previous message, the sender's keychain ratcheted,
+    ///    but some error occurred in the subsequent processing and it did not reach the receiver,
+    ///    and its generation was skipped.
+    /// 2. The order of the received messages is changed.
+    ///    Due to the order guarantee between network connections and the transaction order,
+    ///    the order is changed and recorded in the message queue (blockchain).
+    /// In the case of 1, consistency is guaranteed between TEE node clusters,
+    /// so there is no problem with processing as usual.
+    /// In case of 2, it is necessary to process the message of
+    /// the next generation received later without skipping first.
+    /// Therefore, cache the message received earlier for a specific number of attempts shared by the cluster,
+    /// and wait for the message of the next generation to come.
+    /// If the next message does not come after waiting for the number of attempts, that message is skipped.
+    /// (This skip process is performed by guaranteeing consistency in all TEEs in the cluster)
+    pub fn ensure_order_guarantee(
+        &mut self,
+        mut payloads: Vec<PayloadType>,
+        immutable_payloads: Vec<PayloadType>,
+        max_trials_num: u32,
+    ) -> Vec<PayloadType> {
+        for (index, curr_payload) in immutable_payloads.iter().enumerate() {
+            self.increment_trials_counter(&curr_payload);
+
+            // The case current payload is the correct order
+            if self.is_next_msg(&curr_payload) {
+                self.update_treekem_counter(&curr_payload);
+            } else {
+                warn!("Received a discontinuous message: {:?}", curr_payload);
+
+                let payloads_from_pool = self.find_next_payloads(&curr_payload, max_trials_num);
+
+                // if reset the number of trials, skip the event
+                if self
+                    .trials_counter
+                    .get(&curr_payload.roster_idx())
+                    .unwrap_or_else(|| &0)
+                    == &0
+                {
+                    self.update_treekem_counter(&curr_payload);
+                    payloads = Self::insert_chunks_at_index(payloads, &payloads_from_pool, index);
+                    return payloads;
+                }
+
+                // The case compensatable payloads are not found, so cache those.
+                if payloads_from_pool.is_empty() {
+                    warn!(
+                        "Not found the next payload even in the cache, so cache the current payloads: {:?}",
+                        curr_payload
+                    );
+                    self.insert_payloads_pool(curr_payload.clone(), &mut payloads);
+                // The case compensatable payloads are found, so insert those to payloads buffer.
+                } else {
+                    if !payloads_from_pool[payloads_from_pool.len() - 1].is_next(&curr_payload) {
+                        self.insert_payloads_pool(curr_payload.clone(), &mut payloads);
+                    }
+                    self.update_treekem_counter(&curr_payload);
+                    payloads = Self::insert_chunks_at_index(payloads, &payloads_from_pool, index);
+                }
+            }
+        }
+
+        payloads
+    }
+
+    /// Increment the number of trials for each roster index
+    pub fn increment_multi_trials_counter(&mut self, payloads: &[PayloadType]) {
+        let mut roster_idx_list: Vec<RosterIdx> = payloads.iter().map(|p| p.roster_idx()).collect();
+        roster_idx_list.dedup();
+        for roster_idx in roster_idx_list {
+            let traial_num = self.trials_counter.entry(roster_idx).or_default();
+            *traial_num += 1;
+        }
+    }
+
+    fn increment_trials_counter(&mut self, payload: &PayloadType) {
+        let traial_num = self.trials_counter.entry(payload.roster_idx()).or_default();
+        *traial_num += 1;
+    }
+
+    fn insert_chunks_at_index(
+        mut payloads: Vec<PayloadType>,
+        payloads_from_pool: &[PayloadType],
+        index: usize,
+    ) -> Vec<PayloadType> {
+        payloads.reserve(payloads_from_pool.len());
+        let mut v = payloads.split_off(index);
+        payloads.extend_from_slice(&payloads_from_pool);
+        payloads.append(&mut v);
+
+        payloads
+    }
+
+    /// Whether the payload the next according to the treekem counter
+    fn is_next_msg(&self, msg: &PayloadType) -> bool {
+        let roster_idx = msg.roster_idx();
+        let (current_epoch, current_gen) = *self
+            .treekem_counter
+            .get(&roster_idx)
+            .unwrap_or_else(|| &(0, 0));
+
+        if msg.epoch() == current_epoch {
+            msg.generation() == current_gen + 1
+                || msg.generation() == 0
+                || msg.generation() == u32::MAX // handshake
+        } else {
+            // TODO: Handling reorder over epoch
+            true
+        }
+    }
+
+    /// Set the continuous payloads from cache pool into payloads buffer
+    fn set_continuous_payloads(
+        mut acc: Vec<PayloadType>,
+        payloads_from_pool: &mut Vec<PayloadType>,
+    ) -> Vec<PayloadType> {
+        if !payloads_from_pool.is_empty() {
+            let mut tmp = &payloads_from_pool[0];
+            acc.push(tmp.clone());
+            for curr_payload in &payloads_from_pool[1..] {
+                if tmp.is_next(&curr_payload) {
+                    acc.push(curr_payload.clone());
+                    tmp = curr_payload;
+                } else {
+                    break;
+                }
+            }
+            for _ in 0..acc.len() {
+                payloads_from_pool.remove(0);
+            }
+        }
+
+        acc
+    }
+
+    /// Finds next payloads.
+    /// If the maximum number of trials is over,
+    /// skip the event and get a continuous vector from the smallest payload in the `payloads_pool`,
+    /// otherwise get continuous payloads from the `payloads_pool`.
+    fn find_next_payloads(
+        &mut self,
+        prior_payload: &PayloadType,
+        max_trials_num: u32,
+    ) -> Vec<PayloadType> {
+        let roster_idx = prior_payload.roster_idx();
+        let mut acc: Vec<PayloadType> = vec![];
+
+        let is_next_msg = match self.payloads_pool.get(&roster_idx) {
+            Some(payloads) if !payloads.is_empty() => self.is_next_msg(&payloads[0]),
+            _ => false,
+        };
+
+        match self.payloads_pool.entry(roster_idx) {
+            Entry::Occupied(mut entry) => {
+                if self.trials_counter.get(&roster_idx).unwrap_or_else(|| &0) > &max_trials_num {
+                    acc = Self::set_continuous_payloads(acc, entry.get_mut());
+                    self.reset_trials_counter(roster_idx);
+                    // The maximum number of trials is over, so the next event of the prior payload is skipped.
+                    let resumed = acc.first().unwrap_or(prior_payload);
+                    let mut letter = DeadLetter::new(SkipReason::MaxTrials {
+                        epoch: resumed.epoch(),
+                        generation: resumed.generation(),
+                    })
+                    .set_roster_idx(roster_idx);
+                    if let Some(block_num) = resumed.block_num() {
+                        letter = letter.set_block_num(block_num);
+                    }
+                    self.insert_dead_letter(letter);
+                } else {
+                    if is_next_msg {
+                        acc = Self::set_continuous_payloads(acc, entry.get_mut());
+                        self.reset_trials_counter(roster_idx);
+                    } else {
+                        return acc;
+                    }
+                }
+            }
+            Entry::Vacant(_) => return acc,
+        }
+
+        acc
+    }
+
+    // reset the number of trials
+    fn reset_trials_counter(&mut self, roster_idx: RosterIdx) {
+        let trials_num = self.trials_counter.entry(roster_idx).or_default();
+        *trials_num = 0;
+    }
+
+    fn insert_payloads_pool(&mut self, payload: PayloadType, payloads: &mut Vec<PayloadType>) {
+        let payloads_from_pool = self.payloads_pool.entry(payload.roster_idx()).or_default();
+        payloads_from_pool.push(payload.clone());
+        payloads_from_pool.sort();
+
+        // Duplicated items are already removed.
+        payloads.remove(
+            payloads
+                .iter()
+                .position(|p| p == &payload)
+                .expect("payloads must have payload"),
+        );
+    }
+
+    fn update_treekem_counter(&mut self, msg: &PayloadType) {
+        self.treekem_counter
+            .insert(msg.roster_idx(), (msg.epoch(), msg.generation()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use codec::{Decode, Encode};
+
+    #[derive(Debug)]
+    struct TestLedger;
+
+    impl LedgerDriver for TestLedger {
+        type Address = u64;
+        type TxId = u64;
+        type BlockPosition = u64;
+        type LogId = (u64, u32);
+        type TxConfig = ();
+        type Signer = ();
+
+        fn parse_address(addr: &str) -> Result<u64> {
+            addr.parse().map_err(|e| anyhow::anyhow!("{:?}", e).into())
+        }
+
+        fn format_address(addr: &u64) -> String {
+            addr.to_string()
+        }
+
+        fn signer_address(_signer: &()) -> u64 {
+            0
+        }
+
+        fn encode_block_position(position: &u64) -> Vec<u8> {
+            position.encode()
+        }
+
+        fn decode_block_position(bytes: &[u8]) -> Result<u64> {
+            Decode::decode(&mut &bytes[..]).map_err(Into::into)
+        }
+
+        fn encode_log_id(log_id: &(u64, u32)) -> Vec<u8> {
+            log_id.encode()
+        }
+
+        fn decode_log_id(bytes: &[u8]) -> Result<(u64, u32)> {
+            Decode::decode(&mut &bytes[..]).map_err(Into::into)
+        }
+    }
+
+    #[test]
+    fn test_processed_logs() {
+        let mut cache = InnerEventCache::<TestLedger>::default();
+        assert!(cache.insert_processed_log(1, 10, (10, 0)));
+        assert!(cache.insert_processed_log(1, 11, (11, 0)));
+        assert!(!cache.insert_processed_log(1, 10, (10, 0)));
+        // The same log of another contract
+        assert!(cache.insert_processed_log(2, 10, (10, 0)));
+
+        cache.insert_next_block_num(1, 11 + REORG_REWIND_DEPTH);
+        assert!(!cache.is_processed_log(1, 10, &(10, 0)));
+        assert!(cache.is_processed_log(1, 11, &(11, 0)));
+        assert!(cache.is_processed_log(2, 10, &(10, 0)));
+    }
+
+    #[test]
+    fn test_rewind_on_reorg() {
+        let mut cache = InnerEventCache::<TestLedger>::default();
+        assert!(cache.insert_processed_log(1, 100, (100, 0)));
+        assert!(cache.insert_processed_log(1, 150, (150, 0)));
+        cache.insert_next_block_num(1, 200);
+        cache.insert_scanned_block(1, 199);
+
+        assert_eq!(cache.rewind_on_reorg(1, 199), 199 - REORG_REWIND_DEPTH);
+        assert_eq!(
+            cache.get_latest_block_num(1),
+            Some(199 - REORG_REWIND_DEPTH)
+        );
+        assert_eq!(cache.get_scanned_block(1), None);
+        // The logs in the rewound blocks are not inserted again unless they are reorganized.
+        assert!(!cache.insert_processed_log(1, 150, (150, 0)));
+        assert!(cache.insert_processed_log(1, 151, (151, 0)));
+        assert!(!cache.is_processed_log(1, 100, &(100, 0)));
+
+        // A reorg of a later block never advances it,
+        assert_eq!(
+            cache.rewind_on_reorg(1, 199 + REORG_REWIND_DEPTH),
+            199 - REORG_REWIND_DEPTH
+        );
+        // nor rewound before the genesis
+        assert_eq!(cache.rewind_on_reorg(1, 10), 0);
+    }
+
+    /// A cache persisting the checkpoint of the contract in the directory,
+    /// restored into the enclave in the state
+    fn restore(
+        dir: &Path,
+        contract_addr: u64,
+        state_root: Option<[u8; 32]>,
+    ) -> Result<EventCache<TestLedger>> {
+        let cache = EventCache::with_checkpoint_dir(dir, state_root)?;
+        cache.inner().write().restore_checkpoint(contract_addr)?;
+        Ok(cache)
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("anonify-cache-{}", std::process::id()));
+        let persisted = restore(&dir, 1, None).unwrap();
+        let mut cache = persisted.inner().write();
+        assert!(cache.insert_processed_log(1, 10, (10, 0)));
+        assert!(cache.insert_processed_log(1, 10, (10, 1)));
+        cache.insert_next_block_num(1, 11);
+        cache.insert_scanned_block(1, 10);
+        let letter_id = cache.insert_dead_letter(
+            DeadLetter::new(SkipReason::Decode("invalid".to_string())).set_raw_log(vec![1]),
+        );
+        // The second generation is pooled until the first one comes.
+        let payloads = vec![
+            PayloadType::new(0, 0, 1, Default::default()).set_position(10, 0),
+            PayloadType::new(0, 0, 3, Default::default()).set_position(10, 1),
+        ];
+        let inserted = cache.ensure_order_guarantee(payloads.clone(), payloads, MAX_TRIALS_NUM);
+        assert_eq!(inserted.len(), 1);
+        cache.insert_state_root((10, 0), (0, 0, 1), [1; 32]);
+        cache.save_checkpoint(1).unwrap();
+        // Not persisted until the cursor is saved
+        assert!(cache.insert_processed_log(1, 11, (11, 0)));
+        assert!(cache.insert_processed_log(2, 10, (10, 0)));
+
+        // Not restored into an enclave in another state, e.g. a new one
+        for state_root in &[None, Some([2; 32])] {
+            assert!(matches!(
+                restore(&dir, 1, *state_root),
+                Err(HostError::CheckpointMismatch(_))
+            ));
+        }
+        let reloaded = restore(&dir, 1, Some([1; 32])).unwrap();
+        let mut reloaded = reloaded.inner().write();
+        assert_eq!(reloaded.get_latest_block_num(1), Some(11));
+        assert_eq!(reloaded.get_scanned_block(1), Some(10));
+        assert_eq!(reloaded.get_latest_block_num(2), None);
+        assert_eq!(reloaded.latest_state_root(), Some(((0, 0, 1), [1; 32])));
+        // The logs fetched again after the restart are skipped.
+        assert!(!reloaded.insert_processed_log(1, 10, (10, 0)));
+        assert!(!reloaded.insert_processed_log(1, 10, (10, 1)));
+        assert!(reloaded.insert_processed_log(1, 11, (11, 0)));
+        assert!(reloaded.insert_processed_log(2, 10, (10, 0)));
+        let dead_letters = reloaded.dead_letters();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].id(), letter_id);
+        assert_eq!(dead_letters[0].raw_log(), Some(&[1][..]));
+        // The pooled payload is inserted once the missing one comes after the restart.
+        let payloads = vec![
+            PayloadType::new(0, 0, 2, Default::default()).set_position(11, 0),
+            PayloadType::new(0, 0, 4, Default::default()).set_position(11, 1),
+        ];
+        let inserted = reloaded.ensure_order_guarantee(payloads.clone(), payloads, MAX_TRIALS_NUM);
+        let generations: Vec<u32> = inserted.iter().map(|p| p.generation()).collect();
+        assert_eq!(generations, vec![2, 3, 4]);
+
+        // The replayed dead letter is not restored after the next restart.
+        reloaded.take_dead_letter(letter_id).unwrap();
+        reloaded.save_checkpoints().unwrap();
+        let restarted = restore(&dir, 1, Some([1; 32])).unwrap();
+        assert!(restarted.inner().read().dead_letters().is_empty());
+        assert_eq!(restarted.inner().read().get_latest_block_num(1), Some(11));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoints_of_contracts() {
+        let dir =
+            std::env::temp_dir().join(format!("anonify-cache-contracts-{}", std::process::id()));
+        // Each contract is served by its own cache with the checkpoints in the same directory.
+        let caches: Vec<EventCache<TestLedger>> = (1..=2u64)
+            .map(|contract_addr| {
+                let cache = restore(&dir, contract_addr, None).unwrap();
+                {
+                    let mut w = cache.inner().write();
+                    w.insert_next_block_num(contract_addr, 10 * contract_addr);
+                    w.insert_dead_letter(
+                        DeadLetter::new(SkipReason::Decode("invalid".to_string()))
+                            .set_raw_log(vec![contract_addr as u8]),
+                    );
+                    w.insert_state_root((contract_addr, 0), (0, 0, 1), [contract_addr as u8; 32]);
+                    w.save_checkpoint(contract_addr).unwrap();
+                    // The checkpoint of the other contract is never written by this cache.
+                    w.save_checkpoint(3 - contract_addr).unwrap();
+                }
+                cache
+            })
+            .collect();
+        assert!(matches!(
+            caches[0].inner().write().restore_checkpoint(2),
+            Err(HostError::ContractNotServed(_))
+        ));
+
+        // Only the cursor and the dead letters of its own contract are restored into each cache.
+        for contract_addr in 1..=2u64 {
+            let cache = restore(&dir, contract_addr, Some([contract_addr as u8; 32])).unwrap();
+            let cache = cache.inner().read();
+            assert_eq!(
+                cache.get_latest_block_num(contract_addr),
+                Some(10 * contract_addr)
+            );
+            assert_eq!(cache.get_latest_block_num(3 - contract_addr), None);
+            let dead_letters = cache.dead_letters();
+            assert_eq!(dead_letters.len(), 1);
+            assert_eq!(dead_letters[0].raw_log(), Some(&[contract_addr as u8][..]));
+        }
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_retry_missing_blob() {
+        let mut cache = InnerEventCache::<TestLedger>::default();
+        assert!(cache.retry_missing_blob([1; 32], 3));
+        assert!(cache.retry_missing_blob([1; 32], 3));
+        assert!(cache.retry_missing_blob([2; 32], 3));
+        // Skipped on the last trial, and counted again from the start if it is fetched again.
+        assert!(!cache.retry_missing_blob([1; 32], 3));
+        assert!(cache.retry_missing_blob([1; 32], 3));
+
+        cache.remove_missing_blob(&[2; 32]);
+        assert!(cache.retry_missing_blob([2; 32], 3));
+        assert!(cache.retry_missing_blob([2; 32], 3));
+        assert!(!cache.retry_missing_blob([2; 32], 3));
+    }
+
+    #[test]
+    fn test_correct_order_diff_roster_idx() {
+        let dummy_payloads1 = vec![
+            PayloadType::new(0, 0, 1, Default::default()),
+            PayloadType::new(0, 0, 2, Default::default()),
+            PayloadType::new(1, 0, 1, Default::default()),
+            PayloadType::new(1, 0, 2, Default::default()),
+            PayloadType::new(1, 0, 3, Default::default()),
+        ];
+
+        let dummy_payloads2 = vec![
+            PayloadType::new(1, 0, 4, Default::default()),
+            PayloadType::new(2, 0, 1, Default::default()),
+        ];
+
+        let mut cache = InnerEventCache::<TestLedger>::default();
+        let res1 =
+            cache.ensure_order_guarantee(dummy_payloads1.clone(), dummy_payloads1, MAX_TRIALS_NUM);
+        assert_eq!(
+            res1,
+            vec![
+                PayloadType::new(0, 0, 1, Default::default()),
+                PayloadType::new(0, 0, 2, Default::default()),
+                PayloadType::new(1, 0, 1, Default::default()),
+                PayloadType::new(1, 0, 2, Default::default()),
+                PayloadType::new(1, 0, 3, Default::default()),
+            ]
+        );
+
+        let res2 =
+            cache.ensure_order_guarantee(dummy_payloads2.clone(), dummy_payloads2, MAX_TRIALS_NUM);
+        assert_eq!(
+            res2,
+            vec![
+                PayloadType::new(1, 0, 4, Default::default()),
+                PayloadType::new(2, 0, 1, Default::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fix_reorder_using_cache() {
+        let dummy_payloads1 = vec![
+            PayloadType::new(0, 0, 1, Default::default()),
+            PayloadType::new(0, 0, 2, Default::default()),
+            PayloadType::new(0, 0, 4, Default::default()),
+            PayloadType::new(0, 0, 5, Default::default()),
+        ];
+
+        let dummy_payloads2 = vec![
+            PayloadType::new(0, 0, 3, Default::default()),
+            PayloadType::new(0, 0, 6, Default::default()),
+            PayloadType::new(0, 0, 7, Default::default()),
+        ];
+
+        let mut cache = InnerEventCache::<TestLedger>::default();
+        let res1 =
+            cache.ensure_order_guarantee(dummy_payloads1.clone(), dummy_payloads1, MAX_TRIALS_NUM);
+        assert_eq!(
+            res1,
+            vec![
+                PayloadType::new(0, 0, 1, Default::default()),
+                PayloadType::new(0, 0, 2, Default::default()),
+            ]
+        );
+
+        let res2 =
+            cache.ensure_order_guarantee(dummy_payloads2.clone(), dummy_payloads2, MAX_TRIALS_NUM);
+        assert_eq!(
+            res2,
+            vec![
+                PayloadType::new(0, 0, 3, Default::default()),
+                PayloadType::new(0, 0, 4, Default::default()),
+                PayloadType::new(0, 0, 5, Default::default()),
+                PayloadType::new(0, 0, 6, Default::default()),
+                PayloadType::new(0, 0, 7, Default::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fix_order_handshake() {
+        let dummy_payloads1 = vec![
+            PayloadType::new(0, 0, 1, Default::default()),
+            PayloadType::new(0, 0, 2, Default::default()),
+            PayloadType::new(0, 1, 1, Default::default()),
+            PayloadType::new(0, 0, u32::MAX, Default::default()),
+        ];
+
+        let dummy_payloads2 = vec![
+            PayloadType::new(0, 1, 2, Default::default()),
+            PayloadType::new(0, 1, 3, Default::default()),
+            PayloadType::new(0, 1, 4, Default::default()),
+        ];
+
+        let mut cache = InnerEventCache::<TestLedger>::default();
+        let res1 =
+            cache.ensure_order_guarantee(dummy_payloads1.clone(), dummy_payloads1, MAX_TRIALS_NUM);
+        assert_eq!(
+            res1,
+            vec![
+                PayloadType::new(0, 0, 1, Default::default()),
+                PayloadType::new(0, 0, 2, Default::default()),
+                PayloadType::new(0, 1, 1, Default::default()),
+                PayloadType::new(0, 0, u32::MAX, Default::default()),
+            ]
+        );
+
+        let res2 =
+            cache.ensure_order_guarantee(dummy_payloads2.clone(), dummy_payloads2, MAX_TRIALS_NUM);
+        assert_eq!(
+            res2,
+            vec![
+                PayloadType::new(0, 1, 2, Default::default()),
+                PayloadType::new(0, 1, 3, Default::default()),
+                PayloadType::new(0, 1, 4, Default::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_over_max_trials_num() {
+        let dummy_payloads1 = vec![
+            PayloadType::new(0, 0, 1, Default::default()),
+            PayloadType::new(0, 0, 2, Default::default()),
+            PayloadType::new(0, 0, 4, Default::default()),
+            PayloadType::new(0, 0, 5, Default::default()),
+        ];
+
+        let dummy_payloads2 = vec![
+            PayloadType::new(0, 0, 3, Default::default()),
+            PayloadType::new(0, 0, 6, Default::default()),
+            PayloadType::new(0, 0, 7, Default::default()),
+        ];
+
+        let mut cache = InnerEventCache::<TestLedger>::default();
+        let res1 = cache.ensure_order_guarantee(dummy_payloads1.clone(), dummy_payloads1, 0);
+        assert_eq!(
+            res1,
+            vec![
+                PayloadType::new(0, 0, 1, Default::default()),
+                PayloadType::new(0, 0, 2, Default::default()),
+                PayloadType::new(0, 0, 5, Default::default()),
+                PayloadType::new(0, 0, 4, Default::default()),
+            ]
+        );
+        // The generation 3 is skipped, resuming from the generation 4.
+        let dead_letters = cache.dead_letters();
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(
+            dead_letters[0].reason(),
+            &SkipReason::MaxTrials {
+                epoch: 0,
+                generation: 4
+            }
+        );
+
+        let res2 = cache.ensure_order_guarantee(dummy_payloads2.clone(), dummy_payloads2, 0);
+        assert_eq!(
+            res2,
+            vec![
+                PayloadType::new(0, 0, 3, Default::default()),
+                PayloadType::new(0, 0, 6, Default::default()),
+                PayloadType::new(0, 0, 7, Default::default()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use crate::{dead_letter::DeadLetter, error::Result, payload::PayloadType};
+use codec::{Decode, Encode};
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+/// The extension of the checkpoint files, each of which is named by the address of its contract
+const CHECKPOINT_EXTENSION: &str = "checkpoint";
+
+/// The cursor of the fetched blocks of a contract and the logs inserted into the enclave,
+/// which are persisted in one file so that they are never out of sync after a restart.
+/// The block positions and the log ids are encoded by the ledger driver.
+/// The dead letters are persisted with them, so that they are able to be replayed after a restart,
+/// and so are the payloads waiting for the order guarantee, which are never fetched again.
+/// The root of the enclave state after the last inserted ciphertext identifies the state
+/// which the checkpoint is valid for.
+#[derive(Debug, Clone, Default, Encode, Decode)]
+pub(crate) struct Checkpoint {
+    pub(crate) next_block_num: Option<u64>,
+    pub(crate) scanned_block: Option<Vec<u8>>,
+    pub(crate) processed_logs: Vec<(u64, Vec<Vec<u8>>)>,
+    pub(crate) dead_letters: Vec<DeadLetter>,
+    pub(crate) treekem_counter: Vec<(u32, (u32, u32))>,
+    pub(crate) trials_counter: Vec<(u32, u32)>,
+    pub(crate) payloads_pool: Vec<(u32, Vec<PayloadType>)>,
+    pub(crate) state_root: Option<((u64, u64), (u32, u32, u32), [u8; 32])>,
+}
+
+impl Checkpoint {
+    /// Load the checkpoint of the contract in the directory, or `None` if it has not been saved.
+    pub(crate) fn load(dir: &Path, name: &str) -> Result<Option<Checkpoint>> {
+        let path = dir.join(name).with_extension(CHECKPOINT_EXTENSION);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let bytes = fs::read(&path)?;
+
+        Ok(Some(Checkpoint::decode(&mut &bytes[..])?))
+    }
+
+    /// Write the checkpoint to a temporary file and rename it,
+    /// so that a crash never leaves a partially written checkpoint.
+    pub(crate) fn save(&self, dir: &Path, name: &str) -> Result<()> {
+        let path = dir.join(name).with_extension(CHECKPOINT_EXTENSION);
+        let tmp_path = path.with_extension("tmp");
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(&self.encode())?;
+        file.sync_all()?;
+        fs::rename(tmp_path, path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dead_letter::SkipReason;
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("anonify-checkpoints-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(Checkpoint::load(&dir, "0x01").unwrap().is_none());
+
+        let checkpoint = Checkpoint {
+            next_block_num: Some(11),
+            scanned_block: Some(vec![10]),
+            processed_logs: vec![(10, vec![vec![1], vec![2]])],
+            dead_letters: vec![DeadLetter::new(SkipReason::Decode("invalid".to_string()))],
+            treekem_counter: vec![(0, (0, 1))],
+            trials_counter: vec![(0, 1)],
+            payloads_pool: vec![(0, vec![PayloadType::new(0, 0, 3, Default::default())])],
+            state_root: Some(((10, 0), (0, 0, 1), [1; 32])),
+        };
+        checkpoint.save(&dir, "0x01").unwrap();
+        Checkpoint::default().save(&dir, "0x02").unwrap();
+        // Overwritten as a whole
+        checkpoint.save(&dir, "0x01").unwrap();
+
+        let loaded = Checkpoint::load(&dir, "0x01").unwrap().unwrap();
+        assert_eq!(loaded.encode(), checkpoint.encode());
+        let loaded = Checkpoint::load(&dir, "0x02").unwrap().unwrap();
+        assert_eq!(loaded.encode(), Checkpoint::default().encode());
+        assert!(!dir.join("0x01.tmp").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+use frame_common::crypto::StateCommitment;
+use log::{error, info, warn};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// The maximum number of local state roots kept per cache, and of the mismatched commitments.
+/// The oldest one is dropped first.
+pub const MAX_STATE_ROOTS: usize = 1024;
+
+/// The roster index, epoch and generation of a ciphertext
+type CiphertextId = (u32, u32, u32);
+/// The block number and the index of the log in the block
+type ChainPosition = (u64, u64);
+
+/// The result of checking a commitment published by a member against the local state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommitmentCheck {
+    /// The same state has been computed locally after the ciphertext
+    Matched,
+    /// A different state has been computed locally after the same ciphertext
+    Mismatched { local_root: [u8; 32] },
+    /// The ciphertext has not been processed locally, or it was processed too long ago to compare
+    Unknown,
+}
+
+/// A state commitment published by a member, and the result of checking it
+#[derive(Debug, Clone)]
+pub struct RemoteCommitment {
+    commitment: StateCommitment,
+    block_num: Option<u64>,
+    // The chain position of the committed ciphertext if it has been processed locally
+    position: Option<ChainPosition>,
+    check: CommitmentCheck,
+}
+
+impl RemoteCommitment {
+    pub fn commitment(&self) -> &StateCommitment {
+        &self.commitment
+    }
+
+    pub fn block_num(&self) -> Option<u64> {
+        self.block_num
+    }
+
+    pub fn position(&self) -> Option<ChainPosition> {
+        self.position
+    }
+
+    pub fn check(&self) -> CommitmentCheck {
+        self.check
+    }
+}
+
+/// The local state roots after the processed ciphertexts keyed by their chain positions,
+/// and the commitments of the members checked against them.
+/// Every node inserts the payloads in the chain order however the logs are fetched in batches,
+/// so the states after the same chain position are comparable among the members.
+#[derive(Debug, Default)]
+pub(crate) struct StateCommitments {
+    local_roots: HashMap<ChainPosition, (CiphertextId, [u8; 32])>,
+    // The chain positions of the processed ciphertexts, which the commitments name
+    positions: HashMap<CiphertextId, ChainPosition>,
+    // The order of processing, to drop the oldest root
+    local_order: VecDeque<ChainPosition>,
+    // The latest commitment of each member by the roster index
+    latest: BTreeMap<u32, RemoteCommitment>,
+    mismatches: VecDeque<RemoteCommitment>,
+    // The ciphertext of the last commitment published by this node
+    published: Option<CiphertextId>,
+}
+
+impl StateCommitments {
+    pub(crate) fn insert_local_root(
+        &mut self,
+        position: ChainPosition,
+        ciphertext_id: CiphertextId,
+        state_root: [u8; 32],
+    ) {
+        if self
+            .local_roots
+            .insert(position, (ciphertext_id, state_root))
+            .is_none()
+        {
+            self.local_order.push_back(position);
+        }
+        self.positions.insert(ciphertext_id, position);
+        while self.local_order.len() > MAX_STATE_ROOTS {
+            if let Some(oldest) = self.local_order.pop_front() {
+                if let Some((ciphertext_id, _)) = self.local_roots.remove(&oldest) {
+                    if self.positions.get(&ciphertext_id) == Some(&oldest) {
+                        self.positions.remove(&ciphertext_id);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The last processed ciphertext and the state root after it
+    pub(crate) fn latest_local_root(&self) -> Option<(CiphertextId, [u8; 32])> {
+        self.latest_local_position()
+            .map(|(_, ciphertext_id, state_root)| (ciphertext_id, state_root))
+    }
+
+    /// The latest local root with the chain position of its ciphertext
+    pub(crate) fn latest_local_position(&self) -> Option<(ChainPosition, CiphertextId, [u8; 32])> {
+        let position = self.local_order.back()?;
+        self.local_roots
+            .get(position)
+            .map(|(ciphertext_id, state_root)| (*position, *ciphertext_id, *state_root))
+    }
+
+    /// Check the commitment against the local state after the chain position of the same ciphertext.
+    pub(crate) fn check(
+        &mut self,
+        commitment: StateCommitment,
+        block_num: Option<u64>,
+    ) -> CommitmentCheck {
+        let position = self.positions.get(&commitment.ciphertext_id()).copied();
+        let local_root = position
+            .and_then(|position| self.local_roots.get(&position))
+            .map(|(_, state_root)| state_root);
+        let check = match local_root {
+            Some(local_root) if *local_root == commitment.state_root() => {
+                info!("The state commitment matched: {:?}", commitment);
+                CommitmentCheck::Matched
+            }
+            Some(local_root) => {
+                error!(
+                    "The state committed by roster_idx {} does not match the local state after the ciphertext {:?} at {:?}: remote: {}, local: {}",
+                    commitment.roster_idx(),
+                    commitment.ciphertext_id(),
+                    position,
+                    hex::encode(commitment.state_root()),
+                    hex::encode(local_root),
+                );
+                CommitmentCheck::Mismatched {
+                    local_root: *local_root,
+                }
+            }
+            None => {
+                warn!(
+                    "The ciphertext of the state commitment has not been processed locally: {:?}",
+                    commitment
+                );
+                CommitmentCheck::Unknown
+            }
+        };
+
+        let remote = RemoteCommitment {
+            commitment,
+            block_num,
+            position,
+            check,
+        };
+        if let CommitmentCheck::Mismatched { .. } = check {
+            self.mismatches.push_back(remote.clone());
+            if self.mismatches.len() > MAX_STATE_ROOTS {
+                self.mismatches.pop_front();
+            }
+        }
+        self.latest.insert(remote.commitment.roster_idx(), remote);
+
+        check
+    }
+
+    pub(crate) fn latest(&self) -> Vec<RemoteCommitment> {
+        self.latest.values().cloned().collect()
+    }
+
+    pub(crate) fn mismatches(&self) -> Vec<RemoteCommitment> {
+        self.mismatches.iter().cloned().collect()
+    }
+
+    pub(crate) fn published(&self) -> Option<CiphertextId> {
+        self.published
+    }
+
+    pub(crate) fn set_published(&mut self, ciphertext_id: CiphertextId) {
+        self.published = Some(ciphertext_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_commitments() {
+        let mut commitments = StateCommitments::default();
+        assert!(commitments.latest_local_root().is_none());
+        commitments.insert_local_root((10, 0), (0, 0, 1), [1u8; 32]);
+        commitments.insert_local_root((10, 1), (1, 0, 1), [2u8; 32]);
+        assert_eq!(
+            commitments.latest_local_root(),
+            Some(((1, 0, 1), [2u8; 32]))
+        );
+
+        let matched = StateCommitment::new(1, (0, 0, 1), [1u8; 32]);
+        assert_eq!(
+            commitments.check(matched, Some(10)),
+            CommitmentCheck::Matched
+        );
+        let mismatched = StateCommitment::new(2, (1, 0, 1), [3u8; 32]);
+        assert_eq!(
+            commitments.check(mismatched, Some(11)),
+            CommitmentCheck::Mismatched {
+                local_root: [2u8; 32]
+            }
+        );
+        let unknown = StateCommitment::new(1, (0, 0, 2), [4u8; 32]);
+        assert_eq!(commitments.check(unknown, None), CommitmentCheck::Unknown);
+
+        let latest = commitments.latest();
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0].check(), CommitmentCheck::Unknown);
+        assert_eq!(commitments.mismatches().len(), 1);
+        assert_eq!(commitments.mismatches()[0].block_num(), Some(11));
+        assert_eq!(commitments.mismatches()[0].position(), Some((10, 1)));
+
+        for generation in 0..MAX_STATE_ROOTS as u32 {
+            commitments.insert_local_root((12, generation.into()), (2, 0, generation), [5u8; 32]);
+        }
+        let oldest = StateCommitment::new(1, (0, 0, 1), [1u8; 32]);
+        assert_eq!(commitments.check(oldest, None), CommitmentCheck::Unknown);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::payload::PayloadType;
+use codec::{Decode, Encode};
+use log::warn;
+use std::{collections::BTreeMap, fmt};
+
+/// The maximum number of dead letters kept per cache. The oldest one is dropped first.
+pub const MAX_DEAD_LETTERS: usize = 1024;
+
+/// The reason why a payload was skipped instead of being inserted into the enclave
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum SkipReason {
+    /// The log could not be decoded into a payload
+    Decode(String),
+    /// The enclave failed to insert the payload, e.g. it could not decrypt the ciphertext
+    Enclave(String),
+    /// The state updated by the payload could not be decoded on the host
+    StateTransition(String),
+    /// The messages before this generation did not arrive within the maximum number of trials
+    MaxTrials { epoch: u32, generation: u32 },
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SkipReason::Decode(e) => write!(f, "Failed to decode the log: {}", e),
+            SkipReason::Enclave(e) => write!(f, "Failed to insert into the enclave: {}", e),
+            SkipReason::StateTransition(e) => {
+                write!(f, "Failed to decode the updated state: {}", e)
+            }
+            SkipReason::MaxTrials { epoch, generation } => write!(
+                f,
+                "The messages before epoch {} generation {} did not arrive",
+                epoch, generation
+            ),
+        }
+    }
+}
+
+/// A payload which was skipped, kept so that operators are able to inspect it
+/// and to replay it once the cause is fixed.
+#[derive(Debug, Clone, Encode, Decode)]
+pub struct DeadLetter {
+    id: u64,
+    reason: SkipReason,
+    block_num: Option<u64>,
+    roster_idx: Option<u32>,
+    // Only the payloads which have not changed the enclave state are replayable.
+    payload: Option<PayloadType>,
+    // The data of the log which could not be decoded into a payload
+    raw_log: Option<Vec<u8>>,
+}
+
+impl DeadLetter {
+    pub fn new(reason: SkipReason) -> Self {
+        DeadLetter {
+            id: 0,
+            reason,
+            block_num: None,
+            roster_idx: None,
+            payload: None,
+            raw_log: None,
+        }
+    }
+
+    pub fn set_reason(mut self, reason: SkipReason) -> Self {
+        self.reason = reason;
+        self
+    }
+
+    pub fn set_block_num(mut self, block_num: u64) -> Self {
+        self.block_num = Some(block_num);
+        self
+    }
+
+    pub fn set_roster_idx(mut self, roster_idx: u32) -> Self {
+        self.roster_idx = Some(roster_idx);
+        self
+    }
+
+    /// Set the payload to be replayed. The block number and the roster index are taken from it.
+    pub fn set_payload(mut self, payload: PayloadType) -> Self {
+        self.block_num = payload.block_num().or(self.block_num);
+        self.roster_idx = Some(payload.roster_idx());
+        self.payload = Some(payload);
+        self
+    }
+
+    /// Keep the data of the log which could not be decoded, so that it is able to be inspected.
+    pub fn set_raw_log(mut self, raw_log: Vec<u8>) -> Self {
+        self.raw_log = Some(raw_log);
+        self
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn reason(&self) -> &SkipReason {
+        &self.reason
+    }
+
+    pub fn block_num(&self) -> Option<u64> {
+        self.block_num
+    }
+
+    pub fn roster_idx(&self) -> Option<u32> {
+        self.roster_idx
+    }
+
+    pub fn payload(&self) -> Option<&PayloadType> {
+        self.payload.as_ref()
+    }
+
+    pub fn raw_log(&self) -> Option<&[u8]> {
+        self.raw_log.as_deref()
+    }
+
+    pub fn is_replayable(&self) -> bool {
+        self.payload.is_some()
+    }
+}
+
+/// The dead letters ordered by their ids
+#[derive(Debug, Default)]
+pub(crate) struct DeadLetters {
+    letters: BTreeMap<u64, DeadLetter>,
+    next_id: u64,
+}
+
+impl DeadLetters {
+    pub(crate) fn insert(&mut self, mut letter: DeadLetter) -> u64 {
+        self.next_id += 1;
+        letter.id = self.next_id;
+        self.restore(letter);
+        self.next_id
+    }
+
+    /// Put back the dead letter taken to be replayed or persisted, keeping its id.
+    pub(crate) fn restore(&mut self, letter: DeadLetter) {
+        self.next_id = self.next_id.max(letter.id);
+        self.letters.insert(letter.id, letter);
+        while self.letters.len() > MAX_DEAD_LETTERS {
+            let oldest = *self
+                .letters
+                .keys()
+                .next()
+                .expect("dead letters must not be empty");
+            if let Some(letter) = self.letters.remove(&oldest) {
+                warn!("Dropped the oldest dead letter: {:?}", letter);
+            }
+        }
+    }
+
+    pub(crate) fn take(&mut self, id: u64) -> Option<DeadLetter> {
+        self.letters.remove(&id)
+    }
+
+    pub(crate) fn list(&self) -> Vec<DeadLetter> {
+        self.letters.values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payload::Payload;
+
+    #[test]
+    fn test_dead_letters() {
+        let mut letters = DeadLetters::default();
+        let payload = PayloadType::new(1, 0, 2, Payload::default()).set_block_num(10);
+        let id = letters.insert(
+            DeadLetter::new(SkipReason::Enclave("no path secret".to_string())).set_payload(payload),
+        );
+        letters.insert(
+            DeadLetter::new(SkipReason::Decode("invalid".to_string()))
+                .set_block_num(11)
+                .set_raw_log(vec![1, 2]),
+        );
+
+        let list = letters.list();
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].block_num(), Some(10));
+        assert_eq!(list[0].roster_idx(), Some(1));
+        assert!(list[0].is_replayable());
+        assert!(!list[1].is_replayable());
+
+        let letter = letters.take(id).unwrap();
+        assert!(letters.take(id).is_none());
+        letters.restore(letter);
+        assert_eq!(letters.list()[0].id(), id);
+
+        // The ids of the letters restored from a checkpoint are never reused.
+        let mut reloaded = DeadLetters::default();
+        for letter in letters.list() {
+            reloaded.restore(DeadLetter::decode(&mut &letter.encode()[..]).unwrap());
+        }
+        assert_eq!(reloaded.list()[1].raw_log(), Some(&[1, 2][..]));
+        assert_eq!(
+            reloaded.insert(DeadLetter::new(SkipReason::Decode("invalid".to_string()))),
+            3
+        );
+
+        for _ in 0..MAX_DEAD_LETTERS {
+            letters.insert(DeadLetter::new(SkipReason::Decode("invalid".to_string())));
+        }
+        let list = letters.list();
+        assert_eq!(list.len(), MAX_DEAD_LETTERS);
+        assert!(list.iter().all(|letter| letter.id() > 2));
+    }
+}
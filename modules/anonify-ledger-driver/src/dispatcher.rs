@@ -0,0 +1,559 @@
+use crate::workflow::*;
+use crate::{
+    blob::SharedBlobStore,
+    cache::EventCache,
+    commitment::RemoteCommitment,
+    dead_letter::{DeadLetter, SkipReason},
+    error::{HostError, Result},
+    ledger::*,
+    payload::Payload,
+    traits::*,
+    utils::*,
+    workflow::host_input,
+};
+use anonify_io_types::output;
+use frame_common::{crypto::ExportPathSecret, state_types::UpdatedState, traits::*};
+use frame_host::engine::HostEngine;
+use frame_treekem::EciesCiphertext;
+use log::warn;
+use parking_lot::RwLock;
+use sgx_types::sgx_enclave_id_t;
+use std::{fmt::Debug, marker::Send, path::Path};
+
+/// This dispatcher communicates with a blockchain node.
+#[derive(Debug)]
+pub struct Dispatcher<D: Deployer, S: Sender, W: Watcher> {
+    inner: RwLock<InnerDispatcher<D, S, W>>,
+}
+
+#[derive(Debug)]
+struct InnerDispatcher<D: Deployer, S: Sender, W: Watcher> {
+    deployer: D,
+    sender: Option<S>,
+    watcher: Option<W>,
+    cache: EventCache<D::Ledger>,
+    watcher_config: WatcherConfig,
+    tx_config: TxConfigOf<D::Ledger>,
+    signer: Option<SignerOf<D::Ledger>>,
+    blob_store: Option<SharedBlobStore>,
+}
+
+impl<D, S, W> Dispatcher<D, S, W>
+where
+    D: Deployer,
+    S: Sender<Ledger = D::Ledger>,
+    W: Watcher<Ledger = D::Ledger>,
+{
+    pub fn new(
+        enclave_id: sgx_enclave_id_t,
+        node_url: &str,
+        cache: EventCache<D::Ledger>,
+    ) -> Result<Self> {
+        let deployer = D::new(enclave_id, node_url)?;
+        let inner = RwLock::new(InnerDispatcher {
+            deployer,
+            cache,
+            sender: None,
+            watcher: None,
+            watcher_config: WatcherConfig::default(),
+            tx_config: Default::default(),
+            signer: None,
+            blob_store: None,
+        });
+
+        Ok(Dispatcher { inner })
+    }
+
+    /// Set how events are watched for the contract set after this.
+    pub fn set_watcher_config(self, config: WatcherConfig) -> Self {
+        self.inner.write().watcher_config = config;
+        self
+    }
+
+    /// Set how transactions are sent to the contract set after this.
+    pub fn set_tx_config(self, config: TxConfigOf<D::Ledger>) -> Self {
+        self.inner.write().tx_config = config;
+        self
+    }
+
+    /// Sign transactions with the local key instead of unlocked accounts of the node.
+    /// The signer's address is used as the account of this node.
+    pub fn set_signer(self, signer: SignerOf<D::Ledger>) -> Self {
+        self.inner.write().signer = Some(signer);
+        self
+    }
+
+    /// Keep the ciphertexts of commands in the blob store, and store only their hashes on the ledger.
+    /// All the nodes of the contract must share the store to fetch the ciphertexts.
+    pub fn set_blob_store(self, blob_store: SharedBlobStore) -> Self {
+        self.inner.write().blob_store = Some(blob_store);
+        self
+    }
+
+    pub fn set_contract_addr<P: AsRef<Path> + Copy>(
+        &self,
+        contract_addr: &str,
+        abi_path: P,
+    ) -> Result<()> {
+        let mut inner = self.inner.write();
+        let enclave_id = inner.deployer.get_enclave_id();
+        let node_url = inner.deployer.get_node_url();
+        // The identity key is sealed per contract, so that enclaves serving other contracts don't share it.
+        BindIdentityKeyWorkflow::exec(
+            host_input::BindIdentityKey::new(contract_addr.to_string()),
+            enclave_id,
+        )?;
+
+        let contract_info = ContractInfo::new(abi_path, contract_addr);
+        // The cache serves this contract alone, so that its checkpoint is not mixed with the ones of the others.
+        inner
+            .cache
+            .inner()
+            .write()
+            .restore_checkpoint(contract_info.address::<D::Ledger>()?)?;
+        let mut sender = S::new(
+            enclave_id,
+            node_url,
+            contract_info,
+            inner.tx_config.clone(),
+            inner.signer.clone(),
+        )?;
+        let mut watcher_config = inner.watcher_config.clone();
+        if let Some(blob_store) = &inner.blob_store {
+            sender = sender.set_blob_store(blob_store.clone());
+            watcher_config = watcher_config.set_blob_store(blob_store.clone());
+        }
+        let watcher = W::new(node_url, contract_info, inner.cache.clone(), watcher_config)?;
+
+        inner.sender = Some(sender);
+        inner.watcher = Some(watcher);
+
+        Ok(())
+    }
+
+    pub async fn deploy<P: AsRef<Path> + Send>(
+        &self,
+        deploy_user: AddressOf<D::Ledger>,
+        gas: u64,
+        abi_path: P,
+        bin_path: P,
+        confirmations: usize,
+    ) -> Result<(String, ExportPathSecret)> {
+        let mut inner = self.inner.write();
+        let eid = inner.deployer.get_enclave_id();
+        let input = host_input::JoinGroup::new(deploy_user, gas);
+        let host_output = JoinGroupWorkflow::exec(input, eid)?;
+
+        let tx_config = inner.tx_config.clone();
+        let signer = inner.signer.clone();
+        let contract_addr = inner
+            .deployer
+            .deploy(
+                host_output.clone(),
+                abi_path,
+                bin_path,
+                confirmations as u64,
+                tx_config,
+                signer,
+            )
+            .await?;
+        let export_path_secret = host_output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?
+            .export_path_secret();
+
+        Ok((contract_addr, export_path_secret))
+    }
+
+    pub async fn join_group<P: AsRef<Path> + Copy>(
+        &self,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+        contract_addr: &str,
+        abi_path: P,
+    ) -> Result<(TxIdOf<D::Ledger>, ExportPathSecret)> {
+        self.send_report_handshake(signer, gas, contract_addr, abi_path, "joinGroup")
+            .await
+    }
+
+    pub async fn register_report<P: AsRef<Path> + Copy>(
+        &self,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+        contract_addr: &str,
+        abi_path: P,
+    ) -> Result<TxIdOf<D::Ledger>> {
+        self.set_contract_addr(contract_addr, abi_path)?;
+
+        let inner = self.inner.read();
+        let eid = inner.deployer.get_enclave_id();
+        let input = host_input::RegisterReport::new(signer, gas);
+        let host_output = RegisterReportWorkflow::exec(input, eid)?;
+
+        let tx_hash = inner
+            .sender
+            .as_ref()
+            .ok_or(HostError::AddressNotSet)?
+            .register_report(host_output)
+            .await?;
+
+        Ok(tx_hash)
+    }
+
+    /// Replace the enclave's identity key, registering the new one and revoking the old one.
+    /// The enclave keeps using the old key unless the new one is registered,
+    /// so a failed rotation can be retried.
+    pub async fn rotate_identity_key(
+        &self,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+    ) -> Result<TxIdOf<D::Ledger>> {
+        let inner = self.inner.read();
+        let eid = inner.deployer.get_enclave_id();
+        let sender = inner.sender.as_ref().ok_or(HostError::AddressNotSet)?;
+        let input = host_input::RotateIdentityKey::new(signer, gas);
+        let host_output = RotateIdentityKeyWorkflow::exec(input, eid)?;
+
+        sender
+            .register_report(host_output.register_report())
+            .await?;
+        CommitIdentityKeyWorkflow::exec(host_input::CommitIdentityKey::default(), eid)?;
+
+        sender.revoke_keys(host_output).await
+    }
+
+    /// Propose the mrenclave of this enclave as the new version of the contract.
+    /// It replaces the current one once approved by the members.
+    pub async fn propose_mrenclave<P: AsRef<Path> + Copy>(
+        &self,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+        contract_addr: &str,
+        abi_path: P,
+    ) -> Result<(TxIdOf<D::Ledger>, ExportPathSecret)> {
+        self.send_report_handshake(signer, gas, contract_addr, abi_path, "proposeMrenclave")
+            .await
+    }
+
+    pub async fn approve_mrenclave(
+        &self,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+        new_version: u32,
+    ) -> Result<TxIdOf<D::Ledger>> {
+        let inner = self.inner.read();
+        match &inner.sender {
+            Some(s) => s.approve_mrenclave(signer, gas, new_version).await,
+            None => Err(HostError::AddressNotSet),
+        }
+    }
+
+    pub async fn execute_mrenclave(
+        &self,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+        new_version: u32,
+    ) -> Result<TxIdOf<D::Ledger>> {
+        let inner = self.inner.read();
+        match &inner.sender {
+            Some(s) => s.execute_mrenclave(signer, gas, new_version).await,
+            None => Err(HostError::AddressNotSet),
+        }
+    }
+
+    async fn send_report_handshake<P: AsRef<Path> + Copy>(
+        &self,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+        contract_addr: &str,
+        abi_path: P,
+        method: &str,
+    ) -> Result<(TxIdOf<D::Ledger>, ExportPathSecret)> {
+        self.set_contract_addr(contract_addr, abi_path)?;
+
+        let inner = self.inner.read();
+        let eid = inner.deployer.get_enclave_id();
+        let input = host_input::JoinGroup::new(signer, gas);
+        let host_output = JoinGroupWorkflow::exec(input, eid)?;
+
+        let tx_hash = inner
+            .sender
+            .as_ref()
+            .ok_or(HostError::AddressNotSet)?
+            .send_report_handshake(host_output.clone(), method)
+            .await?;
+
+        let export_path_secret = host_output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?
+            .export_path_secret();
+
+        Ok((tx_hash, export_path_secret))
+    }
+
+    pub async fn send_command<C, AP>(
+        &self,
+        access_policy: AP,
+        encrypted_command: EciesCiphertext,
+        call_name: &str,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+    ) -> Result<TxIdOf<D::Ledger>>
+    where
+        C: CallNameConverter,
+        AP: AccessPolicy,
+    {
+        let inner = self.inner.read();
+        let input = host_input::Command::<C, AP, _>::new(
+            encrypted_command,
+            call_name.to_string(),
+            access_policy,
+            signer,
+            gas,
+        );
+        let eid = inner.deployer.get_enclave_id();
+        let host_output = CommandWorkflow::exec(input, eid)?;
+
+        match &inner.sender {
+            Some(s) => s.send_command(host_output).await,
+            None => Err(HostError::AddressNotSet),
+        }
+    }
+
+    pub fn get_state<ST, AP, C>(&self, access_policy: AP, call_name: &str) -> Result<ST>
+    where
+        ST: State + StateDecoder,
+        AP: AccessPolicy,
+        C: CallNameConverter,
+    {
+        let call_id = C::as_id(call_name);
+        let eid = self.inner.read().deployer.get_enclave_id();
+        let input = host_input::GetState::new(access_policy, call_id);
+
+        let vec = GetStateWorkflow::exec(input, eid)?
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?
+            .into_vec(); // into Vec<u8> in StateType
+
+        ST::decode_vec(vec).map_err(Into::into)
+    }
+
+    pub async fn handshake(
+        &self,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+    ) -> Result<(TxIdOf<D::Ledger>, ExportPathSecret)> {
+        let inner = self.inner.read();
+        let input = host_input::Handshake::new(signer, gas);
+        let eid = inner.deployer.get_enclave_id();
+        let host_output = HandshakeWorkflow::exec(input, eid)?;
+
+        let tx_hash = inner
+            .sender
+            .as_ref()
+            .ok_or(HostError::AddressNotSet)?
+            .handshake(host_output.clone())
+            .await?;
+        let export_path_secret = host_output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?
+            .export_path_secret();
+
+        Ok((tx_hash, export_path_secret))
+    }
+
+    /// Publish the commitment to the enclave state after the last processed ciphertext.
+    /// Nothing is published if no ciphertext has been processed since the last publication.
+    pub async fn publish_state_commitment(
+        &self,
+        signer: AddressOf<D::Ledger>,
+        gas: u64,
+    ) -> Result<Option<TxIdOf<D::Ledger>>> {
+        let inner = self.inner.read();
+        {
+            let cache = inner.cache.inner().read();
+            let latest = cache
+                .latest_state_root()
+                .map(|(ciphertext_id, _)| ciphertext_id);
+            if latest.is_none() || latest == cache.published_commitment() {
+                return Ok(None);
+            }
+        }
+
+        let eid = inner.deployer.get_enclave_id();
+        let input = host_input::StateCommitment::new(signer, gas);
+        let host_output = StateCommitmentWorkflow::exec(input, eid)?;
+        let ciphertext_id = host_output
+            .ecall_output
+            .as_ref()
+            .ok_or_else(|| HostError::EcallOutputNotSet)?
+            .commitment()
+            .ciphertext_id();
+
+        let tx_id = inner
+            .sender
+            .as_ref()
+            .ok_or(HostError::AddressNotSet)?
+            .publish_state_commitment(host_output)
+            .await?;
+        inner
+            .cache
+            .inner()
+            .write()
+            .set_published_commitment(ciphertext_id);
+
+        Ok(Some(tx_id))
+    }
+
+    /// The root of the enclave state after the last processed ciphertext,
+    /// and the latest commitment published by each member checked against the local state.
+    pub fn state_commitments(&self) -> (Option<[u8; 32]>, Vec<RemoteCommitment>) {
+        let inner = self.inner.read();
+        let cache = inner.cache.inner().read();
+        let state_root = cache.latest_state_root().map(|(_, state_root)| state_root);
+
+        (state_root, cache.state_commitments())
+    }
+
+    /// The commitments of the members which did not match the local state.
+    pub fn commitment_mismatches(&self) -> Vec<RemoteCommitment> {
+        self.inner
+            .read()
+            .cache
+            .inner()
+            .read()
+            .commitment_mismatches()
+    }
+
+    pub async fn fetch_events<St>(&self) -> Result<Option<Vec<UpdatedState<St>>>>
+    where
+        St: State,
+    {
+        let inner = self.inner.read();
+        let eid = inner.deployer.get_enclave_id();
+        inner
+            .watcher
+            .as_ref()
+            .ok_or(HostError::EventWatcherNotSet)?
+            .fetch_events(eid)
+            .await
+    }
+
+    /// The number of the latest block on the ledger and the last one whose events have been fetched.
+    pub async fn sync_position(&self) -> Result<(u64, Option<u64>)> {
+        let inner = self.inner.read();
+        let watcher = inner
+            .watcher
+            .as_ref()
+            .ok_or(HostError::EventWatcherNotSet)?;
+        let latest_block_num = watcher.latest_block_num().await?;
+
+        Ok((latest_block_num, watcher.scanned_block_num()))
+    }
+
+    pub async fn get_account(&self, index: usize, password: &str) -> Result<AddressOf<D::Ledger>> {
+        let inner = self.inner.read();
+        match &inner.signer {
+            Some(signer) => Ok(<D::Ledger as LedgerDriver>::signer_address(signer)),
+            None => inner.deployer.get_account(index, password).await,
+        }
+    }
+
+    /// The encrypting key with the IAS report attesting it
+    pub fn get_encrypting_key(&self) -> Result<output::ReturnEncryptingKey> {
+        let input = host_input::GetEncryptingKey::default();
+        let eid = self.inner.read().deployer.get_enclave_id();
+        let encrypting_key = GetEncryptingKeyWorkflow::exec(input, eid)?;
+
+        encrypting_key
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)
+    }
+
+    /// The age and quote status of the cached evidence attesting the enclave's identity key
+    pub fn get_attestation_status(&self) -> Result<output::ReturnAttestationStatus> {
+        let input = host_input::GetAttestationStatus::default();
+        let eid = self.inner.read().deployer.get_enclave_id();
+        let status = GetAttestationStatusWorkflow::exec(input, eid)?;
+
+        status
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)
+    }
+
+    pub fn register_notification<AP>(&self, access_policy: AP) -> Result<()>
+    where
+        AP: AccessPolicy,
+    {
+        let inner = self.inner.read();
+        let input = host_input::RegisterNotification::new(access_policy);
+        let eid = inner.deployer.get_enclave_id();
+        let _host_output = RegisterNotificationWorkflow::exec(input, eid)?;
+
+        Ok(())
+    }
+
+    /// The payloads skipped instead of being inserted into the enclave.
+    pub fn dead_letters(&self) -> Vec<DeadLetter> {
+        self.inner.read().cache.inner().read().dead_letters()
+    }
+
+    /// Insert the skipped payload into the enclave again once the cause is fixed,
+    /// e.g. the missing path secret has been restored.
+    /// The dead letter is kept with the new reason if the enclave fails again.
+    pub fn replay_dead_letter<St>(&self, id: u64) -> Result<Option<UpdatedState<St>>>
+    where
+        St: State,
+    {
+        let inner = self.inner.read();
+        let cache = inner.cache.inner();
+        let letter = cache
+            .write()
+            .take_dead_letter(id)
+            .ok_or(HostError::DeadLetterNotFound(id))?;
+        let payload = match letter.payload().cloned() {
+            Some(payload) => payload,
+            None => {
+                cache.write().restore_dead_letter(letter);
+                return Err(HostError::DeadLetterNotReplayable(id));
+            }
+        };
+
+        let eid = inner.deployer.get_enclave_id();
+        let updated_state = match payload.into_payload() {
+            Payload::Ciphertext(ciphertext) => {
+                let input = host_input::InsertCiphertext::new(ciphertext);
+                // The state root is not recorded, since the replayed payload is applied out of the chain order
+                // and the state after it is not comparable with the commitments of the other members.
+                InsertCiphertextWorkflow::exec(input, eid)
+                    .map_err(Into::into)
+                    .and_then(|e| e.ecall_output.ok_or_else(|| HostError::EcallOutputNotSet))
+                    .map(|update| update.updated_state)
+            }
+            Payload::Handshake(handshake) => {
+                let input = host_input::InsertHandshake::new(handshake);
+                InsertHandshakeWorkflow::exec(input, eid)
+                    .map(|_| None)
+                    .map_err(Into::into)
+            }
+        };
+        let updated_state = match updated_state {
+            Ok(updated_state) => updated_state,
+            Err(e) => {
+                cache
+                    .write()
+                    .restore_dead_letter(letter.set_reason(SkipReason::Enclave(e.to_string())));
+                return Err(e);
+            }
+        };
+
+        // The state has been updated in the enclave, so the dead letter must not be replayed again.
+        if let Err(e) = cache.read().save_checkpoints() {
+            warn!("Failed to persist the replayed dead letter {}: {}", id, e);
+        }
+        updated_state
+            .map(UpdatedState::<St>::from_state_type)
+            .transpose()
+            .map_err(Into::into)
+    }
+}
@@ -0,0 +1,41 @@
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, HostError>;
+
+#[derive(Error, Debug)]
+pub enum HostError {
+    #[error("Error: {0}")]
+    Error(#[from] anyhow::Error),
+    #[error("Contract address have not been set.")]
+    AddressNotSet,
+    #[error("Event watcher have not been set.")]
+    EventWatcherNotSet,
+    #[error("The contract is not served by this node: {0}")]
+    ContractNotServed(String),
+    #[error("Ecall output is not set. An error would have occurred in the enclave")]
+    EcallOutputNotSet,
+    #[error("The ledger stores the hashes of ciphertexts, but no blob store has been set.")]
+    BlobStoreNotSet,
+    #[error("The blob has not been stored: {0}")]
+    BlobNotFound(String),
+    #[error("The blob does not match the hash: {0}")]
+    BlobMismatch(String),
+    #[error("The dead letter is not found: {0}")]
+    DeadLetterNotFound(u64),
+    #[error("The dead letter has no payload to be replayed: {0}")]
+    DeadLetterNotReplayable(u64),
+    #[error("The checkpoint does not match the enclave state: {0}")]
+    CheckpointMismatch(String),
+    #[error("The sync service is already running.")]
+    SyncAlreadyRunning,
+    #[error("The ledger does not support {0}")]
+    Unsupported(&'static str),
+    #[error("Ledger error: {0}")]
+    LedgerError(Box<dyn std::error::Error + Send + Sync>),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Codec error: {0}")]
+    CodecError(#[from] codec::Error),
+    #[error("Frame host error: {0}")]
+    FrameHostError(#[from] frame_host::Error),
+}
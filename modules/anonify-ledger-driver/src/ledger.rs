@@ -0,0 +1,43 @@
+use crate::error::Result;
+use std::{fmt::Debug, hash::Hash};
+
+/// The types and conversions specific to a ledger which the Anonify contract is deployed on.
+/// Backends of the `Deployer`, `Sender` and `Watcher` traits share the same `LedgerDriver`,
+/// so that the dispatcher is able to pass values between them without knowing the ledger.
+pub trait LedgerDriver: Send + Sync + 'static {
+    /// An address of accounts and contracts
+    type Address: Copy + Eq + Hash + Debug + Send + Sync + 'static;
+    /// An identifier of submitted transactions
+    type TxId: Copy + Eq + Debug + Send + Sync + 'static;
+    /// A position of a block in the chain, used to detect reorganizations of fetched blocks
+    type BlockPosition: Copy + Eq + Debug + Send + Sync + 'static;
+    /// An identifier of an event log, which is unique even among the forks of the chain
+    type LogId: Copy + Eq + Hash + Debug + Send + Sync + 'static;
+    /// Configuration of how transactions are sent and confirmed
+    type TxConfig: Clone + Default + Debug + Send + Sync + 'static;
+    /// A key signing transactions on the host
+    type Signer: Clone + Debug + Send + Sync + 'static;
+
+    fn parse_address(addr: &str) -> Result<Self::Address>;
+
+    fn format_address(addr: &Self::Address) -> String;
+
+    fn signer_address(signer: &Self::Signer) -> Self::Address;
+
+    /// Serialize a block position to persist the scanned block of a contract.
+    fn encode_block_position(position: &Self::BlockPosition) -> Vec<u8>;
+
+    fn decode_block_position(bytes: &[u8]) -> Result<Self::BlockPosition>;
+
+    /// Serialize a log id to persist the logs inserted into the enclave.
+    fn encode_log_id(log_id: &Self::LogId) -> Vec<u8>;
+
+    fn decode_log_id(bytes: &[u8]) -> Result<Self::LogId>;
+}
+
+pub type AddressOf<L> = <L as LedgerDriver>::Address;
+pub type TxIdOf<L> = <L as LedgerDriver>::TxId;
+pub type BlockPositionOf<L> = <L as LedgerDriver>::BlockPosition;
+pub type LogIdOf<L> = <L as LedgerDriver>::LogId;
+pub type TxConfigOf<L> = <L as LedgerDriver>::TxConfig;
+pub type SignerOf<L> = <L as LedgerDriver>::Signer;
@@ -0,0 +1,33 @@
+#![crate_type = "lib"]
+
+//! The ledger-neutral layer of the host.
+//! Each ledger backend implements `LedgerDriver` and the `Deployer`, `Sender` and `Watcher` traits,
+//! and the dispatcher drives the enclave and the backend through them.
+
+mod blob;
+mod cache;
+mod checkpoint;
+mod commitment;
+mod dead_letter;
+pub mod dispatcher;
+mod error;
+mod ledger;
+mod payload;
+mod registry;
+pub mod traits;
+mod utils;
+pub mod workflow;
+
+pub use blob::{
+    blob_hash, fetch_blob, put_blob, BlobHash, BlobStore, FsBlobStore, HttpBlobStore,
+    SharedBlobStore, MAX_BLOB_TRIALS,
+};
+pub use cache::{EventCache, InnerEventCache, MAX_TRIALS_NUM};
+pub use commitment::{CommitmentCheck, RemoteCommitment, MAX_STATE_ROOTS};
+pub use dead_letter::{DeadLetter, SkipReason, MAX_DEAD_LETTERS};
+pub use dispatcher::Dispatcher;
+pub use error::{HostError, Result};
+pub use ledger::{AddressOf, BlockPositionOf, LedgerDriver, LogIdOf, SignerOf, TxConfigOf, TxIdOf};
+pub use payload::{Payload, PayloadType};
+pub use registry::DispatcherRegistry;
+pub use utils::{CommandInfo, ContractInfo, WatcherConfig};
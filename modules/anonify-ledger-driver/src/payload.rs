@@ -0,0 +1,131 @@
+use codec::{Decode, Encode};
+use frame_common::crypto::{Ciphertext, ExportHandshake};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, Hash, Encode, Decode)]
+pub struct PayloadType {
+    roster_idx: u32,
+    epoch: u32,
+    generation: u32,
+    payload: Payload,
+    // The block containing the payload, to report it if it is skipped
+    block_num: Option<u64>,
+    // The index of the log in the block, which orders the payloads in the chain
+    log_index: Option<u64>,
+}
+
+impl PayloadType {
+    pub fn new(roster_idx: u32, epoch: u32, generation: u32, payload: Payload) -> Self {
+        PayloadType {
+            roster_idx,
+            epoch,
+            generation,
+            payload,
+            block_num: None,
+            log_index: None,
+        }
+    }
+
+    pub fn set_block_num(mut self, block_num: u64) -> Self {
+        self.block_num = Some(block_num);
+        self
+    }
+
+    /// Set the position of the log in the chain,
+    /// which is the order the payloads are inserted into the enclave in on every node.
+    pub fn set_position(mut self, block_num: u64, log_index: u64) -> Self {
+        self.block_num = Some(block_num);
+        self.log_index = Some(log_index);
+        self
+    }
+
+    /// other is the next of self
+    pub fn is_next(&self, other: &Self) -> bool {
+        self.roster_idx == other.roster_idx
+            && ((self.epoch == other.epoch && self.generation + 1 == other.generation) ||
+            (self.epoch == other.epoch && other.generation == u32::MAX) || // TODO: order gurantee with handshake
+            (self.epoch + 1 == other.epoch && self.generation == u32::MAX && other.generation == 0))
+    }
+
+    pub fn roster_idx(&self) -> u32 {
+        self.roster_idx
+    }
+
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    pub fn block_num(&self) -> Option<u64> {
+        self.block_num
+    }
+
+    /// The block number and the index of the log in the block
+    pub fn position(&self) -> Option<(u64, u64)> {
+        Some((self.block_num?, self.log_index?))
+    }
+
+    pub fn payload(&self) -> &Payload {
+        &self.payload
+    }
+
+    pub fn into_payload(self) -> Payload {
+        self.payload
+    }
+}
+
+impl PartialEq for PayloadType {
+    fn eq(&self, other: &PayloadType) -> bool {
+        self.roster_idx == other.roster_idx
+            && self.epoch == other.epoch
+            && self.generation == other.generation
+    }
+}
+
+impl Eq for PayloadType {}
+
+/// Ordering PayloadType> like:
+/// epoch      | 0              1            2 ..
+/// generation | 0 1 2 3 .. MAX 0 1 2 .. MAX 0 ..
+impl PartialOrd for PayloadType {
+    fn partial_cmp(&self, other: &PayloadType) -> Option<Ordering> {
+        let roster_idx_ord = self.roster_idx.partial_cmp(&other.roster_idx)?;
+        if roster_idx_ord != Ordering::Equal {
+            return Some(roster_idx_ord);
+        }
+
+        let epoch_ord = self.epoch.partial_cmp(&other.epoch)?;
+        if epoch_ord != Ordering::Equal {
+            return Some(epoch_ord);
+        }
+
+        let gen_ord = self.generation.partial_cmp(&other.generation)?;
+        if gen_ord != Ordering::Equal {
+            return Some(gen_ord);
+        }
+
+        Some(Ordering::Equal)
+    }
+}
+
+impl Ord for PayloadType {
+    fn cmp(&self, other: &PayloadType) -> Ordering {
+        self.partial_cmp(&other)
+            .expect("PayloadType must be ordered")
+    }
+}
+
+#[derive(Debug, Clone, Hash, Encode, Decode)]
+pub enum Payload {
+    Ciphertext(Ciphertext),
+    Handshake(ExportHandshake),
+}
+
+impl Default for Payload {
+    fn default() -> Self {
+        Payload::Ciphertext(Default::default())
+    }
+}
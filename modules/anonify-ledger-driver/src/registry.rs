@@ -0,0 +1,94 @@
+use crate::{
+    dispatcher::Dispatcher,
+    error::{HostError, Result},
+    ledger::{AddressOf, LedgerDriver},
+    traits::*,
+};
+use frame_common::{state_types::UpdatedState, traits::*};
+use futures::future::join_all;
+use parking_lot::RwLock;
+use std::{collections::HashMap, sync::Arc};
+
+/// Dispatchers of the contracts served by a node.
+/// Each dispatcher is supposed to be connected to its own enclave and event cache,
+/// so that the group keys and the states of independent deployments are isolated.
+#[derive(Debug)]
+pub struct DispatcherRegistry<D: Deployer, S: Sender, W: Watcher> {
+    dispatchers: RwLock<HashMap<AddressOf<D::Ledger>, Arc<Dispatcher<D, S, W>>>>,
+}
+
+impl<D, S, W> DispatcherRegistry<D, S, W>
+where
+    D: Deployer,
+    S: Sender<Ledger = D::Ledger>,
+    W: Watcher<Ledger = D::Ledger>,
+{
+    pub fn new() -> Self {
+        DispatcherRegistry {
+            dispatchers: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Register the dispatcher whose contract address has been set.
+    pub fn insert(&self, contract_addr: &str, dispatcher: Arc<Dispatcher<D, S, W>>) -> Result<()> {
+        let contract_addr = <D::Ledger as LedgerDriver>::parse_address(contract_addr)?;
+        self.dispatchers.write().insert(contract_addr, dispatcher);
+        Ok(())
+    }
+
+    pub fn get(&self, contract_addr: &str) -> Result<Arc<Dispatcher<D, S, W>>> {
+        let contract_addr = <D::Ledger as LedgerDriver>::parse_address(contract_addr)?;
+        self.dispatchers
+            .read()
+            .get(&contract_addr)
+            .cloned()
+            .ok_or_else(|| {
+                HostError::ContractNotServed(<D::Ledger as LedgerDriver>::format_address(
+                    &contract_addr,
+                ))
+            })
+    }
+
+    pub fn remove(&self, contract_addr: &str) -> Result<Option<Arc<Dispatcher<D, S, W>>>> {
+        let contract_addr = <D::Ledger as LedgerDriver>::parse_address(contract_addr)?;
+        Ok(self.dispatchers.write().remove(&contract_addr))
+    }
+
+    pub fn contract_addrs(&self) -> Vec<AddressOf<D::Ledger>> {
+        self.dispatchers.read().keys().copied().collect()
+    }
+
+    /// Fetch events of all the contracts concurrently.
+    /// An error in a contract does not stop fetching the others.
+    pub async fn fetch_events<St>(
+        &self,
+    ) -> Vec<(AddressOf<D::Ledger>, Result<Option<Vec<UpdatedState<St>>>>)>
+    where
+        St: State,
+    {
+        let dispatchers: Vec<(AddressOf<D::Ledger>, Arc<Dispatcher<D, S, W>>)> = self
+            .dispatchers
+            .read()
+            .iter()
+            .map(|(addr, dispatcher)| (*addr, dispatcher.clone()))
+            .collect();
+
+        join_all(
+            dispatchers
+                .into_iter()
+                .map(|(addr, dispatcher)| async move { (addr, dispatcher.fetch_events().await) }),
+        )
+        .await
+    }
+}
+
+impl<D, S, W> Default for DispatcherRegistry<D, S, W>
+where
+    D: Deployer,
+    S: Sender<Ledger = D::Ledger>,
+    W: Watcher<Ledger = D::Ledger>,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
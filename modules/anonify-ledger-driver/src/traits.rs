@@ -0,0 +1,145 @@
+#![allow(dead_code)]
+
+use crate::{
+    blob::SharedBlobStore, cache::EventCache, error::Result, ledger::*, utils::*, workflow::*,
+};
+
+use async_trait::async_trait;
+use frame_common::{state_types::UpdatedState, traits::*};
+use sgx_types::sgx_enclave_id_t;
+use std::{marker::Send, path::Path};
+
+/// A trait for deploying contracts
+#[async_trait]
+pub trait Deployer: Sized {
+    type Ledger: LedgerDriver;
+    /// A handle of the deployed contract, passed to the sender of the same backend
+    type Contract: Send;
+
+    fn new(enclave_id: sgx_enclave_id_t, node_url: &str) -> Result<Self>;
+
+    async fn get_account(&self, index: usize, password: &str) -> Result<AddressOf<Self::Ledger>>;
+
+    /// Deploying contract with attestation.
+    /// It waits for the deploying transaction to be confirmed by the blocks.
+    async fn deploy<P: AsRef<Path> + Send>(
+        &mut self,
+        host_output: host_output::JoinGroup<AddressOf<Self::Ledger>>,
+        abi_path: P,
+        bin_path: P,
+        confirmations: u64,
+        config: TxConfigOf<Self::Ledger>,
+        signer: Option<SignerOf<Self::Ledger>>,
+    ) -> Result<String>;
+
+    fn get_contract<P: AsRef<Path>>(self, abi_path: P) -> Result<Self::Contract>;
+
+    fn get_enclave_id(&self) -> sgx_enclave_id_t;
+
+    fn get_node_url(&self) -> &str;
+}
+
+/// A trait for sending transactions to blockchain nodes
+#[async_trait]
+pub trait Sender: Sized {
+    type Ledger: LedgerDriver;
+    type Contract: Send;
+
+    fn new<P: AsRef<Path>>(
+        enclave_id: sgx_enclave_id_t,
+        node_url: &str,
+        contract_info: ContractInfo<'_, P>,
+        config: TxConfigOf<Self::Ledger>,
+        signer: Option<SignerOf<Self::Ledger>>,
+    ) -> Result<Self>;
+
+    fn from_contract(enclave_id: sgx_enclave_id_t, contract: Self::Contract) -> Self;
+
+    /// Put the ciphertexts of commands into the blob store, and send only their hashes.
+    fn set_blob_store(self, blob_store: SharedBlobStore) -> Self;
+
+    async fn get_account(&self, index: usize, password: &str) -> Result<AddressOf<Self::Ledger>>;
+
+    /// Send an encrypted command of state transition to blockchain nodes.
+    async fn send_command(
+        &self,
+        host_output: host_output::Command<AddressOf<Self::Ledger>>,
+    ) -> Result<TxIdOf<Self::Ledger>>;
+
+    /// Attestation with deployed contract.
+    async fn send_report_handshake(
+        &self,
+        host_output: host_output::JoinGroup<AddressOf<Self::Ledger>>,
+        method: &str,
+    ) -> Result<TxIdOf<Self::Ledger>>;
+
+    /// Approve the proposal of the new mrenclave version,
+    /// which is executed if the approvals reach the threshold after the timelock.
+    async fn approve_mrenclave(
+        &self,
+        signer: AddressOf<Self::Ledger>,
+        gas: u64,
+        new_version: u32,
+    ) -> Result<TxIdOf<Self::Ledger>>;
+
+    /// Execute the proposal of the new mrenclave version approved by the threshold of the members.
+    async fn execute_mrenclave(
+        &self,
+        signer: AddressOf<Self::Ledger>,
+        gas: u64,
+        new_version: u32,
+    ) -> Result<TxIdOf<Self::Ledger>>;
+
+    async fn register_report(
+        &self,
+        host_output: host_output::RegisterReport<AddressOf<Self::Ledger>>,
+    ) -> Result<TxIdOf<Self::Ledger>>;
+
+    /// Revoke the old identity key in favor of the rotated one, which must have been registered.
+    async fn revoke_keys(
+        &self,
+        host_output: host_output::RotateIdentityKey<AddressOf<Self::Ledger>>,
+    ) -> Result<TxIdOf<Self::Ledger>>;
+
+    async fn handshake(
+        &self,
+        host_output: host_output::Handshake<AddressOf<Self::Ledger>>,
+    ) -> Result<TxIdOf<Self::Ledger>>;
+
+    /// Publish the commitment to the enclave state signed by the enclave,
+    /// so that the other members are able to check they have computed the same state.
+    async fn publish_state_commitment(
+        &self,
+        host_output: host_output::StateCommitment<AddressOf<Self::Ledger>>,
+    ) -> Result<TxIdOf<Self::Ledger>>;
+
+    fn get_contract(self) -> Self::Contract;
+}
+
+/// A trait of fetching event from blockchian nodes
+#[async_trait]
+pub trait Watcher: Sized {
+    type Ledger: LedgerDriver;
+    type Contract: Send;
+
+    fn new<P: AsRef<Path>>(
+        node_url: &str,
+        contract_info: ContractInfo<'_, P>,
+        cache: EventCache<Self::Ledger>,
+        config: WatcherConfig,
+    ) -> Result<Self>;
+
+    /// Blocking event fetch from blockchain nodes.
+    async fn fetch_events<S: State>(
+        &self,
+        eid: sgx_enclave_id_t,
+    ) -> Result<Option<Vec<UpdatedState<S>>>>;
+
+    /// The number of the latest block on the ledger, regardless of the confirmations.
+    async fn latest_block_num(&self) -> Result<u64>;
+
+    /// The number of the last block whose events have been fetched.
+    fn scanned_block_num(&self) -> Option<u64>;
+
+    fn get_contract(self) -> Self::Contract;
+}
@@ -0,0 +1,98 @@
+use crate::{blob::SharedBlobStore, error::Result, ledger::LedgerDriver};
+use anonify_io_types::*;
+use frame_common::traits::*;
+use frame_treekem::EciesCiphertext;
+use std::{marker::PhantomData, path::Path};
+
+/// Needed information to handle smart contracts.
+#[derive(Debug, Clone, Copy)]
+pub struct ContractInfo<'a, P: AsRef<Path>> {
+    abi_path: P,
+    addr: &'a str,
+}
+
+impl<'a, P: AsRef<Path>> ContractInfo<'a, P> {
+    pub fn new(abi_path: P, addr: &'a str) -> Self {
+        ContractInfo { abi_path, addr }
+    }
+
+    pub fn abi_path(&self) -> &Path {
+        self.abi_path.as_ref()
+    }
+
+    pub fn address<L: LedgerDriver>(&self) -> Result<L::Address> {
+        L::parse_address(self.addr)
+    }
+}
+
+/// Configuration of how events are watched.
+/// Events are fetched by polling if the WebSocket URL is not set.
+#[derive(Debug, Clone, Default)]
+pub struct WatcherConfig {
+    confirmations: u64,
+    ws_url: Option<String>,
+    blob_store: Option<SharedBlobStore>,
+}
+
+impl WatcherConfig {
+    pub fn new() -> Self {
+        WatcherConfig::default()
+    }
+
+    /// Set the number of blocks which must be built on top of a block
+    /// before its events are inserted into the enclave.
+    pub fn set_confirmations(mut self, confirmations: u64) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    /// Receive events by subscribing to them via the WebSocket endpoint.
+    pub fn set_ws_url(mut self, ws_url: &str) -> Self {
+        self.ws_url = Some(ws_url.to_string());
+        self
+    }
+
+    /// Fetch the ciphertexts whose hashes are stored on the ledger from the blob store.
+    pub fn set_blob_store(mut self, blob_store: SharedBlobStore) -> Self {
+        self.blob_store = Some(blob_store);
+        self
+    }
+
+    pub fn confirmations(&self) -> u64 {
+        self.confirmations
+    }
+
+    pub fn ws_url(&self) -> Option<&str> {
+        self.ws_url.as_deref()
+    }
+
+    pub fn blob_store(&self) -> Option<&SharedBlobStore> {
+        self.blob_store.as_ref()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandInfo<'a, C: CallNameConverter> {
+    encrypted_command: EciesCiphertext,
+    call_name: &'a str,
+    phantom: PhantomData<C>,
+}
+
+impl<'a, C: CallNameConverter> CommandInfo<'a, C> {
+    pub fn new(encrypted_command: EciesCiphertext, call_name: &'a str) -> Self {
+        CommandInfo {
+            encrypted_command,
+            call_name,
+            phantom: PhantomData::<C>,
+        }
+    }
+
+    pub fn call_name_to_id(&self) -> u32 {
+        C::as_id(self.call_name)
+    }
+
+    pub fn crate_input<AP: AccessPolicy>(self, access_policy: AP) -> input::Command<AP> {
+        let call_id = self.call_name_to_id();
+        input::Command::new(access_policy, self.encrypted_command, call_id)
+    }
+}
@@ -8,53 +8,109 @@ use frame_common::{
 use frame_host::engine::*;
 use frame_treekem::EciesCiphertext;
 use std::marker::PhantomData;
-use web3::types::Address;
 
 pub const OUTPUT_MAX_LEN: usize = 2048;
+/// The encrypting key is returned with the IAS report, its signature and the signing certificate.
+pub const ENCRYPTING_KEY_OUTPUT_MAX_LEN: usize = 8192;
 
-pub struct CommandWorkflow<C: CallNameConverter, AP: AccessPolicy> {
+pub struct CommandWorkflow<C: CallNameConverter, AP: AccessPolicy, A> {
     c: PhantomData<C>,
     ap: PhantomData<AP>,
+    a: PhantomData<A>,
 }
 
-impl<C: CallNameConverter, AP: AccessPolicy> HostEngine for CommandWorkflow<C, AP> {
-    type HI = host_input::Command<C, AP>;
+impl<C: CallNameConverter, AP: AccessPolicy, A> HostEngine for CommandWorkflow<C, AP, A> {
+    type HI = host_input::Command<C, AP, A>;
     type EI = input::Command<AP>;
     type EO = output::Command;
-    type HO = host_output::Command;
+    type HO = host_output::Command<A>;
     const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
     const CMD: u32 = ENCRYPT_COMMAND_CMD;
 }
 
-pub struct JoinGroupWorkflow;
+pub struct JoinGroupWorkflow<A> {
+    a: PhantomData<A>,
+}
 
-impl HostEngine for JoinGroupWorkflow {
-    type HI = host_input::JoinGroup;
+impl<A> HostEngine for JoinGroupWorkflow<A> {
+    type HI = host_input::JoinGroup<A>;
     type EI = input::CallJoinGroup;
     type EO = output::ReturnJoinGroup;
-    type HO = host_output::JoinGroup;
+    type HO = host_output::JoinGroup<A>;
     const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
     const CMD: u32 = CALL_JOIN_GROUP_CMD;
 }
 
-pub struct RegisterReportWorkflow;
+pub struct RegisterReportWorkflow<A> {
+    a: PhantomData<A>,
+}
 
-impl HostEngine for RegisterReportWorkflow {
-    type HI = host_input::RegisterReport;
+impl<A> HostEngine for RegisterReportWorkflow<A> {
+    type HI = host_input::RegisterReport<A>;
     type EI = input::CallRegisterReport;
     type EO = output::ReturnRegisterReport;
-    type HO = host_output::RegisterReport;
+    type HO = host_output::RegisterReport<A>;
     const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
     const CMD: u32 = CALL_REGISTER_REPORT_CMD;
 }
 
-pub struct HandshakeWorkflow;
+pub struct RotateIdentityKeyWorkflow<A> {
+    a: PhantomData<A>,
+}
+
+impl<A> HostEngine for RotateIdentityKeyWorkflow<A> {
+    type HI = host_input::RotateIdentityKey<A>;
+    type EI = input::CallRotateIdentityKey;
+    type EO = output::ReturnRotateIdentityKey;
+    type HO = host_output::RotateIdentityKey<A>;
+    const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
+    const CMD: u32 = CALL_ROTATE_IDENTITY_KEY_CMD;
+}
+
+pub struct CommitIdentityKeyWorkflow;
+
+impl HostEngine for CommitIdentityKeyWorkflow {
+    type HI = host_input::CommitIdentityKey;
+    type EI = input::CallCommitIdentityKey;
+    type EO = output::Empty;
+    type HO = host_output::CommitIdentityKey;
+    const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
+    const CMD: u32 = CALL_COMMIT_IDENTITY_KEY_CMD;
+}
+
+pub struct BindIdentityKeyWorkflow;
+
+impl HostEngine for BindIdentityKeyWorkflow {
+    type HI = host_input::BindIdentityKey;
+    type EI = input::CallBindIdentityKey;
+    type EO = output::Empty;
+    type HO = host_output::BindIdentityKey;
+    const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
+    const CMD: u32 = CALL_BIND_IDENTITY_KEY_CMD;
+}
+
+pub struct StateCommitmentWorkflow<A> {
+    a: PhantomData<A>,
+}
 
-impl HostEngine for HandshakeWorkflow {
-    type HI = host_input::Handshake;
+impl<A> HostEngine for StateCommitmentWorkflow<A> {
+    type HI = host_input::StateCommitment<A>;
+    type EI = input::CallStateCommitment;
+    type EO = output::ReturnStateCommitment;
+    type HO = host_output::StateCommitment<A>;
+    const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
+    const CMD: u32 = GET_STATE_COMMITMENT_CMD;
+}
+
+pub struct HandshakeWorkflow<A> {
+    a: PhantomData<A>,
+}
+
+impl<A> HostEngine for HandshakeWorkflow<A> {
+    type HI = host_input::Handshake<A>;
     type EI = input::CallHandshake;
     type EO = output::ReturnHandshake;
-    type HO = host_output::Handshake;
+    type HO = host_output::Handshake<A>;
     const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
     const CMD: u32 = CALL_HANDSHAKE_CMD;
 }
@@ -114,28 +170,39 @@ impl HostEngine for GetEncryptingKeyWorkflow {
     type EI = input::GetEncryptingKey;
     type EO = output::ReturnEncryptingKey;
     type HO = host_output::ReturnEncryptingKey;
-    const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
+    const OUTPUT_MAX_LEN: usize = ENCRYPTING_KEY_OUTPUT_MAX_LEN;
     const CMD: u32 = GET_ENCRYPTING_KEY_CMD;
 }
 
+pub struct GetAttestationStatusWorkflow;
+
+impl HostEngine for GetAttestationStatusWorkflow {
+    type HI = host_input::GetAttestationStatus;
+    type EI = input::GetAttestationStatus;
+    type EO = output::ReturnAttestationStatus;
+    type HO = host_output::ReturnAttestationStatus;
+    const OUTPUT_MAX_LEN: usize = OUTPUT_MAX_LEN;
+    const CMD: u32 = GET_ATTESTATION_STATUS_CMD;
+}
+
 pub mod host_input {
     use super::*;
 
-    pub struct Command<C: CallNameConverter, AP: AccessPolicy> {
+    pub struct Command<C: CallNameConverter, AP: AccessPolicy, A> {
         encrypted_command: EciesCiphertext,
         call_name: String,
         access_policy: AP,
-        signer: Address,
+        signer: A,
         gas: u64,
         phantom: PhantomData<C>,
     }
 
-    impl<C: CallNameConverter, AP: AccessPolicy> Command<C, AP> {
+    impl<C: CallNameConverter, AP: AccessPolicy, A> Command<C, AP, A> {
         pub fn new(
             encrypted_command: EciesCiphertext,
             call_name: String,
             access_policy: AP,
-            signer: Address,
+            signer: A,
             gas: u64,
         ) -> Self {
             Command {
@@ -149,9 +216,9 @@ pub mod host_input {
         }
     }
 
-    impl<C: CallNameConverter, AP: AccessPolicy> HostInput for Command<C, AP> {
+    impl<C: CallNameConverter, AP: AccessPolicy, A> HostInput for Command<C, AP, A> {
         type EcallInput = input::Command<AP>;
-        type HostOutput = host_output::Command;
+        type HostOutput = host_output::Command<A>;
 
         fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
             let command_info = CommandInfo::<C>::new(self.encrypted_command, &self.call_name);
@@ -162,20 +229,20 @@ pub mod host_input {
         }
     }
 
-    pub struct JoinGroup {
-        signer: Address,
+    pub struct JoinGroup<A> {
+        signer: A,
         gas: u64,
     }
 
-    impl JoinGroup {
-        pub fn new(signer: Address, gas: u64) -> Self {
+    impl<A> JoinGroup<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
             JoinGroup { signer, gas }
         }
     }
 
-    impl HostInput for JoinGroup {
+    impl<A> HostInput for JoinGroup<A> {
         type EcallInput = input::CallJoinGroup;
-        type HostOutput = host_output::JoinGroup;
+        type HostOutput = host_output::JoinGroup<A>;
 
         fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
             let host_output = host_output::JoinGroup::new(self.signer, self.gas);
@@ -184,20 +251,20 @@ pub mod host_input {
         }
     }
 
-    pub struct RegisterReport {
-        signer: Address,
+    pub struct RegisterReport<A> {
+        signer: A,
         gas: u64,
     }
 
-    impl RegisterReport {
-        pub fn new(signer: Address, gas: u64) -> Self {
+    impl<A> RegisterReport<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
             RegisterReport { signer, gas }
         }
     }
 
-    impl HostInput for RegisterReport {
+    impl<A> HostInput for RegisterReport<A> {
         type EcallInput = input::CallRegisterReport;
-        type HostOutput = host_output::RegisterReport;
+        type HostOutput = host_output::RegisterReport<A>;
 
         fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
             let host_output = host_output::RegisterReport::new(self.signer, self.gas);
@@ -206,20 +273,98 @@ pub mod host_input {
         }
     }
 
-    pub struct Handshake {
-        signer: Address,
+    pub struct RotateIdentityKey<A> {
+        signer: A,
         gas: u64,
     }
 
-    impl Handshake {
-        pub fn new(signer: Address, gas: u64) -> Self {
+    impl<A> RotateIdentityKey<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
+            RotateIdentityKey { signer, gas }
+        }
+    }
+
+    impl<A> HostInput for RotateIdentityKey<A> {
+        type EcallInput = input::CallRotateIdentityKey;
+        type HostOutput = host_output::RotateIdentityKey<A>;
+
+        fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
+            let host_output = host_output::RotateIdentityKey::new(self.signer, self.gas);
+
+            Ok((Self::EcallInput::default(), host_output))
+        }
+    }
+
+    #[derive(Default)]
+    pub struct CommitIdentityKey;
+
+    impl HostInput for CommitIdentityKey {
+        type EcallInput = input::CallCommitIdentityKey;
+        type HostOutput = host_output::CommitIdentityKey;
+
+        fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
+            Ok((Self::EcallInput::default(), Self::HostOutput::default()))
+        }
+    }
+
+    pub struct BindIdentityKey {
+        contract_addr: String,
+    }
+
+    impl BindIdentityKey {
+        pub fn new(contract_addr: String) -> Self {
+            BindIdentityKey { contract_addr }
+        }
+    }
+
+    impl HostInput for BindIdentityKey {
+        type EcallInput = input::CallBindIdentityKey;
+        type HostOutput = host_output::BindIdentityKey;
+
+        fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
+            Ok((
+                Self::EcallInput::new(self.contract_addr),
+                Self::HostOutput::default(),
+            ))
+        }
+    }
+
+    pub struct StateCommitment<A> {
+        signer: A,
+        gas: u64,
+    }
+
+    impl<A> StateCommitment<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
+            StateCommitment { signer, gas }
+        }
+    }
+
+    impl<A> HostInput for StateCommitment<A> {
+        type EcallInput = input::CallStateCommitment;
+        type HostOutput = host_output::StateCommitment<A>;
+
+        fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
+            let host_output = host_output::StateCommitment::new(self.signer, self.gas);
+
+            Ok((Self::EcallInput::default(), host_output))
+        }
+    }
+
+    pub struct Handshake<A> {
+        signer: A,
+        gas: u64,
+    }
+
+    impl<A> Handshake<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
             Handshake { signer, gas }
         }
     }
 
-    impl HostInput for Handshake {
+    impl<A> HostInput for Handshake<A> {
         type EcallInput = input::CallHandshake;
-        type HostOutput = host_output::Handshake;
+        type HostOutput = host_output::Handshake<A>;
 
         fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
             let host_output = host_output::Handshake::new(self.signer, self.gas);
@@ -327,19 +472,43 @@ pub mod host_input {
             Ok((Self::EcallInput::default(), Self::HostOutput::new()))
         }
     }
+
+    #[derive(Default)]
+    pub struct GetAttestationStatus;
+
+    impl HostInput for GetAttestationStatus {
+        type EcallInput = input::GetAttestationStatus;
+        type HostOutput = host_output::ReturnAttestationStatus;
+
+        fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
+            Ok((Self::EcallInput::default(), Self::HostOutput::new()))
+        }
+    }
 }
 
 pub mod host_output {
     use super::*;
 
+    /// The contract verifies only IAS reports, and the enclave returns an empty signature
+    /// for DCAP evidence, so refuse to send a transaction which is certain to revert.
+    fn ensure_verifiable_on_chain(report_sig: &[u8]) -> anyhow::Result<()> {
+        if report_sig.is_empty() {
+            return Err(anyhow::anyhow!(
+                "DCAP is not verifiable on-chain: the contract verifies only IAS reports, \
+                so use EPID attestation to join the group or register reports"
+            ));
+        }
+        Ok(())
+    }
+
     #[derive(Debug)]
-    pub struct Command {
-        pub signer: Address,
+    pub struct Command<A> {
+        pub signer: A,
         pub gas: u64,
         pub ecall_output: Option<output::Command>,
     }
 
-    impl HostOutput for Command {
+    impl<A> HostOutput for Command<A> {
         type EcallOutput = output::Command;
 
         fn set_ecall_output(mut self, output: Self::EcallOutput) -> anyhow::Result<Self> {
@@ -349,8 +518,8 @@ pub mod host_output {
         }
     }
 
-    impl Command {
-        pub fn new(signer: Address, gas: u64) -> Self {
+    impl<A> Command<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
             Command {
                 signer,
                 gas,
@@ -360,24 +529,25 @@ pub mod host_output {
     }
 
     #[derive(Debug, Clone)]
-    pub struct JoinGroup {
-        pub signer: Address,
+    pub struct JoinGroup<A> {
+        pub signer: A,
         pub gas: u64,
         pub ecall_output: Option<output::ReturnJoinGroup>,
     }
 
-    impl HostOutput for JoinGroup {
+    impl<A> HostOutput for JoinGroup<A> {
         type EcallOutput = output::ReturnJoinGroup;
 
         fn set_ecall_output(mut self, output: Self::EcallOutput) -> anyhow::Result<Self> {
+            ensure_verifiable_on_chain(output.report_sig())?;
             self.ecall_output = Some(output);
 
             Ok(self)
         }
     }
 
-    impl JoinGroup {
-        pub fn new(signer: Address, gas: u64) -> Self {
+    impl<A> JoinGroup<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
             JoinGroup {
                 signer,
                 gas,
@@ -387,24 +557,25 @@ pub mod host_output {
     }
 
     #[derive(Debug, Clone)]
-    pub struct RegisterReport {
-        pub signer: Address,
+    pub struct RegisterReport<A> {
+        pub signer: A,
         pub gas: u64,
         pub ecall_output: Option<output::ReturnRegisterReport>,
     }
 
-    impl HostOutput for RegisterReport {
+    impl<A> HostOutput for RegisterReport<A> {
         type EcallOutput = output::ReturnRegisterReport;
 
         fn set_ecall_output(mut self, output: Self::EcallOutput) -> anyhow::Result<Self> {
+            ensure_verifiable_on_chain(output.report_sig())?;
             self.ecall_output = Some(output);
 
             Ok(self)
         }
     }
 
-    impl RegisterReport {
-        pub fn new(signer: Address, gas: u64) -> Self {
+    impl<A> RegisterReport<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
             RegisterReport {
                 signer,
                 gas,
@@ -414,13 +585,96 @@ pub mod host_output {
     }
 
     #[derive(Debug, Clone)]
-    pub struct Handshake {
-        pub signer: Address,
+    pub struct RotateIdentityKey<A> {
+        pub signer: A,
+        pub gas: u64,
+        pub ecall_output: Option<output::ReturnRotateIdentityKey>,
+    }
+
+    impl<A> HostOutput for RotateIdentityKey<A> {
+        type EcallOutput = output::ReturnRotateIdentityKey;
+
+        fn set_ecall_output(mut self, output: Self::EcallOutput) -> anyhow::Result<Self> {
+            ensure_verifiable_on_chain(output.register_report().report_sig())?;
+            self.ecall_output = Some(output);
+
+            Ok(self)
+        }
+    }
+
+    impl<A> RotateIdentityKey<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
+            RotateIdentityKey {
+                signer,
+                gas,
+                ecall_output: None,
+            }
+        }
+    }
+
+    impl<A: Clone> RotateIdentityKey<A> {
+        /// The registration of the new identity key, which is sent before the old key is revoked.
+        pub fn register_report(&self) -> RegisterReport<A> {
+            RegisterReport {
+                signer: self.signer.clone(),
+                gas: self.gas,
+                ecall_output: self
+                    .ecall_output
+                    .as_ref()
+                    .map(|output| output.register_report().clone()),
+            }
+        }
+    }
+
+    #[derive(Default)]
+    pub struct CommitIdentityKey;
+
+    impl HostOutput for CommitIdentityKey {
+        type EcallOutput = output::Empty;
+    }
+
+    #[derive(Default)]
+    pub struct BindIdentityKey;
+
+    impl HostOutput for BindIdentityKey {
+        type EcallOutput = output::Empty;
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct StateCommitment<A> {
+        pub signer: A,
+        pub gas: u64,
+        pub ecall_output: Option<output::ReturnStateCommitment>,
+    }
+
+    impl<A> HostOutput for StateCommitment<A> {
+        type EcallOutput = output::ReturnStateCommitment;
+
+        fn set_ecall_output(mut self, output: Self::EcallOutput) -> anyhow::Result<Self> {
+            self.ecall_output = Some(output);
+
+            Ok(self)
+        }
+    }
+
+    impl<A> StateCommitment<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
+            StateCommitment {
+                signer,
+                gas,
+                ecall_output: None,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Handshake<A> {
+        pub signer: A,
         pub gas: u64,
         pub ecall_output: Option<output::ReturnHandshake>,
     }
 
-    impl HostOutput for Handshake {
+    impl<A> HostOutput for Handshake<A> {
         type EcallOutput = output::ReturnHandshake;
 
         fn set_ecall_output(mut self, output: Self::EcallOutput) -> anyhow::Result<Self> {
@@ -430,8 +684,8 @@ pub mod host_output {
         }
     }
 
-    impl Handshake {
-        pub fn new(signer: Address, gas: u64) -> Self {
+    impl<A> Handshake<A> {
+        pub fn new(signer: A, gas: u64) -> Self {
             Handshake {
                 signer,
                 gas,
@@ -513,4 +767,43 @@ pub mod host_output {
             ReturnEncryptingKey { ecall_output: None }
         }
     }
+
+    pub struct ReturnAttestationStatus {
+        pub ecall_output: Option<output::ReturnAttestationStatus>,
+    }
+
+    impl HostOutput for ReturnAttestationStatus {
+        type EcallOutput = output::ReturnAttestationStatus;
+
+        fn set_ecall_output(mut self, output: Self::EcallOutput) -> anyhow::Result<Self> {
+            self.ecall_output = Some(output);
+
+            Ok(self)
+        }
+    }
+
+    impl ReturnAttestationStatus {
+        pub fn new() -> Self {
+            ReturnAttestationStatus { ecall_output: None }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_refuse_reports_not_verifiable_on_chain() {
+        let epid_report = output::ReturnRegisterReport::new(vec![1], vec![2], 0, 0);
+        assert!(host_output::RegisterReport::new((), 0)
+            .set_ecall_output(epid_report)
+            .is_ok());
+
+        let dcap_report = output::ReturnRegisterReport::new(vec![1], vec![], 0, 0);
+        let err = host_output::RegisterReport::new((), 0)
+            .set_ecall_output(dcap_report)
+            .unwrap_err();
+        assert!(err.to_string().contains("DCAP is not verifiable on-chain"));
+    }
 }
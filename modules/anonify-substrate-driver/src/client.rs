@@ -0,0 +1,436 @@
+use crate::{
+    error::{Result, SubstrateError},
+    ledger::{from_account_id, to_account_id, Substrate},
+    runtime::*,
+    signer::SubstrateSigner,
+    utils::TxConfig,
+};
+use anonify_ledger_driver::{
+    put_blob, workflow::*, BlobHash, ContractInfo, HostError, LedgerDriver, SharedBlobStore,
+};
+use anyhow::anyhow;
+use codec::Decode;
+use futures::lock::Mutex;
+use futures_timer::Delay;
+use log::debug;
+use std::{fmt, sync::Arc};
+use substrate_subxt::{
+    sp_core::{storage::StorageKey, twox_128, H256},
+    Call, Client, ClientBuilder, ExtrinsicSuccess, Raw,
+};
+
+// libsecp256k1 library generates RecoveryId as 0/1.
+// However the pallet accepts 27/28 as `Secp256k1.recover` of the contract does.
+const RECOVERY_ID_OFFSET: u8 = 27;
+const ANONIFY_MODULE: &str = "Anonify";
+
+/// A connection to a node, established on the first request.
+pub struct Connection {
+    node_url: String,
+    client: Mutex<Option<Client<AnonifyRuntime>>>,
+}
+
+impl Connection {
+    pub fn new(node_url: &str) -> Self {
+        Connection {
+            node_url: node_url.to_string(),
+            client: Mutex::new(None),
+        }
+    }
+
+    pub fn node_url(&self) -> &str {
+        &self.node_url
+    }
+
+    pub async fn client(&self) -> Result<Client<AnonifyRuntime>> {
+        let mut client = self.client.lock().await;
+        if client.is_none() {
+            *client = Some(
+                ClientBuilder::<AnonifyRuntime>::new()
+                    .set_url(self.node_url.as_str())
+                    .build()
+                    .await?,
+            );
+        }
+
+        Ok(client.as_ref().expect("connected above").clone())
+    }
+
+    /// The node holds no keys of the host, so an account is only known from the configured signer.
+    pub fn get_account(&self, index: usize) -> Result<H256> {
+        Err(SubstrateError::SignerNotSet(index))
+    }
+
+    pub async fn block_number(&self) -> Result<u64> {
+        let header = self
+            .client()
+            .await?
+            .header::<H256>(None)
+            .await?
+            .ok_or_else(|| anyhow!("Not found the best block"))?;
+        Ok(header.number as u64)
+    }
+
+    /// The hash of the canonical block at the number, or `None` if it has not been built yet.
+    pub async fn get_block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+        self.client()
+            .await?
+            .block_hash(Some((block_num as u32).into()))
+            .await
+            .map_err(Into::into)
+    }
+
+    /// The events emitted by the pallet in the block, with their indices in the block.
+    pub async fn get_events(&self, block_hash: H256) -> Result<Vec<(u32, Raw)>> {
+        let client = self.client().await?;
+        let key = StorageKey([twox_128(b"System"), twox_128(b"Events")].concat());
+        // The raw value is requested because the records are decoded with the metadata.
+        let data = client
+            .query_storage(vec![key], block_hash, Some(block_hash))
+            .await?
+            .into_iter()
+            .flat_map(|change_set| change_set.changes)
+            .find_map(|(_, data)| data);
+        let data = match data {
+            Some(data) => data.0,
+            None => return Ok(vec![]),
+        };
+
+        let events = client
+            .events_decoder()
+            .decode_events(&mut &data[..])?
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, (_, raw))| match &raw {
+                Raw::Event(event) if event.module == ANONIFY_MODULE => Some((i as u32, raw)),
+                _ => None,
+            })
+            .collect();
+
+        Ok(events)
+    }
+
+    /// Submit the call and wait for it to be included and confirmed.
+    async fn submit<C: Call<AnonifyRuntime> + Send + Sync>(
+        &self,
+        call: C,
+        signer: &SubstrateSigner,
+        config: &TxConfig,
+    ) -> Result<ExtrinsicSuccess<AnonifyRuntime>> {
+        let client = self.client().await?;
+        let success = client.watch(call, &signer.pair_signer()).await?;
+
+        let included = client
+            .header(Some(success.block))
+            .await?
+            .ok_or_else(|| anyhow!("Not found the block: {:?}", success.block))?
+            .number as u64;
+        let confirmed = included + config.confirmations().saturating_sub(1);
+        while self.block_number().await? < confirmed {
+            Delay::new(config.poll_interval()).await;
+        }
+        debug!(
+            "Extrinsic {:?} is confirmed in {}",
+            success.extrinsic, included
+        );
+
+        Ok(success)
+    }
+
+    /// Create a group with the report of the enclave, and return its address.
+    pub async fn deploy(
+        &self,
+        output: host_output::JoinGroup<H256>,
+        config: TxConfig,
+        signer: Option<SubstrateSigner>,
+    ) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let call = DeployCall::<AnonifyRuntime> {
+            report: ecall_output.report().to_vec(),
+            report_sig: ecall_output.report_sig().to_vec(),
+            handshake: ecall_output.handshake().to_vec(),
+            mrenclave_ver: ecall_output.mrenclave_ver(),
+            _runtime: Default::default(),
+        };
+
+        let signer = signer_for(signer.as_ref(), output.signer)?;
+        let deployed = self
+            .submit(call, &signer, &config)
+            .await?
+            .find_event::<DeployedEvent<AnonifyRuntime>>()?
+            .ok_or(SubstrateError::EventNotFound("Deployed"))?;
+
+        Ok(from_account_id(&deployed.group_id))
+    }
+}
+
+impl fmt::Debug for Connection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Connection")
+            .field("node_url", &self.node_url)
+            .finish()
+    }
+}
+
+/// The pallet calls of a group.
+#[derive(Debug, Clone)]
+pub struct GroupClient {
+    conn: Arc<Connection>,
+    group_id: H256,
+    config: TxConfig,
+    signer: Option<SubstrateSigner>,
+    blob_store: Option<SharedBlobStore>,
+}
+
+impl GroupClient {
+    pub fn new<P: AsRef<std::path::Path>>(
+        conn: Arc<Connection>,
+        contract_info: ContractInfo<'_, P>,
+    ) -> Result<Self> {
+        let group_id = contract_info.address::<Substrate>()?;
+
+        Ok(GroupClient {
+            conn,
+            group_id,
+            config: TxConfig::default(),
+            signer: None,
+            blob_store: None,
+        })
+    }
+
+    pub fn set_tx_config(mut self, config: TxConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn set_signer(mut self, signer: Option<SubstrateSigner>) -> Self {
+        self.signer = signer;
+        self
+    }
+
+    pub fn set_blob_store(mut self, blob_store: SharedBlobStore) -> Self {
+        self.blob_store = Some(blob_store);
+        self
+    }
+
+    pub async fn send_report_handshake(
+        &self,
+        output: host_output::JoinGroup<H256>,
+        method: &str,
+    ) -> Result<H256> {
+        // pallet-anonify never moves a group to a new enclave, neither by the owner nor by the members.
+        if let "updateMrenclave" | "proposeMrenclave" = method {
+            return Err(HostError::Unsupported("the mrenclave governance").into());
+        }
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let group_id = to_account_id(&self.group_id);
+        let report = ecall_output.report().to_vec();
+        let report_sig = ecall_output.report_sig().to_vec();
+        let handshake = ecall_output.handshake().to_vec();
+
+        match method {
+            "joinGroup" => {
+                let call = JoinGroupCall::<AnonifyRuntime> {
+                    group_id,
+                    report,
+                    report_sig,
+                    handshake,
+                    version: ecall_output.mrenclave_ver(),
+                    roster_idx: ecall_output.roster_idx(),
+                };
+                self.send(call, output.signer).await
+            }
+            _ => Err(anyhow!("Unknown method: {}", method).into()),
+        }
+    }
+
+    pub async fn register_report(&self, output: host_output::RegisterReport<H256>) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let call = RegisterReportCall::<AnonifyRuntime> {
+            group_id: to_account_id(&self.group_id),
+            report: ecall_output.report().to_vec(),
+            report_sig: ecall_output.report_sig().to_vec(),
+            version: ecall_output.mrenclave_ver(),
+            roster_idx: ecall_output.roster_idx(),
+        };
+
+        self.send(call, output.signer).await
+    }
+
+    /// Revoke the old identity key in favor of the rotated one, which must have been registered.
+    pub async fn revoke_keys(&self, output: host_output::RotateIdentityKey<H256>) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let group_id = to_account_id(&self.group_id);
+        let mut enclave_sig = ecall_output.encode_revocation_sig().to_vec();
+        enclave_sig.push(ecall_output.encode_recovery_id() + RECOVERY_ID_OFFSET);
+        let call = RevokeKeysCall::<AnonifyRuntime> {
+            group_id,
+            verifying_key: *ecall_output.old_verifying_key(),
+            encrypting_key: ecall_output.old_encrypting_key().to_vec(),
+            new_verifying_key: *ecall_output.new_verifying_key(),
+            enclave_sig,
+        };
+        self.send(call, output.signer).await
+    }
+
+    pub async fn send_command(&self, output: host_output::Command<H256>) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let ciphertext = ecall_output.encode_ciphertext();
+        let mut enclave_sig = ecall_output.encode_enclave_sig().to_vec();
+        enclave_sig.push(ecall_output.encode_recovery_id() + RECOVERY_ID_OFFSET);
+        let group_id = to_account_id(&self.group_id);
+
+        match &self.blob_store {
+            Some(blob_store) => {
+                let call = StoreCommandHashCall::<AnonifyRuntime> {
+                    group_id,
+                    ciphertext_hash: put_blob(blob_store, ciphertext).await?,
+                    enclave_sig,
+                };
+                self.send(call, output.signer).await
+            }
+            None => {
+                let call = StoreCommandCall::<AnonifyRuntime> {
+                    group_id,
+                    ciphertext,
+                    enclave_sig,
+                };
+                self.send(call, output.signer).await
+            }
+        }
+    }
+
+    pub async fn handshake(&self, output: host_output::Handshake<H256>) -> Result<H256> {
+        let ecall_output = output
+            .ecall_output
+            .ok_or_else(|| HostError::EcallOutputNotSet)?;
+        let mut enclave_sig = ecall_output.encode_enclave_sig().to_vec();
+        enclave_sig.push(ecall_output.encode_recovery_id() + RECOVERY_ID_OFFSET);
+        let call = HandshakeCall::<AnonifyRuntime> {
+            group_id: to_account_id(&self.group_id),
+            handshake: ecall_output.encode_handshake(),
+            enclave_sig,
+            roster_idx: ecall_output.roster_idx(),
+        };
+
+        self.send(call, output.signer).await
+    }
+
+    /// The ciphertexts and handshakes stored in the group in the block, in the emitted order.
+    pub async fn get_payload_events(&self, block_hash: H256) -> Result<Vec<GroupEvent>> {
+        let mut events = vec![];
+        for (index, raw) in self.conn.get_events(block_hash).await? {
+            let event = match raw {
+                Raw::Event(event) => event,
+                Raw::Error(_) => continue,
+            };
+            let (group_id, payload) = match event.variant.as_str() {
+                "StoreCiphertext" => {
+                    let e = StoreCiphertextEvent::<AnonifyRuntime>::decode(&mut &event.data[..])?;
+                    (e.group_id, GroupPayload::Ciphertext(e.ciphertext))
+                }
+                "StoreCiphertextHash" => {
+                    let e =
+                        StoreCiphertextHashEvent::<AnonifyRuntime>::decode(&mut &event.data[..])?;
+                    (e.group_id, GroupPayload::CiphertextHash(e.ciphertext_hash))
+                }
+                "StoreHandshake" => {
+                    let e = StoreHandshakeEvent::<AnonifyRuntime>::decode(&mut &event.data[..])?;
+                    (e.group_id, GroupPayload::Handshake(e.handshake))
+                }
+                _ => continue,
+            };
+            if from_account_id(&group_id) == self.group_id {
+                events.push(GroupEvent { index, payload });
+            }
+        }
+
+        Ok(events)
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.conn
+    }
+
+    pub fn group_id(&self) -> H256 {
+        self.group_id
+    }
+
+    async fn send<C: Call<AnonifyRuntime> + Send + Sync>(
+        &self,
+        call: C,
+        sender: H256,
+    ) -> Result<H256> {
+        let signer = signer_for(self.signer.as_ref(), sender)?;
+        let success = self.conn.submit(call, &signer, &self.config).await?;
+        Ok(success.extrinsic)
+    }
+}
+
+/// An event of the group, indexed by its position in the block.
+#[derive(Debug, Clone)]
+pub struct GroupEvent {
+    pub index: u32,
+    pub payload: GroupPayload,
+}
+
+#[derive(Debug, Clone)]
+pub enum GroupPayload {
+    Ciphertext(Vec<u8>),
+    /// The hash of a ciphertext kept in the blob store
+    CiphertextHash(BlobHash),
+    Handshake(Vec<u8>),
+}
+
+/// The key signing for the account, which must be the configured signer.
+fn signer_for(signer: Option<&SubstrateSigner>, account: H256) -> Result<SubstrateSigner> {
+    signer
+        .filter(|signer| Substrate::signer_address(signer) == account)
+        .cloned()
+        .ok_or_else(|| SubstrateError::UnknownAccount(Substrate::format_address(&account)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_keyring::AccountKeyring;
+
+    #[test]
+    fn test_signer_for() {
+        let alice = SubstrateSigner::from_keyring(AccountKeyring::Alice);
+        let alice_addr = Substrate::signer_address(&alice);
+        let bob_addr = from_account_id(&AccountKeyring::Bob.to_account_id());
+
+        let signer = signer_for(Some(&alice), alice_addr).unwrap();
+        assert_eq!(Substrate::signer_address(&signer), alice_addr);
+
+        // The development accounts are not used unless they are configured as the signer.
+        assert!(matches!(
+            signer_for(Some(&alice), bob_addr),
+            Err(SubstrateError::UnknownAccount(_))
+        ));
+        assert!(matches!(
+            signer_for(None, alice_addr),
+            Err(SubstrateError::UnknownAccount(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_account_without_signer() {
+        let conn = Connection::new("ws://localhost:9944");
+        assert!(matches!(
+            conn.get_account(0),
+            Err(SubstrateError::SignerNotSet(0))
+        ));
+    }
+}
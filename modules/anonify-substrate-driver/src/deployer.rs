@@ -0,0 +1,105 @@
+use crate::{
+    client::{Connection, GroupClient},
+    ledger::Substrate,
+    signer::SubstrateSigner,
+    utils::TxConfig,
+};
+use anonify_ledger_driver::{traits::*, workflow::*, ContractInfo, LedgerDriver, Result};
+use async_trait::async_trait;
+use sgx_types::sgx_enclave_id_t;
+use std::{marker::Send, path::Path, sync::Arc};
+use substrate_subxt::sp_core::H256;
+
+/// Components needed to create a group in the pallet
+#[derive(Debug)]
+pub struct SubstrateDeployer {
+    enclave_id: sgx_enclave_id_t,
+    conn: Arc<Connection>,
+    group_id: Option<H256>,
+}
+
+#[async_trait]
+impl Deployer for SubstrateDeployer {
+    type Ledger = Substrate;
+    type Contract = GroupClient;
+
+    fn new(enclave_id: sgx_enclave_id_t, node_url: &str) -> Result<Self> {
+        Ok(SubstrateDeployer {
+            enclave_id,
+            conn: Arc::new(Connection::new(node_url)),
+            group_id: None,
+        })
+    }
+
+    /// The accounts are only known from the signer, which the dispatcher resolves before calling this.
+    async fn get_account(&self, index: usize, _password: &str) -> Result<H256> {
+        self.conn.get_account(index).map_err(Into::into)
+    }
+
+    /// The pallet is a part of the runtime, so the ABI and the bytecode are not used.
+    async fn deploy<P: AsRef<Path> + Send>(
+        &mut self,
+        host_output: host_output::JoinGroup<H256>,
+        _abi_path: P,
+        _bin_path: P,
+        confirmations: u64,
+        config: TxConfig,
+        signer: Option<SubstrateSigner>,
+    ) -> Result<String> {
+        let config = config.set_confirmations(confirmations);
+        let group_id = self.conn.deploy(host_output, config, signer).await?;
+        self.group_id = Some(group_id);
+
+        Ok(Substrate::format_address(&group_id))
+    }
+
+    fn get_contract<P: AsRef<Path>>(self, abi_path: P) -> Result<GroupClient> {
+        let addr =
+            Substrate::format_address(&self.group_id.expect("The group hasn't be deployed yet."));
+        let contract_info = ContractInfo::new(abi_path, &addr);
+        GroupClient::new(self.conn, contract_info).map_err(Into::into)
+    }
+
+    fn get_enclave_id(&self) -> sgx_enclave_id_t {
+        self.enclave_id
+    }
+
+    fn get_node_url(&self) -> &str {
+        self.conn.node_url()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anonify_ledger_driver::HostError;
+    use futures::executor::block_on;
+    use sp_keyring::AccountKeyring;
+
+    const NODE_URL: &str = "ws://localhost:9944";
+
+    #[test]
+    fn test_get_account_without_signer() {
+        let deployer = SubstrateDeployer::new(0, NODE_URL).unwrap();
+        assert_eq!(deployer.get_node_url(), NODE_URL);
+        assert!(matches!(
+            block_on(deployer.get_account(0, "")),
+            Err(HostError::LedgerError(_))
+        ));
+    }
+
+    #[test]
+    fn test_deploy_without_ecall_output() {
+        let mut deployer = SubstrateDeployer::new(0, NODE_URL).unwrap();
+        let signer = SubstrateSigner::from_keyring(AccountKeyring::Alice);
+        let output = host_output::JoinGroup {
+            signer: Substrate::signer_address(&signer),
+            gas: 0,
+            ecall_output: None,
+        };
+
+        let res = block_on(deployer.deploy(output, "", "", 1, TxConfig::default(), Some(signer)));
+        assert!(matches!(res, Err(HostError::EcallOutputNotSet)));
+        assert!(deployer.group_id.is_none());
+    }
+}
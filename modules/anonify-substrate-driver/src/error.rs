@@ -0,0 +1,31 @@
+use anonify_ledger_driver::HostError;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, SubstrateError>;
+
+#[derive(Error, Debug)]
+pub enum SubstrateError {
+    #[error("Error: {0}")]
+    Error(#[from] anyhow::Error),
+    #[error("Host error: {0}")]
+    HostError(#[from] HostError),
+    #[error("No signer is set for the account at index {0}")]
+    SignerNotSet(usize),
+    #[error("No key is available to sign for the account: {0}")]
+    UnknownAccount(String),
+    #[error("The extrinsic has been included without the expected event: {0:?}")]
+    EventNotFound(&'static str),
+    #[error("Subxt error: {0}")]
+    SubxtError(#[from] substrate_subxt::Error),
+    #[error("Codec error: {0}")]
+    CodecError(#[from] codec::Error),
+}
+
+impl From<SubstrateError> for HostError {
+    fn from(err: SubstrateError) -> Self {
+        match err {
+            SubstrateError::HostError(err) => err,
+            err => HostError::LedgerError(Box::new(err)),
+        }
+    }
+}
@@ -0,0 +1,647 @@
+use crate::{
+    client::{Connection, GroupClient, GroupEvent, GroupPayload},
+    error::Result,
+    ledger::Substrate,
+    EventCache,
+};
+use anonify_ledger_driver::{
+    fetch_blob, traits::*, workflow::*, ContractInfo, DeadLetter, HostError, Payload, PayloadType,
+    SkipReason, WatcherConfig, MAX_BLOB_TRIALS, MAX_TRIALS_NUM,
+};
+use anyhow::anyhow;
+use async_trait::async_trait;
+use codec::Decode;
+use frame_common::{
+    crypto::{Ciphertext, ExportHandshake},
+    state_types::UpdatedState,
+    traits::*,
+};
+use frame_host::engine::HostEngine;
+use log::{debug, error, info, warn};
+use sgx_types::sgx_enclave_id_t;
+use std::{path::Path, sync::Arc};
+use substrate_subxt::sp_core::H256;
+
+// The maximum number of blocks whose events are fetched at once
+const BLOCK_LIMIT: u64 = 100;
+
+/// Components needed to watch events.
+/// The events are fetched by polling the blocks, so the WebSocket URL of the config is not used.
+#[derive(Debug)]
+pub struct EventWatcher {
+    client: GroupClient,
+    cache: EventCache,
+    config: WatcherConfig,
+}
+
+#[async_trait]
+impl Watcher for EventWatcher {
+    type Ledger = Substrate;
+    type Contract = GroupClient;
+
+    fn new<P: AsRef<Path>>(
+        node_url: &str,
+        contract_info: ContractInfo<'_, P>,
+        cache: EventCache,
+        config: WatcherConfig,
+    ) -> anonify_ledger_driver::Result<Self> {
+        let conn = Arc::new(Connection::new(node_url));
+        let client = GroupClient::new(conn, contract_info)?;
+
+        Ok(EventWatcher {
+            client,
+            cache,
+            config,
+        })
+    }
+
+    /// Fetch the events of the group in the blocks with the configured confirmations
+    /// since the last fetched one, and insert them into the enclave.
+    /// The events which fail in the enclave are skipped as the Ethereum backend does,
+    /// and the blocks before the last fetched one are fetched again if it has been reorganized.
+    async fn fetch_events<S: State>(
+        &self,
+        eid: sgx_enclave_id_t,
+    ) -> anonify_ledger_driver::Result<Option<Vec<UpdatedState<S>>>> {
+        let enclave_log = match self.fetch_enclave_log(&self.client).await? {
+            Some(enclave_log) => enclave_log,
+            None => return Ok(None),
+        };
+        let updated_states = insert_enclave(eid, enclave_log.payloads, &self.cache);
+        self.save_scanned_block(enclave_log.scanned_block)?;
+
+        Ok(updated_states)
+    }
+
+    async fn latest_block_num(&self) -> anonify_ledger_driver::Result<u64> {
+        self.client
+            .connection()
+            .block_number()
+            .await
+            .map_err(Into::into)
+    }
+
+    fn scanned_block_num(&self) -> Option<u64> {
+        self.cache
+            .inner()
+            .read()
+            .get_scanned_block(self.client.group_id())
+            .map(|(block_num, _)| block_num)
+    }
+
+    fn get_contract(self) -> GroupClient {
+        self.client
+    }
+}
+
+impl EventWatcher {
+    /// Fetch the payloads of the group in the blocks with the configured confirmations
+    /// since the last fetched one, in the order to be inserted into the enclave.
+    /// It is `None` if no block has been confirmed, or if the blocks have been reorganized while fetching them.
+    async fn fetch_enclave_log<E: EventSource>(
+        &self,
+        source: &E,
+    ) -> anonify_ledger_driver::Result<Option<EnclaveLog>> {
+        let group_id = self.client.group_id();
+        let (mut next_block_num, scanned_block) = {
+            let cache = self.cache.inner().read();
+            (
+                cache.get_latest_block_num(group_id).unwrap_or_default(),
+                cache.get_scanned_block(group_id),
+            )
+        };
+
+        if let Some((block_num, block_hash)) = scanned_block {
+            if source.get_block_hash(block_num).await? != Some(block_hash) {
+                next_block_num = self
+                    .cache
+                    .inner()
+                    .write()
+                    .rewind_on_reorg(group_id, block_num);
+            }
+        }
+
+        let latest_block_num = source.block_number().await?;
+        let confirmed_block_num = match latest_block_num.checked_sub(self.config.confirmations()) {
+            Some(num) if num >= next_block_num => num.min(next_block_num + BLOCK_LIMIT - 1),
+            _ => return Ok(None),
+        };
+        let confirmed_block_hash = block_hash(source, confirmed_block_num).await?;
+
+        let mut payloads = vec![];
+        let mut processed_logs = vec![];
+        let mut dead_letters = vec![];
+        let mut missing = None;
+        for block_num in next_block_num..=confirmed_block_num {
+            let hash = block_hash(source, block_num).await?;
+            for event in source.get_payload_events(hash).await? {
+                let log_id = (hash, event.index);
+                if self
+                    .cache
+                    .inner()
+                    .read()
+                    .is_processed_log(group_id, block_num, &log_id)
+                {
+                    debug!("Skipped an event already inserted: {:?}", log_id);
+                    continue;
+                }
+                processed_logs.push((block_num, log_id));
+
+                info!(
+                    "Inserting enclave event: block: {}, index: {}",
+                    block_num, event.index
+                );
+                let payload = match self.fetch_blob(event.payload).await {
+                    Ok(payload) => payload,
+                    // Every missing blob is counted before the blocks are fetched again.
+                    Err(e @ HostError::BlobNotFound(_)) => {
+                        missing = missing.or(Some(e));
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                let raw_log = raw_payload(&payload);
+                match into_payload(payload) {
+                    Ok(payload) => {
+                        payloads.push(payload.set_position(block_num, u64::from(event.index)))
+                    }
+                    Err(e) => dead_letters.push(
+                        DeadLetter::new(SkipReason::Decode(e.to_string()))
+                            .set_block_num(block_num)
+                            .set_raw_log(raw_log),
+                    ),
+                }
+            }
+        }
+
+        if let Some(e) = missing {
+            return Err(e);
+        }
+        // Fetch the events again on the next call if they have been reorganized while fetching them.
+        if source.get_block_hash(confirmed_block_num).await? != Some(confirmed_block_hash) {
+            warn!(
+                "The block {} has been reorganized while fetching its events",
+                confirmed_block_num
+            );
+            return Ok(None);
+        }
+
+        // Inserted in the chain order, so that the states after the same event are identical on every node
+        // however the events are split into fetches.
+        payloads.sort_by_key(|payload| payload.position());
+        let immutable_payloads = payloads.clone();
+        let payloads = {
+            let mut mut_cache = self.cache.inner().write();
+            // The events are recorded only once all of them have been fetched,
+            // so that they are fetched again if this fetch fails.
+            for (block_num, log_id) in processed_logs {
+                mut_cache.insert_processed_log(group_id, block_num, log_id);
+            }
+            for letter in dead_letters {
+                mut_cache.insert_dead_letter(letter);
+            }
+            mut_cache.ensure_order_guarantee(payloads, immutable_payloads, MAX_TRIALS_NUM)
+        };
+
+        Ok(Some(EnclaveLog {
+            payloads,
+            scanned_block: (confirmed_block_num, confirmed_block_hash),
+        }))
+    }
+
+    /// Fetch the blocks after the scanned one next time, and persist the cursor if the cache has a checkpoint directory.
+    fn save_scanned_block(
+        &self,
+        (block_num, block_hash): (u64, H256),
+    ) -> anonify_ledger_driver::Result<()> {
+        let group_id = self.client.group_id();
+        let mut w = self.cache.inner().write();
+        w.insert_next_block_num(group_id, block_num + 1);
+        w.insert_scanned_block(group_id, (block_num, block_hash));
+        w.save_checkpoint(group_id)?;
+
+        Ok(())
+    }
+
+    /// Replace the hash of a ciphertext with the ciphertext fetched from the blob store.
+    /// It fails if the ciphertext has not been stored yet, so that the blocks are fetched again later,
+    /// but once it has been fetched `MAX_BLOB_TRIALS` times the hash is left to be skipped as a dead letter.
+    /// A blob which does not match the hash is never inserted into the enclave.
+    async fn fetch_blob(
+        &self,
+        payload: GroupPayload,
+    ) -> anonify_ledger_driver::Result<GroupPayload> {
+        let hash = match payload {
+            GroupPayload::CiphertextHash(hash) => hash,
+            payload => return Ok(payload),
+        };
+        let blob_store = self.config.blob_store().ok_or(HostError::BlobStoreNotSet)?;
+
+        match fetch_blob(blob_store, &hash).await {
+            Ok(blob) => {
+                self.cache.inner().write().remove_missing_blob(&hash);
+                return Ok(GroupPayload::Ciphertext(blob));
+            }
+            Err(HostError::BlobMismatch(e)) => {
+                error!("The blob does not match the hash in the event: {}", e)
+            }
+            Err(HostError::BlobNotFound(e)) => {
+                let retry = self
+                    .cache
+                    .inner()
+                    .write()
+                    .retry_missing_blob(hash, MAX_BLOB_TRIALS);
+                if retry {
+                    return Err(HostError::BlobNotFound(e));
+                }
+                error!(
+                    "The blob has not been stored after {} trials, so its event is skipped: {}",
+                    MAX_BLOB_TRIALS, e
+                );
+            }
+            Err(e) => return Err(e),
+        }
+
+        Ok(GroupPayload::CiphertextHash(hash))
+    }
+}
+
+/// The payloads fetched from the blocks up to the scanned one
+#[derive(Debug)]
+struct EnclaveLog {
+    payloads: Vec<PayloadType>,
+    scanned_block: (u64, H256),
+}
+
+/// The blocks and the events of the group which the watcher fetches, which are served by the node
+/// but can be replaced in tests.
+#[async_trait]
+trait EventSource: Send + Sync {
+    async fn block_number(&self) -> Result<u64>;
+
+    async fn get_block_hash(&self, block_num: u64) -> Result<Option<H256>>;
+
+    async fn get_payload_events(&self, block_hash: H256) -> Result<Vec<GroupEvent>>;
+}
+
+#[async_trait]
+impl EventSource for GroupClient {
+    async fn block_number(&self) -> Result<u64> {
+        self.connection().block_number().await
+    }
+
+    async fn get_block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+        self.connection().get_block_hash(block_num).await
+    }
+
+    async fn get_payload_events(&self, block_hash: H256) -> Result<Vec<GroupEvent>> {
+        GroupClient::get_payload_events(self, block_hash).await
+    }
+}
+
+async fn block_hash<E: EventSource>(source: &E, block_num: u64) -> Result<H256> {
+    source
+        .get_block_hash(block_num)
+        .await?
+        .ok_or_else(|| anyhow!("Not found block: {}", block_num).into())
+}
+
+/// The data of the event, which is kept in the dead letter if it cannot be decoded
+fn raw_payload(payload: &GroupPayload) -> Vec<u8> {
+    match payload {
+        GroupPayload::Ciphertext(data) | GroupPayload::Handshake(data) => data.clone(),
+        GroupPayload::CiphertextHash(hash) => hash.to_vec(),
+    }
+}
+
+fn into_payload(payload: GroupPayload) -> Result<PayloadType> {
+    match payload {
+        GroupPayload::CiphertextHash(hash) => {
+            Err(anyhow!("The blob has not been fetched: {}", hex::encode(hash)).into())
+        }
+        GroupPayload::Ciphertext(data) => {
+            let ciphertext = Ciphertext::decode(&mut &data[..])?;
+            Ok(PayloadType::new(
+                ciphertext.roster_idx(),
+                ciphertext.epoch(),
+                ciphertext.generation(),
+                Payload::Ciphertext(ciphertext),
+            ))
+        }
+        GroupPayload::Handshake(data) => {
+            let handshake = ExportHandshake::decode(&mut &data[..])?;
+            Ok(PayloadType::new(
+                handshake.roster_idx(),
+                handshake.prior_epoch(),
+                u32::MAX, // handshake is the last of the generation
+                Payload::Handshake(handshake),
+            ))
+        }
+    }
+}
+
+/// Insert the payloads into the enclave in order.
+/// Even if an error occurs in the enclave, it is unlikely that retrying will succeed,
+/// so the payload is skipped and kept in the cache as a dead letter.
+fn insert_enclave<S: State>(
+    eid: sgx_enclave_id_t,
+    payloads: Vec<PayloadType>,
+    cache: &EventCache,
+) -> Option<Vec<UpdatedState<S>>> {
+    if payloads.is_empty() {
+        debug!("No events to insert into the enclave.");
+        return None;
+    }
+
+    let mut acc = vec![];
+    for payload in payloads {
+        match payload.clone().into_payload() {
+            Payload::Ciphertext(ciphertext) => {
+                let input = host_input::InsertCiphertext::new(ciphertext);
+                let output = InsertCiphertextWorkflow::exec(input, eid)
+                    .map_err(Into::into)
+                    .and_then(|e| e.ecall_output.ok_or_else(|| HostError::EcallOutputNotSet));
+                match output {
+                    Ok(update) => {
+                        if let Some(upd_type) = update.updated_state {
+                            match UpdatedState::<S>::from_state_type(upd_type) {
+                                Ok(upd_trait) => acc.push(upd_trait),
+                                // The state has been updated in the enclave,
+                                // so the payload is not replayable.
+                                Err(err) => {
+                                    let mut letter = DeadLetter::new(SkipReason::StateTransition(
+                                        err.to_string(),
+                                    ))
+                                    .set_roster_idx(payload.roster_idx());
+                                    if let Some(block_num) = payload.block_num() {
+                                        letter = letter.set_block_num(block_num);
+                                    }
+                                    cache.inner().write().insert_dead_letter(letter);
+                                }
+                            }
+                        }
+                    }
+                    Err(err) => {
+                        error!(
+                            "Error in enclave (InsertCiphertextWorkflow::exec): {:?}",
+                            err
+                        );
+                        cache.inner().write().insert_dead_letter(
+                            DeadLetter::new(SkipReason::Enclave(err.to_string()))
+                                .set_payload(payload),
+                        );
+                    }
+                }
+            }
+            Payload::Handshake(handshake) => {
+                let input = host_input::InsertHandshake::new(handshake);
+                if let Err(e) = InsertHandshakeWorkflow::exec(input, eid) {
+                    error!("Error in enclave (InsertHandshakeWorkflow::exec): {:?}", e);
+                    cache.inner().write().insert_dead_letter(
+                        DeadLetter::new(SkipReason::Enclave(e.to_string())).set_payload(payload),
+                    );
+                }
+            }
+        }
+    }
+
+    if acc.is_empty() {
+        None
+    } else {
+        Some(acc)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anonify_ledger_driver::{blob_hash, put_blob, FsBlobStore, LedgerDriver, SharedBlobStore};
+    use codec::Encode;
+    use futures::executor::block_on;
+    use pallet_anonify::{
+        mock::{deploy, new_test_ext, Anonify, Enclave, Origin, System, TestEvent},
+        RawEvent,
+    };
+
+    fn watcher(config: WatcherConfig) -> EventWatcher {
+        let group_id = Substrate::format_address(&H256::repeat_byte(1));
+        let contract_info = ContractInfo::new("", group_id.as_str());
+        EventWatcher::new(
+            "ws://localhost:9944",
+            contract_info,
+            EventCache::default(),
+            config,
+        )
+        .unwrap()
+    }
+
+    /// The blocks built by calling the pallet on its mock runtime
+    #[derive(Default)]
+    struct MockChain {
+        blocks: Vec<(H256, Vec<GroupEvent>)>,
+    }
+
+    impl MockChain {
+        /// Build a block on top of the chain by the calls, keeping the events of the group.
+        /// The hash of the block is derived from the fork as well as the number,
+        /// so that a reorganized block differs from the one it replaces.
+        fn build_block<F: FnOnce()>(&mut self, group_id: u64, fork: u8, calls: F) {
+            let block_num = self.blocks.len() as u64;
+            System::reset_events();
+            System::set_block_number(block_num);
+            calls();
+
+            let events = System::events()
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, record)| {
+                    let payload = match record.event {
+                        TestEvent::anonify(RawEvent::StoreCiphertext(id, ciphertext))
+                            if id == group_id =>
+                        {
+                            GroupPayload::Ciphertext(ciphertext)
+                        }
+                        TestEvent::anonify(RawEvent::StoreCiphertextHash(id, hash))
+                            if id == group_id =>
+                        {
+                            GroupPayload::CiphertextHash(hash)
+                        }
+                        TestEvent::anonify(RawEvent::StoreHandshake(id, handshake))
+                            if id == group_id =>
+                        {
+                            GroupPayload::Handshake(handshake)
+                        }
+                        _ => return None,
+                    };
+                    Some(GroupEvent {
+                        index: index as u32,
+                        payload,
+                    })
+                })
+                .collect();
+            let mut hash = H256::from_low_u64_be(block_num);
+            hash.0[0] = fork;
+            self.blocks.push((hash, events));
+        }
+    }
+
+    #[async_trait]
+    impl EventSource for MockChain {
+        async fn block_number(&self) -> Result<u64> {
+            Ok(self.blocks.len() as u64 - 1)
+        }
+
+        async fn get_block_hash(&self, block_num: u64) -> Result<Option<H256>> {
+            Ok(self.blocks.get(block_num as usize).map(|(hash, _)| *hash))
+        }
+
+        async fn get_payload_events(&self, block_hash: H256) -> Result<Vec<GroupEvent>> {
+            self.blocks
+                .iter()
+                .find(|(hash, _)| *hash == block_hash)
+                .map(|(_, events)| events.clone())
+                .ok_or_else(|| anyhow!("Not found block: {:?}", block_hash).into())
+        }
+    }
+
+    /// The position in the group and the block of each payload
+    fn positions(enclave_log: &EnclaveLog) -> Vec<(u32, u32, u32, Option<u64>)> {
+        enclave_log
+            .payloads
+            .iter()
+            .map(|p| (p.roster_idx(), p.epoch(), p.generation(), p.block_num()))
+            .collect()
+    }
+
+    #[test]
+    fn test_fetch_enclave_log() {
+        new_test_ext().execute_with(|| {
+            let enclave = Enclave::new(1);
+            let group_id = Anonify::group_id(&1, 0);
+            let store_command = |ciphertext: Vec<u8>| {
+                let sig = enclave.sign(&ciphertext);
+                Anonify::store_command(Origin::signed(1), group_id, ciphertext, sig).unwrap();
+            };
+            let mut chain = MockChain::default();
+            // The events of the genesis block are not recorded.
+            chain.build_block(group_id, 0, || assert_eq!(deploy(&enclave), group_id));
+            chain.build_block(group_id, 0, || {
+                store_command(Ciphertext::new(1, 0, 0, vec![1]).encode())
+            });
+            chain.build_block(group_id, 0, || {
+                store_command(Ciphertext::new(2, 0, 0, vec![2]).encode());
+                store_command(vec![0xff]);
+            });
+
+            let watcher = watcher(WatcherConfig::new().set_confirmations(1));
+            let enclave_log = block_on(watcher.fetch_enclave_log(&chain))
+                .unwrap()
+                .unwrap();
+            assert_eq!(positions(&enclave_log), vec![(0, 0, 1, Some(1))]);
+            assert_eq!(enclave_log.scanned_block, (1, chain.blocks[1].0));
+            watcher
+                .save_scanned_block(enclave_log.scanned_block)
+                .unwrap();
+            assert_eq!(watcher.scanned_block_num(), Some(1));
+            // The last block is not confirmed yet.
+            assert!(block_on(watcher.fetch_enclave_log(&chain))
+                .unwrap()
+                .is_none());
+
+            // The undecodable ciphertext is skipped as a dead letter.
+            chain.build_block(group_id, 0, || {});
+            let enclave_log = block_on(watcher.fetch_enclave_log(&chain))
+                .unwrap()
+                .unwrap();
+            assert_eq!(positions(&enclave_log), vec![(0, 0, 2, Some(2))]);
+            watcher
+                .save_scanned_block(enclave_log.scanned_block)
+                .unwrap();
+            let dead_letters = watcher.cache.inner().read().dead_letters();
+            assert_eq!(dead_letters.len(), 1);
+            assert_eq!(dead_letters[0].block_num(), Some(2));
+            assert_eq!(dead_letters[0].raw_log(), Some(&[0xff][..]));
+
+            // Only the events of the reorganized blocks are fetched again.
+            chain.blocks.truncate(2);
+            chain.build_block(group_id, 1, || {
+                let handshake = ExportHandshake::new(0, 0, vec![3]).encode();
+                let msg = [&handshake[..], &0u32.to_be_bytes()].concat();
+                let sig = enclave.sign(&msg);
+                Anonify::handshake(Origin::signed(1), group_id, handshake, sig, 0).unwrap();
+            });
+            chain.build_block(group_id, 1, || {});
+            chain.build_block(group_id, 1, || {});
+            let enclave_log = block_on(watcher.fetch_enclave_log(&chain))
+                .unwrap()
+                .unwrap();
+            assert_eq!(positions(&enclave_log), vec![(0, 0, u32::MAX, Some(2))]);
+            assert_eq!(enclave_log.scanned_block, (3, chain.blocks[3].0));
+        });
+    }
+
+    #[test]
+    fn test_fetch_blob() {
+        let dir =
+            std::env::temp_dir().join(format!("anonify-substrate-blobs-{}", std::process::id()));
+        let store: SharedBlobStore = Arc::new(FsBlobStore::new(&dir).unwrap());
+        let ciphertext = Ciphertext::new(0, 0, 0, vec![1, 2, 3]).encode();
+        let hash = block_on(put_blob(&store, ciphertext.clone())).unwrap();
+
+        // The hash is resolved only if a blob store is set.
+        let res =
+            block_on(watcher(WatcherConfig::new()).fetch_blob(GroupPayload::CiphertextHash(hash)));
+        assert!(matches!(res, Err(HostError::BlobStoreNotSet)));
+
+        let watcher = watcher(WatcherConfig::new().set_blob_store(store));
+        assert_eq!(watcher.scanned_block_num(), None);
+        match block_on(watcher.fetch_blob(GroupPayload::CiphertextHash(hash))).unwrap() {
+            GroupPayload::Ciphertext(blob) => assert_eq!(blob, ciphertext),
+            payload => panic!("Unexpected payload: {:?}", payload),
+        }
+        match block_on(watcher.fetch_blob(GroupPayload::Handshake(vec![4]))).unwrap() {
+            GroupPayload::Handshake(handshake) => assert_eq!(handshake, vec![4]),
+            payload => panic!("Unexpected payload: {:?}", payload),
+        }
+
+        // A blob altered in the store is never inserted.
+        std::fs::write(dir.join(hex::encode(hash)), b"altered").unwrap();
+        let payload = block_on(watcher.fetch_blob(GroupPayload::CiphertextHash(hash))).unwrap();
+        assert!(into_payload(payload).is_err());
+
+        // A missing blob is fetched again until the maximum number of trials,
+        // and then left to be skipped.
+        let missing = blob_hash(b"missing");
+        for _ in 1..MAX_BLOB_TRIALS {
+            let res = block_on(watcher.fetch_blob(GroupPayload::CiphertextHash(missing)));
+            assert!(matches!(res, Err(HostError::BlobNotFound(_))));
+        }
+        let payload = block_on(watcher.fetch_blob(GroupPayload::CiphertextHash(missing))).unwrap();
+        assert!(into_payload(payload).is_err());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_into_payload() {
+        let ciphertext = Ciphertext::new(3, 2, 1, vec![1, 2, 3]);
+        let payload = into_payload(GroupPayload::Ciphertext(ciphertext.encode())).unwrap();
+        assert_eq!(payload.roster_idx(), 1);
+        assert_eq!(payload.epoch(), 2);
+        assert_eq!(payload.generation(), 3);
+
+        // The handshake comes after every ciphertext of the epoch.
+        let handshake = ExportHandshake::new(2, 1, vec![4, 5]);
+        let handshake = into_payload(GroupPayload::Handshake(handshake.encode())).unwrap();
+        assert_eq!(handshake.generation(), u32::MAX);
+        assert!(payload < handshake);
+
+        assert!(into_payload(GroupPayload::CiphertextHash([0; 32])).is_err());
+        assert!(into_payload(GroupPayload::Handshake(vec![])).is_err());
+        assert_eq!(raw_payload(&GroupPayload::Handshake(vec![6])), vec![6]);
+        assert_eq!(
+            raw_payload(&GroupPayload::CiphertextHash([7; 32])),
+            vec![7; 32]
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use crate::{signer::SubstrateSigner, utils::TxConfig};
+use anonify_ledger_driver::{LedgerDriver, Result};
+use anyhow::anyhow;
+use codec::{Decode, Encode};
+use substrate_subxt::sp_core::{
+    crypto::{AccountId32, Ss58Codec},
+    H256,
+};
+
+/// Substrate chains running `pallet-anonify`.
+/// Accounts and groups are addressed by the raw bytes of their `AccountId32`,
+/// which are written in SS58 or hex.
+#[derive(Debug, Clone, Copy)]
+pub struct Substrate;
+
+impl LedgerDriver for Substrate {
+    type Address = H256;
+    type TxId = H256;
+    type BlockPosition = (u64, H256);
+    /// The hash of the block, and the index of the event in the block
+    type LogId = (H256, u32);
+    type TxConfig = TxConfig;
+    type Signer = SubstrateSigner;
+
+    fn parse_address(addr: &str) -> Result<H256> {
+        if let Ok(account) = AccountId32::from_ss58check(addr) {
+            return Ok(from_account_id(&account));
+        }
+
+        let bytes = hex::decode(addr.trim_start_matches("0x"))
+            .map_err(|e| anyhow!("Invalid address {}: {:?}", addr, e))?;
+        if bytes.len() != 32 {
+            return Err(anyhow!("Invalid address length {}: {}", addr, bytes.len()).into());
+        }
+
+        Ok(H256::from_slice(&bytes))
+    }
+
+    fn format_address(addr: &H256) -> String {
+        to_account_id(addr).to_ss58check()
+    }
+
+    fn signer_address(signer: &SubstrateSigner) -> H256 {
+        from_account_id(&signer.account_id())
+    }
+
+    fn encode_block_position(position: &(u64, H256)) -> Vec<u8> {
+        position.encode()
+    }
+
+    fn decode_block_position(bytes: &[u8]) -> Result<(u64, H256)> {
+        Decode::decode(&mut &bytes[..]).map_err(Into::into)
+    }
+
+    fn encode_log_id(log_id: &(H256, u32)) -> Vec<u8> {
+        log_id.encode()
+    }
+
+    fn decode_log_id(bytes: &[u8]) -> Result<(H256, u32)> {
+        Decode::decode(&mut &bytes[..]).map_err(Into::into)
+    }
+}
+
+pub(crate) fn to_account_id(addr: &H256) -> AccountId32 {
+    AccountId32::from(addr.to_fixed_bytes())
+}
+
+pub(crate) fn from_account_id(account: &AccountId32) -> H256 {
+    let bytes: &[u8] = account.as_ref();
+    H256::from_slice(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sp_keyring::AccountKeyring;
+
+    #[test]
+    fn test_address_format() {
+        let account = AccountKeyring::Alice.to_account_id();
+        let addr = from_account_id(&account);
+
+        let formatted = Substrate::format_address(&addr);
+        assert_eq!(formatted, account.to_ss58check());
+        assert_eq!(Substrate::parse_address(&formatted).unwrap(), addr);
+        assert_eq!(
+            Substrate::parse_address(&format!("0x{}", hex::encode(addr))).unwrap(),
+            addr
+        );
+        assert!(Substrate::parse_address("0x1234").is_err());
+    }
+
+    #[test]
+    fn test_log_id_encoding() {
+        let position = (10, H256::repeat_byte(1));
+        let log_id = (H256::repeat_byte(2), 3);
+
+        assert_eq!(
+            Substrate::decode_block_position(&Substrate::encode_block_position(&position)).unwrap(),
+            position
+        );
+        assert_eq!(
+            Substrate::decode_log_id(&Substrate::encode_log_id(&log_id)).unwrap(),
+            log_id
+        );
+        assert!(Substrate::decode_log_id(&[0; 3]).is_err());
+    }
+}
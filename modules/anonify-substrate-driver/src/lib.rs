@@ -0,0 +1,34 @@
+#![crate_type = "lib"]
+
+//! The substrate backend of the ledger driver.
+//! The enclaves are served by `pallet-anonify` in the runtime of the chain,
+//! and each deployment creates a group in the pallet instead of a contract.
+//!
+//! Unlike the Ethereum backend, it does not support
+//! - the mrenclave governance, whose proposals fail with `HostError::Unsupported`,
+//! - publishing the state commitments, which fails in the same way,
+//! - recording the state roots and the commitments of other nodes, so that no mismatch is ever reported,
+//! - receiving the events by subscription, since they are always fetched by polling the blocks,
+//! - signing the transactions with a keystore, since they are signed with a key derived from a secret URI.
+
+mod client;
+mod deployer;
+mod error;
+mod event_watcher;
+mod ledger;
+pub mod runtime;
+mod sender;
+mod signer;
+mod utils;
+
+pub use client::GroupClient;
+pub use deployer::SubstrateDeployer;
+pub use error::SubstrateError;
+pub use event_watcher::EventWatcher;
+pub use ledger::Substrate;
+pub use sender::SubstrateSender;
+pub use signer::SubstrateSigner;
+pub use utils::TxConfig;
+
+/// The event cache of groups on substrate chains
+pub type EventCache = anonify_ledger_driver::EventCache<Substrate>;
@@ -0,0 +1,140 @@
+//! The calls and events of `pallet-anonify`, and a runtime which contains it.
+
+use codec::{Decode, Encode};
+use std::marker::PhantomData;
+use substrate_subxt::{
+    balances::{AccountData, Balances, BalancesEventTypeRegistry},
+    extrinsic::DefaultExtra,
+    module, register_default_type_sizes,
+    sp_core::H256,
+    sp_runtime::{
+        generic::Header,
+        traits::{BlakeTwo256, IdentifyAccount, Verify},
+        MultiSignature, OpaqueExtrinsic,
+    },
+    system::{System, SystemEventTypeRegistry},
+    Call, Event, EventTypeRegistry, Runtime,
+};
+
+/// The address of a verifying key of an enclave
+pub type VerifyingKey = [u8; 20];
+
+/// A runtime of the node template with `pallet-anonify`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct AnonifyRuntime;
+
+impl Runtime for AnonifyRuntime {
+    type Signature = MultiSignature;
+    type Extra = DefaultExtra<Self>;
+
+    fn register_type_sizes(event_type_registry: &mut EventTypeRegistry<Self>) {
+        event_type_registry.with_system();
+        event_type_registry.with_balances();
+        event_type_registry.with_anonify();
+        register_default_type_sizes(event_type_registry);
+    }
+}
+
+impl System for AnonifyRuntime {
+    type Index = u32;
+    type BlockNumber = u32;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = <<MultiSignature as Verify>::Signer as IdentifyAccount>::AccountId;
+    type Address = Self::AccountId;
+    type Header = Header<Self::BlockNumber, BlakeTwo256>;
+    type Extrinsic = OpaqueExtrinsic;
+    type AccountData = AccountData<<Self as Balances>::Balance>;
+}
+
+impl Balances for AnonifyRuntime {
+    type Balance = u128;
+}
+
+impl Anonify for AnonifyRuntime {}
+
+#[module]
+pub trait Anonify: System {}
+
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct DeployCall<T: Anonify> {
+    pub report: Vec<u8>,
+    pub report_sig: Vec<u8>,
+    pub handshake: Vec<u8>,
+    pub mrenclave_ver: u32,
+    pub _runtime: PhantomData<T>,
+}
+
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct JoinGroupCall<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub report: Vec<u8>,
+    pub report_sig: Vec<u8>,
+    pub handshake: Vec<u8>,
+    pub version: u32,
+    pub roster_idx: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct RegisterReportCall<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub report: Vec<u8>,
+    pub report_sig: Vec<u8>,
+    pub version: u32,
+    pub roster_idx: u32,
+}
+
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct RevokeKeysCall<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub verifying_key: VerifyingKey,
+    pub encrypting_key: Vec<u8>,
+    pub new_verifying_key: VerifyingKey,
+    pub enclave_sig: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct StoreCommandCall<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub ciphertext: Vec<u8>,
+    pub enclave_sig: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct StoreCommandHashCall<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub ciphertext_hash: [u8; 32],
+    pub enclave_sig: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, Call, Encode)]
+pub struct HandshakeCall<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub handshake: Vec<u8>,
+    pub enclave_sig: Vec<u8>,
+    pub roster_idx: u32,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+pub struct DeployedEvent<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub owner: <T as System>::AccountId,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+pub struct StoreCiphertextEvent<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+pub struct StoreCiphertextHashEvent<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub ciphertext_hash: [u8; 32],
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Event, Decode)]
+pub struct StoreHandshakeEvent<T: Anonify> {
+    pub group_id: <T as System>::AccountId,
+    pub handshake: Vec<u8>,
+}
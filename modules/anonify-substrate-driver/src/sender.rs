@@ -0,0 +1,228 @@
+use crate::{
+    client::{Connection, GroupClient},
+    ledger::Substrate,
+    signer::SubstrateSigner,
+    utils::TxConfig,
+};
+use anonify_ledger_driver::{
+    traits::*, workflow::*, ContractInfo, HostError, Result, SharedBlobStore,
+};
+use async_trait::async_trait;
+use log::info;
+use sgx_types::sgx_enclave_id_t;
+use std::{path::Path, sync::Arc};
+use substrate_subxt::sp_core::H256;
+
+/// Components needed to send an extrinsic
+#[derive(Debug)]
+pub struct SubstrateSender {
+    enclave_id: sgx_enclave_id_t,
+    client: GroupClient,
+}
+
+#[async_trait]
+impl Sender for SubstrateSender {
+    type Ledger = Substrate;
+    type Contract = GroupClient;
+
+    fn new<P: AsRef<Path>>(
+        enclave_id: sgx_enclave_id_t,
+        node_url: &str,
+        contract_info: ContractInfo<'_, P>,
+        config: TxConfig,
+        signer: Option<SubstrateSigner>,
+    ) -> Result<Self> {
+        let conn = Arc::new(Connection::new(node_url));
+        let client = GroupClient::new(conn, contract_info)?
+            .set_tx_config(config)
+            .set_signer(signer);
+
+        Ok(SubstrateSender { enclave_id, client })
+    }
+
+    fn from_contract(enclave_id: sgx_enclave_id_t, client: GroupClient) -> Self {
+        SubstrateSender { enclave_id, client }
+    }
+
+    fn set_blob_store(mut self, blob_store: SharedBlobStore) -> Self {
+        self.client = self.client.set_blob_store(blob_store);
+        self
+    }
+
+    /// The accounts are only known from the signer, which the dispatcher resolves before calling this.
+    async fn get_account(&self, index: usize, _password: &str) -> Result<H256> {
+        self.client
+            .connection()
+            .get_account(index)
+            .map_err(Into::into)
+    }
+
+    async fn send_report_handshake(
+        &self,
+        host_output: host_output::JoinGroup<H256>,
+        method: &str,
+    ) -> Result<H256> {
+        info!("Sending a handshake to blockchain: {:?}", host_output);
+        self.client
+            .send_report_handshake(host_output, method)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// pallet-anonify does not move groups to new enclaves.
+    async fn approve_mrenclave(&self, _signer: H256, _gas: u64, _new_version: u32) -> Result<H256> {
+        Err(HostError::Unsupported("the mrenclave governance"))
+    }
+
+    async fn execute_mrenclave(&self, _signer: H256, _gas: u64, _new_version: u32) -> Result<H256> {
+        Err(HostError::Unsupported("the mrenclave governance"))
+    }
+
+    /// pallet-anonify has no storage for the state commitments.
+    async fn publish_state_commitment(
+        &self,
+        _host_output: host_output::StateCommitment<H256>,
+    ) -> Result<H256> {
+        Err(HostError::Unsupported("the state commitments"))
+    }
+
+    async fn register_report(
+        &self,
+        host_output: host_output::RegisterReport<H256>,
+    ) -> Result<H256> {
+        info!("Registering report to blockchain: {:?}", host_output);
+        self.client
+            .register_report(host_output)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn revoke_keys(&self, host_output: host_output::RotateIdentityKey<H256>) -> Result<H256> {
+        info!(
+            "Revoking the old identity key on blockchain: {:?}",
+            host_output
+        );
+        self.client
+            .revoke_keys(host_output)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn send_command(&self, host_output: host_output::Command<H256>) -> Result<H256> {
+        info!("Sending a command to blockchain: {:?}", host_output);
+        self.client
+            .send_command(host_output)
+            .await
+            .map_err(Into::into)
+    }
+
+    async fn handshake(&self, host_output: host_output::Handshake<H256>) -> Result<H256> {
+        info!("Sending a handshake to blockchain: {:?}", host_output);
+        self.client.handshake(host_output).await.map_err(Into::into)
+    }
+
+    fn get_contract(self) -> GroupClient {
+        self.client
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anonify_ledger_driver::LedgerDriver;
+    use futures::executor::block_on;
+    use sp_keyring::AccountKeyring;
+
+    fn sender(signer: Option<SubstrateSigner>) -> SubstrateSender {
+        let group_id = Substrate::format_address(&H256::repeat_byte(1));
+        let contract_info = ContractInfo::new("", group_id.as_str());
+        SubstrateSender::new(
+            0,
+            "ws://localhost:9944",
+            contract_info,
+            TxConfig::default(),
+            signer,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_get_account_without_signer() {
+        assert_eq!(sender(None).get_contract().group_id(), H256::repeat_byte(1));
+        assert!(matches!(
+            block_on(sender(None).get_account(0, "")),
+            Err(HostError::LedgerError(_))
+        ));
+    }
+
+    #[test]
+    fn test_unsupported_calls() {
+        let signer = SubstrateSigner::from_keyring(AccountKeyring::Alice);
+        let addr = Substrate::signer_address(&signer);
+        let sender = sender(Some(signer));
+
+        assert!(matches!(
+            block_on(sender.approve_mrenclave(addr, 0, 1)),
+            Err(HostError::Unsupported(_))
+        ));
+        assert!(matches!(
+            block_on(sender.execute_mrenclave(addr, 0, 1)),
+            Err(HostError::Unsupported(_))
+        ));
+        for method in &["updateMrenclave", "proposeMrenclave"] {
+            let output = host_output::JoinGroup {
+                signer: addr,
+                gas: 0,
+                ecall_output: None,
+            };
+            assert!(matches!(
+                block_on(sender.send_report_handshake(output, method)),
+                Err(HostError::Unsupported(_))
+            ));
+        }
+        let output = host_output::StateCommitment {
+            signer: addr,
+            gas: 0,
+            ecall_output: None,
+        };
+        assert!(matches!(
+            block_on(sender.publish_state_commitment(output)),
+            Err(HostError::Unsupported(_))
+        ));
+    }
+
+    #[test]
+    fn test_send_without_ecall_output() {
+        let signer = SubstrateSigner::from_keyring(AccountKeyring::Alice);
+        let addr = Substrate::signer_address(&signer);
+        let sender = sender(Some(signer));
+
+        let output = host_output::JoinGroup {
+            signer: addr,
+            gas: 0,
+            ecall_output: None,
+        };
+        assert!(matches!(
+            block_on(sender.send_report_handshake(output, "joinGroup")),
+            Err(HostError::EcallOutputNotSet)
+        ));
+        let output = host_output::RegisterReport {
+            signer: addr,
+            gas: 0,
+            ecall_output: None,
+        };
+        assert!(matches!(
+            block_on(sender.register_report(output)),
+            Err(HostError::EcallOutputNotSet)
+        ));
+        let output = host_output::Handshake {
+            signer: addr,
+            gas: 0,
+            ecall_output: None,
+        };
+        assert!(matches!(
+            block_on(sender.handshake(output)),
+            Err(HostError::EcallOutputNotSet)
+        ));
+    }
+}
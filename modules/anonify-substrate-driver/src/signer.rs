@@ -0,0 +1,46 @@
+use crate::{error::Result, runtime::AnonifyRuntime};
+use anyhow::anyhow;
+use sp_keyring::AccountKeyring;
+use std::fmt;
+use substrate_subxt::{
+    sp_core::{crypto::AccountId32, sr25519, Pair},
+    PairSigner,
+};
+
+/// Signs extrinsics with a sr25519 key held by the host.
+#[derive(Clone)]
+pub struct SubstrateSigner {
+    pair: sr25519::Pair,
+}
+
+impl SubstrateSigner {
+    /// Derive the key from a secret URI such as a mnemonic phrase or `//Alice`.
+    pub fn from_suri(suri: &str, password: Option<&str>) -> Result<Self> {
+        let pair = sr25519::Pair::from_string(suri, password)
+            .map_err(|e| anyhow!("Invalid secret URI: {:?}", e))?;
+        Ok(SubstrateSigner { pair })
+    }
+
+    /// The key of a well-known development account.
+    pub fn from_keyring(keyring: AccountKeyring) -> Self {
+        SubstrateSigner {
+            pair: keyring.pair(),
+        }
+    }
+
+    pub fn account_id(&self) -> AccountId32 {
+        self.pair.public().into()
+    }
+
+    pub(crate) fn pair_signer(&self) -> PairSigner<AnonifyRuntime, sr25519::Pair> {
+        PairSigner::new(self.pair.clone())
+    }
+}
+
+impl fmt::Debug for SubstrateSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubstrateSigner")
+            .field("account_id", &self.account_id())
+            .finish()
+    }
+}
@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+/// Configuration of how extrinsics are sent and confirmed.
+#[derive(Debug, Clone)]
+pub struct TxConfig {
+    confirmations: u64,
+    poll_interval: Duration,
+}
+
+impl TxConfig {
+    pub fn new() -> Self {
+        TxConfig::default()
+    }
+
+    /// Set the number of blocks, including the one containing an extrinsic,
+    /// which must be built before the extrinsic is regarded as confirmed.
+    pub fn set_confirmations(mut self, confirmations: u64) -> Self {
+        self.confirmations = confirmations;
+        self
+    }
+
+    pub fn set_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn confirmations(&self) -> u64 {
+        self.confirmations
+    }
+
+    pub fn poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+}
+
+impl Default for TxConfig {
+    fn default() -> Self {
+        TxConfig {
+            confirmations: 1,
+            poll_interval: Duration::from_millis(500),
+        }
+    }
+}
@@ -1,7 +1,30 @@
 use thiserror::Error;
 
+pub type Result<T> = std::result::Result<T, KeyVaultEnclaveError>;
+
 #[derive(Error, Debug)]
 pub enum KeyVaultEnclaveError {
     #[error("Anyhow error: {0}")]
     AnyhowError(#[from] anyhow::Error),
-}
\ No newline at end of file
+
+    #[error("I/O error: {0}")]
+    IoError(#[from] std::io::Error),
+
+    #[error("{0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+
+    #[error("Sgx error: {0:?}")]
+    SgxError(sgx_types::sgx_status_t),
+
+    #[error("Invalid sealed data")]
+    InvalidSealedData,
+
+    #[error("Not found path secret (roster_idx: {roster_idx}, epoch: {epoch})")]
+    NotFound { roster_idx: u32, epoch: u32 },
+
+    #[error("The sealed path secret doesn't match the requested key")]
+    KeyMismatch,
+
+    #[error("The path secret is owned by another enclave group")]
+    Unauthorized,
+}
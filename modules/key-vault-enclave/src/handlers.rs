@@ -1,14 +1,37 @@
-use frame_mra_tls::RequestHandler;
-use frame_common::crypto::BackupPathSecret;
+use crate::store::PathSecretStore;
+use anonify_config::{KEY_VAULT_LIST_METHOD, KEY_VAULT_RECOVER_METHOD, KEY_VAULT_STORE_METHOD};
+use frame_common::crypto::{BackupPathSecret, ListPathSecrets, RecoverPathSecret};
+use frame_mra_tls::{EnclaveMeasurements, Router};
 
-use std::vec::Vec;
+/// Routes of the key-vault methods to the path secret store.
+/// Path secrets are owned by the MRENCLAVE of the peer which stored them,
+/// so peers running another enclave cannot recover or list them.
+/// Peers of other groups running the same enclave can, since they are not told apart by the attestation.
+pub fn key_vault_router(store: PathSecretStore) -> Router {
+    let recover_store = store.clone();
+    let list_store = store.clone();
 
-#[derive(Default, Clone)]
-pub struct BackupHandler;
-
-impl RequestHandler for BackupHandler {
-    fn handle_json(&self, msg: &[u8]) -> anyhow::Result<Vec<u8>> {
-        let backup_path_secret: BackupPathSecret = serde_json::from_slice(&msg)?;
-        serde_json::to_vec(&backup_path_secret).map_err(Into::into)
-    }
-}
\ No newline at end of file
+    Router::new()
+        .route_with_peer(
+            KEY_VAULT_STORE_METHOD,
+            move |peer: &EnclaveMeasurements, req: BackupPathSecret| {
+                store.store(&peer.mr_enclave, &req).map_err(Into::into)
+            },
+        )
+        .route_with_peer(
+            KEY_VAULT_RECOVER_METHOD,
+            move |peer: &EnclaveMeasurements, req: RecoverPathSecret| {
+                recover_store
+                    .recover(&peer.mr_enclave, &req)
+                    .map_err(Into::into)
+            },
+        )
+        .route_with_peer(
+            KEY_VAULT_LIST_METHOD,
+            move |peer: &EnclaveMeasurements, req: ListPathSecrets| {
+                list_store
+                    .list(&peer.mr_enclave, req.roster_idx())
+                    .map_err(Into::into)
+            },
+        )
+}
@@ -8,7 +8,18 @@ extern crate sgx_tstd as std;
 mod error;
 mod handlers;
 pub mod server;
+mod store;
 
 pub mod workflow {
     pub use crate::server::{ServerStarter, ServerStopper};
-}
\ No newline at end of file
+}
+#[cfg(debug_assertions)]
+pub mod tests {
+    use super::*;
+    use std::prelude::v1::*;
+    use test_utils::*;
+
+    pub fn run_tests() -> bool {
+        check_all_passed!(store::tests::run_tests(),)
+    }
+}
@@ -1,13 +1,27 @@
-use crate::handlers::BackupHandler;
-use anonify_config::IAS_ROOT_CERT;
+use crate::handlers::key_vault_router;
+use crate::store::PathSecretStore;
 use anonify_io_types::*;
+use anyhow::anyhow;
 use frame_common::state_types::StateType;
 use frame_enclave::EnclaveEngine;
-use frame_mra_tls::{AttestedTlsConfig, Server, ServerConfig};
+use frame_mra_tls::{AttestationPolicy, AttestedTlsConfig, Server, ServerConfig, ServerStopHandle};
 use frame_runtime::traits::*;
-use std::{string::ToString, thread};
+use log::error;
+use once_cell::sync::Lazy;
+use std::{
+    str,
+    string::ToString,
+    sync::SgxMutex,
+    thread::{self, JoinHandle},
+};
 
-const SERVER_ADDRESS: &str = "0.0.0.0:12345";
+/// The key-vault server running in this enclave
+static RUNNING_SERVER: Lazy<SgxMutex<Option<RunningServer>>> = Lazy::new(|| SgxMutex::new(None));
+
+struct RunningServer {
+    stop_handle: ServerStopHandle,
+    join_handle: JoinHandle<()>,
+}
 
 /// A server starter
 #[derive(Debug, Clone)]
@@ -18,7 +32,7 @@ impl EnclaveEngine for ServerStarter {
     type EO = output::Empty;
 
     fn handle<R, C>(
-        _ecall_input: Self::EI,
+        ecall_input: Self::EI,
         enclave_context: &C,
         _max_mem_size: usize,
     ) -> anyhow::Result<Self::EO>
@@ -26,19 +40,43 @@ impl EnclaveEngine for ServerStarter {
         R: RuntimeExecutor<C, S = StateType>,
         C: ContextOps<S = StateType> + Clone,
     {
-        let ias_url = enclave_context.ias_url();
-        let sub_key = enclave_context.sub_key();
-        let spid = enclave_context.spid();
+        let mut running_server = RUNNING_SERVER.lock().map_err(|e| anyhow!("{:?}", e))?;
+        match running_server.take() {
+            Some(server) if !server.stop_handle.is_stopped() => {
+                *running_server = Some(server);
+                return Err(anyhow!("The key-vault server is already running"));
+            }
+            // The previous server has exited by itself, e.g. on a listener error.
+            Some(server) => {
+                let _ = server.join_handle.join();
+            }
+            None => {}
+        }
+        let listen_address = str::from_utf8(ecall_input.listen_address())?;
 
-        let attested_tls_config =
-            AttestedTlsConfig::new_by_ra(&spid, &ias_url, &sub_key, IAS_ROOT_CERT.to_vec())?;
+        let attestation = enclave_context.attestation();
+        let attested_tls_config = AttestedTlsConfig::new_by_ra(attestation)?;
 
+        let policy = AttestationPolicy::default()
+            .set_quote_status_policy(enclave_context.quote_status_policy().clone());
         let server_config = ServerConfig::from_attested_tls_config(attested_tls_config)?
-            .set_attestation_report_verifier(IAS_ROOT_CERT.to_vec());
+            .set_attestation_policy(policy)
+            .set_attestation_report_verifier(attestation.root_cert().to_vec());
 
-        let mut server = Server::new(SERVER_ADDRESS.to_string(), server_config);
-        let handler = BackupHandler::default();
-        thread::spawn(move || server.run(handler).unwrap());
+        let mut server = Server::new(listen_address.to_string(), server_config);
+        server.bind()?;
+        let stop_handle = server.stop_handle()?;
+        let handler = key_vault_router(PathSecretStore::new()?);
+        let join_handle = thread::spawn(move || {
+            if let Err(e) = server.run(handler) {
+                error!("The key-vault server stopped with error: {:?}", e);
+            }
+        });
+
+        *running_server = Some(RunningServer {
+            stop_handle,
+            join_handle,
+        });
 
         Ok(output::Empty::default())
     }
@@ -61,6 +99,19 @@ impl EnclaveEngine for ServerStopper {
         R: RuntimeExecutor<C, S = StateType>,
         C: ContextOps<S = StateType> + Clone,
     {
+        let running_server = RUNNING_SERVER
+            .lock()
+            .map_err(|e| anyhow!("{:?}", e))?
+            .take()
+            .ok_or_else(|| anyhow!("The key-vault server is not running"))?;
+
+        running_server.stop_handle.stop();
+        // Wait for the listener to be dropped so that the address can be bound again.
+        running_server
+            .join_handle
+            .join()
+            .map_err(|_| anyhow!("The key-vault server thread panicked"))?;
+
         Ok(output::Empty::default())
     }
 }
@@ -0,0 +1,269 @@
+use crate::error::{KeyVaultEnclaveError, Result};
+use frame_common::crypto::{BackupPathSecret, RecoverPathSecret, EXPORT_ID_SIZE};
+use sgx_tseal::SgxSealedData;
+use sgx_types::sgx_sealed_data_t;
+use std::{
+    env,
+    path::PathBuf,
+    prelude::v1::*,
+    untrusted::{fs, path::PathEx},
+};
+
+const DEFAULT_STORE_DIR: &str = ".anonify/key-vault";
+const SEALED_FILE_EXT: &str = "sealed";
+/// The owner of a path secret is the MRENCLAVE of the enclave which stored it.
+pub const OWNER_SIZE: usize = 32;
+
+/// A persistent store of backed up path secrets.
+/// Each path secret is sealed with the enclave's MRSIGNER-based sealing key
+/// and saved as a file named after its `(roster_idx, epoch, id)`.
+/// The MRENCLAVE of the enclave which stored it is sealed together as the owner,
+/// so that only the enclaves built from the same code can recover or list it.
+/// The owner does not identify a group: every group running the same enclave binary has the same MRENCLAVE,
+/// so a key-vault must not be shared by groups which do not trust each other.
+#[derive(Debug, Clone)]
+pub struct PathSecretStore {
+    dir: PathBuf,
+}
+
+impl PathSecretStore {
+    pub fn new() -> Result<Self> {
+        let dir = env::var("KEY_VAULT_STORE_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_STORE_DIR));
+        fs::create_dir_all(&dir)?;
+
+        Ok(PathSecretStore { dir })
+    }
+
+    pub fn store(
+        &self,
+        owner: &[u8; OWNER_SIZE],
+        backup_path_secret: &BackupPathSecret,
+    ) -> Result<RecoverPathSecret> {
+        let key = backup_path_secret.recover_key();
+        let path = self.file_path(&key);
+        // Other enclaves must not overwrite the path secret.
+        if path.exists() {
+            self.read(&path, owner)?;
+        }
+
+        let mut plaintext = owner.to_vec();
+        plaintext.extend_from_slice(&serde_json::to_vec(backup_path_secret)?);
+        fs::write(path, seal(&plaintext)?)?;
+
+        Ok(key)
+    }
+
+    pub fn recover(
+        &self,
+        owner: &[u8; OWNER_SIZE],
+        key: &RecoverPathSecret,
+    ) -> Result<BackupPathSecret> {
+        let path = self.file_path(key);
+        if !path.exists() {
+            return Err(KeyVaultEnclaveError::NotFound {
+                roster_idx: key.roster_idx(),
+                epoch: key.epoch(),
+            });
+        }
+        let backup_path_secret = self.read(&path, owner)?;
+
+        // The file name is not authenticated, so check it against the sealed contents.
+        if backup_path_secret.recover_key() != *key {
+            return Err(KeyVaultEnclaveError::KeyMismatch);
+        }
+
+        Ok(backup_path_secret)
+    }
+
+    /// List the keys of the path secrets of the roster index owned by the MRENCLAVE.
+    pub fn list(
+        &self,
+        owner: &[u8; OWNER_SIZE],
+        roster_idx: u32,
+    ) -> Result<Vec<RecoverPathSecret>> {
+        let mut keys = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SEALED_FILE_EXT) {
+                continue;
+            }
+            let key = match path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(parse_file_stem)
+            {
+                Some(key) if key.roster_idx() == roster_idx => key,
+                _ => continue,
+            };
+            match self.read(&path, owner) {
+                Ok(backup_path_secret) if backup_path_secret.recover_key() == key => keys.push(key),
+                Ok(_) | Err(KeyVaultEnclaveError::Unauthorized) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        keys.sort_by_key(|k| k.epoch());
+
+        Ok(keys)
+    }
+
+    /// Read the sealed path secret if it is owned by the owner.
+    fn read(&self, path: &PathBuf, owner: &[u8; OWNER_SIZE]) -> Result<BackupPathSecret> {
+        let mut sealed = fs::read(path)?;
+        let plaintext = unseal(&mut sealed)?;
+        if plaintext.len() < OWNER_SIZE {
+            return Err(KeyVaultEnclaveError::InvalidSealedData);
+        }
+        if plaintext[..OWNER_SIZE] != owner[..] {
+            return Err(KeyVaultEnclaveError::Unauthorized);
+        }
+
+        serde_json::from_slice(&plaintext[OWNER_SIZE..]).map_err(Into::into)
+    }
+
+    fn file_path(&self, key: &RecoverPathSecret) -> PathBuf {
+        let id: String = key.id().iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(format!(
+            "{}_{}_{}.{}",
+            key.roster_idx(),
+            key.epoch(),
+            id,
+            SEALED_FILE_EXT
+        ))
+    }
+}
+
+fn parse_file_stem(stem: &str) -> Option<RecoverPathSecret> {
+    let mut parts = stem.split('_');
+    let roster_idx = parts.next()?.parse().ok()?;
+    let epoch = parts.next()?.parse().ok()?;
+    let id_hex = parts.next()?;
+    if parts.next().is_some() || id_hex.len() != EXPORT_ID_SIZE * 2 {
+        return None;
+    }
+
+    let mut id = [0u8; EXPORT_ID_SIZE];
+    for (i, b) in id.iter_mut().enumerate() {
+        *b = u8::from_str_radix(id_hex.get(i * 2..i * 2 + 2)?, 16).ok()?;
+    }
+
+    Some(RecoverPathSecret::new(roster_idx, epoch, id))
+}
+
+#[allow(clippy::cast_ptr_alignment)]
+fn seal(plaintext: &[u8]) -> Result<Vec<u8>> {
+    let additional = [0u8; 0];
+    let sealed_data = SgxSealedData::<[u8]>::seal_data(&additional, plaintext)
+        .map_err(KeyVaultEnclaveError::SgxError)?;
+    let sealed_len = SgxSealedData::<[u8]>::calc_raw_sealed_data_size(0, plaintext.len() as u32);
+
+    let mut res = vec![0u8; sealed_len as usize];
+    unsafe {
+        sealed_data
+            .to_raw_sealed_data_t(res.as_mut_ptr() as *mut sgx_sealed_data_t, sealed_len)
+            .ok_or(KeyVaultEnclaveError::InvalidSealedData)?;
+    }
+
+    Ok(res)
+}
+
+#[allow(clippy::cast_ptr_alignment)]
+fn unseal(sealed: &mut [u8]) -> Result<Vec<u8>> {
+    let sealed_data = unsafe {
+        SgxSealedData::<[u8]>::from_raw_sealed_data_t(
+            sealed.as_mut_ptr() as *mut sgx_sealed_data_t,
+            sealed.len() as u32,
+        )
+    }
+    .ok_or(KeyVaultEnclaveError::InvalidSealedData)?;
+    let unsealed_data = sealed_data
+        .unseal_data()
+        .map_err(KeyVaultEnclaveError::SgxError)?;
+
+    Ok(unsealed_data.get_decrypt_txt().to_vec())
+}
+
+#[cfg(debug_assertions)]
+pub(crate) mod tests {
+    use super::*;
+    use test_utils::*;
+
+    pub(crate) fn run_tests() -> bool {
+        run_tests!(
+            test_seal_unseal,
+            test_parse_file_stem,
+            test_store_recover_list,
+        )
+    }
+
+    fn test_seal_unseal() {
+        let plaintext = b"path secret".to_vec();
+        let mut sealed = seal(&plaintext).unwrap();
+        assert_eq!(unseal(&mut sealed).unwrap(), plaintext);
+    }
+
+    fn test_parse_file_stem() {
+        let key = RecoverPathSecret::new(2, 5, [7u8; EXPORT_ID_SIZE]);
+        let store = PathSecretStore {
+            dir: PathBuf::from("."),
+        };
+        let path = store.file_path(&key);
+        let stem = path.file_stem().unwrap().to_str().unwrap();
+        assert_eq!(parse_file_stem(stem), Some(key));
+        assert_eq!(parse_file_stem("2_5_00"), None);
+    }
+
+    fn test_store_recover_list() {
+        let dir = PathBuf::from(".anonify/test-key-vault");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let store = PathSecretStore { dir: dir.clone() };
+        let owner = [1u8; OWNER_SIZE];
+        let other = [2u8; OWNER_SIZE];
+
+        let backup =
+            BackupPathSecret::new(b"share".to_vec(), [0u8; 32], 5, 2, [7u8; EXPORT_ID_SIZE]);
+        let key = store.store(&owner, &backup).unwrap();
+        store
+            .store(
+                &owner,
+                &BackupPathSecret::new(b"share".to_vec(), [0u8; 32], 3, 2, [8u8; EXPORT_ID_SIZE]),
+            )
+            .unwrap();
+        store
+            .store(
+                &owner,
+                &BackupPathSecret::new(b"share".to_vec(), [0u8; 32], 4, 9, [9u8; EXPORT_ID_SIZE]),
+            )
+            .unwrap();
+
+        let recovered = store.recover(&owner, &key).unwrap();
+        assert_eq!(recovered.path_secret(), backup.path_secret());
+        assert_eq!(recovered.recover_key(), key);
+        let keys = store.list(&owner, 2).unwrap();
+        assert_eq!(
+            keys.iter().map(|k| k.epoch()).collect::<Vec<_>>(),
+            vec![3, 5]
+        );
+
+        // Another enclave can neither read nor overwrite it.
+        assert!(is_unauthorized(store.recover(&other, &key)));
+        assert!(store.list(&other, 2).unwrap().is_empty());
+        assert!(is_unauthorized(store.store(&other, &backup)));
+        assert!(store.recover(&owner, &key).is_ok());
+        assert!(store
+            .recover(&owner, &RecoverPathSecret::new(2, 6, [7u8; EXPORT_ID_SIZE]))
+            .is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    fn is_unauthorized<T>(res: Result<T>) -> bool {
+        if let Err(KeyVaultEnclaveError::Unauthorized) = res {
+            true
+        } else {
+            false
+        }
+    }
+}
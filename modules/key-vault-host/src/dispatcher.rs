@@ -13,15 +13,18 @@ pub struct Dispatcher {
 }
 
 impl Dispatcher {
-    pub fn new(enclave_id: sgx_enclave_id_t) -> Result<Self> {
-        let inner = RwLock::new(InnerDispatcher { enclave_id });
+    pub fn new(enclave_id: sgx_enclave_id_t, listen_address: String) -> Result<Self> {
+        let inner = RwLock::new(InnerDispatcher {
+            enclave_id,
+            listen_address,
+        });
 
         Ok(Dispatcher { inner })
     }
 
     pub async fn start(&self) -> Result<()> {
         let inner = self.inner.read();
-        let input = host_input::StartServer::new();
+        let input = host_input::StartServer::new(inner.listen_address.clone());
         let eid = inner.get_enclave_id();
         let _host_output = StartServerWorkflow::exec(input, eid)?;
 
@@ -41,6 +44,7 @@ impl Dispatcher {
 #[derive(Debug)]
 struct InnerDispatcher {
     enclave_id: sgx_enclave_id_t,
+    listen_address: String,
 }
 
 impl InnerDispatcher {
@@ -29,12 +29,13 @@ impl HostEngine for StopServerWorkflow {
 pub mod host_input {
     use super::*;
 
-    pub struct StartServer;
+    pub struct StartServer {
+        listen_address: String,
+    }
 
     impl StartServer {
-        pub fn new() -> Self {
-            StartServer {
-            }
+        pub fn new(listen_address: String) -> Self {
+            StartServer { listen_address }
         }
     }
 
@@ -43,7 +44,8 @@ pub mod host_input {
         type HostOutput = host_output::StartServer;
 
         fn apply(self) -> anyhow::Result<(Self::EcallInput, Self::HostOutput)> {
-            Ok((Self::EcallInput::default(), Self::HostOutput::default()))
+            let ecall_input = Self::EcallInput::new(self.listen_address.into_bytes());
+            Ok((ecall_input, Self::HostOutput::default()))
         }
     }
 
@@ -0,0 +1,441 @@
+//! A pallet which mirrors `Anonify.sol`, so that a substrate chain can serve as the ledger
+//! of the enclaves in place of an Ethereum contract.
+//!
+//! Each deployment creates a group, identified by an account derived from the deployer.
+//! The group keeps the same storage as the contract and emits `StoreCiphertext` and
+//! `StoreHandshake` events with the same payloads.
+//! A group stays on the enclave it was deployed with, since the pallet does not implement
+//! the approval of a new mrenclave by the members of the contract.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+mod report;
+#[cfg(test)]
+mod tests;
+
+use codec::{Decode, Encode};
+use frame_support::{
+    decl_error, decl_event, decl_module, decl_storage, dispatch::DispatchResult, ensure,
+    traits::Get, weights::Weight,
+};
+use frame_system::ensure_signed;
+use sp_runtime::RuntimeDebug;
+use sp_std::prelude::*;
+
+/// The address of a verifying key, the last 20 bytes of the keccak-256 hash of the public key
+pub type VerifyingKey = [u8; 20];
+
+// The offset of the roster index in the handshake, which is read by the constructor.
+const HANDSHAKE_ROSTER_IDX_OFFSET: usize = 4;
+// The base weights of the calls verifying a report and of the others.
+// All the calls are charged per byte of their inputs on top of them.
+const REPORT_WEIGHT: Weight = 200_000_000;
+const BASE_WEIGHT: Weight = 50_000_000;
+const WEIGHT_PER_BYTE: Weight = 10_000;
+// secp256k1n / 2, the upper bound of `s` which the contract accepts
+const HALF_CURVE_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+pub trait Trait: frame_system::Trait {
+    type Event: From<Event<Self>> + Into<<Self as frame_system::Trait>::Event>;
+    /// The modulus of the RSA key which IAS signs the reports with
+    type IasRsaModulus: Get<Vec<u8>>;
+    /// The public exponent of the RSA key which IAS signs the reports with
+    type IasRsaExponent: Get<Vec<u8>>;
+}
+
+/// The storage of a deployment of `Anonify.sol`
+#[derive(Encode, Decode, Clone, PartialEq, Eq, Default, RuntimeDebug)]
+pub struct Group<AccountId> {
+    pub owner: AccountId,
+    pub mrenclave: [u8; 32],
+    pub mrenclave_ver: u32,
+    pub roster_idx_counter: u32,
+    /// Pairs of the verifying keys and the encrypting keys registered by the same report
+    pub keys: Vec<(VerifyingKey, Vec<u8>)>,
+}
+
+impl<AccountId> Group<AccountId> {
+    fn is_registered(&self, verifying_key: &VerifyingKey) -> bool {
+        self.keys.iter().any(|(key, _)| key == verifying_key)
+    }
+}
+
+decl_storage! {
+    trait Store for Module<T: Trait> as Anonify {
+        /// The groups indexed by their addresses
+        pub Groups get(fn groups): map hasher(blake2_128_concat) T::AccountId => Option<Group<T::AccountId>>;
+        /// The roster index of each sender in a group, zero if it has not joined
+        pub RosterIdx get(fn roster_idx):
+            double_map hasher(blake2_128_concat) T::AccountId, hasher(blake2_128_concat) T::AccountId => u32;
+        /// The number of the groups which have been deployed, used to derive their addresses
+        pub Nonce get(fn nonce): u64;
+    }
+}
+
+decl_event! {
+    pub enum Event<T> where AccountId = <T as frame_system::Trait>::AccountId {
+        /// A group was deployed by the owner. [group, owner]
+        Deployed(AccountId, AccountId),
+        /// An encrypted command was stored. [group, ciphertext]
+        StoreCiphertext(AccountId, Vec<u8>),
+        /// The hash of an encrypted command kept off the chain was stored. [group, ciphertext_hash]
+        StoreCiphertextHash(AccountId, [u8; 32]),
+        /// A handshake was stored. [group, handshake]
+        StoreHandshake(AccountId, Vec<u8>),
+        /// The keys were revoked. [group, verifying_key, encrypting_key]
+        RevokeKeys(AccountId, VerifyingKey, Vec<u8>),
+    }
+}
+
+decl_error! {
+    pub enum Error for Module<T: Trait> {
+        /// The group has not been deployed
+        GroupNotFound,
+        /// The report is not signed by IAS
+        InvalidReportSig,
+        /// isvEnclaveQuoteBody not found in report
+        QuoteNotFound,
+        /// The handshake is too short to contain a roster index
+        InvalidHandshake,
+        /// First roster_idx must be zero
+        FirstRosterIdxNotZero,
+        /// Must be same version
+        VersionMismatch,
+        /// Joining the group must be ordered accordingly by roster index
+        RosterIdxNotOrdered,
+        /// The msg.sender can join only once
+        AlreadyJoined,
+        /// mrenclave included in the report is not correct.
+        InvalidMrenclave,
+        /// The verifying key has already been registered.
+        VerifyingKeyRegistered,
+        /// The encrypting key has already been registered.
+        EncryptingKeyRegistered,
+        /// The verifying key has not been registered.
+        VerifyingKeyNotRegistered,
+        /// The encrypting key is not paired with the verifying key.
+        KeysNotPaired,
+        /// The roster index must be same as the registered one
+        RosterIdxMismatch,
+        /// The msg.sender has not joined the group
+        NotMember,
+        /// recovered verifyingKey was address(0)
+        InvalidSignature,
+        /// Invalid enclave signature.
+        InvalidEnclaveSig,
+    }
+}
+
+decl_module! {
+    pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+        type Error = Error<T>;
+
+        fn deposit_event() = default;
+
+        /// Create a group with the report of the owner's enclave.
+        #[weight = weight(REPORT_WEIGHT, report.len() + report_sig.len() + handshake.len())]
+        pub fn deploy(
+            origin,
+            report: Vec<u8>,
+            report_sig: Vec<u8>,
+            handshake: Vec<u8>,
+            mrenclave_ver: u32,
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            let roster_idx = handshake
+                .get(HANDSHAKE_ROSTER_IDX_OFFSET..HANDSHAKE_ROSTER_IDX_OFFSET + 4)
+                .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+                .ok_or(Error::<T>::InvalidHandshake)?;
+            ensure!(roster_idx == 0, Error::<T>::FirstRosterIdxNotZero);
+
+            let mut group = Group::default();
+            let quote = Self::extract_from_report(&group, &report, &report_sig)?;
+            group.keys.push((quote.verifying_key, quote.encrypting_key));
+            group.mrenclave = quote.mrenclave;
+            group.owner = sender.clone();
+            group.mrenclave_ver = mrenclave_ver;
+            group.roster_idx_counter = roster_idx;
+
+            let nonce = Nonce::get();
+            let group_id = Self::group_id(&sender, nonce);
+            Nonce::put(nonce + 1);
+            Groups::<T>::insert(&group_id, group);
+            RosterIdx::<T>::insert(&group_id, &sender, roster_idx);
+
+            Self::deposit_event(RawEvent::Deployed(group_id.clone(), sender));
+            Self::deposit_event(RawEvent::StoreHandshake(group_id, handshake));
+            Ok(())
+        }
+
+        #[weight = weight(REPORT_WEIGHT, report.len() + report_sig.len() + handshake.len())]
+        pub fn join_group(
+            origin,
+            group_id: T::AccountId,
+            report: Vec<u8>,
+            report_sig: Vec<u8>,
+            handshake: Vec<u8>,
+            version: u32,
+            roster_idx: u32,
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            Self::mutate_group(&group_id, |group| {
+                ensure!(group.mrenclave_ver == version, Error::<T>::VersionMismatch);
+                ensure!(
+                    roster_idx == group.roster_idx_counter + 1,
+                    Error::<T>::RosterIdxNotOrdered
+                );
+                ensure!(
+                    !Self::is_member(&group_id, &sender),
+                    Error::<T>::AlreadyJoined
+                );
+
+                Self::handle_report(group, &report, &report_sig)?;
+                group.roster_idx_counter = roster_idx;
+                Ok(())
+            })?;
+            RosterIdx::<T>::insert(&group_id, &sender, roster_idx);
+
+            Self::deposit_event(RawEvent::StoreHandshake(group_id, handshake));
+            Ok(())
+        }
+
+        /// Register the keys of a rotated or re-attested enclave of a member without handshake.
+        #[weight = weight(REPORT_WEIGHT, report.len() + report_sig.len())]
+        pub fn register_report(
+            origin,
+            group_id: T::AccountId,
+            report: Vec<u8>,
+            report_sig: Vec<u8>,
+            version: u32,
+            roster_idx: u32,
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            Self::ensure_roster_idx(&group_id, &sender, roster_idx)?;
+            Self::mutate_group(&group_id, |group| {
+                ensure!(group.mrenclave_ver == version, Error::<T>::VersionMismatch);
+                Self::handle_report(group, &report, &report_sig)
+            })?;
+
+            Ok(())
+        }
+
+        /// Revoke the keys of an enclave which has registered the new verifying key.
+        #[weight = weight(BASE_WEIGHT, encrypting_key.len() + enclave_sig.len())]
+        pub fn revoke_keys(
+            origin,
+            group_id: T::AccountId,
+            verifying_key: VerifyingKey,
+            encrypting_key: Vec<u8>,
+            new_verifying_key: VerifyingKey,
+            enclave_sig: Vec<u8>,
+        ) -> DispatchResult {
+            ensure_signed(origin)?;
+            Self::mutate_group(&group_id, |group| {
+                ensure!(
+                    group.is_registered(&new_verifying_key) && new_verifying_key != [0u8; 20],
+                    Error::<T>::VerifyingKeyNotRegistered
+                );
+                let msg = [&verifying_key[..], &encrypting_key, &new_verifying_key[..]].concat();
+                let recovered = recover(&msg, &enclave_sig).ok_or(Error::<T>::InvalidSignature)?;
+                ensure!(recovered == verifying_key, Error::<T>::InvalidEnclaveSig);
+
+                let i = group
+                    .keys
+                    .iter()
+                    .position(|(key, _)| *key == verifying_key)
+                    .ok_or(Error::<T>::VerifyingKeyNotRegistered)?;
+                ensure!(group.keys[i].1 == encrypting_key, Error::<T>::KeysNotPaired);
+                group.keys.swap_remove(i);
+                Ok(())
+            })?;
+
+            Self::deposit_event(RawEvent::RevokeKeys(group_id, verifying_key, encrypting_key));
+            Ok(())
+        }
+
+        /// Store an encrypted command signed by a registered enclave.
+        #[weight = weight(BASE_WEIGHT, ciphertext.len() + enclave_sig.len())]
+        pub fn store_command(
+            origin,
+            group_id: T::AccountId,
+            ciphertext: Vec<u8>,
+            enclave_sig: Vec<u8>,
+        ) -> DispatchResult {
+            ensure_signed(origin)?;
+            let group = Self::groups(&group_id).ok_or(Error::<T>::GroupNotFound)?;
+            Self::verify_enclave_sig(&group, &ciphertext, &enclave_sig)?;
+
+            Self::deposit_event(RawEvent::StoreCiphertext(group_id, ciphertext));
+            Ok(())
+        }
+
+        /// Store the SHA-256 hash of an encrypted command instead of the command,
+        /// which is signed by the enclave in the same way as `store_command`.
+        #[weight = weight(BASE_WEIGHT, enclave_sig.len())]
+        pub fn store_command_hash(
+            origin,
+            group_id: T::AccountId,
+            ciphertext_hash: [u8; 32],
+            enclave_sig: Vec<u8>,
+        ) -> DispatchResult {
+            ensure_signed(origin)?;
+            let group = Self::groups(&group_id).ok_or(Error::<T>::GroupNotFound)?;
+            Self::verify_enclave_sig_of_hash(&group, &ciphertext_hash, &enclave_sig)?;
+
+            Self::deposit_event(RawEvent::StoreCiphertextHash(group_id, ciphertext_hash));
+            Ok(())
+        }
+
+        /// Store a handshake signed by the enclave of the sender's roster index.
+        #[weight = weight(BASE_WEIGHT, handshake.len() + enclave_sig.len())]
+        pub fn handshake(
+            origin,
+            group_id: T::AccountId,
+            handshake: Vec<u8>,
+            enclave_sig: Vec<u8>,
+            roster_idx: u32,
+        ) -> DispatchResult {
+            let sender = ensure_signed(origin)?;
+            let group = Self::groups(&group_id).ok_or(Error::<T>::GroupNotFound)?;
+            Self::ensure_roster_idx(&group_id, &sender, roster_idx)?;
+            let msg = [&handshake[..], &roster_idx.to_be_bytes()].concat();
+            Self::verify_enclave_sig(&group, &msg, &enclave_sig)?;
+
+            Self::deposit_event(RawEvent::StoreHandshake(group_id, handshake));
+            Ok(())
+        }
+    }
+}
+
+impl<T: Trait> Module<T> {
+    /// The address of the group deployed by the sender with the nonce
+    pub fn group_id(sender: &T::AccountId, nonce: u64) -> T::AccountId {
+        let hash = sp_io::hashing::blake2_256(&(b"anonify", sender, nonce).encode());
+        T::AccountId::decode(&mut &hash[..]).unwrap_or_default()
+    }
+
+    /// Whether the sender has deployed or joined the group
+    fn is_member(group_id: &T::AccountId, sender: &T::AccountId) -> bool {
+        RosterIdx::<T>::contains_key(group_id, sender)
+    }
+
+    /// Ensure the sender is a member of the group with the roster index.
+    fn ensure_roster_idx(
+        group_id: &T::AccountId,
+        sender: &T::AccountId,
+        roster_idx: u32,
+    ) -> DispatchResult {
+        ensure!(Self::is_member(group_id, sender), Error::<T>::NotMember);
+        ensure!(
+            RosterIdx::<T>::get(group_id, sender) == roster_idx,
+            Error::<T>::RosterIdxMismatch
+        );
+        Ok(())
+    }
+
+    /// Apply the changes to the group, which are discarded if any of them fails.
+    fn mutate_group<F>(group_id: &T::AccountId, f: F) -> DispatchResult
+    where
+        F: FnOnce(&mut Group<T::AccountId>) -> DispatchResult,
+    {
+        let mut group = Self::groups(group_id).ok_or(Error::<T>::GroupNotFound)?;
+        f(&mut group)?;
+        Groups::<T>::insert(group_id, group);
+        Ok(())
+    }
+
+    fn handle_report(
+        group: &mut Group<T::AccountId>,
+        report: &[u8],
+        report_sig: &[u8],
+    ) -> DispatchResult {
+        let quote = Self::extract_from_report(group, report, report_sig)?;
+        ensure!(
+            group.mrenclave == quote.mrenclave,
+            Error::<T>::InvalidMrenclave
+        );
+
+        group.keys.push((quote.verifying_key, quote.encrypting_key));
+        Ok(())
+    }
+
+    fn extract_from_report(
+        group: &Group<T::AccountId>,
+        report: &[u8],
+        report_sig: &[u8],
+    ) -> Result<report::QuoteData, Error<T>> {
+        ensure!(
+            report::verify_report_sig(
+                report,
+                report_sig,
+                &T::IasRsaModulus::get(),
+                &T::IasRsaExponent::get()
+            ),
+            Error::<T>::InvalidReportSig
+        );
+        let quote = report::extract_quote(report).ok_or(Error::<T>::QuoteNotFound)?;
+
+        ensure!(
+            !group.is_registered(&quote.verifying_key),
+            Error::<T>::VerifyingKeyRegistered
+        );
+        ensure!(
+            !group
+                .keys
+                .iter()
+                .any(|(_, key)| *key == quote.encrypting_key),
+            Error::<T>::EncryptingKeyRegistered
+        );
+
+        Ok(quote)
+    }
+
+    fn verify_enclave_sig(
+        group: &Group<T::AccountId>,
+        msg: &[u8],
+        enclave_sig: &[u8],
+    ) -> DispatchResult {
+        Self::verify_enclave_sig_of_hash(group, &sp_io::hashing::sha2_256(msg), enclave_sig)
+    }
+
+    fn verify_enclave_sig_of_hash(
+        group: &Group<T::AccountId>,
+        hash: &[u8; 32],
+        enclave_sig: &[u8],
+    ) -> DispatchResult {
+        let verifying_key = recover_hash(hash, enclave_sig).ok_or(Error::<T>::InvalidSignature)?;
+        ensure!(
+            group.is_registered(&verifying_key),
+            Error::<T>::InvalidEnclaveSig
+        );
+        Ok(())
+    }
+}
+
+/// The weight of a call with the base weight and the total length of its inputs
+fn weight(base: Weight, len: usize) -> Weight {
+    base.saturating_add(WEIGHT_PER_BYTE.saturating_mul(len as Weight))
+}
+
+/// Recover the address which signed the SHA-256 hash of the message, as `Secp256k1.recover` does.
+fn recover(msg: &[u8], sig: &[u8]) -> Option<VerifyingKey> {
+    recover_hash(&sp_io::hashing::sha2_256(msg), sig)
+}
+
+fn recover_hash(hash: &[u8; 32], sig: &[u8]) -> Option<VerifyingKey> {
+    if sig.len() != 65 || sig[32..64] > HALF_CURVE_ORDER[..] || !(sig[64] == 27 || sig[64] == 28) {
+        return None;
+    }
+    let mut rsv = [0u8; 65];
+    rsv.copy_from_slice(sig);
+
+    let public = sp_io::crypto::secp256k1_ecdsa_recover(&rsv, hash).ok()?;
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&sp_io::hashing::keccak_256(&public)[12..]);
+    Some(address)
+}
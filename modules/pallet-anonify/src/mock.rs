@@ -0,0 +1,179 @@
+//! The runtime which the pallet is tested on, and the enclaves and the mock IAS to call it with.
+//! It is exposed with the `mock` feature so that the clients of the pallet can be tested against it.
+
+use crate::{report::*, Module, Trait};
+use frame_support::{
+    assert_ok, impl_outer_event, impl_outer_origin, parameter_types, traits::Get, weights::Weight,
+};
+use frame_system as system;
+use rsa::{Hash, PaddingScheme, PublicKeyParts, RSAPrivateKey};
+use sp_core::H256;
+use sp_io::hashing::{keccak_256, sha2_256};
+use sp_runtime::{
+    testing::Header,
+    traits::{BlakeTwo256, IdentityLookup},
+    Perbill,
+};
+
+impl_outer_origin! {
+    pub enum Origin for Test {}
+}
+
+mod anonify {
+    pub use crate::Event;
+}
+
+impl_outer_event! {
+    pub enum TestEvent for Test {
+        system<T>,
+        anonify<T>,
+    }
+}
+
+#[derive(Clone, Eq, PartialEq)]
+pub struct Test;
+
+parameter_types! {
+    pub const BlockHashCount: u64 = 250;
+    pub const MaximumBlockWeight: Weight = 1024 * 1_000_000_000;
+    pub const MaximumBlockLength: u32 = 2 * 1024;
+    pub const AvailableBlockRatio: Perbill = Perbill::from_percent(75);
+}
+
+impl system::Trait for Test {
+    type BaseCallFilter = ();
+    type Origin = Origin;
+    type Call = ();
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = TestEvent;
+    type BlockHashCount = BlockHashCount;
+    type MaximumBlockWeight = MaximumBlockWeight;
+    type DbWeight = ();
+    type BlockExecutionWeight = ();
+    type ExtrinsicBaseWeight = ();
+    type MaximumExtrinsicWeight = MaximumBlockWeight;
+    type MaximumBlockLength = MaximumBlockLength;
+    type AvailableBlockRatio = AvailableBlockRatio;
+    type Version = ();
+    type PalletInfo = ();
+    type AccountData = ();
+    type OnNewAccount = ();
+    type OnKilledAccount = ();
+    type SystemWeightInfo = ();
+}
+
+thread_local! {
+    /// The key which the mock IAS signs the reports with
+    pub static IAS_KEY: RSAPrivateKey =
+        RSAPrivateKey::new(&mut rand::thread_rng(), 1024).expect("failed to generate a key");
+}
+
+pub struct IasRsaModulus;
+impl Get<Vec<u8>> for IasRsaModulus {
+    fn get() -> Vec<u8> {
+        IAS_KEY.with(|key| key.n().to_bytes_be())
+    }
+}
+
+pub struct IasRsaExponent;
+impl Get<Vec<u8>> for IasRsaExponent {
+    fn get() -> Vec<u8> {
+        IAS_KEY.with(|key| key.e().to_bytes_be())
+    }
+}
+
+impl Trait for Test {
+    type Event = TestEvent;
+    type IasRsaModulus = IasRsaModulus;
+    type IasRsaExponent = IasRsaExponent;
+}
+
+pub type System = system::Module<Test>;
+pub type Anonify = Module<Test>;
+
+pub fn new_test_ext() -> sp_io::TestExternalities {
+    let t = system::GenesisConfig::default()
+        .build_storage::<Test>()
+        .unwrap();
+    let mut ext = sp_io::TestExternalities::new(t);
+    // Events are not recorded in the genesis block.
+    ext.execute_with(|| System::set_block_number(1));
+    ext
+}
+
+/// An enclave whose keys are attested by the reports of the mock IAS
+pub struct Enclave {
+    secret: secp256k1::SecretKey,
+    encrypting_key: Vec<u8>,
+}
+
+impl Enclave {
+    pub fn new(seed: u8) -> Self {
+        Enclave {
+            secret: secp256k1::SecretKey::parse(&[seed; 32]).unwrap(),
+            encrypting_key: vec![seed; ENCRYPTING_KEY_LEN],
+        }
+    }
+
+    fn verifying_key(&self) -> [u8; 20] {
+        let public = secp256k1::PublicKey::from_secret_key(&self.secret).serialize();
+        let mut address = [0u8; 20];
+        address.copy_from_slice(&keccak_256(&public[1..])[12..]);
+        address
+    }
+
+    /// The report attesting the keys of the enclave, and its signature by the mock IAS
+    pub fn report(&self, mrenclave: u8) -> (Vec<u8>, Vec<u8>) {
+        let mut quote = vec![0u8; 432];
+        quote[MRENCLAVE_OFFSET..MRENCLAVE_OFFSET + 32].copy_from_slice(&[mrenclave; 32]);
+        quote[VERIFYING_KEY_OFFSET..VERIFYING_KEY_OFFSET + 20]
+            .copy_from_slice(&self.verifying_key());
+        quote[ENCRYPTING_KEY_OFFSET..ENCRYPTING_KEY_OFFSET + ENCRYPTING_KEY_LEN]
+            .copy_from_slice(&self.encrypting_key);
+        let report =
+            format!(r#"{{"isvEnclaveQuoteBody":"{}"}}"#, base64::encode(quote)).into_bytes();
+        let report_sig = IAS_KEY.with(|key| {
+            key.sign(
+                PaddingScheme::new_pkcs1v15_sign(Some(Hash::SHA2_256)),
+                &sha2_256(&report),
+            )
+            .unwrap()
+        });
+
+        (report, report_sig)
+    }
+
+    pub fn sign(&self, msg: &[u8]) -> Vec<u8> {
+        let message = secp256k1::Message::parse(&sha2_256(msg));
+        let (sig, recovery_id) = secp256k1::sign(&message, &self.secret);
+        let mut sig = sig.serialize().to_vec();
+        sig.push(recovery_id.serialize() + 27);
+        sig
+    }
+}
+
+/// A handshake of the roster index, which is not decodable by the enclaves
+pub fn handshake(roster_idx: u32) -> Vec<u8> {
+    let mut handshake = vec![0u8; 8];
+    handshake[4..].copy_from_slice(&roster_idx.to_be_bytes());
+    handshake
+}
+
+/// Deploy a group owned by the account 1 with the report of the enclave.
+pub fn deploy(enclave: &Enclave) -> u64 {
+    let (report, report_sig) = enclave.report(1);
+    assert_ok!(Anonify::deploy(
+        Origin::signed(1),
+        report,
+        report_sig,
+        handshake(0),
+        0
+    ));
+    Anonify::group_id(&1, 0)
+}
@@ -0,0 +1,86 @@
+//! Verification of the IAS reports, as `ReportHandle.sol` does.
+
+use crate::VerifyingKey;
+use num_bigint::BigUint;
+use sp_std::prelude::*;
+
+// The offsets of the fields in the quote. See ReportHandle.sol.
+pub(crate) const MRENCLAVE_OFFSET: usize = 112;
+pub(crate) const VERIFYING_KEY_OFFSET: usize = 368;
+pub(crate) const ENCRYPTING_KEY_OFFSET: usize = 388;
+pub(crate) const ENCRYPTING_KEY_LEN: usize = 33;
+// The length of the base64 encoded quote body
+const QUOTE_BODY_LEN: usize = 576;
+// The length of `Body":"` which precedes the quote body in the report
+const QUOTE_BODY_PREFIX_LEN: usize = 7;
+
+// The DER encoded DigestInfo of SHA-256, which precedes the hash in PKCS#1 v1.5 signatures
+const SHA256_DIGEST_INFO: [u8; 19] = [
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01, 0x05,
+    0x00, 0x04, 0x20,
+];
+
+/// The keys and the measurement in the quote of a report
+pub(crate) struct QuoteData {
+    pub(crate) mrenclave: [u8; 32],
+    pub(crate) verifying_key: VerifyingKey,
+    pub(crate) encrypting_key: Vec<u8>,
+}
+
+/// Verify the RSASSA-PKCS1-v1_5 signature with SHA-256 of the report.
+pub(crate) fn verify_report_sig(
+    report: &[u8],
+    report_sig: &[u8],
+    modulus: &[u8],
+    exponent: &[u8],
+) -> bool {
+    let n = BigUint::from_bytes_be(modulus);
+    let k = ((n.bits() + 7) / 8) as usize;
+    if report_sig.len() != k || k < SHA256_DIGEST_INFO.len() + 32 + 11 {
+        return false;
+    }
+    let s = BigUint::from_bytes_be(report_sig);
+    if s >= n {
+        return false;
+    }
+
+    let m = s
+        .modpow(&BigUint::from_bytes_be(exponent), &n)
+        .to_bytes_be();
+    let mut em: Vec<u8> = sp_std::iter::repeat(0).take(k - m.len()).collect();
+    em.extend(m);
+
+    let hash = sp_io::hashing::sha2_256(report);
+    let padding_len = k - SHA256_DIGEST_INFO.len() - hash.len() - 3;
+    let mut expected = Vec::with_capacity(k);
+    expected.extend_from_slice(&[0x00, 0x01]);
+    expected.extend(sp_std::iter::repeat(0xff).take(padding_len));
+    expected.push(0x00);
+    expected.extend_from_slice(&SHA256_DIGEST_INFO);
+    expected.extend_from_slice(&hash);
+
+    em == expected
+}
+
+/// Extract the fields from `isvEnclaveQuoteBody` of the report.
+pub(crate) fn extract_quote(report: &[u8]) -> Option<QuoteData> {
+    let start = report.windows(4).position(|w| w == b"Body")? + QUOTE_BODY_PREFIX_LEN;
+    let body = report.get(start..start + QUOTE_BODY_LEN)?;
+    let quote = base64::decode(body).ok()?;
+    if quote.len() < ENCRYPTING_KEY_OFFSET + ENCRYPTING_KEY_LEN {
+        return None;
+    }
+
+    let mut mrenclave = [0u8; 32];
+    mrenclave.copy_from_slice(&quote[MRENCLAVE_OFFSET..MRENCLAVE_OFFSET + 32]);
+    let mut verifying_key = [0u8; 20];
+    verifying_key.copy_from_slice(&quote[VERIFYING_KEY_OFFSET..VERIFYING_KEY_OFFSET + 20]);
+    let encrypting_key =
+        quote[ENCRYPTING_KEY_OFFSET..ENCRYPTING_KEY_OFFSET + ENCRYPTING_KEY_LEN].to_vec();
+
+    Some(QuoteData {
+        mrenclave,
+        verifying_key,
+        encrypting_key,
+    })
+}
@@ -0,0 +1,252 @@
+use crate::{mock::*, Call, Error, Event, RawEvent};
+use frame_support::{assert_noop, assert_ok, weights::GetDispatchInfo};
+use sp_io::hashing::sha2_256;
+
+#[test]
+fn test_report_signature() {
+    new_test_ext().execute_with(|| {
+        let (report, mut report_sig) = Enclave::new(1).report(1);
+        report_sig[0] ^= 1;
+        assert_noop!(
+            Anonify::deploy(Origin::signed(1), report, report_sig, handshake(0), 0),
+            Error::<Test>::InvalidReportSig
+        );
+    });
+}
+
+#[test]
+fn test_roster_ordering() {
+    new_test_ext().execute_with(|| {
+        let enclaves: Vec<Enclave> = (1..=3).map(Enclave::new).collect();
+        let group_id = deploy(&enclaves[0]);
+
+        let (report, report_sig) = enclaves[1].report(1);
+        assert_noop!(
+            Anonify::join_group(
+                Origin::signed(2),
+                group_id,
+                report.clone(),
+                report_sig.clone(),
+                handshake(2),
+                0,
+                2
+            ),
+            Error::<Test>::RosterIdxNotOrdered
+        );
+        let (other_report, other_report_sig) = enclaves[1].report(2);
+        assert_noop!(
+            Anonify::join_group(
+                Origin::signed(2),
+                group_id,
+                other_report,
+                other_report_sig,
+                handshake(1),
+                0,
+                1
+            ),
+            Error::<Test>::InvalidMrenclave
+        );
+        assert_ok!(Anonify::join_group(
+            Origin::signed(2),
+            group_id,
+            report.clone(),
+            report_sig.clone(),
+            handshake(1),
+            0,
+            1
+        ));
+        // The keys are registered only once
+        assert_noop!(
+            Anonify::join_group(
+                Origin::signed(3),
+                group_id,
+                report,
+                report_sig,
+                handshake(2),
+                0,
+                2
+            ),
+            Error::<Test>::VerifyingKeyRegistered
+        );
+        let (report, report_sig) = enclaves[2].report(1);
+        assert_ok!(Anonify::join_group(
+            Origin::signed(3),
+            group_id,
+            report,
+            report_sig,
+            handshake(2),
+            0,
+            2
+        ));
+
+        assert_eq!(Anonify::roster_idx(group_id, 3), 2);
+        let handshakes = System::events()
+            .into_iter()
+            .filter(|record| match record.event {
+                TestEvent::anonify(RawEvent::StoreHandshake(..)) => true,
+                _ => false,
+            })
+            .count();
+        assert_eq!(handshakes, 3);
+    });
+}
+
+#[test]
+fn test_enclave_signature() {
+    new_test_ext().execute_with(|| {
+        let (enclave, other) = (Enclave::new(1), Enclave::new(2));
+        let group_id = deploy(&enclave);
+
+        let ciphertext = b"ciphertext".to_vec();
+        assert_noop!(
+            Anonify::store_command(
+                Origin::signed(1),
+                group_id,
+                ciphertext.clone(),
+                other.sign(&ciphertext)
+            ),
+            Error::<Test>::InvalidEnclaveSig
+        );
+        let mut malformed = enclave.sign(&ciphertext);
+        malformed[64] -= 27;
+        assert_noop!(
+            Anonify::store_command(Origin::signed(1), group_id, ciphertext.clone(), malformed),
+            Error::<Test>::InvalidSignature
+        );
+        assert_ok!(Anonify::store_command(
+            Origin::signed(1),
+            group_id,
+            ciphertext.clone(),
+            enclave.sign(&ciphertext)
+        ));
+
+        let msg = [&handshake(1)[..], &1u32.to_be_bytes()].concat();
+        assert_noop!(
+            Anonify::handshake(
+                Origin::signed(1),
+                group_id,
+                handshake(1),
+                enclave.sign(&msg),
+                1
+            ),
+            Error::<Test>::RosterIdxMismatch
+        );
+        let msg = [&handshake(1)[..], &0u32.to_be_bytes()].concat();
+        assert_ok!(Anonify::handshake(
+            Origin::signed(1),
+            group_id,
+            handshake(1),
+            enclave.sign(&msg),
+            0
+        ));
+
+        let event = System::events().last().unwrap().event.clone();
+        assert_eq!(
+            event,
+            TestEvent::anonify(Event::<Test>::StoreHandshake(group_id, handshake(1)))
+        );
+    });
+}
+
+#[test]
+fn test_ciphertext_hash() {
+    new_test_ext().execute_with(|| {
+        let (enclave, other) = (Enclave::new(1), Enclave::new(2));
+        let group_id = deploy(&enclave);
+
+        // The enclave signs the hash of the ciphertext in the same way as `store_command`.
+        let ciphertext = b"ciphertext";
+        let ciphertext_hash = sha2_256(ciphertext);
+        assert_noop!(
+            Anonify::store_command_hash(
+                Origin::signed(1),
+                group_id,
+                ciphertext_hash,
+                other.sign(ciphertext)
+            ),
+            Error::<Test>::InvalidEnclaveSig
+        );
+        assert_ok!(Anonify::store_command_hash(
+            Origin::signed(1),
+            group_id,
+            ciphertext_hash,
+            enclave.sign(ciphertext)
+        ));
+
+        let event = System::events().last().unwrap().event.clone();
+        assert_eq!(
+            event,
+            TestEvent::anonify(Event::<Test>::StoreCiphertextHash(
+                group_id,
+                ciphertext_hash
+            ))
+        );
+    });
+}
+
+#[test]
+fn test_register_report() {
+    new_test_ext().execute_with(|| {
+        let (enclave, rotated) = (Enclave::new(1), Enclave::new(2));
+        let group_id = deploy(&enclave);
+
+        let (report, report_sig) = rotated.report(1);
+        assert_noop!(
+            Anonify::register_report(
+                Origin::signed(2),
+                group_id,
+                report.clone(),
+                report_sig.clone(),
+                0,
+                0
+            ),
+            Error::<Test>::NotMember
+        );
+        assert_noop!(
+            Anonify::register_report(
+                Origin::signed(1),
+                group_id,
+                report.clone(),
+                report_sig.clone(),
+                0,
+                1
+            ),
+            Error::<Test>::RosterIdxMismatch
+        );
+        assert_ok!(Anonify::register_report(
+            Origin::signed(1),
+            group_id,
+            report,
+            report_sig,
+            0,
+            0
+        ));
+        assert_eq!(Anonify::roster_idx(group_id, 1), 0);
+
+        // A non-member cannot store a handshake as the owner's roster index either.
+        let msg = [&handshake(0)[..], &0u32.to_be_bytes()].concat();
+        assert_noop!(
+            Anonify::handshake(
+                Origin::signed(2),
+                group_id,
+                handshake(0),
+                rotated.sign(&msg),
+                0
+            ),
+            Error::<Test>::NotMember
+        );
+    });
+}
+
+#[test]
+fn test_weight_scales_with_input_length() {
+    let (report, report_sig) = (vec![0u8; 100], vec![0u8; 256]);
+    let short = Call::<Test>::store_command(1, vec![0u8; 10], vec![0u8; 65]).get_dispatch_info();
+    let long = Call::<Test>::store_command(1, vec![0u8; 10_000], vec![0u8; 65]).get_dispatch_info();
+    assert!(long.weight > short.weight);
+
+    let register = Call::<Test>::register_report(1, report.clone(), report_sig.clone(), 0, 0)
+        .get_dispatch_info();
+    let deploy = Call::<Test>::deploy(report, report_sig, handshake(0), 0).get_dispatch_info();
+    assert!(deploy.weight > register.weight);
+}
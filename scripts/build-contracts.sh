@@ -0,0 +1,19 @@
+#!/bin/bash
+
+set -e
+
+# Compile the contracts into contract-build.
+# With DEV=1, contracts/dev overlays the contracts so that the reports signed by the mock IAS (tests/mock-ias) are accepted.
+
+dirpath=$(cd $(dirname $0) && pwd)
+cd "${dirpath}/.."
+
+if [ "${DEV}" = "1" ]; then
+    workdir=$(mktemp -d)
+    trap "rm -rf ${workdir}" EXIT
+    cp -r contracts/. ${workdir}
+    cp contracts/dev/*.sol ${workdir}
+    solc -o contract-build --bin --abi --optimize --overwrite ${workdir}/Anonify.sol
+else
+    solc -o contract-build --bin --abi --optimize --overwrite contracts/Anonify.sol
+fi
@@ -3,17 +3,72 @@
 set -e
 
 export ETH_URL=http://172.18.0.2:8545
+# The substrate backend (anonify-substrate-driver with pallet-anonify) does not support the mrenclave governance
+# (MRENCLAVE_APPROVAL_PERCENT, MRENCLAVE_TIMELOCK_BLOCKS), the state commitments (STATE_COMMITMENT_INTERVAL_SECS),
+# ETH_WS_URL or ETH_KEYFILE: the proposals and the commitments fail as unsupported, and no state root is recorded.
 export ANONIFY_URL=172.18.0.3:8080 # depends on the container's ip in the docker network
 export ABI_PATH=../../../contract-build/Anonify.abi
 export BIN_PATH=../../../contract-build/Anonify.bin
 export MY_ROSTER_IDX=0
 export MAX_ROSTER_IDX=2
+# Transactions are regarded as confirmed after this many blocks, including the one containing them, are built
 export CONFIRMATIONS=1
+# Transactions not mined in this many seconds are replaced with higher gas prices
+export TX_REPLACEMENT_TIMEOUT_SECS=60
+# Mined transactions not confirmed in this many seconds are regarded as failed
+export TX_CONFIRMATION_TIMEOUT_SECS=600
+# A new mrenclave proposed by a member is approved by more than this percentage of the members, and at least two,
+# and executed after this many blocks. They are fixed when the contract is deployed.
+export MRENCLAVE_APPROVAL_PERCENT=50
+export MRENCLAVE_TIMELOCK_BLOCKS=5760
+# The interval of fetching events is doubled on each consecutive error up to this many seconds
+export SYNC_BC_MAX_BACKOFF_SECS=60
+# The signed state commitments are published by the sync service at this interval if set
+# export STATE_COMMITMENT_INTERVAL_SECS=600
+# Events are inserted into the enclave after this many blocks are built on top of them
+export EVENT_CONFIRMATIONS=6
+# Events are received by eth_subscribe if set, otherwise polled by eth_getLogs
+# export ETH_WS_URL=ws://172.18.0.2:8546
+# Ciphertexts of commands are kept in this off-chain store and only their hashes are sent to the contract if set.
+# All the nodes of a contract must share the store: a directory (BLOB_STORE_DIR) or an HTTP blob server (BLOB_STORE_URL).
+# export BLOB_STORE_DIR=.anonify/blobs
+# export BLOB_STORE_URL=http://172.18.0.4:8000/blobs
+# The fetched blocks, the logs inserted into the enclave and the dead letters of each contract are persisted
+# in <contract address>.checkpoint in this directory if set.
+# They are restored only into an enclave in the state they were taken after, since the restored logs are never inserted again,
+# so the server, whose enclaves start empty, refuses to serve a contract with a checkpoint: remove it to fetch all the events again.
+# export EVENT_CACHE_DIR=.anonify/event_cache
 export ACCOUNT_INDEX=1
 export PASSWORD=anonify0101
+# Transactions are signed with this encrypted key and sent via eth_sendRawTransaction if set,
+# instead of by the node account unlocked with ACCOUNT_INDEX and PASSWORD.
+# It is a Web3 Secret Storage (v3) keystore, e.g. created by `geth account new` or exported from MetaMask.
+# export ETH_KEYFILE=
+# export ETH_KEYFILE_PASSWORD_FILE=
 export ENCLAVE_PKG_NAME=erc20
 
 export SPID=
 export IAS_URL=https://api.trustedservices.intel.com/sgx/dev/attestation/v3/report
 export SUB_KEY=
+# Root certificate of the report signing key, only read by debug builds (e.g. tests/mock-ias/certs/root_cert.pem for the mock IAS)
+export IAS_ROOT_CERT_PATH=
 export MRA_TLS_SERVER_ADDRESS=localhost:12345
+# Comma-separated key-vault addresses which path secret shares are backed up to (defaults to MRA_TLS_SERVER_ADDRESS)
+# Path secrets are owned by MRENCLAVE, so a key-vault must not be shared with untrusted groups running the same enclave
+export KEY_VAULT_ENDPOINTS=localhost:12345
+export KEY_VAULT_THRESHOLD=1
+# "epid" (IAS) or "dcap" (ECDSA quotes verified with the collateral from PCCS)
+export ATTESTATION_TYPE=epid
+export PCCS_URL=https://localhost:8081
+export DCAP_ROOT_CERT_PATH=
+# Attested evidence is reused until it gets older than this, and refreshed in the background after half of it
+export ATTESTATION_MAX_AGE_SECS=21600
+# Comma-separated quote statuses accepted besides OK (defaults to GROUP_OUT_OF_DATE), e.g. SW_HARDENING_NEEDED after risk review
+export ACCEPTED_QUOTE_STATUSES=GROUP_OUT_OF_DATE
+# Comma-separated advisory IDs allowed for the accepted statuses (any advisories if unset)
+# export ALLOWED_ADVISORY_IDS=INTEL-SA-00334
+# Reject IAS reports older than this if set
+# export MAX_REPORT_AGE_SECS=86400
+# The identity key of each contract is sealed to <contract address>.sealed in this directory
+# and reloaded on restart (a new key on every start if unset).
+export IDENTITY_KEY_DIR=.anonify/identity_keys
@@ -4,13 +4,14 @@ set -e
 
 source /root/.docker_bashrc
 export PATH=~/.cargo/bin:$PATH
-export SGX_MODE=HW
+export SGX_MODE=${SGX_MODE:-HW}
 export RUSTFLAGS=-Ctarget-feature=+aes,+sse2,+sse4.1,+ssse3
 ANONIFY_ROOT=/root/anonify
 
 dirpath=$(cd $(dirname $0) && pwd)
 cd "${dirpath}/.."
-solc -o contract-build --bin --abi --optimize --overwrite contracts/Anonify.sol
+# Tests attest enclaves with the mock IAS, so the contract verifies reports with its signing key.
+DEV=1 ./scripts/build-contracts.sh
 
 cd frame/types
 cargo build
@@ -49,7 +50,7 @@ cd ${ANONIFY_ROOT}/scripts
 make DEBUG=1 TEST=1 ENCLAVE_DIR=tests/units/enclave
 
 cd ${ANONIFY_ROOT}
-RUST_BACKTRACE=1 RUST_LOG=debug TEST=1 cargo test -p unit-tests-host -p anonify-eth-driver -p frame-runtime -- --nocapture
+RUST_BACKTRACE=1 RUST_LOG=debug TEST=1 cargo test -p unit-tests-host -p anonify-eth-driver -p anonify-ledger-driver -p anonify-substrate-driver -p pallet-anonify -p frame-runtime -- --nocapture
 
 # Buildings
 
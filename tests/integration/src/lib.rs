@@ -1,56 +1,58 @@
 #[macro_use]
 extern crate lazy_static;
-use anonify_eth_driver::{dispatcher::*, eth::*, EventCache};
+use anonify_eth_driver::{
+    dispatcher::*, eth::event_watcher::EthEvent, mock::*, DeadLetter, EventCache, HostError,
+    Payload, PayloadType, SkipReason,
+};
 use codec::{Decode, Encode};
 use erc20_state_transition::{
     approve, burn, construct, mint, transfer, transfer_from, CallName, MemName, CIPHERTEXT_SIZE,
 };
-use ethabi::Contract as ContractABI;
 use frame_common::{
-    crypto::{AccountId, Ed25519ChallengeResponse, COMMON_ACCESS_POLICY},
+    crypto::{AccountId, Ciphertext, Ed25519ChallengeResponse, COMMON_ACCESS_POLICY},
     traits::*,
 };
 use frame_host::EnclaveDir;
 use frame_runtime::primitives::{Approved, U64};
 use frame_treekem::{DhPubKey, EciesCiphertext};
+use ias_verifier::ReportVerifier;
 use sgx_types::*;
-use std::{collections::BTreeMap, env, fs::File, io::BufReader, str::FromStr};
-use web3::{
-    contract::{Contract, Options},
-    transports::Http,
-    types::Address,
-    Web3,
-};
+use std::{collections::BTreeMap, env, fs, str::FromStr};
+use web3::types::Address;
 
-const ETH_URL: &str = "http://172.28.0.2:8545";
+/// The mock ledger shared by the tests, each of which deploys its own contract.
+const ETH_URL: &str = "mock://integration";
 const ABI_PATH: &str = "../../contract-build/Anonify.abi";
 const BIN_PATH: &str = "../../contract-build/Anonify.bin";
 const CONFIRMATIONS: usize = 0;
 const ACCOUNT_INDEX: usize = 0;
 const PASSWORD: &str = "anonify0101";
 
-pub async fn get_encrypting_key(
+pub fn get_encrypting_key(
     contract_addr: &str,
-    dispatcher: &Dispatcher<EthDeployer, EthSender, EventWatcher>,
+    dispatcher: &Dispatcher<MockDeployer, MockSender, MockWatcher>,
 ) -> DhPubKey {
-    let encrypting_key = dispatcher.get_encrypting_key().unwrap();
-    let transport = Http::new(ETH_URL).unwrap();
-    let web3 = Web3::new(transport);
-    let web3_conn = web3.eth();
-
+    let output = dispatcher.get_encrypting_key().unwrap();
+    let ledger = MockLedger::connect(ETH_URL);
     let address = Address::from_str(contract_addr).unwrap();
-    let f = File::open(ABI_PATH).unwrap();
-    let abi = ContractABI::load(BufReader::new(f)).unwrap();
-
-    let query_encrypting_key: Vec<u8> = Contract::new(web3_conn, address, abi)
-        .query(
-            "getEncryptingKey",
-            encrypting_key.encode(),
-            None,
-            Options::default(),
-            None,
+
+    // The encrypting key must be attested by the enclave registered to the contract.
+    let mrenclave = ledger.mrenclave(address).unwrap();
+    let root_cert = fs::read(mock_ias::root_cert_path()).unwrap();
+    ReportVerifier::from_pem(&root_cert)
+        .unwrap()
+        .verify_encrypting_key(
+            &output.encrypting_key_as_ref().encode(),
+            output.report(),
+            output.report_sig(),
+            output.report_cert(),
+            &mrenclave,
         )
-        .await
+        .unwrap();
+    let encrypting_key = output.encrypting_key();
+
+    let query_encrypting_key = ledger
+        .get_encrypting_key(address, &encrypting_key.encode())
         .unwrap();
 
     assert_eq!(
@@ -61,7 +63,7 @@ pub async fn get_encrypting_key(
 }
 
 #[actix_rt::test]
-async fn test_integration_eth_construct() {
+async fn test_integration_construct() {
     set_env_vars();
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
@@ -70,7 +72,7 @@ async fn test_integration_eth_construct() {
     let gas = 5_000_000;
     let cache = EventCache::default();
     let dispatcher =
-        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+        Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(eid, ETH_URL, cache).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher
@@ -99,7 +101,7 @@ async fn test_integration_eth_construct() {
 
     // Init state
     let total_supply = U64::from_raw(100);
-    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher);
     let init_cmd = construct { total_supply };
     let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
     let receipt = dispatcher
@@ -145,7 +147,7 @@ async fn test_auto_notification() {
     let gas = 5_000_000;
     let cache = EventCache::default();
     let dispatcher =
-        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+        Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(eid, ETH_URL, cache).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher
@@ -172,7 +174,7 @@ async fn test_auto_notification() {
     dispatcher.fetch_events::<U64>().await.unwrap();
 
     // Init state
-    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher);
     let total_supply = U64::from_raw(100);
     let init_cmd = construct { total_supply };
     let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
@@ -230,7 +232,7 @@ async fn test_auto_notification() {
 }
 
 #[actix_rt::test]
-async fn test_integration_eth_transfer() {
+async fn test_integration_transfer() {
     set_env_vars();
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
@@ -241,7 +243,7 @@ async fn test_integration_eth_transfer() {
     let gas = 5_000_000;
     let cache = EventCache::default();
     let dispatcher =
-        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+        Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(eid, ETH_URL, cache).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher
@@ -269,7 +271,7 @@ async fn test_integration_eth_transfer() {
 
     // Init state
     let total_supply = U64::from_raw(100);
-    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher);
     let init_cmd = construct { total_supply };
     let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
     let receipt = dispatcher
@@ -350,7 +352,7 @@ async fn test_key_rotation() {
     let gas = 5_000_000;
     let cache = EventCache::default();
     let dispatcher =
-        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+        Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(eid, ETH_URL, cache).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher
@@ -388,7 +390,7 @@ async fn test_key_rotation() {
 
     // init state
     let total_supply = U64::from_raw(100);
-    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher);
     let init_cmd = construct { total_supply };
     let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
     let receipt = dispatcher
@@ -422,7 +424,7 @@ async fn test_key_rotation() {
 }
 
 #[actix_rt::test]
-async fn test_integration_eth_approve() {
+async fn test_integration_approve() {
     set_env_vars();
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
@@ -432,7 +434,7 @@ async fn test_integration_eth_approve() {
     let gas = 5_000_000;
     let cache = EventCache::default();
     let dispatcher =
-        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+        Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(eid, ETH_URL, cache).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher
@@ -460,7 +462,7 @@ async fn test_integration_eth_approve() {
 
     // Init state
     let total_supply = U64::from_raw(100);
-    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher);
     let init_cmd = construct { total_supply };
     let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
     let receipt = dispatcher
@@ -526,7 +528,7 @@ async fn test_integration_eth_approve() {
 }
 
 #[actix_rt::test]
-async fn test_integration_eth_transfer_from() {
+async fn test_integration_transfer_from() {
     set_env_vars();
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
@@ -537,7 +539,7 @@ async fn test_integration_eth_transfer_from() {
     let gas = 5_000_000;
     let cache = EventCache::default();
     let dispatcher =
-        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+        Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(eid, ETH_URL, cache).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher
@@ -565,7 +567,7 @@ async fn test_integration_eth_transfer_from() {
 
     // Init state
     let total_supply = U64::from_raw(100);
-    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher);
     let init_cmd = construct { total_supply };
     let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
     let receipt = dispatcher
@@ -722,7 +724,7 @@ async fn test_integration_eth_transfer_from() {
 }
 
 #[actix_rt::test]
-async fn test_integration_eth_mint() {
+async fn test_integration_mint() {
     set_env_vars();
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
@@ -732,7 +734,7 @@ async fn test_integration_eth_mint() {
     let gas = 5_000_000;
     let cache = EventCache::default();
     let dispatcher =
-        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+        Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(eid, ETH_URL, cache).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher
@@ -760,7 +762,7 @@ async fn test_integration_eth_mint() {
 
     // Init state
     let total_supply = U64::from_raw(100);
-    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher);
     let init_cmd = construct { total_supply };
     let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
     let receipt = dispatcher
@@ -816,7 +818,7 @@ async fn test_integration_eth_mint() {
 }
 
 #[actix_rt::test]
-async fn test_integration_eth_burn() {
+async fn test_integration_burn() {
     set_env_vars();
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let eid = enclave.geteid();
@@ -826,7 +828,7 @@ async fn test_integration_eth_burn() {
     let gas = 5_000_000;
     let cache = EventCache::default();
     let dispatcher =
-        Dispatcher::<EthDeployer, EthSender, EventWatcher>::new(eid, ETH_URL, cache).unwrap();
+        Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(eid, ETH_URL, cache).unwrap();
 
     // Deploy
     let deployer_addr = dispatcher
@@ -854,7 +856,7 @@ async fn test_integration_eth_burn() {
 
     // Init state
     let total_supply = U64::from_raw(100);
-    let pubkey = get_encrypting_key(&contract_addr, &dispatcher).await;
+    let pubkey = get_encrypting_key(&contract_addr, &dispatcher);
     let init_cmd = construct { total_supply };
     let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
     let receipt = dispatcher
@@ -931,17 +933,223 @@ lazy_static! {
     pub static ref ENV_LOGGER_INIT: () = env_logger::init();
 }
 
+#[actix_rt::test]
+async fn test_mock_ledger_transfer() {
+    set_env_vars();
+    let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+    let eid = enclave.geteid();
+    let my_access_policy = Ed25519ChallengeResponse::new_from_rng().unwrap();
+    let other_access_policy = Ed25519ChallengeResponse::new_from_rng().unwrap();
+
+    let gas = 5_000_000;
+    let cache = EventCache::default();
+    let dispatcher = Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(
+        eid,
+        "mock://test_mock_ledger_transfer",
+        cache,
+    )
+    .unwrap();
+
+    // Deploy
+    let deployer_addr = dispatcher
+        .get_account(ACCOUNT_INDEX, PASSWORD)
+        .await
+        .unwrap();
+    let (contract_addr, _) = dispatcher
+        .deploy(deployer_addr, gas, ABI_PATH, BIN_PATH, CONFIRMATIONS)
+        .await
+        .unwrap();
+    dispatcher
+        .set_contract_addr(&contract_addr, ABI_PATH)
+        .unwrap();
+
+    // Get handshake from the ledger
+    dispatcher.fetch_events::<U64>().await.unwrap();
+
+    // Init state
+    let total_supply = U64::from_raw(100);
+    let pubkey = dispatcher.get_encrypting_key().unwrap().encrypting_key();
+    let init_cmd = construct { total_supply };
+    let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
+    dispatcher
+        .send_command::<CallName, _>(
+            my_access_policy.clone(),
+            encrypted_command,
+            "construct",
+            deployer_addr,
+            gas,
+        )
+        .await
+        .unwrap();
+
+    // Transfer
+    let amount = U64::from_raw(30);
+    let recipient = other_access_policy.into_account_id();
+    let transfer_cmd = transfer { amount, recipient };
+    let encrypted_command = EciesCiphertext::encrypt(&pubkey, transfer_cmd.encode()).unwrap();
+    dispatcher
+        .send_command::<CallName, _>(
+            my_access_policy.clone(),
+            encrypted_command,
+            "transfer",
+            deployer_addr,
+            gas,
+        )
+        .await
+        .unwrap();
+
+    // Both commands are fetched in order from the ledger
+    dispatcher.fetch_events::<U64>().await.unwrap();
+
+    let my_state = dispatcher
+        .get_state::<U64, _, CallName>(my_access_policy, "balance_of")
+        .unwrap();
+    let other_state = dispatcher
+        .get_state::<U64, _, CallName>(other_access_policy, "balance_of")
+        .unwrap();
+    assert_eq!(my_state, U64::from_raw(70));
+    assert_eq!(other_state, amount);
+}
+
+#[actix_rt::test]
+async fn test_replay_dead_letter() {
+    set_env_vars();
+    let enclave = EnclaveDir::new().init_enclave(true).unwrap();
+    let eid = enclave.geteid();
+    let my_access_policy = Ed25519ChallengeResponse::new_from_rng().unwrap();
+    let eth_url = "mock://test_replay_dead_letter";
+
+    let gas = 5_000_000;
+    let cache = EventCache::default();
+    let dispatcher =
+        Dispatcher::<MockDeployer, MockSender, MockWatcher>::new(eid, eth_url, cache.clone())
+            .unwrap();
+
+    // Deploy
+    let deployer_addr = dispatcher
+        .get_account(ACCOUNT_INDEX, PASSWORD)
+        .await
+        .unwrap();
+    let (contract_addr, _) = dispatcher
+        .deploy(deployer_addr, gas, ABI_PATH, BIN_PATH, CONFIRMATIONS)
+        .await
+        .unwrap();
+    dispatcher
+        .set_contract_addr(&contract_addr, ABI_PATH)
+        .unwrap();
+
+    // Get handshake from the ledger
+    dispatcher.fetch_events::<U64>().await.unwrap();
+
+    // Init state, whose ciphertext is taken from the ledger as a dead letter instead of being fetched
+    let total_supply = U64::from_raw(100);
+    let pubkey = dispatcher.get_encrypting_key().unwrap().encrypting_key();
+    let init_cmd = construct { total_supply };
+    let encrypted_command = EciesCiphertext::encrypt(&pubkey, init_cmd.encode()).unwrap();
+    dispatcher
+        .send_command::<CallName, _>(
+            my_access_policy.clone(),
+            encrypted_command,
+            "construct",
+            deployer_addr,
+            gas,
+        )
+        .await
+        .unwrap();
+    let ciphertext = stored_ciphertexts(eth_url, &contract_addr).pop().unwrap();
+    let insert_dead_letter = |ciphertext: Ciphertext| {
+        let payload = PayloadType::new(
+            ciphertext.roster_idx(),
+            ciphertext.epoch(),
+            ciphertext.generation(),
+            Payload::Ciphertext(ciphertext),
+        );
+        cache.inner().write().insert_dead_letter(
+            DeadLetter::new(SkipReason::Enclave("no path secret".to_string())).set_payload(payload),
+        )
+    };
+
+    // A dead letter without the payload is not replayable, and is kept.
+    let not_replayable = cache
+        .inner()
+        .write()
+        .insert_dead_letter(DeadLetter::new(SkipReason::Decode("invalid".to_string())));
+    assert!(matches!(
+        dispatcher.replay_dead_letter::<U64>(not_replayable),
+        Err(HostError::DeadLetterNotReplayable(_))
+    ));
+    assert!(matches!(
+        dispatcher.replay_dead_letter::<U64>(not_replayable + 100),
+        Err(HostError::DeadLetterNotFound(_))
+    ));
+    assert_eq!(dispatcher.dead_letters().len(), 1);
+
+    // The dead letter is removed once it is inserted into the enclave.
+    let id = insert_dead_letter(ciphertext.clone());
+    dispatcher.replay_dead_letter::<U64>(id).unwrap();
+    assert!(dispatcher
+        .dead_letters()
+        .iter()
+        .all(|letter| letter.id() != id));
+    let my_state = dispatcher
+        .get_state::<U64, _, CallName>(my_access_policy, "balance_of")
+        .unwrap();
+    assert_eq!(my_state, total_supply);
+
+    // The dead letter is restored with the new reason if the enclave fails again.
+    let corrupted = Ciphertext::new(
+        ciphertext.generation() + 1,
+        ciphertext.epoch(),
+        ciphertext.roster_idx(),
+        vec![0; ciphertext.encrypted_state_ref().len()],
+    );
+    let id = insert_dead_letter(corrupted);
+    assert!(dispatcher.replay_dead_letter::<U64>(id).is_err());
+    let letter = dispatcher
+        .dead_letters()
+        .into_iter()
+        .find(|letter| letter.id() == id)
+        .unwrap();
+    assert!(letter.is_replayable());
+    assert_ne!(
+        letter.reason(),
+        &SkipReason::Enclave("no path secret".to_string())
+    );
+    assert_eq!(dispatcher.dead_letters().len(), 2);
+}
+
+/// The ciphertexts stored in the contract on the mock ledger, in the order of the logs
+fn stored_ciphertexts(eth_url: &str, contract_addr: &str) -> Vec<Ciphertext> {
+    let ledger = MockLedger::connect(eth_url);
+    let signature = EthEvent::create_event().ciphertext_signature();
+    ledger
+        .get_logs(
+            Address::from_str(contract_addr).unwrap(),
+            0,
+            ledger.block_number(),
+        )
+        .into_iter()
+        .filter(|log| log.topics[0] == signature)
+        .map(|log| {
+            let data = ethabi::decode(&[ethabi::ParamType::Bytes], &log.data.0)
+                .unwrap()
+                .pop()
+                .and_then(|token| token.to_bytes())
+                .unwrap();
+            Ciphertext::decode(&mut &data[..]).unwrap()
+        })
+        .collect()
+}
+
 pub fn set_env_vars() {
     *ENV_LOGGER_INIT;
     env::set_var("RUST_LOG", "DEBUG");
     env::set_var("MY_ROSTER_IDX", "0");
     env::set_var("MAX_ROSTER_IDX", "2");
     env::set_var("SPID", "2C149BFC94A61D306A96211AED155BE9");
-    env::set_var(
-        "IAS_URL",
-        "https://api.trustedservices.intel.com/sgx/dev/attestation/v3/report",
-    );
     env::set_var("SUB_KEY", "77e2533de0624df28dc3be3a5b9e50d9");
+    // Sets IAS_URL and IAS_ROOT_CERT_PATH
+    mock_ias::set_mock_ias_env();
     env::set_var("MRA_TLS_SERVER_ADDRESS", "localhost:12345");
     env::set_var("AUDITOR_ENDPOINT", "test");
     env::set_var("ENCLAVE_PKG_NAME", "erc20");
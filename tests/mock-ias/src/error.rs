@@ -0,0 +1,19 @@
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, MockIasError>;
+
+#[derive(Error, Debug)]
+pub enum MockIasError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("Serde json error: {0}")]
+    SerdeJsonError(#[from] serde_json::Error),
+    #[error("Base64 decode error: {0}")]
+    Base64Error(#[from] base64::DecodeError),
+    #[error("Invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("Invalid signing key: {0}")]
+    InvalidSigningKey(String),
+    #[error("Failed to sign the report")]
+    SigningError,
+}
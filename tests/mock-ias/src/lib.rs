@@ -0,0 +1,276 @@
+//! A mock of Intel Attestation Service for the end-to-end tests without network.
+//! Reports are signed with the test key in `certs/` instead of Intel's one,
+//! so enclaves have to be built in debug mode with `IAS_ROOT_CERT_PATH` pointing at `certs/root_cert.pem`
+//! and the contract has to be built with `DEV=1 scripts/build-contracts.sh`.
+
+mod error;
+
+pub use crate::error::{MockIasError, Result};
+use chrono::Utc;
+use log::{debug, error};
+use once_cell::sync::OnceCell;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use ring::{rand::SystemRandom, signature};
+use serde_json::Value;
+use std::{
+    env,
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    thread,
+};
+
+const SIGNING_KEY: &[u8] = include_bytes!("../certs/signing_key.pk8");
+const SIGNING_CERT: &str = include_str!("../certs/signing_cert.pem");
+const ROOT_CERT: &str = include_str!("../certs/root_cert.pem");
+const REPORT_PATH: &str = "/sgx/dev/attestation/v3/report";
+const REPORT_VERSION: u64 = 3;
+const QUOTE_STATUS_OK: &str = "OK";
+// The quote body in reports excludes `signature_len` and `signature` of `sgx_quote_t`.
+const QUOTE_BODY_LEN: usize = 432;
+
+static MOCK_IAS: OnceCell<MockIasServer> = OnceCell::new();
+
+/// The path of the PEM-encoded root certificate which the mock IAS's reports are verified with.
+pub fn root_cert_path() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("certs/root_cert.pem")
+}
+
+/// The DER-encoded certificate of the report signing key, as sent in `X-IASReport-Signing-Certificate`.
+pub fn signing_cert() -> Result<Vec<u8>> {
+    let body: String = SIGNING_CERT
+        .lines()
+        .filter(|l| !l.starts_with("-----"))
+        .collect();
+    base64::decode(body).map_err(Into::into)
+}
+
+/// Start the mock IAS once in this process, then point `IAS_URL` and `IAS_ROOT_CERT_PATH` at it.
+pub fn set_mock_ias_env() {
+    let server = MOCK_IAS.get_or_init(|| {
+        MockIas::new()
+            .and_then(|ias| ias.start("127.0.0.1:0"))
+            .expect("Failed to start the mock IAS")
+    });
+    env::set_var("IAS_URL", server.report_url());
+    env::set_var("IAS_ROOT_CERT_PATH", root_cert_path());
+}
+
+/// A mock IAS which accepts any quote and returns a report signed by the test key
+pub struct MockIas {
+    key_pair: signature::RsaKeyPair,
+    rng: SystemRandom,
+    quote_status: String,
+    report_id: AtomicU64,
+}
+
+impl MockIas {
+    pub fn new() -> Result<Self> {
+        let key_pair = signature::RsaKeyPair::from_pkcs8(SIGNING_KEY)
+            .map_err(|e| MockIasError::InvalidSigningKey(e.to_string()))?;
+
+        Ok(MockIas {
+            key_pair,
+            rng: SystemRandom::new(),
+            quote_status: QUOTE_STATUS_OK.to_string(),
+            report_id: AtomicU64::new(0),
+        })
+    }
+
+    /// Set `isvEnclaveQuoteStatus` of the returned reports to test the attestation policies.
+    pub fn set_quote_status(mut self, quote_status: &str) -> Self {
+        self.quote_status = quote_status.to_string();
+        self
+    }
+
+    /// Listen on the address and serve requests in a background thread.
+    pub fn start(self, address: &str) -> Result<MockIasServer> {
+        let listener = TcpListener::bind(address)?;
+        let local_addr = listener.local_addr()?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let res = stream
+                    .map_err(Into::into)
+                    .and_then(|stream| self.handle_connection(stream));
+                if let Err(e) = res {
+                    error!("Failed to handle the request to mock IAS: {}", e);
+                }
+            }
+        });
+
+        debug!("Mock IAS is listening on {}", local_addr);
+        Ok(MockIasServer { local_addr })
+    }
+
+    fn handle_connection(&self, mut stream: TcpStream) -> Result<()> {
+        match self.read_quote(&mut stream) {
+            Ok(quote) => {
+                let (report, report_sig) = self.create_report(&quote)?;
+                let cert = format!("{}{}", SIGNING_CERT, ROOT_CERT);
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\n\
+                     Content-Type: application/json\r\n\
+                     Content-Length: {}\r\n\
+                     X-IASReport-Signature: {}\r\n\
+                     X-IASReport-Signing-Certificate: {}\r\n\
+                     Connection: close\r\n\r\n",
+                    report.len(),
+                    base64::encode(&report_sig),
+                    utf8_percent_encode(&cert, NON_ALPHANUMERIC),
+                )?;
+                stream.write_all(report.as_bytes())?;
+            }
+            Err(e) => {
+                write!(
+                    stream,
+                    "HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                )?;
+                return Err(e);
+            }
+        }
+
+        stream.flush().map_err(Into::into)
+    }
+
+    /// Read a request to the report API and return the decoded quote in its body.
+    fn read_quote(&self, stream: &mut TcpStream) -> Result<Vec<u8>> {
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line)?;
+        if !request_line.starts_with("POST ") || !request_line.contains("/report ") {
+            return Err(MockIasError::InvalidRequest(request_line));
+        }
+
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let mut header = line.splitn(2, ':');
+            let name = header.next().unwrap_or_default();
+            if name.eq_ignore_ascii_case("Content-Length") {
+                content_length = header
+                    .next()
+                    .unwrap_or_default()
+                    .trim()
+                    .parse()
+                    .map_err(|_| MockIasError::InvalidRequest(line.to_string()))?;
+            }
+        }
+
+        let mut body = vec![0u8; content_length];
+        reader.read_exact(&mut body)?;
+        let body: Value = serde_json::from_slice(&body)?;
+        let quote = body["isvEnclaveQuote"]
+            .as_str()
+            .ok_or_else(|| MockIasError::InvalidRequest("Not found isvEnclaveQuote".to_string()))?;
+        let quote = base64::decode(quote)?;
+        if quote.len() < QUOTE_BODY_LEN {
+            return Err(MockIasError::InvalidRequest(format!(
+                "The quote is too short: {} bytes",
+                quote.len()
+            )));
+        }
+
+        Ok(quote)
+    }
+
+    /// Create a report of the quote and its signature by the test key.
+    /// The quote must be at least as long as the quote body.
+    pub fn create_report(&self, quote: &[u8]) -> Result<(String, Vec<u8>)> {
+        // Keep the same field order as IAS because the contract searches the quote body by the word "Body".
+        let report = format!(
+            "{{\"id\":\"{}\",\"timestamp\":\"{}\",\"version\":{},\"isvEnclaveQuoteStatus\":\"{}\",\"isvEnclaveQuoteBody\":\"{}\"}}",
+            self.report_id.fetch_add(1, Ordering::SeqCst),
+            Utc::now().format("%Y-%m-%dT%H:%M:%S%.6f"),
+            REPORT_VERSION,
+            self.quote_status,
+            base64::encode(&quote[..QUOTE_BODY_LEN]),
+        );
+
+        let mut report_sig = vec![0u8; self.key_pair.public_modulus_len()];
+        self.key_pair
+            .sign(
+                &signature::RSA_PKCS1_SHA256,
+                &self.rng,
+                report.as_bytes(),
+                &mut report_sig,
+            )
+            .map_err(|_| MockIasError::SigningError)?;
+
+        Ok((report, report_sig))
+    }
+}
+
+/// A running mock IAS
+#[derive(Debug, Clone)]
+pub struct MockIasServer {
+    local_addr: SocketAddr,
+}
+
+impl MockIasServer {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// The URL to be set to `IAS_URL`
+    pub fn report_url(&self) -> String {
+        format!("http://{}{}", self.local_addr, REPORT_PATH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ring::signature::KeyPair;
+
+    #[test]
+    fn test_report_is_signed_by_test_key() {
+        let server = MockIas::new()
+            .unwrap()
+            .set_quote_status("GROUP_OUT_OF_DATE")
+            .start("127.0.0.1:0")
+            .unwrap();
+        let quote = base64::encode(&[7u8; QUOTE_BODY_LEN + 4][..]);
+        let body = format!("{{\"isvEnclaveQuote\":\"{}\"}}\r\n", quote);
+
+        let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            REPORT_PATH,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut resp = String::new();
+        stream.read_to_string(&mut resp).unwrap();
+
+        let (headers, report) = resp.split_at(resp.find("\r\n\r\n").unwrap() + 4);
+        assert!(headers.starts_with("HTTP/1.1 200 OK"));
+        let sig = headers
+            .lines()
+            .find_map(|l| l.strip_prefix("X-IASReport-Signature: "))
+            .unwrap();
+        let report_json: Value = serde_json::from_str(report).unwrap();
+        assert_eq!(report_json["isvEnclaveQuoteStatus"], "GROUP_OUT_OF_DATE");
+        assert_eq!(
+            base64::decode(report_json["isvEnclaveQuoteBody"].as_str().unwrap()).unwrap(),
+            vec![7u8; QUOTE_BODY_LEN]
+        );
+
+        let key_pair = signature::RsaKeyPair::from_pkcs8(SIGNING_KEY).unwrap();
+        signature::UnparsedPublicKey::new(
+            &signature::RSA_PKCS1_2048_8192_SHA256,
+            key_pair.public_key().as_ref(),
+        )
+        .verify(report.as_bytes(), &base64::decode(sig).unwrap())
+        .unwrap();
+    }
+}
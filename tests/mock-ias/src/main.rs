@@ -0,0 +1,23 @@
+use mock_ias::MockIas;
+use std::{env, thread};
+
+fn main() {
+    env_logger::init();
+    let address = env::var("MOCK_IAS_ADDRESS").unwrap_or_else(|_| "0.0.0.0:8090".to_string());
+    let quote_status = env::var("MOCK_IAS_QUOTE_STATUS").unwrap_or_else(|_| "OK".to_string());
+
+    let server = MockIas::new()
+        .expect("Failed to load the signing key")
+        .set_quote_status(&quote_status)
+        .start(&address)
+        .expect("Failed to start the mock IAS");
+    println!("Mock IAS is serving {}", server.report_url());
+    println!(
+        "Set IAS_ROOT_CERT_PATH={}",
+        mock_ias::root_cert_path().display()
+    );
+
+    loop {
+        thread::park();
+    }
+}
@@ -13,6 +13,8 @@ pub fn ecall_run_tests() {
         frame_treekem::tests::run_tests(),
         anonify_enclave::tests::run_tests(),
         frame_mra_tls::tests::run_tests(),
+        key_vault_enclave::tests::run_tests(),
+        remote_attestation::tests::run_tests(),
     );
 
     assert!(ret);
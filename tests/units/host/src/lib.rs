@@ -7,6 +7,7 @@ extern "C" {
 
 #[test]
 fn test_in_enclave() {
+    mock_ias::set_mock_ias_env();
     let enclave = EnclaveDir::new().init_enclave(true).unwrap();
     let ret = unsafe { ecall_run_tests(enclave.geteid()) };
 
@@ -150,10 +150,7 @@ pub fn set_env_vars() {
     env::set_var("MY_ROSTER_IDX", "0");
     env::set_var("MAX_ROSTER_IDX", "2");
     env::set_var("SPID", "2C149BFC94A61D306A96211AED155BE9");
-    env::set_var(
-        "IAS_URL",
-        "https://api.trustedservices.intel.com/sgx/dev/attestation/v3/report",
-    );
     env::set_var("SUB_KEY", "77e2533de0624df28dc3be3a5b9e50d9");
+    // IAS_URL and IAS_ROOT_CERT_PATH are set by the host running the mock IAS
     env::set_var("AUDITOR_ENDPOINT", "test");
 }
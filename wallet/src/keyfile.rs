@@ -1,5 +1,6 @@
 //! Keyfile operations such as signing.
 use crate::{
+    disk,
     error::{Result, WalletError},
     SerdeBytes,
 };
@@ -10,7 +11,7 @@ use parity_crypto::Keccak256;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use smallvec::SmallVec;
-use std::collections::HashMap;
+use std::{collections::HashMap, fs, path::Path};
 
 #[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -96,16 +97,25 @@ impl KeyCiphertext {
         password: &[u8],
         iters: u32,
         rng: &mut R,
+    ) -> Result<Self> {
+        Self::encrypt_bytes(&key_pair.to_bytes()[..], password, iters, rng)
+    }
+
+    /// Encrypt a secret key of any kind
+    pub fn encrypt_bytes<R: Rng>(
+        plain: &[u8],
+        password: &[u8],
+        iters: u32,
+        rng: &mut R,
     ) -> Result<Self> {
         assert!(iters != 0);
         let salt: [u8; 32] = rng.gen();
         let iv: [u8; 16] = rng.gen();
 
         let (derived_left, derived_right) = crypto::derive_key_iterations(password, &salt, iters);
-        let key_pair_bytes = key_pair.to_bytes();
-        let mut ciphertext: SmallVec<[u8; 32]> = SmallVec::from_vec(vec![0; key_pair_bytes.len()]);
+        let mut ciphertext: SmallVec<[u8; 32]> = SmallVec::from_vec(vec![0; plain.len()]);
 
-        crypto::aes::encrypt_128_ctr(&derived_left, &iv, &key_pair_bytes[..], &mut *ciphertext)
+        crypto::aes::encrypt_128_ctr(&derived_left, &iv, plain, &mut *ciphertext)
             .map_err(crypto::Error::from)?;
         let mac = crypto::derive_mac(&derived_right, &*ciphertext).keccak256();
 
@@ -119,21 +129,231 @@ impl KeyCiphertext {
     }
 
     pub fn decrypt(&self, password: &[u8]) -> Result<Keypair> {
+        let plain = self.decrypt_bytes(password)?;
+        let key_pair = Keypair::from_bytes(&plain[..]).map_err(|e| anyhow!("{:?}", e))?;
+
+        Ok(key_pair)
+    }
+
+    pub fn decrypt_bytes(&self, password: &[u8]) -> Result<Vec<u8>> {
         let (derived_left, derived_right) =
             crypto::derive_key_iterations(password, &self.salt.0[..], self.iters);
-        let mac = crypto::derive_mac(&derived_right, &self.ciphertext.0).keccak256();
 
-        if !crypto::is_equal(&mac, &self.mac.0) {
-            return Err(WalletError::InvalidPassword);
+        decrypt_derived(
+            &derived_left,
+            &derived_right,
+            &self.iv.0,
+            &self.ciphertext.0,
+            &self.mac.0,
+        )
+    }
+}
+
+/// The version of the Web3 Secret Storage Definition
+const ETH_KEYFILE_VERSION: u32 = 3;
+const ETH_KEYFILE_CIPHER: &str = "aes-128-ctr";
+const ETH_KEYFILE_PRF: &str = "hmac-sha256";
+const ETH_KEYFILE_DKLEN: u32 = 32;
+
+/// Keystore of a secp256k1 secret key which signs Ethereum transactions,
+/// in the Web3 Secret Storage Definition (version 3) written by geth and MetaMask.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct EthKeyFile {
+    /// Hex-encoded Ethereum address of the key, which some keystores omit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    #[serde(alias = "Crypto")]
+    pub crypto: EthKeyCrypto,
+    pub id: String,
+    pub version: u32,
+}
+
+/// The encrypted secret key and the parameters to decrypt it
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct EthKeyCrypto {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    #[serde(with = "hex_bytes")]
+    pub ciphertext: Vec<u8>,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    #[serde(with = "hex_bytes")]
+    pub mac: Vec<u8>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+pub struct CipherParams {
+    #[serde(with = "hex_bytes")]
+    pub iv: Vec<u8>,
+}
+
+/// The parameters of the key derivation function named by `kdf`
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum KdfParams {
+    Pbkdf2 {
+        c: u32,
+        dklen: u32,
+        prf: String,
+        #[serde(with = "hex_bytes")]
+        salt: Vec<u8>,
+    },
+    Scrypt {
+        dklen: u32,
+        n: u32,
+        p: u32,
+        r: u32,
+        #[serde(with = "hex_bytes")]
+        salt: Vec<u8>,
+    },
+}
+
+impl EthKeyFile {
+    /// Encrypt the secret key with PBKDF2 of `iters` iterations.
+    pub fn new<R: Rng>(
+        address: &str,
+        password: &[u8],
+        iters: u32,
+        secret: &[u8],
+        rng: &mut R,
+    ) -> Result<Self> {
+        let encrypted_key = KeyCiphertext::encrypt_bytes(secret, password, iters, rng)?;
+        let id: [u8; 16] = rng.gen();
+
+        Ok(EthKeyFile {
+            address: Some(address.to_string()),
+            crypto: EthKeyCrypto {
+                cipher: ETH_KEYFILE_CIPHER.to_string(),
+                cipherparams: CipherParams {
+                    iv: encrypted_key.iv.0,
+                },
+                ciphertext: encrypted_key.ciphertext.0,
+                kdf: "pbkdf2".to_string(),
+                kdfparams: KdfParams::Pbkdf2 {
+                    c: iters,
+                    dklen: ETH_KEYFILE_DKLEN,
+                    prf: ETH_KEYFILE_PRF.to_string(),
+                    salt: encrypted_key.salt.0,
+                },
+                mac: encrypted_key.mac.0,
+            },
+            id: uuid_v4(id),
+            version: ETH_KEYFILE_VERSION,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let file = fs::File::open(path)?;
+        let keyfile: Self = serde_json::from_reader(file)?;
+        if keyfile.version != ETH_KEYFILE_VERSION {
+            return Err(WalletError::InvalidKeyfile);
         }
 
-        let mut plain: SmallVec<[u8; 32]> = SmallVec::from_vec(vec![0; self.ciphertext.0.len()]);
-        crypto::aes::decrypt_128_ctr(&derived_left, &self.iv.0, &self.ciphertext.0, &mut plain)
-            .map_err(crypto::Error::from)?;
+        Ok(keyfile)
+    }
 
-        let key_pair = Keypair::from_bytes(&plain.to_vec()[..]).map_err(|e| anyhow!("{:?}", e))?;
+    pub fn store<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let file = disk::create_new_file(path.as_ref())?;
+        serde_json::to_writer(file, self).map_err(Into::into)
+    }
 
-        Ok(key_pair)
+    pub fn get_secret(&self, password: &[u8]) -> Result<Vec<u8>> {
+        let key = &self.crypto;
+        if key.cipher != ETH_KEYFILE_CIPHER {
+            return Err(WalletError::InvalidKeyfile);
+        }
+        let (derived_left, derived_right) = match (key.kdf.as_str(), &key.kdfparams) {
+            (
+                "pbkdf2",
+                KdfParams::Pbkdf2 {
+                    c,
+                    dklen: ETH_KEYFILE_DKLEN,
+                    prf,
+                    salt,
+                },
+            ) if prf == ETH_KEYFILE_PRF => crypto::derive_key_iterations(password, salt, *c),
+            (
+                "scrypt",
+                KdfParams::Scrypt {
+                    dklen: ETH_KEYFILE_DKLEN,
+                    n,
+                    p,
+                    r,
+                    salt,
+                },
+            ) => crypto::scrypt::derive_key(password, salt, *n, *p, *r)
+                .map_err(crypto::Error::from)?,
+            _ => return Err(WalletError::InvalidKeyfile),
+        };
+
+        decrypt_derived(
+            &derived_left,
+            &derived_right,
+            &key.cipherparams.iv,
+            &key.ciphertext,
+            &key.mac,
+        )
+    }
+}
+
+/// Check the MAC and decrypt the ciphertext with the derived key, as specified in the Web3 Secret Storage.
+fn decrypt_derived(
+    derived_left: &[u8],
+    derived_right: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+    mac: &[u8],
+) -> Result<Vec<u8>> {
+    let expected_mac = crypto::derive_mac(derived_right, ciphertext).keccak256();
+    if !crypto::is_equal(&expected_mac, mac) {
+        return Err(WalletError::InvalidPassword);
+    }
+
+    let mut plain: SmallVec<[u8; 32]> = SmallVec::from_vec(vec![0; ciphertext.len()]);
+    crypto::aes::decrypt_128_ctr(derived_left, iv, ciphertext, &mut plain)
+        .map_err(crypto::Error::from)?;
+
+    Ok(plain.to_vec())
+}
+
+/// Format the random bytes as a version 4 UUID.
+fn uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Hex encoding of bytes without the `0x` prefix, as in the Web3 Secret Storage
+mod hex_bytes {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        let hex = hex.trim_start_matches("0x");
+        if hex.len() % 2 != 0 {
+            return Err(D::Error::custom("Odd length of hex"));
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|b| u8::from_str_radix(b, 16).ok())
+                    .ok_or_else(|| D::Error::custom(format!("Invalid hex: {}", hex)))
+            })
+            .collect()
     }
 }
 
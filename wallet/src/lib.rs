@@ -11,7 +11,7 @@ mod keyfile;
 
 pub use disk::{KeystoreDirectory, WalletDirectory};
 pub use error::WalletError as Error;
-pub use keyfile::KeyFile;
+pub use keyfile::{EthKeyFile, KeyFile};
 
 /// Operations in a wallet directory
 pub trait DirOperations {